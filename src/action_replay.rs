@@ -0,0 +1,263 @@
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::gecko::{self, CodeLine, GeckoCode};
+use crate::patch::{Patch, PatchValue, PatchWrite};
+
+/// Gecko register used as scratch space by converted add codes
+const SCRATCH_REGISTER: u32 = 0xF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Size {
+    U8,
+    U16,
+    U32,
+    F32,
+}
+
+/// A decrypted Action Replay code line. The first word packs the subtype, type, value size, and
+/// 25-bit address.
+#[derive(Debug, Clone, Copy)]
+struct ArLine {
+    subtype: u32,
+    type_: u32,
+    size: Size,
+    /// Offset from 0x80000000
+    offset: u32,
+    data: u32,
+}
+
+impl ArLine {
+    fn new(line: CodeLine) -> ArLine {
+        ArLine {
+            subtype: line[0] >> 30,
+            type_: (line[0] >> 27) & 7,
+            size: [Size::U8, Size::U16, Size::U32, Size::F32][(line[0] >> 25) as usize & 3],
+            offset: line[0] & 0x01FFFFFF,
+            data: line[1],
+        }
+    }
+
+    fn address(&self) -> u32 {
+        0x80000000 | self.offset
+    }
+}
+
+/// How far an Action Replay conditional reaches
+#[derive(Debug, Clone, Copy)]
+enum Block {
+    /// The given number of following lines
+    Lines(u32),
+    /// Up to the next `00000000 40000000` line
+    UntilNormal,
+    /// The rest of the code
+    UntilEnd,
+}
+
+/// Parse decrypted Action Replay codes, in the same text form as Gecko codes. Encrypted codes
+/// (`XXXX-XXXX-XXXXX`) aren't supported.
+pub fn parse_codes(buf: &[u8]) -> anyhow::Result<Vec<GeckoCode>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse code list as UTF-8")?;
+    if let Some((line_num, _)) = s.lines().enumerate().find(|(_, line)| is_encrypted(line)) {
+        bail!(
+            "Line {} is an encrypted Action Replay code. Only decrypted codes are supported, such \
+             as those Dolphin saves in a game's INI.",
+            line_num + 1
+        );
+    }
+    gecko::parse_code_text(buf)
+}
+
+fn is_encrypted(line: &str) -> bool {
+    let groups: Vec<&str> = line.trim().split('-').collect();
+    matches!(groups[..], [a, b, c] if a.len() == 4 && b.len() == 4 && c.len() == 5)
+        && groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Gecko line closing `count` ifs
+fn endif(count: usize) -> CodeLine {
+    [0xE2000000 | count as u32, 0]
+}
+
+fn gecko_line(codetype: u32, offset: u32, value: u32) -> CodeLine {
+    [(codetype << 24) | offset, value]
+}
+
+/// Gecko lines for one Action Replay line, and the block it opens if it's a conditional
+fn convert_line(line: ArLine, out: &mut Vec<CodeLine>) -> anyhow::Result<Option<Block>> {
+    if line.type_ == 0 {
+        match (line.subtype, line.size) {
+            // RAM write and fill, which Gecko's write codetypes encode identically
+            (0, Size::U8) => {
+                let count = line.data >> 8;
+                ensure!(count <= 0xFFFF, "Fill count {count} is too large for Gecko");
+                out.push(gecko_line(
+                    0x00,
+                    line.offset,
+                    count << 16 | line.data & 0xFF,
+                ));
+            }
+            (0, Size::U16) => out.push(gecko_line(0x02, line.offset, line.data)),
+            (0, Size::U32 | Size::F32) => out.push(gecko_line(0x04, line.offset, line.data)),
+            // Write through a pointer, via Gecko's pointer register
+            (1, size) => {
+                out.push(gecko_line(0x48, 0, line.address()));
+                out.push(match size {
+                    Size::U8 => gecko_line(0x10, line.data >> 8, line.data & 0xFF),
+                    Size::U16 => gecko_line(0x12, (line.data >> 16) * 2, line.data & 0xFFFF),
+                    Size::U32 | Size::F32 => gecko_line(0x14, 0, line.data),
+                });
+            }
+            // Add to a value in memory, via a Gecko register
+            (2, Size::F32) => bail!("Floating point add codes have no Gecko equivalent"),
+            (2, size) => {
+                let (width, value) = match size {
+                    Size::U8 => (0, line.data & 0xFF),
+                    Size::U16 => (1, line.data & 0xFFFF),
+                    _ => (2, line.data),
+                };
+                let register = width << 20 | SCRATCH_REGISTER;
+                out.push([0x82000000 | register, line.address()]);
+                out.push([0x86000000 | SCRATCH_REGISTER, value]);
+                out.push([0x84000000 | register, line.address()]);
+            }
+            // The master code hooks the game for the Action Replay, which Gecko doesn't need
+            (3, _) => {}
+            _ => unreachable!(),
+        }
+        return Ok(None);
+    }
+
+    let block = match line.subtype {
+        0 => Block::Lines(1),
+        1 => Block::Lines(2),
+        2 => Block::UntilNormal,
+        _ => Block::UntilEnd,
+    };
+    // Gecko's 32-bit and 16-bit if codetypes, in the order of Action Replay's equal, not equal,
+    // and unsigned less than and greater than
+    let (codetype_32, codetype_16) = match line.type_ {
+        1 => (0x20, 0x28),
+        2 => (0x22, 0x2A),
+        5 => (0x26, 0x2E),
+        6 => (0x24, 0x2C),
+        3 | 4 => bail!("Signed comparisons have no Gecko equivalent"),
+        _ => bail!("Mask comparisons have no Gecko equivalent"),
+    };
+    out.push(match line.size {
+        Size::U32 => gecko_line(codetype_32, line.offset, line.data),
+        Size::U16 => gecko_line(codetype_16, line.offset, line.data & 0xFFFF),
+        // Compare the halfword containing the byte, with the other byte masked out
+        Size::U8 if line.offset.is_multiple_of(2) => gecko_line(
+            codetype_16,
+            line.offset,
+            0x00FF0000 | (line.data & 0xFF) << 8,
+        ),
+        Size::U8 => gecko_line(codetype_16, line.offset - 1, 0xFF000000 | line.data & 0xFF),
+        Size::F32 => bail!("Floating point comparisons have no Gecko equivalent"),
+    });
+    Ok(Some(block))
+}
+
+/// Convert a decrypted Action Replay code to a Gecko code. RAM writes, pointer writes, adds,
+/// and unsigned comparisons are supported.
+pub fn to_gecko(code: &GeckoCode) -> anyhow::Result<GeckoCode> {
+    let mut lines = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    for (i, &line) in code.lines.iter().enumerate() {
+        let context = || format!("Cannot convert line {} of '{}'", i + 1, code.name);
+        if line[0] == 0 {
+            match line[1] >> 29 {
+                0 => break,
+                // Normal execution: ends conditionals that skip up to here
+                2 => {
+                    let index = blocks
+                        .iter()
+                        .rposition(|b| matches!(b, Block::UntilNormal))
+                        .ok_or_else(|| anyhow!("Normal execution line without a conditional"))
+                        .with_context(context)?;
+                    lines.push(endif(blocks.len() - index));
+                    blocks.truncate(index);
+                    continue;
+                }
+                _ => bail!(
+                    "Unsupported zero code {:08X} on line {} of '{}'",
+                    line[1],
+                    i + 1,
+                    code.name
+                ),
+            }
+        }
+
+        let opened = convert_line(ArLine::new(line), &mut lines).with_context(context)?;
+        // This line counts towards the conditionals around it
+        for block in &mut blocks {
+            if let Block::Lines(count) = block {
+                *count -= 1;
+            }
+        }
+        while let Some(Block::Lines(0)) = blocks.last() {
+            blocks.pop();
+            lines.push(endif(1));
+        }
+        ensure!(
+            !blocks.iter().any(|b| matches!(b, Block::Lines(0))),
+            "Conditionals overlap without nesting on line {} of '{}'",
+            i + 1,
+            code.name
+        );
+        blocks.extend(opened);
+    }
+    if !blocks.is_empty() {
+        lines.push(endif(blocks.len()));
+    }
+
+    Ok(GeckoCode {
+        name: code.name.clone(),
+        creator: code.creator.clone(),
+        notes: code.notes.clone(),
+        lines,
+    })
+}
+
+/// Convert a decrypted Action Replay code made up only of RAM writes to a patch, so it can be
+/// applied to the DOL directly
+pub fn to_patch(code: &GeckoCode) -> anyhow::Result<Patch> {
+    let mut writes = Vec::new();
+    for (i, &line) in code.lines.iter().enumerate() {
+        if line[0] == 0 && line[1] == 0 {
+            break;
+        }
+        let line = ArLine::new(line);
+        ensure!(
+            line.type_ == 0 && matches!(line.subtype, 0 | 3),
+            "Line {} of '{}' isn't a plain RAM write, so it can't be a patch",
+            i + 1,
+            code.name
+        );
+        if line.subtype == 3 {
+            continue;
+        }
+        let (values, stride) = match line.size {
+            Size::U8 => ((line.data >> 8) + 1, 1),
+            Size::U16 => ((line.data >> 16) + 1, 2),
+            Size::U32 | Size::F32 => (1, 4),
+        };
+        for n in 0..values {
+            let value = match line.size {
+                Size::U8 => PatchValue::U8(line.data as u8),
+                Size::U16 => PatchValue::U16(line.data as u16),
+                Size::U32 | Size::F32 => PatchValue::U32(line.data),
+            };
+            writes.push(PatchWrite {
+                address: line.address() + n * stride,
+                value,
+            });
+        }
+    }
+    Ok(Patch {
+        name: code.name.clone(),
+        writes,
+    })
+}
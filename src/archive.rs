@@ -0,0 +1,164 @@
+pub mod rarc;
+pub mod u8arc;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::yaz0::{self, CompressionLevel};
+use rarc::{Rarc, RARC_MAGIC};
+use u8arc::{U8Archive, U8_MAGIC};
+
+/// Manifest written next to the contents of an unpacked archive
+pub const MANIFEST_NAME: &str = "manifest.toml";
+
+/// Decompress an archive if it's Yaz0 compressed (e.g. `.szs`). Returns the archive data and
+/// whether it was compressed.
+pub fn decompress(buf: Vec<u8>) -> anyhow::Result<(Vec<u8>, bool)> {
+    if yaz0::is_yaz0(&buf) {
+        Ok((yaz0::decompress(&buf)?, true))
+    } else {
+        Ok((buf, false))
+    }
+}
+
+/// An archive in any supported format. Without file data, this is also the manifest written
+/// when unpacking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum Archive {
+    Rarc(Rarc),
+    U8(U8Archive),
+}
+
+impl Archive {
+    /// All files as (path, data)
+    pub fn files(&self) -> anyhow::Result<Vec<(PathBuf, &[u8])>> {
+        Ok(match self {
+            Archive::Rarc(rarc) => rarc
+                .files()?
+                .into_iter()
+                .map(|(path, e)| (path, e.data.as_slice()))
+                .collect(),
+            Archive::U8(u8arc) => u8arc
+                .files()
+                .into_iter()
+                .map(|(path, e)| (path, e.data.as_slice()))
+                .collect(),
+        })
+    }
+
+    pub fn extract(&self, out_dir: &Path) -> anyhow::Result<()> {
+        match self {
+            Archive::Rarc(rarc) => rarc.extract(out_dir),
+            Archive::U8(u8arc) => u8arc.extract(out_dir),
+        }
+    }
+
+    pub fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        match self {
+            Archive::Rarc(rarc) => rarc.load_dir(dir),
+            Archive::U8(u8arc) => u8arc.load_dir(dir),
+        }
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Archive::Rarc(rarc) => rarc.to_bytes(),
+            Archive::U8(u8arc) => u8arc.to_bytes(),
+        }
+    }
+}
+
+pub fn parse(buf: &[u8]) -> anyhow::Result<Archive> {
+    if buf.starts_with(RARC_MAGIC) {
+        Ok(Archive::Rarc(Rarc::parse(buf)?))
+    } else if buf.starts_with(U8_MAGIC) {
+        Ok(Archive::U8(U8Archive::parse(buf)?))
+    } else {
+        bail!("Unknown archive format")
+    }
+}
+
+/// Contents of [`MANIFEST_NAME`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// Yaz0 compression level to repack with, if the original archive was compressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yaz0: Option<CompressionLevel>,
+    #[serde(flatten)]
+    pub archive: Archive,
+}
+
+/// Yaz0 level whose output is identical to `compressed`, if any
+fn find_yaz0_level(data: &[u8], compressed: &[u8]) -> anyhow::Result<Option<CompressionLevel>> {
+    for level in CompressionLevel::ALL {
+        if yaz0::compress(data, level)? == compressed {
+            return Ok(Some(level));
+        }
+    }
+    Ok(None)
+}
+
+/// Unpack an archive into `out_dir`, with a manifest recording the layout for repacking.
+/// Repacking an unchanged directory reproduces the original archive byte for byte, except when
+/// it was Yaz0 compressed by an encoder other than this one; returns false in that case.
+pub fn unpack(buf: Vec<u8>, out_dir: &Path) -> anyhow::Result<bool> {
+    let (data, compressed) = decompress(buf.clone())?;
+    let archive = parse(&data)?;
+    let (yaz0, exact) = if compressed {
+        match find_yaz0_level(&data, &buf)? {
+            Some(level) => (Some(level), true),
+            None => (Some(CompressionLevel::default()), false),
+        }
+    } else {
+        (None, true)
+    };
+    archive.extract(out_dir)?;
+    let manifest = toml::to_string_pretty(&ArchiveManifest { yaz0, archive })?;
+    std::fs::write(out_dir.join(MANIFEST_NAME), manifest)
+        .with_context(|| format!("cannot write manifest in {}", out_dir.to_string_lossy()))?;
+    Ok(exact)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackOptions {
+    /// Whether to Yaz0 compress the archive. Defaults to what the manifest records.
+    pub yaz0: Option<bool>,
+    /// Yaz0 compression level. Defaults to what the manifest records.
+    pub level: Option<CompressionLevel>,
+}
+
+/// Pack a directory written by [`unpack`]. Without a manifest, the directory itself becomes the
+/// root of a new RARC archive.
+pub fn pack(dir: &Path, options: &PackOptions) -> anyhow::Result<Vec<u8>> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("cannot read directory {}", dir.to_string_lossy()))?;
+    let dir = dir.as_path();
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let manifest = if manifest_path.exists() {
+        let mut manifest: ArchiveManifest = crate::manifest::read_file(&manifest_path)?;
+        manifest.archive.load_dir(dir)?;
+        manifest
+    } else {
+        let name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut archive = Rarc::new(&name);
+        archive.load_dir(dir.parent().unwrap_or(Path::new(".")))?;
+        ArchiveManifest {
+            yaz0: None,
+            archive: Archive::Rarc(archive),
+        }
+    };
+    let buf = manifest.archive.to_bytes()?;
+    let level = options.level.or(manifest.yaz0).unwrap_or_default();
+    if options.yaz0.unwrap_or(manifest.yaz0.is_some()) {
+        yaz0::compress(&buf, level)
+    } else {
+        Ok(buf)
+    }
+}
@@ -0,0 +1,768 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::iso::fst;
+use crate::safe_path;
+
+pub const RARC_MAGIC: &[u8; 4] = b"RARC";
+
+/// Entry attribute flags
+pub const ATTR_FILE: u8 = 0x01;
+pub const ATTR_DIRECTORY: u8 = 0x02;
+pub const ATTR_COMPRESSED: u8 = 0x04;
+pub const ATTR_PRELOAD_MRAM: u8 = 0x10;
+pub const ATTR_PRELOAD_ARAM: u8 = 0x20;
+pub const ATTR_LOAD_DVD: u8 = 0x40;
+pub const ATTR_YAZ0: u8 = 0x80;
+
+/// Data offset of the `..` entry in the root node
+pub const NO_PARENT: u32 = u32::MAX;
+const DIRECTORY_ID: u16 = 0xFFFF;
+const ALIGNMENT: usize = 0x20;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawHeader {
+    magic: [u8; 4],
+    file_size: big_endian::U32,
+    header_size: big_endian::U32,
+    /// Offsets from here on are relative to the end of the header
+    data_offset: big_endian::U32,
+    data_size: big_endian::U32,
+    mram_size: big_endian::U32,
+    aram_size: big_endian::U32,
+    dvd_size: big_endian::U32,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawInfo {
+    node_count: big_endian::U32,
+    node_offset: big_endian::U32,
+    entry_count: big_endian::U32,
+    entry_offset: big_endian::U32,
+    string_table_size: big_endian::U32,
+    string_table_offset: big_endian::U32,
+    next_file_id: big_endian::U16,
+    sync_file_ids: u8,
+    padding: [u8; 5],
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawNode {
+    kind: [u8; 4],
+    name_offset: big_endian::U32,
+    name_hash: big_endian::U16,
+    entry_count: big_endian::U16,
+    first_entry: big_endian::U32,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawEntry {
+    id: big_endian::U16,
+    name_hash: big_endian::U16,
+    attributes: u8,
+    padding0: u8,
+    name_offset: big_endian::U16,
+    /// File data offset, or node index for directories
+    data_offset: big_endian::U32,
+    data_size: big_endian::U32,
+    padding1: big_endian::U32,
+}
+
+/// A name or node type as stored in the archive. Japanese archives use Shift-JIS, so names are kept
+/// as bytes, which are what the game hashes and compares, and only decoded for display and host
+/// paths. Manifests hold the decoded text when it encodes back to the same bytes, and the bytes
+/// otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RarcName(pub Vec<u8>);
+
+impl RarcName {
+    /// A name from the host, such as a file name, encoded as by [`fst::encode_name`]
+    pub fn from_host(name: &str) -> anyhow::Result<RarcName> {
+        Ok(RarcName(fst::encode_name(name)?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The name as text, decoded as by [`fst::decode_name`]
+    pub fn display_name(&self) -> String {
+        fst::decode_name(&self.0)
+    }
+}
+
+impl From<&str> for RarcName {
+    fn from(name: &str) -> RarcName {
+        RarcName(name.as_bytes().to_vec())
+    }
+}
+
+impl Display for RarcName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_name())
+    }
+}
+
+impl Serialize for RarcName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let text = self.display_name();
+        if fst::encode_name(&text).is_ok_and(|bytes| bytes == self.0) {
+            serializer.serialize_str(&text)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RarcName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RarcName, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Bytes(Vec<u8>),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => RarcName::from_host(&text).map_err(D::Error::custom),
+            Repr::Bytes(bytes) => Ok(RarcName(bytes)),
+        }
+    }
+}
+
+/// A file or directory entry within a node. Every node also has `.` and `..` directory entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RarcEntry {
+    pub name: RarcName,
+    /// File ID, or 0xFFFF for directories
+    pub id: u16,
+    pub attributes: u8,
+    /// Node index for directories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<u32>,
+    /// Offset of the file's data in the archive it was unpacked from. Rebuilding keeps files in
+    /// this order within each load group; files without one go last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl RarcEntry {
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    /// Whether this is a `.` or `..` link rather than a real subdirectory
+    pub fn is_special(&self) -> bool {
+        self.name.as_bytes() == b"." || self.name.as_bytes() == b".."
+    }
+}
+
+/// A directory. Node 0 is the root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RarcNode {
+    /// Four character type, conventionally the first letters of the name in upper case
+    pub kind: RarcName,
+    pub name: RarcName,
+    pub entries: Vec<RarcEntry>,
+}
+
+/// A parsed RARC archive. Without file data, this is also the manifest written when unpacking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rarc {
+    pub next_file_id: u16,
+    /// Whether file IDs are kept equal to entry indices
+    pub sync_file_ids: bool,
+    /// The original string table, in order. Rebuilding starts from it so name offsets stay the
+    /// same, and adds any names it's missing to the end.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub strings: Vec<RarcName>,
+    pub nodes: Vec<RarcNode>,
+}
+
+/// Hash stored alongside every name in the archive
+pub fn name_hash(name: &[u8]) -> u16 {
+    name.iter()
+        .fold(0u16, |hash, &b| hash.wrapping_mul(3).wrapping_add(b as u16))
+}
+
+/// The first four bytes of a name in upper case, padded with spaces
+fn node_kind(name: &RarcName) -> RarcName {
+    let mut kind = name.as_bytes().to_ascii_uppercase();
+    kind.resize(4, b' ');
+    RarcName(kind)
+}
+
+fn read_name(strings: &[u8], offset: usize) -> anyhow::Result<RarcName> {
+    let tail = strings
+        .get(offset..)
+        .ok_or_else(|| anyhow!("RARC name offset {offset:#x} out of bounds"))?;
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    Ok(RarcName(tail[..end].to_vec()))
+}
+
+fn section<'a>(buf: &'a [u8], offset: u32, size: usize, what: &str) -> anyhow::Result<&'a [u8]> {
+    let start = size_of::<RawHeader>() + offset as usize;
+    buf.get(start..start + size)
+        .ok_or_else(|| anyhow!("RARC {what} extends past end of file"))
+}
+
+impl Rarc {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Rarc> {
+        let (header, rest) = RawHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("RARC is too small to contain a header"))?;
+        ensure!(&header.magic == RARC_MAGIC, "Not a RARC archive");
+        let (info, _) = RawInfo::ref_from_prefix(rest)
+            .map_err(|_| anyhow!("RARC is too small to contain an info block"))?;
+
+        let node_count = info.node_count.get() as usize;
+        let entry_count = info.entry_count.get() as usize;
+        let raw_nodes = section(
+            buf,
+            info.node_offset.get(),
+            node_count * size_of::<RawNode>(),
+            "node table",
+        )?;
+        let raw_nodes = <[RawNode]>::ref_from_bytes(raw_nodes).unwrap();
+        let raw_entries = section(
+            buf,
+            info.entry_offset.get(),
+            entry_count * size_of::<RawEntry>(),
+            "entry table",
+        )?;
+        let raw_entries = <[RawEntry]>::ref_from_bytes(raw_entries).unwrap();
+        let strings = section(
+            buf,
+            info.string_table_offset.get(),
+            info.string_table_size.get() as usize,
+            "string table",
+        )?;
+        let data_start = size_of::<RawHeader>() + header.data_offset.get() as usize;
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for raw_node in raw_nodes {
+            let first = raw_node.first_entry.get() as usize;
+            let count = raw_node.entry_count.get() as usize;
+            let raw_node_entries = raw_entries
+                .get(first..first + count)
+                .ok_or_else(|| anyhow!("RARC node entries out of bounds"))?;
+
+            let mut entries = Vec::with_capacity(count);
+            for raw in raw_node_entries {
+                let name = read_name(strings, raw.name_offset.get() as usize)?;
+                let attributes = raw.attributes;
+                let (node, offset, data) = if attributes & ATTR_DIRECTORY != 0 {
+                    (Some(raw.data_offset.get()), None, Vec::new())
+                } else {
+                    let start = data_start + raw.data_offset.get() as usize;
+                    let end = start + raw.data_size.get() as usize;
+                    let data = buf
+                        .get(start..end)
+                        .ok_or_else(|| anyhow!("Data for {name} extends past end of file"))?;
+                    (None, Some(raw.data_offset.get()), data.to_vec())
+                };
+                entries.push(RarcEntry {
+                    name,
+                    id: raw.id.get(),
+                    attributes,
+                    node,
+                    offset,
+                    data,
+                });
+            }
+
+            nodes.push(RarcNode {
+                kind: RarcName(raw_node.kind.to_vec()),
+                name: read_name(strings, raw_node.name_offset.get() as usize)?,
+                entries,
+            });
+        }
+        ensure!(!nodes.is_empty(), "RARC has no root node");
+
+        Ok(Rarc {
+            next_file_id: info.next_file_id.get(),
+            sync_file_ids: info.sync_file_ids != 0,
+            strings: split_strings(strings),
+            nodes,
+        })
+    }
+
+    /// Path of each node relative to the archive root, with the root named after itself
+    pub fn node_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        // Names come from the archive, so each must stay one component of the output path
+        let root = self.nodes[0].name.display_name();
+        safe_path::check_name(&root).context("Invalid root node name")?;
+        let mut paths: Vec<Option<PathBuf>> = vec![None; self.nodes.len()];
+        paths[0] = Some(PathBuf::from(root));
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let parent = paths[index].clone().unwrap();
+            for entry in &self.nodes[index].entries {
+                let Some(node) = entry.node.filter(|_| !entry.is_special()) else {
+                    continue;
+                };
+                let name = entry.name.display_name();
+                safe_path::check_name(&name)?;
+                let path = paths
+                    .get_mut(node as usize)
+                    .ok_or_else(|| anyhow!("Directory {} has invalid node {node}", entry.name))?;
+                ensure!(path.is_none(), "Node {node} is reachable twice");
+                *path = Some(parent.join(name));
+                stack.push(node as usize);
+            }
+        }
+        paths
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| p.ok_or_else(|| anyhow!("Node {i} is unreachable from the root")))
+            .collect()
+    }
+
+    /// All files as (path, entry)
+    pub fn files(&self) -> anyhow::Result<Vec<(PathBuf, &RarcEntry)>> {
+        let paths = self.node_paths()?;
+        let mut files = Vec::new();
+        for (node, path) in self.nodes.iter().zip(paths) {
+            for entry in node.entries.iter().filter(|e| !e.is_dir()) {
+                let name = entry.name.display_name();
+                safe_path::check_name(&name)?;
+                files.push((path.join(name), entry));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Write every file under `out_dir`, inside a directory named after the root node
+    pub fn extract(&self, out_dir: &Path) -> anyhow::Result<()> {
+        // Both check every name, so nothing is written for a malformed archive
+        let (dirs, files) = (self.node_paths()?, self.files()?);
+        for path in dirs {
+            std::fs::create_dir_all(out_dir.join(path))?;
+        }
+        for (path, entry) in files {
+            let dest = out_dir.join(path);
+            std::fs::write(&dest, &entry.data)
+                .with_context(|| format!("cannot write {}", dest.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+
+    /// An empty archive with only a root node
+    pub fn new(root_name: &str) -> Rarc {
+        Rarc {
+            next_file_id: 0,
+            sync_file_ids: true,
+            strings: Vec::new(),
+            nodes: vec![RarcNode {
+                kind: "ROOT".into(),
+                name: root_name.into(),
+                entries: special_entries(0, NO_PARENT),
+            }],
+        }
+    }
+
+    /// Fill in file data from `dir`, laid out as written by [`Rarc::extract`]. Files and
+    /// directories on disk that aren't in the archive yet are added to the end of their node.
+    pub fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        for node in &self.nodes {
+            for entry in &node.entries {
+                ensure!(
+                    entry.is_dir() == entry.node.is_some(),
+                    "Entry {} in node {} must have a node index exactly when it is a directory",
+                    entry.name,
+                    node.name
+                );
+            }
+        }
+        let paths = self.node_paths()?;
+        for (node, path) in self.nodes.iter_mut().zip(&paths) {
+            for entry in node.entries.iter_mut().filter(|e| !e.is_dir()) {
+                let path = dir.join(path).join(entry.name.display_name());
+                entry.data = std::fs::read(&path)
+                    .with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+            }
+        }
+        self.add_new_files(0, &dir.join(&paths[0]))?;
+        if self.sync_file_ids {
+            self.sync_ids();
+        }
+        Ok(())
+    }
+
+    fn add_new_files(&mut self, node: usize, dir: &Path) -> anyhow::Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("cannot read directory {}", dir.to_string_lossy()))?
+            .collect::<Result<_, _>>()?;
+        children.sort_by_key(|c| c.file_name());
+
+        for child in children {
+            let host_name = child.file_name().to_string_lossy().into_owned();
+            let existing = self.nodes[node]
+                .entries
+                .iter()
+                .find(|e| e.name.display_name() == host_name);
+            if child.file_type()?.is_dir() {
+                let child_node = match existing {
+                    Some(entry) => entry.node.context("Directory entry has no node")? as usize,
+                    None => {
+                        let name = RarcName::from_host(&host_name)?;
+                        let index = self.nodes.len();
+                        self.nodes.push(RarcNode {
+                            kind: node_kind(&name),
+                            name: name.clone(),
+                            entries: special_entries(index as u32, node as u32),
+                        });
+                        self.insert_entry(
+                            node,
+                            RarcEntry {
+                                name,
+                                id: DIRECTORY_ID,
+                                attributes: ATTR_DIRECTORY,
+                                node: Some(index as u32),
+                                offset: None,
+                                data: Vec::new(),
+                            },
+                        );
+                        index
+                    }
+                };
+                self.add_new_files(child_node, &child.path())?;
+            } else if existing.is_none() {
+                let name = RarcName::from_host(&host_name)?;
+                let data = std::fs::read(child.path())?;
+                let id = self.next_file_id;
+                self.next_file_id = self.next_file_id.wrapping_add(1);
+                self.insert_entry(
+                    node,
+                    RarcEntry {
+                        name,
+                        id,
+                        attributes: ATTR_FILE | ATTR_PRELOAD_MRAM,
+                        node: None,
+                        offset: None,
+                        data,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an entry to a node, before its `.` and `..` entries
+    fn insert_entry(&mut self, node: usize, entry: RarcEntry) {
+        let entries = &mut self.nodes[node].entries;
+        let pos = entries
+            .iter()
+            .position(|e| e.is_special())
+            .unwrap_or(entries.len());
+        entries.insert(pos, entry);
+    }
+
+    /// Set every file's ID to its entry index
+    fn sync_ids(&mut self) {
+        let mut index = 0u16;
+        for entry in self.nodes.iter_mut().flat_map(|n| &mut n.entries) {
+            if !entry.is_dir() {
+                entry.id = index;
+            }
+            index += 1;
+        }
+        self.next_file_id = index;
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut strings = StringTable::default();
+        if self.strings.is_empty() {
+            strings.add(b".");
+            strings.add(b"..");
+        } else {
+            for s in &self.strings {
+                strings.push(s.as_bytes());
+            }
+        }
+
+        let entry_count: usize = self.nodes.iter().map(|n| n.entries.len()).sum();
+        let mut raw_nodes = Vec::with_capacity(self.nodes.len());
+        let mut raw_entries = Vec::with_capacity(entry_count);
+        for node in &self.nodes {
+            let kind: [u8; 4] = node
+                .kind
+                .as_bytes()
+                .try_into()
+                .map_err(|_| anyhow!("Node type '{}' is not four bytes", node.kind))?;
+            raw_nodes.push(RawNode {
+                kind,
+                name_offset: strings.add(node.name.as_bytes()).into(),
+                name_hash: name_hash(node.name.as_bytes()).into(),
+                entry_count: u16::try_from(node.entries.len())
+                    .context("Too many entries in one node")?
+                    .into(),
+                first_entry: (raw_entries.len() as u32).into(),
+            });
+            for entry in &node.entries {
+                let name_offset = u16::try_from(strings.add(entry.name.as_bytes()))
+                    .context("RARC string table is too large")?;
+                raw_entries.push(RawEntry {
+                    id: entry.id.into(),
+                    name_hash: name_hash(entry.name.as_bytes()).into(),
+                    attributes: entry.attributes,
+                    padding0: 0,
+                    name_offset: name_offset.into(),
+                    data_offset: entry.node.unwrap_or(0).into(),
+                    data_size: if entry.is_dir() { 0x10 } else { 0 }.into(),
+                    padding1: 0.into(),
+                });
+            }
+        }
+
+        // File data is grouped by where the game loads it: MRAM, then ARAM, then DVD
+        let mut files: Vec<(usize, &RarcEntry)> = self
+            .nodes
+            .iter()
+            .flat_map(|n| &n.entries)
+            .enumerate()
+            .filter(|(_, e)| !e.is_dir())
+            .collect();
+        files.sort_by_key(|(_, e)| e.offset.unwrap_or(u32::MAX));
+        let mut data = Vec::new();
+        let mut group_sizes = [0u32; 3];
+        for (group, size) in group_sizes.iter_mut().enumerate() {
+            let start = data.len();
+            for &(index, entry) in &files {
+                if load_group(entry.attributes) != group {
+                    continue;
+                }
+                let raw = &mut raw_entries[index];
+                raw.data_offset = u32::try_from(data.len())
+                    .context("RARC data is too large")?
+                    .into();
+                raw.data_size = (entry.data.len() as u32).into();
+                data.extend_from_slice(&entry.data);
+                data.resize(data.len().next_multiple_of(ALIGNMENT), 0);
+            }
+            *size = (data.len() - start) as u32;
+        }
+
+        let node_offset = size_of::<RawInfo>();
+        let entry_offset =
+            (node_offset + raw_nodes.len() * size_of::<RawNode>()).next_multiple_of(ALIGNMENT);
+        let string_offset =
+            (entry_offset + raw_entries.len() * size_of::<RawEntry>()).next_multiple_of(ALIGNMENT);
+        let mut string_table = strings.buf;
+        string_table.resize(string_table.len().next_multiple_of(ALIGNMENT), 0);
+        let data_offset = string_offset + string_table.len();
+        let file_size = size_of::<RawHeader>() + data_offset + data.len();
+
+        let header = RawHeader {
+            magic: *RARC_MAGIC,
+            file_size: (file_size as u32).into(),
+            header_size: (size_of::<RawHeader>() as u32).into(),
+            data_offset: (data_offset as u32).into(),
+            data_size: (data.len() as u32).into(),
+            mram_size: group_sizes[0].into(),
+            aram_size: group_sizes[1].into(),
+            dvd_size: group_sizes[2].into(),
+        };
+        let info = RawInfo {
+            node_count: (raw_nodes.len() as u32).into(),
+            node_offset: (node_offset as u32).into(),
+            entry_count: (raw_entries.len() as u32).into(),
+            entry_offset: (entry_offset as u32).into(),
+            string_table_size: (string_table.len() as u32).into(),
+            string_table_offset: (string_offset as u32).into(),
+            next_file_id: self.next_file_id.into(),
+            sync_file_ids: self.sync_file_ids as u8,
+            padding: [0; 5],
+        };
+
+        let mut buf = Vec::with_capacity(file_size);
+        buf.extend_from_slice(header.as_bytes());
+        buf.extend_from_slice(info.as_bytes());
+        buf.extend_from_slice(raw_nodes.as_bytes());
+        buf.resize(size_of::<RawHeader>() + entry_offset, 0);
+        buf.extend_from_slice(raw_entries.as_bytes());
+        buf.resize(size_of::<RawHeader>() + string_offset, 0);
+        buf.extend_from_slice(&string_table);
+        buf.extend_from_slice(&data);
+        Ok(buf)
+    }
+}
+
+/// 0 for MRAM, 1 for ARAM, 2 for DVD
+fn load_group(attributes: u8) -> usize {
+    if attributes & ATTR_PRELOAD_MRAM != 0 {
+        0
+    } else if attributes & ATTR_PRELOAD_ARAM != 0 {
+        1
+    } else {
+        2
+    }
+}
+
+fn special_entries(node: u32, parent: u32) -> Vec<RarcEntry> {
+    [(".", node), ("..", parent)]
+        .into_iter()
+        .map(|(name, node)| RarcEntry {
+            name: name.into(),
+            id: DIRECTORY_ID,
+            attributes: ATTR_DIRECTORY,
+            node: Some(node),
+            offset: None,
+            data: Vec::new(),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct StringTable {
+    buf: Vec<u8>,
+    offsets: HashMap<Vec<u8>, u32>,
+}
+
+impl StringTable {
+    fn add(&mut self, s: &[u8]) -> u32 {
+        match self.offsets.get(s) {
+            Some(&offset) => offset,
+            None => self.push(s),
+        }
+    }
+
+    /// Append a string even if it's already in the table. Lookups find its first copy.
+    fn push(&mut self, s: &[u8]) -> u32 {
+        let offset = self.buf.len() as u32;
+        self.buf.extend_from_slice(s);
+        self.buf.push(0);
+        self.offsets.entry(s.to_vec()).or_insert(offset);
+        offset
+    }
+}
+
+/// Strings in a string table, without the padding at the end
+fn split_strings(strings: &[u8]) -> Vec<RarcName> {
+    let mut split: Vec<RarcName> = strings
+        .split(|&b| b == 0)
+        .map(|s| RarcName(s.to_vec()))
+        .collect();
+    while split.last().is_some_and(|s| s.0.is_empty()) {
+        split.pop();
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> RarcEntry {
+        RarcEntry {
+            name: name.into(),
+            id: 0,
+            attributes: ATTR_FILE,
+            node: None,
+            offset: None,
+            data: vec![1, 2, 3],
+        }
+    }
+
+    /// マリオ in Shift-JIS, which isn't valid UTF-8
+    const MARIO: &[u8] = b"\x83\x7d\x83\x8a\x83\x49";
+
+    fn japanese_archive() -> Rarc {
+        let mut rarc = Rarc::new("root");
+        let mut name = MARIO.to_vec();
+        name.extend_from_slice(b".bin");
+        rarc.insert_entry(
+            0,
+            RarcEntry {
+                name: RarcName(name),
+                ..file("")
+            },
+        );
+        // Not valid Shift-JIS either, so it can only be kept as bytes
+        rarc.insert_entry(
+            0,
+            RarcEntry {
+                name: RarcName(b"bad\xFF.bin".to_vec()),
+                ..file("")
+            },
+        );
+        rarc.nodes.push(RarcNode {
+            kind: node_kind(&RarcName(MARIO.to_vec())),
+            name: RarcName(MARIO.to_vec()),
+            entries: special_entries(1, 0),
+        });
+        rarc.insert_entry(
+            0,
+            RarcEntry {
+                name: RarcName(MARIO.to_vec()),
+                id: DIRECTORY_ID,
+                attributes: ATTR_DIRECTORY,
+                node: Some(1),
+                offset: None,
+                data: Vec::new(),
+            },
+        );
+        rarc.insert_entry(1, file("a.bin"));
+        rarc.sync_ids();
+        rarc
+    }
+
+    #[test]
+    fn shift_jis_names_round_trip() {
+        let buf = japanese_archive().to_bytes().unwrap();
+        let parsed = Rarc::parse(&buf).unwrap();
+        let name = &parsed.nodes[0].entries[0].name;
+        assert_eq!(name.display_name(), "マリオ.bin");
+        assert_eq!(parsed.nodes[1].name.as_bytes(), MARIO);
+        assert_eq!(parsed.to_bytes().unwrap(), buf);
+
+        // The game looks names up by the hash of their stored bytes
+        let (info, _) = RawInfo::ref_from_prefix(&buf[size_of::<RawHeader>()..]).unwrap();
+        let entries = &buf[size_of::<RawHeader>() + info.entry_offset.get() as usize..];
+        let (entry, _) = RawEntry::ref_from_prefix(entries).unwrap();
+        assert_eq!(entry.name_hash.get(), name_hash(name.as_bytes()));
+    }
+
+    #[test]
+    fn shift_jis_names_repack_identically() {
+        let dir = std::env::temp_dir().join(format!("gctools-rarc-sjis-{}", std::process::id()));
+        let buf = japanese_archive().to_bytes().unwrap();
+        assert!(crate::archive::unpack(buf.clone(), &dir).unwrap());
+        assert!(dir.join("root/マリオ/a.bin").exists());
+        assert!(dir.join("root/マリオ.bin").exists());
+        let repacked = crate::archive::pack(&dir, &Default::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(repacked.unwrap(), buf);
+    }
+
+    #[test]
+    fn manifest_names_keep_their_bytes() {
+        let rarc = japanese_archive();
+        let text = toml::to_string(&rarc).unwrap();
+        assert!(text.contains("\"マリオ.bin\""));
+        let parsed: Rarc = toml::from_str(&text).unwrap();
+        let names: Vec<_> = parsed.nodes[0].entries.iter().map(|e| &e.name).collect();
+        let expected: Vec<_> = rarc.nodes[0].entries.iter().map(|e| &e.name).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn extract_rejects_traversal() {
+        let out_dir = std::env::temp_dir().join(format!("gctools-rarc-{}", std::process::id()));
+        for (root, name) in [("root", ".."), ("root", "../evil"), ("..", "a.bin")] {
+            let mut rarc = Rarc::new(root);
+            rarc.nodes[0].entries.push(file(name));
+            assert!(rarc.extract(&out_dir).is_err(), "{root}/{name}");
+            assert!(!out_dir.exists());
+        }
+    }
+}
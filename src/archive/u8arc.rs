@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::iso::fst::{self, Fst, FstEntry, FstEntryKind};
+use crate::safe_path;
+
+pub const U8_MAGIC: &[u8; 4] = &[0x55, 0xAA, 0x38, 0x2D];
+const ALIGNMENT: usize = 0x20;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawHeader {
+    magic: [u8; 4],
+    root_offset: big_endian::U32,
+    /// Size of the node table and string table
+    node_size: big_endian::U32,
+    data_offset: big_endian::U32,
+    padding: [u8; 0x10],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct U8Entry {
+    /// Path relative to the archive root, ending in `/` for directories
+    pub path: String,
+    /// Offset of the file's data in the archive it was unpacked from. Rebuilding keeps files in
+    /// this order; files without one go last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl U8Entry {
+    pub fn is_dir(&self) -> bool {
+        self.path.ends_with('/')
+    }
+}
+
+/// A parsed U8 archive. The node table has the same layout as a disc's FST. Without file data,
+/// this is also the manifest written when unpacking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct U8Archive {
+    /// Every entry except the root, in node table order
+    pub entries: Vec<U8Entry>,
+}
+
+/// Directory that unpacked U8 contents are written to, since the root node has no name
+const ROOT_DIR_NAME: &str = "root";
+
+/// Path with `.` components removed, for comparing archive paths with paths on disk
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+impl U8Archive {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<U8Archive> {
+        let (header, _) = RawHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("U8 archive is too small to contain a header"))?;
+        ensure!(&header.magic == U8_MAGIC, "Not a U8 archive");
+        let start = header.root_offset.get() as usize;
+        let nodes = buf
+            .get(start..start + header.node_size.get() as usize)
+            .ok_or_else(|| anyhow!("U8 node table extends past end of file"))?;
+        let fst = Fst::parse(nodes).context("Invalid U8 node table")?;
+
+        let mut entries = Vec::with_capacity(fst.entries.len());
+        for (entry, path) in fst.entries.iter().zip(fst.paths()).skip(1) {
+            let path = path.trim_start_matches('/');
+            entries.push(match entry.kind {
+                FstEntryKind::Directory { .. } => U8Entry {
+                    path: format!("{path}/"),
+                    offset: None,
+                    data: Vec::new(),
+                },
+                FstEntryKind::File { offset, size } => U8Entry {
+                    path: path.to_string(),
+                    offset: Some(offset),
+                    data: buf
+                        .get(offset as usize..offset as usize + size as usize)
+                        .ok_or_else(|| anyhow!("Data for {path} extends past end of file"))?
+                        .to_vec(),
+                },
+            });
+        }
+        Ok(U8Archive { entries })
+    }
+
+    /// Rebuild the node table from the entry paths, with file offsets left as zero. Paths are
+    /// decoded from Shift-JIS when parsed, so non-ASCII names are encoded back to it.
+    fn fst(&self) -> anyhow::Result<Fst> {
+        let mut fst = Fst {
+            entries: vec![FstEntry {
+                name: Vec::new(),
+                kind: FstEntryKind::Directory { parent: 0, next: 0 },
+            }],
+        };
+        // Stack of (directory path, node index) for the directories containing the next entry
+        let mut dirs: Vec<(String, usize)> = vec![(String::new(), 0)];
+        for entry in &self.entries {
+            let trimmed = entry.path.trim_end_matches('/');
+            let (parent, name) = match trimmed.rsplit_once('/') {
+                Some((parent, name)) => (format!("{parent}/"), name),
+                None => (String::new(), trimmed),
+            };
+            ensure!(!name.is_empty(), "Invalid U8 path '{}'", entry.path);
+            while dirs.last().is_some_and(|(path, _)| *path != parent) {
+                let (_, index) = dirs.pop().unwrap();
+                close_dir(&mut fst, index);
+            }
+            let Some(&(_, parent_index)) = dirs.last() else {
+                bail!(
+                    "'{}' does not directly follow its parent directory's other entries",
+                    entry.path
+                );
+            };
+
+            let index = fst.entries.len();
+            fst.entries.push(FstEntry {
+                name: fst::encode_name(name)
+                    .with_context(|| format!("Invalid U8 path '{}'", entry.path))?,
+                kind: if entry.is_dir() {
+                    FstEntryKind::Directory {
+                        parent: parent_index as u32,
+                        next: 0,
+                    }
+                } else {
+                    FstEntryKind::File {
+                        offset: 0,
+                        size: entry.data.len() as u32,
+                    }
+                },
+            });
+            if entry.is_dir() {
+                dirs.push((entry.path.clone(), index));
+            }
+        }
+        for (_, index) in dirs.into_iter().rev() {
+            close_dir(&mut fst, index);
+        }
+        Ok(fst)
+    }
+
+    /// All files as (path, entry), under a directory named `root`
+    pub fn files(&self) -> Vec<(PathBuf, &U8Entry)> {
+        let root = Path::new(ROOT_DIR_NAME);
+        self.entries
+            .iter()
+            .filter(|e| !e.is_dir())
+            .map(|e| (root.join(&e.path), e))
+            .collect()
+    }
+
+    /// Write every file under `out_dir`, inside a directory named `root`
+    pub fn extract(&self, out_dir: &Path) -> anyhow::Result<()> {
+        // Paths come from the archive's node table, so check them all before creating anything
+        for entry in &self.entries {
+            safe_path::check_relative_path(entry.path.trim_end_matches('/'))?;
+        }
+        let root = out_dir.join(ROOT_DIR_NAME);
+        std::fs::create_dir_all(&root)?;
+        for entry in self.entries.iter().filter(|e| e.is_dir()) {
+            std::fs::create_dir_all(root.join(&entry.path))?;
+        }
+        for (path, entry) in self.files() {
+            let dest = out_dir.join(path);
+            std::fs::write(&dest, &entry.data)
+                .with_context(|| format!("cannot write {}", dest.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+
+    /// Fill in file data from `dir`, laid out as written by [`U8Archive::extract`]. Files and
+    /// directories not in the archive yet are added.
+    pub fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let root = dir.join(ROOT_DIR_NAME);
+        let mut known = HashSet::new();
+        for entry in &mut self.entries {
+            let path = root.join(&entry.path);
+            known.insert(normalize(&path));
+            if !entry.is_dir() {
+                entry.data = std::fs::read(&path)
+                    .with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+            }
+        }
+        if !root.exists() {
+            return Ok(());
+        }
+        self.add_new_files(&root, &root, &known)
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut fst = self.fst()?;
+        let node_size = fst.to_bytes_with_root_name().len();
+        let root_offset = size_of::<RawHeader>();
+        let data_offset = (root_offset + node_size).next_multiple_of(ALIGNMENT);
+
+        // (node index, entry) for every file, in the order their data is written
+        let mut files: Vec<(usize, &U8Entry)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.is_dir())
+            .map(|(i, e)| (i + 1, e))
+            .collect();
+        files.sort_by_key(|(_, e)| e.offset.unwrap_or(u32::MAX));
+        let mut data = Vec::new();
+        for (index, entry) in files {
+            let position =
+                u32::try_from(data_offset + data.len()).context("U8 archive is too large")?;
+            if let FstEntryKind::File { offset, .. } = &mut fst.entries[index].kind {
+                *offset = position;
+            }
+            data.extend_from_slice(&entry.data);
+            data.resize(data.len().next_multiple_of(ALIGNMENT), 0);
+        }
+
+        let header = RawHeader {
+            magic: *U8_MAGIC,
+            root_offset: (root_offset as u32).into(),
+            node_size: (node_size as u32).into(),
+            data_offset: (data_offset as u32).into(),
+            padding: [0; 0x10],
+        };
+        let mut buf = Vec::with_capacity(data_offset + data.len());
+        buf.extend_from_slice(header.as_bytes());
+        buf.extend_from_slice(&fst.to_bytes_with_root_name());
+        buf.resize(data_offset, 0);
+        buf.extend_from_slice(&data);
+        Ok(buf)
+    }
+
+    /// Add files and directories under `dir` that aren't in the archive yet, after the last entry
+    /// in their parent directory
+    fn add_new_files(
+        &mut self,
+        root: &Path,
+        dir: &Path,
+        known: &HashSet<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("cannot read directory {}", dir.to_string_lossy()))?
+            .collect::<Result<_, _>>()?;
+        children.sort_by_key(|c| c.file_name());
+
+        for child in children {
+            let path = child.path();
+            let is_dir = child.file_type()?.is_dir();
+            if !known.contains(&normalize(&path)) {
+                let relative = path.strip_prefix(root).unwrap().to_string_lossy();
+                let relative = relative.replace('\\', "/");
+                let entry = if is_dir {
+                    U8Entry {
+                        path: format!("{relative}/"),
+                        offset: None,
+                        data: Vec::new(),
+                    }
+                } else {
+                    U8Entry {
+                        path: relative,
+                        offset: None,
+                        data: std::fs::read(&path)?,
+                    }
+                };
+                self.insert_entry(entry);
+            }
+            if is_dir {
+                self.add_new_files(root, &path, known)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_entry(&mut self, entry: U8Entry) {
+        let trimmed = entry.path.trim_end_matches('/');
+        // Insert after the parent directory's last descendant, keeping each directory contiguous
+        let pos = match trimmed.rsplit_once('/') {
+            Some((parent, _)) => {
+                let parent = format!("{parent}/");
+                self.entries
+                    .iter()
+                    .rposition(|e| e.path.starts_with(&parent))
+                    .map_or(self.entries.len(), |i| i + 1)
+            }
+            None => self.entries.len(),
+        };
+        self.entries.insert(pos, entry);
+    }
+}
+
+/// Set a directory's end index to the current entry count
+fn close_dir(fst: &mut Fst, index: usize) {
+    let end = fst.entries.len() as u32;
+    if let FstEntryKind::Directory { next, .. } = &mut fst.entries[index].kind {
+        *next = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, data: &[u8]) -> U8Entry {
+        U8Entry {
+            path: path.to_string(),
+            offset: None,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn shift_jis_names_round_trip() {
+        let archive = U8Archive {
+            entries: vec![
+                entry("マリオ/", &[]),
+                entry("マリオ/ルイージ.bin", &[1, 2, 3]),
+                entry("a.bin", &[4]),
+            ],
+        };
+        let buf = archive.to_bytes().unwrap();
+        // マリオ in Shift-JIS
+        assert!(buf.windows(6).any(|w| w == b"\x83\x7d\x83\x8a\x83\x49"));
+        let parsed = U8Archive::parse(&buf).unwrap();
+        let paths: Vec<_> = parsed.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["マリオ/", "マリオ/ルイージ.bin", "a.bin"]);
+        assert_eq!(parsed.to_bytes().unwrap(), buf);
+    }
+
+    #[test]
+    fn rejects_names_outside_shift_jis() {
+        let archive = U8Archive {
+            entries: vec![entry("\u{1F600}.bin", &[])],
+        };
+        assert!(archive.to_bytes().is_err());
+    }
+
+    #[test]
+    fn extract_rejects_traversal() {
+        let out_dir = std::env::temp_dir().join(format!("gctools-u8-{}", std::process::id()));
+        for path in ["../evil", "a/../../evil", "/etc/evil", "../"] {
+            let archive = U8Archive {
+                entries: vec![U8Entry {
+                    path: path.to_string(),
+                    offset: None,
+                    data: vec![1, 2, 3],
+                }],
+            };
+            assert!(archive.extract(&out_dir).is_err(), "{path}");
+            assert!(!out_dir.exists());
+        }
+    }
+}
@@ -0,0 +1,286 @@
+// The AST streamed audio container, as used by JAudio games. A header is followed by blocks, each
+// holding a run of every channel's samples one channel after another, coded as 16-bit PCM or as
+// 4-bit ADPCM with a fixed table of coefficients.
+
+use anyhow::{bail, ensure, Context};
+use zerocopy::byteorder::big_endian;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::dsp::{self, History};
+
+const HEADER_MAGIC: &[u8; 4] = b"STRM";
+const BLOCK_MAGIC: &[u8; 4] = b"BLCK";
+/// Bytes of each channel's data per block
+const BLOCK_SIZE: usize = 0x2760;
+const BLOCK_ALIGN: usize = 0x20;
+/// Channels whose history fits in a block header
+const MAX_HISTORY_CHANNELS: usize = 4;
+const ADPCM_FRAME_SIZE: usize = 9;
+const ADPCM_FRAME_SAMPLES: usize = 16;
+
+/// Predictor coefficients every ADPCM frame picks from
+const ADPCM_COEFS: [[i16; 2]; 16] = [
+    [0, 0],
+    [2048, 0],
+    [0, 2048],
+    [1024, 1024],
+    [4096, -2048],
+    [3584, -1536],
+    [3072, -1024],
+    [4608, -2560],
+    [4200, -2248],
+    [4800, -2624],
+    [5120, -3072],
+    [2048, -2048],
+    [1024, -1024],
+    [-1024, 1024],
+    [-1024, 0],
+    [-2048, 0],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AstFormat {
+    Adpcm,
+    Pcm16,
+}
+
+impl AstFormat {
+    fn samples_per_block(&self) -> usize {
+        match self {
+            AstFormat::Adpcm => BLOCK_SIZE / ADPCM_FRAME_SIZE * ADPCM_FRAME_SAMPLES,
+            AstFormat::Pcm16 => BLOCK_SIZE / 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ast {
+    pub format: AstFormat,
+    pub sample_rate: u32,
+    /// Samples of each channel
+    pub channels: Vec<Vec<i16>>,
+    /// First and last sample of the loop, inclusive
+    pub loop_points: Option<(u32, u32)>,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct AstHeader {
+    magic: [u8; 4],
+    /// Size of everything after the header
+    data_size: big_endian::U32,
+    format: big_endian::U16,
+    bits_per_sample: big_endian::U16,
+    channel_count: big_endian::U16,
+    loop_flag: big_endian::U16,
+    sample_rate: big_endian::U32,
+    sample_count: big_endian::U32,
+    loop_start: big_endian::U32,
+    /// One past the last sample of the loop
+    loop_end: big_endian::U32,
+    first_block_size: big_endian::U32,
+    unknown: big_endian::U32,
+    volume: u8,
+    padding: [u8; 23],
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct BlockHeader {
+    magic: [u8; 4],
+    size: big_endian::U32,
+    /// ADPCM history of the first four channels at the start of the block
+    history: [[big_endian::I16; 2]; MAX_HISTORY_CHANNELS],
+    padding: [u8; 8],
+}
+
+fn decode_adpcm_sample(nibble: i32, scale: u8, coefs: [i16; 2], history: &mut History) -> i16 {
+    let sample = ((nibble as i64) << scale << 11)
+        + coefs[0] as i64 * history.yn1 as i64
+        + coefs[1] as i64 * history.yn2 as i64;
+    let sample = (sample >> 11).clamp(i16::MIN as i64, i16::MAX as i64) as i16;
+    history.yn2 = history.yn1;
+    history.yn1 = sample;
+    sample
+}
+
+fn decode_adpcm(data: &[u8], sample_count: usize, history: &mut History, out: &mut Vec<i16>) {
+    let end = out.len() + sample_count;
+    for frame in data.chunks_exact(ADPCM_FRAME_SIZE) {
+        let scale = frame[0] >> 4;
+        let coefs = ADPCM_COEFS[frame[0] as usize & 0xf];
+        for &byte in &frame[1..] {
+            for nibble in [byte >> 4, byte & 0xf] {
+                if out.len() == end {
+                    return;
+                }
+                let nibble = ((nibble as i32) << 28) >> 28;
+                out.push(decode_adpcm_sample(nibble, scale, coefs, history));
+            }
+        }
+    }
+}
+
+impl Ast {
+    pub fn sample_count(&self) -> usize {
+        self.channels.first().map_or(0, |c| c.len())
+    }
+
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Ast> {
+        let (header, mut rest) =
+            AstHeader::read_from_prefix(buf).map_err(|_| anyhow::anyhow!("AST is too small"))?;
+        ensure!(&header.magic == HEADER_MAGIC, "Not an AST file");
+        let format = match header.format.get() {
+            0 => AstFormat::Adpcm,
+            1 => AstFormat::Pcm16,
+            format => bail!("Unsupported AST format {format}"),
+        };
+        let channel_count = header.channel_count.get() as usize;
+        ensure!(channel_count > 0, "AST has no channels");
+        let sample_count = header.sample_count.get() as usize;
+        let loop_points = (header.loop_flag.get() != 0).then(|| {
+            (
+                header.loop_start.get(),
+                header.loop_end.get().saturating_sub(1),
+            )
+        });
+
+        let mut channels = vec![Vec::with_capacity(sample_count); channel_count];
+        let mut histories = vec![History::default(); channel_count];
+        while channels[0].len() < sample_count {
+            let (block, data) = BlockHeader::read_from_prefix(rest)
+                .map_err(|_| anyhow::anyhow!("AST is truncated"))?;
+            ensure!(&block.magic == BLOCK_MAGIC, "Invalid AST block");
+            let size = block.size.get() as usize;
+            let data = data
+                .get(..size * channel_count)
+                .context("AST block is truncated")?;
+            let samples = (sample_count - channels[0].len()).min(match format {
+                AstFormat::Adpcm => size / ADPCM_FRAME_SIZE * ADPCM_FRAME_SAMPLES,
+                AstFormat::Pcm16 => size / 2,
+            });
+            ensure!(samples > 0, "AST block is empty");
+            for ((channel, history), data) in channels
+                .iter_mut()
+                .zip(&mut histories)
+                .zip(data.chunks_exact(size))
+            {
+                match format {
+                    AstFormat::Adpcm => decode_adpcm(data, samples, history, channel),
+                    AstFormat::Pcm16 => channel.extend(
+                        data.chunks_exact(2)
+                            .take(samples)
+                            .map(|b| i16::from_be_bytes([b[0], b[1]])),
+                    ),
+                }
+            }
+            rest = &rest[size_of::<BlockHeader>() + size * channel_count..];
+        }
+
+        Ok(Ast {
+            format,
+            sample_rate: header.sample_rate.get(),
+            channels,
+            loop_points,
+        })
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let channel_count = self.channels.len();
+        ensure!(channel_count > 0, "AST has no channels");
+        let sample_count = self.sample_count();
+        ensure!(
+            self.channels.iter().all(|c| c.len() == sample_count),
+            "AST channels have different lengths"
+        );
+        if let Some((start, end)) = self.loop_points {
+            ensure!(
+                start <= end && (end as usize) < sample_count,
+                "Loop {start}-{end} is outside the {sample_count} samples of audio"
+            );
+        }
+
+        let mut blocks = Vec::new();
+        let mut first_block_size = 0;
+        let mut histories = vec![History::default(); channel_count];
+        let samples_per_block = self.format.samples_per_block();
+        for start in (0..sample_count).step_by(samples_per_block) {
+            let end = (start + samples_per_block).min(sample_count);
+            let mut channel_data = Vec::with_capacity(channel_count);
+            let mut block_history = [[big_endian::I16::ZERO; 2]; MAX_HISTORY_CHANNELS];
+            for (i, (channel, history)) in self.channels.iter().zip(&mut histories).enumerate() {
+                if let Some(slot) = block_history.get_mut(i) {
+                    *slot = [history.yn1.into(), history.yn2.into()];
+                }
+                let samples = &channel[start..end];
+                let mut data: Vec<u8> = match self.format {
+                    AstFormat::Adpcm => samples
+                        .chunks(ADPCM_FRAME_SAMPLES)
+                        .flat_map(|samples| {
+                            let mut frame = [0; ADPCM_FRAME_SIZE];
+                            let (index, scale) = dsp::encode_frame(
+                                samples,
+                                &ADPCM_COEFS,
+                                15,
+                                history,
+                                decode_adpcm_sample,
+                                &mut frame[1..],
+                            );
+                            frame[0] = scale << 4 | index;
+                            frame
+                        })
+                        .collect(),
+                    AstFormat::Pcm16 => samples.iter().flat_map(|s| s.to_be_bytes()).collect(),
+                };
+                data.resize(data.len().next_multiple_of(BLOCK_ALIGN), 0);
+                channel_data.push(data);
+            }
+            let header = BlockHeader {
+                magic: *BLOCK_MAGIC,
+                size: (channel_data[0].len() as u32).into(),
+                history: block_history,
+                padding: [0; 8],
+            };
+            if blocks.is_empty() {
+                first_block_size = channel_data[0].len() as u32;
+            }
+            blocks.extend_from_slice(header.as_bytes());
+            channel_data.iter().for_each(|data| blocks.extend(data));
+        }
+
+        let (loop_start, loop_end) = match self.loop_points {
+            Some((start, end)) => (start, end + 1),
+            None => (0, sample_count as u32),
+        };
+        let header = AstHeader {
+            magic: *HEADER_MAGIC,
+            data_size: u32::try_from(blocks.len())
+                .context("Audio is too long")?
+                .into(),
+            format: match self.format {
+                AstFormat::Adpcm => 0,
+                AstFormat::Pcm16 => 1,
+            }
+            .into(),
+            bits_per_sample: 16.into(),
+            channel_count: (channel_count as u16).into(),
+            loop_flag: if self.loop_points.is_some() {
+                0xffff
+            } else {
+                0
+            }
+            .into(),
+            sample_rate: self.sample_rate.into(),
+            sample_count: (sample_count as u32).into(),
+            loop_start: loop_start.into(),
+            loop_end: loop_end.into(),
+            first_block_size: first_block_size.into(),
+            unknown: 0.into(),
+            volume: 0x7f,
+            padding: [0; 23],
+        };
+        let mut out = header.as_bytes().to_vec();
+        out.extend_from_slice(&blocks);
+        Ok(out)
+    }
+}
@@ -0,0 +1,288 @@
+// Converting many inputs in one run, for projects that build many modules: glob patterns for
+// inputs, output names templated from input names, and a pool of threads to convert them on.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{bail, ensure};
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Match one path component against a pattern with `*`, `?`, and `[...]` classes (`[!...]`
+/// negates a class)
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches_component(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && matches_component(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some(end) = rest.iter().skip(1).position(|&c| c == ']').map(|i| i + 1) else {
+                return name.first() == Some(&'[') && matches_component(rest, &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negate, class) = match rest[..end].split_first() {
+                Some(('!', class)) => (true, class),
+                _ => (false, &rest[..end]),
+            };
+            let mut in_class = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    in_class |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_class |= class[i] == c;
+                    i += 1;
+                }
+            }
+            in_class != negate && matches_component(&rest[end + 1..], &name[1..])
+        }
+        Some((&p, rest)) => name.first() == Some(&p) && matches_component(rest, &name[1..]),
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .map(|p| p.strip_prefix("./").map(Path::to_path_buf).unwrap_or(p))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn glob_from(base: &Path, components: &[&str], out: &mut Vec<PathBuf>) {
+    let Some((&component, rest)) = components.split_first() else {
+        if base.exists() {
+            out.push(base.to_path_buf());
+        }
+        return;
+    };
+    if component == "**" {
+        // Any number of directories, including none
+        glob_from(base, rest, out);
+        for path in read_dir_sorted(base) {
+            if path.is_dir() {
+                glob_from(&path, components, out);
+            }
+        }
+    } else if is_pattern(component) {
+        let pattern: Vec<char> = component.chars().collect();
+        for path in read_dir_sorted(base) {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            // Like shells, wildcards don't match hidden files
+            if name.starts_with('.') && !component.starts_with('.') {
+                continue;
+            }
+            let name: Vec<char> = name.chars().collect();
+            if matches_component(&pattern, &name) {
+                glob_from(&path, rest, out);
+            }
+        }
+    } else {
+        glob_from(&base.join(component), rest, out);
+    }
+}
+
+/// Expand glob patterns such as `build/*.elf` or `mods/**/*.elf` into the files they match, in
+/// sorted order. Arguments without wildcards are passed through as they are, so shells that
+/// already expanded a pattern work the same.
+pub fn expand_globs(patterns: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let Some(text) = pattern.to_str().filter(|t| is_pattern(t)) else {
+            paths.push(pattern.clone());
+            continue;
+        };
+        let text = text.replace('\\', "/");
+        let (base, text) = match text.strip_prefix('/') {
+            Some(rest) => (PathBuf::from("/"), rest),
+            None => (PathBuf::new(), text.as_str()),
+        };
+        let components: Vec<&str> = text.split('/').filter(|c| !c.is_empty()).collect();
+        let start = paths.len();
+        glob_from(&base, &components, &mut paths);
+        ensure!(
+            paths.len() > start,
+            "No files match '{}'",
+            pattern.to_string_lossy()
+        );
+    }
+    Ok(paths)
+}
+
+/// Where converted files go: one output path, a template with `{stem}`, `{name}`, and `{dir}`
+/// filled in from each input, or a directory with outputs named after their inputs
+#[derive(Debug, Clone)]
+pub struct OutputNames {
+    pub output: Option<String>,
+    pub output_dir: Option<PathBuf>,
+    /// Extension outputs get in place of their input's by default, such as `rel`
+    pub extension: &'static str,
+}
+
+impl OutputNames {
+    /// Output path for each of `inputs`, checking that every input gets its own. Paths are all
+    /// worked out before converting anything, so two inputs can't race to write one file.
+    pub fn paths(&self, inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+        if let Some(output) = &self.output {
+            if self.output_dir.is_some() {
+                bail!("Give either an output path or an output directory, not both");
+            }
+            if inputs.len() > 1 && !output.contains('{') {
+                bail!(
+                    "{} inputs can't all be written to {output}; use a template such as \
+                     `{{stem}}.{}`, or --output-dir",
+                    inputs.len(),
+                    self.extension
+                );
+            }
+        }
+        let paths: Vec<PathBuf> = inputs.iter().map(|input| self.path_for(input)).collect();
+        let mut seen = HashMap::new();
+        for (input, path) in inputs.iter().zip(&paths) {
+            if let Some(other) = seen.insert(path, input) {
+                bail!(
+                    "{} and {} would both be written to {}; use a template with `{{dir}}`, \
+                     such as `{{dir}}/{{stem}}.{}`",
+                    other.to_string_lossy(),
+                    input.to_string_lossy(),
+                    path.to_string_lossy(),
+                    self.extension
+                );
+            }
+        }
+        Ok(paths)
+    }
+
+    fn path_for(&self, input: &Path) -> PathBuf {
+        let default_name = input.with_extension(self.extension);
+        if let Some(output) = &self.output {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            let name = input.file_name().unwrap_or_default().to_string_lossy();
+            let dir = input.parent().unwrap_or(Path::new("")).to_string_lossy();
+            let dir = if dir.is_empty() { ".".into() } else { dir };
+            PathBuf::from(
+                output
+                    .replace("{stem}", &stem)
+                    .replace("{name}", &name)
+                    .replace("{dir}", &dir),
+            )
+        } else if let Some(output_dir) = &self.output_dir {
+            output_dir.join(default_name.file_name().unwrap_or_default())
+        } else {
+            default_name
+        }
+    }
+}
+
+/// Run `f` on each input across up to `jobs` threads, returning the inputs that failed along
+/// with their errors
+pub fn run_parallel<T: Sync>(
+    inputs: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> anyhow::Result<()> + Sync,
+) -> Vec<(&T, anyhow::Error)> {
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else {
+                    break;
+                };
+                if let Err(e) = f(input) {
+                    failures.lock().unwrap().push((i, e));
+                }
+            });
+        }
+    });
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by_key(|&(i, _)| i);
+    failures.into_iter().map(|(i, e)| (&inputs[i], e)).collect()
+}
+
+/// Turn a batch's failures into its result. A lone input fails with its own error, the same as
+/// without batches; otherwise each failure is logged and the batch fails as a whole.
+pub fn into_result(
+    failures: Vec<(&Path, anyhow::Error)>,
+    count: usize,
+    noun: &str,
+) -> anyhow::Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    if count == 1 {
+        return Err(failures.into_iter().next().unwrap().1);
+    }
+    for (input, e) in &failures {
+        log::error!("{}: {e:#}", input.to_string_lossy());
+    }
+    bail!("{} of {count} {noun} failed to convert", failures.len())
+}
+
+/// Threads to convert on when not told otherwise
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(output: Option<&str>, output_dir: Option<&str>) -> OutputNames {
+        OutputNames {
+            output: output.map(String::from),
+            output_dir: output_dir.map(PathBuf::from),
+            extension: "rel",
+        }
+    }
+
+    #[test]
+    fn output_paths() {
+        let inputs = [PathBuf::from("a/x.elf"), PathBuf::from("b/y.elf")];
+        assert_eq!(
+            names(None, None).paths(&inputs).unwrap(),
+            [PathBuf::from("a/x.rel"), PathBuf::from("b/y.rel")]
+        );
+        assert_eq!(
+            names(Some("rels/{stem}.rel"), None).paths(&inputs).unwrap(),
+            [PathBuf::from("rels/x.rel"), PathBuf::from("rels/y.rel")]
+        );
+        assert_eq!(
+            names(None, Some("out")).paths(&inputs).unwrap(),
+            [PathBuf::from("out/x.rel"), PathBuf::from("out/y.rel")]
+        );
+        assert!(names(Some("x.rel"), None).paths(&inputs).is_err());
+        assert!(names(Some("{stem}.rel"), Some("out"))
+            .paths(&inputs)
+            .is_err());
+    }
+
+    #[test]
+    fn colliding_outputs_are_rejected() {
+        let inputs = [PathBuf::from("a/x.elf"), PathBuf::from("b/x.elf")];
+        assert!(names(None, Some("out")).paths(&inputs).is_err());
+        assert!(names(Some("rels/{stem}.rel"), None).paths(&inputs).is_err());
+        assert!(names(Some("{name}.rel"), None).paths(&inputs).is_err());
+        assert!(names(Some("{dir}/{stem}.rel"), None).paths(&inputs).is_ok());
+        assert!(names(None, None).paths(&inputs).is_ok());
+    }
+}
@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::archive::{self, Archive, PackOptions};
+use gamecube_tools::yaz0::CompressionLevel;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct ArcArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the files in an archive
+    List {
+        /// Path to input archive (RARC or U8, optionally Yaz0 compressed)
+        input: PathBuf,
+    },
+    /// Extract an archive and write a manifest for repacking
+    Unpack {
+        /// Path to input archive (RARC or U8, optionally Yaz0 compressed)
+        input: PathBuf,
+        /// Directory to extract to
+        output_dir: PathBuf,
+    },
+    /// Build an archive from an unpacked directory
+    Pack {
+        /// Directory written by unpack, or any directory to use as the archive root
+        input_dir: PathBuf,
+        /// Path to output archive
+        output: PathBuf,
+        /// Compress the archive with Yaz0, regardless of the manifest
+        #[arg(long, conflicts_with = "no_yaz0")]
+        yaz0: bool,
+        /// Don't compress the archive, regardless of the manifest
+        #[arg(long)]
+        no_yaz0: bool,
+        /// Yaz0 compression level: fast, default, or best. Defaults to the level recorded in the
+        /// manifest.
+        #[arg(long, value_parser = CompressionLevel::parse)]
+        level: Option<CompressionLevel>,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = ArcArgs::parse_from(args);
+    match args.command {
+        Command::List { input } => {
+            let (buf, _) = archive::decompress(read_file(&input)?)?;
+            let archive = archive::parse(&buf)?;
+            match archive {
+                Archive::Rarc(rarc) => {
+                    for (path, entry) in rarc.files()? {
+                        println!(
+                            "{:>5} {:02X} {:>10} {}",
+                            entry.id,
+                            entry.attributes,
+                            entry.data.len(),
+                            path.to_string_lossy()
+                        );
+                    }
+                }
+                Archive::U8(u8arc) => {
+                    for (path, entry) in u8arc.files() {
+                        println!("{:>10} {}", entry.data.len(), path.to_string_lossy());
+                    }
+                }
+            }
+        }
+        Command::Unpack { input, output_dir } => {
+            let exact = archive::unpack(read_file(&input)?, &output_dir)
+                .with_context(|| format!("cannot unpack {}", input.to_string_lossy()))?;
+            if !exact {
+                eprintln!(
+                    "warning: {} was compressed by a different Yaz0 encoder, so repacking will not \
+                     reproduce it exactly",
+                    input.to_string_lossy()
+                );
+            }
+        }
+        Command::Pack {
+            input_dir,
+            output,
+            yaz0,
+            no_yaz0,
+            level,
+        } => {
+            let options = PackOptions {
+                yaz0: (yaz0 || no_yaz0).then_some(yaz0),
+                level,
+            };
+            let buf = archive::pack(&input_dir, &options)?;
+            std::fs::write(&output, buf)
+                .with_context(|| format!("cannot write {}", output.to_string_lossy()))?;
+        }
+    }
+
+    Ok(())
+}
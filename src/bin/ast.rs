@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::ast::{Ast, AstFormat};
+use gamecube_tools::wav::Wav;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Convert between WAV and AST streamed audio", long_about = None)]
+struct AstArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Decode an AST to a WAV, keeping its channels and loop
+    Unpack {
+        /// Path to input AST
+        input: PathBuf,
+        /// Path to output WAV. Defaults to the input with a `.wav` extension.
+        output: Option<PathBuf>,
+    },
+    /// Encode a WAV as an AST
+    Pack {
+        /// Path to input WAV
+        input: PathBuf,
+        /// Path to output AST. Defaults to the input with an `.ast` extension.
+        output: Option<PathBuf>,
+        /// Store 16-bit PCM instead of ADPCM, which is four times the size
+        #[arg(long)]
+        pcm: bool,
+        /// First sample of the loop, overriding the WAV's loop
+        #[arg(long)]
+        loop_start: Option<u32>,
+        /// Last sample of the loop, inclusive. Defaults to the last sample.
+        #[arg(long, requires = "loop_start")]
+        loop_end: Option<u32>,
+        /// Don't loop, even if the WAV has a loop
+        #[arg(long, conflicts_with = "loop_start")]
+        no_loop: bool,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = AstArgs::parse_from(args);
+    match args.command {
+        Command::Unpack { input, output } => {
+            let ast = Ast::parse(&read_file(&input)?)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            let wav = Wav {
+                sample_rate: ast.sample_rate,
+                channels: ast.channels,
+                loop_points: ast.loop_points,
+            };
+            let output = output.unwrap_or_else(|| input.with_extension("wav"));
+            write_file(&output, wav.to_bytes()?)?;
+        }
+        Command::Pack {
+            input,
+            output,
+            pcm,
+            loop_start,
+            loop_end,
+            no_loop,
+        } => {
+            let wav = Wav::parse(&read_file(&input)?)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            let loop_points = match loop_start {
+                Some(start) => Some((
+                    start,
+                    loop_end.unwrap_or((wav.sample_count() as u32).saturating_sub(1)),
+                )),
+                None if no_loop => None,
+                None => wav.loop_points,
+            };
+            let ast = Ast {
+                format: if pcm {
+                    AstFormat::Pcm16
+                } else {
+                    AstFormat::Adpcm
+                },
+                sample_rate: wav.sample_rate,
+                channels: wav.channels,
+                loop_points,
+            };
+            let output = output.unwrap_or_else(|| input.with_extension("ast"));
+            write_file(&output, ast.to_bytes()?)?;
+        }
+    }
+    Ok(())
+}
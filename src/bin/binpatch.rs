@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::binpatch::{self, Bps, Ips, PatchFormat};
+use gamecube_tools::iso;
+use gamecube_tools::vcdiff::{self, Vcdiff};
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Ips,
+    Bps,
+    Vcdiff,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Apply and create binary patches for DOLs and disc images", long_about = None)]
+struct BinPatchArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply an IPS, BPS, or VCDIFF (xdelta) patch
+    Apply {
+        /// Path to the patch file
+        patch: PathBuf,
+        /// Path to the file to patch. Compressed disc images are decompressed as they're read.
+        input: PathBuf,
+        /// Path to output file
+        output: PathBuf,
+    },
+    /// Create a patch from an original file to a modified one
+    Create {
+        /// Path to the original file
+        source: PathBuf,
+        /// Path to the modified file
+        target: PathBuf,
+        /// Path to output patch file
+        output: PathBuf,
+        /// Patch format. Chosen from the output extension by default: `.ips`, `.bps`, or
+        /// `.xdelta`/`.vcdiff`.
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+        /// Drop everything after the modified disc image's last file, so the patched image
+        /// comes out trimmed
+        #[arg(long)]
+        trim: bool,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn create_file(path: &Path) -> anyhow::Result<File> {
+    File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn apply(patch_path: &Path, input: &Path, output: &Path) -> anyhow::Result<()> {
+    let patch = read_file(patch_path)?;
+    let Some(format) = binpatch::detect(&patch) else {
+        bail!(
+            "{} is not an IPS or BPS patch",
+            patch_path.to_string_lossy()
+        );
+    };
+    let mut source = iso::open_image(input)?;
+    match format {
+        PatchFormat::Ips => {
+            let ips = Ips::parse(&patch).context("cannot parse IPS patch")?;
+            let mut target = create_file(output)?;
+            io::copy(&mut source, &mut target)?;
+            ips.apply(&mut target)?;
+            if let Some(size) = ips.truncate {
+                target.set_len(size as u64)?;
+            }
+            println!("Applied {} IPS records", ips.records.len());
+        }
+        PatchFormat::Bps => {
+            let bps = Bps::parse(&patch).context("cannot parse BPS patch")?;
+            let mut target = create_file(output)?;
+            bps.apply(&mut source, &mut target)?;
+            println!("Applied BPS patch, writing {} bytes", bps.target_size);
+        }
+        PatchFormat::Vcdiff => {
+            let vcdiff = Vcdiff::parse(&patch).context("cannot parse VCDIFF patch")?;
+            let mut target = create_file(output)?;
+            let size = vcdiff.apply(&mut source, &mut target)?;
+            println!("Applied VCDIFF patch, writing {size} bytes");
+        }
+    }
+    Ok(())
+}
+
+fn output_format(path: &Path) -> anyhow::Result<Format> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    Ok(match extension.as_deref() {
+        Some("ips") => Format::Ips,
+        Some("bps") => Format::Bps,
+        Some("xdelta" | "vcdiff") => Format::Vcdiff,
+        _ => bail!(
+            "cannot tell the patch format from {}, pass --format",
+            path.to_string_lossy()
+        ),
+    })
+}
+
+fn create(
+    source_path: &Path,
+    target_path: &Path,
+    output: &Path,
+    format: Format,
+    trim: bool,
+) -> anyhow::Result<()> {
+    let mut source = iso::open_image(source_path)?;
+    let mut target = iso::open_image(target_path)?;
+    let mut target_size = target.seek(SeekFrom::End(0))?;
+    target.seek(SeekFrom::Start(0))?;
+
+    // Files that moved between disc images are matched by path
+    let mut target_disc = iso::open(target_path).ok();
+    let moved = match (iso::open(source_path).ok(), &mut target_disc) {
+        (Some(mut source_disc), Some(target_disc)) => {
+            binpatch::moved_files(&mut source_disc, target_disc)?
+        }
+        _ => Vec::new(),
+    };
+    if trim {
+        let Some(target_disc) = &mut target_disc else {
+            bail!("{} is not a disc image", target_path.to_string_lossy());
+        };
+        target_size = target_disc.used_size()?;
+    }
+    let mut target = target.take(target_size);
+
+    let mut patch = BufWriter::new(create_file(output)?);
+    match format {
+        Format::Ips => {
+            if target_size > binpatch::IPS_MAX_SIZE as u64 {
+                bail!("IPS patches can't patch past 16 MiB, use BPS or VCDIFF instead");
+            }
+            let mut source_buf = Vec::new();
+            source.take(target_size + 1).read_to_end(&mut source_buf)?;
+            let mut target_buf = Vec::new();
+            target.read_to_end(&mut target_buf)?;
+            let ips = Ips::diff(&source_buf, &target_buf)?;
+            patch.write_all(&ips.to_bytes())?;
+        }
+        Format::Bps => {
+            binpatch::create_bps(&mut source, &mut target, target_size, &moved, &mut patch)?
+        }
+        Format::Vcdiff => vcdiff::create(&mut source, &mut target, &moved, &mut patch)?,
+    }
+    patch.flush()?;
+    let size = patch.get_ref().metadata()?.len();
+    let name = match format {
+        Format::Ips => "IPS",
+        Format::Bps => "BPS",
+        Format::Vcdiff => "VCDIFF",
+    };
+    println!("Wrote {size} byte {name} patch");
+    if !moved.is_empty() {
+        println!("Matched {} moved files", moved.len());
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = BinPatchArgs::parse_from(args);
+    match args.command {
+        Command::Apply {
+            patch,
+            input,
+            output,
+        } => apply(&patch, &input, &output)
+            .with_context(|| format!("cannot patch {}", input.to_string_lossy()))?,
+        Command::Create {
+            source,
+            target,
+            output,
+            format,
+            trim,
+        } => {
+            let format = match format {
+                Some(format) => format,
+                None => output_format(&output)?,
+            };
+            create(&source, &target, &output, format, trim)
+                .with_context(|| format!("cannot create patch for {}", target.to_string_lossy()))?
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use gamecube_tools::bnr::{
+    Bnr, BnrLanguage, BnrManifest, BnrManifestEntry, BnrMetadata, BnrVersion, BANNER_HEIGHT,
+    BANNER_WIDTH,
+};
+use gamecube_tools::gcimage;
+use gamecube_tools::manifest;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct BnrPackArgs {
+    /// 96x32 banner image
+    image: PathBuf,
+    /// Path to output opening.bnr
+    output: PathBuf,
+    /// TOML or JSON metadata file, as written by bnrunpack, to take the version and strings from
+    #[arg(long, conflicts_with_all = ["name", "maker", "long_name", "long_maker", "description"])]
+    metadata: Option<PathBuf>,
+    /// Game title shown in the IPL's menu
+    #[arg(long, required_unless_present = "metadata")]
+    name: Option<String>,
+    /// Developer or publisher
+    #[arg(long, default_value = "")]
+    maker: String,
+    /// Full game title [default: the value of --name]
+    #[arg(long)]
+    long_name: Option<String>,
+    /// Full developer or publisher name [default: the value of --maker]
+    #[arg(long)]
+    long_maker: Option<String>,
+    /// Description shown in the IPL's menu
+    #[arg(long, default_value = "")]
+    description: String,
+    /// Write a PAL BNR2 banner, using the same strings for every language
+    #[arg(long, conflicts_with = "metadata")]
+    bnr2: bool,
+    /// Store the strings as Shift-JIS, as Japanese games' banners are, instead of Latin-1
+    #[arg(long, conflicts_with = "metadata")]
+    shift_jis: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = BnrPackArgs::parse_from(args);
+    let (rgba, width, height) = gcimage::read_png(&read_file(&args.image)?)
+        .with_context(|| format!("cannot decode {}", args.image.to_string_lossy()))?;
+    ensure!(
+        (width, height) == (BANNER_WIDTH, BANNER_HEIGHT),
+        "Banner image must be {BANNER_WIDTH}x{BANNER_HEIGHT}, got {width}x{height}"
+    );
+
+    let manifest = match &args.metadata {
+        Some(path) => manifest::read_file(path)?,
+        None => {
+            let name = args.name.unwrap_or_default();
+            let strings = BnrMetadata {
+                long_name: args.long_name.unwrap_or(name.clone()),
+                long_maker: args.long_maker.unwrap_or(args.maker.clone()),
+                short_name: name,
+                short_maker: args.maker,
+                description: args.description,
+            };
+            let (version, language) = if args.bnr2 {
+                (BnrVersion::Bnr2, Some(BnrLanguage::English))
+            } else {
+                (BnrVersion::Bnr1, None)
+            };
+            BnrManifest {
+                version,
+                shift_jis: args.shift_jis,
+                metadata: vec![BnrManifestEntry { language, strings }],
+            }
+        }
+    };
+    let bnr = Bnr::from_rgba(
+        manifest.version,
+        &rgba,
+        manifest.metadata()?,
+        manifest.shift_jis,
+    )?;
+    std::fs::write(&args.output, bnr.to_bytes()?)
+        .with_context(|| format!("cannot write {}", args.output.to_string_lossy()))?;
+
+    Ok(())
+}
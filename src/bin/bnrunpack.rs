@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::bnr::{Bnr, BANNER_HEIGHT, BANNER_WIDTH};
+use gamecube_tools::gcimage;
+use gamecube_tools::manifest::{self, ManifestFormat};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct BnrUnpackArgs {
+    /// Path to input opening.bnr
+    input: PathBuf,
+    /// Directory to write banner.png and the metadata file to
+    output_dir: PathBuf,
+    /// Metadata format: toml or json
+    #[arg(long, default_value = "toml", value_parser = ManifestFormat::parse)]
+    format: ManifestFormat,
+    /// Decode the strings as Shift-JIS, for a Japanese game's banner, instead of Latin-1
+    #[arg(long)]
+    shift_jis: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = BnrUnpackArgs::parse_from(args);
+    let bnr = Bnr::parse(&read_file(&args.input)?, args.shift_jis)?;
+
+    std::fs::create_dir_all(&args.output_dir).with_context(|| {
+        format!(
+            "cannot create directory {}",
+            args.output_dir.to_string_lossy()
+        )
+    })?;
+    let png = gcimage::write_png(&bnr.image_rgba(), BANNER_WIDTH, BANNER_HEIGHT)?;
+    write_file(&args.output_dir.join("banner.png"), png)?;
+    let metadata = manifest::write(&bnr.manifest(), args.format)?;
+    let metadata_path = args
+        .output_dir
+        .join("metadata")
+        .with_extension(args.format.extension());
+    write_file(&metadata_path, metadata)?;
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::bti::Bti;
+use gamecube_tools::gcimage::{self, MipmapFilter, PaletteFormat, TextureFormat, WrapMode};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct BtiArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show a BTI texture's format and sampler settings
+    Info {
+        /// Path to input BTI
+        input: PathBuf,
+    },
+    /// Convert a PNG to a BTI
+    Encode {
+        /// Input PNG
+        input: PathBuf,
+        /// Path to output BTI
+        output: PathBuf,
+        /// Texture format: i4, i8, ia4, ia8, rgb565, rgb5a3, rgba8, cmpr, ci4, or ci8
+        #[arg(long, default_value = "cmpr", value_parser = TextureFormat::parse)]
+        format: TextureFormat,
+        /// Palette format for ci4 and ci8: rgb565, rgb5a3, or ia8
+        #[arg(long, default_value = "rgb5a3", value_parser = PaletteFormat::parse)]
+        palette_format: PaletteFormat,
+        /// Horizontal wrap mode: clamp, repeat, or mirror
+        #[arg(long, default_value = "clamp", value_parser = WrapMode::parse)]
+        wrap_s: WrapMode,
+        /// Vertical wrap mode: clamp, repeat, or mirror
+        #[arg(long, default_value = "clamp", value_parser = WrapMode::parse)]
+        wrap_t: WrapMode,
+        /// Generate a full mipmap chain, downscaling with this filter: box or lanczos
+        #[arg(long, value_parser = MipmapFilter::parse)]
+        mipmaps: Option<MipmapFilter>,
+    },
+    /// Convert a BTI to a PNG
+    Decode {
+        /// Path to input BTI
+        input: PathBuf,
+        /// Path to output PNG
+        output: PathBuf,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = BtiArgs::parse_from(args);
+    match args.command {
+        Command::Info { input } => {
+            let bti = Bti::parse(&read_file(&input)?)?;
+            println!("Format:     {}", bti.format);
+            println!("Size:       {}x{}", bti.width, bti.height);
+            println!("Alpha:      {:?}", bti.alpha_mode);
+            println!("Wrap:       {:?}/{:?}", bti.wrap_s, bti.wrap_t);
+            println!("Filters:    min {} mag {}", bti.min_filter, bti.mag_filter);
+            println!(
+                "Images:     {} (mipmaps {})",
+                bti.image_count,
+                if bti.mipmaps_enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+            println!(
+                "LOD:        {} to {}, bias {}",
+                bti.min_lod, bti.max_lod, bti.lod_bias
+            );
+            if let Some(tlut) = &bti.palette {
+                println!("Palette:    {} x{}", tlut.format, tlut.len());
+            }
+        }
+        Command::Encode {
+            input,
+            output,
+            format,
+            palette_format,
+            wrap_s,
+            wrap_t,
+            mipmaps,
+        } => {
+            let (rgba, width, height) = gcimage::read_png(&read_file(&input)?)
+                .with_context(|| format!("cannot decode {}", input.to_string_lossy()))?;
+            let mut bti = Bti::from_rgba(format, palette_format, &rgba, width, height, mipmaps)
+                .with_context(|| format!("cannot encode {}", input.to_string_lossy()))?;
+            bti.wrap_s = wrap_s;
+            bti.wrap_t = wrap_t;
+            write_file(&output, bti.to_bytes()?)?;
+        }
+        Command::Decode { input, output } => {
+            let bti = Bti::parse(&read_file(&input)?)?;
+            let png = gcimage::write_png(&bti.to_rgba()?, bti.width, bti.height)?;
+            write_file(&output, png)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use gamecube_tools::binpatch::{self, Ips};
+use gamecube_tools::dist::{DistGci, DistIsoPatch, DistManifest, DistPatchFormat};
+use gamecube_tools::gcipack::{GciImage, GciPackOptions, IconFrame};
+use gamecube_tools::gecko::{self, GeckoCode};
+use gamecube_tools::{dolphin_ini, gcimage, gcipack, iso, manifest, patch, symbol_map, vcdiff};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Build a mod's GCI, GCT, Dolphin INI, and disc image patch from a manifest",
+    long_about = None
+)]
+struct DistArgs {
+    /// Path to the TOML or JSON manifest
+    manifest: PathBuf,
+    /// Version to stamp the artifacts with, overriding the manifest's
+    #[arg(long)]
+    stamp: Option<String>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Read an image, converting it to RGB5A3 if it's a PNG
+fn read_image(path: &Path, width: usize, height: usize) -> anyhow::Result<Vec<u8>> {
+    let buf = read_file(path)?;
+    if !buf.starts_with(b"\x89PNG") {
+        return Ok(buf);
+    }
+    let (rgba, w, h) = gcimage::read_png(&buf)
+        .with_context(|| format!("cannot decode {}", path.to_string_lossy()))?;
+    ensure!(
+        (w, h) == (width, height),
+        "{} must be {width}x{height}, got {w}x{h}",
+        path.to_string_lossy()
+    );
+    Ok(gcimage::encode_rgb5a3(&rgba, width, height))
+}
+
+/// Time to stamp GCIs with when none is given: `SOURCE_DATE_EPOCH` if it's set, so rebuilds are
+/// byte-identical, otherwise the current time
+fn default_timestamp() -> anyhow::Result<u32> {
+    let unix_time = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.trim().parse().map_err(|_| {
+            anyhow!("SOURCE_DATE_EPOCH must be a number of seconds since 1970, got '{epoch}'")
+        })?,
+        Err(_) => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    Ok(gcipack::card_time(unix_time))
+}
+
+fn build_gci(manifest: &DistManifest, gci: &DistGci) -> anyhow::Result<Vec<u8>> {
+    let input = read_file(&gci.input)?;
+    let banner = gci
+        .banner
+        .as_ref()
+        .map(|path| read_image(path, 96, 32))
+        .transpose()?;
+    let icon = read_image(&gci.icon, 32, 32)?;
+    let timestamp = match gci.timestamp {
+        Some(timestamp) => timestamp,
+        None => default_timestamp()?,
+    };
+    Ok(gcipack::gcipack(
+        &input,
+        banner.as_deref().map(GciImage::Rgb5a3),
+        &[IconFrame::still(&icon)],
+        &GciPackOptions {
+            file_name: &gci.file_name,
+            title: &manifest.stamp(&gci.title),
+            description: &manifest.stamp(&gci.description),
+            encoding: gci.encoding,
+            gamecode: &gci.gamecode,
+            timestamp,
+            permissions: gci.permissions.clone(),
+        },
+    )?)
+}
+
+fn build_iso_patch(iso_patch: &DistIsoPatch, output: &Path) -> anyhow::Result<()> {
+    let mut source = iso::open_image(&iso_patch.source)?;
+    let mut target = iso::open_image(&iso_patch.target)?;
+    let mut target_size = target.seek(SeekFrom::End(0))?;
+    target.seek(SeekFrom::Start(0))?;
+
+    // Files that moved between disc images are matched by path
+    let mut source_disc = iso::open(&iso_patch.source)?;
+    let mut target_disc = iso::open(&iso_patch.target)?;
+    let moved = binpatch::moved_files(&mut source_disc, &mut target_disc)?;
+    if iso_patch.trim {
+        target_size = target_disc.used_size()?;
+    }
+    let mut target = target.take(target_size);
+
+    let mut patch = BufWriter::new(
+        File::create(output)
+            .with_context(|| format!("cannot write {}", output.to_string_lossy()))?,
+    );
+    match iso_patch.format {
+        DistPatchFormat::Ips => {
+            if target_size > binpatch::IPS_MAX_SIZE as u64 {
+                bail!("IPS patches can't patch past 16 MiB, use BPS or VCDIFF instead");
+            }
+            let mut source_buf = Vec::new();
+            source.take(target_size + 1).read_to_end(&mut source_buf)?;
+            let mut target_buf = Vec::new();
+            target.read_to_end(&mut target_buf)?;
+            patch.write_all(&Ips::diff(&source_buf, &target_buf)?.to_bytes())?;
+        }
+        DistPatchFormat::Bps => {
+            binpatch::create_bps(&mut source, &mut target, target_size, &moved, &mut patch)?
+        }
+        DistPatchFormat::Vcdiff => vcdiff::create(&mut source, &mut target, &moved, &mut patch)?,
+    }
+    patch.flush()?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DistArgs::parse_from(args);
+    let mut manifest: DistManifest = manifest::read_file(&args.manifest)?;
+    if let Some(version) = args.stamp {
+        manifest.version = version;
+    }
+    if let Some(base) = args.manifest.parent() {
+        manifest.resolve_paths(base);
+    }
+    std::fs::create_dir_all(&manifest.output_dir)
+        .with_context(|| format!("cannot create {}", manifest.output_dir.to_string_lossy()))?;
+
+    let symbols = match &manifest.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => Default::default(),
+    };
+    let patches = match &manifest.patches {
+        Some(path) => patch::parse_patch_list(&read_file(path)?, &symbols)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => Vec::new(),
+    };
+    let mut codes: Vec<GeckoCode> = Vec::new();
+    for path in &manifest.codes {
+        codes.extend(
+            gecko::parse_codes(&read_file(path)?)
+                .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        );
+    }
+
+    let mut artifacts = Vec::new();
+    if let Some(gci) = &manifest.gci {
+        let path = manifest.artifact_path("gci");
+        write_file(
+            &path,
+            build_gci(&manifest, gci).context("cannot build GCI")?,
+        )?;
+        artifacts.push(path);
+    }
+    if manifest.gct {
+        ensure!(
+            !patches.is_empty() || !codes.is_empty(),
+            "A GCT needs patches or codes"
+        );
+        let mut gct_codes = gecko::codes_from_patches(&patches).context("cannot build GCT")?;
+        gct_codes.extend(codes.iter().cloned());
+        let path = manifest.artifact_path("gct");
+        write_file(&path, gecko::write_gct(&gct_codes))?;
+        artifacts.push(path);
+    }
+    if let Some(ini) = &manifest.ini {
+        let mut out = String::new();
+        if !patches.is_empty() {
+            out.push_str(&dolphin_ini::write_patches(
+                &patches,
+                &ini.section,
+                ini.enable,
+            ));
+        }
+        if !codes.is_empty() {
+            out.push_str(&dolphin_ini::write_gecko_codes(&codes, ini.enable));
+        }
+        ensure!(!out.is_empty(), "An INI needs patches or codes");
+        let path = manifest.artifact_path("ini");
+        write_file(&path, out)?;
+        artifacts.push(path);
+    }
+    if let Some(iso_patch) = &manifest.iso_patch {
+        let path = manifest.artifact_path(iso_patch.format.extension());
+        build_iso_patch(iso_patch, &path).context("cannot build disc image patch")?;
+        artifacts.push(path);
+    }
+
+    for path in &artifacts {
+        println!("Wrote {}", path.to_string_lossy());
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::{doldiff, symbol_map};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct DolDiffArgs {
+    /// Path to original DOL file
+    old_dol: PathBuf,
+    /// Path to modified DOL file
+    new_dol: PathBuf,
+    /// Path to symbol map used to name changed functions
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DolDiffArgs::parse_from(args);
+    let old_dol = read_file(&args.old_dol)?;
+    let new_dol = read_file(&args.new_dol)?;
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)?,
+        None => Default::default(),
+    };
+
+    let diff = doldiff::doldiff(&old_dol, &new_dol)?;
+
+    for change in &diff.header_changes {
+        println!("{}: {:08X} -> {:08X}", change.field, change.old, change.new);
+    }
+    for change in &diff.changes {
+        print!(
+            "{:08X}-{:08X} {} ({:#x} bytes) in {}",
+            change.address,
+            change.address + change.size,
+            change.kind,
+            change.size,
+            change.section
+        );
+        if let Some(symbol) = symbols.find_enclosing(change.address) {
+            print!(" [{}+{:#x}]", symbol.name, change.address - symbol.address);
+        }
+        println!();
+    }
+
+    Ok(())
+}
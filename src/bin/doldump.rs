@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::dol::{self, DolHeader, DolSectionKind, Severity};
+use gamecube_tools::formats::Format;
+use gamecube_tools::listing::Listing;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct DolDumpArgs {
+    /// Path to input DOL file
+    input_dol: PathBuf,
+    /// Treat warnings as errors
+    #[arg(long)]
+    strict: bool,
+    /// Disassemble the code around the entry point
+    #[arg(short, long)]
+    disassemble: bool,
+    /// Also disassemble around this address, in hex (may be given more than once)
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    at: Vec<u32>,
+    /// Instructions to show either side of each address
+    #[arg(short = 'C', long, default_value_t = 4)]
+    context: u32,
+}
+
+fn parse_address(s: &str) -> anyhow::Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).with_context(|| format!("invalid address {s}"))
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DolDumpArgs::parse_from(args);
+    let input_dol = read_file(&args.input_dol)?;
+    let header = DolHeader::parse(&input_dol)?;
+
+    println!("Section  Offset    Address   Size");
+    for (kind, index, offset, address, size) in header.sections() {
+        println!(
+            "{:<8} {offset:08X}  {address:08X}  {size:08X}",
+            format!("{kind}{index}")
+        );
+    }
+    println!(
+        "bss      -         {:08X}  {:08X}",
+        header.bss_address.get(),
+        header.bss_size.get()
+    );
+    println!("Entry point: {:08X}", header.entry_point.get());
+
+    if args.disassemble || !args.at.is_empty() {
+        for (kind, index, offset, address, size) in header.sections() {
+            if kind != DolSectionKind::Text || size == 0 {
+                continue;
+            }
+            let Some(code) = input_dol.get(offset as usize..(offset as usize + size as usize))
+            else {
+                continue;
+            };
+            let mut listing = Listing::new(code, address);
+            if args.disassemble {
+                listing.note(header.entry_point.get(), "entry point");
+            }
+            for &at in &args.at {
+                listing.note(at, "requested address");
+            }
+            if listing.is_empty() {
+                continue;
+            }
+            println!();
+            println!("{kind}{index}:");
+            print!("{}", listing.render(args.context));
+        }
+    }
+
+    let issues = dol::validate(&input_dol)?;
+    if !issues.is_empty() {
+        println!();
+    }
+    for issue in &issues {
+        println!("{}: {}", issue.severity, issue.message);
+    }
+
+    let error_count = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error || args.strict)
+        .count();
+    if error_count > 0 {
+        bail!("DOL failed validation with {error_count} error(s)");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use gamecube_tools::{dolmap, symbol_map};
+
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum MapFormat {
+    /// `address:name` lines, as accepted by elf2rel
+    Lst,
+    /// Dolphin symbol map
+    Dolphin,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct DolMapArgs {
+    /// Path to input DOL file
+    input_dol: PathBuf,
+    /// Path to output symbol map
+    #[arg(short, long)]
+    output_map: Option<PathBuf>,
+    /// Output symbol map format
+    #[arg(long, value_enum, default_value_t = MapFormat::Lst)]
+    format: MapFormat,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DolMapArgs::parse_from(args);
+    let input_dol = read_file(&args.input_dol)?;
+    let extension = match args.format {
+        MapFormat::Lst => "lst",
+        MapFormat::Dolphin => "map",
+    };
+    let output_map_path = args
+        .output_map
+        .unwrap_or(args.input_dol.with_extension(extension));
+
+    let map = dolmap::dolmap(&input_dol)?;
+    let output = match args.format {
+        MapFormat::Lst => symbol_map::write_lst(&map),
+        MapFormat::Dolphin => symbol_map::write_dolphin_map(&map),
+    };
+
+    let mut output_file = File::create(output_map_path)?;
+    output_file.write_all(output.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::dol::Dol;
+use gamecube_tools::gdb::{GdbClient, REG_PC};
+use gamecube_tools::patch;
+use gamecube_tools::rel::Rel;
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Work with a game running in Dolphin", long_about = None)]
+struct DolphinArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write a REL, DOL, or patch list into the running game's memory through Dolphin's GDB
+    /// stub
+    Push {
+        /// Path to the REL, DOL, or patch list to write. Files are told apart by extension:
+        /// `.rel`, `.dol`, or anything else for a patch list.
+        input: PathBuf,
+        /// Address of Dolphin's GDB stub, as set by GDBPort in Dolphin.ini
+        #[arg(long, default_value = "localhost:2345")]
+        host: String,
+        /// Address to write a REL at
+        #[arg(short, long, value_parser = symbol_map::parse_int)]
+        address: Option<u32>,
+        /// Address of a REL's BSS, passed to `--call` as its second argument
+        #[arg(long, value_parser = symbol_map::parse_int)]
+        bss_address: Option<u32>,
+        /// Path to symbol map used to resolve `--call` and patch list addresses, in any format
+        /// mapconv reads
+        #[arg(short, long)]
+        symbol_map: Option<PathBuf>,
+        /// Function to call once the file is written, such as OSLink. A REL's address and BSS
+        /// address are passed as arguments.
+        #[arg(long)]
+        call: Option<String>,
+        /// Jump to a DOL's entry point once it's written
+        #[arg(long)]
+        jump: bool,
+        /// Leave the game stopped afterwards instead of resuming it
+        #[arg(long)]
+        stay_paused: bool,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+/// Write the file into memory, returning the arguments to pass to `--call`
+fn push_file(
+    client: &mut GdbClient,
+    input: &Path,
+    buf: &[u8],
+    address: Option<u32>,
+    bss_address: Option<u32>,
+    symbols: &SymbolMap,
+) -> anyhow::Result<Vec<u32>> {
+    let extension = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("rel") => {
+            let Some(address) = address else {
+                bail!("pass --address to write a REL");
+            };
+            let rel = Rel::parse(buf)?;
+            if rel.total_bss_size != 0 && bss_address.is_none() {
+                eprintln!(
+                    "Warning: REL has {:#x} bytes of BSS, but no --bss-address",
+                    rel.total_bss_size
+                );
+            }
+            client.write_memory(address, buf)?;
+            println!("Wrote REL {} at {address:08X}", rel.id);
+            Ok(vec![address, bss_address.unwrap_or(0)])
+        }
+        Some("dol") => {
+            let dol = Dol::parse(buf)?;
+            for section in &dol.sections {
+                client.write_memory(section.address, &section.data)?;
+                println!(
+                    "Wrote {} at {:08X}-{:08X}",
+                    section.name(),
+                    section.address,
+                    section.end_address()
+                );
+            }
+            Ok(Vec::new())
+        }
+        _ => {
+            let patches = patch::parse_patch_list(buf, symbols)?;
+            for write in patches.iter().flat_map(|p| &p.writes) {
+                client.write_memory(write.address, &write.value.to_be_bytes())?;
+            }
+            println!("Wrote {} patches", patches.len());
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DolphinArgs::parse_from(args);
+    match args.command {
+        Command::Push {
+            input,
+            host,
+            address,
+            bss_address,
+            symbol_map,
+            call,
+            jump,
+            stay_paused,
+        } => {
+            let buf = read_file(&input)?;
+            let symbols = match &symbol_map {
+                Some(path) => symbol_map::parse(&read_file(path)?)
+                    .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+                None => SymbolMap::default(),
+            };
+            let call = call.map(|expr| symbols.resolve(&expr)).transpose()?;
+
+            let mut client = GdbClient::connect(&host)?;
+            client.interrupt()?;
+            let call_args = push_file(&mut client, &input, &buf, address, bss_address, &symbols)
+                .with_context(|| format!("cannot push {}", input.to_string_lossy()))?;
+            if let Some(call) = call {
+                let result = client.call(call, &call_args)?;
+                println!("Called {call:08X}, which returned {result:#x}");
+            }
+            if jump {
+                let entry_point = Dol::parse(&buf).context("--jump needs a DOL")?.entry_point;
+                client.write_register(REG_PC, entry_point)?;
+                println!("Jumping to {entry_point:08X}");
+            }
+            if !stay_paused {
+                client.resume()?;
+            }
+        }
+    }
+    Ok(())
+}
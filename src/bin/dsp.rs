@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use gamecube_tools::dsp::Dsp;
+use gamecube_tools::wav::Wav;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Convert between WAV and DSP-ADPCM audio", long_about = None)]
+struct DspArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Encode a WAV as DSP-ADPCM, writing one `.dsp` per channel
+    Encode {
+        /// Path to input WAV
+        input: PathBuf,
+        /// Path to output DSP. Defaults to the input with a `.dsp` extension. With more than one
+        /// channel, `_L` and `_R` or the channel number is added to the name.
+        output: Option<PathBuf>,
+        /// First sample of the loop, overriding the WAV's loop
+        #[arg(long)]
+        loop_start: Option<u32>,
+        /// Last sample of the loop, inclusive. Defaults to the last sample.
+        #[arg(long, requires = "loop_start")]
+        loop_end: Option<u32>,
+        /// Don't loop, even if the WAV has a loop
+        #[arg(long, conflicts_with = "loop_start")]
+        no_loop: bool,
+    },
+    /// Decode DSPs to a WAV, one channel per DSP
+    Decode {
+        /// Paths to input DSPs
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Path to output WAV
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Path of one channel's DSP
+fn channel_path(output: &Path, channel: usize, channel_count: usize) -> PathBuf {
+    if channel_count == 1 {
+        return output.to_path_buf();
+    }
+    let suffix = match (channel_count, channel) {
+        (2, 0) => "L".to_string(),
+        (2, 1) => "R".to_string(),
+        _ => channel.to_string(),
+    };
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{stem}_{suffix}.dsp"))
+}
+
+fn encode(
+    input: &Path,
+    output: &Path,
+    loop_start: Option<u32>,
+    loop_end: Option<u32>,
+    no_loop: bool,
+) -> anyhow::Result<()> {
+    let wav = Wav::parse(&read_file(input)?)?;
+    let loop_points = match loop_start {
+        Some(start) => Some((
+            start,
+            loop_end.unwrap_or((wav.sample_count() as u32).saturating_sub(1)),
+        )),
+        None if no_loop => None,
+        None => wav.loop_points,
+    };
+    for (i, samples) in wav.channels.iter().enumerate() {
+        let dsp = Dsp::encode(samples, wav.sample_rate, loop_points)?;
+        let path = channel_path(output, i, wav.channels.len());
+        write_file(&path, dsp.to_bytes())?;
+        println!("Wrote {}", path.to_string_lossy());
+    }
+    if let Some((start, end)) = loop_points {
+        println!("Looping samples {start}-{end}");
+    }
+    Ok(())
+}
+
+fn decode(inputs: &[PathBuf], output: &Path) -> anyhow::Result<()> {
+    let mut wav = Wav::default();
+    for (i, input) in inputs.iter().enumerate() {
+        let dsp = Dsp::parse(&read_file(input)?)
+            .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+        if i == 0 {
+            wav.sample_rate = dsp.sample_rate;
+            wav.loop_points = dsp.loop_points;
+        } else {
+            ensure!(
+                dsp.sample_rate == wav.sample_rate
+                    && dsp.sample_count as usize == wav.sample_count(),
+                "{} doesn't have the same sample rate and length as {}",
+                input.to_string_lossy(),
+                inputs[0].to_string_lossy()
+            );
+        }
+        wav.channels.push(dsp.decode());
+    }
+    write_file(output, wav.to_bytes()?)
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = DspArgs::parse_from(args);
+    match args.command {
+        Command::Encode {
+            input,
+            output,
+            loop_start,
+            loop_end,
+            no_loop,
+        } => {
+            let output = output.unwrap_or_else(|| input.with_extension("dsp"));
+            if output == input {
+                bail!("Output would overwrite the input WAV");
+            }
+            encode(&input, &output, loop_start, loop_end, no_loop)
+                .with_context(|| format!("cannot encode {}", input.to_string_lossy()))?
+        }
+        Command::Decode { inputs, output } => decode(&inputs, &output)?,
+    }
+    Ok(())
+}
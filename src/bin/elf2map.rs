@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use gamecube_tools::elf2rel::RelVersion;
+use gamecube_tools::{elf2map, symbol_map};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Elf2MapArgs {
+    /// Path to input ELF file, either linked or a relocatable object as passed to elf2rel
+    input_elf: PathBuf,
+    /// Path to output symbol map
+    #[arg(short, long)]
+    output_map: Option<PathBuf>,
+    /// Address the REL built from a relocatable ELF is loaded at
+    #[arg(long, value_parser = symbol_map::parse_int)]
+    rel_base: Option<u32>,
+    /// REL file format version (1, 2, or 3) the relocatable ELF is built into
+    #[arg(long, default_value_t = 3)]
+    rel_version: u8,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Elf2MapArgs::parse_from(args);
+    let input_elf = read_file(&args.input_elf)?;
+    let output_map_path = args
+        .output_map
+        .unwrap_or(args.input_elf.with_extension("lst"));
+    let rel_version = RelVersion::try_from(args.rel_version)
+        .map_err(|_| anyhow!("Invalid REL version: {}", args.rel_version))?;
+
+    let map = elf2map::elf2map(&input_elf, args.rel_base, rel_version)
+        .with_context(|| format!("cannot parse {}", args.input_elf.to_string_lossy()))?;
+    std::fs::write(&output_map_path, symbol_map::write_lst(&map))
+        .with_context(|| format!("cannot write {}", output_map_path.to_string_lossy()))?;
+
+    Ok(())
+}
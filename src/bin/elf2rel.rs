@@ -2,29 +2,67 @@ use std::{
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use anyhow::anyhow;
-use anyhow::Context;
-use gamecube_tools::elf2rel::{self, RelVersion};
+use anyhow::{ensure, Context};
+use gamecube_tools::batch::{self, OutputNames};
+use gamecube_tools::depfile::{self, Rule};
+use gamecube_tools::diagnostic::{Diagnostic, MessageFormat, Severity};
+use gamecube_tools::elf2rel::{self, RelVersion, SectionRename};
+use gamecube_tools::elfcheck;
+use gamecube_tools::progress::ProgressBar;
+use gamecube_tools::symbol_map;
 
 use clap::Parser;
+use log::{Level, LevelFilter};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Elf2RelArgs {
-    /// Path to input ELF file
-    input_elf: PathBuf,
+    /// Paths to input ELF files, or glob patterns such as `build/*.elf`
+    #[arg(required = true)]
+    input_elfs: Vec<PathBuf>,
     /// Path to input symbol map
     input_symbol_map: PathBuf,
-    /// Path to output REL file
+    /// Path to output REL file. With several inputs, a template such as `rels/{stem}.rel`, where
+    /// `{stem}`, `{name}`, and `{dir}` come from each input's path.
     #[arg(short, long)]
-    output_rel: Option<PathBuf>,
+    output_rel: Option<String>,
+    /// Directory to write RELs to, named after their ELFs
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+    /// Module ID. With several inputs, each gets the next ID after the one before it.
     #[arg(long, default_value_t = 0x1000)]
     rel_id: u32,
     /// REL file format version (1, 2, or 3)
     #[arg(long, default_value_t = 3)]
     rel_version: u8,
+    /// Rename an input section before deciding whether to keep it, such as `.hooks=.text` to keep
+    /// a custom linker script section as code. May be given more than once.
+    #[arg(long, value_name = "FROM=TO", value_parser = SectionRename::parse)]
+    rename_section: Vec<SectionRename>,
+    /// Check the ELF for code and data the console can't run before converting it
+    #[arg(long)]
+    check: bool,
+    /// Fail instead of warning when the ELF defines a symbol that's also in the symbol map
+    #[arg(long)]
+    deny_duplicate_symbols: bool,
+    /// Explain the conversion's decisions, such as dropped sections. Repeat for more detail.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// How to print errors and warnings: `human`, or `json` for one JSON record per line on
+    /// stdout
+    #[arg(long, default_value = "human", value_parser = MessageFormat::parse)]
+    message_format: MessageFormat,
+    /// Inputs to convert at once. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Write a Makefile-style dependency file listing the inputs read, for Make and Ninja.
+    /// Defaults to beside each REL, with a `.d` extension.
+    #[arg(long, value_name = "PATH")]
+    emit_depfile: Option<Option<PathBuf>>,
 }
 
 fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
@@ -34,19 +72,137 @@ where
     std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
 }
 
+/// Show the library's log messages on stderr, more of them for each `-v`
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // Ignore a logger that's already installed, as when gctools runs this tool
+    let _ = env_logger::Builder::new()
+        .filter_level(level)
+        .format(|f, record| {
+            let level = match record.level() {
+                Level::Warn => "warning".to_string(),
+                level => level.as_str().to_ascii_lowercase(),
+            };
+            let module = record.module_path().unwrap_or_default();
+            let module = module.rsplit("::").next().unwrap_or(module);
+            writeln!(f, "{level}: {module}: {}", record.args())
+        })
+        .try_init();
+}
+
 fn main() -> anyhow::Result<()> {
-    let args = Elf2RelArgs::parse();
-    let input_elf = read_file(&args.input_elf)?;
-    let input_symbol_map = read_file(&args.input_symbol_map)?;
-    let output_rel_path = args
-        .output_rel
-        .unwrap_or(args.input_elf.with_extension("rel"));
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Elf2RelArgs::parse_from(args);
+    init_logging(args.verbose);
+    let inputs = batch::expand_globs(&args.input_elfs)?;
+    let outputs = OutputNames {
+        output: args.output_rel.clone(),
+        output_dir: args.output_dir.clone(),
+        extension: "rel",
+    };
+    let output_paths = outputs.paths(&inputs)?;
     let rel_version = RelVersion::try_from(args.rel_version)
         .map_err(|_| anyhow!("Invalid REL version: {}", args.rel_version))?;
+    let input_symbol_map = read_file(&args.input_symbol_map)?;
+    if let Some(output_dir) = &args.output_dir {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("cannot create {}", output_dir.to_string_lossy()))?;
+    }
+
+    let jobs: Vec<(u32, &PathBuf, &PathBuf)> = (args.rel_id..)
+        .zip(&inputs)
+        .zip(&output_paths)
+        .map(|((id, input), output)| (id, input, output))
+        .collect();
+    // Bars for conversions running in parallel would draw over each other
+    let show_progress = jobs.len() == 1;
+    let rules = Mutex::new(Vec::new());
+    let failures = batch::run_parallel(
+        &jobs,
+        args.jobs.unwrap_or_else(batch::default_jobs),
+        |&(id, input, output)| {
+            convert(
+                &args,
+                input,
+                output,
+                id,
+                rel_version,
+                &input_symbol_map,
+                show_progress,
+            )
+            .map(|()| {
+                let mut rule = Rule::new(output);
+                rule.input(input);
+                rule.input(&args.input_symbol_map);
+                rules.lock().unwrap().push(rule);
+            })
+            .inspect_err(|e| {
+                if args.message_format == MessageFormat::Json {
+                    args.message_format
+                        .emit(&Diagnostic::from_error(e).with_file(input));
+                }
+            })
+        },
+    );
+    let failures = failures
+        .into_iter()
+        .map(|(&(_, input, _), e)| (input.as_path(), e))
+        .collect();
+
+    if let Some(path) = &args.emit_depfile {
+        depfile::emit(path.as_deref(), rules.into_inner().unwrap())?;
+    }
+    batch::into_result(failures, inputs.len(), "ELFs")
+}
+
+fn convert(
+    args: &Elf2RelArgs,
+    input_elf_path: &Path,
+    output_rel_path: &Path,
+    rel_id: u32,
+    rel_version: RelVersion,
+    input_symbol_map: &[u8],
+    show_progress: bool,
+) -> anyhow::Result<()> {
+    let input_elf = read_file(input_elf_path)?;
+    let symbols = symbol_map::parse_lst(input_symbol_map)?;
+
+    let mut diagnostics =
+        elfcheck::duplicate_symbols(&input_elf, &symbols, args.deny_duplicate_symbols)?;
+    if args.check {
+        diagnostics.extend(elfcheck::check(&input_elf, Some(&symbols))?);
+    }
+    for diagnostic in diagnostics.iter().cloned() {
+        args.message_format
+            .emit(&diagnostic.with_file(input_elf_path));
+    }
+    ensure!(
+        diagnostics.iter().all(|d| d.severity != Severity::Error),
+        "{} failed checks",
+        input_elf_path.to_string_lossy()
+    );
 
-    let rel = elf2rel::elf2rel(&input_elf, &input_symbol_map, args.rel_id, rel_version)?;
+    let mut bar = show_progress.then(|| ProgressBar::counting("Relocating", "relocations"));
+    let rel = elf2rel::elf2rel_with_progress(
+        &input_elf,
+        input_symbol_map,
+        rel_id,
+        rel_version,
+        &args.rename_section,
+        &mut |done, total| bar.iter_mut().for_each(|bar| bar.update(done, total)),
+    )?;
+    bar.iter_mut().for_each(ProgressBar::finish);
 
-    let mut output_file = File::create(output_rel_path)?;
+    let mut output_file = File::create(output_rel_path)
+        .with_context(|| format!("cannot create {}", output_rel_path.to_string_lossy()))?;
     output_file.write_all(&rel)?;
 
     Ok(())
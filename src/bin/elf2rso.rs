@@ -0,0 +1,178 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{ensure, Context};
+use gamecube_tools::batch::{self, OutputNames};
+use gamecube_tools::depfile::{self, Rule};
+use gamecube_tools::diagnostic::{Diagnostic, MessageFormat, Severity};
+use gamecube_tools::elf2rso;
+use gamecube_tools::elfcheck;
+use gamecube_tools::progress::ProgressBar;
+
+use clap::Parser;
+use log::{Level, LevelFilter};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Elf2RsoArgs {
+    /// Paths to input ELF files, or glob patterns such as `build/*.elf`
+    #[arg(required = true)]
+    input_elfs: Vec<PathBuf>,
+    /// Path to output RSO file. With several inputs, a template such as `rsos/{stem}.rso`, where
+    /// `{stem}`, `{name}`, and `{dir}` come from each input's path.
+    #[arg(short, long)]
+    output_rso: Option<String>,
+    /// Directory to write RSOs to, named after their ELFs
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+    /// Module name stored in the RSO, for a single input. Defaults to the output file name.
+    #[arg(long)]
+    name: Option<String>,
+    /// Check the ELF for code and data the console can't run before converting it
+    #[arg(long)]
+    check: bool,
+    /// Explain the conversion's decisions, such as dropped sections. Repeat for more detail.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// How to print errors and warnings: `human`, or `json` for one JSON record per line on
+    /// stdout
+    #[arg(long, default_value = "human", value_parser = MessageFormat::parse)]
+    message_format: MessageFormat,
+    /// Inputs to convert at once. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Write a Makefile-style dependency file listing the inputs read, for Make and Ninja.
+    /// Defaults to beside each RSO, with a `.d` extension.
+    #[arg(long, value_name = "PATH")]
+    emit_depfile: Option<Option<PathBuf>>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+/// Show the library's log messages on stderr, more of them for each `-v`
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // Ignore a logger that's already installed, as when gctools runs this tool
+    let _ = env_logger::Builder::new()
+        .filter_level(level)
+        .format(|f, record| {
+            let level = match record.level() {
+                Level::Warn => "warning".to_string(),
+                level => level.as_str().to_ascii_lowercase(),
+            };
+            let module = record.module_path().unwrap_or_default();
+            let module = module.rsplit("::").next().unwrap_or(module);
+            writeln!(f, "{level}: {module}: {}", record.args())
+        })
+        .try_init();
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Elf2RsoArgs::parse_from(args);
+    init_logging(args.verbose);
+    let inputs = batch::expand_globs(&args.input_elfs)?;
+    let outputs = OutputNames {
+        output: args.output_rso.clone(),
+        output_dir: args.output_dir.clone(),
+        extension: "rso",
+    };
+    let output_paths = outputs.paths(&inputs)?;
+    ensure!(
+        args.name.is_none() || inputs.len() == 1,
+        "--name only applies to a single input"
+    );
+    if let Some(output_dir) = &args.output_dir {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("cannot create {}", output_dir.to_string_lossy()))?;
+    }
+
+    let jobs: Vec<(&PathBuf, &PathBuf)> = inputs.iter().zip(&output_paths).collect();
+    // Bars for conversions running in parallel would draw over each other
+    let show_progress = jobs.len() == 1;
+    let rules = Mutex::new(Vec::new());
+    let failures = batch::run_parallel(
+        &jobs,
+        args.jobs.unwrap_or_else(batch::default_jobs),
+        |&(input, output)| {
+            convert(&args, input, output, show_progress)
+                .map(|()| {
+                    let mut rule = Rule::new(output);
+                    rule.input(input);
+                    rules.lock().unwrap().push(rule);
+                })
+                .inspect_err(|e| {
+                    if args.message_format == MessageFormat::Json {
+                        args.message_format
+                            .emit(&Diagnostic::from_error(e).with_file(input));
+                    }
+                })
+        },
+    );
+    let failures = failures
+        .into_iter()
+        .map(|(&(input, _), e)| (input.as_path(), e))
+        .collect();
+
+    if let Some(path) = &args.emit_depfile {
+        depfile::emit(path.as_deref(), rules.into_inner().unwrap())?;
+    }
+    batch::into_result(failures, inputs.len(), "ELFs")
+}
+
+fn convert(
+    args: &Elf2RsoArgs,
+    input_elf_path: &Path,
+    output_rso_path: &Path,
+    show_progress: bool,
+) -> anyhow::Result<()> {
+    let input_elf = read_file(input_elf_path)?;
+    let name = args.name.clone().unwrap_or_else(|| {
+        output_rso_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+
+    if args.check {
+        let diagnostics = elfcheck::check(&input_elf, None)?;
+        for diagnostic in diagnostics.iter().cloned() {
+            args.message_format
+                .emit(&diagnostic.with_file(input_elf_path));
+        }
+        ensure!(
+            diagnostics.iter().all(|d| d.severity != Severity::Error),
+            "{} failed checks",
+            input_elf_path.to_string_lossy()
+        );
+    }
+
+    let mut bar = show_progress.then(|| ProgressBar::counting("Relocating", "relocations"));
+    let rso = elf2rso::elf2rso_with_progress(&input_elf, &name, &mut |done, total| {
+        bar.iter_mut().for_each(|bar| bar.update(done, total))
+    })?;
+    bar.iter_mut().for_each(ProgressBar::finish);
+
+    let mut output_file = File::create(output_rso_path)
+        .with_context(|| format!("cannot create {}", output_rso_path.to_string_lossy()))?;
+    output_file.write_all(&rso)?;
+
+    Ok(())
+}
@@ -2,55 +2,226 @@ use std::{
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use anyhow::Context;
-use gamecube_tools::gcipack;
+use anyhow::{anyhow, ensure};
+use gamecube_tools::depfile::{self, Rule};
+use gamecube_tools::diagnostic::{Diagnostic, MessageFormat};
+use gamecube_tools::gcipack::{
+    GciPackConfig, GciPackError, GciPermissions, IconSpeed, ImageFormat, ImageKind, TextEncoding,
+    XorKey,
+};
+use gamecube_tools::{gcipack, manifest, memcard};
 
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct GciPackArgs {
-    /// The payload to store inside the GCI
-    input: PathBuf,
+    /// The payload to store inside the GCI, or a directory whose files are bundled into it
+    #[arg(required_unless_present = "manifest")]
+    input: Option<PathBuf>,
     /// The internal name of the GCI file
-    file_name: String,
+    #[arg(required_unless_present = "manifest")]
+    file_name: Option<String>,
     /// Game name
-    title: String,
+    #[arg(required_unless_present = "manifest")]
+    title: Option<String>,
     /// File description
-    description: String,
-    /// Path to banner image
-    banner: PathBuf,
-    /// Path to icon image
-    icon: PathBuf,
+    #[arg(required_unless_present = "manifest")]
+    description: Option<String>,
+    /// Path to banner image: a 96x32 PNG or raw RGB5A3 data. Left out with --no-banner.
+    #[arg(required_unless_present_any = ["manifest", "no_banner"])]
+    banner: Option<PathBuf>,
+    /// Path to icon image: a 32x32 PNG, an animated PNG of up to 8 frames, or raw RGB5A3 data
+    #[arg(required_unless_present = "manifest")]
+    icon: Option<PathBuf>,
     /// Six character gamecode
-    gamecode: String,
+    #[arg(required_unless_present_any = ["manifest", "no_banner"])]
+    gamecode: Option<String>,
+    /// Pack a save without a banner, as some games' saves are, leaving out the banner argument
+    #[arg(long)]
+    no_banner: bool,
+    /// Read everything above and the packing options below from a TOML or JSON manifest instead
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "input", "no_banner", "icon_frame", "icon_speed", "banner_format", "icon_format", "encoding",
+            "timestamp", "xor_key", "private", "no_copy", "no_move", "copy_times",
+        ]
+    )]
+    manifest: Option<PathBuf>,
+    /// Another frame of an animated icon, in the same formats as the icon (repeatable)
+    #[arg(long, value_name = "PATH")]
+    icon_frame: Vec<PathBuf>,
+    /// How long each icon frame is shown: `fast`, `medium`, or `slow` (4, 8, or 12 video
+    /// frames). Defaults to the closest to an animated PNG's frame delays, or `slow`.
+    #[arg(long, value_parser = IconSpeed::parse)]
+    icon_speed: Option<IconSpeed>,
+    /// Format to store the banner in: `rgb5a3`, or `ci8` for a 256 color palette at 3584 bytes
+    /// instead of 6144
+    #[arg(long, value_parser = ImageFormat::parse)]
+    banner_format: Option<ImageFormat>,
+    /// Format to store the icon in: `rgb5a3`, or `ci8` for a 256 color palette shared by every
+    /// frame
+    #[arg(long, value_parser = ImageFormat::parse)]
+    icon_format: Option<ImageFormat>,
+    /// Encoding of the title and description: `ascii`, or `shift-jis` for saves on Japanese
+    /// consoles, whose memory card browser shows them in Shift-JIS
+    #[arg(long, value_parser = TextEncoding::parse)]
+    encoding: Option<TextEncoding>,
+    /// Last modified time to stamp the GCI with, as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, or
+    /// seconds since 2000-01-01. Defaults to SOURCE_DATE_EPOCH if it's set, otherwise the current
+    /// time.
+    #[arg(long, value_parser = memcard::parse_timestamp)]
+    timestamp: Option<u32>,
+    /// Only let the game with the save's gamecode open it
+    #[arg(long)]
+    private: bool,
+    /// Mark the save as not to be copied to another card
+    #[arg(long)]
+    no_copy: bool,
+    /// Mark the save as not to be moved to another card
+    #[arg(long)]
+    no_move: bool,
+    /// Times the save has been copied, as the header's copy counter records
+    #[arg(long, default_value_t = 0)]
+    copy_times: u8,
+    /// Obfuscate the payload by XORing it with this key, given in hex, for loaders that expect it
+    #[arg(long, value_name = "HEX", value_parser = XorKey::parse)]
+    xor_key: Option<XorKey>,
+    /// How to print errors: `human`, or `json` for one JSON record per line on stdout
+    #[arg(long, default_value = "human", value_parser = MessageFormat::parse)]
+    message_format: MessageFormat,
+    /// Write a Makefile-style dependency file listing the inputs read, for Make and Ninja.
+    /// Defaults to beside the GCI, with a `.d` extension.
+    #[arg(long, value_name = "PATH")]
+    emit_depfile: Option<Option<PathBuf>>,
+}
+
+impl GciPackArgs {
+    /// The packing config, from the manifest if there is one or else from the arguments
+    fn config(&self) -> anyhow::Result<GciPackConfig> {
+        if let Some(path) = &self.manifest {
+            let mut config: GciPackConfig = manifest::read_file(path)?;
+            if let Some(base) = path.parent() {
+                config.resolve_paths(base);
+            }
+            return Ok(config);
+        }
+        // Every positional argument is required without a manifest. Without a banner, the icon
+        // and gamecode are given where the banner and icon would be.
+        let (banner, icon, gamecode) = if self.no_banner {
+            ensure!(
+                self.gamecode.is_none(),
+                "Leave out the banner argument with --no-banner"
+            );
+            let gamecode = self.icon.clone().unwrap().into_os_string().into_string();
+            let gamecode = gamecode.map_err(|_| anyhow!("Gamecode isn't valid UTF-8"))?;
+            (None, self.banner.clone().unwrap(), gamecode)
+        } else {
+            let icon = self.icon.clone().unwrap();
+            (self.banner.clone(), icon, self.gamecode.clone().unwrap())
+        };
+        Ok(GciPackConfig {
+            input: self.input.clone().unwrap(),
+            output: None,
+            file_name: self.file_name.clone().unwrap(),
+            title: self.title.clone().unwrap(),
+            description: self.description.clone().unwrap(),
+            encoding: self.encoding.unwrap_or_default(),
+            banner,
+            icon,
+            icon_frames: self.icon_frame.clone(),
+            icon_speed: self.icon_speed,
+            banner_format: self.banner_format.unwrap_or_default(),
+            icon_format: self.icon_format.unwrap_or_default(),
+            gamecode,
+            xor_key: self.xor_key.clone(),
+            timestamp: self.timestamp,
+            permissions: GciPermissions {
+                public: !self.private,
+                no_copy: self.no_copy,
+                no_move: self.no_move,
+                copy_times: self.copy_times,
+            },
+        })
+    }
 }
 
-fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
-where
-    P: AsRef<Path>,
-{
-    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+/// Time to stamp GCIs with when none is given: `SOURCE_DATE_EPOCH` if it's set, so rebuilds are
+/// byte-identical, otherwise the current time
+fn default_timestamp() -> anyhow::Result<u32> {
+    let unix_time = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.trim().parse().map_err(|_| {
+            anyhow!("SOURCE_DATE_EPOCH must be a number of seconds since 1970, got '{epoch}'")
+        })?,
+        Err(_) => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    Ok(gcipack::card_time(unix_time))
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = GciPackArgs::parse();
-    let input = read_file(&args.input)?;
-    let banner = read_file(&args.banner)?;
-    let icon = read_file(&args.icon)?;
-    let gci = gcipack::gcipack(
-        &input,
-        &args.file_name,
-        &args.title,
-        &args.description,
-        &banner,
-        &icon,
-        &args.gamecode,
-    )?;
-    let mut output_file = File::create(args.input.with_extension("gci"))?;
-    output_file.write_all(&gci)?;
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = GciPackArgs::parse_from(args);
+    let emit = |e: &anyhow::Error, file: &Path| {
+        if args.message_format == MessageFormat::Json {
+            args.message_format
+                .emit(&Diagnostic::from_error(e).with_file(file));
+        }
+    };
+    // Only reading a manifest, or a banner given with --no-banner, can fail here
+    let config = args.config().inspect_err(|e| {
+        emit(
+            e,
+            args.manifest.as_deref().or(args.input.as_deref()).unwrap(),
+        )
+    })?;
+    pack(&config, &args).inspect_err(|e| {
+        // Point image errors at the image, and the rest at the payload being packed
+        let file = match e.downcast_ref::<GciPackError>() {
+            Some(GciPackError::ImageInvalidSize {
+                kind: ImageKind::Banner,
+                ..
+            }) => config.banner.as_deref().unwrap_or(&config.input),
+            Some(GciPackError::ImageInvalidSize {
+                kind: ImageKind::Icon,
+                ..
+            }) => &config.icon,
+            _ => &config.input,
+        };
+        emit(e, file);
+    })
+}
+
+fn pack(config: &GciPackConfig, args: &GciPackArgs) -> anyhow::Result<()> {
+    let mut config = config.clone();
+    if config.timestamp.is_none() {
+        config.timestamp = Some(default_timestamp()?);
+    }
+    let packed = gcipack::pack(&config)?;
+    let output_path = config.output_path();
+    let mut output_file = File::create(&output_path)?;
+    output_file.write_all(&packed.gci)?;
+
+    if let Some(path) = &args.emit_depfile {
+        let mut rule = Rule::new(&output_path);
+        for path in &packed.inputs {
+            rule.input(path);
+        }
+        if let Some(path) = &args.manifest {
+            rule.input(path);
+        }
+        depfile::emit(path.as_deref(), vec![rule])?;
+    }
 
     Ok(())
 }
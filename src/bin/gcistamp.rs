@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context};
+use gamecube_tools::formats::gci::GciHeader;
+use gamecube_tools::memcard;
+use zerocopy::FromBytes;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Set the last modified time of GCIs, so memory card menus list them in a fixed order",
+    long_about = None
+)]
+struct GciStampArgs {
+    /// GCI files, or directories whose `.gci` files to update
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Time to give the first file, as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, or seconds since
+    /// 2000-01-01
+    #[arg(short, long, value_parser = memcard::parse_timestamp)]
+    time: u32,
+    /// Seconds to add for each following file, in order of file name, so they sort in that order
+    #[arg(short, long, default_value_t = 0)]
+    step: u32,
+    /// Count down instead of up, so the first file by name is the newest
+    #[arg(short, long)]
+    reverse: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn is_gci(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gci"))
+}
+
+fn gci_files(inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let entries = std::fs::read_dir(input)
+            .with_context(|| format!("cannot read {}", input.to_string_lossy()))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.extend(entries.into_iter().filter(|p| p.is_file() && is_gci(p)));
+    }
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()).then_with(|| a.cmp(b)));
+    files.dedup();
+    ensure!(!files.is_empty(), "No GCI files to update");
+    Ok(files)
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = GciStampArgs::parse_from(args);
+    let files = gci_files(&args.inputs)?;
+
+    let span = args.step as u64 * (files.len() as u64 - 1);
+    ensure!(
+        if args.reverse {
+            span <= args.time as u64
+        } else {
+            args.time as u64 + span <= u32::MAX as u64
+        },
+        "{} files {} seconds apart don't fit in the card's timestamps",
+        files.len(),
+        args.step
+    );
+
+    for (i, path) in files.iter().enumerate() {
+        let offset = args.step * i as u32;
+        let time = if args.reverse {
+            args.time - offset
+        } else {
+            args.time + offset
+        };
+        let mut gci = read_file(path)?;
+        let (header, _) = GciHeader::mut_from_prefix(&mut gci)
+            .map_err(|_| anyhow!("{} is too small to be a GCI", path.to_string_lossy()))?;
+        header.last_modified = time.into();
+        std::fs::write(path, &gci)
+            .with_context(|| format!("cannot write {}", path.to_string_lossy()))?;
+        println!(
+            "{}  {}",
+            memcard::format_timestamp(time),
+            path.to_string_lossy()
+        );
+    }
+
+    Ok(())
+}
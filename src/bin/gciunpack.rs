@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use gamecube_tools::gcipack::XorKey;
+use gamecube_tools::{bundle, gcipack};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Extract the payload of a GCI made by gcipack, unbundling it if it holds several files",
+    long_about = None
+)]
+struct GciUnpackArgs {
+    /// Path to input GCI file
+    input_gci: PathBuf,
+    /// Where to write the payload: a file, or a directory for a bundle of files. Defaults to the
+    /// GCI's path with a `.bin` extension, or without one for a bundle.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Key the payload was XORed with by `gcipack --xor-key`, in hex
+    #[arg(long, value_name = "HEX", value_parser = XorKey::parse)]
+    xor_key: Option<XorKey>,
+    /// List the files in a bundle instead of extracting them
+    #[arg(short, long)]
+    list: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("cannot create {}", parent.to_string_lossy()))?;
+    }
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = GciUnpackArgs::parse_from(args);
+    let gci = read_file(&args.input_gci)?;
+    let mut payload = gcipack::gciunpack(&gci)
+        .with_context(|| format!("cannot unpack {}", args.input_gci.to_string_lossy()))?
+        .to_vec();
+    if let Some(key_size) = gcipack::xor_key_size(&gci) {
+        let Some(key) = &args.xor_key else {
+            bail!("The payload is XORed with a {key_size}-byte key; give it with --xor-key");
+        };
+        ensure!(
+            key.0.len() == key_size,
+            "The payload is XORed with a {key_size}-byte key, not {} bytes",
+            key.0.len()
+        );
+        gcipack::xor(&mut payload, &key.0);
+    }
+    let payload = payload.as_slice();
+
+    if !bundle::is_bundle(payload) {
+        if args.list {
+            println!("{:>8}  (single file)", payload.len());
+            return Ok(());
+        }
+        let output = args
+            .output
+            .unwrap_or_else(|| args.input_gci.with_extension("bin"));
+        write_file(&output, payload)?;
+        println!("Wrote {}", output.to_string_lossy());
+        return Ok(());
+    }
+
+    let files = bundle::unpack(payload)?;
+    if args.list {
+        for (name, data) in &files {
+            println!("{:>8}  {name}", data.len());
+        }
+        return Ok(());
+    }
+    let output = args
+        .output
+        .unwrap_or_else(|| args.input_gci.with_extension(""));
+    for (name, data) in &files {
+        write_file(&output.join(name), data)?;
+    }
+    println!(
+        "Wrote {} files to {}",
+        files.len(),
+        output.to_string_lossy()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,334 @@
+// Every tool in one binary, for installing a single artifact. Each tool's source is included as a
+// module, so its own main goes unused here.
+#![allow(dead_code)]
+
+use std::ffi::OsString;
+
+use clap::{Arg, Command};
+
+#[path = "arc.rs"]
+mod arc;
+#[path = "ast.rs"]
+mod ast;
+#[path = "binpatch.rs"]
+mod binpatch;
+#[path = "bnrpack.rs"]
+mod bnrpack;
+#[path = "bnrunpack.rs"]
+mod bnrunpack;
+#[path = "bti.rs"]
+mod bti;
+#[path = "gctools/build.rs"]
+mod build;
+#[path = "crashreport.rs"]
+mod crashreport;
+#[path = "dist.rs"]
+mod dist;
+#[path = "doldiff.rs"]
+mod doldiff;
+#[path = "doldump.rs"]
+mod doldump;
+#[path = "dolmap.rs"]
+mod dolmap;
+#[path = "dolphin.rs"]
+mod dolphin;
+#[path = "dsp.rs"]
+mod dsp;
+#[path = "elf2map.rs"]
+mod elf2map;
+#[path = "elf2rel.rs"]
+mod elf2rel;
+#[path = "elf2rso.rs"]
+mod elf2rso;
+#[path = "gcipack.rs"]
+mod gcipack;
+#[path = "gcistamp.rs"]
+mod gcistamp;
+#[path = "gciunpack.rs"]
+mod gciunpack;
+#[path = "gecko.rs"]
+mod gecko;
+#[path = "hookgen.rs"]
+mod hookgen;
+#[path = "isotool.rs"]
+mod isotool;
+#[path = "mapconv.rs"]
+mod mapconv;
+#[path = "mapdiff.rs"]
+mod mapdiff;
+#[path = "mapheader.rs"]
+mod mapheader;
+#[path = "mapmerge.rs"]
+mod mapmerge;
+#[path = "mc.rs"]
+mod mc;
+#[path = "objdiffreport.rs"]
+mod objdiffreport;
+#[path = "patch2ini.rs"]
+mod patch2ini;
+#[path = "patchc.rs"]
+mod patchc;
+#[path = "rel2map.rs"]
+mod rel2map;
+#[path = "reldeps.rs"]
+mod reldeps;
+#[path = "reldump.rs"]
+mod reldump;
+#[path = "rellink.rs"]
+mod rellink;
+#[path = "relsplit.rs"]
+mod relsplit;
+#[path = "relstats.rs"]
+mod relstats;
+#[path = "reltool.rs"]
+mod reltool;
+#[path = "send.rs"]
+mod send;
+#[path = "stackwalk.rs"]
+mod stackwalk;
+#[path = "swisspack.rs"]
+mod swisspack;
+#[path = "symbolicate.rs"]
+mod symbolicate;
+#[path = "thp.rs"]
+mod thp;
+#[path = "tpl.rs"]
+mod tpl;
+#[path = "yay0.rs"]
+mod yay0;
+#[path = "yaz0.rs"]
+mod yaz0;
+
+type Run = fn(Vec<OsString>) -> anyhow::Result<()>;
+
+const TOOLS: &[(&str, &str, Run)] = &[
+    ("arc", "Unpack and repack RARC archives", |args| {
+        arc::run(args)
+    }),
+    (
+        "ast",
+        "Convert between WAV and AST streamed audio",
+        |args| ast::run(args),
+    ),
+    (
+        "binpatch",
+        "Apply and create binary patches for DOLs and disc images",
+        |args| binpatch::run(args),
+    ),
+    (
+        "bnrpack",
+        "Build an opening.bnr from a PNG and strings",
+        |args| bnrpack::run(args),
+    ),
+    (
+        "bnrunpack",
+        "Decode an opening.bnr into a PNG and metadata",
+        |args| bnrunpack::run(args),
+    ),
+    (
+        "build",
+        "Build a project's ELF into a REL and GCI, as described by its gctools.toml",
+        |args| build::run(args),
+    ),
+    ("bti", "Convert between PNGs and BTI textures", |args| {
+        bti::run(args)
+    }),
+    (
+        "crashreport",
+        "Turn a crash screen or RAM dump into a symbolized crash report",
+        |args| crashreport::run(args),
+    ),
+    (
+        "dist",
+        "Build a mod's GCI, GCT, Dolphin INI, and disc image patch from a manifest",
+        |args| dist::run(args),
+    ),
+    ("doldiff", "Compare two DOLs by load address", |args| {
+        doldiff::run(args)
+    }),
+    ("doldump", "Print and check a DOL's section table", |args| {
+        doldump::run(args)
+    }),
+    (
+        "dolmap",
+        "Generate a skeleton symbol map for a DOL",
+        |args| dolmap::run(args),
+    ),
+    ("dolphin", "Work with a game running in Dolphin", |args| {
+        dolphin::run(args)
+    }),
+    ("dsp", "Convert between WAV and DSP-ADPCM audio", |args| {
+        dsp::run(args)
+    }),
+    (
+        "elf2map",
+        "Write the symbols defined in an ELF as a symbol map",
+        |args| elf2map::run(args),
+    ),
+    ("elf2rel", "Build a REL from an ELF", |args| {
+        elf2rel::run(args)
+    }),
+    ("elf2rso", "Build an RSO from an ELF", |args| {
+        elf2rso::run(args)
+    }),
+    (
+        "gcipack",
+        "Generate a GCI savefile containing a file",
+        |args| gcipack::run(args),
+    ),
+    ("gcistamp", "Set the last modified time of GCIs", |args| {
+        gcistamp::run(args)
+    }),
+    (
+        "gciunpack",
+        "Extract the payload of a GCI made by gcipack",
+        |args| gciunpack::run(args),
+    ),
+    (
+        "gecko",
+        "Compile, decompile, and check Gecko codes",
+        |args| gecko::run(args),
+    ),
+    (
+        "hookgen",
+        "Generate branch patches and trampolines for function hooks",
+        |args| hookgen::run(args),
+    ),
+    (
+        "isotool",
+        "Inspect, extract, and rebuild disc images",
+        |args| isotool::run(args),
+    ),
+    ("mapconv", "Convert symbol maps between formats", |args| {
+        mapconv::run(args)
+    }),
+    ("mapdiff", "Compare two symbol maps", |args| {
+        mapdiff::run(args)
+    }),
+    (
+        "mapheader",
+        "Generate a C header from a symbol map",
+        |args| mapheader::run(args),
+    ),
+    (
+        "mapmerge",
+        "Merge symbol maps, reporting conflicts",
+        |args| mapmerge::run(args),
+    ),
+    (
+        "mc",
+        "Inspect raw memory card images and copy saves between them",
+        |args| mc::run(args),
+    ),
+    (
+        "objdiffreport",
+        "Compare a reference DOL or REL with a rebuilt one for objdiff",
+        |args| objdiffreport::run(args),
+    ),
+    (
+        "patch2ini",
+        "Convert a patch list to Dolphin INI patches",
+        |args| patch2ini::run(args),
+    ),
+    (
+        "patchc",
+        "Compile a patch list to DOL edits, Gecko codes, Dolphin INI patches, or C",
+        |args| patchc::run(args),
+    ),
+    (
+        "rel2map",
+        "Write a Dolphin symbol map for a REL at its load address",
+        |args| rel2map::run(args),
+    ),
+    ("reldeps", "Print the import graph between RELs", |args| {
+        reldeps::run(args)
+    }),
+    (
+        "reldump",
+        "Print a REL's header and imports, and disassemble its relocation sites",
+        |args| reldump::run(args),
+    ),
+    (
+        "rellink",
+        "Link RELs the way OSLink does, writing the relocated images",
+        |args| rellink::run(args),
+    ),
+    (
+        "relsplit",
+        "Split a REL's sections into binary files",
+        |args| relsplit::run(args),
+    ),
+    (
+        "relstats",
+        "Report relocation counts and DolphinNop runs for RELs",
+        |args| relstats::run(args),
+    ),
+    ("reltool", "Edit prebuilt RELs", |args| reltool::run(args)),
+    (
+        "send",
+        "Send a DOL or REL to a loader on a real GameCube",
+        |args| send::run(args),
+    ),
+    (
+        "stackwalk",
+        "Print a symbolized backtrace by walking the stack in a RAM dump",
+        |args| stackwalk::run(args),
+    ),
+    (
+        "swisspack",
+        "Lay out a DOL on an SD card the way Swiss expects",
+        |args| swisspack::run(args),
+    ),
+    (
+        "symbolicate",
+        "Print the symbol and offset of RAM addresses",
+        |args| symbolicate::run(args),
+    ),
+    ("thp", "Extract and rebuild THP movies", |args| {
+        thp::run(args)
+    }),
+    ("tpl", "Convert between PNGs and TPL textures", |args| {
+        tpl::run(args)
+    }),
+    ("yay0", "Compress or decompress Yay0 data", |args| {
+        yay0::run(args)
+    }),
+    ("yaz0", "Compress or decompress Yaz0 data", |args| {
+        yaz0::run(args)
+    }),
+];
+
+fn main() -> anyhow::Result<()> {
+    let command = Command::new("gctools")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("GameCube modding tools. Run `gctools <tool> --help` for each tool's options.")
+        .subcommand_value_name("TOOL")
+        .subcommand_help_heading("Tools")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands(TOOLS.iter().map(|&(name, about, _)| {
+            // Arguments, including --help, are passed through to the tool to parse
+            Command::new(name)
+                .about(about)
+                .disable_help_flag(true)
+                .disable_version_flag(true)
+                .arg(
+                    Arg::new("args")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(OsString)),
+                )
+        }));
+    let matches = command.get_matches();
+    let (name, matches) = matches.subcommand().unwrap();
+    let &(_, _, run) = TOOLS.iter().find(|&&(n, _, _)| n == name).unwrap();
+    let args = matches
+        .get_many::<OsString>("args")
+        .into_iter()
+        .flatten()
+        .cloned();
+    run(std::iter::once(OsString::from(format!("gctools {name}")))
+        .chain(args)
+        .collect())
+}
@@ -0,0 +1,353 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use gamecube_tools::depfile::{self, Rule};
+use gamecube_tools::diagnostic::{Diagnostic, Severity};
+use gamecube_tools::elf2rel::{self, RelVersion, SectionRename};
+use gamecube_tools::elfcheck;
+use gamecube_tools::gcipack::{GciImage, GciPackOptions, IconFrame};
+use gamecube_tools::gdb::GdbClient;
+use gamecube_tools::progress::ProgressBar;
+use gamecube_tools::project::{Project, ProjectGci, ProjectPush, PROJECT_FILE_NAME};
+use gamecube_tools::{gcimage, gcipack, manifest, symbol_map};
+
+use clap::Parser;
+use log::{Level, LevelFilter};
+
+const GCI_HEADER_SIZE: usize = 0x40;
+const CARD_BLOCK_SIZE: usize = 0x2000;
+/// User blocks on the smallest official memory card, the Memory Card 59
+const SMALLEST_CARD_BLOCKS: usize = 59;
+/// How often `--watch` checks the inputs for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Build a project's ELF into a REL and GCI, as described by its project file",
+    long_about = None
+)]
+struct BuildArgs {
+    /// Path to the TOML or JSON project file
+    #[arg(default_value = PROJECT_FILE_NAME)]
+    project: PathBuf,
+    /// Keep running, and rebuild whenever the project file or one of its inputs changes
+    #[arg(long)]
+    watch: bool,
+    /// Write the REL into the game running in Dolphin after each build, as set up in the
+    /// project's `[push]` section
+    #[arg(long)]
+    push: bool,
+    /// Explain the conversion's decisions, such as dropped sections. Repeat for more detail.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Write a Makefile-style dependency file listing the inputs read, for Make and Ninja.
+    /// Defaults to beside the REL, with a `.d` extension.
+    #[arg(long, value_name = "PATH")]
+    emit_depfile: Option<Option<PathBuf>>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("cannot create {}", parent.to_string_lossy()))?;
+    }
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Read an image, converting it to RGB5A3 if it's a PNG
+fn read_image(path: &Path, width: usize, height: usize) -> anyhow::Result<Vec<u8>> {
+    let buf = read_file(path)?;
+    if !buf.starts_with(b"\x89PNG") {
+        return Ok(buf);
+    }
+    let (rgba, w, h) = gcimage::read_png(&buf)
+        .with_context(|| format!("cannot decode {}", path.to_string_lossy()))?;
+    ensure!(
+        (w, h) == (width, height),
+        "{} must be {width}x{height}, got {w}x{h}",
+        path.to_string_lossy()
+    );
+    Ok(gcimage::encode_rgb5a3(&rgba, width, height))
+}
+
+/// Diagnostics from every step, printed as they come and tallied for the end of the build
+#[derive(Default)]
+struct Diagnostics {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Diagnostics {
+    fn report(&mut self, step: &str, diagnostic: &Diagnostic) {
+        match diagnostic.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        eprintln!("{step}: {diagnostic}");
+    }
+
+    fn warn(&mut self, step: &str, code: &'static str, message: String) {
+        self.report(step, &Diagnostic::new(Severity::Warning, code, message));
+    }
+
+    fn error(&mut self, step: &str, code: &'static str, message: String) {
+        self.report(step, &Diagnostic::new(Severity::Error, code, message));
+    }
+
+    /// Stop the build if a step reported errors
+    fn check(&self, step: &str) -> anyhow::Result<()> {
+        if self.errors > 0 {
+            bail!("{step} reported {} error(s)", self.errors);
+        }
+        Ok(())
+    }
+}
+
+/// Time to stamp GCIs with when none is given: `SOURCE_DATE_EPOCH` if it's set, so rebuilds are
+/// byte-identical, otherwise the current time
+fn default_timestamp() -> anyhow::Result<u32> {
+    let unix_time = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.trim().parse().map_err(|_| {
+            anyhow!("SOURCE_DATE_EPOCH must be a number of seconds since 1970, got '{epoch}'")
+        })?,
+        Err(_) => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    Ok(gcipack::card_time(unix_time))
+}
+
+fn build_gci(
+    gci: &ProjectGci,
+    rel: &[u8],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Vec<u8>> {
+    let banner = gci
+        .banner
+        .as_ref()
+        .map(|path| read_image(path, 96, 32))
+        .transpose()?;
+    let icon = read_image(&gci.icon, 32, 32)?;
+    let timestamp = match gci.timestamp {
+        Some(timestamp) => timestamp,
+        None => default_timestamp()?,
+    };
+    let out = gcipack::gcipack(
+        rel,
+        banner.as_deref().map(GciImage::Rgb5a3),
+        &[IconFrame::still(&icon)],
+        &GciPackOptions {
+            file_name: &gci.file_name,
+            title: &gci.title,
+            description: &gci.description,
+            encoding: gci.encoding,
+            gamecode: &gci.gamecode,
+            timestamp,
+            permissions: gci.permissions.clone(),
+        },
+    )?;
+
+    let blocks = (out.len() - GCI_HEADER_SIZE) / CARD_BLOCK_SIZE;
+    if let Some(max_blocks) = gci.max_blocks
+        && blocks > max_blocks as usize
+    {
+        diagnostics.error(
+            "gcipack",
+            "gci-over-block-limit",
+            format!("GCI takes {blocks} blocks, more than the project's limit of {max_blocks}"),
+        );
+    } else if blocks > SMALLEST_CARD_BLOCKS {
+        diagnostics.warn(
+            "gcipack",
+            "gci-over-card-size",
+            format!(
+                "GCI takes {blocks} blocks, which won't fit on a Memory Card 59 \
+                 ({SMALLEST_CARD_BLOCKS} blocks)"
+            ),
+        );
+    }
+    diagnostics.check("gcipack")?;
+    Ok(out)
+}
+
+fn push_rel(push: &ProjectPush, rel: &[u8], symbol_map_path: &Path) -> anyhow::Result<()> {
+    let call = match &push.call {
+        Some(expr) => {
+            let symbols = symbol_map::parse(&read_file(symbol_map_path)?)
+                .with_context(|| format!("cannot parse {}", symbol_map_path.to_string_lossy()))?;
+            Some(symbols.resolve(expr)?)
+        }
+        None => None,
+    };
+    let mut client = GdbClient::connect(&push.host)?;
+    client.interrupt()?;
+    client.write_memory(push.address, rel)?;
+    println!("Pushed REL to {:08X}", push.address);
+    if let Some(call) = call {
+        let args = [push.address, push.bss_address.unwrap_or(0)];
+        let result = client.call(call, &args)?;
+        println!("Called {call:08X}, which returned {result:#x}");
+    }
+    client.resume()
+}
+
+fn read_project(path: &Path) -> anyhow::Result<Project> {
+    let mut project: Project = manifest::read_file(path)?;
+    if let Some(base) = path.parent() {
+        project.resolve_paths(base);
+    }
+    Ok(project)
+}
+
+fn build(args: &BuildArgs) -> anyhow::Result<()> {
+    let project = read_project(&args.project)?;
+    let mut diagnostics = Diagnostics::default();
+
+    let elf = read_file(&project.rel.elf)?;
+    let symbols = read_file(&project.rel.symbol_map)?;
+    let map = symbol_map::parse_lst(&symbols)
+        .with_context(|| format!("cannot parse {}", project.rel.symbol_map.to_string_lossy()))?;
+    for diagnostic in elfcheck::duplicate_symbols(&elf, &map, project.rel.deny_duplicate_symbols)? {
+        diagnostics.report("elf2rel", &diagnostic);
+    }
+    diagnostics.check("elf2rel")?;
+    if project.rel.check {
+        for diagnostic in elfcheck::check(&elf, Some(&map))? {
+            diagnostics.report("check", &diagnostic);
+        }
+        diagnostics.check("check")?;
+    }
+
+    let rel_version = RelVersion::try_from(project.rel.version)
+        .map_err(|_| anyhow!("Invalid REL version: {}", project.rel.version))?;
+    let renames = project
+        .rel
+        .rename_sections
+        .iter()
+        .map(|(from, to)| SectionRename::new(from, to))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut bar = ProgressBar::counting("Relocating", "relocations");
+    let rel = elf2rel::elf2rel_with_progress(
+        &elf,
+        &symbols,
+        project.rel.module_id,
+        rel_version,
+        &renames,
+        &mut |done, total| bar.update(done, total),
+    )
+    .context("cannot build REL")?;
+    bar.finish();
+    let rel_path = project.rel_path();
+    write_file(&rel_path, &rel)?;
+    println!("Wrote {}", rel_path.to_string_lossy());
+
+    if let (Some(gci), Some(gci_path)) = (&project.gci, project.gci_path()) {
+        let out = build_gci(gci, &rel, &mut diagnostics).context("cannot build GCI")?;
+        write_file(&gci_path, out)?;
+        println!("Wrote {}", gci_path.to_string_lossy());
+    }
+
+    if let Some(path) = &args.emit_depfile {
+        let mut rule = Rule::new(&rel_path);
+        rule.targets.extend(project.gci_path());
+        rule.input(&args.project);
+        for input in project.inputs() {
+            rule.input(input);
+        }
+        depfile::emit(path.as_deref(), vec![rule])?;
+    }
+
+    if diagnostics.warnings > 0 {
+        eprintln!("Built with {} warning(s)", diagnostics.warnings);
+    }
+
+    if args.push {
+        let Some(push) = &project.push else {
+            bail!("--push needs a [push] section in the project file");
+        };
+        push_rel(push, &rel, &project.rel.symbol_map).context("cannot push REL to Dolphin")?;
+    }
+    Ok(())
+}
+
+/// Modification times of the project file and its inputs. Missing files, such as an ELF the
+/// linker is still writing, count as changes once they reappear.
+fn input_times(project_path: &Path) -> Vec<Option<SystemTime>> {
+    let mut paths = vec![project_path.to_path_buf()];
+    if let Ok(project) = read_project(project_path) {
+        paths.extend(project.inputs().into_iter().map(Path::to_path_buf));
+    }
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn watch(args: &BuildArgs) -> ! {
+    let project_path = &args.project;
+    loop {
+        if let Err(e) = build(args) {
+            eprintln!("Error: {e:#}");
+        }
+        println!("Watching for changes...");
+
+        let times = input_times(project_path);
+        let mut changed = times.clone();
+        while changed == times {
+            std::thread::sleep(WATCH_INTERVAL);
+            changed = input_times(project_path);
+        }
+        // Wait for the files to settle, so a build doesn't read half-written output
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let settled = input_times(project_path);
+            if settled == changed {
+                break;
+            }
+            changed = settled;
+        }
+    }
+}
+
+/// Show the library's log messages on stderr, more of them for each `-v`
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // Ignore a logger that's already installed, as when gctools runs this tool
+    let _ = env_logger::Builder::new()
+        .filter_level(level)
+        .format(|f, record| {
+            let level = match record.level() {
+                Level::Warn => "warning".to_string(),
+                level => level.as_str().to_ascii_lowercase(),
+            };
+            let module = record.module_path().unwrap_or_default();
+            let module = module.rsplit("::").next().unwrap_or(module);
+            writeln!(f, "{level}: {module}: {}", record.args())
+        })
+        .try_init();
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = BuildArgs::parse_from(args);
+    init_logging(args.verbose);
+    if args.watch {
+        watch(&args);
+    }
+    build(&args)
+}
@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::dol::Dol;
+use gamecube_tools::gecko::GeckoCode;
+use gamecube_tools::{action_replay, dolphin_ini, gecko, patch, symbol_map};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct GeckoArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile Gecko codes in text form into a GCT file
+    Compile {
+        /// Path to input code list
+        input: PathBuf,
+        /// Path to output GCT file
+        output: PathBuf,
+    },
+    /// Print the codes in a GCT file with descriptions and disassembly
+    Decompile {
+        /// Path to input GCT file
+        input: PathBuf,
+        /// Path to output code list, instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert decrypted Action Replay codes to Gecko codes
+    FromAr {
+        /// Path to input Action Replay code list
+        input: PathBuf,
+        /// Path to output code list
+        output: PathBuf,
+        /// Write a patch list instead, for codes made only of RAM writes
+        #[arg(long)]
+        patches: bool,
+    },
+    /// Generate Gecko codes from a patch list that may reference symbols
+    Generate {
+        /// Path to input patch list
+        input: PathBuf,
+        /// Path to output code list
+        output: PathBuf,
+        /// Path to symbol map used to resolve symbol names
+        #[arg(short, long)]
+        symbol_map: Option<PathBuf>,
+        /// Write a GCT file instead of a code list
+        #[arg(long)]
+        gct: bool,
+    },
+    /// Write a GCT or code list as a Dolphin GameINI [Gecko] section
+    Ini {
+        /// Path to input GCT file or code list
+        input: PathBuf,
+        /// Path to output INI file
+        #[arg(short, long)]
+        output_ini: Option<PathBuf>,
+        /// Don't emit the `[Gecko_Enabled]` section that turns codes on by default
+        #[arg(long)]
+        no_enable: bool,
+    },
+    /// Install a codehandler and codes into a DOL, so they run without a loader
+    Install {
+        /// Path to input DOL
+        input_dol: PathBuf,
+        /// Path to GCT file or code list
+        codes: PathBuf,
+        /// Path to output DOL
+        output_dol: PathBuf,
+        /// Path to the codehandler binary, such as Dolphin's Sys/codehandler.bin
+        #[arg(long)]
+        codehandler: PathBuf,
+        /// Address or symbol of an instruction that runs once per frame, replaced by a branch to
+        /// the codehandler
+        #[arg(long)]
+        hook: String,
+        /// Path to symbol map used to resolve the hook symbol
+        #[arg(short, long)]
+        symbol_map: Option<PathBuf>,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Read a GCT file or code list. A GCT has no code names, so its codes become one code named
+/// after the file.
+fn read_codes(path: &Path) -> anyhow::Result<Vec<GeckoCode>> {
+    let buf = read_file(path)?;
+    let codes = if buf.starts_with(&gecko::GCT_HEADER[0].to_be_bytes()) {
+        let lines = gecko::parse_gct(&buf)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+        vec![GeckoCode {
+            name: path
+                .file_stem()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            lines,
+            ..Default::default()
+        }]
+    } else {
+        gecko::parse_codes(&buf)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?
+    };
+    Ok(codes)
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = GeckoArgs::parse_from(args);
+    match args.command {
+        Command::Compile { input, output } => {
+            let codes = gecko::parse_codes(&read_file(&input)?)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            write_file(&output, gecko::write_gct(&codes))?;
+        }
+        Command::Decompile { input, output } => {
+            let lines = gecko::parse_gct(&read_file(&input)?)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            let name = input
+                .file_stem()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let text = gecko::annotate(&name, &lines)?;
+            match output {
+                Some(path) => write_file(&path, text)?,
+                None => print!("{text}"),
+            }
+        }
+        Command::FromAr {
+            input,
+            output,
+            patches,
+        } => {
+            let codes = action_replay::parse_codes(&read_file(&input)?)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            let text = if patches {
+                // Master codes convert to nothing and are dropped
+                let mut patches = codes
+                    .iter()
+                    .map(action_replay::to_patch)
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                patches.retain(|p| !p.writes.is_empty());
+                patch::write_patch_list(&patches)
+            } else {
+                let mut codes = codes
+                    .iter()
+                    .map(action_replay::to_gecko)
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                codes.retain(|c| !c.lines.is_empty());
+                gecko::write_codes(&codes)
+            };
+            write_file(&output, text)?;
+        }
+        Command::Generate {
+            input,
+            output,
+            symbol_map,
+            gct,
+        } => {
+            let symbols = match &symbol_map {
+                Some(path) => symbol_map::parse_lst(&read_file(path)?)?,
+                None => Default::default(),
+            };
+            let codes = gecko::generate_codes(&read_file(&input)?, &symbols)
+                .with_context(|| format!("cannot parse {}", input.to_string_lossy()))?;
+            if gct {
+                write_file(&output, gecko::write_gct(&codes))?;
+            } else {
+                write_file(&output, gecko::write_codes(&codes))?;
+            }
+        }
+        Command::Ini {
+            input,
+            output_ini,
+            no_enable,
+        } => {
+            let codes = read_codes(&input)?;
+            let output_ini = output_ini.unwrap_or(input.with_extension("ini"));
+            write_file(
+                &output_ini,
+                dolphin_ini::write_gecko_codes(&codes, !no_enable),
+            )?;
+        }
+        Command::Install {
+            input_dol,
+            codes,
+            output_dol,
+            codehandler,
+            hook,
+            symbol_map,
+        } => {
+            let symbols = match &symbol_map {
+                Some(path) => symbol_map::parse_lst(&read_file(path)?)?,
+                None => Default::default(),
+            };
+            let hook = symbols.resolve(&hook)?;
+            let mut dol = Dol::parse(&read_file(&input_dol)?)?;
+            let gct = gecko::write_gct(&read_codes(&codes)?);
+            gecko::install_codehandler(&mut dol, &read_file(&codehandler)?, &gct, hook)?;
+            write_file(&output_dol, dol.to_bytes())?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::dol::Dol;
+use gamecube_tools::{gecko, hook, patch, symbol_map};
+
+use clap::{Parser, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum HookFormat {
+    /// A copy of the DOL with the hooks installed
+    Dol,
+    /// A patch list, as read by patch2ini
+    Patches,
+    /// A Gecko code list
+    Gecko,
+    /// C source with the hook table for a REL to install at runtime
+    Rel,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Generate branch patches and trampolines for function hooks",
+    long_about = None
+)]
+struct HookGenArgs {
+    /// Path to the game's DOL, to read the instructions the hooks displace
+    input_dol: PathBuf,
+    /// Path to hook list, with one `<target> <replacement>` per line
+    hooks: PathBuf,
+    /// Path to output file
+    #[arg(short, long)]
+    output: PathBuf,
+    /// What to generate
+    #[arg(short, long, value_enum, default_value_t = HookFormat::Patches)]
+    format: HookFormat,
+    /// Path to symbol map used to resolve targets and replacements, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// Address to place the trampolines at, one after another. Required unless generating
+    /// for a REL.
+    #[arg(short, long, value_parser = symbol_map::parse_int)]
+    trampoline_address: Option<u32>,
+    /// Write the trampolines' addresses to this symbol map, for linking replacements that call
+    /// them
+    #[arg(long)]
+    trampoline_map: Option<PathBuf>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = HookGenArgs::parse_from(args);
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => Default::default(),
+    };
+    let mut dol = Dol::parse(&read_file(&args.input_dol)?)?;
+    let specs = hook::parse_hook_list(&read_file(&args.hooks)?, &symbols)?;
+
+    let trampoline_address = match (args.format, args.trampoline_address) {
+        (HookFormat::Rel, None) => None,
+        (HookFormat::Rel, Some(_)) => {
+            bail!("RELs allocate their own trampolines, so --trampoline-address can't be used")
+        }
+        (_, None) => bail!("--trampoline-address is needed to place the trampolines"),
+        (_, address) => address,
+    };
+    let hooks = hook::build_hooks(&specs, &dol, &symbols, trampoline_address)?;
+
+    let output = match args.format {
+        HookFormat::Dol => {
+            hook::apply_to_dol(&mut dol, &hooks)?;
+            dol.to_bytes()
+        }
+        HookFormat::Patches => patch::write_patch_list(&hook::to_patches(&hooks)?).into_bytes(),
+        HookFormat::Gecko => gecko::write_codes(&hook::to_gecko(&hooks)?).into_bytes(),
+        HookFormat::Rel => hook::to_c_source(&hooks)?.into_bytes(),
+    };
+    write_file(&args.output, output)?;
+    if let Some(path) = &args.trampoline_map {
+        write_file(path, symbol_map::write_lst(&hook::trampoline_map(&hooks)))?;
+    }
+
+    Ok(())
+}
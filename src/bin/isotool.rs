@@ -0,0 +1,541 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use gamecube_tools::bnr::{self, Bnr, BANNER_HEIGHT, BANNER_WIDTH};
+use gamecube_tools::dol::Severity;
+use gamecube_tools::gcimage;
+use gamecube_tools::iso::{
+    self,
+    apploader::Apploader,
+    build::{self, IsoBuildOptions},
+    fst::FstEntryKind,
+    header::{Bi2, Country, DiskHeader, HeaderEdit},
+    verify::{self, Algorithm, Verdict},
+    ExtractLayout, Replacement, SystemFile,
+};
+use gamecube_tools::progress::ProgressBar;
+use zerocopy::IntoBytes;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct IsoToolArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the files on a disc image
+    List {
+        /// Path to input disc image
+        input_iso: PathBuf,
+    },
+    /// Extract files from a disc image
+    Extract {
+        /// Path to input disc image
+        input_iso: PathBuf,
+        /// Directory to extract to
+        output_dir: PathBuf,
+        /// Only extract these files or directories (repeatable)
+        #[arg(short, long)]
+        path: Vec<String>,
+        /// How to lay out the extracted disc
+        #[arg(long, value_enum, default_value_t = Layout::Systemdata, conflicts_with = "path")]
+        layout: Layout,
+    },
+    /// Replace a single file inside a disc image without rebuilding it
+    Replace {
+        /// Path to disc image to modify
+        iso: PathBuf,
+        /// Path of the file on disc
+        path: String,
+        /// Path to the new file contents
+        input: PathBuf,
+        /// Alignment used if the file has to be moved
+        #[arg(long, default_value_t = IsoBuildOptions::default().alignment)]
+        alignment: u32,
+    },
+    /// Extract or replace main.dol
+    Dol {
+        #[command(subcommand)]
+        action: SystemFileAction,
+    },
+    /// Inspect, extract, or replace apploader.img
+    Apploader {
+        #[command(subcommand)]
+        action: ApploaderAction,
+    },
+    /// Print boot.bin and bi2.bin, or change their fields for region or multi-disc variants
+    Header {
+        /// Path to a disc image, an extracted disc, or a directory with boot.bin and bi2.bin, to
+        /// modify in place
+        input: PathBuf,
+        #[command(flatten)]
+        edit: HeaderEditArgs,
+    },
+    /// Extract and decode opening.bnr
+    Bnr {
+        /// Path to input disc image
+        input_iso: PathBuf,
+        /// Directory to write opening.bnr, banner.png, and the raw RGB5A3 banner.bin (usable
+        /// as a gcipack banner) to
+        output_dir: PathBuf,
+    },
+    /// Write a smaller copy of a disc image with unused space removed
+    Trim {
+        /// Path to input disc image
+        input_iso: PathBuf,
+        /// Path to output disc image
+        output_iso: PathBuf,
+        /// Also remove padding between files by repacking them
+        #[arg(long)]
+        compact: bool,
+        /// Alignment of file data when compacting
+        #[arg(long, default_value_t = IsoBuildOptions::default().alignment)]
+        alignment: u32,
+    },
+    /// Compute CRC32, MD5 and SHA-1 checksums of a disc image
+    Verify {
+        /// Path to input disc image
+        input_iso: PathBuf,
+        /// File with expected checksums (redump .dat, md5sum/sha1sum output, .sfv, ...)
+        #[arg(short, long)]
+        checksums: Option<PathBuf>,
+    },
+    /// Convert a TGC container into a standalone disc image
+    #[command(name = "tgc2gcm")]
+    Tgc2Gcm {
+        /// Path to input TGC file
+        input_tgc: PathBuf,
+        /// Path to output disc image
+        output_iso: PathBuf,
+    },
+    /// Build a disc image from an extracted file system
+    Rebuild {
+        /// Directory containing the disc's file system
+        root_dir: PathBuf,
+        /// Path to output disc image
+        output_iso: PathBuf,
+        /// Directory containing boot.bin, bi2.bin, apploader.img and main.dol [default:
+        /// <ROOT_DIR>/&&systemdata, or <ROOT_DIR>/sys if ROOT_DIR has Dolphin's `sys/` and
+        /// `files/` layout]
+        #[arg(long)]
+        sys_dir: Option<PathBuf>,
+        /// Alignment of file data on disc
+        #[arg(long, default_value_t = IsoBuildOptions::default().alignment)]
+        alignment: u32,
+        /// File of per-extension or per-path alignment rules, one `<pattern> <alignment>` per
+        /// line (e.g. `*.adp 0x8000` or `/audio/ 0x8000`)
+        #[arg(long)]
+        alignment_rules: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Layout {
+    /// The file system in the output directory, with system files in `&&systemdata/`
+    Systemdata,
+    /// Dolphin's layout: system files in `sys/` and the file system in `files/`
+    Dolphin,
+}
+
+impl From<Layout> for ExtractLayout {
+    fn from(layout: Layout) -> ExtractLayout {
+        match layout {
+            Layout::Systemdata => ExtractLayout::SystemData,
+            Layout::Dolphin => ExtractLayout::Dolphin,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct HeaderEditArgs {
+    /// Six character game id, e.g. GALE01
+    #[arg(long)]
+    game_id: Option<String>,
+    /// Two character maker code, e.g. 01
+    #[arg(long)]
+    maker_code: Option<String>,
+    /// Zero-based index of the disc in a multi-disc game
+    #[arg(long)]
+    disc_number: Option<u8>,
+    /// Revision of the game
+    #[arg(long)]
+    version: Option<u8>,
+    /// Whether the game streams audio from the disc
+    #[arg(long)]
+    audio_streaming: Option<bool>,
+    #[arg(long)]
+    stream_buffer_size: Option<u8>,
+    /// Name of the game shown by loaders
+    #[arg(long)]
+    game_name: Option<String>,
+    /// Region the console must be to boot the disc: jp, us, eu, kr, or a country code
+    #[arg(long, value_parser = Country::parse)]
+    country: Option<u32>,
+    /// Debug flag in bi2.bin
+    #[arg(long)]
+    debug_flag: Option<u32>,
+}
+
+impl From<HeaderEditArgs> for HeaderEdit {
+    fn from(args: HeaderEditArgs) -> HeaderEdit {
+        HeaderEdit {
+            game_id: args.game_id,
+            maker_code: args.maker_code,
+            disc_number: args.disc_number,
+            version: args.version,
+            audio_streaming: args.audio_streaming,
+            stream_buffer_size: args.stream_buffer_size,
+            game_name: args.game_name,
+            country_code: args.country,
+            debug_flag: args.debug_flag,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum SystemFileAction {
+    /// Copy the file out of a disc image
+    Extract {
+        /// Path to input disc image
+        input_iso: PathBuf,
+        /// Path to write the file to
+        output: PathBuf,
+    },
+    /// Replace the file in a disc image, moving other system files if needed
+    Replace {
+        /// Path to disc image to modify
+        iso: PathBuf,
+        /// Path to the new file
+        input: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ApploaderAction {
+    /// Print the apploader header and check it for problems
+    Info {
+        /// Path to a disc image or apploader.img
+        input: PathBuf,
+    },
+    #[command(flatten)]
+    File(SystemFileAction),
+}
+
+fn apploader_info(input: &Path) -> anyhow::Result<()> {
+    let data =
+        std::fs::read(input).with_context(|| format!("cannot read {}", input.to_string_lossy()))?;
+    // Anything that isn't a disc image is treated as a bare apploader.img
+    let data = match iso::open(input) {
+        Ok(mut iso) => iso.read_system_file(SystemFile::Apploader)?,
+        Err(_) => data,
+    };
+    let apploader = Apploader::parse(&data)?;
+    let (load_start, load_end) = apploader.load_range();
+
+    println!("Date:        {}", apploader.date);
+    println!("Entry point: {:#010x}", apploader.entry_point);
+    println!("Code size:   {:#x}", apploader.code.len());
+    println!("Trailer:     {:#x}", apploader.trailer.len());
+    println!("Loaded at:   {load_start:#010x}..{load_end:#010x}");
+
+    let issues = apploader.validate();
+    for issue in &issues {
+        println!("{}: {}", issue.severity, issue.message);
+    }
+    if issues.iter().any(|i| i.severity == Severity::Error) {
+        bail!("Apploader has errors");
+    }
+    Ok(())
+}
+
+fn print_header(header: &DiskHeader, bi2: &Bi2) {
+    let country = bi2.country_code.get();
+    println!("Game id:         {}", header.game_id());
+    println!("Name:            {}", header.game_name());
+    println!("Disc number:     {}", header.disc_number);
+    println!("Version:         {}", header.version);
+    println!(
+        "Audio streaming: {} (buffer size {})",
+        if header.audio_streaming != 0 {
+            "on"
+        } else {
+            "off"
+        },
+        header.stream_buffer_size
+    );
+    println!(
+        "Country:         {} ({country})",
+        bi2.country().map_or("unknown", Country::name)
+    );
+    println!("Debug flag:      {}", bi2.debug_flag);
+}
+
+/// Print or edit the headers of a disc image, or of a directory of extracted system files
+fn header(input: &Path, edit: HeaderEdit) -> anyhow::Result<()> {
+    if !input.is_dir() {
+        if edit.is_empty() {
+            let mut iso = iso::open(input)?;
+            let bi2 = iso.read_bi2()?;
+            print_header(&iso.header, &bi2);
+        } else {
+            let mut iso = iso::open_rw(input)?;
+            iso.edit_header(&edit)?;
+            let bi2 = iso.read_bi2()?;
+            print_header(&iso.header, &bi2);
+        }
+        return Ok(());
+    }
+
+    // An extracted disc's root finds its system files by its layout
+    let input = if input.join(SystemFile::Boot.file_name()).is_file() {
+        input.to_path_buf()
+    } else {
+        ExtractLayout::detect(input).dirs(input).1
+    };
+    let boot_path = input.join(SystemFile::Boot.file_name());
+    let bi2_path = input.join(SystemFile::Bi2.file_name());
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))
+    };
+    let mut boot = read(&boot_path)?;
+    let mut header = DiskHeader::parse(&boot).context("Invalid boot.bin")?;
+    let mut bi2 = Bi2::parse(&read(&bi2_path)?)?;
+    if !edit.is_empty() {
+        edit.apply(&mut header, &mut bi2)?;
+        boot[..size_of::<DiskHeader>()].copy_from_slice(header.as_bytes());
+        std::fs::write(&boot_path, &boot)
+            .with_context(|| format!("cannot write {}", boot_path.to_string_lossy()))?;
+        std::fs::write(&bi2_path, bi2.as_bytes())
+            .with_context(|| format!("cannot write {}", bi2_path.to_string_lossy()))?;
+    }
+    print_header(&header, &bi2);
+    Ok(())
+}
+
+fn system_file_action(file: SystemFile, action: SystemFileAction) -> anyhow::Result<()> {
+    match action {
+        SystemFileAction::Extract { input_iso, output } => {
+            let data = iso::open(&input_iso)?.read_system_file(file)?;
+            std::fs::write(&output, data)
+                .with_context(|| format!("cannot write {}", output.to_string_lossy()))?;
+        }
+        SystemFileAction::Replace { iso, input } => {
+            let data = std::fs::read(&input)
+                .with_context(|| format!("cannot read {}", input.to_string_lossy()))?;
+            let mut iso = iso::open_rw(&iso)?;
+            match iso.replace_system_file(file, &data)? {
+                Replacement::InPlace { offset } => {
+                    println!("Replaced {} in place at {offset:#x}", file.file_name())
+                }
+                Replacement::Relocated {
+                    old_offset,
+                    new_offset,
+                } => println!(
+                    "Moved {} from {old_offset:#x} to {new_offset:#x}",
+                    file.file_name()
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = IsoToolArgs::parse_from(args);
+    match args.command {
+        Command::List { input_iso } => {
+            let iso = iso::open(&input_iso)?;
+            println!("{} - {}", iso.header.game_id(), iso.header.game_name());
+            for (entry, path) in iso.fst.entries.iter().zip(iso.fst.paths()).skip(1) {
+                match entry.kind {
+                    FstEntryKind::File { offset, size } => {
+                        println!("{offset:08X} {size:>10} {path}")
+                    }
+                    FstEntryKind::Directory { .. } => println!("{:>19} {path}/", "-"),
+                }
+            }
+        }
+        Command::Extract {
+            input_iso,
+            output_dir,
+            path,
+            layout,
+        } => {
+            let mut iso = iso::open(&input_iso)?;
+            let mut bar = ProgressBar::new("Extracting");
+            let mut progress = |done, total| bar.update(done, total);
+            if path.is_empty() {
+                iso.extract_all_with_layout(&output_dir, layout.into(), &mut progress)?;
+            } else {
+                for path in &path {
+                    iso.extract_path_with_progress(path, &output_dir, &mut progress)?;
+                }
+            }
+            bar.finish();
+        }
+        Command::Replace {
+            iso,
+            path,
+            input,
+            alignment,
+        } => {
+            let data = std::fs::read(&input)
+                .with_context(|| format!("cannot read {}", input.to_string_lossy()))?;
+            let mut iso = iso::open_rw(&iso)?;
+            match iso.replace_file(&path, &data, alignment)? {
+                Replacement::InPlace { offset } => {
+                    println!("Replaced {path} in place at {offset:#x}")
+                }
+                Replacement::Relocated {
+                    old_offset,
+                    new_offset,
+                } => println!("Moved {path} from {old_offset:#x} to {new_offset:#x}"),
+            }
+        }
+        Command::Dol { action } => system_file_action(SystemFile::MainDol, action)?,
+        Command::Apploader { action } => match action {
+            ApploaderAction::Info { input } => apploader_info(&input)?,
+            ApploaderAction::File(action) => system_file_action(SystemFile::Apploader, action)?,
+        },
+        Command::Header { input, edit } => header(&input, edit.into())?,
+        Command::Bnr {
+            input_iso,
+            output_dir,
+        } => {
+            let mut iso = iso::open(&input_iso)?;
+            let data = iso.read_banner()?;
+            let banner = Bnr::parse(&data, bnr::uses_shift_jis(&iso.header.game_code))?;
+            std::fs::create_dir_all(&output_dir)?;
+            std::fs::write(output_dir.join("opening.bnr"), &data)?;
+            std::fs::write(output_dir.join("banner.bin"), &banner.image)?;
+            let png = gcimage::write_png(&banner.image_rgba(), BANNER_WIDTH, BANNER_HEIGHT)?;
+            std::fs::write(output_dir.join("banner.png"), png)?;
+
+            println!("Version: {}", banner.version);
+            for (language, metadata) in banner.languages().zip(&banner.metadata) {
+                println!();
+                if let Some(language) = language {
+                    println!("Language: {language}");
+                }
+                println!("Name: {}", metadata.short_name);
+                println!("Maker: {}", metadata.short_maker);
+                println!("Full name: {}", metadata.long_name);
+                println!("Full maker: {}", metadata.long_maker);
+                println!("Description: {}", metadata.description);
+            }
+        }
+        Command::Trim {
+            input_iso,
+            output_iso,
+            compact,
+            alignment,
+        } => {
+            let mut iso = iso::open(&input_iso)?;
+            let output_file = File::create(&output_iso)
+                .with_context(|| format!("cannot create {}", output_iso.to_string_lossy()))?;
+            let mut writer = BufWriter::new(output_file);
+            let report = if compact {
+                iso.compact_to(alignment, &mut writer)?
+            } else {
+                iso.truncate_to(&mut writer)?
+            };
+            println!(
+                "{} -> {} bytes, saved {} bytes ({:.1} MiB)",
+                report.original_size,
+                report.trimmed_size,
+                report.bytes_saved(),
+                report.bytes_saved() as f64 / (1024.0 * 1024.0)
+            );
+        }
+        Command::Verify {
+            input_iso,
+            checksums,
+        } => {
+            let actual = verify::compute(&mut iso::open_image(&input_iso)?)?;
+            println!("Size:  {}", actual.size);
+            for algorithm in [Algorithm::Crc32, Algorithm::Md5, Algorithm::Sha1] {
+                println!("{:<6} {}", format!("{algorithm}:"), actual.hex(algorithm));
+            }
+
+            if let Some(checksums) = checksums {
+                let expected = std::fs::read_to_string(&checksums)
+                    .with_context(|| format!("cannot read {}", checksums.to_string_lossy()))?;
+                let results = actual
+                    .verify(&expected)
+                    .with_context(|| format!("cannot read {}", checksums.to_string_lossy()))?;
+                println!();
+                for (algorithm, verdict) in &results {
+                    let verdict = match verdict {
+                        Verdict::Match => "OK",
+                        Verdict::Mismatch => "MISMATCH",
+                        Verdict::Missing => "not in checksum file",
+                    };
+                    println!("{algorithm}: {verdict}");
+                }
+                if results.iter().any(|(_, v)| *v == Verdict::Mismatch) {
+                    bail!("Disc image does not match the expected checksums");
+                }
+                if results.iter().all(|(_, v)| *v == Verdict::Missing) {
+                    bail!("No checksums found in {}", checksums.to_string_lossy());
+                }
+            }
+        }
+        Command::Tgc2Gcm {
+            input_tgc,
+            output_iso,
+        } => {
+            let mut iso = iso::open(&input_tgc)?;
+            let output_file = File::create(&output_iso)
+                .with_context(|| format!("cannot create {}", output_iso.to_string_lossy()))?;
+            // Repacking assigns fresh offsets to everything, dropping the TGC offset shift
+            iso.compact_to(
+                IsoBuildOptions::default().alignment,
+                &mut BufWriter::new(output_file),
+            )?;
+        }
+        Command::Rebuild {
+            root_dir,
+            output_iso,
+            sys_dir,
+            alignment,
+            alignment_rules,
+        } => {
+            let (root_dir, default_sys_dir) = ExtractLayout::detect(&root_dir).dirs(&root_dir);
+            let sys_dir = sys_dir.unwrap_or(default_sys_dir);
+            let alignment_rules = match alignment_rules {
+                Some(path) => {
+                    let buf = std::fs::read(&path)
+                        .with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+                    build::parse_alignment_rules(&buf)?
+                }
+                None => Vec::new(),
+            };
+            let options = IsoBuildOptions {
+                alignment,
+                alignment_rules,
+            };
+            let output_file = File::create(&output_iso)
+                .with_context(|| format!("cannot create {}", output_iso.to_string_lossy()))?;
+            build::build_iso(
+                &root_dir,
+                &sys_dir,
+                &options,
+                &mut BufWriter::new(output_file),
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, ValueEnum};
+use gamecube_tools::demangle;
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MapFormat {
+    /// `address:name` lines, as accepted by elf2rel
+    Lst,
+    /// Dolphin symbol map
+    Dolphin,
+    /// CodeWarrior linker map
+    Codewarrior,
+    /// `address,size,section,name` CSV
+    Csv,
+    /// GNU ld script of `PROVIDE` statements (output only)
+    Ld,
+    /// ld `--defsym` arguments, to pass as `@file` (output only)
+    Defsym,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Convert symbol maps between formats", long_about = None)]
+struct MapConvArgs {
+    /// Path to input symbol map
+    input: PathBuf,
+    /// Path to output symbol map
+    output: PathBuf,
+    /// Input format. Detected from the contents by default; Dolphin and CodeWarrior maps are
+    /// read the same way.
+    #[arg(long, value_enum)]
+    from: Option<MapFormat>,
+    /// Output format. Chosen from the output extension by default: `.lst`, `.csv`, `.ld`, or
+    /// `.map` for a Dolphin map.
+    #[arg(long, value_enum)]
+    to: Option<MapFormat>,
+    /// Replace mangled C++ names with demangled ones
+    #[arg(long)]
+    demangle: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn output_format(path: &Path) -> anyhow::Result<MapFormat> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    Ok(match extension.as_deref() {
+        Some("lst") | Some("txt") => MapFormat::Lst,
+        Some("csv") => MapFormat::Csv,
+        Some("map") => MapFormat::Dolphin,
+        Some("ld") => MapFormat::Ld,
+        _ => bail!(
+            "cannot tell the output format from {}, pass --to",
+            path.to_string_lossy()
+        ),
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = MapConvArgs::parse_from(args);
+    let input = read_file(&args.input)?;
+    let mut map: SymbolMap = match args.from {
+        None => symbol_map::parse(&input),
+        Some(MapFormat::Lst) => symbol_map::parse_lst(&input),
+        Some(MapFormat::Dolphin | MapFormat::Codewarrior) => symbol_map::parse_map(&input),
+        Some(MapFormat::Csv) => symbol_map::parse_csv(&input),
+        Some(MapFormat::Ld | MapFormat::Defsym) => Err(anyhow!(
+            "ld scripts and --defsym files can only be written, not read"
+        )),
+    }
+    .with_context(|| format!("cannot parse {}", args.input.to_string_lossy()))?;
+
+    if args.demangle {
+        for symbol in &mut map.symbols {
+            symbol.name = demangle::display_name(&symbol.name);
+        }
+    }
+
+    let to = match args.to {
+        Some(to) => to,
+        None => output_format(&args.output)?,
+    };
+    let output = match to {
+        MapFormat::Lst => symbol_map::write_lst(&map),
+        MapFormat::Dolphin => symbol_map::write_dolphin_map(&map),
+        MapFormat::Codewarrior => symbol_map::write_codewarrior_map(&map),
+        MapFormat::Csv => symbol_map::write_csv(&map),
+        MapFormat::Ld => symbol_map::write_linker_script(&map),
+        MapFormat::Defsym => symbol_map::write_defsyms(&map),
+    };
+    std::fs::write(&args.output, output)
+        .with_context(|| format!("cannot write {}", args.output.to_string_lossy()))?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::demangle;
+use gamecube_tools::mapdiff::{self, MapChangeKind};
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Compare two symbol maps", long_about = None)]
+struct MapDiffArgs {
+    /// Path to original symbol map, in any format mapconv reads
+    old_map: PathBuf,
+    /// Path to updated symbol map, in any format mapconv reads
+    new_map: PathBuf,
+    /// Show demangled C++ names in place of mangled ones. Symbols are still matched by their
+    /// names as written in the maps.
+    #[arg(long)]
+    demangle: bool,
+}
+
+fn read_map(path: &Path) -> anyhow::Result<SymbolMap> {
+    let buf =
+        std::fs::read(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+    symbol_map::parse(&buf).with_context(|| format!("cannot parse {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = MapDiffArgs::parse_from(args);
+    let old = read_map(&args.old_map)?;
+    let new = read_map(&args.new_map)?;
+
+    for mut change in mapdiff::mapdiff(&old, &new) {
+        if args.demangle {
+            for symbol in change.old.iter_mut().chain(change.new.iter_mut()) {
+                symbol.name = demangle::display_name(&symbol.name);
+            }
+        }
+        match (change.kind, &change.old, &change.new) {
+            (MapChangeKind::Added, _, Some(new)) => {
+                println!("+ {:08X} {}", new.address, new.name)
+            }
+            (MapChangeKind::Removed, Some(old), _) => {
+                println!("- {:08X} {}", old.address, old.name)
+            }
+            (MapChangeKind::Moved, Some(old), Some(new)) => println!(
+                "> {:08X} {} (moved from {:08X})",
+                new.address, new.name, old.address
+            ),
+            (MapChangeKind::Renamed, Some(old), Some(new)) => println!(
+                "~ {:08X} {} (renamed from {})",
+                new.address, new.name, old.name
+            ),
+            (MapChangeKind::Resized, Some(old), Some(new)) => println!(
+                "~ {:08X} {} (size {:#x} -> {:#x})",
+                new.address,
+                new.name,
+                old.size.unwrap_or(0),
+                new.size.unwrap_or(0)
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use gamecube_tools::mapheader::{self, HeaderOptions, HeaderStyle};
+use gamecube_tools::symbol_map;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Style {
+    /// `extern` function and data declarations, resolved by the linker
+    Extern,
+    /// `#define` address constants
+    Constants,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Generate a C header from a symbol map", long_about = None)]
+struct MapHeaderArgs {
+    /// Path to input symbol map, in any format mapconv reads
+    input_map: PathBuf,
+    /// Path to output header
+    #[arg(short, long)]
+    output_header: Option<PathBuf>,
+    /// What to declare for each symbol
+    #[arg(long, value_enum, default_value_t = Style::Extern)]
+    style: Style,
+    /// Prefix for constant names
+    #[arg(long, default_value = "")]
+    prefix: String,
+    /// Only include symbols matching this pattern, which may contain `*`. May be repeated.
+    #[arg(long)]
+    include: Vec<String>,
+    /// Leave out symbols matching this pattern, which may contain `*`. May be repeated.
+    #[arg(long)]
+    exclude: Vec<String>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = MapHeaderArgs::parse_from(args);
+    let map = symbol_map::parse(&read_file(&args.input_map)?)
+        .with_context(|| format!("cannot parse {}", args.input_map.to_string_lossy()))?;
+    let output_header_path = args
+        .output_header
+        .unwrap_or(args.input_map.with_extension("h"));
+
+    // Include guard from the header's file name, e.g. GAME_SYMBOLS_H
+    let guard = output_header_path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let options = HeaderOptions {
+        style: match args.style {
+            Style::Extern => HeaderStyle::Extern,
+            Style::Constants => HeaderStyle::Constants,
+        },
+        prefix: args.prefix,
+        include: args.include,
+        exclude: args.exclude,
+        guard,
+    };
+
+    let (header, skipped) = mapheader::generate_header(&map, &options);
+    for name in skipped {
+        eprintln!("Skipping {name}, which isn't a valid C identifier");
+    }
+    std::fs::write(&output_header_path, header)
+        .with_context(|| format!("cannot write {}", output_header_path.to_string_lossy()))?;
+
+    Ok(())
+}
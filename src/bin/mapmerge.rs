@@ -0,0 +1,145 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::{Parser, ValueEnum};
+use gamecube_tools::symbol_map;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MapFormat {
+    /// `address:name` lines, as accepted by elf2rel
+    Lst,
+    /// Dolphin symbol map
+    Dolphin,
+    /// CodeWarrior linker map
+    Codewarrior,
+    /// `address,size,section,name` CSV
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Precedence {
+    /// Earlier maps win conflicts
+    First,
+    /// Later maps win conflicts
+    Last,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Merge symbol maps, reporting conflicts", long_about = None)]
+struct MapMergeArgs {
+    /// Paths to input symbol maps, in any format mapconv reads
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Path to output symbol map
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Output format. Chosen from the output extension by default: `.lst`, `.csv`, or `.map` for
+    /// a Dolphin map.
+    #[arg(long, value_enum)]
+    to: Option<MapFormat>,
+    /// Which map's symbol is kept when maps conflict
+    #[arg(long, value_enum, default_value_t = Precedence::First)]
+    precedence: Precedence,
+    /// Write the conflict report to a file instead of stderr
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Fail without writing the output if there are any conflicts
+    #[arg(long)]
+    strict: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn output_format(path: &Path) -> anyhow::Result<MapFormat> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    Ok(match extension.as_deref() {
+        Some("lst") | Some("txt") => MapFormat::Lst,
+        Some("csv") => MapFormat::Csv,
+        Some("map") => MapFormat::Dolphin,
+        _ => bail!(
+            "cannot tell the output format from {}, pass --to",
+            path.to_string_lossy()
+        ),
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = MapMergeArgs::parse_from(args);
+    let to = match args.to {
+        Some(to) => to,
+        None => output_format(&args.output)?,
+    };
+
+    let mut inputs = args.inputs.clone();
+    if args.precedence == Precedence::Last {
+        inputs.reverse();
+    }
+    let mut maps = Vec::new();
+    for path in &inputs {
+        let map = symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+        maps.push(map);
+    }
+
+    // Report which map each side of a conflict came from. Merged symbols may have had their
+    // size or section filled in, so only the name and address are compared.
+    let source = |symbol: &symbol_map::Symbol| {
+        let i = maps
+            .iter()
+            .position(|m| {
+                m.symbols
+                    .iter()
+                    .any(|s| s.name == symbol.name && s.address == symbol.address)
+            })
+            .unwrap();
+        inputs[i].to_string_lossy()
+    };
+    let (merged, conflicts) = symbol_map::merge(&maps);
+    let mut report = String::new();
+    for conflict in &conflicts {
+        let (kept, dropped) = match conflict {
+            symbol_map::Conflict::Address { kept, dropped }
+            | symbol_map::Conflict::Name { kept, dropped } => (kept, dropped),
+        };
+        writeln!(
+            report,
+            "{conflict} (kept {}, dropped {})",
+            source(kept),
+            source(dropped)
+        )
+        .unwrap();
+    }
+    match &args.report {
+        Some(path) => write_file(path, &report)?,
+        None => eprint!("{report}"),
+    }
+    if args.strict && !conflicts.is_empty() {
+        bail!("{} conflicts between the maps", conflicts.len());
+    }
+
+    let output = match to {
+        MapFormat::Lst => symbol_map::write_lst(&merged),
+        MapFormat::Dolphin => symbol_map::write_dolphin_map(&merged),
+        MapFormat::Codewarrior => symbol_map::write_codewarrior_map(&merged),
+        MapFormat::Csv => symbol_map::write_csv(&merged),
+    };
+    write_file(&args.output, output)?;
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::formats::gci::PERMISSION_NO_COPY;
+use gamecube_tools::memcard::{MemoryCard, SaveInfo};
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Inspect raw memory card images and copy saves between them", long_about = None)]
+struct McArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the saves on a card
+    #[command(name = "ls")]
+    List {
+        /// Path to a raw memory card image, such as Dolphin's `.raw`
+        card: PathBuf,
+        /// Print the listing as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Copy a save from one card to another, updating the destination card in place
+    #[command(name = "cp")]
+    Copy {
+        /// Card to copy the save from
+        source: PathBuf,
+        /// Card to copy the save to
+        destination: PathBuf,
+        /// File name of the save, as shown by `mc ls`
+        file_name: String,
+        /// Gamecode of the save, when several games have saves with the same file name
+        #[arg(long)]
+        gamecode: Option<String>,
+        /// Replace the game's save with the same name on the destination card
+        #[arg(long)]
+        replace: bool,
+        /// Copy the save even if the game marked it as not to be copied
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct Listing<'a> {
+    total_blocks: usize,
+    free_blocks: u16,
+    saves: &'a [SaveInfo],
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn read_card(path: &Path) -> anyhow::Result<MemoryCard> {
+    MemoryCard::parse(read_file(path)?)
+        .with_context(|| format!("cannot read memory card {}", path.to_string_lossy()))
+}
+
+fn print_saves(card: &MemoryCard, saves: &[SaveInfo]) {
+    println!(
+        "{} saves, {} of {} blocks free",
+        saves.len(),
+        card.free_blocks(),
+        card.total_blocks() - gamecube_tools::memcard::SYSTEM_BLOCKS
+    );
+    if saves.is_empty() {
+        return;
+    }
+    println!();
+    println!(
+        "Code    {:<32}  Blocks  Modified             Banner  Title",
+        "File name"
+    );
+    for save in saves {
+        println!(
+            "{:<6}  {:<32}  {:>6}  {}  {:<6}  {}",
+            save.gamecode,
+            save.file_name,
+            save.blocks,
+            save.modified,
+            if save.banner { "yes" } else { "no" },
+            save.title
+        );
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = McArgs::parse_from(args);
+    match args.command {
+        Command::List { card, json } => {
+            let card = read_card(&card)?;
+            let saves = card.saves()?;
+            if json {
+                let listing = Listing {
+                    total_blocks: card.total_blocks(),
+                    free_blocks: card.free_blocks(),
+                    saves: &saves,
+                };
+                println!("{}", serde_json::to_string_pretty(&listing)?);
+            } else {
+                print_saves(&card, &saves);
+            }
+        }
+        Command::Copy {
+            source,
+            destination,
+            file_name,
+            gamecode,
+            replace,
+            force,
+        } => {
+            let source_card = read_card(&source)?;
+            let index = source_card
+                .find(&file_name, gamecode.as_deref())
+                .with_context(|| format!("cannot copy from {}", source.to_string_lossy()))?;
+            let save = source_card.save_info(index)?;
+            if save.permissions & PERMISSION_NO_COPY != 0 && !force {
+                bail!(
+                    "{} is marked as not to be copied; use --force to copy it anyway",
+                    save.file_name
+                );
+            }
+            let gci = source_card.export_gci(index)?;
+
+            let mut card = read_card(&destination)?;
+            if replace && let Ok(existing) = card.find(&save.file_name, Some(&save.gamecode)) {
+                card.delete(existing)?;
+            }
+            card.import_gci(&gci)
+                .with_context(|| format!("cannot copy to {}", destination.to_string_lossy()))?;
+            std::fs::write(&destination, &card.data)
+                .with_context(|| format!("cannot write {}", destination.to_string_lossy()))?;
+            println!(
+                "Copied {} ({}, {} blocks); {} blocks free",
+                save.file_name,
+                save.gamecode,
+                save.blocks,
+                card.free_blocks()
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::objdiff::{self, Unit};
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Compare a reference DOL or REL with a rebuilt one, writing an objdiff progress report",
+    long_about = None
+)]
+struct ObjdiffReportArgs {
+    /// Path to the DOL or REL from the game
+    reference: PathBuf,
+    /// Path to the DOL or REL built from the decompilation
+    rebuilt: PathBuf,
+    /// Symbol map for the reference binary, in any format mapconv reads, naming the functions to
+    /// report on
+    #[arg(long)]
+    reference_map: Option<PathBuf>,
+    /// Symbol map for the rebuilt binary, to find functions that have moved by name
+    #[arg(long)]
+    rebuilt_map: Option<PathBuf>,
+    /// Address RELs were linked at when their symbol maps were made, in hex
+    #[arg(long, value_parser = parse_address, default_value = "0")]
+    rel_address: u32,
+    /// Name of the unit in the report; defaults to the reference's file name
+    #[arg(long)]
+    name: Option<String>,
+    /// Path to write the report to; defaults to standard output
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn parse_address(s: &str) -> anyhow::Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).with_context(|| format!("invalid address {s}"))
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn is_rel(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("rel"))
+}
+
+fn load_sections(path: &Path, rel_address: u32) -> anyhow::Result<Vec<objdiff::Section>> {
+    objdiff::load_sections(&read_file(path)?, is_rel(path), rel_address)
+        .with_context(|| format!("cannot parse {}", path.to_string_lossy()))
+}
+
+fn load_map(path: Option<&PathBuf>) -> anyhow::Result<Option<SymbolMap>> {
+    path.map(|path| {
+        symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))
+    })
+    .transpose()
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = ObjdiffReportArgs::parse_from(args);
+    let reference = load_sections(&args.reference, args.rel_address)?;
+    let rebuilt = load_sections(&args.rebuilt, args.rel_address)?;
+    let reference_map = load_map(args.reference_map.as_ref())?;
+    let rebuilt_map = load_map(args.rebuilt_map.as_ref())?;
+
+    let name = args.name.clone().unwrap_or_else(|| {
+        args.reference
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+    let report = objdiff::report(&[Unit {
+        name,
+        reference: &reference,
+        rebuilt: &rebuilt,
+        reference_map: reference_map.as_ref(),
+        rebuilt_map: rebuilt_map.as_ref(),
+    }]);
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("cannot write {}", path.to_string_lossy()))?,
+        None => println!("{json}"),
+    }
+    eprintln!(
+        "{:.2}% of code and {:.2}% of data matches",
+        report.measures.fuzzy_match_percent, report.measures.matched_data_percent
+    );
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use gamecube_tools::{dolphin_ini, patch, symbol_map};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Patch2IniArgs {
+    /// Path to input patch list
+    input_patches: PathBuf,
+    /// Path to symbol map used to resolve symbol names
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// Path to output INI file
+    #[arg(short, long)]
+    output_ini: Option<PathBuf>,
+    /// INI section to emit patches into
+    #[arg(long, default_value = "OnFrame")]
+    section: String,
+    /// Don't emit the `_Enabled` section that turns patches on by default
+    #[arg(long)]
+    no_enable: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Patch2IniArgs::parse_from(args);
+    let input_patches = read_file(&args.input_patches)?;
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)?,
+        None => Default::default(),
+    };
+    let output_ini_path = args
+        .output_ini
+        .unwrap_or(args.input_patches.with_extension("ini"));
+
+    let patches = patch::parse_patch_list(&input_patches, &symbols)?;
+    let ini = dolphin_ini::write_patches(&patches, &args.section, !args.no_enable);
+
+    let mut output_file = File::create(output_ini_path)?;
+    output_file.write_all(ini.as_bytes())?;
+
+    Ok(())
+}
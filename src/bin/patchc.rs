@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::{Parser, ValueEnum};
+use gamecube_tools::dol::Dol;
+use gamecube_tools::{dolphin_ini, gecko, patch, symbol_map};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PatchFormat {
+    /// A copy of the DOL given with `--dol`, with the patches applied
+    Dol,
+    /// Gecko GCT file
+    Gct,
+    /// Gecko code list
+    Codes,
+    /// Dolphin GameINI patch section
+    Ini,
+    /// C source with a table of writes, for a runtime installer
+    C,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Compile a patch list to DOL edits, Gecko codes, Dolphin INI patches, or C",
+    long_about = None
+)]
+struct PatchCArgs {
+    /// Path to input patch list
+    input_patches: PathBuf,
+    /// Path to output file
+    output: PathBuf,
+    /// Output format. Chosen from the output extension by default: `.dol`, `.gct`, `.txt` for a
+    /// code list, `.ini`, or `.c`.
+    #[arg(short, long, value_enum)]
+    format: Option<PatchFormat>,
+    /// Path to symbol map used to resolve symbol names, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// Path to the DOL to patch, for `--format dol`
+    #[arg(long)]
+    dol: Option<PathBuf>,
+    /// INI section to emit patches into
+    #[arg(long, default_value = "OnFrame")]
+    section: String,
+    /// Don't emit the INI section that turns patches on by default
+    #[arg(long)]
+    no_enable: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn output_format(path: &Path) -> anyhow::Result<PatchFormat> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    Ok(match extension.as_deref() {
+        Some("dol") => PatchFormat::Dol,
+        Some("gct") => PatchFormat::Gct,
+        Some("txt") => PatchFormat::Codes,
+        Some("ini") => PatchFormat::Ini,
+        Some("c") => PatchFormat::C,
+        _ => bail!(
+            "cannot tell the output format from {}, pass --format",
+            path.to_string_lossy()
+        ),
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = PatchCArgs::parse_from(args);
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => Default::default(),
+    };
+    let patches = patch::parse_patch_list(&read_file(&args.input_patches)?, &symbols)
+        .with_context(|| format!("cannot parse {}", args.input_patches.to_string_lossy()))?;
+    let format = match args.format {
+        Some(format) => format,
+        None => output_format(&args.output)?,
+    };
+
+    let output = match format {
+        PatchFormat::Dol => {
+            let Some(dol_path) = &args.dol else {
+                bail!("--dol is needed to write a patched DOL");
+            };
+            let mut dol = Dol::parse(&read_file(dol_path)?)?;
+            patch::apply_to_dol(&mut dol, &patches)?;
+            dol.to_bytes()
+        }
+        PatchFormat::Gct => gecko::write_gct(&gecko::codes_from_patches(&patches)?),
+        PatchFormat::Codes => {
+            gecko::write_codes(&gecko::codes_from_patches(&patches)?).into_bytes()
+        }
+        PatchFormat::Ini => {
+            dolphin_ini::write_patches(&patches, &args.section, !args.no_enable).into_bytes()
+        }
+        PatchFormat::C => patch::write_c_array(&patches).into_bytes(),
+    };
+    write_file(&args.output, output)?;
+
+    Ok(())
+}
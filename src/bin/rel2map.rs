@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::rel::{LoadedRel, Rel};
+use gamecube_tools::rel2map;
+use gamecube_tools::symbol_map;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Write a Dolphin symbol map for a REL at its load address",
+    long_about = None
+)]
+struct Rel2MapArgs {
+    /// Path to input REL file
+    input: PathBuf,
+    /// Address the REL is loaded at
+    #[arg(short, long, value_parser = symbol_map::parse_int)]
+    address: u32,
+    /// Address of the REL's BSS, to include it in the map
+    #[arg(long, value_parser = symbol_map::parse_int)]
+    bss_address: Option<u32>,
+    /// Path to the ELF the REL was built from, to map its symbols instead of whole sections
+    #[arg(short, long)]
+    elf: Option<PathBuf>,
+    /// Path to output map. Defaults to the REL's path with a `.map` extension.
+    #[arg(short, long)]
+    output_map: Option<PathBuf>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Rel2MapArgs::parse_from(args);
+    let buf = read_file(&args.input)?;
+    let rel = Rel::parse(&buf)
+        .with_context(|| format!("cannot parse {}", args.input.to_string_lossy()))?;
+    let loaded = LoadedRel {
+        name: args
+            .input
+            .file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        rel,
+        address: args.address,
+        bss_address: args.bss_address,
+    };
+    let elf = args.elf.as_ref().map(read_file).transpose()?;
+
+    let map = rel2map::rel2map(&loaded, elf.as_deref())?;
+    let output_map_path = args.output_map.unwrap_or(args.input.with_extension("map"));
+    std::fs::write(&output_map_path, symbol_map::write_dolphin_map(&map))
+        .with_context(|| format!("cannot write {}", output_map_path.to_string_lossy()))?;
+
+    Ok(())
+}
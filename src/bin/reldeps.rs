@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::rel::Rel;
+use gamecube_tools::reldeps;
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Print the import graph between RELs", long_about = None)]
+struct RelDepsArgs {
+    /// Paths to REL files
+    #[arg(required = true)]
+    rels: Vec<PathBuf>,
+    /// Path to the DOL's symbol map, used to check DOL imports, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// Write the graph in Graphviz format
+    #[arg(long)]
+    dot: bool,
+    /// Exit with an error if there are dangling imports
+    #[arg(long)]
+    strict: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelDepsArgs::parse_from(args);
+    let dol_map = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => SymbolMap::default(),
+    };
+    let mut rels = Vec::new();
+    for path in &args.rels {
+        let rel = Rel::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        rels.push((name, rel));
+    }
+
+    let graph = reldeps::dependency_graph(&rels, &dol_map)?;
+    if args.dot {
+        print!("{}", graph.to_dot());
+    } else {
+        print!("{}", graph.to_text());
+    }
+    if args.strict && !graph.dangling.is_empty() {
+        bail!("{} dangling imports", graph.dangling.len());
+    }
+
+    Ok(())
+}
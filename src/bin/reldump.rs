@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::crash;
+use gamecube_tools::listing::Listing;
+use gamecube_tools::rel::{Rel, RelRelocation};
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Print a REL's header, sections, and imports, and disassemble its relocation sites",
+    long_about = None
+)]
+struct RelDumpArgs {
+    /// Path to input REL file
+    input_rel: PathBuf,
+    /// Disassemble the code around each relocation site and the prolog, epilog, and unresolved
+    /// functions
+    #[arg(short, long)]
+    disassemble: bool,
+    /// Instructions to show either side of each site
+    #[arg(short = 'C', long, default_value_t = 2)]
+    context: u32,
+    /// Path to the game's symbol map, in any format mapconv reads, to name relocation targets in
+    /// the DOL
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+/// Describe where a relocation points: an address in the DOL, or a section of a REL
+fn describe_target(
+    rel: &Rel,
+    module_id: u32,
+    relocation: &RelRelocation,
+    map: &SymbolMap,
+) -> String {
+    let addend = relocation.addend;
+    if module_id == 0 {
+        match crash::describe(addend, map, &[]) {
+            Some(location) => format!("{addend:08X} {location}"),
+            None => format!("{addend:08X}"),
+        }
+    } else if module_id == rel.id {
+        format!("section {} + {addend:#x}", relocation.target_section)
+    } else {
+        format!(
+            "module {module_id} section {} + {addend:#x}",
+            relocation.target_section
+        )
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelDumpArgs::parse_from(args);
+    let buf = read_file(&args.input_rel)?;
+    let rel = Rel::parse(&buf)
+        .with_context(|| format!("cannot parse {}", args.input_rel.to_string_lossy()))?;
+    let map = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => SymbolMap::default(),
+    };
+
+    println!("Module {} (version {})", rel.id, rel.version);
+    println!();
+    println!("Section  Offset    Size");
+    for (i, section) in rel.sections.iter().enumerate() {
+        if section.size == 0 {
+            continue;
+        }
+        let kind = if section.offset == 0 {
+            "bss"
+        } else if section.executable {
+            "text"
+        } else {
+            "data"
+        };
+        println!(
+            "{i:<8} {:08X}  {:08X}  {kind}",
+            section.offset, section.size
+        );
+    }
+    println!("BSS size: {:08X}", rel.total_bss_size);
+    let functions = [
+        ("Prolog", rel.prolog),
+        ("Epilog", rel.epilog),
+        ("Unresolved", rel.unresolved),
+    ];
+    for (name, function) in functions {
+        if function.section != 0 {
+            println!(
+                "{name}: section {} + {:#x}",
+                function.section, function.offset
+            );
+        }
+    }
+    println!();
+    println!("Imports:");
+    for import in &rel.imports {
+        println!(
+            "  module {:<6} {} relocations",
+            import.module_id,
+            import.relocations.len()
+        );
+    }
+
+    if !args.disassemble {
+        return Ok(());
+    }
+    for (i, section) in rel.sections.iter().enumerate() {
+        if !section.executable || section.offset == 0 {
+            continue;
+        }
+        let code = &buf[section.offset as usize..(section.offset + section.size) as usize];
+        // Addresses are offsets within the section, since a REL is linked wherever it's loaded
+        let mut listing = Listing::new(code, 0);
+        for (name, function) in functions {
+            if function.section as usize == i {
+                listing.note(function.offset, name.to_lowercase());
+            }
+        }
+        for import in &rel.imports {
+            for relocation in import
+                .relocations
+                .iter()
+                .filter(|r| r.section as usize == i)
+            {
+                listing.note(
+                    relocation.offset,
+                    format!(
+                        "{:?} -> {}",
+                        relocation.type_,
+                        describe_target(&rel, import.module_id, relocation, &map)
+                    ),
+                );
+            }
+        }
+        if listing.is_empty() {
+            continue;
+        }
+        println!();
+        println!("Section {i}:");
+        print!("{}", listing.render(args.context));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context};
+use gamecube_tools::rel::{LoadedRel, Rel};
+use gamecube_tools::rellink;
+use gamecube_tools::symbol_map::{self, SymbolMap};
+use gamecube_tools::symbolicate;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Link RELs the way OSLink does, writing the relocated images",
+    long_about = None
+)]
+struct RelLinkArgs {
+    /// A REL to link, as `<path>@<address>` or `<path>@<address>,<bss address>`. May be repeated;
+    /// imports between the given RELs are resolved.
+    #[arg(long, required = true)]
+    rel: Vec<String>,
+    /// Output path: a file when linking one REL, otherwise a directory. Defaults to each REL's
+    /// path with a `.bin` extension.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Path to symbol map used to label unresolved relocations, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+}
+
+/// Parse an address, with or without a `0x` prefix
+fn parse_address(s: &str) -> anyhow::Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).with_context(|| format!("invalid address {s}"))
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Load a REL from a `--rel` argument. Without a BSS address, the BSS is placed right after the
+/// REL, as when both are allocated together.
+fn load_rel(arg: &str) -> anyhow::Result<(PathBuf, Vec<u8>, LoadedRel)> {
+    let (path, addresses) = arg
+        .rsplit_once('@')
+        .ok_or_else(|| anyhow!("expected <path>@<address> for --rel, got {arg}"))?;
+    let (address, bss_address) = match addresses.split_once(',') {
+        Some((address, bss)) => (parse_address(address)?, Some(parse_address(bss)?)),
+        None => (parse_address(addresses)?, None),
+    };
+    let path = PathBuf::from(path);
+    let buf = read_file(&path)?;
+    let rel =
+        Rel::parse(&buf).with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+    let bss_address = bss_address.or_else(|| {
+        let align = rel.max_bss_align.max(32);
+        (rel.total_bss_size != 0).then(|| (address + buf.len() as u32).next_multiple_of(align))
+    });
+    let loaded = LoadedRel {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        rel,
+        address,
+        bss_address,
+    };
+    Ok((path, buf, loaded))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelLinkArgs::parse_from(args);
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => SymbolMap::default(),
+    };
+    let inputs = args
+        .rel
+        .iter()
+        .map(|arg| load_rel(arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let loaded: Vec<LoadedRel> = inputs.iter().map(|(_, _, rel)| rel.clone()).collect();
+    for (i, rel) in loaded.iter().enumerate() {
+        ensure!(
+            !loaded[..i].iter().any(|other| other.rel.id == rel.rel.id),
+            "{} has the same module ID as another REL",
+            rel.name
+        );
+    }
+    if let Some(output) = &args.output
+        && inputs.len() > 1
+    {
+        std::fs::create_dir_all(output)
+            .with_context(|| format!("cannot create {}", output.to_string_lossy()))?;
+    }
+
+    for (path, buf, rel) in &inputs {
+        let linked = rellink::rellink(buf, rel, &loaded)
+            .with_context(|| format!("cannot link {}", path.to_string_lossy()))?;
+        let output_path = match &args.output {
+            Some(output) if inputs.len() == 1 => output.clone(),
+            Some(output) => output.join(Path::new(&rel.name).with_extension("bin")),
+            None => path.with_extension("bin"),
+        };
+        write_file(&output_path, &linked.image)?;
+
+        for unresolved in &linked.unresolved {
+            let relocation = &unresolved.relocation;
+            print!(
+                "{}: {:08X} {:?} against module {} section {}+{:#x} is unresolved",
+                rel.name,
+                unresolved.address,
+                relocation.type_,
+                unresolved.module_id,
+                relocation.target_section,
+                relocation.addend
+            );
+            if let Some(location) = symbolicate::symbolicate(unresolved.address, &symbols, &loaded)
+            {
+                print!(" [{location}]");
+            }
+            println!();
+        }
+    }
+
+    Ok(())
+}
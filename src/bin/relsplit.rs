@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use gamecube_tools::manifest::{self, ManifestFormat};
+use gamecube_tools::rel::Rel;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Split a REL's sections into binary files", long_about = None)]
+struct RelSplitArgs {
+    /// Path to input REL file
+    input: PathBuf,
+    /// Directory to write the section files and the manifest to
+    output_dir: PathBuf,
+    /// Manifest format: toml or json
+    #[arg(long, default_value = "toml", value_parser = ManifestFormat::parse)]
+    format: ManifestFormat,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelSplitArgs::parse_from(args);
+    let buf = read_file(&args.input)?;
+    let rel = Rel::parse(&buf)
+        .with_context(|| format!("cannot parse {}", args.input.to_string_lossy()))?;
+
+    std::fs::create_dir_all(&args.output_dir).with_context(|| {
+        format!(
+            "cannot create directory {}",
+            args.output_dir.to_string_lossy()
+        )
+    })?;
+    let manifest = rel.manifest();
+    for section in &manifest.sections {
+        let Some(file) = &section.file else {
+            continue;
+        };
+        let data = rel
+            .section_data(&buf, section.index as usize)
+            .ok_or_else(|| anyhow!("REL section {} has no data", section.index))?;
+        write_file(&args.output_dir.join(file), data)?;
+    }
+    let manifest_path = args
+        .output_dir
+        .join("manifest")
+        .with_extension(args.format.extension());
+    write_file(&manifest_path, manifest::write(&manifest, args.format)?)?;
+
+    Ok(())
+}
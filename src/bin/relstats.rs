@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::rel::Rel;
+use gamecube_tools::relstats;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Report relocation counts and DolphinNop runs for RELs", long_about = None)]
+struct RelStatsArgs {
+    /// Paths to REL files
+    #[arg(required = true)]
+    rels: Vec<PathBuf>,
+    /// Number of the longest DolphinNop runs to list
+    #[arg(long, default_value_t = 10)]
+    nop_runs: usize,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelStatsArgs::parse_from(args);
+    for (i, path) in args.rels.iter().enumerate() {
+        let buf = read_file(path)?;
+        let stats = Rel::parse(&buf)
+            .and_then(|rel| relstats::relocation_stats(&buf, &rel))
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+        if i > 0 {
+            println!();
+        }
+        print!(
+            "{}: {}",
+            path.to_string_lossy(),
+            stats.to_text(args.nop_runs)
+        );
+    }
+
+    Ok(())
+}
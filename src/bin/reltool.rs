@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::rel::RelFunction;
+use gamecube_tools::symbol_map;
+use gamecube_tools::{reledit, relmerge};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Edit prebuilt RELs", long_about = None)]
+struct RelToolArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Change a REL's module id, updating its imports against itself
+    SetId {
+        /// Path to input REL file
+        input: PathBuf,
+        /// New module id
+        #[arg(value_parser = symbol_map::parse_int)]
+        id: u32,
+        /// Path to output REL file. Defaults to modifying the input in place.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Retarget a REL's imports from one module id to another
+    Retarget {
+        /// Path to input REL file
+        input: PathBuf,
+        /// Module id the imports are currently against
+        #[arg(value_parser = symbol_map::parse_int)]
+        from: u32,
+        /// Module id to import from instead
+        #[arg(value_parser = symbol_map::parse_int)]
+        to: u32,
+        /// Path to output REL file. Defaults to modifying the input in place.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Merge two RELs into one module
+    Merge {
+        /// Path to the first REL, whose prolog, epilog, and unresolved functions are kept
+        first: PathBuf,
+        /// Path to the second REL
+        second: PathBuf,
+        /// Path to output REL file
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Module id of the merged REL [default: the first REL's id]
+        #[arg(long, value_parser = symbol_map::parse_int)]
+        id: Option<u32>,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = RelToolArgs::parse_from(args);
+    match args.command {
+        Command::SetId { input, id, output } => {
+            let mut buf = read_file(&input)?;
+            let changed = reledit::set_module_id(&mut buf, id)
+                .with_context(|| format!("cannot change id of {}", input.to_string_lossy()))?;
+            write_file(output.as_ref().unwrap_or(&input), &buf)?;
+            println!("Set module id to {id}, updating {changed} self imports");
+        }
+        Command::Retarget {
+            input,
+            from,
+            to,
+            output,
+        } => {
+            let mut buf = read_file(&input)?;
+            let changed = reledit::retarget_imports(&mut buf, from, to)
+                .with_context(|| format!("cannot retarget {}", input.to_string_lossy()))?;
+            write_file(output.as_ref().unwrap_or(&input), &buf)?;
+            println!("Retargeted {changed} imports from module {from} to module {to}");
+        }
+        Command::Merge {
+            first,
+            second,
+            output,
+            id,
+        } => {
+            let merged = relmerge::merge_rels(&read_file(&first)?, &read_file(&second)?, id)?;
+            write_file(&output, &merged.data)?;
+            let functions = [
+                ("prolog", merged.second_prolog),
+                ("epilog", merged.second_epilog),
+                ("unresolved", merged.second_unresolved),
+            ];
+            for (name, RelFunction { section, offset }) in functions {
+                if section != 0 {
+                    println!(
+                        "Second REL's {name} is at section {section}+{offset:#x} and must be \
+                         called from the first's"
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use gamecube_tools::dol::Dol;
+use gamecube_tools::rel::Rel;
+use gamecube_tools::wiiload;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Send a DOL or REL to a loader on a real GameCube", long_about = None)]
+struct SendArgs {
+    /// Path to the DOL or REL to send
+    input: PathBuf,
+    /// Where to send the file: a console's IP address or `tcp:host[:port]` to send it over a
+    /// Broadband Adapter, or a USB Gecko device, such as /dev/ttyUSB0 or COM3
+    target: String,
+    /// Send the file uncompressed, for loaders that can't inflate it
+    #[arg(long)]
+    no_compress: bool,
+    /// Arguments passed to the executable after its file name
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+/// Put a serial device in raw mode, so bytes are sent as they are instead of having newlines
+/// translated
+#[cfg(unix)]
+fn set_raw(device: &str) -> anyhow::Result<()> {
+    let flag = if cfg!(target_os = "linux") {
+        "-F"
+    } else {
+        "-f"
+    };
+    let status = std::process::Command::new("stty")
+        .args([flag, device, "raw", "-echo"])
+        .status()
+        .context("cannot run stty")?;
+    if !status.success() {
+        bail!("stty failed to put {device} in raw mode");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_raw(_device: &str) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Open a USB Gecko. It's a USB FIFO rather than a real serial port, so there's no baud rate to
+/// set.
+fn open_usb_gecko(device: &str) -> anyhow::Result<File> {
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .open(device)
+        .with_context(|| format!("cannot open USB Gecko at {device}"))?;
+    if file.metadata()?.file_type().is_file() {
+        bail!("{device} is a file, not a USB Gecko");
+    }
+    set_raw(device)?;
+    Ok(file)
+}
+
+/// Connect to a loader over the network, such as `192.168.1.20` or `tcp:gamecube:4299`
+fn connect(target: &str) -> Option<anyhow::Result<TcpStream>> {
+    let host = match target.strip_prefix("tcp:") {
+        Some(host) => host,
+        None if target.parse::<IpAddr>().is_ok() || target.parse::<SocketAddr>().is_ok() => target,
+        None => return None,
+    };
+    // SocketAddr puts IPv6 addresses in brackets, so the port isn't taken for part of them
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    let address = if let Ok(ip) = ip.parse::<IpAddr>() {
+        SocketAddr::new(ip, wiiload::PORT).to_string()
+    } else if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{}", wiiload::PORT)
+    };
+    Some(
+        TcpStream::connect(&address)
+            .with_context(|| format!("cannot connect to loader at {address}")),
+    )
+}
+
+fn check_executable(input: &Path, buf: &[u8]) -> anyhow::Result<()> {
+    let extension = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("dol") => {
+            let dol = Dol::parse(buf)?;
+            println!("Sending DOL with entry point {:08X}", dol.entry_point);
+        }
+        Some("rel") => {
+            let rel = Rel::parse(buf)?;
+            println!("Sending REL {}", rel.id);
+        }
+        _ => bail!("Expected a .dol or .rel file"),
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = SendArgs::parse_from(args);
+    let buf = read_file(&args.input)?;
+    check_executable(&args.input, &buf)
+        .with_context(|| format!("cannot send {}", args.input.to_string_lossy()))?;
+
+    let name = args
+        .input
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut loader_args = vec![name];
+    loader_args.extend(args.args);
+
+    let mut out: Box<dyn Write> = match connect(&args.target) {
+        Some(stream) => Box::new(stream?),
+        None => Box::new(open_usb_gecko(&args.target)?),
+    };
+    wiiload::send(&mut out, &buf, &loader_args, !args.no_compress)
+        .with_context(|| format!("cannot send to {}", args.target))?;
+    println!("Sent {} bytes to {}", buf.len(), args.target);
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use gamecube_tools::crash::{self, OsContext};
+use gamecube_tools::rel::{LoadedRel, Rel};
+use gamecube_tools::symbol_map::{self, SymbolMap};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Print a symbolized backtrace by walking the stack in a RAM dump",
+    long_about = None
+)]
+struct StackWalkArgs {
+    /// Path to a dump of MEM1, such as Dolphin's mem1.raw
+    input: PathBuf,
+    /// Stack pointer to start from. Defaults to r1 of the OS's current context.
+    #[arg(value_parser = parse_address)]
+    sp: Option<u32>,
+    /// Path to symbol map, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// A loaded REL, as `<path>@<address>` or `<path>@<address>,<bss address>`. May be repeated.
+    #[arg(long)]
+    rel: Vec<String>,
+}
+
+/// Parse an address, with or without a `0x` prefix
+fn parse_address(s: &str) -> anyhow::Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).with_context(|| format!("invalid address {s}"))
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn load_rel(arg: &str) -> anyhow::Result<LoadedRel> {
+    let (path, addresses) = arg
+        .rsplit_once('@')
+        .ok_or_else(|| anyhow!("expected <path>@<address> for --rel, got {arg}"))?;
+    let (address, bss_address) = match addresses.split_once(',') {
+        Some((address, bss)) => (parse_address(address)?, Some(parse_address(bss)?)),
+        None => (parse_address(addresses)?, None),
+    };
+    let path = Path::new(path);
+    let rel = Rel::parse(&read_file(path)?)
+        .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+    Ok(LoadedRel {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        rel,
+        address,
+        bss_address,
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = StackWalkArgs::parse_from(args);
+    let mem = read_file(&args.input)?;
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => SymbolMap::default(),
+    };
+    let rels = args
+        .rel
+        .iter()
+        .map(|arg| load_rel(arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let sp = match args.sp {
+        Some(sp) => sp,
+        None => {
+            let address = crash::read_word(&mem, crash::CURRENT_CONTEXT_POINTER)
+                .ok_or_else(|| anyhow!("Dump is too small to hold the OS globals"))?;
+            OsContext::from_dump(&mem, address)
+                .context("cannot find a stack pointer, so pass one")?
+                .gpr[1]
+        }
+    };
+    let frames = crash::walk_stack(&mem, sp);
+    if frames.is_empty() {
+        bail!("Stack pointer {sp:08X} is outside the dump");
+    }
+
+    // Each frame's LR save slot is filled in by the function it called, so it holds the address
+    // that function returns to
+    println!("     Frame     Return");
+    for (i, frame) in frames.iter().enumerate() {
+        let location = crash::describe(frame.lr_save, &symbols, &rels);
+        println!(
+            "#{i:<3} {:08X}  {:08X}  {}",
+            frame.address,
+            frame.lr_save,
+            location.as_deref().unwrap_or("?")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use gamecube_tools::dol::Dol;
+use gamecube_tools::safe_path;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Lay out a DOL on an SD card the way Swiss expects", long_about = None)]
+struct SwissPackArgs {
+    /// Path to the DOL to package
+    input: PathBuf,
+    /// Folder to lay the SD card out in, which is created if it doesn't exist
+    output_dir: PathBuf,
+    /// Name of the DOL on the card, without extension. Defaults to the input's name.
+    #[arg(short, long)]
+    name: Option<String>,
+    /// Folder on the card to put the DOL in, such as `apps/mymod`. Defaults to the card's root.
+    #[arg(short, long)]
+    dir: Option<PathBuf>,
+    /// Argument to pass to the DOL, written to a `.cli` file next to it. Can be repeated.
+    #[arg(short, long = "arg", allow_hyphen_values = true)]
+    args: Vec<String>,
+    /// Other file to copy next to the DOL, such as a REL it loads. Can be repeated.
+    #[arg(short, long)]
+    include: Vec<PathBuf>,
+    /// Also write the DOL as `ipl.dol` in the card's root, which iplboot and gekkoboot boot at
+    /// power on
+    #[arg(long)]
+    autoexec: bool,
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file<P>(p: P, data: &[u8]) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    std::fs::write(&p, data)
+        .with_context(|| format!("cannot write {}", p.as_ref().to_string_lossy()))
+}
+
+/// Write a DOL, and a `.cli` file listing its arguments one per line if there are any. No `.dcp`
+/// is written: it offers choices of arguments in Swiss's menu, which fixed `--arg`s don't need.
+fn write_dol(dir: &Path, name: &str, dol: &[u8], args: &[String]) -> anyhow::Result<()> {
+    let path = dir.join(format!("{name}.dol"));
+    write_file(&path, dol)?;
+    println!("Wrote {}", path.to_string_lossy());
+    if !args.is_empty() {
+        let path = dir.join(format!("{name}.cli"));
+        let cli: String = args.iter().map(|arg| format!("{arg}\n")).collect();
+        write_file(&path, cli.as_bytes())?;
+        println!("Wrote {}", path.to_string_lossy());
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = SwissPackArgs::parse_from(args);
+    let dol = read_file(&args.input)?;
+    Dol::parse(&dol).with_context(|| format!("cannot parse {}", args.input.to_string_lossy()))?;
+    for arg in &args.args {
+        ensure!(!arg.contains('\n'), "Argument {arg:?} contains a newline");
+    }
+    let name = match args.name {
+        Some(name) => name,
+        None => args
+            .input
+            .file_stem()
+            .context("Input has no file name")?
+            .to_string_lossy()
+            .into_owned(),
+    };
+    safe_path::check_name(&name).context("--name must be a file name, without a folder")?;
+
+    let dir = match &args.dir {
+        Some(dir) => {
+            ensure!(
+                dir.components().all(|c| matches!(c, Component::Normal(_))),
+                "--dir must be a folder within the card's root"
+            );
+            args.output_dir.join(dir)
+        }
+        None => args.output_dir.clone(),
+    };
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("cannot create {}", dir.to_string_lossy()))?;
+    write_dol(&dir, &name, &dol, &args.args)?;
+    for include in &args.include {
+        let file_name = include.file_name().context("Included file has no name")?;
+        safe_path::check_name(&file_name.to_string_lossy())?;
+        let path = dir.join(file_name);
+        write_file(&path, &read_file(include)?)?;
+        println!("Wrote {}", path.to_string_lossy());
+    }
+    if args.autoexec {
+        write_dol(&args.output_dir, "ipl", &dol, &args.args)?;
+    }
+    Ok(())
+}
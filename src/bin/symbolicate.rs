@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use gamecube_tools::demangle;
+use gamecube_tools::rel::{LoadedRel, Rel};
+use gamecube_tools::symbol_map::{self, SymbolMap};
+use gamecube_tools::symbolicate::{self, Location};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Print the symbol and offset of RAM addresses", long_about = None)]
+struct SymbolicateArgs {
+    /// Addresses to look up, such as those on a crash screen
+    #[arg(required = true, value_parser = parse_address)]
+    addresses: Vec<u32>,
+    /// Path to symbol map, in any format mapconv reads
+    #[arg(short, long)]
+    symbol_map: Option<PathBuf>,
+    /// A loaded REL, as `<path>@<address>` or `<path>@<address>,<bss address>`. May be repeated.
+    #[arg(long)]
+    rel: Vec<String>,
+    /// Show demangled C++ names next to mangled ones
+    #[arg(long)]
+    demangle: bool,
+}
+
+/// Parse an address, with or without a `0x` prefix
+fn parse_address(s: &str) -> anyhow::Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).with_context(|| format!("invalid address {s}"))
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn load_rel(arg: &str) -> anyhow::Result<LoadedRel> {
+    let (path, addresses) = arg
+        .rsplit_once('@')
+        .ok_or_else(|| anyhow!("expected <path>@<address> for --rel, got {arg}"))?;
+    let (address, bss_address) = match addresses.split_once(',') {
+        Some((address, bss)) => (parse_address(address)?, Some(parse_address(bss)?)),
+        None => (parse_address(addresses)?, None),
+    };
+    let path = Path::new(path);
+    let rel = Rel::parse(&read_file(path)?)
+        .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+    Ok(LoadedRel {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        rel,
+        address,
+        bss_address,
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = SymbolicateArgs::parse_from(args);
+    let symbols = match &args.symbol_map {
+        Some(path) => symbol_map::parse(&read_file(path)?)
+            .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?,
+        None => SymbolMap::default(),
+    };
+    let rels = args
+        .rel
+        .iter()
+        .map(|arg| load_rel(arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    for &address in &args.addresses {
+        match symbolicate::symbolicate(address, &symbols, &rels) {
+            Some(location @ Location::Symbol { symbol, .. }) if args.demangle => {
+                match demangle::demangle(&symbol.name) {
+                    Some(demangled) => println!("{address:08X}  {location} ({demangled})"),
+                    None => println!("{address:08X}  {location}"),
+                }
+            }
+            Some(location) => println!("{address:08X}  {location}"),
+            None => println!("{address:08X}  ?"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use gamecube_tools::manifest;
+use gamecube_tools::thp::{self, Thp, ThpAudio, ThpManifest, MANIFEST_NAME};
+use gamecube_tools::wav::Wav;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Extract and rebuild THP movies", long_about = None)]
+struct ThpArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Extract a movie's frames as JPEGs and its audio as a WAV
+    Unpack {
+        /// Path to input THP
+        input: PathBuf,
+        /// Directory to write the frames, audio.wav, and thp.toml to
+        output_dir: PathBuf,
+    },
+    /// Build a movie from a directory of JPEG frames, taken in name order, and optionally a WAV
+    Pack {
+        /// Directory of frames, as written by `unpack`
+        input_dir: PathBuf,
+        /// Path to output THP
+        output: PathBuf,
+        /// Frame rate, overriding thp.toml
+        #[arg(long)]
+        fps: Option<f32>,
+        /// Path to the audio, overriding thp.toml
+        #[arg(long)]
+        audio: Option<PathBuf>,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+fn unpack(input: &Path, output_dir: &Path) -> anyhow::Result<()> {
+    let thp = Thp::parse(&read_file(input)?)?;
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("cannot create directory {}", output_dir.to_string_lossy()))?;
+    for (i, frame) in thp.frames.iter().enumerate() {
+        write_file(&output_dir.join(format!("frame_{i:05}.jpg")), frame)?;
+    }
+    let mut manifest = ThpManifest {
+        fps: thp.fps,
+        audio: None,
+    };
+    if let Some(audio) = thp.audio {
+        let wav = Wav {
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            loop_points: None,
+        };
+        write_file(&output_dir.join("audio.wav"), wav.to_bytes()?)?;
+        manifest.audio = Some("audio.wav".to_string());
+    }
+    write_file(
+        &output_dir.join(MANIFEST_NAME),
+        manifest::write(&manifest, manifest::ManifestFormat::Toml)?,
+    )?;
+    println!(
+        "Extracted {} {}x{} frames at {} fps",
+        thp.frames.len(),
+        thp.width,
+        thp.height,
+        thp.fps
+    );
+    Ok(())
+}
+
+fn pack(
+    input_dir: &Path,
+    output: &Path,
+    fps: Option<f32>,
+    audio: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let manifest_path = input_dir.join(MANIFEST_NAME);
+    let manifest: Option<ThpManifest> = if manifest_path.exists() {
+        Some(manifest::read_file(&manifest_path)?)
+    } else {
+        None
+    };
+    let Some(fps) = fps.or(manifest.as_ref().map(|m| m.fps)) else {
+        bail!("pass --fps, or put a {MANIFEST_NAME} with the frame rate in the directory");
+    };
+    let audio_path = audio.or_else(|| {
+        manifest
+            .as_ref()
+            .and_then(|m| m.audio.as_ref())
+            .map(|a| input_dir.join(a))
+    });
+
+    let mut frame_paths = Vec::new();
+    for entry in std::fs::read_dir(input_dir)
+        .with_context(|| format!("cannot read directory {}", input_dir.to_string_lossy()))?
+    {
+        let path = entry?.path();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if matches!(extension.as_deref(), Some("jpg" | "jpeg")) {
+            frame_paths.push(path);
+        }
+    }
+    frame_paths.sort();
+    ensure!(
+        !frame_paths.is_empty(),
+        "{} has no JPEG frames",
+        input_dir.to_string_lossy()
+    );
+    let frames = frame_paths
+        .iter()
+        .map(read_file)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (width, height) = thp::jpeg_size(&frames[0])
+        .with_context(|| format!("cannot read {}", frame_paths[0].to_string_lossy()))?;
+
+    let audio = match &audio_path {
+        Some(path) => {
+            let wav = Wav::parse(&read_file(path)?)
+                .with_context(|| format!("cannot parse {}", path.to_string_lossy()))?;
+            Some(ThpAudio {
+                sample_rate: wav.sample_rate,
+                channels: wav.channels,
+            })
+        }
+        None => None,
+    };
+    let thp = Thp {
+        fps,
+        width,
+        height,
+        frames,
+        audio,
+    };
+    write_file(output, thp.to_bytes()?)?;
+    println!(
+        "Wrote {} {width}x{height} frames at {fps} fps",
+        thp.frames.len()
+    );
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = ThpArgs::parse_from(args);
+    match args.command {
+        Command::Unpack { input, output_dir } => unpack(&input, &output_dir)
+            .with_context(|| format!("cannot unpack {}", input.to_string_lossy()))?,
+        Command::Pack {
+            input_dir,
+            output,
+            fps,
+            audio,
+        } => pack(&input_dir, &output, fps, audio)
+            .with_context(|| format!("cannot build {}", output.to_string_lossy()))?,
+    }
+    Ok(())
+}
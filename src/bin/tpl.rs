@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gamecube_tools::gcimage;
+use gamecube_tools::tpl::{MipmapFilter, PaletteFormat, TextureFormat, Tpl, TplImage, WrapMode};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct TplArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the textures in a TPL file
+    Info {
+        /// Path to input TPL
+        input: PathBuf,
+    },
+    /// Convert PNGs to a TPL, one texture per image
+    Encode {
+        /// Path to output TPL
+        output: PathBuf,
+        /// Input PNGs
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Texture format: i4, i8, ia4, ia8, rgb565, rgb5a3, rgba8, cmpr, ci4, or ci8
+        #[arg(long, default_value = "rgb5a3", value_parser = TextureFormat::parse)]
+        format: TextureFormat,
+        /// Palette format for ci4 and ci8: rgb565, rgb5a3, or ia8
+        #[arg(long, default_value = "rgb5a3", value_parser = PaletteFormat::parse)]
+        palette_format: PaletteFormat,
+        /// Horizontal wrap mode: clamp, repeat, or mirror
+        #[arg(long, default_value = "clamp", value_parser = WrapMode::parse)]
+        wrap_s: WrapMode,
+        /// Vertical wrap mode: clamp, repeat, or mirror
+        #[arg(long, default_value = "clamp", value_parser = WrapMode::parse)]
+        wrap_t: WrapMode,
+        /// Generate a full mipmap chain, downscaling with this filter: box or lanczos
+        #[arg(long, value_parser = MipmapFilter::parse)]
+        mipmaps: Option<MipmapFilter>,
+    },
+    /// Convert the textures in a TPL to PNGs
+    Decode {
+        /// Path to input TPL
+        input: PathBuf,
+        /// Path to output PNG. With several textures, the index is added to the file name, e.g.
+        /// out_1.png.
+        output: PathBuf,
+    },
+}
+
+fn read_file<P>(p: P) -> anyhow::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    std::fs::read(&p).with_context(|| format!("cannot read {}", p.as_ref().to_string_lossy()))
+}
+
+fn write_file(path: &Path, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    std::fs::write(path, data).with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// `output` with `_<index>` added to the file name
+fn indexed_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}_{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{index}"),
+    };
+    output.with_file_name(name)
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = TplArgs::parse_from(args);
+    match args.command {
+        Command::Info { input } => {
+            let tpl = Tpl::parse(&read_file(&input)?)?;
+            for (i, image) in tpl.images.iter().enumerate() {
+                print!(
+                    "{i:>3} {:<7} {:>4}x{:<4} wrap {:?}/{:?} images {}",
+                    image.format.to_string(),
+                    image.width,
+                    image.height,
+                    image.wrap_s,
+                    image.wrap_t,
+                    image.image_count()
+                );
+                match &image.palette {
+                    Some(tlut) => println!(" palette {} x{}", tlut.format, tlut.len()),
+                    None => println!(),
+                }
+            }
+        }
+        Command::Encode {
+            output,
+            inputs,
+            format,
+            palette_format,
+            wrap_s,
+            wrap_t,
+            mipmaps,
+        } => {
+            let mut tpl = Tpl::default();
+            for input in &inputs {
+                let (rgba, width, height) = gcimage::read_png(&read_file(input)?)
+                    .with_context(|| format!("cannot decode {}", input.to_string_lossy()))?;
+                let mut image =
+                    TplImage::from_rgba(format, palette_format, &rgba, width, height, mipmaps)
+                        .with_context(|| format!("cannot encode {}", input.to_string_lossy()))?;
+                image.wrap_s = wrap_s;
+                image.wrap_t = wrap_t;
+                tpl.images.push(image);
+            }
+            write_file(&output, tpl.to_bytes()?)?;
+        }
+        Command::Decode { input, output } => {
+            let tpl = Tpl::parse(&read_file(&input)?)?;
+            for (i, image) in tpl.images.iter().enumerate() {
+                let rgba = image
+                    .to_rgba()
+                    .with_context(|| format!("cannot decode texture {i}"))?;
+                let png = gcimage::write_png(&rgba, image.width, image.height)?;
+                let path = if tpl.images.len() == 1 {
+                    output.clone()
+                } else {
+                    indexed_path(&output, i)
+                };
+                write_file(&path, png)?;
+            }
+        }
+    }
+
+    Ok(())
+}
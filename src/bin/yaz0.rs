@@ -0,0 +1,87 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use gamecube_tools::progress::{ProgressBar, ProgressIo};
+use gamecube_tools::yaz0::{self, CompressionLevel};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Yaz0Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compress a file with Yaz0
+    Compress {
+        /// Path to input file
+        input: PathBuf,
+        /// Path to output file
+        output: PathBuf,
+        /// Compression level: fast, default, or best
+        #[arg(long, default_value = "default", value_parser = CompressionLevel::parse)]
+        level: CompressionLevel,
+    },
+    /// Decompress a Yaz0 file
+    Decompress {
+        /// Path to input file
+        input: PathBuf,
+        /// Path to output file
+        output: PathBuf,
+    },
+}
+
+/// Open a file along with its size, for reporting progress through it
+fn open_file(path: &Path) -> anyhow::Result<(BufReader<File>, u64)> {
+    let file =
+        File::open(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+    let size = file.metadata()?.len();
+    Ok((BufReader::new(file), size))
+}
+
+fn create_file(path: &Path) -> anyhow::Result<BufWriter<File>> {
+    let file =
+        File::create(path).with_context(|| format!("cannot create {}", path.to_string_lossy()))?;
+    Ok(BufWriter::new(file))
+}
+
+fn main() -> anyhow::Result<()> {
+    run(std::env::args_os())
+}
+
+pub fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> anyhow::Result<()> {
+    let args = Yaz0Args::parse_from(args);
+    match args.command {
+        Command::Compress {
+            input,
+            output,
+            level,
+        } => {
+            let (reader, size) = open_file(&input)?;
+            let mut bar = ProgressBar::new("Compressing");
+            let mut progress = |done, total| bar.update(done, total);
+            let mut reader = ProgressIo::new(reader, 0, size, &mut progress);
+            yaz0::compress_stream(&mut reader, &mut create_file(&output)?, level)
+                .with_context(|| format!("cannot compress {}", input.to_string_lossy()))?;
+            bar.finish();
+        }
+        Command::Decompress { input, output } => {
+            let (reader, size) = open_file(&input)?;
+            let mut bar = ProgressBar::new("Decompressing");
+            let mut progress = |done, total| bar.update(done, total);
+            let mut reader = ProgressIo::new(reader, 0, size, &mut progress);
+            yaz0::decompress_stream(&mut reader, &mut create_file(&output)?)
+                .with_context(|| format!("cannot decompress {}", input.to_string_lossy()))?;
+            bar.finish();
+        }
+    }
+
+    Ok(())
+}
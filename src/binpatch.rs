@@ -0,0 +1,1102 @@
+// Binary patch formats used to distribute game patches: IPS and BPS, plus VCDIFF in `vcdiff`.
+// Patches are applied as streams, so a whole disc image never has to be held in memory.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::iso::fst::FstEntryKind;
+use crate::iso::{Iso, SystemFile};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+const VCDIFF_MAGIC: &[u8] = &[0xD6, 0xC3, 0xC4];
+
+/// Largest file an IPS patch can address
+pub const IPS_MAX_SIZE: usize = 1 << 24;
+/// A record at this offset would be read as the end of the patch
+const IPS_EOF_OFFSET: u32 = 0x454F46;
+/// Unchanged bytes between changes that are rewritten rather than starting a new record, which
+/// costs 5 bytes
+const IPS_MAX_GAP: usize = 5;
+/// Shortest run of a byte written as an RLE record
+const IPS_MIN_RUN: usize = 9;
+
+/// Size of the target windows matched against the source when creating a patch
+const WINDOW_SIZE: usize = 8 << 20;
+/// How far either side of a target window its matches are searched for in the source
+const SOURCE_MARGIN: u64 = 1 << 20;
+/// Length of the blocks of the source that are indexed for matching
+const BLOCK_SIZE: usize = 16;
+/// Shortest run of a byte that's written as a run rather than literally
+const MIN_RUN: usize = 32;
+
+/// Size of the chunks data is copied in
+pub(crate) const CHUNK_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+    Vcdiff,
+}
+
+/// Detect a patch's format from its magic
+pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+    if patch.starts_with(IPS_MAGIC) {
+        Some(PatchFormat::Ips)
+    } else if patch.starts_with(BPS_MAGIC) {
+        Some(PatchFormat::Bps)
+    } else if patch.starts_with(VCDIFF_MAGIC) {
+        Some(PatchFormat::Vcdiff)
+    } else {
+        None
+    }
+}
+
+/// Bytes written by one IPS record
+#[derive(Debug, Clone)]
+pub enum IpsData {
+    Bytes(Vec<u8>),
+    /// A byte repeated, for runs
+    Rle {
+        len: u16,
+        value: u8,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct IpsRecord {
+    pub offset: u32,
+    pub data: IpsData,
+}
+
+/// An IPS patch. Offsets are 24-bit, so only the first 16 MiB of a file can be patched.
+#[derive(Debug, Clone, Default)]
+pub struct Ips {
+    pub records: Vec<IpsRecord>,
+    /// Size to truncate the output to, from the extension some patchers write after `EOF`
+    pub truncate: Option<u32>,
+}
+
+/// Reader over a patch held in memory
+pub(crate) struct PatchReader<'a> {
+    pub buf: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    pub fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("Patch ends unexpectedly at {:#x}", self.pos))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn be(&mut self, len: usize) -> anyhow::Result<u32> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |value, &b| value << 8 | b as u32))
+    }
+
+    /// A BPS variable-length number
+    fn varint(&mut self) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        let mut shift = 1u64;
+        loop {
+            let byte = self.byte()?;
+            value = (byte as u64 & 0x7F)
+                .checked_mul(shift)
+                .and_then(|v| v.checked_add(value))
+                .ok_or_else(|| anyhow!("Number too large at {:#x}", self.pos))?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift <<= 7;
+            value += shift;
+        }
+    }
+}
+
+impl Ips {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Ips> {
+        ensure!(buf.starts_with(IPS_MAGIC), "Not an IPS patch");
+        let mut reader = PatchReader {
+            buf,
+            pos: IPS_MAGIC.len(),
+        };
+        let mut ips = Ips::default();
+        loop {
+            if reader.bytes(3)? == IPS_EOF {
+                break;
+            }
+            reader.pos -= 3;
+            let offset = reader.be(3)?;
+            let data = match reader.be(2)? {
+                0 => IpsData::Rle {
+                    len: reader.be(2)? as u16,
+                    value: reader.byte()?,
+                },
+                len => IpsData::Bytes(reader.bytes(len as usize)?.to_vec()),
+            };
+            ips.records.push(IpsRecord { offset, data });
+        }
+        if buf.len() - reader.pos >= 3 {
+            ips.truncate = Some(reader.be(3)?);
+        }
+        Ok(ips)
+    }
+
+    /// Find the records that turn `source` into `target`. Nearby changes share a record, and runs
+    /// of a byte become RLE records.
+    pub fn diff(source: &[u8], target: &[u8]) -> anyhow::Result<Ips> {
+        ensure!(
+            target.len() <= IPS_MAX_SIZE,
+            "IPS patches can't patch past 16 MiB, use BPS or VCDIFF instead"
+        );
+        let differs = |i: usize| source.get(i) != Some(&target[i]);
+        let mut ips = Ips::default();
+        let mut i = 0;
+        while i < target.len() {
+            if !differs(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            let mut end = i + 1;
+            loop {
+                while end < target.len() && differs(end) {
+                    end += 1;
+                }
+                let gap = (end..target.len().min(end + IPS_MAX_GAP + 1))
+                    .take_while(|&j| !differs(j))
+                    .count();
+                if gap <= IPS_MAX_GAP && end + gap < target.len() {
+                    end += gap;
+                } else {
+                    break;
+                }
+            }
+            ips.push_changes(target, start, end);
+            i = end;
+        }
+        if target.len() < source.len() {
+            ips.truncate = Some(target.len() as u32);
+        }
+        Ok(ips)
+    }
+
+    /// Add records writing `target[start..end]`
+    fn push_changes(&mut self, target: &[u8], start: usize, end: usize) {
+        let mut pending = start;
+        let mut i = start;
+        while i < end {
+            let run = target[i..end]
+                .iter()
+                .take_while(|&&b| b == target[i])
+                .count();
+            if run < IPS_MIN_RUN {
+                i += run;
+                continue;
+            }
+            self.push_bytes(target, pending, i);
+            for offset in (i..i + run).step_by(u16::MAX as usize) {
+                let len = (i + run - offset).min(u16::MAX as usize);
+                self.push_record(
+                    target,
+                    offset,
+                    IpsData::Rle {
+                        len: len as u16,
+                        value: target[i],
+                    },
+                );
+            }
+            i += run;
+            pending = i;
+        }
+        self.push_bytes(target, pending, end);
+    }
+
+    fn push_bytes(&mut self, target: &[u8], start: usize, end: usize) {
+        // One less than the maximum, leaving room for `push_record` to add a byte
+        for offset in (start..end).step_by(u16::MAX as usize - 1) {
+            let chunk_end = end.min(offset + u16::MAX as usize - 1);
+            let data = IpsData::Bytes(target[offset..chunk_end].to_vec());
+            self.push_record(target, offset, data);
+        }
+    }
+
+    /// Add a record, starting it a byte early if its offset would read as `EOF`
+    fn push_record(&mut self, target: &[u8], offset: usize, data: IpsData) {
+        if offset as u32 != IPS_EOF_OFFSET {
+            self.records.push(IpsRecord {
+                offset: offset as u32,
+                data,
+            });
+            return;
+        }
+        let mut bytes = vec![target[offset - 1]];
+        match data {
+            IpsData::Bytes(data) => bytes.extend(data),
+            IpsData::Rle { len, value } => {
+                bytes.push(value);
+                if len > 1 {
+                    self.records.push(IpsRecord {
+                        offset: offset as u32 + 1,
+                        data: IpsData::Rle {
+                            len: len - 1,
+                            value,
+                        },
+                    });
+                }
+            }
+        }
+        self.records.push(IpsRecord {
+            offset: offset as u32 - 1,
+            data: IpsData::Bytes(bytes),
+        });
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = IPS_MAGIC.to_vec();
+        for record in &self.records {
+            out.extend_from_slice(&record.offset.to_be_bytes()[1..]);
+            match &record.data {
+                IpsData::Bytes(bytes) => {
+                    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+                    out.extend_from_slice(bytes);
+                }
+                IpsData::Rle { len, value } => {
+                    out.extend_from_slice(&[0, 0]);
+                    out.extend_from_slice(&len.to_be_bytes());
+                    out.push(*value);
+                }
+            }
+        }
+        out.extend_from_slice(IPS_EOF);
+        if let Some(size) = self.truncate {
+            out.extend_from_slice(&size.to_be_bytes()[1..]);
+        }
+        out
+    }
+
+    /// Write the patch's records over `target`, which already holds a copy of the file being
+    /// patched. Truncation is left to the caller.
+    pub fn apply<W: Write + Seek>(&self, target: &mut W) -> anyhow::Result<()> {
+        for record in &self.records {
+            target.seek(SeekFrom::Start(record.offset as u64))?;
+            match &record.data {
+                IpsData::Bytes(bytes) => target.write_all(bytes)?,
+                IpsData::Rle { len, value } => target.write_all(&vec![*value; *len as usize])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A BPS patch. The patch's actions are decoded as it's applied.
+#[derive(Debug, Clone)]
+pub struct Bps<'a> {
+    pub source_size: u64,
+    pub target_size: u64,
+    pub metadata: &'a [u8],
+    actions: &'a [u8],
+    pub source_crc32: u32,
+    pub target_crc32: u32,
+}
+
+/// The output of a BPS patch, written front to back, which TargetCopy actions read back from
+struct BpsOutput<'a, W> {
+    target: &'a mut W,
+    pos: u64,
+    crc32: crc32fast::Hasher,
+}
+
+impl<W: Read + Write + Seek> BpsOutput<'_, W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.target.seek(SeekFrom::Start(self.pos))?;
+        self.target.write_all(bytes)?;
+        self.crc32.update(bytes);
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Copy `len` bytes from `from` in the output written so far. The source can overlap what's
+    /// being written, which repeats the bytes in between.
+    fn copy_within(&mut self, from: u64, len: u64) -> anyhow::Result<()> {
+        ensure!(
+            from < self.pos,
+            "TargetCopy reads past the output written so far"
+        );
+        let distance = self.pos - from;
+        if distance < len && distance < CHUNK_SIZE as u64 {
+            // The copy repeats the last `distance` bytes, so write whole repeats at a time
+            let mut pattern = vec![0; distance as usize];
+            self.target.seek(SeekFrom::Start(from))?;
+            self.target.read_exact(&mut pattern)?;
+            let block = pattern.repeat(CHUNK_SIZE / pattern.len());
+            let mut remaining = len;
+            while remaining > 0 {
+                let chunk = remaining.min(block.len() as u64) as usize;
+                self.write(&block[..chunk])?;
+                remaining -= chunk as u64;
+            }
+            return Ok(());
+        }
+
+        let mut buf = vec![0; distance.min(CHUNK_SIZE as u64) as usize];
+        let mut copied = 0;
+        while copied < len {
+            let chunk = (len - copied).min(buf.len() as u64) as usize;
+            self.target.seek(SeekFrom::Start(from + copied))?;
+            self.target.read_exact(&mut buf[..chunk])?;
+            self.write(&buf[..chunk])?;
+            copied += chunk as u64;
+        }
+        Ok(())
+    }
+}
+
+fn copy_from<R: Read + Seek, W: Read + Write + Seek>(
+    source: &mut R,
+    offset: u64,
+    len: u64,
+    output: &mut BpsOutput<W>,
+) -> anyhow::Result<()> {
+    source.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(CHUNK_SIZE as u64) as usize;
+        source
+            .read_exact(&mut buf[..chunk])
+            .context("Patch reads past the end of the source")?;
+        output.write(&buf[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    Ok(())
+}
+
+fn apply_offset(offset: u64, encoded: u64) -> anyhow::Result<u64> {
+    let delta = encoded >> 1;
+    let offset = if encoded & 1 != 0 {
+        offset.checked_sub(delta)
+    } else {
+        offset.checked_add(delta)
+    };
+    offset.ok_or_else(|| anyhow!("Copy offset out of range"))
+}
+
+/// CRC32 of a whole stream, read from the start
+fn stream_crc32<R: Read + Seek>(reader: &mut R) -> io::Result<(u64, u32)> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut crc32 = crc32fast::Hasher::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        crc32.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, crc32.finalize()))
+}
+
+impl<'a> Bps<'a> {
+    pub fn parse(buf: &'a [u8]) -> anyhow::Result<Bps<'a>> {
+        ensure!(buf.starts_with(BPS_MAGIC), "Not a BPS patch");
+        ensure!(buf.len() >= BPS_MAGIC.len() + 12, "BPS patch is truncated");
+        let footer = &buf[buf.len() - 12..];
+        let crc = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+        let patch_crc32 = crc32fast::hash(&buf[..buf.len() - 4]);
+        ensure!(
+            patch_crc32 == crc(8),
+            "BPS patch is corrupt: checksum {patch_crc32:08x}, expected {:08x}",
+            crc(8)
+        );
+
+        let mut reader = PatchReader {
+            buf: &buf[..buf.len() - 12],
+            pos: BPS_MAGIC.len(),
+        };
+        let source_size = reader.varint()?;
+        let target_size = reader.varint()?;
+        let metadata_size = reader.varint()?;
+        let metadata = reader.bytes(metadata_size as usize)?;
+        Ok(Bps {
+            source_size,
+            target_size,
+            metadata,
+            actions: &reader.buf[reader.pos..],
+            source_crc32: crc(0),
+            target_crc32: crc(4),
+        })
+    }
+
+    /// Write the patched file to `target`, which must be empty, reading from `source`. Both
+    /// the source and the result are checked against the patch's checksums.
+    pub fn apply<R: Read + Seek, W: Read + Write + Seek>(
+        &self,
+        source: &mut R,
+        target: &mut W,
+    ) -> anyhow::Result<()> {
+        let (source_size, source_crc32) = stream_crc32(source)?;
+        ensure!(
+            source_size == self.source_size && source_crc32 == self.source_crc32,
+            "Patch is for a different file: expected {} bytes with CRC32 {:08x}, got {} bytes \
+             with CRC32 {source_crc32:08x}",
+            self.source_size,
+            self.source_crc32,
+            source_size
+        );
+
+        let mut reader = PatchReader {
+            buf: self.actions,
+            pos: 0,
+        };
+        let mut output = BpsOutput {
+            target,
+            pos: 0,
+            crc32: crc32fast::Hasher::new(),
+        };
+        let mut source_offset = 0;
+        let mut target_offset = 0;
+        while !reader.is_empty() {
+            let action = reader.varint()?;
+            let len = (action >> 2) + 1;
+            ensure!(
+                output.pos + len <= self.target_size,
+                "Patch writes past the end of the target"
+            );
+            match action & 3 {
+                // SourceRead
+                0 => copy_from(source, output.pos, len, &mut output)?,
+                // TargetRead
+                1 => {
+                    let bytes = reader.bytes(len as usize)?;
+                    output.write(bytes)?;
+                }
+                // SourceCopy
+                2 => {
+                    source_offset = apply_offset(source_offset, reader.varint()?)?;
+                    copy_from(source, source_offset, len, &mut output)?;
+                    source_offset += len;
+                }
+                // TargetCopy
+                _ => {
+                    target_offset = apply_offset(target_offset, reader.varint()?)?;
+                    output.copy_within(target_offset, len)?;
+                    target_offset += len;
+                }
+            }
+        }
+
+        ensure!(
+            output.pos == self.target_size,
+            "Patch produced {} bytes, expected {}",
+            output.pos,
+            self.target_size
+        );
+        let target_crc32 = output.crc32.finalize();
+        if target_crc32 != self.target_crc32 {
+            bail!(
+                "Patched file has CRC32 {target_crc32:08x}, expected {:08x}",
+                self.target_crc32
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A range of the target that's expected at a different offset in the source, such as a file
+/// that moved when a disc image was rebuilt
+#[derive(Debug, Clone, Copy)]
+pub struct MovedRange {
+    pub target_offset: u64,
+    pub size: u64,
+    pub source_offset: u64,
+}
+
+/// Where the data at an offset in the target is expected in the source
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Expected {
+    pub source_offset: u64,
+    /// Offset in the target where the expectation changes
+    pub end: u64,
+    /// Whether the data is in a moved range
+    pub moved: bool,
+}
+
+/// Where the data at `offset` in the target is expected in the source, given `moved` sorted by
+/// target offset. Data outside the moved ranges is expected at the same offset.
+pub(crate) fn expected_source(moved: &[MovedRange], offset: u64) -> Expected {
+    let next = moved.partition_point(|r| r.target_offset <= offset);
+    match next.checked_sub(1).map(|i| &moved[i]) {
+        Some(range) if offset < range.target_offset + range.size => Expected {
+            source_offset: range.source_offset + (offset - range.target_offset),
+            end: range.target_offset + range.size,
+            moved: true,
+        },
+        _ => Expected {
+            source_offset: offset,
+            end: moved.get(next).map_or(u64::MAX, |r| r.target_offset),
+            moved: false,
+        },
+    }
+}
+
+/// Files and system files at different offsets in two disc images, matched by path, sorted by
+/// their offset in `target`
+pub fn moved_files<R: Read + Seek, S: Read + Seek>(
+    source: &mut Iso<R>,
+    target: &mut Iso<S>,
+) -> anyhow::Result<Vec<MovedRange>> {
+    let mut pairs = Vec::new();
+    for file in [SystemFile::Apploader, SystemFile::MainDol, SystemFile::Fst] {
+        let (source_offset, _) = source.system_file_range(file)?;
+        let (target_offset, size) = target.system_file_range(file)?;
+        pairs.push((target_offset, size, source_offset));
+    }
+    let source_paths = source.fst.paths();
+    for (path, entry) in target.fst.paths().iter().zip(&target.fst.entries) {
+        let FstEntryKind::File { offset, size } = entry.kind else {
+            continue;
+        };
+        let source_entry = source_paths
+            .iter()
+            .position(|p| p == path)
+            .map(|i| &source.fst.entries[i].kind);
+        if let Some(&FstEntryKind::File {
+            offset: source_offset,
+            ..
+        }) = source_entry
+        {
+            pairs.push((offset as u64, size as u64, source_offset as u64));
+        }
+    }
+
+    pairs.sort_unstable();
+    let mut moved: Vec<MovedRange> = Vec::new();
+    for (target_offset, size, source_offset) in pairs {
+        // Files sharing data are only matched once
+        let overlaps = moved
+            .last()
+            .is_some_and(|last| target_offset < last.target_offset + last.size);
+        if size == 0 || source_offset == target_offset || overlaps {
+            continue;
+        }
+        moved.push(MovedRange {
+            target_offset,
+            size,
+            source_offset,
+        });
+    }
+    Ok(moved)
+}
+
+/// Fill `buf` from `reader`, stopping early only at the end of the stream
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// An action being built up before it's written
+enum PendingAction {
+    None,
+    Copy { source_offset: u64, len: u64 },
+    Literal(Vec<u8>),
+}
+
+/// Writes the actions of a BPS patch, merging adjacent ones
+struct BpsWriter<'a, W> {
+    patch: &'a mut W,
+    crc32: crc32fast::Hasher,
+    output_pos: u64,
+    source_offset: u64,
+    target_offset: u64,
+    pending: PendingAction,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let bits = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(bits | 0x80);
+            return;
+        }
+        out.push(bits);
+        value -= 1;
+    }
+}
+
+/// A relative copy offset, as read by `apply_offset`
+fn write_offset(out: &mut Vec<u8>, from: u64, to: u64) {
+    match to.checked_sub(from) {
+        Some(delta) => write_varint(out, delta << 1),
+        None => write_varint(out, (from - to) << 1 | 1),
+    }
+}
+
+impl<W: Write> BpsWriter<'_, W> {
+    fn emit(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.crc32.update(bytes);
+        self.patch.write_all(bytes)
+    }
+
+    fn action(&mut self, kind: u64, len: u64, extra: &[u8]) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, (len - 1) << 2 | kind);
+        bytes.extend_from_slice(extra);
+        self.emit(&bytes)?;
+        self.output_pos += len;
+        Ok(())
+    }
+
+    fn copy(&mut self, source_offset: u64, len: u64) -> io::Result<()> {
+        if let PendingAction::Copy {
+            source_offset: start,
+            len: pending,
+        } = &mut self.pending
+            && *start + *pending == source_offset
+        {
+            *pending += len;
+            return Ok(());
+        }
+        self.flush()?;
+        self.pending = PendingAction::Copy { source_offset, len };
+        Ok(())
+    }
+
+    fn literal(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if !matches!(self.pending, PendingAction::Literal(_)) {
+            self.flush()?;
+            self.pending = PendingAction::Literal(Vec::new());
+        }
+        let PendingAction::Literal(pending) = &mut self.pending else {
+            unreachable!();
+        };
+        pending.extend_from_slice(bytes);
+        if pending.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match std::mem::replace(&mut self.pending, PendingAction::None) {
+            PendingAction::None => {}
+            // SourceRead
+            PendingAction::Copy { source_offset, len } if source_offset == self.output_pos => {
+                self.action(0, len, &[])?
+            }
+            // SourceCopy
+            PendingAction::Copy { source_offset, len } => {
+                let mut offset = Vec::new();
+                write_offset(&mut offset, self.source_offset, source_offset);
+                self.action(2, len, &offset)?;
+                self.source_offset = source_offset + len;
+            }
+            // TargetRead
+            PendingAction::Literal(bytes) => self.action(1, bytes.len() as u64, &bytes)?,
+        }
+        Ok(())
+    }
+
+    /// A run of a byte, written once and then repeated with TargetCopy
+    fn run(&mut self, byte: u8, len: usize) -> io::Result<()> {
+        self.literal(&[byte])?;
+        self.flush()?;
+        let start = self.output_pos - 1;
+        let mut offset = Vec::new();
+        write_offset(&mut offset, self.target_offset, start);
+        self.action(3, len as u64 - 1, &offset)?;
+        self.target_offset = start + len as u64 - 1;
+        Ok(())
+    }
+}
+
+/// Create a BPS patch from `source` to the first `target_size` bytes of `target`, written to
+/// `patch`, from the windows `diff_windows` matches
+pub fn create_bps<R: Read + Seek, T: Read, W: Write>(
+    source: &mut R,
+    target: &mut T,
+    target_size: u64,
+    moved: &[MovedRange],
+    patch: &mut W,
+) -> anyhow::Result<()> {
+    let (source_size, source_crc32) = stream_crc32(source)?;
+    let mut writer = BpsWriter {
+        patch,
+        crc32: crc32fast::Hasher::new(),
+        output_pos: 0,
+        source_offset: 0,
+        target_offset: 0,
+        pending: PendingAction::None,
+    };
+    let mut header = BPS_MAGIC.to_vec();
+    write_varint(&mut header, source_size);
+    write_varint(&mut header, target_size);
+    write_varint(&mut header, 0);
+    writer.emit(&header)?;
+
+    let mut target_crc32 = crc32fast::Hasher::new();
+    diff_windows(source, &mut target.take(target_size), moved, |window| {
+        target_crc32.update(window.data);
+        for op in &window.ops {
+            match *op {
+                DiffOp::Literal { start, len } => {
+                    writer.literal(&window.data[start..start + len])?
+                }
+                DiffOp::Run { byte, len } => writer.run(byte, len)?,
+                DiffOp::Copy { pos, len, .. } => {
+                    writer.copy(window.segment_start + pos as u64, len as u64)?
+                }
+            }
+        }
+        Ok(())
+    })?;
+    writer.flush()?;
+    ensure!(
+        writer.output_pos == target_size,
+        "Target is shorter than expected"
+    );
+
+    let mut footer = Vec::new();
+    footer.extend_from_slice(&source_crc32.to_le_bytes());
+    footer.extend_from_slice(&target_crc32.finalize().to_le_bytes());
+    writer.emit(&footer)?;
+    let patch_crc32 = writer.crc32.finalize();
+    writer.patch.write_all(&patch_crc32.to_le_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_segment<R: Read + Seek>(
+    reader: &mut R,
+    pos: u64,
+    size: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let mut segment = vec![0; size as usize];
+    reader.seek(SeekFrom::Start(pos))?;
+    reader
+        .read_exact(&mut segment)
+        .with_context(|| format!("Segment at {pos:#x} is past the end of the file"))?;
+    Ok(segment)
+}
+
+/// One step of building a window of the target
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum DiffOp {
+    /// Bytes of the window stored in the patch
+    Literal {
+        start: usize,
+        len: usize,
+    },
+    Run {
+        byte: u8,
+        len: usize,
+    },
+    /// Bytes copied from `pos` in the segment to `at` in the window
+    Copy {
+        pos: usize,
+        len: usize,
+        at: usize,
+    },
+}
+
+/// A window of the target, with the segment of the source it was matched against
+pub(crate) struct DiffWindow<'a> {
+    pub data: &'a [u8],
+    pub segment_start: u64,
+    pub segment: &'a [u8],
+    pub ops: Vec<DiffOp>,
+}
+
+fn block_hash(block: &[u8], bits: u32) -> usize {
+    let low = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let high = u64::from_le_bytes(block[8..BLOCK_SIZE].try_into().unwrap());
+    ((low ^ high.rotate_left(29)).wrapping_mul(0x9E3779B97F4A7C15) >> (64 - bits)) as usize
+}
+
+/// Match `window` against `segment`, where the window's start is expected at `delta`. Matches
+/// are found from an index of the segment's blocks, trying the continuation of the last match
+/// first, which is where unchanged data will be.
+fn match_window(segment: &[u8], window: &[u8], mut delta: isize) -> Vec<DiffOp> {
+    let bits = (segment.len() / BLOCK_SIZE * 2)
+        .max(2)
+        .next_power_of_two()
+        .ilog2();
+    let mut index = vec![u32::MAX; 1 << bits];
+    for pos in (0..segment.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        let slot = &mut index[block_hash(&segment[pos..pos + BLOCK_SIZE], bits)];
+        if *slot == u32::MAX {
+            *slot = pos as u32;
+        }
+    }
+
+    let mut ops = Vec::new();
+    let literal = |ops: &mut Vec<DiffOp>, start: usize, end: usize| {
+        if end > start {
+            ops.push(DiffOp::Literal {
+                start,
+                len: end - start,
+            });
+        }
+    };
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + BLOCK_SIZE <= window.len() {
+        let run = window[i..].iter().take_while(|&&b| b == window[i]).count();
+        if run >= MIN_RUN {
+            literal(&mut ops, literal_start, i);
+            ops.push(DiffOp::Run {
+                byte: window[i],
+                len: run,
+            });
+            i += run;
+            literal_start = i;
+            continue;
+        }
+
+        let block = &window[i..i + BLOCK_SIZE];
+        let matches = |pos: usize| segment.get(pos..pos + BLOCK_SIZE) == Some(block);
+        let expected = i.checked_add_signed(delta).filter(|&pos| matches(pos));
+        let found = expected.or_else(|| {
+            let pos = index[block_hash(block, bits)];
+            (pos != u32::MAX && matches(pos as usize)).then_some(pos as usize)
+        });
+        let Some(mut pos) = found else {
+            i += 1;
+            continue;
+        };
+
+        while i > literal_start && pos > 0 && segment[pos - 1] == window[i - 1] {
+            i -= 1;
+            pos -= 1;
+        }
+        let len = window[i..]
+            .iter()
+            .zip(&segment[pos..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        literal(&mut ops, literal_start, i);
+        ops.push(DiffOp::Copy { pos, len, at: i });
+        i += len;
+        literal_start = i;
+        delta = pos as isize - (i - len) as isize;
+    }
+    literal(&mut ops, literal_start, window.len());
+    ops
+}
+
+/// Split `target` into windows and match each against the source around where it's expected to
+/// be, at the same offset or where `moved` says, so data that's unchanged or moved a short
+/// distance is copied, and anything else is stored in the patch
+pub(crate) fn diff_windows<R: Read + Seek, T: Read>(
+    source: &mut R,
+    target: &mut T,
+    moved: &[MovedRange],
+    mut f: impl FnMut(DiffWindow) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let source_size = source.seek(SeekFrom::End(0))?;
+    let mut window = vec![0; WINDOW_SIZE];
+    let mut offset = 0u64;
+    loop {
+        let expected = expected_source(moved, offset);
+        let max_len = (WINDOW_SIZE as u64).min(expected.end - offset) as usize;
+        let len = read_full(target, &mut window[..max_len])?;
+        if len == 0 {
+            return Ok(());
+        }
+        let data = &window[..len];
+        // Moved files are matched closely, as they're only expected in one place
+        let margin = if expected.moved {
+            SOURCE_MARGIN.min(len as u64)
+        } else {
+            SOURCE_MARGIN
+        };
+        let segment_start = expected
+            .source_offset
+            .saturating_sub(margin)
+            .min(source_size);
+        let segment_end = (expected.source_offset + len as u64 + margin).min(source_size);
+        let segment = read_segment(source, segment_start, segment_end - segment_start)?;
+        let delta = expected.source_offset as isize - segment_start as isize;
+        f(DiffWindow {
+            data,
+            segment_start,
+            segment: &segment,
+            ops: match_window(&segment, data, delta),
+        })?;
+        offset += len as u64;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// Source and target pairs with the target edited, grown, shrunk, and with a block moved,
+    /// each with the moved ranges a disc image diff would pass
+    pub(crate) fn pairs() -> Vec<(Vec<u8>, Vec<u8>, Vec<MovedRange>)> {
+        let source = noise(0x30000, 1);
+
+        let mut edited = source.clone();
+        edited[0x10..0x20].fill(0);
+        edited[0x1000] ^= 0xFF;
+        edited[0x20000..0x20100].copy_from_slice(&noise(0x100, 2));
+
+        let mut grown = source.clone();
+        grown.extend_from_slice(&[0xAA; 0x500]);
+        grown.extend_from_slice(&noise(0x800, 3));
+
+        let shrunk = source[..0x12345].to_vec();
+
+        let mut moved = source.clone();
+        moved[0x28000..0x2C000].copy_from_slice(&source[0x8000..0xC000]);
+        let ranges = vec![MovedRange {
+            target_offset: 0x28000,
+            size: 0x4000,
+            source_offset: 0x8000,
+        }];
+
+        vec![
+            (source.clone(), source.clone(), Vec::new()),
+            (source.clone(), edited, Vec::new()),
+            (source.clone(), grown, Vec::new()),
+            (source.clone(), shrunk, Vec::new()),
+            (source.clone(), moved, ranges),
+            (Vec::new(), noise(0x100, 4), Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn ips_round_trips() {
+        for (source, target, _) in pairs() {
+            let patch = Ips::diff(&source, &target).unwrap().to_bytes();
+            assert_eq!(detect(&patch), Some(PatchFormat::Ips));
+            let ips = Ips::parse(&patch).unwrap();
+            let mut out = Cursor::new(source.clone());
+            ips.apply(&mut out).unwrap();
+            let mut out = out.into_inner();
+            if let Some(size) = ips.truncate {
+                out.truncate(size as usize);
+            }
+            assert_eq!(out, target);
+        }
+    }
+
+    #[test]
+    fn ips_encodes_runs() {
+        let source = vec![0; 0x100];
+        let mut target = source.clone();
+        target[0x40..0x80].fill(0x55);
+        let ips = Ips::diff(&source, &target).unwrap();
+        assert!(ips
+            .records
+            .iter()
+            .any(|r| matches!(r.data, IpsData::Rle { value: 0x55, .. })));
+    }
+
+    #[test]
+    fn ips_rejects_large_targets() {
+        assert!(Ips::diff(&[], &vec![0; IPS_MAX_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn bps_round_trips() {
+        for (source, target, moved) in pairs() {
+            let mut patch = Vec::new();
+            create_bps(
+                &mut Cursor::new(&source),
+                &mut target.as_slice(),
+                target.len() as u64,
+                &moved,
+                &mut patch,
+            )
+            .unwrap();
+            assert_eq!(detect(&patch), Some(PatchFormat::Bps));
+            let bps = Bps::parse(&patch).unwrap();
+            let mut out = Cursor::new(Vec::new());
+            bps.apply(&mut Cursor::new(&source), &mut out).unwrap();
+            assert_eq!(out.into_inner(), target);
+        }
+    }
+
+    #[test]
+    fn bps_checks_the_source() {
+        let (source, target, _) = pairs().swap_remove(1);
+        let mut patch = Vec::new();
+        create_bps(
+            &mut Cursor::new(&source),
+            &mut target.as_slice(),
+            target.len() as u64,
+            &[],
+            &mut patch,
+        )
+        .unwrap();
+        let mut other = source.clone();
+        other[0] ^= 1;
+        let bps = Bps::parse(&patch).unwrap();
+        let mut out = Cursor::new(Vec::new());
+        assert!(bps.apply(&mut Cursor::new(&other), &mut out).is_err());
+    }
+
+    #[test]
+    fn moved_ranges_are_found_in_the_source() {
+        let moved = [MovedRange {
+            target_offset: 0x100,
+            size: 0x10,
+            source_offset: 0x800,
+        }];
+        let before = expected_source(&moved, 0x80);
+        assert_eq!(
+            (before.source_offset, before.end, before.moved),
+            (0x80, 0x100, false)
+        );
+        let inside = expected_source(&moved, 0x108);
+        assert_eq!(
+            (inside.source_offset, inside.end, inside.moved),
+            (0x808, 0x110, true)
+        );
+        let after = expected_source(&moved, 0x110);
+        assert_eq!(
+            (after.source_offset, after.end, after.moved),
+            (0x110, u64::MAX, false)
+        );
+    }
+}
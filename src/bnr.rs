@@ -0,0 +1,404 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::gcimage::{decode_rgb5a3, encode_rgb5a3};
+
+pub const BANNER_WIDTH: usize = 96;
+pub const BANNER_HEIGHT: usize = 32;
+pub const BANNER_SIZE: usize = BANNER_WIDTH * BANNER_HEIGHT * 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BnrVersion {
+    /// Single metadata block (NTSC)
+    #[serde(rename = "BNR1")]
+    Bnr1,
+    /// Six metadata blocks, one per PAL language
+    #[serde(rename = "BNR2")]
+    Bnr2,
+}
+
+impl BnrVersion {
+    pub fn magic(&self) -> &'static [u8; 4] {
+        match self {
+            BnrVersion::Bnr1 => b"BNR1",
+            BnrVersion::Bnr2 => b"BNR2",
+        }
+    }
+
+    pub fn metadata_count(&self) -> usize {
+        match self {
+            BnrVersion::Bnr1 => 1,
+            BnrVersion::Bnr2 => 6,
+        }
+    }
+}
+
+impl Display for BnrVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BnrVersion::Bnr1 => "BNR1",
+            BnrVersion::Bnr2 => "BNR2",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Languages of the BNR2 metadata blocks, in file order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BnrLanguage {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Dutch,
+}
+
+impl BnrLanguage {
+    pub const ALL: [BnrLanguage; 6] = [
+        BnrLanguage::English,
+        BnrLanguage::German,
+        BnrLanguage::French,
+        BnrLanguage::Spanish,
+        BnrLanguage::Italian,
+        BnrLanguage::Dutch,
+    ];
+}
+
+impl Display for BnrLanguage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BnrLanguage::English => "English",
+            BnrLanguage::German => "German",
+            BnrLanguage::French => "French",
+            BnrLanguage::Spanish => "Spanish",
+            BnrLanguage::Italian => "Italian",
+            BnrLanguage::Dutch => "Dutch",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawBnrHeader {
+    magic: [u8; 4],
+    padding: [u8; 0x1C],
+    image: [u8; BANNER_SIZE],
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawBnrMetadata {
+    short_name: [u8; 0x20],
+    short_maker: [u8; 0x20],
+    long_name: [u8; 0x40],
+    long_maker: [u8; 0x40],
+    description: [u8; 0x80],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BnrMetadata {
+    pub short_name: String,
+    pub short_maker: String,
+    pub long_name: String,
+    pub long_maker: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BnrManifestEntry {
+    /// Required for BNR2 banners
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<BnrLanguage>,
+    #[serde(flatten)]
+    pub strings: BnrMetadata,
+}
+
+/// Banner version and strings in an editable form, written alongside the image by bnrunpack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BnrManifest {
+    pub version: BnrVersion,
+    /// Whether the strings are stored as Shift-JIS, as Japanese games' banners are
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shift_jis: bool,
+    pub metadata: Vec<BnrManifestEntry>,
+}
+
+impl BnrManifest {
+    /// Metadata blocks in file order. BNR2 languages missing from the manifest fall back to the
+    /// English strings.
+    pub fn metadata(&self) -> anyhow::Result<Vec<BnrMetadata>> {
+        match self.version {
+            BnrVersion::Bnr1 => {
+                let [entry] = &self.metadata[..] else {
+                    bail!(
+                        "BNR1 banners have exactly one metadata block, got {}",
+                        self.metadata.len()
+                    );
+                };
+                if entry.language.is_some_and(|l| l != BnrLanguage::English) {
+                    bail!("BNR1 banners only have English metadata");
+                }
+                Ok(vec![entry.strings.clone()])
+            }
+            BnrVersion::Bnr2 => {
+                let mut blocks: [Option<&BnrMetadata>; 6] = [None; 6];
+                for entry in &self.metadata {
+                    let language = entry
+                        .language
+                        .ok_or_else(|| anyhow!("BNR2 metadata blocks must specify a language"))?;
+                    let block = &mut blocks[language as usize];
+                    if block.is_some() {
+                        bail!("Duplicate {language} metadata");
+                    }
+                    *block = Some(&entry.strings);
+                }
+                let english = blocks[BnrLanguage::English as usize]
+                    .ok_or_else(|| anyhow!("BNR2 banners need English metadata"))?;
+                Ok(blocks
+                    .iter()
+                    .map(|block| block.unwrap_or(english).clone())
+                    .collect())
+            }
+        }
+    }
+}
+
+/// A parsed opening.bnr
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bnr {
+    pub version: BnrVersion,
+    /// 96x32 RGB5A3 image, in GameCube tile order
+    pub image: Vec<u8>,
+    /// Whether the strings are Shift-JIS rather than Latin-1
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shift_jis: bool,
+    pub metadata: Vec<BnrMetadata>,
+}
+
+/// Whether a game's banner strings are Shift-JIS, from its game code. Japanese games, with a `J`
+/// region as the code's last character, use Shift-JIS, and every other region Latin-1.
+pub fn uses_shift_jis(game_code: &[u8]) -> bool {
+    game_code.get(3) == Some(&b'J')
+}
+
+impl BnrMetadata {
+    fn to_raw(&self, shift_jis: bool) -> anyhow::Result<RawBnrMetadata> {
+        Ok(RawBnrMetadata {
+            short_name: encode_string(&self.short_name, "Short name", shift_jis)?,
+            short_maker: encode_string(&self.short_maker, "Short maker", shift_jis)?,
+            long_name: encode_string(&self.long_name, "Long name", shift_jis)?,
+            long_maker: encode_string(&self.long_maker, "Long maker", shift_jis)?,
+            description: encode_string(&self.description, "Description", shift_jis)?,
+        })
+    }
+}
+
+fn encode_string<const N: usize>(s: &str, field: &str, shift_jis: bool) -> anyhow::Result<[u8; N]> {
+    let bytes = if shift_jis {
+        let (bytes, _, unmappable) = encoding_rs::SHIFT_JIS.encode(s);
+        ensure!(
+            !unmappable,
+            "{field} '{s}' is not representable in Shift-JIS"
+        );
+        bytes.into_owned()
+    } else {
+        s.chars()
+            .map(|c| {
+                u8::try_from(c as u32).map_err(|_| {
+                    anyhow!("{field} contains '{c}', which is not representable in Latin-1")
+                })
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()?
+    };
+    // Leave room for the null terminator
+    ensure!(
+        bytes.len() < N,
+        "{field} '{s}' is longer than {} bytes",
+        N - 1
+    );
+    let mut buf = [0; N];
+    buf[..bytes.len()].copy_from_slice(&bytes);
+    Ok(buf)
+}
+
+fn decode_string(buf: &[u8], shift_jis: bool) -> String {
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    if shift_jis {
+        encoding_rs::SHIFT_JIS
+            .decode_without_bom_handling(&buf[..end])
+            .0
+            .into_owned()
+    } else {
+        buf[..end].iter().map(|&b| b as char).collect()
+    }
+}
+
+impl Bnr {
+    /// Parse a banner, decoding its strings as Shift-JIS or Latin-1. A banner doesn't record its
+    /// encoding, so it comes from the game, as with [`uses_shift_jis`].
+    pub fn parse(buf: &[u8], shift_jis: bool) -> anyhow::Result<Bnr> {
+        let (header, rest) = RawBnrHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("Banner is too small to contain a header"))?;
+        let version = match &header.magic {
+            b"BNR1" => BnrVersion::Bnr1,
+            b"BNR2" => BnrVersion::Bnr2,
+            magic => bail!("Unknown banner magic {:?}", String::from_utf8_lossy(magic)),
+        };
+        let (blocks, _) =
+            <[RawBnrMetadata]>::ref_from_prefix_with_elems(rest, version.metadata_count())
+                .map_err(|_| anyhow!("{version} banner is truncated"))?;
+
+        let metadata = blocks
+            .iter()
+            .map(|block| BnrMetadata {
+                short_name: decode_string(&block.short_name, shift_jis),
+                short_maker: decode_string(&block.short_maker, shift_jis),
+                long_name: decode_string(&block.long_name, shift_jis),
+                long_maker: decode_string(&block.long_maker, shift_jis),
+                description: decode_string(&block.description, shift_jis),
+            })
+            .collect();
+
+        Ok(Bnr {
+            version,
+            image: header.image.to_vec(),
+            shift_jis,
+            metadata,
+        })
+    }
+
+    /// Decode the banner image to 96x32 RGBA8
+    pub fn image_rgba(&self) -> Vec<u8> {
+        decode_rgb5a3(&self.image, BANNER_WIDTH, BANNER_HEIGHT)
+    }
+
+    /// Language of each metadata block, if the version has per-language blocks
+    pub fn languages(&self) -> impl Iterator<Item = Option<BnrLanguage>> + '_ {
+        (0..self.metadata.len()).map(|i| match self.version {
+            BnrVersion::Bnr1 => None,
+            BnrVersion::Bnr2 => BnrLanguage::ALL.get(i).copied(),
+        })
+    }
+
+    pub fn manifest(&self) -> BnrManifest {
+        BnrManifest {
+            version: self.version,
+            shift_jis: self.shift_jis,
+            metadata: self
+                .languages()
+                .zip(&self.metadata)
+                .map(|(language, strings)| BnrManifestEntry {
+                    language,
+                    strings: strings.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Build a banner from a 96x32 RGBA8 image
+    pub fn from_rgba(
+        version: BnrVersion,
+        rgba: &[u8],
+        metadata: Vec<BnrMetadata>,
+        shift_jis: bool,
+    ) -> anyhow::Result<Bnr> {
+        if rgba.len() != BANNER_WIDTH * BANNER_HEIGHT * 4 {
+            bail!("Banner image must be {BANNER_WIDTH}x{BANNER_HEIGHT}");
+        }
+        Ok(Bnr {
+            version,
+            image: encode_rgb5a3(rgba, BANNER_WIDTH, BANNER_HEIGHT),
+            shift_jis,
+            metadata,
+        })
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        if self.metadata.len() != self.version.metadata_count() {
+            bail!(
+                "{} banners need {} metadata blocks, got {}",
+                self.version,
+                self.version.metadata_count(),
+                self.metadata.len()
+            );
+        }
+        let header = RawBnrHeader {
+            magic: *self.version.magic(),
+            padding: [0; 0x1C],
+            image: self
+                .image
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("Banner image must be {BANNER_SIZE:#x} bytes"))?,
+        };
+        let mut buf = header.as_bytes().to_vec();
+        for (language, metadata) in self.languages().zip(&self.metadata) {
+            let block = metadata
+                .to_raw(self.shift_jis)
+                .with_context(|| match language {
+                    Some(language) => format!("Invalid {language} banner metadata"),
+                    None => "Invalid banner metadata".to_string(),
+                })?;
+            buf.extend_from_slice(block.as_bytes());
+        }
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(name: &str) -> Vec<BnrMetadata> {
+        vec![BnrMetadata {
+            short_name: name.to_string(),
+            ..Default::default()
+        }]
+    }
+
+    fn banner(name: &str, shift_jis: bool) -> anyhow::Result<Vec<u8>> {
+        let rgba = vec![0xFF; BANNER_WIDTH * BANNER_HEIGHT * 4];
+        Bnr::from_rgba(BnrVersion::Bnr1, &rgba, metadata(name), shift_jis)?.to_bytes()
+    }
+
+    #[test]
+    fn shift_jis_strings_round_trip() {
+        let buf = banner("ゼルダの伝説", true).unwrap();
+        let bnr = Bnr::parse(&buf, true).unwrap();
+        assert_eq!(bnr.metadata, metadata("ゼルダの伝説"));
+        assert!(bnr.manifest().shift_jis);
+    }
+
+    #[test]
+    fn latin1_strings_round_trip() {
+        let buf = banner("Pokémon", false).unwrap();
+        assert_eq!(
+            Bnr::parse(&buf, false).unwrap().metadata,
+            metadata("Pokémon")
+        );
+    }
+
+    #[test]
+    fn rejects_strings_outside_the_encoding() {
+        assert!(banner("ゼルダ", false).is_err());
+        assert!(banner("Ω€ \u{1F600}", true).is_err());
+        // Each kana takes two bytes, leaving room for 15 in the 32 byte short name
+        assert!(banner(&"ア".repeat(15), true).is_ok());
+        assert!(banner(&"ア".repeat(16), true).is_err());
+    }
+
+    #[test]
+    fn japanese_game_codes_use_shift_jis() {
+        assert!(uses_shift_jis(b"GALJ"));
+        assert!(!uses_shift_jis(b"GALE"));
+        assert!(!uses_shift_jis(b"GALP"));
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, ensure, Context};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::gcimage::{
+    encode_texture, max_image_count, MipmapFilter, PaletteFormat, TextureFormat, Tlut, WrapMode,
+};
+
+const ALIGNMENT: usize = 0x20;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawHeader {
+    format: u8,
+    alpha_mode: u8,
+    width: big_endian::U16,
+    height: big_endian::U16,
+    wrap_s: u8,
+    wrap_t: u8,
+    indexed: u8,
+    palette_format: u8,
+    palette_count: big_endian::U16,
+    /// Offsets are relative to the start of the header
+    palette_offset: big_endian::U32,
+    mipmaps_enabled: u8,
+    edge_lod: u8,
+    bias_clamp: u8,
+    max_anisotropy: u8,
+    min_filter: u8,
+    mag_filter: u8,
+    min_lod: i8,
+    max_lod: i8,
+    image_count: u8,
+    padding: u8,
+    /// LOD bias times 100
+    lod_bias: big_endian::I16,
+    data_offset: big_endian::U32,
+}
+
+/// How the game blends a texture's alpha
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum AlphaMode {
+    /// Alpha is ignored
+    #[default]
+    Opaque = 0,
+    /// Pixels are either fully opaque or fully transparent
+    Cutout = 1,
+    /// Alpha is blended
+    Translucent = 2,
+}
+
+impl AlphaMode {
+    /// The mode matching the alpha values in RGBA8 pixels
+    pub fn detect(rgba: &[u8]) -> AlphaMode {
+        let alpha = rgba.chunks_exact(4).map(|p| p[3]);
+        if alpha.clone().all(|a| a == 0xFF) {
+            AlphaMode::Opaque
+        } else if alpha.clone().all(|a| a == 0 || a == 0xFF) {
+            AlphaMode::Cutout
+        } else {
+            AlphaMode::Translucent
+        }
+    }
+}
+
+/// Linear filtering, the usual choice for both minification and magnification
+const FILTER_LINEAR: u8 = 1;
+/// Trilinear filtering
+const FILTER_LINEAR_MIPMAP_LINEAR: u8 = 5;
+
+/// A standalone texture, as used by J3D models and stored in archives alongside them
+#[derive(Debug, Clone)]
+pub struct Bti {
+    pub format: TextureFormat,
+    pub alpha_mode: AlphaMode,
+    pub width: usize,
+    pub height: usize,
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub mipmaps_enabled: bool,
+    pub edge_lod: bool,
+    pub bias_clamp: bool,
+    pub max_anisotropy: u8,
+    pub min_filter: u8,
+    pub mag_filter: u8,
+    /// Level of detail range, in eighths
+    pub min_lod: i8,
+    pub max_lod: i8,
+    /// Number of images, the full size texture followed by its mipmaps
+    pub image_count: u8,
+    pub lod_bias: f32,
+    /// Encoded texture data for every image
+    pub data: Vec<u8>,
+    /// Palette for the color indexed formats
+    pub palette: Option<Tlut>,
+}
+
+impl Bti {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Bti> {
+        let (raw, _) = RawHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("BTI is too small to contain a header"))?;
+        let format = TextureFormat::try_from(raw.format as u32)
+            .map_err(|_| anyhow!("BTI has unknown format {}", raw.format))?;
+        let (width, height) = (raw.width.get() as usize, raw.height.get() as usize);
+        let image_count = raw.image_count.max(1);
+        ensure!(
+            image_count as usize <= max_image_count(width, height),
+            "BTI has {image_count} mipmap levels, more than a {width}x{height} texture can have"
+        );
+        let size = format.mipmap_data_size(width, height, image_count as usize);
+        let start = raw.data_offset.get() as usize;
+        let data = buf
+            .get(start..start + size)
+            .ok_or_else(|| anyhow!("BTI data extends past end of file"))?;
+
+        let palette = if format.is_indexed() {
+            let palette_format = PaletteFormat::try_from(raw.palette_format as u32)
+                .map_err(|_| anyhow!("BTI has unknown palette format {}", raw.palette_format))?;
+            let start = raw.palette_offset.get() as usize;
+            let data = buf
+                .get(start..start + raw.palette_count.get() as usize * 2)
+                .ok_or_else(|| anyhow!("BTI palette extends past end of file"))?;
+            Some(Tlut {
+                format: palette_format,
+                data: data.to_vec(),
+            })
+        } else {
+            None
+        };
+
+        Ok(Bti {
+            format,
+            alpha_mode: AlphaMode::try_from(raw.alpha_mode).unwrap_or_default(),
+            width,
+            height,
+            wrap_s: WrapMode::try_from(raw.wrap_s as u32).unwrap_or_default(),
+            wrap_t: WrapMode::try_from(raw.wrap_t as u32).unwrap_or_default(),
+            mipmaps_enabled: raw.mipmaps_enabled != 0,
+            edge_lod: raw.edge_lod != 0,
+            bias_clamp: raw.bias_clamp != 0,
+            max_anisotropy: raw.max_anisotropy,
+            min_filter: raw.min_filter,
+            mag_filter: raw.mag_filter,
+            min_lod: raw.min_lod,
+            max_lod: raw.max_lod,
+            image_count,
+            lod_bias: raw.lod_bias.get() as f32 / 100.0,
+            data: data.to_vec(),
+            palette,
+        })
+    }
+
+    /// Encode RGBA8 pixels with default sampler settings. Color indexed formats get a palette
+    /// in `palette_format`. With a mipmap filter, a full mipmap chain is generated and the
+    /// texture is set up for trilinear filtering.
+    pub fn from_rgba(
+        format: TextureFormat,
+        palette_format: PaletteFormat,
+        rgba: &[u8],
+        width: usize,
+        height: usize,
+        mipmap_filter: Option<MipmapFilter>,
+    ) -> anyhow::Result<Bti> {
+        ensure!(
+            (1..=1024).contains(&width) && (1..=1024).contains(&height),
+            "Texture dimensions must be between 1 and 1024, got {width}x{height}"
+        );
+        let encoded = encode_texture(format, rgba, width, height, palette_format, mipmap_filter)?;
+        let mipmaps = encoded.image_count > 1;
+        Ok(Bti {
+            format,
+            alpha_mode: AlphaMode::detect(rgba),
+            width,
+            height,
+            wrap_s: WrapMode::default(),
+            wrap_t: WrapMode::default(),
+            mipmaps_enabled: mipmaps,
+            edge_lod: false,
+            bias_clamp: false,
+            max_anisotropy: 0,
+            min_filter: if mipmaps {
+                FILTER_LINEAR_MIPMAP_LINEAR
+            } else {
+                FILTER_LINEAR
+            },
+            mag_filter: FILTER_LINEAR,
+            min_lod: 0,
+            max_lod: ((encoded.image_count - 1) * 8) as i8,
+            image_count: encoded.image_count as u8,
+            lod_bias: 0.0,
+            data: encoded.data,
+            palette: encoded.tlut,
+        })
+    }
+
+    /// Decode the full size image to linear RGBA8
+    pub fn to_rgba(&self) -> anyhow::Result<Vec<u8>> {
+        self.format
+            .decode(&self.data, self.width, self.height, self.palette.as_ref())
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        // The palette, then the texture data, each aligned
+        let palette_offset = size_of::<RawHeader>();
+        let palette_data = self.palette.as_ref().map(|t| t.data.as_slice());
+        let palette_size = palette_data.map_or(0, |d| d.len().next_multiple_of(ALIGNMENT));
+        let data_offset = palette_offset + palette_size;
+
+        let header = RawHeader {
+            format: u32::from(self.format) as u8,
+            alpha_mode: self.alpha_mode.into(),
+            width: u16::try_from(self.width)
+                .context("Texture is too wide")?
+                .into(),
+            height: u16::try_from(self.height)
+                .context("Texture is too tall")?
+                .into(),
+            wrap_s: u32::from(self.wrap_s) as u8,
+            wrap_t: u32::from(self.wrap_t) as u8,
+            indexed: self.palette.is_some() as u8,
+            palette_format: self
+                .palette
+                .as_ref()
+                .map_or(0, |t| u32::from(t.format) as u8),
+            palette_count: u16::try_from(self.palette.as_ref().map_or(0, |t| t.len()))
+                .context("Palette has too many entries")?
+                .into(),
+            palette_offset: if palette_data.is_some() {
+                palette_offset as u32
+            } else {
+                0
+            }
+            .into(),
+            mipmaps_enabled: self.mipmaps_enabled as u8,
+            edge_lod: self.edge_lod as u8,
+            bias_clamp: self.bias_clamp as u8,
+            max_anisotropy: self.max_anisotropy,
+            min_filter: self.min_filter,
+            mag_filter: self.mag_filter,
+            min_lod: self.min_lod,
+            max_lod: self.max_lod,
+            image_count: self.image_count,
+            padding: 0,
+            lod_bias: ((self.lod_bias * 100.0).round() as i16).into(),
+            data_offset: (data_offset as u32).into(),
+        };
+
+        let mut buf = Vec::with_capacity(data_offset + self.data.len());
+        buf.extend_from_slice(header.as_bytes());
+        if let Some(palette) = palette_data {
+            buf.extend_from_slice(palette);
+            buf.resize(data_offset, 0);
+        }
+        buf.extend_from_slice(&self.data);
+        Ok(buf)
+    }
+}
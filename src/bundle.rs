@@ -0,0 +1,110 @@
+// Bundles of several files in one GCI payload, so a mod can ship its code and assets in a single
+// save. The layout is in `formats::bundle`, which loaders on the console can read from.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context};
+use zerocopy::IntoBytes;
+
+use crate::formats::bundle::{
+    self, BundleEntry, BundleHeader, BUNDLE_ALIGN, BUNDLE_MAGIC, MAX_ENTRY_NAME_SIZE,
+};
+use crate::safe_path;
+
+pub fn is_bundle(buf: &[u8]) -> bool {
+    buf.starts_with(&BUNDLE_MAGIC)
+}
+
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("cannot read {}", dir.to_string_lossy()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+        let relative = path.strip_prefix(root).unwrap();
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("{} isn't valid UTF-8", path.to_string_lossy()))?
+            .join("/");
+        files.push((name, path));
+    }
+    Ok(())
+}
+
+/// Every file under `dir`, in sorted order, named by its path relative to `dir` with `/`
+/// separators
+pub fn dir_files(dir: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    ensure!(!files.is_empty(), "{} has no files", dir.to_string_lossy());
+    Ok(files)
+}
+
+/// Pack named files into a bundle
+pub fn pack(files: &[(String, Vec<u8>)]) -> anyhow::Result<Vec<u8>> {
+    let header = BundleHeader {
+        magic: BUNDLE_MAGIC,
+        count: (files.len() as u32).into(),
+    };
+    let mut entries = Vec::with_capacity(files.len());
+    let mut data = Vec::new();
+    let data_start = (size_of::<BundleHeader>() + files.len() * size_of::<BundleEntry>())
+        .next_multiple_of(BUNDLE_ALIGN);
+    for (name, contents) in files {
+        ensure!(
+            name.len() <= MAX_ENTRY_NAME_SIZE,
+            "Name '{name}' is longer than {MAX_ENTRY_NAME_SIZE} bytes"
+        );
+        ensure!(
+            !entries
+                .iter()
+                .any(|e: &BundleEntry| e.name() == name.as_bytes()),
+            "Bundle already has a file named '{name}'"
+        );
+        let mut entry_name = [0; MAX_ENTRY_NAME_SIZE];
+        entry_name[..name.len()].copy_from_slice(name.as_bytes());
+        data.resize(data.len().next_multiple_of(BUNDLE_ALIGN), 0);
+        entries.push(BundleEntry {
+            name: entry_name,
+            offset: ((data_start + data.len()) as u32).into(),
+            size: (contents.len() as u32).into(),
+        });
+        data.extend_from_slice(contents);
+    }
+
+    let mut out = Vec::with_capacity(data_start + data.len());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(entries.as_bytes());
+    out.resize(data_start, 0);
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+/// The named files in a bundle. Names are checked to stay within the directory they're
+/// extracted to.
+pub fn unpack(buf: &[u8]) -> anyhow::Result<Vec<(String, &[u8])>> {
+    let entries = bundle::entries(buf).ok_or_else(|| anyhow!("Not a valid bundle"))?;
+    let mut files = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = std::str::from_utf8(entry.name())
+            .map_err(|_| anyhow!("Bundle has a file name that isn't valid UTF-8"))?;
+        safe_path::check_relative_path(name)
+            .with_context(|| format!("Bundle has a file with an invalid name '{name}'"))?;
+        let data = entry
+            .data(buf)
+            .ok_or_else(|| anyhow!("{name} extends past the end of the bundle"))?;
+        files.push((name.to_string(), data));
+    }
+    Ok(files)
+}
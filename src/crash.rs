@@ -0,0 +1,294 @@
+// Crash reports from the OSContext the OS saves when a game crashes, read from a RAM dump or from
+// the values on the crash screen, with every address resolved through symbol maps and RELs.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, ensure};
+
+use crate::demangle;
+use crate::rel::LoadedRel;
+use crate::symbol_map::SymbolMap;
+use crate::symbolicate::{self, Location};
+
+pub const MEM1_ADDRESS: u32 = 0x80000000;
+pub const MEM1_SIZE: u32 = 0x1800000;
+/// Where the OS keeps a pointer to the current thread's context
+pub const CURRENT_CONTEXT_POINTER: u32 = 0x800000d4;
+const OS_CONTEXT_SIZE: usize = 0x2c8;
+/// Frames walked before giving up on a stack that never terminates
+const MAX_STACK_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    pub address: u32,
+    pub back_chain: u32,
+    /// Return address into the function owning the frame, saved by the function it called
+    pub lr_save: u32,
+}
+
+/// Registers saved when a game crashed, and the stack frames printed alongside them
+#[derive(Debug, Clone, Default)]
+pub struct OsContext {
+    pub gpr: [u32; 32],
+    pub cr: u32,
+    pub lr: u32,
+    pub ctr: u32,
+    pub xer: u32,
+    pub srr0: u32,
+    pub srr1: u32,
+    pub dsisr: Option<u32>,
+    pub dar: Option<u32>,
+    pub exception: Option<u8>,
+    pub stack: Vec<StackFrame>,
+}
+
+pub fn exception_name(exception: u8) -> Option<&'static str> {
+    Some(match exception {
+        0 => "system reset",
+        1 => "machine check",
+        2 => "DSI",
+        3 => "ISI",
+        4 => "external interrupt",
+        5 => "alignment",
+        6 => "program",
+        7 => "floating point unavailable",
+        8 => "decrementer",
+        9 => "system call",
+        10 => "trace",
+        11 => "performance monitor",
+        12 => "breakpoint",
+        13 => "system interrupt",
+        14 => "thermal interrupt",
+        15 => "memory protection",
+        16 => "floating point exception",
+        _ => return None,
+    })
+}
+
+/// Offset of `address` in a dump of MEM1, accepting cached, uncached, and physical addresses
+pub fn mem1_offset(address: u32) -> Option<usize> {
+    let physical = address & 0x3fffffff;
+    (physical < MEM1_SIZE).then_some(physical as usize)
+}
+
+/// Read a big-endian word from a MEM1 dump
+pub fn read_word(mem: &[u8], address: u32) -> Option<u32> {
+    let offset = mem1_offset(address)?;
+    let bytes = mem.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Follow the back chain of stack frames starting at `sp`, as left by the EABI prologue. The walk
+/// stops at the OS's 0xFFFFFFFF terminator, at a null or unreadable frame, or when a back chain
+/// fails to move up the stack, which happens once it runs into garbage.
+pub fn walk_stack(mem: &[u8], sp: u32) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+    let mut address = sp;
+    while frames.len() < MAX_STACK_DEPTH && address.is_multiple_of(4) {
+        let (Some(back_chain), Some(lr_save)) =
+            (read_word(mem, address), read_word(mem, address + 4))
+        else {
+            break;
+        };
+        frames.push(StackFrame {
+            address,
+            back_chain,
+            lr_save,
+        });
+        if back_chain == 0 || back_chain == 0xffffffff || back_chain <= address {
+            break;
+        }
+        address = back_chain;
+    }
+    frames
+}
+
+fn parse_hex(token: &str) -> Option<u32> {
+    let token = token.trim_end_matches([',', ';', ')']);
+    let hex = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+impl OsContext {
+    /// Read an OSContext at `address` in a MEM1 dump, walking the stack from its r1
+    pub fn from_dump(mem: &[u8], address: u32) -> anyhow::Result<OsContext> {
+        let offset = mem1_offset(address)
+            .ok_or_else(|| anyhow!("Context address {address:08X} is outside MEM1"))?;
+        let context = mem
+            .get(offset..offset + OS_CONTEXT_SIZE)
+            .ok_or_else(|| anyhow!("Context at {address:08X} is past the end of the dump"))?;
+        let word =
+            |offset: usize| u32::from_be_bytes(context[offset..offset + 4].try_into().unwrap());
+        let mut gpr = [0; 32];
+        for (i, reg) in gpr.iter_mut().enumerate() {
+            *reg = word(i * 4);
+        }
+        Ok(OsContext {
+            stack: walk_stack(mem, gpr[1]),
+            gpr,
+            cr: word(0x80),
+            lr: word(0x84),
+            ctr: word(0x88),
+            xer: word(0x8c),
+            srr0: word(0x198),
+            srr1: word(0x19c),
+            ..Default::default()
+        })
+    }
+
+    /// Parse the registers and stack frames on a crash screen, as printed by OSDumpContext and
+    /// the default exception handler. Values are read from `name = value` pairs, so text
+    /// transcribed by hand works as long as each register is named.
+    pub fn parse_text(text: &str) -> anyhow::Result<OsContext> {
+        let mut context = OsContext::default();
+        let mut found_pc = false;
+        for line in text.lines() {
+            let line = line.replace('=', " = ");
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            // Stack frames: `0x80123450:   0x80123480    0x80004560`
+            if let [address, back_chain, lr_save, ..] = tokens.as_slice()
+                && let (Some(address), Some(back_chain), Some(lr_save)) = (
+                    address.strip_suffix(':').and_then(parse_hex),
+                    parse_hex(back_chain),
+                    parse_hex(lr_save),
+                )
+            {
+                context.stack.push(StackFrame {
+                    address,
+                    back_chain,
+                    lr_save,
+                });
+                continue;
+            }
+
+            for (i, token) in tokens.iter().enumerate() {
+                if token.eq_ignore_ascii_case("exception")
+                    && let Some(n) = tokens.get(i + 1).and_then(|n| n.parse().ok())
+                {
+                    context.exception = Some(n);
+                }
+                if tokens.get(i + 1) != Some(&"=") {
+                    continue;
+                }
+                let Some(value) = tokens.get(i + 2).and_then(|v| parse_hex(v)) else {
+                    continue;
+                };
+                let name = token.to_ascii_lowercase();
+                match name.as_str() {
+                    "lr" => context.lr = value,
+                    "cr" => context.cr = value,
+                    "ctr" => context.ctr = value,
+                    "xer" => context.xer = value,
+                    "srr0" | "pc" => {
+                        context.srr0 = value;
+                        found_pc = true;
+                    }
+                    "srr1" | "msr" => context.srr1 = value,
+                    "dsisr" => context.dsisr = Some(value),
+                    "dar" => context.dar = Some(value),
+                    _ => {
+                        if let Some(reg) = name
+                            .strip_prefix('r')
+                            .and_then(|n| n.parse::<usize>().ok())
+                            .filter(|&n| n < 32)
+                        {
+                            context.gpr[reg] = value;
+                        }
+                    }
+                }
+            }
+        }
+        ensure!(found_pc, "No SRR0 found in the crash text");
+        Ok(context)
+    }
+
+    /// Return addresses from the crash outwards: SRR0, LR, then the LR saves of each caller's
+    /// frame. The top frame's LR save slot belongs to whatever the crashed function last called,
+    /// so it's skipped.
+    pub fn backtrace(&self) -> Vec<(u32, &'static str)> {
+        let mut trace = vec![(self.srr0, "SRR0"), (self.lr, "LR")];
+        for frame in self.stack.iter().skip(1) {
+            if frame.lr_save == 0 || trace.last().is_some_and(|&(a, _)| a == frame.lr_save) {
+                continue;
+            }
+            trace.push((frame.lr_save, "stack"));
+        }
+        trace
+    }
+}
+
+/// Describe a MEM1 address by symbol or REL section, demangling C++ names
+pub fn describe(address: u32, map: &SymbolMap, rels: &[LoadedRel]) -> Option<String> {
+    if !(MEM1_ADDRESS..MEM1_ADDRESS + MEM1_SIZE).contains(&address) {
+        return None;
+    }
+    let location = symbolicate::symbolicate(address, map, rels)?;
+    Some(match &location {
+        Location::Symbol { symbol, .. } => match demangle::demangle(&symbol.name) {
+            Some(demangled) => format!("{location} ({demangled})"),
+            None => location.to_string(),
+        },
+        Location::RelSection { .. } => location.to_string(),
+    })
+}
+
+/// A readable report of where the game crashed and why
+pub fn report(context: &OsContext, map: &SymbolMap, rels: &[LoadedRel]) -> String {
+    let mut out = String::new();
+    let line = |address: u32| describe(address, map, rels).unwrap_or_else(|| "?".to_string());
+
+    if let Some(exception) = context.exception {
+        let name = exception_name(exception).unwrap_or("unknown");
+        writeln!(out, "Exception {exception} ({name})").unwrap();
+    }
+    writeln!(
+        out,
+        "Crashed at {:08X}  {}",
+        context.srr0,
+        line(context.srr0)
+    )
+    .unwrap();
+    if let Some(dar) = context.dar {
+        // DSISR bit 6 is set for stores
+        let access = match context.dsisr {
+            Some(dsisr) if dsisr & 0x02000000 != 0 => "writing",
+            Some(_) => "reading",
+            None => "accessing",
+        };
+        writeln!(out, "while {access} {dar:08X}").unwrap();
+    }
+
+    writeln!(out, "\nBacktrace:").unwrap();
+    for (i, (address, source)) in context.backtrace().into_iter().enumerate() {
+        writeln!(
+            out,
+            "  #{i:<2} {address:08X}  {:<5}  {}",
+            source,
+            line(address)
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\nRegisters:").unwrap();
+    for (i, &value) in context.gpr.iter().enumerate() {
+        let name = format!("r{i}");
+        match describe(value, map, rels) {
+            Some(location) => writeln!(out, "  {name:<4} = {value:08X}  {location}").unwrap(),
+            None => writeln!(out, "  {name:<4} = {value:08X}").unwrap(),
+        }
+    }
+    for (name, value) in [
+        ("LR", context.lr),
+        ("CTR", context.ctr),
+        ("CR", context.cr),
+        ("XER", context.xer),
+        ("SRR1", context.srr1),
+    ] {
+        writeln!(out, "  {name:<4} = {value:08X}").unwrap();
+    }
+    out
+}
@@ -0,0 +1,295 @@
+// Demangling of C++ symbol names, in both the Itanium scheme used by GCC and Clang and the older
+// scheme used by CodeWarrior, which built most GameCube games.
+
+/// Demangle a C++ symbol name. Returns `None` for names that aren't mangled, such as C functions.
+pub fn demangle(name: &str) -> Option<String> {
+    if name.starts_with("_Z") {
+        let symbol = cpp_demangle::Symbol::new(name).ok()?;
+        return Some(symbol.to_string());
+    }
+    demangle_codewarrior(name)
+}
+
+/// The display form of a symbol: the demangled name, or the name as is if it isn't mangled
+pub fn display_name(name: &str) -> String {
+    demangle(name).unwrap_or_else(|| name.to_string())
+}
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("__nwa", "operator new[]"),
+    ("__dla", "operator delete[]"),
+    ("__nw", "operator new"),
+    ("__dl", "operator delete"),
+    ("__apl", "operator+="),
+    ("__ami", "operator-="),
+    ("__amu", "operator*="),
+    ("__adv", "operator/="),
+    ("__amd", "operator%="),
+    ("__aer", "operator^="),
+    ("__aad", "operator&="),
+    ("__aor", "operator|="),
+    ("__ars", "operator>>="),
+    ("__als", "operator<<="),
+    ("__pl", "operator+"),
+    ("__mi", "operator-"),
+    ("__ml", "operator*"),
+    ("__dv", "operator/"),
+    ("__md", "operator%"),
+    ("__er", "operator^"),
+    ("__ad", "operator&"),
+    ("__or", "operator|"),
+    ("__co", "operator~"),
+    ("__nt", "operator!"),
+    ("__as", "operator="),
+    ("__lt", "operator<"),
+    ("__gt", "operator>"),
+    ("__ls", "operator<<"),
+    ("__rs", "operator>>"),
+    ("__eq", "operator=="),
+    ("__ne", "operator!="),
+    ("__le", "operator<="),
+    ("__ge", "operator>="),
+    ("__aa", "operator&&"),
+    ("__oo", "operator||"),
+    ("__pp", "operator++"),
+    ("__mm", "operator--"),
+    ("__cm", "operator,"),
+    ("__rm", "operator->*"),
+    ("__rf", "operator->"),
+    ("__cl", "operator()"),
+    ("__vc", "operator[]"),
+];
+
+/// Parser over the part of a CodeWarrior mangled name after the base name
+struct Parser<'a> {
+    s: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.s.strip_prefix(c) {
+            Some(rest) => {
+                self.s = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = self
+            .s
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.s.len());
+        let n = self.s[..len].parse().ok()?;
+        self.s = &self.s[len..];
+        Some(n)
+    }
+
+    /// A length-prefixed name, with any template arguments demangled
+    fn class_name(&mut self) -> Option<String> {
+        let len = self.number()?;
+        let name = self.s.get(..len)?;
+        self.s = &self.s[len..];
+        Some(demangle_template(name))
+    }
+
+    /// A class name, or a `Q`-qualified list of them
+    fn qualified_name(&mut self) -> Option<Vec<String>> {
+        if self.eat('Q') {
+            let count = self.s.get(..1)?.parse().ok()?;
+            self.s = &self.s[1..];
+            (0..count).map(|_| self.class_name()).collect()
+        } else if self.peek()?.is_ascii_digit() {
+            Some(vec![self.class_name()?])
+        } else {
+            None
+        }
+    }
+
+    /// Function parameters up to the end of the name or a `_` before a return type
+    fn parameters(&mut self) -> Option<String> {
+        let mut params = Vec::new();
+        while !self.s.is_empty() && self.peek() != Some('_') {
+            params.push(self.type_()?);
+        }
+        Some(match params[..] {
+            [ref only] if only == "void" => String::new(),
+            _ => params.join(", "),
+        })
+    }
+
+    /// A function type after its `F`: parameters, then the return type
+    fn function_type(&mut self, declarator: &str) -> Option<String> {
+        let params = self.parameters()?;
+        if !self.eat('_') {
+            return None;
+        }
+        let ret = self.type_()?;
+        Some(format!("{ret} ({declarator})({params})"))
+    }
+
+    fn type_(&mut self) -> Option<String> {
+        let c = self.peek()?;
+        if c.is_ascii_digit() || c == 'Q' {
+            return Some(self.qualified_name()?.join("::"));
+        }
+        self.s = &self.s[1..];
+        Some(match c {
+            'v' => "void".into(),
+            'b' => "bool".into(),
+            'c' => "char".into(),
+            's' => "short".into(),
+            'i' => "int".into(),
+            'l' => "long".into(),
+            'x' => "long long".into(),
+            'f' => "float".into(),
+            'd' => "double".into(),
+            'r' => "long double".into(),
+            'w' => "wchar_t".into(),
+            'e' => "...".into(),
+            'U' => format!("unsigned {}", self.type_()?),
+            'S' => format!("signed {}", self.type_()?),
+            'C' | 'V' => {
+                let qualifier = if c == 'C' { "const" } else { "volatile" };
+                let inner = self.type_()?;
+                if inner.ends_with(['*', '&']) {
+                    format!("{inner} {qualifier}")
+                } else {
+                    format!("{qualifier} {inner}")
+                }
+            }
+            'P' | 'R' => {
+                let declarator = if c == 'P' { "*" } else { "&" };
+                if self.eat('F') {
+                    self.function_type(declarator)?
+                } else {
+                    format!("{}{declarator}", self.type_()?)
+                }
+            }
+            'M' => {
+                let class = self.qualified_name()?.join("::");
+                let declarator = format!("{class}::*");
+                // Member function pointers include the const qualifier of the method
+                self.eat('C');
+                if self.eat('F') {
+                    self.function_type(&declarator)?
+                } else {
+                    format!("{} {declarator}", self.type_()?)
+                }
+            }
+            'A' => {
+                let len = self.number()?;
+                if !self.eat('_') {
+                    return None;
+                }
+                format!("{}[{len}]", self.type_()?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Demangle the arguments of a template name such as `TVec3<f>`, leaving it as is if they
+/// don't parse
+fn demangle_template(name: &str) -> String {
+    let Some((base, args)) = name
+        .split_once('<')
+        .and_then(|(base, rest)| Some((base, rest.strip_suffix('>')?)))
+    else {
+        return name.to_string();
+    };
+    let mut demangled = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices().chain([(args.len(), ',')]) {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                let arg = &args[start..i];
+                let mut parser = Parser { s: arg };
+                match parser.type_() {
+                    Some(t) if parser.s.is_empty() => demangled.push(t),
+                    // Non-type arguments, such as integers
+                    _ => demangled.push(arg.to_string()),
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    format!("{base}<{}>", demangled.join(", "))
+}
+
+/// Demangle the remainder after a base name: an optional class, then an optional function
+/// signature
+fn demangle_rest(base: &str, rest: &str) -> Option<String> {
+    let mut parser = Parser { s: rest };
+    let class = if parser.peek()? != 'F' {
+        Some(parser.qualified_name()?)
+    } else {
+        None
+    };
+    let is_const = parser.eat('C');
+    let params = if parser.eat('F') {
+        Some(parser.parameters()?)
+    } else {
+        None
+    };
+    if !parser.s.is_empty() || (params.is_none() && (class.is_none() || is_const)) {
+        return None;
+    }
+
+    let class_name = class.as_ref().and_then(|c| c.last()).map(|n| {
+        // Constructors and destructors are named after the class, without template arguments
+        n.split_once('<').map_or(n.as_str(), |(n, _)| n).to_string()
+    });
+    let name = match base {
+        "__ct" => class_name?,
+        "__dt" => format!("~{}", class_name?),
+        _ => match base.strip_prefix("__op") {
+            Some(type_) => {
+                let mut type_parser = Parser { s: type_ };
+                let type_ = type_parser.type_()?;
+                format!("operator {type_}")
+            }
+            None => OPERATORS
+                .iter()
+                .find(|(mangled, _)| *mangled == base)
+                .map_or_else(|| demangle_template(base), |(_, op)| op.to_string()),
+        },
+    };
+
+    let mut out = match &class {
+        Some(class) => format!("{}::{name}", class.join("::")),
+        None => name,
+    };
+    if let Some(params) = params {
+        out.push_str(&format!("({params})"));
+        if is_const {
+            out.push_str(" const");
+        }
+    }
+    Some(out)
+}
+
+/// Demangle a CodeWarrior mangled name, such as `update__9CGameLoopFf`. The base name is
+/// separated from the rest by the first `__` that leaves a valid remainder.
+fn demangle_codewarrior(name: &str) -> Option<String> {
+    // Special names such as `__ct` begin with `__` themselves
+    let search_start = if name.starts_with("__") { 2 } else { 1 };
+    let mut start = search_start;
+    while let Some(pos) = name.get(start..)?.find("__") {
+        let split = start + pos;
+        if let Some(demangled) = demangle_rest(&name[..split], &name[split + 2..]) {
+            return Some(demangled);
+        }
+        start = split + 1;
+    }
+    None
+}
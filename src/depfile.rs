@@ -0,0 +1,82 @@
+// Makefile-style dependency files, listing every input a conversion read so Make and Ninja
+// rebuild its outputs whenever one of them changes.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Outputs and the inputs they were built from
+#[derive(Debug, Clone, Default)]
+pub struct Rule {
+    pub targets: Vec<PathBuf>,
+    pub inputs: Vec<PathBuf>,
+}
+
+impl Rule {
+    pub fn new(target: &Path) -> Rule {
+        Rule {
+            targets: vec![target.to_path_buf()],
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn input(&mut self, path: &Path) {
+        if !self.inputs.iter().any(|p| p == path) {
+            self.inputs.push(path.to_path_buf());
+        }
+    }
+}
+
+/// Escape a path the way GCC does in its dependency files, which Make and Ninja both read
+fn escape(path: &Path) -> String {
+    let mut out = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            ' ' | '#' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '$' => out.push_str("$$"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn format(rules: &[Rule]) -> String {
+    let mut out = String::new();
+    for rule in rules {
+        let targets: Vec<String> = rule.targets.iter().map(|p| escape(p)).collect();
+        out.push_str(&targets.join(" "));
+        out.push(':');
+        for input in &rule.inputs {
+            out.push_str(" \\\n  ");
+            out.push_str(&escape(input));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn write(path: &Path, rules: &[Rule]) -> anyhow::Result<()> {
+    std::fs::write(path, format(rules))
+        .with_context(|| format!("cannot write {}", path.to_string_lossy()))
+}
+
+/// Where a depfile goes when `--emit-depfile` is given without a path: beside the output, with a
+/// `.d` extension
+pub fn default_path(output: &Path) -> PathBuf {
+    output.with_extension("d")
+}
+
+/// Write the rules for `--emit-depfile`: all to `path` if one was given, otherwise each to its own
+/// file beside its first target
+pub fn emit(path: Option<&Path>, mut rules: Vec<Rule>) -> anyhow::Result<()> {
+    rules.sort_by(|a, b| a.targets.cmp(&b.targets));
+    match path {
+        Some(path) => write(path, &rules),
+        None => rules.iter().try_for_each(|rule| {
+            write(&default_path(&rule.targets[0]), std::slice::from_ref(rule))
+        }),
+    }
+}
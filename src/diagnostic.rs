@@ -0,0 +1,103 @@
+// Errors and warnings from the converters, printed for people or, with `--message-format json`, as
+// one JSON record per line so IDEs and build servers can show them inline.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::elf2rel::Elf2RelError;
+use crate::gcipack::GciPackError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable name for the kind of problem, such as `unresolved-symbol`
+    pub code: &'static str,
+    pub message: String,
+    /// File the problem is in
+    pub file: Option<PathBuf>,
+    /// Symbol the problem is about
+    pub symbol: Option<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            message,
+            file: None,
+            symbol: None,
+        }
+    }
+
+    pub fn with_file(mut self, file: &Path) -> Diagnostic {
+        self.file = Some(file.to_path_buf());
+        self
+    }
+
+    pub fn with_symbol(mut self, symbol: impl Into<String>) -> Diagnostic {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    /// Describe an error that stopped a conversion, taking its code and symbol from the first
+    /// error in its chain that has them
+    pub fn from_error(error: &anyhow::Error) -> Diagnostic {
+        let (code, symbol) = error
+            .chain()
+            .find_map(|e| {
+                if let Some(e) = e.downcast_ref::<Elf2RelError>() {
+                    return Some((e.code(), e.symbol()));
+                }
+                e.downcast_ref::<GciPackError>().map(|e| (e.code(), None))
+            })
+            .unwrap_or(("error", None));
+        Diagnostic {
+            symbol: symbol.map(str::to_string),
+            ..Diagnostic::new(Severity::Error, code, format!("{error:#}"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl MessageFormat {
+    pub fn parse(name: &str) -> anyhow::Result<MessageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => anyhow::bail!("Unknown message format '{name}'"),
+        }
+    }
+
+    /// Print a diagnostic: as text on stderr, or as a JSON record on its own line on stdout
+    pub fn emit(self, diagnostic: &Diagnostic) {
+        match self {
+            MessageFormat::Human => eprintln!("{diagnostic}"),
+            MessageFormat::Json => println!("{}", serde_json::to_string(diagnostic).unwrap()),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Manifest for building every distribution artifact of a mod in one run, stamped with the same
+// version.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::gcipack::{self, GciPermissions, TextEncoding};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DistManifest {
+    /// Base name of every artifact
+    pub name: String,
+    /// Version stamped into artifact names, and substituted for `{version}` in GCI strings
+    pub version: String,
+    /// Folder to write artifacts to
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    /// Symbol map used to resolve symbol names in the patch list
+    #[serde(default)]
+    pub symbol_map: Option<PathBuf>,
+    /// Patch list compiled into the GCT and INI
+    #[serde(default)]
+    pub patches: Option<PathBuf>,
+    /// Gecko code lists added to the GCT and INI
+    #[serde(default)]
+    pub codes: Vec<PathBuf>,
+    /// Write a GCT of the patches and codes
+    #[serde(default)]
+    pub gct: bool,
+    #[serde(default)]
+    pub gci: Option<DistGci>,
+    #[serde(default)]
+    pub ini: Option<DistIni>,
+    #[serde(default)]
+    pub iso_patch: Option<DistIsoPatch>,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("dist")
+}
+
+/// Memory card file, packed like gcipack does
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DistGci {
+    pub input: PathBuf,
+    pub file_name: String,
+    pub title: String,
+    pub description: String,
+    /// Encoding of the title and description, `shift-jis` for Japanese saves
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    /// Left out for a save without a banner
+    #[serde(default)]
+    pub banner: Option<PathBuf>,
+    pub icon: PathBuf,
+    pub gamecode: String,
+    /// `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, or seconds since 2000-01-01. Defaults to
+    /// `SOURCE_DATE_EPOCH` if it's set, otherwise the current time.
+    #[serde(default, deserialize_with = "gcipack::deserialize_timestamp")]
+    pub timestamp: Option<u32>,
+    #[serde(default)]
+    pub permissions: GciPermissions,
+}
+
+/// Dolphin GameINI with the patches and codes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DistIni {
+    #[serde(default = "default_ini_section")]
+    pub section: String,
+    /// Turn every patch and code on by default
+    #[serde(default = "default_true")]
+    pub enable: bool,
+}
+
+fn default_ini_section() -> String {
+    "OnFrame".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistPatchFormat {
+    Ips,
+    Bps,
+    #[serde(alias = "xdelta")]
+    Vcdiff,
+}
+
+impl DistPatchFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DistPatchFormat::Ips => "ips",
+            DistPatchFormat::Bps => "bps",
+            DistPatchFormat::Vcdiff => "xdelta",
+        }
+    }
+}
+
+/// Patch from the original disc image to the modded one
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DistIsoPatch {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    #[serde(default = "default_patch_format")]
+    pub format: DistPatchFormat,
+    /// Drop everything after the modded image's last file
+    #[serde(default)]
+    pub trim: bool,
+}
+
+fn default_patch_format() -> DistPatchFormat {
+    DistPatchFormat::Bps
+}
+
+impl DistManifest {
+    /// Make the manifest's paths relative to the folder it's in
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        resolve(&mut self.output_dir);
+        self.symbol_map.iter_mut().for_each(resolve);
+        self.patches.iter_mut().for_each(resolve);
+        self.codes.iter_mut().for_each(resolve);
+        if let Some(gci) = &mut self.gci {
+            resolve(&mut gci.input);
+            gci.banner.iter_mut().for_each(resolve);
+            resolve(&mut gci.icon);
+        }
+        if let Some(iso_patch) = &mut self.iso_patch {
+            resolve(&mut iso_patch.source);
+            resolve(&mut iso_patch.target);
+        }
+    }
+
+    /// Path of an artifact, named after the mod and its version
+    pub fn artifact_path(&self, extension: &str) -> PathBuf {
+        self.output_dir
+            .join(format!("{}-{}.{extension}", self.name, self.version))
+    }
+
+    /// Fill in `{version}` in a string
+    pub fn stamp(&self, s: &str) -> String {
+        s.replace("{version}", &self.version)
+    }
+}
@@ -0,0 +1,392 @@
+use std::fmt::Display;
+
+use anyhow::{bail, ensure, Context};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use zerocopy::{FromZeros, IntoBytes};
+
+pub use crate::formats::dol::{DolHeader, DolSectionKind, DATA_SECTION_COUNT, TEXT_SECTION_COUNT};
+use crate::formats::Format;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DolSection {
+    pub kind: DolSectionKind,
+    /// Index within the text or data section table
+    pub index: usize,
+    pub offset: u32,
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl DolSection {
+    pub fn name(&self) -> String {
+        format!("{}{}", self.kind, self.index)
+    }
+
+    /// Address just past the section. `Dol::parse` and `Dol::add_section` reject sections that
+    /// would wrap past the end of the address space.
+    pub fn end_address(&self) -> u32 {
+        self.address + self.data.len() as u32
+    }
+
+    pub fn contains(&self, address: u32) -> bool {
+        (self.address..self.end_address()).contains(&address)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Dol {
+    pub sections: Vec<DolSection>,
+    pub bss_address: u32,
+    pub bss_size: u32,
+    pub entry_point: u32,
+}
+
+impl Dol {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Dol> {
+        let header = DolHeader::parse(buf)?;
+        let mut sections = Vec::new();
+        for (kind, index, offset, address, size) in header.sections() {
+            let start = offset as usize;
+            let end = start + size as usize;
+            ensure!(
+                end <= buf.len(),
+                "DOL section {kind}{index} (offset {offset:#x}, size {size:#x}) extends past end of file"
+            );
+            ensure!(
+                address.checked_add(size).is_some(),
+                "DOL section {kind}{index} (address {address:#010x}, size {size:#x}) extends past \
+                 the end of memory"
+            );
+            sections.push(DolSection {
+                kind,
+                index,
+                offset,
+                address,
+                data: buf[start..end].to_vec(),
+            });
+        }
+
+        Ok(Dol {
+            sections,
+            bss_address: header.bss_address.get(),
+            bss_size: header.bss_size.get(),
+            entry_point: header.entry_point.get(),
+        })
+    }
+
+    pub fn text_sections(&self) -> impl Iterator<Item = &DolSection> {
+        self.sections
+            .iter()
+            .filter(|s| s.kind == DolSectionKind::Text)
+    }
+
+    pub fn section_at(&self, address: u32) -> Option<&DolSection> {
+        self.sections.iter().find(|s| s.contains(address))
+    }
+
+    /// Add a section, in the first free slot of its kind. Its data is placed after the end of
+    /// the file.
+    pub fn add_section(
+        &mut self,
+        kind: DolSectionKind,
+        address: u32,
+        data: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let count = match kind {
+            DolSectionKind::Text => TEXT_SECTION_COUNT,
+            DolSectionKind::Data => DATA_SECTION_COUNT,
+        };
+        let index = (0..count)
+            .find(|&i| !self.sections.iter().any(|s| s.kind == kind && s.index == i))
+            .with_context(|| format!("DOL has no free {kind} section"))?;
+        let end = u32::try_from(data.len())
+            .ok()
+            .and_then(|size| address.checked_add(size))
+            .with_context(|| {
+                format!(
+                    "New section at {address:#010x} with size {:#x} extends past the end of memory",
+                    data.len()
+                )
+            })?;
+        if let Some(other) = self
+            .sections
+            .iter()
+            .find(|s| s.address < end && address < s.end_address())
+        {
+            bail!(
+                "New section at {address:#010x}..{end:#010x} overlaps {}",
+                other.name()
+            );
+        }
+        let offset = self
+            .sections
+            .iter()
+            .map(|s| s.offset + s.data.len() as u32)
+            .max()
+            .unwrap_or(size_of::<DolHeader>() as u32)
+            .next_multiple_of(32);
+        self.sections.push(DolSection {
+            kind,
+            index,
+            offset,
+            address,
+            data,
+        });
+        Ok(())
+    }
+
+    pub fn read_u32(&self, address: u32) -> anyhow::Result<u32> {
+        let section = self
+            .section_at(address)
+            .with_context(|| format!("Address {address:#010x} is not in any DOL section"))?;
+        let start = (address - section.address) as usize;
+        let bytes = section
+            .data
+            .get(start..start + 4)
+            .with_context(|| format!("Address {address:#010x} crosses end of section"))?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn write_u32(&mut self, address: u32, value: u32) -> anyhow::Result<()> {
+        self.write(address, &value.to_be_bytes())
+    }
+
+    /// Overwrite bytes at `address`, which must all be in one section
+    pub fn write(&mut self, address: u32, bytes: &[u8]) -> anyhow::Result<()> {
+        let section = self
+            .sections
+            .iter_mut()
+            .find(|s| s.contains(address))
+            .with_context(|| format!("Address {address:#010x} is not in any DOL section"))?;
+        let start = (address - section.address) as usize;
+        section
+            .data
+            .get_mut(start..start + bytes.len())
+            .with_context(|| format!("Address {address:#010x} crosses end of section"))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Serialize the DOL, with every section at its recorded file offset
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = DolHeader::new_zeroed();
+        let mut buf = vec![0; size_of::<DolHeader>()];
+        for section in &self.sections {
+            let size = section.data.len() as u32;
+            let (offsets, addresses, sizes) = match section.kind {
+                DolSectionKind::Text => (
+                    &mut header.text_offsets[..],
+                    &mut header.text_addresses[..],
+                    &mut header.text_sizes[..],
+                ),
+                DolSectionKind::Data => (
+                    &mut header.data_offsets[..],
+                    &mut header.data_addresses[..],
+                    &mut header.data_sizes[..],
+                ),
+            };
+            offsets[section.index] = section.offset.into();
+            addresses[section.index] = section.address.into();
+            sizes[section.index] = size.into();
+
+            let start = section.offset as usize;
+            let end = start + section.data.len();
+            if buf.len() < end {
+                buf.resize(end, 0);
+            }
+            buf[start..end].copy_from_slice(&section.data);
+        }
+        header.bss_address = self.bss_address.into();
+        header.bss_size = self.bss_size.into();
+        header.entry_point = self.entry_point.into();
+        buf[..size_of::<DolHeader>()].copy_from_slice(header.as_bytes());
+        buf
+    }
+}
+
+pub const MEM1_START: u32 = 0x80000000;
+pub const MEM1_END: u32 = 0x81800000;
+/// Start of memory not reserved for the OS globals and exception vectors
+pub const OS_RESERVED_END: u32 = 0x80003100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DolIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+fn ranges_overlap(a: (u32, u32), b: (u32, u32)) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+/// Check a DOL for structural problems that would prevent it from loading or running
+pub fn validate(buf: &[u8]) -> anyhow::Result<Vec<DolIssue>> {
+    let header = DolHeader::parse(buf)?;
+    let mut issues = Vec::new();
+    let mut issue = |severity, message| issues.push(DolIssue { severity, message });
+
+    let sections: Vec<_> = header.sections().collect();
+    for &(kind, index, offset, address, size) in &sections {
+        let name = format!("{kind}{index}");
+        let file_end = offset as u64 + size as u64;
+        if file_end > buf.len() as u64 {
+            issue(
+                Severity::Error,
+                format!(
+                    "{name} data ({offset:#x}..{file_end:#x}) extends past end of file ({:#x})",
+                    buf.len()
+                ),
+            );
+        }
+        if (offset as usize) < size_of::<DolHeader>() {
+            issue(
+                Severity::Error,
+                format!("{name} data at {offset:#x} overlaps the DOL header"),
+            );
+        }
+
+        let load_end = address as u64 + size as u64;
+        if address < MEM1_START || load_end > MEM1_END as u64 {
+            issue(
+                Severity::Error,
+                format!("{name} load range {address:#010x}..{load_end:#010x} is outside MEM1"),
+            );
+        } else if address < OS_RESERVED_END {
+            issue(
+                Severity::Warning,
+                format!("{name} loads at {address:#010x}, inside the OS reserved area"),
+            );
+        }
+
+        if !offset.is_multiple_of(32) || !address.is_multiple_of(32) {
+            issue(
+                Severity::Warning,
+                format!("{name} offset or address is not 32-byte aligned"),
+            );
+        }
+    }
+
+    // Check for overlapping load ranges between every pair of sections
+    for (i, a) in sections.iter().enumerate() {
+        for b in &sections[i + 1..] {
+            let range_a = (a.3, a.3.saturating_add(a.4));
+            let range_b = (b.3, b.3.saturating_add(b.4));
+            if ranges_overlap(range_a, range_b) {
+                issue(
+                    Severity::Error,
+                    format!(
+                        "{}{} ({:#010x}..{:#010x}) overlaps {}{} ({:#010x}..{:#010x})",
+                        a.0, a.1, range_a.0, range_a.1, b.0, b.1, range_b.0, range_b.1
+                    ),
+                );
+            }
+        }
+    }
+
+    let entry_point = header.entry_point.get();
+    let entry_in_text = sections.iter().any(|&(kind, _, _, address, size)| {
+        kind == DolSectionKind::Text
+            && (address as u64..address as u64 + size as u64).contains(&(entry_point as u64))
+    });
+    if !entry_in_text {
+        issue(
+            Severity::Error,
+            format!("Entry point {entry_point:#010x} is not inside a text section"),
+        );
+    }
+
+    let bss_address = header.bss_address.get();
+    let bss_size = header.bss_size.get();
+    if bss_size != 0 {
+        let bss_range = (bss_address, bss_address.saturating_add(bss_size));
+        if bss_range.0 < MEM1_START || bss_range.1 > MEM1_END {
+            issue(
+                Severity::Error,
+                format!(
+                    "bss range {:#010x}..{:#010x} is outside MEM1",
+                    bss_range.0, bss_range.1
+                ),
+            );
+        }
+        // Linkers commonly place small data sections inside the bss range, which is harmless
+        // unless startup code clears the whole range
+        for &(kind, index, _, address, size) in &sections {
+            let range = (address, address.saturating_add(size));
+            if ranges_overlap(bss_range, range) {
+                issue(
+                    Severity::Warning,
+                    format!(
+                        "bss range {:#010x}..{:#010x} overlaps {kind}{index} ({:#010x}..{:#010x})",
+                        bss_range.0, bss_range.1, range.0, range.1
+                    ),
+                );
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| std::cmp::Reverse(i.severity));
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dol(address: u32, size: u32) -> Vec<u8> {
+        let mut header = DolHeader::new_zeroed();
+        header.text_offsets[0] = (size_of::<DolHeader>() as u32).into();
+        header.text_addresses[0] = address.into();
+        header.text_sizes[0] = size.into();
+        let mut buf = header.as_bytes().to_vec();
+        buf.resize(buf.len() + size as usize, 0);
+        buf
+    }
+
+    #[test]
+    fn parses_sections() {
+        let dol = Dol::parse(&dol(0x80003100, 0x20)).unwrap();
+        assert_eq!(dol.sections.len(), 1);
+        assert_eq!(dol.sections[0].end_address(), 0x80003120);
+        assert!(dol.section_at(0x8000311C).is_some());
+    }
+
+    #[test]
+    fn rejects_sections_past_the_end_of_memory() {
+        assert!(Dol::parse(&dol(0xFFFFFFC0, 0x20)).is_ok());
+        assert!(Dol::parse(&dol(0xFFFFFFE0, 0x20)).is_err());
+        assert!(Dol::parse(&dol(0xFFFFFFF0, 0x20)).is_err());
+    }
+
+    #[test]
+    fn added_sections_must_fit_in_memory() {
+        let mut dol = Dol::parse(&dol(0x80003100, 0x20)).unwrap();
+        assert!(dol
+            .add_section(DolSectionKind::Data, 0xFFFFFFF0, vec![0; 0x20])
+            .is_err());
+        assert!(dol
+            .add_section(DolSectionKind::Data, 0x80003110, vec![0; 0x20])
+            .is_err());
+        dol.add_section(DolSectionKind::Data, 0x80004000, vec![1; 0x20])
+            .unwrap();
+        assert_eq!(Dol::parse(&dol.to_bytes()).unwrap().sections.len(), 2);
+    }
+}
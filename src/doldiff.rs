@@ -0,0 +1,193 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use crate::dol::{Dol, DolSection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DolChangeKind {
+    /// Bytes present in both DOLs with different values
+    Changed,
+    /// Bytes only loaded by the new DOL
+    Added,
+    /// Bytes only loaded by the old DOL
+    Removed,
+}
+
+impl Display for DolChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            DolChangeKind::Changed => "changed",
+            DolChangeKind::Added => "added",
+            DolChangeKind::Removed => "removed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DolChange {
+    pub kind: DolChangeKind,
+    pub address: u32,
+    pub size: u32,
+    /// Name of the section containing the change, in the new DOL if present there
+    pub section: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeaderChange {
+    pub field: &'static str,
+    pub old: u32,
+    pub new: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DolDiff {
+    pub header_changes: Vec<HeaderChange>,
+    pub changes: Vec<DolChange>,
+}
+
+fn section_at<'a>(sections: &[&'a DolSection], address: u32) -> Option<&'a DolSection> {
+    sections.iter().find(|s| s.contains(address)).copied()
+}
+
+/// The bytes of `section` loaded at `start..end`
+fn bytes_at(section: &DolSection, start: u32, end: u32) -> &[u8] {
+    &section.data[(start - section.address) as usize..(end - section.address) as usize]
+}
+
+fn push_change(
+    changes: &mut Vec<DolChange>,
+    kind: DolChangeKind,
+    address: u32,
+    size: u32,
+    section: &str,
+) {
+    if let Some(last) = changes.last_mut()
+        && last.kind == kind
+        && last.address + last.size == address
+        && last.section == section
+    {
+        last.size += size;
+        return;
+    }
+    changes.push(DolChange {
+        kind,
+        address,
+        size,
+        section: section.to_string(),
+    });
+}
+
+/// Compare two DOLs by load address rather than file offset
+pub fn doldiff(old_buf: &[u8], new_buf: &[u8]) -> anyhow::Result<DolDiff> {
+    let old = Dol::parse(old_buf)?;
+    let new = Dol::parse(new_buf)?;
+    let mut diff = DolDiff::default();
+
+    for (field, old_value, new_value) in [
+        ("entry point", old.entry_point, new.entry_point),
+        ("bss address", old.bss_address, new.bss_address),
+        ("bss size", old.bss_size, new.bss_size),
+    ] {
+        if old_value != new_value {
+            diff.header_changes.push(HeaderChange {
+                field,
+                old: old_value,
+                new: new_value,
+            });
+        }
+    }
+
+    // Walk the union of all loaded address ranges in order
+    let old_sections: Vec<&DolSection> = old.sections.iter().collect();
+    let new_sections: Vec<&DolSection> = new.sections.iter().collect();
+    let mut boundaries = BTreeSet::new();
+    for section in old_sections.iter().chain(&new_sections) {
+        boundaries.insert(section.address);
+        boundaries.insert(section.end_address());
+    }
+    let boundaries: Vec<u32> = boundaries.into_iter().collect();
+
+    for window in boundaries.windows(2) {
+        // Sections don't change within a window since every section edge is a boundary
+        let (start, end) = (window[0], window[1]);
+        let (kind, section) = match (
+            section_at(&old_sections, start),
+            section_at(&new_sections, start),
+        ) {
+            (Some(a), Some(b)) => {
+                // Each run of differing bytes is one change
+                let name = b.name();
+                let (old_bytes, new_bytes) = (bytes_at(a, start, end), bytes_at(b, start, end));
+                let mut i = 0;
+                while let Some(skip) =
+                    (i..old_bytes.len()).position(|j| old_bytes[j] != new_bytes[j])
+                {
+                    let run_start = i + skip;
+                    let run = (run_start..old_bytes.len())
+                        .take_while(|&j| old_bytes[j] != new_bytes[j])
+                        .count();
+                    let address = start + run_start as u32;
+                    push_change(
+                        &mut diff.changes,
+                        DolChangeKind::Changed,
+                        address,
+                        run as u32,
+                        &name,
+                    );
+                    i = run_start + run;
+                }
+                continue;
+            }
+            (None, Some(b)) => (DolChangeKind::Added, b),
+            (Some(a), None) => (DolChangeKind::Removed, a),
+            (None, None) => continue,
+        };
+        push_change(&mut diff.changes, kind, start, end - start, &section.name());
+    }
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::{FromZeros, IntoBytes};
+
+    use super::*;
+    use crate::dol::{DolHeader, DolSectionKind};
+
+    fn dol(sections: &[(DolSectionKind, u32, &[u8])]) -> Vec<u8> {
+        let mut dol = Dol::parse(DolHeader::new_zeroed().as_bytes()).unwrap();
+        for &(kind, address, data) in sections {
+            dol.add_section(kind, address, data.to_vec()).unwrap();
+        }
+        dol.to_bytes()
+    }
+
+    #[test]
+    fn groups_changed_bytes_into_runs() {
+        let old = dol(&[
+            (DolSectionKind::Text, 0x80003100, &[0, 1, 2, 3, 4, 5, 6, 7]),
+            (DolSectionKind::Data, 0x80004000, &[0; 4]),
+        ]);
+        let new = dol(&[
+            (DolSectionKind::Text, 0x80003100, &[0, 9, 9, 3, 4, 5, 9, 7]),
+            (DolSectionKind::Data, 0x80005000, &[0; 4]),
+        ]);
+        let diff = doldiff(&old, &new).unwrap();
+        let changes: Vec<_> = diff
+            .changes
+            .iter()
+            .map(|c| (c.kind, c.address, c.size, c.section.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (DolChangeKind::Changed, 0x80003101, 2, "text0"),
+                (DolChangeKind::Changed, 0x80003106, 1, "text0"),
+                (DolChangeKind::Removed, 0x80004000, 4, "data0"),
+                (DolChangeKind::Added, 0x80005000, 4, "data0"),
+            ]
+        );
+    }
+}
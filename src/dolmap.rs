@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use crate::dol::{Dol, DolSection};
+use crate::symbol_map::{Symbol, SymbolMap};
+
+const INS_BLR: u32 = 0x4E800020;
+const INS_RFI: u32 = 0x4C000064;
+const INS_MFLR_R0: u32 = 0x7C0802A6;
+const INS_STW_R0_4_R1: u32 = 0x90010004;
+
+fn is_stwu_r1_frame(ins: u32) -> bool {
+    // stwu r1, -N(r1)
+    ins & 0xFFFF0000 == 0x94210000 && ins & 0x8000 != 0
+}
+
+fn is_unconditional_branch(ins: u32) -> bool {
+    // b/ba (not bl), or blr/bctr
+    (ins >> 26 == 18 && ins & 1 == 0) || ins == INS_BLR || ins == 0x4E800420
+}
+
+fn is_block_terminator(ins: u32) -> bool {
+    ins == 0 || ins == INS_RFI || is_unconditional_branch(ins)
+}
+
+fn branch_target(ins: u32, address: u32) -> Option<u32> {
+    if ins >> 26 != 18 {
+        return None;
+    }
+    // Sign extend the 24-bit word offset
+    let offset = (((ins & 0x03FFFFFC) << 6) as i32) >> 6;
+    if ins & 2 != 0 {
+        Some(offset as u32)
+    } else {
+        Some(address.wrapping_add(offset as u32))
+    }
+}
+
+fn words(section: &DolSection) -> impl Iterator<Item = (u32, u32)> + '_ {
+    section.data.chunks_exact(4).enumerate().map(|(i, w)| {
+        (
+            section.address + i as u32 * 4,
+            u32::from_be_bytes(w.try_into().unwrap()),
+        )
+    })
+}
+
+fn read_word(section: &DolSection, address: u32) -> Option<u32> {
+    let start = address.checked_sub(section.address)? as usize;
+    let bytes = section.data.get(start..start + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn find_prologue_starts(section: &DolSection, starts: &mut BTreeSet<u32>) {
+    for (address, ins) in words(section) {
+        if !is_stwu_r1_frame(ins) {
+            continue;
+        }
+
+        // Walk back over the usual link register save sequence
+        let mut start = address;
+        for _ in 0..2 {
+            let Some(prev) = read_word(section, start.wrapping_sub(4)) else {
+                break;
+            };
+            if prev == INS_MFLR_R0 || prev == INS_STW_R0_4_R1 {
+                start -= 4;
+            } else {
+                break;
+            }
+        }
+
+        // Only accept frames that begin a new block, otherwise this is likely a mid-function
+        // stack adjustment
+        let begins_block = match read_word(section, start.wrapping_sub(4)) {
+            Some(prev) => is_block_terminator(prev),
+            None => true,
+        };
+        if begins_block {
+            starts.insert(start);
+        }
+    }
+}
+
+fn find_call_targets(dol: &Dol, section: &DolSection, starts: &mut BTreeSet<u32>) {
+    for (address, ins) in words(section) {
+        // Only calls (bl/bla) reliably point at function starts
+        if ins & 1 == 0 {
+            continue;
+        }
+        let Some(target) = branch_target(ins, address) else {
+            continue;
+        };
+        if dol.text_sections().any(|s| s.contains(target)) && target % 4 == 0 {
+            starts.insert(target);
+        }
+    }
+}
+
+fn function_size(section: &DolSection, start: u32, next_start: u32) -> u32 {
+    let end = next_start.min(section.end_address());
+
+    // Trim trailing zero padding between functions
+    let mut trimmed_end = end;
+    while trimmed_end > start && read_word(section, trimmed_end - 4) == Some(0) {
+        trimmed_end -= 4;
+    }
+    trimmed_end - start
+}
+
+/// Generate a skeleton symbol map for a DOL by scanning for function prologues and call targets
+pub fn dolmap(dol_buf: &[u8]) -> anyhow::Result<SymbolMap> {
+    let dol = Dol::parse(dol_buf)?;
+
+    let mut starts = BTreeSet::new();
+    if dol.text_sections().any(|s| s.contains(dol.entry_point)) {
+        starts.insert(dol.entry_point);
+    }
+    for section in dol.text_sections() {
+        find_prologue_starts(section, &mut starts);
+        find_call_targets(&dol, section, &mut starts);
+    }
+
+    let mut map = SymbolMap::default();
+    for section in dol.text_sections() {
+        let section_starts: Vec<u32> = starts
+            .range(section.address..section.end_address())
+            .copied()
+            .collect();
+        for (i, &start) in section_starts.iter().enumerate() {
+            let next_start = section_starts
+                .get(i + 1)
+                .copied()
+                .unwrap_or(section.end_address());
+            let size = function_size(section, start, next_start);
+            if size == 0 {
+                continue;
+            }
+            map.symbols.push(Symbol {
+                address: start,
+                name: format!("fn_{start:08X}"),
+                size: Some(size),
+                section: Some(".text".to_string()),
+            });
+        }
+    }
+    map.sort_by_address();
+
+    Ok(map)
+}
@@ -0,0 +1,48 @@
+use std::fmt::Write;
+
+use crate::gecko::{self, GeckoCode};
+use crate::patch::{Patch, PatchValue};
+
+/// Write patches as a Dolphin GameINI patch section (e.g. `OnFrame`), optionally followed by the
+/// matching `_Enabled` section so every patch is active by default
+pub fn write_patches(patches: &[Patch], section: &str, enable: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "[{section}]").unwrap();
+    for patch in patches {
+        writeln!(out, "${}", patch.name).unwrap();
+        for write in &patch.writes {
+            let (type_, value) = match write.value {
+                PatchValue::U8(v) => ("byte", v as u32),
+                PatchValue::U16(v) => ("word", v as u32),
+                PatchValue::U32(v) => ("dword", v),
+            };
+            writeln!(out, "0x{:08X}:{type_}:0x{value:08X}", write.address).unwrap();
+        }
+    }
+
+    if enable {
+        writeln!(out, "[{section}_Enabled]").unwrap();
+        for patch in patches {
+            writeln!(out, "${}", patch.name).unwrap();
+        }
+    }
+
+    out
+}
+
+/// Write Gecko codes as a Dolphin GameINI `[Gecko]` section, optionally followed by
+/// `[Gecko_Enabled]` so every code is active by default
+pub fn write_gecko_codes(codes: &[GeckoCode], enable: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "[Gecko]").unwrap();
+    out.push_str(&gecko::write_codes(codes));
+
+    if enable {
+        writeln!(out, "[Gecko_Enabled]").unwrap();
+        for code in codes {
+            writeln!(out, "${}", code.name).unwrap();
+        }
+    }
+
+    out
+}
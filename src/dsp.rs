@@ -0,0 +1,443 @@
+// The DSP-ADPCM codec and its `.dsp` file format. Audio is coded in frames of 14 samples, each
+// an 8 byte header and 4-bit residuals, predicted from the previous two samples with one of eight
+// coefficient pairs chosen per file.
+
+use anyhow::{ensure, Context};
+use zerocopy::byteorder::big_endian;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+pub const SAMPLES_PER_FRAME: usize = 14;
+pub const BYTES_PER_FRAME: usize = 8;
+const NIBBLES_PER_FRAME: usize = 16;
+pub const COEF_PAIRS: usize = 8;
+/// Rounds of refining the coefficients after each time they're split
+const REFINE_ROUNDS: usize = 16;
+
+/// Decoding state: the previous two samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct History {
+    pub yn1: i16,
+    pub yn2: i16,
+}
+
+/// Audio encoded as DSP-ADPCM, ready to be written as a `.dsp` file
+#[derive(Debug, Clone, Default)]
+pub struct Dsp {
+    pub sample_count: u32,
+    pub sample_rate: u32,
+    pub coefs: [[i16; 2]; COEF_PAIRS],
+    /// First and last sample of the loop, inclusive
+    pub loop_points: Option<(u32, u32)>,
+    pub data: Vec<u8>,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct DspHeader {
+    sample_count: big_endian::U32,
+    nibble_count: big_endian::U32,
+    sample_rate: big_endian::U32,
+    loop_flag: big_endian::U16,
+    format: big_endian::U16,
+    loop_start: big_endian::U32,
+    loop_end: big_endian::U32,
+    current_address: big_endian::U32,
+    coefs: [big_endian::I16; 16],
+    gain: big_endian::U16,
+    ps: big_endian::U16,
+    yn1: big_endian::I16,
+    yn2: big_endian::I16,
+    loop_ps: big_endian::U16,
+    loop_yn1: big_endian::I16,
+    loop_yn2: big_endian::I16,
+    padding: [u8; 22],
+}
+
+/// Nibble address of a sample, counting each frame's header as two nibbles
+pub fn sample_to_nibble(sample: u32) -> u32 {
+    let frame = sample / SAMPLES_PER_FRAME as u32;
+    frame * NIBBLES_PER_FRAME as u32 + sample % SAMPLES_PER_FRAME as u32 + 2
+}
+
+pub fn nibble_to_sample(nibble: u32) -> u32 {
+    let frame = nibble / NIBBLES_PER_FRAME as u32;
+    frame * SAMPLES_PER_FRAME as u32 + (nibble % NIBBLES_PER_FRAME as u32).saturating_sub(2)
+}
+
+fn decode_sample(nibble: i32, scale: u8, coefs: [i16; 2], history: &mut History) -> i16 {
+    let prediction = coefs[0] as i32 * history.yn1 as i32 + coefs[1] as i32 * history.yn2 as i32;
+    let sample = ((nibble << scale << 11) + 1024 + prediction) >> 11;
+    let sample = sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    history.yn2 = history.yn1;
+    history.yn1 = sample;
+    sample
+}
+
+/// Decode `sample_count` samples of DSP-ADPCM data
+pub fn decode(
+    data: &[u8],
+    coefs: &[[i16; 2]; COEF_PAIRS],
+    sample_count: usize,
+    history: &mut History,
+) -> Vec<i16> {
+    let mut samples = Vec::with_capacity(sample_count);
+    for frame in data.chunks(BYTES_PER_FRAME) {
+        let pair = coefs[(frame[0] >> 4) as usize & 7];
+        let scale = frame[0] & 0xf;
+        for &byte in &frame[1..] {
+            for nibble in [byte >> 4, byte & 0xf] {
+                if samples.len() == sample_count {
+                    return samples;
+                }
+                // Sign extend
+                let nibble = ((nibble as i32) << 28) >> 28;
+                samples.push(decode_sample(nibble, scale, pair, history));
+            }
+        }
+    }
+    samples
+}
+
+/// Statistics of a frame that give the squared prediction error of any coefficient pair
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameStats {
+    /// Sums of x[n]², x[n-1]², x[n-2]², x[n]x[n-1], x[n]x[n-2], x[n-1]x[n-2]
+    r00: f64,
+    r11: f64,
+    r22: f64,
+    r01: f64,
+    r02: f64,
+    r12: f64,
+}
+
+impl FrameStats {
+    fn add(&mut self, other: &FrameStats) {
+        self.r00 += other.r00;
+        self.r11 += other.r11;
+        self.r22 += other.r22;
+        self.r01 += other.r01;
+        self.r02 += other.r02;
+        self.r12 += other.r12;
+    }
+
+    fn error(&self, [a1, a2]: [f64; 2]) -> f64 {
+        self.r00 - 2.0 * (a1 * self.r01 + a2 * self.r02)
+            + a1 * a1 * self.r11
+            + 2.0 * a1 * a2 * self.r12
+            + a2 * a2 * self.r22
+    }
+
+    /// The coefficients with the least squared error
+    fn solve(&self) -> [f64; 2] {
+        let det = self.r11 * self.r22 - self.r12 * self.r12;
+        if det > 1e-9 * self.r11 * self.r22 {
+            [
+                (self.r01 * self.r22 - self.r02 * self.r12) / det,
+                (self.r02 * self.r11 - self.r01 * self.r12) / det,
+            ]
+        } else if self.r11 > 0.0 {
+            [self.r01 / self.r11, 0.0]
+        } else {
+            [0.0, 0.0]
+        }
+    }
+}
+
+fn frame_stats(samples: &[i16]) -> Vec<FrameStats> {
+    let sample = |i: isize| {
+        if i < 0 {
+            0.0
+        } else {
+            samples[i as usize] as f64
+        }
+    };
+    (0..samples.len())
+        .step_by(SAMPLES_PER_FRAME)
+        .map(|start| {
+            let mut stats = FrameStats::default();
+            let end = (start + SAMPLES_PER_FRAME).min(samples.len());
+            for i in start as isize..end as isize {
+                let (x0, x1, x2) = (sample(i), sample(i - 1), sample(i - 2));
+                stats.r00 += x0 * x0;
+                stats.r11 += x1 * x1;
+                stats.r22 += x2 * x2;
+                stats.r01 += x0 * x1;
+                stats.r02 += x0 * x2;
+                stats.r12 += x1 * x2;
+            }
+            stats
+        })
+        .collect()
+}
+
+/// Pick eight coefficient pairs that predict the audio well, by splitting the best single pair
+/// in two until there are eight and refining each against the frames it predicts best
+pub fn calculate_coefs(samples: &[i16]) -> [[i16; 2]; COEF_PAIRS] {
+    let frames = frame_stats(samples);
+    let mut total = FrameStats::default();
+    frames.iter().for_each(|f| total.add(f));
+    let mut centers = vec![total.solve()];
+    let mut assignment = vec![0; frames.len()];
+
+    while centers.len() < COEF_PAIRS {
+        let mut split = Vec::with_capacity(centers.len() * 2);
+        for &[a1, a2] in &centers {
+            split.push([a1 * 1.01 + 0.01, a2 * 1.01 - 0.01]);
+            split.push([a1 * 0.99 - 0.01, a2 * 0.99 + 0.01]);
+        }
+        centers = split;
+        centers.truncate(COEF_PAIRS);
+
+        for _ in 0..REFINE_ROUNDS {
+            let mut changed = false;
+            for (frame, assigned) in frames.iter().zip(&mut assignment) {
+                let best = (0..centers.len())
+                    .min_by(|&a, &b| frame.error(centers[a]).total_cmp(&frame.error(centers[b])))
+                    .unwrap();
+                changed |= *assigned != best;
+                *assigned = best;
+            }
+            let mut sums = vec![FrameStats::default(); centers.len()];
+            let mut counts = vec![0; centers.len()];
+            for (frame, &assigned) in frames.iter().zip(&assignment) {
+                sums[assigned].add(frame);
+                counts[assigned] += 1;
+            }
+            for ((center, sum), count) in centers.iter_mut().zip(&sums).zip(counts) {
+                // Unused pairs keep their place, and may win frames over later
+                if count > 0 {
+                    *center = sum.solve();
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    let mut coefs = [[0; 2]; COEF_PAIRS];
+    for (pair, center) in coefs.iter_mut().zip(&centers) {
+        for (coef, a) in pair.iter_mut().zip(center) {
+            *coef = (a * 2048.0).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+        }
+    }
+    coefs
+}
+
+/// Decode a sample from a sign extended nibble, a scale, and a coefficient pair, updating the
+/// history. Codecs differ in how they round.
+pub(crate) type DecodeSample = fn(i32, u8, [i16; 2], &mut History) -> i16;
+
+/// Encode a frame with the given coefficients and scale, returning the nibbles, the squared error,
+/// and the history after it
+fn quantize(
+    samples: &[i16],
+    coefs: [i16; 2],
+    scale: u8,
+    history: History,
+    decode: DecodeSample,
+) -> (Vec<i32>, f64, History) {
+    let mut nibbles = Vec::with_capacity(samples.len());
+    let mut error = 0.0;
+    let mut history = history;
+    let step = (1i64 << scale) * 2048;
+    for &sample in samples {
+        let prediction =
+            coefs[0] as i64 * history.yn1 as i64 + coefs[1] as i64 * history.yn2 as i64;
+        let target = sample as i64 * 2048 - prediction;
+        let guess = ((target as f64 / step as f64).round() as i64).clamp(-8, 7) as i32;
+        // Rounding in the decoder can make a neighbouring nibble closer
+        let mut best = None;
+        for nibble in [guess - 1, guess, guess + 1] {
+            if !(-8..=7).contains(&nibble) {
+                continue;
+            }
+            let mut next = history;
+            let decoded = decode(nibble, scale, coefs, &mut next);
+            let diff = (decoded as f64 - sample as f64).powi(2);
+            if best.is_none_or(|(_, d, _)| diff < d) {
+                best = Some((nibble, diff, next));
+            }
+        }
+        let (nibble, diff, next) = best.unwrap();
+        nibbles.push(nibble);
+        error += diff;
+        history = next;
+    }
+    (nibbles, error, history)
+}
+
+/// Encode a frame of a 4-bit ADPCM codec, trying every coefficient pair at the scales around the
+/// one its residuals need. The nibbles are packed into `out`, high nibble first, and the chosen
+/// pair's index and the scale are returned.
+pub(crate) fn encode_frame(
+    samples: &[i16],
+    coefs: &[[i16; 2]],
+    max_scale: u8,
+    history: &mut History,
+    decode: DecodeSample,
+    out: &mut [u8],
+) -> (u8, u8) {
+    let mut best: Option<(u8, u8, Vec<i32>, f64, History)> = None;
+    for (index, &pair) in coefs.iter().enumerate() {
+        // Residuals predicted from the input itself, which is close to what's decoded
+        let mut previous = [history.yn1 as i64, history.yn2 as i64];
+        let mut max_residual = 0;
+        for &sample in samples {
+            let prediction = (pair[0] as i64 * previous[0] + pair[1] as i64 * previous[1]) >> 11;
+            max_residual = max_residual.max((sample as i64 - prediction).abs());
+            previous = [sample as i64, previous[0]];
+        }
+        let mut needed = 0u8;
+        while needed < max_scale && max_residual > 7 << needed {
+            needed += 1;
+        }
+        for scale in needed.saturating_sub(1)..=(needed + 1).min(max_scale) {
+            let (nibbles, error, after) = quantize(samples, pair, scale, *history, decode);
+            if best.as_ref().is_none_or(|b| error < b.3) {
+                best = Some((index as u8, scale, nibbles, error, after));
+            }
+        }
+    }
+    let (index, scale, nibbles, _, after) = best.unwrap();
+    *history = after;
+
+    out.fill(0);
+    for (i, &nibble) in nibbles.iter().enumerate() {
+        let nibble = nibble as u8 & 0xf;
+        out[i / 2] |= if i % 2 == 0 { nibble << 4 } else { nibble };
+    }
+    (index, scale)
+}
+
+/// Encode samples with the given coefficients, continuing from `history`
+pub fn encode(samples: &[i16], coefs: &[[i16; 2]; COEF_PAIRS], history: &mut History) -> Vec<u8> {
+    let mut data = Vec::with_capacity(samples.len().div_ceil(SAMPLES_PER_FRAME) * BYTES_PER_FRAME);
+    for samples in samples.chunks(SAMPLES_PER_FRAME) {
+        let mut frame = [0; BYTES_PER_FRAME];
+        let (index, scale) =
+            encode_frame(samples, coefs, 12, history, decode_sample, &mut frame[1..]);
+        frame[0] = index << 4 | scale;
+        data.extend_from_slice(&frame);
+    }
+    data
+}
+
+impl Dsp {
+    /// Encode mono audio, calculating coefficients for it
+    pub fn encode(
+        samples: &[i16],
+        sample_rate: u32,
+        loop_points: Option<(u32, u32)>,
+    ) -> anyhow::Result<Dsp> {
+        let sample_count = u32::try_from(samples.len()).context("Audio is too long")?;
+        if let Some((start, end)) = loop_points {
+            ensure!(
+                start <= end && end < sample_count,
+                "Loop {start}-{end} is outside the {sample_count} samples of audio"
+            );
+        }
+        let coefs = calculate_coefs(samples);
+        Ok(Dsp {
+            sample_count,
+            sample_rate,
+            coefs,
+            loop_points,
+            data: encode(samples, &coefs, &mut History::default()),
+        })
+    }
+
+    pub fn decode(&self) -> Vec<i16> {
+        decode(
+            &self.data,
+            &self.coefs,
+            self.sample_count as usize,
+            &mut History::default(),
+        )
+    }
+
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Dsp> {
+        let (header, data) =
+            DspHeader::read_from_prefix(buf).map_err(|_| anyhow::anyhow!("DSP is too small"))?;
+        ensure!(
+            header.format.get() == 0,
+            "Unsupported DSP format {}",
+            header.format.get()
+        );
+        let sample_count = header.sample_count.get();
+        let size = (sample_count as usize).div_ceil(SAMPLES_PER_FRAME) * BYTES_PER_FRAME;
+        ensure!(
+            data.len() >= size,
+            "DSP data is truncated: expected {size:#x} bytes, got {:#x}",
+            data.len()
+        );
+        let mut coefs = [[0; 2]; COEF_PAIRS];
+        for (i, coef) in header.coefs.iter().enumerate() {
+            coefs[i / 2][i % 2] = coef.get();
+        }
+        let loop_points = (header.loop_flag.get() != 0).then(|| {
+            (
+                nibble_to_sample(header.loop_start.get()),
+                nibble_to_sample(header.loop_end.get()),
+            )
+        });
+        Ok(Dsp {
+            sample_count,
+            sample_rate: header.sample_rate.get(),
+            coefs,
+            loop_points,
+            data: data[..size].to_vec(),
+        })
+    }
+
+    /// The frame header and history the decoder needs to start at `sample`
+    fn context_at(&self, sample: u32) -> (u16, History) {
+        let frame = sample as usize / SAMPLES_PER_FRAME;
+        let ps = self.data.get(frame * BYTES_PER_FRAME).copied().unwrap_or(0);
+        let mut history = History::default();
+        decode(&self.data, &self.coefs, sample as usize, &mut history);
+        (ps as u16, history)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let full_frames = self.sample_count / SAMPLES_PER_FRAME as u32;
+        let remainder = self.sample_count % SAMPLES_PER_FRAME as u32;
+        let nibble_count =
+            full_frames * NIBBLES_PER_FRAME as u32 + if remainder > 0 { remainder + 2 } else { 0 };
+        let (loop_start, loop_end) = match self.loop_points {
+            Some((start, end)) => (sample_to_nibble(start), sample_to_nibble(end)),
+            None => (2, nibble_count.saturating_sub(1)),
+        };
+        let (ps, _) = self.context_at(0);
+        let (loop_ps, loop_history) = match self.loop_points {
+            Some((start, _)) => self.context_at(start),
+            None => (0, History::default()),
+        };
+        let mut coefs = [big_endian::I16::ZERO; 16];
+        for (i, coef) in coefs.iter_mut().enumerate() {
+            *coef = self.coefs[i / 2][i % 2].into();
+        }
+        let header = DspHeader {
+            sample_count: self.sample_count.into(),
+            nibble_count: nibble_count.into(),
+            sample_rate: self.sample_rate.into(),
+            loop_flag: (self.loop_points.is_some() as u16).into(),
+            format: 0.into(),
+            loop_start: loop_start.into(),
+            loop_end: loop_end.into(),
+            current_address: 2.into(),
+            coefs,
+            gain: 0.into(),
+            ps: ps.into(),
+            yn1: 0.into(),
+            yn2: 0.into(),
+            loop_ps: loop_ps.into(),
+            loop_yn1: loop_history.yn1.into(),
+            loop_yn2: loop_history.yn2.into(),
+            padding: [0; 22],
+        };
+        let mut out = header.as_bytes().to_vec();
+        out.extend_from_slice(&self.data);
+        out
+    }
+}
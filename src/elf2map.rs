@@ -0,0 +1,66 @@
+use anyhow::{ensure, Context};
+use object::{Object, ObjectKind, ObjectSection, ObjectSymbol, SymbolKind, SymbolSection};
+
+use crate::elf2rel::{self, RelVersion};
+use crate::symbol_map::{Symbol, SymbolMap};
+
+/// Extract the global symbols defined in an ELF as a symbol map.
+///
+/// A linked ELF's symbol addresses are used as is. A relocatable ELF, as passed to elf2rel, has
+/// no addresses of its own, so its symbols are placed where they would be in the REL elf2rel
+/// builds when the REL is loaded at `rel_base`. Symbols in `.bss` aren't placed in the REL file
+/// and are left out.
+pub fn elf2map(
+    elf_buf: &[u8],
+    rel_base: Option<u32>,
+    rel_version: RelVersion,
+) -> anyhow::Result<SymbolMap> {
+    let elf = elf2rel::parse_elf(elf_buf)?;
+    let section_offsets = if elf.kind() == ObjectKind::Relocatable {
+        let rel_base = rel_base.context(
+            "ELF is relocatable, so a REL load address is needed to give its symbols addresses",
+        )?;
+        Some((
+            rel_base,
+            elf2rel::rel_section_offsets(elf_buf, rel_version)?,
+        ))
+    } else {
+        ensure!(
+            rel_base.is_none(),
+            "ELF is already linked, so a REL load address can't be used"
+        );
+        None
+    };
+
+    let mut map = SymbolMap::default();
+    for symbol in elf.symbols() {
+        if !symbol.is_global()
+            || !symbol.is_definition()
+            || !matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data)
+        {
+            continue;
+        }
+        let SymbolSection::Section(section_index) = symbol.section() else {
+            continue;
+        };
+        let address = match &section_offsets {
+            Some((rel_base, offsets)) => {
+                let Some(&offset) = offsets.get(&section_index) else {
+                    continue;
+                };
+                rel_base + offset as u32 + symbol.address() as u32
+            }
+            None => symbol.address() as u32,
+        };
+        let section = elf.section_by_index(section_index)?;
+        map.symbols.push(Symbol {
+            address,
+            name: symbol.name()?.to_string(),
+            size: Some(symbol.size() as u32).filter(|&s| s != 0),
+            section: Some(section.name()?.to_string()),
+        });
+    }
+
+    map.sort_by_address();
+    Ok(map)
+}
@@ -1,15 +1,60 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use anyhow::{bail, ensure};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use object::read::elf::FileHeader;
 use object::{
     elf, Architecture, BigEndian, BinaryFormat, Endianness, Object, ObjectSection, ObjectSymbol,
-    RelocationFlags, RelocationTarget, SectionIndex, SectionKind, SymbolSection,
+    RelocationFlags, RelocationTarget, SectionFlags, SectionIndex, SectionKind, SymbolSection,
 };
-use zerocopy::{big_endian, Immutable, IntoBytes, KnownLayout};
+use thiserror::Error;
+use zerocopy::IntoBytes;
+
+use crate::demangle;
+use crate::progress::ProgressFn;
+use crate::rel::{
+    ImportInfo, ModuleHeader, ModuleV2HeaderAddendum, ModuleV3HeaderAddendum, Relocation,
+    RelocationType, SectionInfo,
+};
+use crate::symbol_map;
+
+#[derive(Error, Debug)]
+pub enum Elf2RelError {
+    #[error("Could not find symbol in ELF: '{0}'")]
+    MissingSymbol(String),
+    #[error(
+        "External symbol '{name}'{} not found in symbol map",
+        demangled.as_ref().map(|d| format!(" ({d})")).unwrap_or_default()
+    )]
+    UnresolvedSymbol {
+        name: String,
+        /// Set when it differs from `name`
+        demangled: Option<String>,
+    },
+    #[error("Unsupported ELF relocation type: {0}")]
+    UnsupportedRelocation(u32),
+}
+
+impl Elf2RelError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Elf2RelError::MissingSymbol(_) => "missing-symbol",
+            Elf2RelError::UnresolvedSymbol { .. } => "unresolved-symbol",
+            Elf2RelError::UnsupportedRelocation(_) => "unsupported-relocation",
+        }
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            Elf2RelError::MissingSymbol(name) | Elf2RelError::UnresolvedSymbol { name, .. } => {
+                Some(name)
+            }
+            Elf2RelError::UnsupportedRelocation(_) => None,
+        }
+    }
+}
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
@@ -19,112 +64,28 @@ pub enum RelVersion {
     V3 = 3,
 }
 
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct ModuleHeader {
-    id: big_endian::U32,
-    prev_link: big_endian::U32,
-    next_link: big_endian::U32,
-    section_count: big_endian::U32,
-    section_info_offset: big_endian::U32,
-    name_offset: big_endian::U32,
-    name_size: big_endian::U32,
-    version: big_endian::U32,
-
-    total_bss_size: big_endian::U32,
-    relocation_offset: big_endian::U32,
-    import_info_offset: big_endian::U32,
-    import_info_size: big_endian::U32,
-    prolog_section: u8,
-    epilog_section: u8,
-    unresolved_section: u8,
-    pad: u8,
-    prolog_offset: big_endian::U32,
-    epilog_offset: big_endian::U32,
-    unresolved_offset: big_endian::U32,
-}
-
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct ModuleV2HeaderAddendum {
-    max_align: big_endian::U32,
-    max_bss_align: big_endian::U32,
-}
-
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct ModuleV3HeaderAddendum {
-    fixed_data_size: big_endian::U32,
-}
-
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct SectionInfo {
-    offset: big_endian::U32,
-    size: big_endian::U32,
-}
-
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct ImportInfo {
-    id: big_endian::U32,
-    offset: big_endian::U32,
-}
-
-#[derive(Default, Immutable, KnownLayout, IntoBytes)]
-#[repr(C)]
-struct Relocation {
-    offset: big_endian::U16,
-    type_: u8,
-    section: u8,
-    addend: big_endian::U32,
-}
-
-#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
-#[repr(u8)]
-enum RelocationType {
-    PpcNone,
-    PpcAddr32,
-    PpcAddr24,
-    PpcAddr16,
-    PpcAddr16Lo,
-    PpcAddr16Hi,
-    PpcAddr16Ha,
-    PpcAddr14,
-    PpcAddr14BrTaken,
-    PpcAddr14BrNkTaken,
-    PpcRel24,
-    PpcRel14,
-
-    PpcRel32 = 26,
-
-    DolphinNop = 201,
-    DolphinSection,
-    DolphinEnd,
-}
-
 #[derive(Debug)]
-struct ElfRelocation {
-    src_section: SectionIndex,
-    src_offset: u32,
-    dest_module: u32,
-    dest_section: SectionIndex,
-    addend: u32,
-    type_: RelocationType,
+pub(crate) struct ElfRelocation {
+    pub(crate) src_section: SectionIndex,
+    pub(crate) src_offset: u32,
+    pub(crate) dest_module: u32,
+    pub(crate) dest_section: SectionIndex,
+    pub(crate) addend: u32,
+    pub(crate) type_: RelocationType,
 }
 
-struct SectionStats {
-    total_bss_size: u32,
-    max_align: u32,
-    max_bss_align: u32,
-    section_info_offset: u32,
-    section_offsets: HashMap<SectionIndex, usize>,
+pub(crate) struct SectionStats {
+    pub(crate) total_bss_size: u32,
+    pub(crate) max_align: u32,
+    pub(crate) max_bss_align: u32,
+    pub(crate) section_info_offset: u32,
+    pub(crate) section_offsets: HashMap<SectionIndex, usize>,
 }
 
-struct RelocationStats {
-    relocations_offset: u32,
-    import_info_offset: u32,
-    import_info_size: u32,
+pub(crate) struct RelocationStats {
+    pub(crate) relocations_offset: u32,
+    pub(crate) import_info_offset: u32,
+    pub(crate) import_info_size: u32,
 }
 
 impl Ord for ElfRelocation {
@@ -154,39 +115,62 @@ const VALID_REL_SECTIONS: &[&str] = &[
     ".init", ".text", ".ctors", ".dtors", ".rodata", ".data", ".bss",
 ];
 
-fn find_symbol<'a>(f: &'a object::File, name: &str) -> anyhow::Result<object::Symbol<'a, 'a>> {
-    f.symbol_by_name(name)
-        .ok_or_else(|| anyhow!("Could not find symbol in ELF: '{name}'"))
+/// A rule renaming an input section before elf2rel decides whether to keep it, so sections from
+/// custom linker scripts such as `.hooks` can be kept as `.text`. Subsections are renamed too:
+/// `.hooks.foo` becomes `.text.foo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionRename {
+    pub from: String,
+    pub to: String,
 }
 
-fn parse_symbol_map(buf: &[u8]) -> anyhow::Result<HashMap<&str, u32>> {
-    let mut map = HashMap::new();
-    let s = std::str::from_utf8(buf).context("Failed to parse symbol map as UTF-8")?;
+impl SectionRename {
+    pub fn new(from: &str, to: &str) -> anyhow::Result<SectionRename> {
+        ensure!(!from.is_empty(), "No section to rename to {to}");
+        ensure!(
+            is_valid_rel_section(to),
+            "Can't rename {from} to {to}; RELs only keep {}",
+            VALID_REL_SECTIONS.join(", ")
+        );
+        Ok(SectionRename {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
 
-    for (line_num, line) in s.lines().enumerate() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with("//") {
-            continue;
-        }
-        let (addr, name) = line
-            .split_once(':')
-            .ok_or_else(|| anyhow!("Invalid symbol mapping on line {}: {}", line_num + 1, line))?;
-        if name.is_empty() {
-            bail!("Empty symbol name on line {}", line_num + 1);
-        }
-        let addr = u32::from_str_radix(addr.trim(), 16).with_context(|| {
-            format!("Failed to parse address on line {}: {}", line_num + 1, addr)
-        })?;
-        map.insert(name, addr);
+    /// Parse a rule of the form `from=to`
+    pub fn parse(s: &str) -> anyhow::Result<SectionRename> {
+        let Some((from, to)) = s.split_once('=') else {
+            bail!("Expected a section rename of the form from=to, not '{s}'");
+        };
+        SectionRename::new(from.trim(), to.trim())
     }
+}
+
+fn is_valid_rel_section(name: &str) -> bool {
+    VALID_REL_SECTIONS
+        .iter()
+        .any(|cand_name| name == *cand_name || name.starts_with(&format!("{cand_name}.")))
+}
+
+/// The name a section is kept or dropped under after the first rule matching it
+fn renamed_section(renames: &[SectionRename], name: &str) -> Option<String> {
+    renames.iter().find_map(|rename| {
+        let rest = name.strip_prefix(rename.from.as_str())?;
+        (rest.is_empty() || rest.starts_with('.')).then(|| format!("{}{rest}", rename.to))
+    })
+}
 
-    Ok(map)
+fn find_symbol<'a>(f: &'a object::File, name: &str) -> anyhow::Result<object::Symbol<'a, 'a>> {
+    f.symbol_by_name(name)
+        .ok_or_else(|| Elf2RelError::MissingSymbol(name.to_string()).into())
 }
 
-fn write_sections(
+pub(crate) fn write_sections(
     elf: &object::File,
     rel: &mut Vec<u8>,
     section_count: u32,
+    renames: &[SectionRename],
 ) -> anyhow::Result<SectionStats> {
     let section_info_offset = rel.len();
     // Write section infos first, before section offsets are determined
@@ -212,17 +196,21 @@ fn write_sections(
             continue;
         };
 
-        let valid_section_name = VALID_REL_SECTIONS.iter().any(|cand_name| {
-            section.name().map_or(false, |section_name| {
-                &section_name == cand_name || section_name.starts_with(&format!("{cand_name}."))
-            })
-        });
-        if valid_section_name {
+        let mut section_name = section.name().unwrap_or_default().to_string();
+        if let Some(new_name) = renamed_section(renames, &section_name) {
+            log::debug!("Renaming section {section_name} to {new_name}");
+            section_name = new_name;
+        }
+        if is_valid_rel_section(&section_name) {
             // Include this section
             if section.kind().is_bss() {
                 max_bss_align = max_bss_align.max(section.align());
                 let size = section.size();
                 total_bss_size += size;
+                log::debug!(
+                    "Keeping BSS section {} ({size:#x} bytes)",
+                    section.name().unwrap_or_default()
+                );
 
                 let section_info = SectionInfo {
                     offset: 0.into(),
@@ -252,11 +240,31 @@ fn write_sections(
                 section_info_buffer.extend_from_slice(section_info.as_bytes());
 
                 // Write section data to main buffer
+                log::debug!(
+                    "Keeping section {} at offset {:#x} ({:#x} bytes)",
+                    section.name().unwrap_or_default(),
+                    rel.len(),
+                    section.size()
+                );
                 section_offsets.insert(section.index(), rel.len());
                 rel.extend_from_slice(section.data()?);
             }
         } else {
-            // Remove this section
+            // Remove this section. Only dropping loaded sections loses anything the game would
+            // have seen.
+            let name = section.name().unwrap_or_default();
+            let loaded = matches!(
+                section.flags(),
+                SectionFlags::Elf { sh_flags } if sh_flags & elf::SHF_ALLOC as u64 != 0
+            );
+            if loaded && section.size() > 0 {
+                log::info!(
+                    "Dropping section {name} ({:#x} bytes), which modules don't keep",
+                    section.size()
+                );
+            } else if !name.is_empty() {
+                log::trace!("Dropping section {name}");
+            }
             let section_info = SectionInfo {
                 offset: 0.into(),
                 size: 0.into(),
@@ -279,15 +287,35 @@ fn write_sections(
     })
 }
 
+/// Number of relocations in the sections written to the module, the total for progress reports
+pub(crate) fn relocation_count(
+    elf: &object::File,
+    section_offsets: &HashMap<SectionIndex, usize>,
+) -> u64 {
+    elf.sections()
+        .filter(|section| section_offsets.contains_key(&section.index()))
+        .map(|section| section.relocations().count() as u64)
+        .sum()
+}
+
 fn extract_relocations(
     elf: &object::File,
     symbol_map: &[u8],
     module_id: u32,
     section_offsets: &HashMap<SectionIndex, usize>,
+    progress: &mut ProgressFn,
 ) -> anyhow::Result<Vec<ElfRelocation>> {
     let mut relocations = Vec::new();
+    let total = relocation_count(elf, section_offsets);
 
-    let symbol_map = parse_symbol_map(symbol_map).context("Failed to parse symbol map")?;
+    let symbol_map = symbol_map::parse_lst(symbol_map).context("Failed to parse symbol map")?;
+    let addresses = symbol_map.addresses_by_name();
+    // Fallback for maps and objects that disagree on mangling, such as a map of demangled names
+    let demangled_addresses: HashMap<String, u32> = symbol_map
+        .symbols
+        .iter()
+        .map(|s| (demangle::display_name(&s.name), s.address))
+        .collect();
 
     for src_section in elf.sections() {
         // Don't include relocations for unwritten sections
@@ -296,6 +324,7 @@ fn extract_relocations(
         }
 
         for (src_offset, relocation) in src_section.relocations() {
+            progress(relocations.len() as u64, total);
             let RelocationTarget::Symbol(symbol_idx) = relocation.target() else {
                 bail!("Unsupported relocation target");
             };
@@ -305,7 +334,7 @@ fn extract_relocations(
                 panic!("Expected ELF relocation flags");
             };
             let type_ = RelocationType::try_from(r_type as u8)
-                .map_err(|_| anyhow!("Unsupported ELF relocation type: {r_type}"))?;
+                .map_err(|_| Elf2RelError::UnsupportedRelocation(r_type))?;
 
             match dest_symbol.section() {
                 SymbolSection::Section(dest_section_idx) => {
@@ -322,9 +351,29 @@ fn extract_relocations(
                 SymbolSection::Undefined => {
                     // Relocation against external symbol
                     let symbol_name = dest_symbol.name()?;
-                    let dest_symbol_addr = *symbol_map.get(&symbol_name).ok_or_else(|| {
-                        anyhow!("External symbol '{}' not found in symbol map", symbol_name)
-                    })?;
+                    let demangled_name = demangle::display_name(symbol_name);
+                    let dest_symbol_addr = *addresses
+                        .get(&symbol_name)
+                        .or_else(|| {
+                            let address = demangled_addresses.get(&demangled_name);
+                            if address.is_some() {
+                                log::debug!(
+                                    "Resolved '{symbol_name}' by its demangled name \
+                                     '{demangled_name}'"
+                                );
+                            }
+                            address
+                        })
+                        .ok_or_else(|| Elf2RelError::UnresolvedSymbol {
+                            name: symbol_name.to_string(),
+                            demangled: (demangled_name != symbol_name)
+                                .then(|| demangled_name.clone()),
+                        })?;
+                    log::trace!(
+                        "{type_:?} at {}+{src_offset:#x} against {symbol_name} at \
+                         {dest_symbol_addr:08X}",
+                        src_section.name().unwrap_or_default()
+                    );
                     relocations.push(ElfRelocation {
                         src_section: src_section.index(),
                         src_offset: src_offset as u32,
@@ -338,13 +387,14 @@ fn extract_relocations(
             }
         }
     }
+    progress(total, total);
 
     relocations.sort_unstable();
 
     Ok(relocations)
 }
 
-fn statically_apply_relocation(
+pub(crate) fn statically_apply_relocation(
     rel: &mut [u8],
     section_offsets: &HashMap<SectionIndex, usize>,
     relocation: &ElfRelocation,
@@ -369,7 +419,7 @@ fn statically_apply_relocation(
     data_slice.copy_from_slice(&data.to_be_bytes());
 }
 
-fn write_relocations(
+pub(crate) fn write_relocations(
     rel: &mut Vec<u8>,
     elf_relocations: &[ElfRelocation],
     module_id: u32,
@@ -401,6 +451,7 @@ fn write_relocations(
     let mut current_module_id = None;
     let mut current_section_index = None;
     let mut current_offset = 0;
+    let mut resolved_early = 0;
 
     for relocation in elf_relocations {
         // Resolve early if possible
@@ -411,6 +462,7 @@ fn write_relocations(
             )
         {
             statically_apply_relocation(rel, section_offsets, relocation);
+            resolved_early += 1;
             continue;
         }
 
@@ -504,6 +556,12 @@ fn write_relocations(
     };
     rel.extend_from_slice(r.as_bytes());
 
+    log::info!(
+        "Resolved {resolved_early} relative relocations within the module, leaving {} for \
+         OSLink",
+        elf_relocations.len() - resolved_early
+    );
+
     // Write final import infos
     let imports_region =
         &mut rel[import_info_offset..import_info_offset + import_info_buffer.len()];
@@ -572,7 +630,7 @@ fn write_module_header(
     Ok(())
 }
 
-fn parse_elf(elf_buf: &[u8]) -> anyhow::Result<object::File> {
+pub(crate) fn parse_elf(elf_buf: &[u8]) -> anyhow::Result<object::File<'_>> {
     let elf = object::read::File::parse(elf_buf)?;
     match elf.architecture() {
         Architecture::PowerPc => {}
@@ -586,11 +644,53 @@ fn parse_elf(elf_buf: &[u8]) -> anyhow::Result<object::File> {
     Ok(elf)
 }
 
+/// Offset of each section from the start of the REL that elf2rel would build from an ELF. BSS
+/// and removed sections have no offset.
+pub(crate) fn rel_section_offsets(
+    elf_buf: &[u8],
+    rel_version: RelVersion,
+) -> anyhow::Result<HashMap<SectionIndex, usize>> {
+    let elf = parse_elf(elf_buf)?;
+    let raw_header = elf::FileHeader32::<BigEndian>::parse(elf_buf)?;
+    let section_count = raw_header.e_shnum.get(BigEndian) as u32;
+
+    let mut header_size = size_of::<ModuleHeader>();
+    if rel_version >= RelVersion::V2 {
+        header_size += size_of::<ModuleV2HeaderAddendum>();
+    }
+    if rel_version >= RelVersion::V3 {
+        header_size += size_of::<ModuleV3HeaderAddendum>();
+    }
+    let mut rel = vec![0; header_size];
+    Ok(write_sections(&elf, &mut rel, section_count, &[])?.section_offsets)
+}
+
 pub fn elf2rel(
     elf_buf: &[u8],
     symbol_map: &[u8],
     module_id: u32,
     rel_version: RelVersion,
+    renames: &[SectionRename],
+) -> anyhow::Result<Vec<u8>> {
+    elf2rel_with_progress(
+        elf_buf,
+        symbol_map,
+        module_id,
+        rel_version,
+        renames,
+        &mut |_, _| {},
+    )
+}
+
+/// Like `elf2rel`, reporting progress in relocations resolved, which takes the bulk of the time
+/// for modules with hundreds of thousands of them
+pub fn elf2rel_with_progress(
+    elf_buf: &[u8],
+    symbol_map: &[u8],
+    module_id: u32,
+    rel_version: RelVersion,
+    renames: &[SectionRename],
+    progress: &mut ProgressFn,
 ) -> anyhow::Result<Vec<u8>> {
     let elf = parse_elf(elf_buf)?;
     let raw_header = elf::FileHeader32::<BigEndian>::parse(elf_buf)?;
@@ -607,9 +707,14 @@ pub fn elf2rel(
         rel.extend_from_slice(ModuleV3HeaderAddendum::default().as_bytes());
     }
 
-    let section_stats = write_sections(&elf, &mut rel, section_count)?;
-    let relocations =
-        extract_relocations(&elf, symbol_map, module_id, &section_stats.section_offsets)?;
+    let section_stats = write_sections(&elf, &mut rel, section_count, renames)?;
+    let relocations = extract_relocations(
+        &elf,
+        symbol_map,
+        module_id,
+        &section_stats.section_offsets,
+        progress,
+    )?;
     let relocation_stats = write_relocations(
         &mut rel,
         &relocations,
@@ -0,0 +1,338 @@
+use anyhow::{anyhow, bail, Context};
+use object::read::elf::FileHeader;
+use object::{
+    elf, BigEndian, Object, ObjectSection, ObjectSymbol, RelocationFlags, RelocationTarget,
+    SectionIndex, SymbolKind, SymbolSection,
+};
+use zerocopy::{big_endian, Immutable, IntoBytes, KnownLayout};
+
+use crate::elf2rel::{self, ElfRelocation};
+use crate::progress::ProgressFn;
+use crate::rel::RelocationType;
+
+const RSO_VERSION: u32 = 1;
+
+#[derive(Default, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+struct RsoHeader {
+    next: big_endian::U32,
+    prev: big_endian::U32,
+    section_count: big_endian::U32,
+    section_info_offset: big_endian::U32,
+    name_offset: big_endian::U32,
+    name_size: big_endian::U32,
+    version: big_endian::U32,
+    bss_size: big_endian::U32,
+    prolog_section: u8,
+    epilog_section: u8,
+    unresolved_section: u8,
+    bss_section: u8,
+    prolog_offset: big_endian::U32,
+    epilog_offset: big_endian::U32,
+    unresolved_offset: big_endian::U32,
+    internal_relocation_offset: big_endian::U32,
+    internal_relocation_size: big_endian::U32,
+    external_relocation_offset: big_endian::U32,
+    external_relocation_size: big_endian::U32,
+    export_table_offset: big_endian::U32,
+    export_table_size: big_endian::U32,
+    export_name_offset: big_endian::U32,
+    import_table_offset: big_endian::U32,
+    import_table_size: big_endian::U32,
+    import_name_offset: big_endian::U32,
+}
+
+/// A relocation, applied at an offset from the start of the RSO. `id` is the target section for
+/// internal relocations, or the index of the imported symbol for external ones.
+#[derive(Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+struct RsoRelocation {
+    offset: big_endian::U32,
+    id_and_type: big_endian::U32,
+    addend: big_endian::U32,
+}
+
+#[derive(Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+struct RsoExport {
+    name_offset: big_endian::U32,
+    offset: big_endian::U32,
+    section: big_endian::U32,
+    hash: big_endian::U32,
+}
+
+#[derive(Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+struct RsoImport {
+    name_offset: big_endian::U32,
+    offset: big_endian::U32,
+    /// Offset of the symbol's first relocation in the external relocation table
+    relocation_offset: big_endian::U32,
+}
+
+/// Hash of a symbol name, used by the runtime to look up exports
+pub fn symbol_hash(name: &str) -> u32 {
+    name.bytes().fold(0, |hash, c| {
+        let hash = (hash << 4).wrapping_add(c as u32);
+        let high = hash & 0xF0000000;
+        if high != 0 {
+            (hash ^ (high >> 24)) & !high
+        } else {
+            hash
+        }
+    })
+}
+
+/// Relocations sorted into those resolved within the module and those against imported symbols
+#[derive(Default)]
+struct RsoRelocations {
+    internal: Vec<RsoRelocation>,
+    /// Imported symbol names, each with its relocations
+    imports: Vec<(String, Vec<RsoRelocation>)>,
+}
+
+fn extract_relocations(
+    elf: &object::File,
+    rso: &mut [u8],
+    section_offsets: &std::collections::HashMap<SectionIndex, usize>,
+    progress: &mut ProgressFn,
+) -> anyhow::Result<RsoRelocations> {
+    let mut relocations = RsoRelocations::default();
+    let mut resolved_early = 0;
+    let total = elf2rel::relocation_count(elf, section_offsets);
+    let mut done = 0;
+
+    for src_section in elf.sections() {
+        // Don't include relocations for unwritten sections
+        let Some(&section_offset) = section_offsets.get(&src_section.index()) else {
+            continue;
+        };
+
+        for (src_offset, relocation) in src_section.relocations() {
+            progress(done, total);
+            done += 1;
+            let RelocationTarget::Symbol(symbol_idx) = relocation.target() else {
+                bail!("Unsupported relocation target");
+            };
+            let dest_symbol = elf.symbol_by_index(symbol_idx)?;
+            let RelocationFlags::Elf { r_type } = relocation.flags() else {
+                panic!("Expected ELF relocation flags");
+            };
+            let type_ = RelocationType::try_from(r_type as u8)
+                .map_err(|_| anyhow!("Unsupported ELF relocation type: {r_type}"))?;
+            let offset = (section_offset + src_offset as usize) as u32;
+
+            match dest_symbol.section() {
+                SymbolSection::Section(dest_section) => {
+                    let addend = (dest_symbol.address() as i64 + relocation.addend()) as u32;
+                    // Relative branches within the module don't change when it moves
+                    if matches!(type_, RelocationType::PpcRel24 | RelocationType::PpcRel32)
+                        && section_offsets.contains_key(&dest_section)
+                    {
+                        let relocation = ElfRelocation {
+                            src_section: src_section.index(),
+                            src_offset: src_offset as u32,
+                            dest_module: 0,
+                            dest_section,
+                            addend,
+                            type_,
+                        };
+                        elf2rel::statically_apply_relocation(rso, section_offsets, &relocation);
+                        resolved_early += 1;
+                        continue;
+                    }
+                    relocations.internal.push(RsoRelocation {
+                        offset: offset.into(),
+                        id_and_type: ((dest_section.0 as u32) << 8 | u8::from(type_) as u32).into(),
+                        addend: addend.into(),
+                    });
+                }
+                SymbolSection::Undefined => {
+                    let name = dest_symbol.name()?;
+                    let index = match relocations.imports.iter().position(|(n, _)| n == name) {
+                        Some(index) => index,
+                        None => {
+                            log::debug!("Importing {name}");
+                            relocations.imports.push((name.to_string(), Vec::new()));
+                            relocations.imports.len() - 1
+                        }
+                    };
+                    relocations.imports[index].1.push(RsoRelocation {
+                        offset: offset.into(),
+                        id_and_type: ((index as u32) << 8 | u8::from(type_) as u32).into(),
+                        addend: (relocation.addend() as u32).into(),
+                    });
+                }
+                section => bail!("Unsupported symbol section: {:?}", section),
+            }
+        }
+    }
+    progress(total, total);
+
+    log::info!(
+        "Resolved {resolved_early} relative relocations within the module, leaving {} internal \
+         and {} external",
+        relocations.internal.len(),
+        relocations
+            .imports
+            .iter()
+            .map(|(_, r)| r.len())
+            .sum::<usize>()
+    );
+    relocations.internal.sort_by_key(|r| r.offset.get());
+    Ok(relocations)
+}
+
+/// Build an RSO module from an ELF. Unlike a REL, an RSO imports symbols by name, which the game
+/// resolves against the exports of other modules when linking, so no symbol map is needed. Every
+/// global symbol defined in the ELF is exported.
+pub fn elf2rso(elf_buf: &[u8], name: &str) -> anyhow::Result<Vec<u8>> {
+    elf2rso_with_progress(elf_buf, name, &mut |_, _| {})
+}
+
+/// Like `elf2rso`, reporting progress in relocations processed
+pub fn elf2rso_with_progress(
+    elf_buf: &[u8],
+    name: &str,
+    progress: &mut ProgressFn,
+) -> anyhow::Result<Vec<u8>> {
+    let elf = elf2rel::parse_elf(elf_buf)?;
+    let raw_header = elf::FileHeader32::<BigEndian>::parse(elf_buf)?;
+    let section_count = raw_header.e_shnum.get(BigEndian) as u32;
+
+    let mut rso = Vec::new();
+    rso.extend_from_slice(RsoHeader::default().as_bytes());
+    let section_stats = elf2rel::write_sections(&elf, &mut rso, section_count, &[])?;
+    let section_offsets = &section_stats.section_offsets;
+
+    let name_offset = rso.len();
+    rso.extend_from_slice(name.as_bytes());
+
+    let relocations = extract_relocations(&elf, &mut rso, section_offsets, progress)?;
+
+    rso.resize(rso.len().next_multiple_of(4), 0);
+    let internal_relocation_offset = rso.len();
+    for relocation in &relocations.internal {
+        rso.extend_from_slice(relocation.as_bytes());
+    }
+    let external_relocation_offset = rso.len();
+    let mut import_relocation_offsets = Vec::new();
+    for (_, import_relocations) in &relocations.imports {
+        import_relocation_offsets.push(rso.len() - external_relocation_offset);
+        for relocation in import_relocations {
+            rso.extend_from_slice(relocation.as_bytes());
+        }
+    }
+    let external_relocation_end = rso.len();
+
+    // Exports, sorted by hash for lookup
+    let mut exports: Vec<(String, u32, u32)> = Vec::new();
+    for symbol in elf.symbols() {
+        if !symbol.is_global()
+            || !symbol.is_definition()
+            || !matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data)
+        {
+            continue;
+        }
+        let SymbolSection::Section(section) = symbol.section() else {
+            continue;
+        };
+        exports.push((
+            symbol.name()?.to_string(),
+            symbol.address() as u32,
+            section.0 as u32,
+        ));
+    }
+    exports.sort_by_key(|(name, _, _)| symbol_hash(name));
+
+    let mut export_names = Vec::new();
+    let mut export_table = Vec::new();
+    for (name, offset, section) in &exports {
+        export_table.extend_from_slice(
+            RsoExport {
+                name_offset: (export_names.len() as u32).into(),
+                offset: (*offset).into(),
+                section: (*section).into(),
+                hash: symbol_hash(name).into(),
+            }
+            .as_bytes(),
+        );
+        export_names.extend_from_slice(name.as_bytes());
+        export_names.push(0);
+    }
+    let export_table_offset = rso.len();
+    rso.extend_from_slice(&export_table);
+    let export_name_offset = rso.len();
+    rso.extend_from_slice(&export_names);
+
+    let mut import_names = Vec::new();
+    let mut import_table = Vec::new();
+    for ((name, _), relocation_offset) in relocations.imports.iter().zip(import_relocation_offsets)
+    {
+        import_table.extend_from_slice(
+            RsoImport {
+                name_offset: (import_names.len() as u32).into(),
+                offset: 0.into(),
+                relocation_offset: (relocation_offset as u32).into(),
+            }
+            .as_bytes(),
+        );
+        import_names.extend_from_slice(name.as_bytes());
+        import_names.push(0);
+    }
+    rso.resize(rso.len().next_multiple_of(4), 0);
+    let import_table_offset = rso.len();
+    rso.extend_from_slice(&import_table);
+    let import_name_offset = rso.len();
+    rso.extend_from_slice(&import_names);
+
+    // The prolog, epilog, and unresolved functions are optional in an RSO
+    let function = |name: &str| -> (u8, u32) {
+        elf.symbol_by_name(name)
+            .and_then(|s| Some((s.section_index()?.0 as u8, s.address() as u32)))
+            .unwrap_or_default()
+    };
+    let (prolog_section, prolog_offset) = function("_prolog");
+    let (epilog_section, epilog_offset) = function("_epilog");
+    let (unresolved_section, unresolved_offset) = function("_unresolved");
+    let bss_section = elf
+        .sections()
+        .find(|s| s.kind().is_bss() && s.size() != 0)
+        .map_or(0, |s| s.index().0 as u8);
+
+    let size = |start: usize, end: usize| -> anyhow::Result<big_endian::U32> {
+        Ok(u32::try_from(end - start)
+            .context("RSO is too large")?
+            .into())
+    };
+    let header = RsoHeader {
+        next: 0.into(),
+        prev: 0.into(),
+        section_count: section_count.into(),
+        section_info_offset: section_stats.section_info_offset.into(),
+        name_offset: (name_offset as u32).into(),
+        name_size: (name.len() as u32).into(),
+        version: RSO_VERSION.into(),
+        bss_size: section_stats.total_bss_size.into(),
+        prolog_section,
+        epilog_section,
+        unresolved_section,
+        bss_section,
+        prolog_offset: prolog_offset.into(),
+        epilog_offset: epilog_offset.into(),
+        unresolved_offset: unresolved_offset.into(),
+        internal_relocation_offset: (internal_relocation_offset as u32).into(),
+        internal_relocation_size: size(internal_relocation_offset, external_relocation_offset)?,
+        external_relocation_offset: (external_relocation_offset as u32).into(),
+        external_relocation_size: size(external_relocation_offset, external_relocation_end)?,
+        export_table_offset: (export_table_offset as u32).into(),
+        export_table_size: size(export_table_offset, export_name_offset)?,
+        export_name_offset: (export_name_offset as u32).into(),
+        import_table_offset: (import_table_offset as u32).into(),
+        import_table_size: size(import_table_offset, import_name_offset)?,
+        import_name_offset: (import_name_offset as u32).into(),
+    };
+    rso[..size_of::<RsoHeader>()].copy_from_slice(header.as_bytes());
+
+    Ok(rso)
+}
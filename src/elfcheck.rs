@@ -0,0 +1,323 @@
+// Preflight checks of an ELF against what the console and the REL/RSO loaders can run, so that
+// mistakes in compiler flags and linker scripts fail at build time instead of on the console.
+
+use object::{
+    elf, Object, ObjectKind, ObjectSection, ObjectSymbol, RelocationFlags, RelocationTarget,
+    SectionFlags, SectionKind, SymbolKind, SymbolSection,
+};
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::elf2rel;
+use crate::symbol_map::SymbolMap;
+
+const MEM1_START: u64 = 0x80000000;
+const MEM1_END: u64 = 0x81800000;
+/// The EABI keeps r1 aligned to 8 bytes
+const STACK_ALIGN: i32 = 8;
+/// Instructions into a function to look for the frame being set up
+const PROLOGUE_SEARCH: usize = 16;
+
+const SMALL_DATA_SECTIONS: &[&str] = &[".sdata", ".sbss", ".sdata2", ".sbss2"];
+const SMALL_DATA_RELOCATIONS: &[u32] = &[
+    elf::R_PPC_SDAREL16,
+    elf::R_PPC_EMB_SDAI16,
+    elf::R_PPC_EMB_SDA2I16,
+    elf::R_PPC_EMB_SDA2REL,
+    elf::R_PPC_EMB_SDA21,
+    elf::R_PPC_EMB_RELSDA,
+];
+const EXCEPTION_SECTIONS: &[&str] = &[
+    ".eh_frame",
+    ".eh_frame_hdr",
+    ".gcc_except_table",
+    "extab",
+    "extabindex",
+];
+/// Runtime support for C++ exceptions, which games are built without
+const EXCEPTION_SYMBOLS: &[&str] = &[
+    "__cxa_allocate_exception",
+    "__cxa_begin_catch",
+    "__cxa_end_catch",
+    "__cxa_rethrow",
+    "__cxa_throw",
+    "__gxx_personality_v0",
+    "_Unwind_Resume",
+];
+
+fn matches_section(name: &str, candidates: &[&str]) -> bool {
+    candidates
+        .iter()
+        .any(|c| name == *c || name.starts_with(&format!("{c}.")))
+}
+
+fn in_mem1(address: u64) -> bool {
+    (MEM1_START..MEM1_END).contains(&address)
+}
+
+/// Size of the frame a function allocates with `stwu r1, -size(r1)` near its start
+fn frame_size(code: &[u8]) -> Option<i32> {
+    code.chunks_exact(4).take(PROLOGUE_SEARCH).find_map(|ins| {
+        let ins = u32::from_be_bytes(ins.try_into().unwrap());
+        let is_stwu_r1 = ins >> 26 == 37 && (ins >> 21) & 0x1f == 1 && (ins >> 16) & 0x1f == 1;
+        is_stwu_r1.then_some(-(ins as i16 as i32))
+    })
+}
+
+/// Check an ELF for code and data the console can't run. Relocatable ELFs, as converted to RELs
+/// and RSOs, have no small data area; executables may use one if they define `_SDA_BASE_`.
+/// `symbol_map` is the game's, used to tell which symbols the ELF can import.
+pub fn check(elf_buf: &[u8], symbol_map: Option<&SymbolMap>) -> anyhow::Result<Vec<Diagnostic>> {
+    let elf = elf2rel::parse_elf(elf_buf)?;
+    let imports = symbol_map.map(|map| map.addresses_by_name());
+    let mut diagnostics = Vec::new();
+
+    let executable = elf.kind() == ObjectKind::Executable;
+    let has_sda = elf.symbol_by_name("_SDA_BASE_").is_some();
+
+    for section in elf.sections() {
+        let name = section.name().unwrap_or_default();
+        let alloc = matches!(
+            section.flags(),
+            SectionFlags::Elf { sh_flags } if sh_flags & elf::SHF_ALLOC as u64 != 0
+        );
+
+        if matches_section(name, SMALL_DATA_SECTIONS) && section.size() > 0 && !has_sda {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                "small-data",
+                format!(
+                    "{name} holds small data, which has no SDA base to be addressed from; build \
+                     with -G0 (or -msdata=none)"
+                ),
+            ));
+        }
+        if matches_section(name, EXCEPTION_SECTIONS) && section.size() > 0 {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "exception-tables",
+                format!(
+                    "{name} holds exception tables, which are dropped; build with -fno-exceptions"
+                ),
+            ));
+        }
+
+        if alloc {
+            let align = section.align().max(1);
+            if !section.address().is_multiple_of(align) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    "misaligned-section",
+                    format!(
+                        "{name} is at {:#x}, which isn't aligned to its {align}-byte alignment",
+                        section.address()
+                    ),
+                ));
+            }
+            if section.kind() == SectionKind::Text && align < 4 {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    "unaligned-code",
+                    format!(
+                        "{name} holds code but is only {align}-byte aligned, where instructions \
+                         need 4"
+                    ),
+                ));
+            }
+            if executable
+                && section.size() > 0
+                && !(in_mem1(section.address()) && section.address() + section.size() <= MEM1_END)
+            {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    "outside-mem1",
+                    format!(
+                        "{name} is at {:#x}-{:#x}, outside MEM1",
+                        section.address(),
+                        section.address() + section.size()
+                    ),
+                ));
+            }
+        }
+
+        let mut small_data_relocations = 0;
+        let mut first_small_data_target = None;
+        for (_, relocation) in section.relocations() {
+            let RelocationFlags::Elf { r_type } = relocation.flags() else {
+                continue;
+            };
+            if !has_sda && SMALL_DATA_RELOCATIONS.contains(&r_type) {
+                small_data_relocations += 1;
+                if first_small_data_target.is_none()
+                    && let RelocationTarget::Symbol(index) = relocation.target()
+                {
+                    first_small_data_target = elf
+                        .symbol_by_index(index)
+                        .ok()
+                        .and_then(|s| s.name().ok())
+                        .map(str::to_string);
+                }
+            }
+        }
+        if small_data_relocations > 0 {
+            let plural = if small_data_relocations == 1 { "" } else { "s" };
+            let target = first_small_data_target
+                .as_ref()
+                .map(|t| format!(", such as to `{t}`"))
+                .unwrap_or_default();
+            let mut diagnostic = Diagnostic::new(
+                Severity::Error,
+                "small-data-relocation",
+                format!(
+                    "{name} has {small_data_relocations} small data relocation{plural}{target}, \
+                     which have no SDA base to resolve against; build with -G0 (or -msdata=none)"
+                ),
+            );
+            if let Some(target) = first_small_data_target {
+                diagnostic = diagnostic.with_symbol(target);
+            }
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    for symbol in elf.symbols() {
+        let name = symbol.name().unwrap_or_default();
+        match symbol.section() {
+            SymbolSection::Undefined => {
+                let imported = imports.as_ref().and_then(|i| i.get(name).copied());
+                if EXCEPTION_SYMBOLS.contains(&name) && imported.is_none() {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            "exception-symbol",
+                            format!(
+                                "`{name}` is referenced to support C++ exceptions, which the \
+                                 game doesn't provide; build with -fno-exceptions"
+                            ),
+                        )
+                        .with_symbol(name),
+                    );
+                }
+                if let Some(address) = imported.filter(|&a| !in_mem1(a as u64)) {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            Severity::Warning,
+                            "import-outside-mem1",
+                            format!(
+                                "`{name}` is imported from {address:#x} in the symbol map, \
+                                 outside MEM1"
+                            ),
+                        )
+                        .with_symbol(name),
+                    );
+                }
+            }
+            SymbolSection::Absolute
+                if !name.is_empty()
+                    && symbol.kind() != SymbolKind::File
+                    && symbol.address() != 0
+                    && !in_mem1(symbol.address()) =>
+            {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "absolute-outside-mem1",
+                        format!(
+                            "`{name}` is at absolute address {:#x}, outside MEM1",
+                            symbol.address()
+                        ),
+                    )
+                    .with_symbol(name),
+                );
+            }
+            SymbolSection::Section(index) if symbol.kind() == SymbolKind::Text => {
+                let Ok(section) = elf.section_by_index(index) else {
+                    continue;
+                };
+                let Ok(data) = section.data() else {
+                    continue;
+                };
+                let start = (symbol.address() - section.address()) as usize;
+                let end = (start + symbol.size() as usize).min(data.len());
+                let Some(size) = data.get(start..end).and_then(frame_size) else {
+                    continue;
+                };
+                if size % STACK_ALIGN != 0 {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            "misaligned-stack-frame",
+                            format!(
+                                "`{name}` allocates a {size}-byte stack frame, which leaves \
+                                 r1 misaligned; frames must be multiples of {STACK_ALIGN} bytes"
+                            ),
+                        )
+                        .with_symbol(name),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    diagnostics.sort_by_key(|d| std::cmp::Reverse(d.severity));
+    Ok(diagnostics)
+}
+
+/// Find global symbols the ELF defines that the game's symbol map also has. References to them
+/// bind to the ELF's definition rather than the game's, which is easy to do by accident, such as
+/// by linking in a copy of a library function the game already has. Each is reported as a
+/// warning, or an error if `deny` is set.
+pub fn duplicate_symbols(
+    elf_buf: &[u8],
+    symbol_map: &SymbolMap,
+    deny: bool,
+) -> anyhow::Result<Vec<Diagnostic>> {
+    let elf = elf2rel::parse_elf(elf_buf)?;
+    let imports = symbol_map.addresses_by_name();
+    let executable = elf.kind() == ObjectKind::Executable;
+    let severity = if deny {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+
+    let mut diagnostics = Vec::new();
+    for symbol in elf.symbols() {
+        if !symbol.is_global() || symbol.kind() == SymbolKind::File {
+            continue;
+        }
+        let SymbolSection::Section(index) = symbol.section() else {
+            continue;
+        };
+        let name = symbol.name().unwrap_or_default();
+        let Some(&game_address) = imports.get(name) else {
+            continue;
+        };
+        // A linked ELF at the game's own address is the same definition, not a second one
+        if executable && symbol.address() == game_address as u64 {
+            continue;
+        }
+        let section = elf
+            .section_by_index(index)
+            .ok()
+            .and_then(|s| s.name().ok().map(str::to_string))
+            .unwrap_or_default();
+        let location = if executable {
+            format!("{:#x} ({section})", symbol.address())
+        } else {
+            format!("{section}+{:#x}", symbol.address())
+        };
+        diagnostics.push(
+            Diagnostic::new(
+                severity,
+                "duplicate-symbol",
+                format!(
+                    "`{name}` is defined in the ELF at {location}, shadowing the game's \
+                     definition at {game_address:#x} in the symbol map"
+                ),
+            )
+            .with_symbol(name),
+        );
+    }
+    Ok(diagnostics)
+}
@@ -0,0 +1,74 @@
+// On-the-wire structures of the console's file formats, shared with loaders and bootstrap code
+// running on the console. Only `core` is used here, so this module is all that's left when the
+// crate is built without its `std` feature.
+
+pub mod bundle;
+pub mod dol;
+pub mod gci;
+pub mod rel;
+
+use core::fmt::Display;
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    TooSmall {
+        what: &'static str,
+        needed: usize,
+        available: usize,
+    },
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FormatError::TooSmall {
+                what,
+                needed,
+                available,
+            } => write!(
+                f,
+                "{what} needs {needed:#x} bytes, but only {available:#x} are available"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for FormatError {}
+
+/// A fixed-size structure stored big-endian at the start of a buffer
+pub trait Format: FromBytes + IntoBytes + KnownLayout + Immutable + Sized {
+    /// What the structure is, for errors
+    const NAME: &'static str;
+
+    fn too_small(available: usize) -> FormatError {
+        FormatError::TooSmall {
+            what: Self::NAME,
+            needed: size_of::<Self>(),
+            available,
+        }
+    }
+
+    /// View the structure at the start of `buf`
+    fn parse(buf: &[u8]) -> Result<&Self, FormatError> {
+        Self::ref_from_prefix(buf)
+            .map(|(s, _)| s)
+            .map_err(|_| Self::too_small(buf.len()))
+    }
+
+    /// View the structure at the start of `buf` for editing in place
+    fn parse_mut(buf: &mut [u8]) -> Result<&mut Self, FormatError> {
+        let available = buf.len();
+        Self::mut_from_prefix(buf)
+            .map(|(s, _)| s)
+            .map_err(|_| Self::too_small(available))
+    }
+
+    /// Write the structure to the start of `out`, returning how many bytes it took
+    fn serialize(&self, out: &mut [u8]) -> Result<usize, FormatError> {
+        self.write_to_prefix(out)
+            .map(|()| size_of::<Self>())
+            .map_err(|_| Self::too_small(out.len()))
+    }
+}
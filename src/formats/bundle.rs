@@ -0,0 +1,71 @@
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::Format;
+
+pub const BUNDLE_MAGIC: [u8; 4] = *b"GCBD";
+pub const MAX_ENTRY_NAME_SIZE: usize = 0x38;
+/// Alignment of each file's data from the start of the bundle, so it can be used in place
+pub const BUNDLE_ALIGN: usize = 0x20;
+
+/// Start of a bundle of files packed into one GCI payload, followed by `count` entries
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct BundleHeader {
+    pub magic: [u8; 4],
+    pub count: big_endian::U32,
+}
+
+impl Format for BundleHeader {
+    const NAME: &'static str = "bundle header";
+}
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct BundleEntry {
+    /// Path within the bundle with `/` separators, null-padded
+    pub name: [u8; MAX_ENTRY_NAME_SIZE],
+    /// Offset of the data from the start of the bundle
+    pub offset: big_endian::U32,
+    pub size: big_endian::U32,
+}
+
+impl Format for BundleEntry {
+    const NAME: &'static str = "bundle entry";
+}
+
+impl BundleEntry {
+    pub fn name(&self) -> &[u8] {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.name.len());
+        &self.name[..len]
+    }
+
+    /// The entry's data within `bundle`, or `None` if it's out of bounds
+    pub fn data<'a>(&self, bundle: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset.get() as usize;
+        bundle.get(start..start.checked_add(self.size.get() as usize)?)
+    }
+}
+
+/// The entries of a bundle, or `None` if `bundle` isn't one
+pub fn entries(bundle: &[u8]) -> Option<&[BundleEntry]> {
+    let header = BundleHeader::parse(bundle).ok()?;
+    if header.magic != BUNDLE_MAGIC {
+        return None;
+    }
+    let rest = &bundle[size_of::<BundleHeader>()..];
+    <[BundleEntry]>::ref_from_prefix_with_elems(rest, header.count.get() as usize)
+        .ok()
+        .map(|(entries, _)| entries)
+}
+
+/// Look up a file in a bundle by name, as a loader on the console would
+pub fn find<'a>(bundle: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    entries(bundle)?
+        .iter()
+        .find(|e| e.name() == name)?
+        .data(bundle)
+}
@@ -0,0 +1,79 @@
+use core::fmt::Display;
+
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::Format;
+
+pub const TEXT_SECTION_COUNT: usize = 7;
+pub const DATA_SECTION_COUNT: usize = 11;
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct DolHeader {
+    pub text_offsets: [big_endian::U32; TEXT_SECTION_COUNT],
+    pub data_offsets: [big_endian::U32; DATA_SECTION_COUNT],
+    pub text_addresses: [big_endian::U32; TEXT_SECTION_COUNT],
+    pub data_addresses: [big_endian::U32; DATA_SECTION_COUNT],
+    pub text_sizes: [big_endian::U32; TEXT_SECTION_COUNT],
+    pub data_sizes: [big_endian::U32; DATA_SECTION_COUNT],
+    pub bss_address: big_endian::U32,
+    pub bss_size: big_endian::U32,
+    pub entry_point: big_endian::U32,
+    pub padding: [u8; 0x1C],
+}
+
+impl Format for DolHeader {
+    const NAME: &'static str = "DOL header";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DolSectionKind {
+    Text,
+    Data,
+}
+
+impl Display for DolSectionKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            DolSectionKind::Text => "text",
+            DolSectionKind::Data => "data",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl DolHeader {
+    /// Iterate over (kind, index, offset, address, size) of all non-empty sections
+    pub fn sections(&self) -> impl Iterator<Item = (DolSectionKind, usize, u32, u32, u32)> + '_ {
+        let text = (0..TEXT_SECTION_COUNT).map(|i| {
+            (
+                DolSectionKind::Text,
+                i,
+                self.text_offsets[i].get(),
+                self.text_addresses[i].get(),
+                self.text_sizes[i].get(),
+            )
+        });
+        let data = (0..DATA_SECTION_COUNT).map(|i| {
+            (
+                DolSectionKind::Data,
+                i,
+                self.data_offsets[i].get(),
+                self.data_addresses[i].get(),
+                self.data_sizes[i].get(),
+            )
+        });
+        text.chain(data).filter(|s| s.4 != 0)
+    }
+
+    /// Size of the DOL file, determined by the end of the furthest section. Saturates for
+    /// corrupt headers whose sections end past 4 GiB.
+    pub fn file_size(&self) -> u32 {
+        self.sections()
+            .map(|(_, _, offset, _, size)| offset.saturating_add(size))
+            .max()
+            .unwrap_or(size_of::<DolHeader>() as u32)
+    }
+}
@@ -0,0 +1,108 @@
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::Format;
+
+pub const MAX_FILE_NAME_SIZE: usize = 0x20;
+
+/// Permission bit for a save any game can open, not just the one with its gamecode
+pub const PERMISSION_PUBLIC: u8 = 0x04;
+/// Permission bit the IPL checks before copying a save to another card
+pub const PERMISSION_NO_COPY: u8 = 0x08;
+/// Permission bit the IPL checks before moving a save to another card
+pub const PERMISSION_NO_MOVE: u8 = 0x10;
+
+/// Directory entry at the start of a GCI, as stored in a memory card's directory
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "GciHeaderFields", into = "GciHeaderFields")
+)]
+#[repr(C)]
+pub struct GciHeader {
+    pub gamecode: [u8; 6],
+    pub unused0: u8,
+    pub banner_fmt: u8,
+    pub filename: [u8; MAX_FILE_NAME_SIZE],
+    /// Seconds since 2000-01-01
+    pub last_modified: big_endian::U32,
+    pub image_offset: big_endian::U32,
+    pub icon_format: big_endian::U16,
+    pub icon_speed: big_endian::U16,
+    /// `PERMISSION_*` bits
+    pub permissions: u8,
+    /// Times the save has been copied, which some games check
+    pub copy_times: u8,
+    pub first_block_num: big_endian::U16,
+    pub block_count: big_endian::U16,
+    pub unused1: big_endian::U16,
+    pub comment_offset: big_endian::U32,
+}
+
+impl Format for GciHeader {
+    const NAME: &'static str = "GCI header";
+}
+
+/// The fields of a `GciHeader` in native byte order, which is how serde sees them
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GciHeaderFields {
+    gamecode: [u8; 6],
+    unused0: u8,
+    banner_fmt: u8,
+    filename: [u8; MAX_FILE_NAME_SIZE],
+    last_modified: u32,
+    image_offset: u32,
+    icon_format: u16,
+    icon_speed: u16,
+    permissions: u8,
+    copy_times: u8,
+    first_block_num: u16,
+    block_count: u16,
+    unused1: u16,
+    comment_offset: u32,
+}
+
+#[cfg(feature = "serde")]
+impl From<GciHeaderFields> for GciHeader {
+    fn from(f: GciHeaderFields) -> GciHeader {
+        GciHeader {
+            gamecode: f.gamecode,
+            unused0: f.unused0,
+            banner_fmt: f.banner_fmt,
+            filename: f.filename,
+            last_modified: f.last_modified.into(),
+            image_offset: f.image_offset.into(),
+            icon_format: f.icon_format.into(),
+            icon_speed: f.icon_speed.into(),
+            permissions: f.permissions,
+            copy_times: f.copy_times,
+            first_block_num: f.first_block_num.into(),
+            block_count: f.block_count.into(),
+            unused1: f.unused1.into(),
+            comment_offset: f.comment_offset.into(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<GciHeader> for GciHeaderFields {
+    fn from(h: GciHeader) -> GciHeaderFields {
+        GciHeaderFields {
+            gamecode: h.gamecode,
+            unused0: h.unused0,
+            banner_fmt: h.banner_fmt,
+            filename: h.filename,
+            last_modified: h.last_modified.get(),
+            image_offset: h.image_offset.get(),
+            icon_format: h.icon_format.get(),
+            icon_speed: h.icon_speed.get(),
+            permissions: h.permissions,
+            copy_times: h.copy_times,
+            first_block_num: h.first_block_num.get(),
+            block_count: h.block_count.get(),
+            unused1: h.unused1.get(),
+            comment_offset: h.comment_offset.get(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::Format;
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct ModuleHeader {
+    pub id: big_endian::U32,
+    pub prev_link: big_endian::U32,
+    pub next_link: big_endian::U32,
+    pub section_count: big_endian::U32,
+    pub section_info_offset: big_endian::U32,
+    pub name_offset: big_endian::U32,
+    pub name_size: big_endian::U32,
+    pub version: big_endian::U32,
+
+    pub total_bss_size: big_endian::U32,
+    pub relocation_offset: big_endian::U32,
+    pub import_info_offset: big_endian::U32,
+    pub import_info_size: big_endian::U32,
+    pub prolog_section: u8,
+    pub epilog_section: u8,
+    pub unresolved_section: u8,
+    pub pad: u8,
+    pub prolog_offset: big_endian::U32,
+    pub epilog_offset: big_endian::U32,
+    pub unresolved_offset: big_endian::U32,
+}
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct ModuleV2HeaderAddendum {
+    pub max_align: big_endian::U32,
+    pub max_bss_align: big_endian::U32,
+}
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct ModuleV3HeaderAddendum {
+    pub fixed_data_size: big_endian::U32,
+}
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct SectionInfo {
+    pub offset: big_endian::U32,
+    pub size: big_endian::U32,
+}
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct ImportInfo {
+    pub id: big_endian::U32,
+    pub offset: big_endian::U32,
+}
+
+#[derive(Default, FromBytes, Immutable, KnownLayout, IntoBytes)]
+#[repr(C)]
+pub struct Relocation {
+    pub offset: big_endian::U16,
+    pub type_: u8,
+    pub section: u8,
+    pub addend: big_endian::U32,
+}
+
+impl Format for ModuleHeader {
+    const NAME: &'static str = "REL header";
+}
+
+impl Format for ModuleV2HeaderAddendum {
+    const NAME: &'static str = "REL v2 header";
+}
+
+impl Format for ModuleV3HeaderAddendum {
+    const NAME: &'static str = "REL v3 header";
+}
+
+impl Format for SectionInfo {
+    const NAME: &'static str = "REL section info";
+}
+
+impl Format for ImportInfo {
+    const NAME: &'static str = "REL import info";
+}
+
+impl Format for Relocation {
+    const NAME: &'static str = "REL relocation";
+}
@@ -0,0 +1,1468 @@
+// Encoding and decoding of the GameCube's tiled texture formats, shared by TPL textures, BNR
+// banners, and save file icons and banners.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, ensure};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+/// GX texture formats, with their hardware IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u32)]
+pub enum TextureFormat {
+    I4 = 0,
+    I8 = 1,
+    Ia4 = 2,
+    Ia8 = 3,
+    Rgb565 = 4,
+    Rgb5a3 = 5,
+    Rgba8 = 6,
+    Ci4 = 8,
+    Ci8 = 9,
+    Ci14x2 = 10,
+    Cmpr = 14,
+}
+
+impl TextureFormat {
+    const NAMES: [(TextureFormat, &'static str); 11] = [
+        (TextureFormat::I4, "i4"),
+        (TextureFormat::I8, "i8"),
+        (TextureFormat::Ia4, "ia4"),
+        (TextureFormat::Ia8, "ia8"),
+        (TextureFormat::Rgb565, "rgb565"),
+        (TextureFormat::Rgb5a3, "rgb5a3"),
+        (TextureFormat::Rgba8, "rgba8"),
+        (TextureFormat::Ci4, "ci4"),
+        (TextureFormat::Ci8, "ci8"),
+        (TextureFormat::Ci14x2, "ci14x2"),
+        (TextureFormat::Cmpr, "cmpr"),
+    ];
+
+    pub fn parse(name: &str) -> anyhow::Result<TextureFormat> {
+        TextureFormat::NAMES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|&(format, _)| format)
+            .ok_or_else(|| anyhow!("Unknown texture format '{name}'"))
+    }
+
+    /// Width and height in pixels of the tiles the image is stored in. Every tile is 32 bytes,
+    /// except RGBA8's, which are split into two 32 byte halves.
+    pub fn tile_size(&self) -> (usize, usize) {
+        match self {
+            TextureFormat::I4 | TextureFormat::Ci4 | TextureFormat::Cmpr => (8, 8),
+            TextureFormat::I8 | TextureFormat::Ia4 | TextureFormat::Ci8 => (8, 4),
+            TextureFormat::Ia8
+            | TextureFormat::Rgb565
+            | TextureFormat::Rgb5a3
+            | TextureFormat::Rgba8
+            | TextureFormat::Ci14x2 => (4, 4),
+        }
+    }
+
+    /// Size in bytes of a `width` by `height` image, padded out to whole tiles
+    pub fn data_size(&self, width: usize, height: usize) -> usize {
+        let (tile_width, tile_height) = self.tile_size();
+        let tile_bytes = if *self == TextureFormat::Rgba8 {
+            64
+        } else {
+            32
+        };
+        width.div_ceil(tile_width) * height.div_ceil(tile_height) * tile_bytes
+    }
+
+    /// Total size in bytes of a `width` by `height` image followed by `count - 1` mipmaps, each
+    /// half the size of the last
+    pub fn mipmap_data_size(&self, width: usize, height: usize, count: usize) -> usize {
+        let shift = |size: usize, level: usize| size.checked_shr(level as u32).unwrap_or(0).max(1);
+        (0..count)
+            .map(|level| self.data_size(shift(width, level), shift(height, level)))
+            .sum()
+    }
+
+    /// Whether pixels are indices into a palette
+    pub fn is_indexed(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Ci4 | TextureFormat::Ci8 | TextureFormat::Ci14x2
+        )
+    }
+
+    /// Number of palette entries the pixels can index
+    pub fn max_colors(&self) -> usize {
+        match self {
+            TextureFormat::Ci4 => 16,
+            TextureFormat::Ci8 => 256,
+            TextureFormat::Ci14x2 => 1 << 14,
+            _ => 0,
+        }
+    }
+
+    /// Decode an image to linear RGBA8. Color indexed formats need the image's palette.
+    pub fn decode(
+        &self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+        tlut: Option<&Tlut>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let size = self.data_size(width, height);
+        ensure!(
+            data.len() >= size,
+            "{width}x{height} {self} image needs {size:#x} bytes, got {:#x}",
+            data.len()
+        );
+        if self.is_indexed() {
+            let tlut = tlut.ok_or_else(|| anyhow!("{self} image has no palette"))?;
+            return Ok(decode_indexed(*self, data, width, height, tlut));
+        }
+        match self {
+            TextureFormat::Rgb5a3 => Ok(decode_rgb5a3(data, width, height)),
+            TextureFormat::Rgb565 => Ok(decode_rgb565(data, width, height)),
+            TextureFormat::Rgba8 => Ok(decode_rgba8(data, width, height)),
+            TextureFormat::Cmpr => Ok(decode_cmpr(data, width, height)),
+            TextureFormat::I4 => Ok(decode_i4(data, width, height)),
+            TextureFormat::I8 => Ok(decode_8bit(data, width, height, (8, 4), |i| [i; 4])),
+            TextureFormat::Ia4 => Ok(decode_8bit(data, width, height, (8, 4), decode_ia4_pixel)),
+            TextureFormat::Ia8 => Ok(decode_16bit(data, width, height, decode_ia8_pixel)),
+            _ => bail!("{self} textures are not supported"),
+        }
+    }
+
+    /// Encode linear RGBA8 pixels in this format. Color indexed formats are encoded with
+    /// [`TextureFormat::encode_indexed`] instead.
+    pub fn encode(&self, rgba: &[u8], width: usize, height: usize) -> anyhow::Result<Vec<u8>> {
+        ensure!(
+            rgba.len() == width * height * 4,
+            "Expected {width}x{height} RGBA pixels"
+        );
+        ensure!(!self.is_indexed(), "{self} textures need a palette");
+        match self {
+            TextureFormat::Rgb5a3 => Ok(encode_rgb5a3(rgba, width, height)),
+            TextureFormat::Rgb565 => Ok(encode_rgb565(rgba, width, height)),
+            TextureFormat::Rgba8 => Ok(encode_rgba8(rgba, width, height)),
+            TextureFormat::Cmpr => Ok(encode_cmpr(rgba, width, height)),
+            TextureFormat::I4 => Ok(encode_i4(rgba, width, height)),
+            TextureFormat::I8 => Ok(encode_8bit(rgba, width, height, (8, 4), intensity)),
+            TextureFormat::Ia4 => Ok(encode_8bit(rgba, width, height, (8, 4), encode_ia4_pixel)),
+            TextureFormat::Ia8 => Ok(encode_16bit(rgba, width, height, encode_ia8_pixel)),
+            _ => bail!("{self} textures are not supported"),
+        }
+    }
+}
+
+impl TextureFormat {
+    /// Quantize linear RGBA8 pixels to a palette in `palette_format` and encode them as indices
+    /// in this color indexed format
+    pub fn encode_indexed(
+        &self,
+        rgba: &[u8],
+        width: usize,
+        height: usize,
+        palette_format: PaletteFormat,
+    ) -> anyhow::Result<(Vec<u8>, Tlut)> {
+        ensure!(
+            rgba.len() == width * height * 4,
+            "Expected {width}x{height} RGBA pixels"
+        );
+        let pixels: Vec<[u8; 4]> = rgba
+            .chunks_exact(4)
+            .map(|p| p.try_into().unwrap())
+            .collect();
+        let tlut = Tlut::from_colors(palette_format, &quantize(&pixels, self.max_colors()));
+        let data = self.encode_with_tlut(rgba, width, height, &tlut)?;
+        Ok((data, tlut))
+    }
+
+    /// Quantize several images of the same size to one palette shared between them, and encode
+    /// each as indices in this color indexed format
+    pub fn encode_indexed_shared(
+        &self,
+        images: &[&[u8]],
+        width: usize,
+        height: usize,
+        palette_format: PaletteFormat,
+    ) -> anyhow::Result<(Vec<Vec<u8>>, Tlut)> {
+        ensure!(
+            images.iter().all(|rgba| rgba.len() == width * height * 4),
+            "Expected {width}x{height} RGBA pixels"
+        );
+        let pixels: Vec<[u8; 4]> = images
+            .iter()
+            .flat_map(|rgba| rgba.chunks_exact(4))
+            .map(|p| p.try_into().unwrap())
+            .collect();
+        let tlut = Tlut::from_colors(palette_format, &quantize(&pixels, self.max_colors()));
+        let data = images
+            .iter()
+            .map(|rgba| self.encode_with_tlut(rgba, width, height, &tlut))
+            .collect::<anyhow::Result<_>>()?;
+        Ok((data, tlut))
+    }
+
+    /// Encode linear RGBA8 pixels as indices of the nearest colors in an existing palette
+    pub fn encode_with_tlut(
+        &self,
+        rgba: &[u8],
+        width: usize,
+        height: usize,
+        tlut: &Tlut,
+    ) -> anyhow::Result<Vec<u8>> {
+        match self {
+            TextureFormat::Ci4 | TextureFormat::Ci8 => {}
+            _ => bail!("{self} textures are not supported"),
+        }
+        ensure!(
+            tlut.len() <= self.max_colors(),
+            "{self} textures can't use more than {} palette entries",
+            self.max_colors()
+        );
+        let colors = tlut.colors();
+        let mut nearest = HashMap::new();
+        let indices: Vec<usize> = rgba
+            .chunks_exact(4)
+            .map(|p| {
+                let p: [u8; 4] = p.try_into().unwrap();
+                *nearest
+                    .entry(p)
+                    .or_insert_with(|| nearest_color(&colors, p))
+            })
+            .collect();
+        Ok(encode_indices(*self, &indices, width, height))
+    }
+}
+
+/// An encoded texture and its mipmaps
+#[derive(Debug, Clone)]
+pub struct EncodedTexture {
+    /// Encoded data of every image, largest first
+    pub data: Vec<u8>,
+    /// Palette shared by every image, for the color indexed formats
+    pub tlut: Option<Tlut>,
+    /// Number of images, including the full size one
+    pub image_count: usize,
+}
+
+/// Number of images in a full mipmap chain for a `width` by `height` texture, halving the larger
+/// side until it reaches 1
+pub fn max_image_count(width: usize, height: usize) -> usize {
+    (usize::BITS - width.max(height).max(1).leading_zeros()) as usize
+}
+
+/// Encode linear RGBA8 pixels in any format, along with a palette in `palette_format` for the
+/// color indexed formats. With a mipmap filter, a full chain of mipmaps down to 1x1 follows the
+/// image.
+pub fn encode_texture(
+    format: TextureFormat,
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    palette_format: PaletteFormat,
+    mipmap_filter: Option<MipmapFilter>,
+) -> anyhow::Result<EncodedTexture> {
+    let (mut data, tlut) = if format.is_indexed() {
+        let (data, tlut) = format.encode_indexed(rgba, width, height, palette_format)?;
+        (data, Some(tlut))
+    } else {
+        (format.encode(rgba, width, height)?, None)
+    };
+    let mut image_count = 1;
+    if let Some(filter) = mipmap_filter {
+        ensure!(
+            width.is_power_of_two() && height.is_power_of_two(),
+            "Mipmapped textures must have power of two dimensions, got {width}x{height}"
+        );
+        let (mut level, mut w, mut h) = (rgba.to_vec(), width, height);
+        while w > 1 || h > 1 {
+            (level, w, h) = downscale(&level, w, h, filter);
+            data.extend_from_slice(&match &tlut {
+                Some(tlut) => format.encode_with_tlut(&level, w, h, tlut)?,
+                None => format.encode(&level, w, h)?,
+            });
+            image_count += 1;
+        }
+    }
+    Ok(EncodedTexture {
+        data,
+        tlut,
+        image_count,
+    })
+}
+
+/// Downscaling filter for generating mipmaps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MipmapFilter {
+    /// Average of each 2x2 block
+    #[default]
+    Box,
+    /// Lanczos with three lobes, for sharper mipmaps
+    Lanczos,
+}
+
+impl MipmapFilter {
+    pub fn parse(name: &str) -> anyhow::Result<MipmapFilter> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Ok(MipmapFilter::Box),
+            "lanczos" => Ok(MipmapFilter::Lanczos),
+            _ => bail!("Unknown mipmap filter '{name}'"),
+        }
+    }
+
+    /// Half the width of the filter, in destination pixels
+    fn radius(&self) -> f32 {
+        match self {
+            MipmapFilter::Box => 0.5,
+            MipmapFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let sinc = |x: f32| {
+            if x == 0.0 {
+                1.0
+            } else {
+                let x = x * std::f32::consts::PI;
+                x.sin() / x
+            }
+        };
+        match self {
+            MipmapFilter::Box => 1.0,
+            MipmapFilter::Lanczos => sinc(x) * sinc(x / 3.0),
+        }
+    }
+}
+
+/// Halve an image's dimensions (down to 1), returning (pixels, width, height). Colors are
+/// weighted by alpha so transparent pixels don't bleed into their neighbors.
+pub fn downscale(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    filter: MipmapFilter,
+) -> (Vec<u8>, usize, usize) {
+    let premultiplied: Vec<f32> = rgba
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3] as f32 / 255.0;
+            [
+                p[0] as f32 * a,
+                p[1] as f32 * a,
+                p[2] as f32 * a,
+                p[3] as f32,
+            ]
+        })
+        .collect();
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let horizontal = resample_axis(&premultiplied, width, height, new_width, true, filter);
+    let both = resample_axis(&horizontal, new_width, height, new_height, false, filter);
+    let out = both
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3].clamp(0.0, 255.0);
+            let unpremultiply = |v: f32| {
+                if a > 0.0 {
+                    (v * 255.0 / a).round().clamp(0.0, 255.0) as u8
+                } else {
+                    0
+                }
+            };
+            [
+                unpremultiply(p[0]),
+                unpremultiply(p[1]),
+                unpremultiply(p[2]),
+                a.round() as u8,
+            ]
+        })
+        .collect();
+    (out, new_width, new_height)
+}
+
+/// Resample RGBA rows (or columns) of `pixels` from their current length to `new_len`
+fn resample_axis(
+    pixels: &[f32],
+    width: usize,
+    height: usize,
+    new_len: usize,
+    horizontal: bool,
+    filter: MipmapFilter,
+) -> Vec<f32> {
+    let (len, lines) = if horizontal {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    let (new_width, new_height) = if horizontal {
+        (new_len, height)
+    } else {
+        (width, new_len)
+    };
+    let index = |line: usize, i: usize, w: usize| {
+        if horizontal {
+            line * w + i
+        } else {
+            i * w + line
+        }
+    };
+
+    let scale = len as f32 / new_len as f32;
+    let mut out = vec![0.0; new_width * new_height * 4];
+    for i in 0..new_len {
+        let center = (i as f32 + 0.5) * scale;
+        let radius = filter.radius() * scale;
+        let first = (center - radius).floor() as isize;
+        let last = (center + radius).ceil() as isize;
+        let taps: Vec<(usize, f32)> = (first..=last)
+            .map(|j| {
+                let w = filter.weight((j as f32 + 0.5 - center) / scale);
+                let inside = ((j as f32 + 0.5 - center) / scale).abs() < filter.radius();
+                (
+                    j.clamp(0, len as isize - 1) as usize,
+                    if inside { w } else { 0.0 },
+                )
+            })
+            .collect();
+        let total: f32 = taps.iter().map(|(_, w)| w).sum();
+        for line in 0..lines {
+            let dest = index(line, i, new_width) * 4;
+            for &(j, w) in &taps {
+                let src = index(line, j, width) * 4;
+                for c in 0..4 {
+                    out[dest + c] += pixels[src + c] * w / total;
+                }
+            }
+        }
+    }
+    out
+}
+
+impl Display for TextureFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = TextureFormat::NAMES
+            .iter()
+            .find(|(format, _)| format == self)
+            .unwrap();
+        write!(f, "{}", name.to_ascii_uppercase())
+    }
+}
+
+/// Pixel coordinates in the order they're stored in a tiled image, including padding pixels
+/// outside the image
+pub fn tile_order(
+    width: usize,
+    height: usize,
+    (tile_width, tile_height): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    (0..height.div_ceil(tile_height)).flat_map(move |tile_y| {
+        (0..width.div_ceil(tile_width)).flat_map(move |tile_x| {
+            (0..tile_height).flat_map(move |y| {
+                (0..tile_width).map(move |x| (tile_x * tile_width + x, tile_y * tile_height + y))
+            })
+        })
+    })
+}
+
+fn decode_rgb565_pixel(pixel: u16) -> [u8; 4] {
+    let expand5 = |v: u16| ((v << 3) | (v >> 2)) as u8;
+    let expand6 = |v: u16| ((v << 2) | (v >> 4)) as u8;
+    [
+        expand5(pixel >> 11),
+        expand6((pixel >> 5) & 0x3F),
+        expand5(pixel & 0x1F),
+        0xFF,
+    ]
+}
+
+fn encode_rgb565_pixel([r, g, b, _]: [u8; 4]) -> u16 {
+    let (r, g, b) = (r as u16, g as u16, b as u16);
+    (r >> 3) << 11 | (g >> 2) << 5 | (b >> 3)
+}
+
+fn decode_rgb565(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    decode_16bit(data, width, height, decode_rgb565_pixel)
+}
+
+fn encode_rgb565(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    encode_16bit(rgba, width, height, encode_rgb565_pixel)
+}
+
+/// Decode an image of one byte pixels
+fn decode_8bit(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    tile_size: (usize, usize),
+    decode_pixel: impl Fn(u8) -> [u8; 4],
+) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    for ((x, y), &pixel) in tile_order(width, height, tile_size).zip(data) {
+        if x < width && y < height {
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].copy_from_slice(&decode_pixel(pixel));
+        }
+    }
+    rgba
+}
+
+/// Encode an image as one byte pixels, with zeroes for padding pixels
+fn encode_8bit(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    tile_size: (usize, usize),
+    encode_pixel: impl Fn([u8; 4]) -> u8,
+) -> Vec<u8> {
+    tile_order(width, height, tile_size)
+        .map(|(x, y)| {
+            if x < width && y < height {
+                let i = (y * width + x) * 4;
+                encode_pixel(rgba[i..i + 4].try_into().unwrap())
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// I4 packs two pixels per byte, the first in the high nibble. Like I8, the intensity is used
+/// for every channel, including alpha.
+fn decode_i4(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    let pixels = data.iter().flat_map(|&b| [b >> 4, b & 0xF]);
+    for ((x, y), pixel) in tile_order(width, height, (8, 8)).zip(pixels) {
+        if x < width && y < height {
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].fill(pixel * 0x11);
+        }
+    }
+    rgba
+}
+
+fn encode_i4(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let nibbles: Vec<u8> = tile_order(width, height, (8, 8))
+        .map(|(x, y)| {
+            if x < width && y < height {
+                let i = (y * width + x) * 4;
+                quantize_4bit(intensity(rgba[i..i + 4].try_into().unwrap()))
+            } else {
+                0
+            }
+        })
+        .collect();
+    nibbles.chunks_exact(2).map(|n| n[0] << 4 | n[1]).collect()
+}
+
+/// Round an 8-bit value to 4 bits
+fn quantize_4bit(v: u8) -> u8 {
+    ((v as u16 * 15 + 127) / 255) as u8
+}
+
+/// Decode an IA4 pixel: alpha in the high nibble, intensity in the low nibble
+fn decode_ia4_pixel(pixel: u8) -> [u8; 4] {
+    let i = (pixel & 0xF) * 0x11;
+    [i, i, i, (pixel >> 4) * 0x11]
+}
+
+fn encode_ia4_pixel(color: [u8; 4]) -> u8 {
+    quantize_4bit(color[3]) << 4 | quantize_4bit(intensity(color))
+}
+
+/// RGBA8 tiles store the alpha and red of all 16 pixels, then their green and blue
+fn decode_rgba8(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    let positions: Vec<_> = tile_order(width, height, (4, 4)).collect();
+    for (tile, positions) in data.chunks_exact(64).zip(positions.chunks_exact(16)) {
+        for (j, &(x, y)) in positions.iter().enumerate() {
+            if x < width && y < height {
+                let i = (y * width + x) * 4;
+                let (ar, gb) = (&tile[j * 2..], &tile[32 + j * 2..]);
+                rgba[i..i + 4].copy_from_slice(&[ar[1], gb[0], gb[1], ar[0]]);
+            }
+        }
+    }
+    rgba
+}
+
+fn encode_rgba8(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let positions: Vec<_> = tile_order(width, height, (4, 4)).collect();
+    let mut data = Vec::with_capacity(positions.len() * 4);
+    for positions in positions.chunks_exact(16) {
+        let mut tile = [0u8; 64];
+        for (j, &(x, y)) in positions.iter().enumerate() {
+            if x < width && y < height {
+                let i = (y * width + x) * 4;
+                let [r, g, b, a] = rgba[i..i + 4].try_into().unwrap();
+                tile[j * 2..j * 2 + 2].copy_from_slice(&[a, r]);
+                tile[32 + j * 2..32 + j * 2 + 2].copy_from_slice(&[g, b]);
+            }
+        }
+        data.extend_from_slice(&tile);
+    }
+    data
+}
+
+/// Decode an RGB5A3 pixel. Pixels with the top bit set are opaque RGB555; otherwise they're
+/// ARGB3444.
+pub fn decode_rgb5a3_pixel(pixel: u16) -> [u8; 4] {
+    if pixel & 0x8000 != 0 {
+        let expand = |v: u16| ((v << 3) | (v >> 2)) as u8;
+        [
+            expand((pixel >> 10) & 0x1F),
+            expand((pixel >> 5) & 0x1F),
+            expand(pixel & 0x1F),
+            0xFF,
+        ]
+    } else {
+        let a = (pixel >> 12) & 0x7;
+        [
+            ((pixel >> 8) & 0xF) as u8 * 0x11,
+            ((pixel >> 4) & 0xF) as u8 * 0x11,
+            (pixel & 0xF) as u8 * 0x11,
+            ((a << 5) | (a << 2) | (a >> 1)) as u8,
+        ]
+    }
+}
+
+/// Encode an RGB5A3 pixel. Alpha that would round to fully opaque gets the extra color precision
+/// of RGB555; anything more transparent is stored as ARGB3444.
+pub fn encode_rgb5a3_pixel([r, g, b, a]: [u8; 4]) -> u16 {
+    let (r, g, b, a) = (r as u16, g as u16, b as u16, a as u16);
+    if a >> 5 == 0x7 {
+        0x8000 | (r >> 3) << 10 | (g >> 3) << 5 | (b >> 3)
+    } else {
+        (a >> 5) << 12 | (r >> 4) << 8 | (g >> 4) << 4 | (b >> 4)
+    }
+}
+
+/// Decode a tiled RGB5A3 image (4x4 pixel tiles) to linear RGBA8
+pub fn decode_rgb5a3(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    decode_16bit(data, width, height, decode_rgb5a3_pixel)
+}
+
+/// Encode linear RGBA8 as a tiled RGB5A3 image (4x4 pixel tiles)
+pub fn encode_rgb5a3(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    encode_16bit(rgba, width, height, encode_rgb5a3_pixel)
+}
+
+/// Decode an image of big-endian 16-bit pixels in 4x4 tiles
+fn decode_16bit(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    decode_pixel: impl Fn(u16) -> [u8; 4],
+) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    let pixels = data
+        .chunks_exact(2)
+        .map(|p| u16::from_be_bytes([p[0], p[1]]));
+    for ((x, y), pixel) in tile_order(width, height, (4, 4)).zip(pixels) {
+        if x < width && y < height {
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].copy_from_slice(&decode_pixel(pixel));
+        }
+    }
+    rgba
+}
+
+/// Encode an image as big-endian 16-bit pixels in 4x4 tiles, with zeroes for padding pixels
+fn encode_16bit(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    encode_pixel: impl Fn([u8; 4]) -> u16,
+) -> Vec<u8> {
+    tile_order(width, height, (4, 4))
+        .flat_map(|(x, y)| {
+            let pixel = if x < width && y < height {
+                let i = (y * width + x) * 4;
+                encode_pixel(rgba[i..i + 4].try_into().unwrap())
+            } else {
+                0
+            };
+            pixel.to_be_bytes()
+        })
+        .collect()
+}
+
+/// Top left corners of the 4x4 blocks in a CMPR image, in storage order. Each 8x8 tile holds four
+/// DXT1 blocks: top left, top right, bottom left, bottom right.
+fn cmpr_blocks(width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+    tile_order(width, height, (8, 8))
+        .step_by(64)
+        .flat_map(|(x, y)| [(x, y), (x + 4, y), (x, y + 4), (x + 4, y + 4)])
+}
+
+/// The four colors a CMPR block can use. With `color0 <= color1`, the last is transparent.
+fn cmpr_palette(color0: u16, color1: u16) -> [[u8; 4]; 4] {
+    let c0 = decode_rgb565_pixel(color0);
+    let c1 = decode_rgb565_pixel(color1);
+    let mix = |w0: u16, w1: u16| -> [u8; 4] {
+        let channel = |i: usize| ((c0[i] as u16 * w0 + c1[i] as u16 * w1) / (w0 + w1)) as u8;
+        [channel(0), channel(1), channel(2), 0xFF]
+    };
+    if color0 > color1 {
+        [c0, c1, mix(2, 1), mix(1, 2)]
+    } else {
+        [c0, c1, mix(1, 1), [0; 4]]
+    }
+}
+
+/// Number of opaque colors in a CMPR block's palette
+fn cmpr_color_count(color0: u16, color1: u16) -> usize {
+    if color0 > color1 {
+        4
+    } else {
+        3
+    }
+}
+
+fn decode_cmpr(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    for (block, (block_x, block_y)) in data.chunks_exact(8).zip(cmpr_blocks(width, height)) {
+        let color0 = u16::from_be_bytes([block[0], block[1]]);
+        let color1 = u16::from_be_bytes([block[2], block[3]]);
+        let palette = cmpr_palette(color0, color1);
+        for (dy, row) in block[4..].iter().enumerate() {
+            for dx in 0..4 {
+                let (x, y) = (block_x + dx, block_y + dy);
+                if x < width && y < height {
+                    let index = (row >> (6 - dx * 2)) & 3;
+                    let i = (y * width + x) * 4;
+                    rgba[i..i + 4].copy_from_slice(&palette[index as usize]);
+                }
+            }
+        }
+    }
+    rgba
+}
+
+fn encode_cmpr(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(TextureFormat::Cmpr.data_size(width, height));
+    for (block_x, block_y) in cmpr_blocks(width, height) {
+        // Pixels outside the image can be any color, so they're left out of the fit
+        let mut pixels = [None; 16];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (block_x + i % 4, block_y + i / 4);
+            if x < width && y < height {
+                let j = (y * width + x) * 4;
+                *pixel = Some(<[u8; 4]>::try_from(&rgba[j..j + 4]).unwrap());
+            }
+        }
+        data.extend_from_slice(&encode_cmpr_block(&pixels));
+    }
+    data
+}
+
+/// Pixels with less alpha than this are encoded as transparent
+const CMPR_ALPHA_THRESHOLD: u8 = 0x80;
+
+type Color = [f32; 3];
+
+fn to_color(pixel: [u8; 4]) -> Color {
+    [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+}
+
+fn distance(a: Color, b: Color) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+fn quantize_rgb565(color: Color) -> u16 {
+    let channel = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    channel(color[0], 31.0) << 11 | channel(color[1], 63.0) << 5 | channel(color[2], 31.0)
+}
+
+/// Ends of the line through `colors` along their direction of greatest variance
+fn principal_endpoints(colors: &[Color]) -> (Color, Color) {
+    let n = colors.len() as f32;
+    let mut mean = [0.0; 3];
+    for c in colors {
+        for i in 0..3 {
+            mean[i] += c[i] / n;
+        }
+    }
+    let mut covariance = [[0.0f32; 3]; 3];
+    for c in colors {
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (c[i] - mean[i]) * (c[j] - mean[j]);
+            }
+        }
+    }
+    // Power iteration for the covariance matrix's largest eigenvector, starting from the row of
+    // the channel that varies most. A fixed start like gray can be orthogonal to the answer, as
+    // it is for a block of red and blue.
+    let widest = (0..3)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap();
+    let mut axis = covariance[widest];
+    for _ in 0..8 {
+        let next: Color = std::array::from_fn(|i| (0..3).map(|j| covariance[i][j] * axis[j]).sum());
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            break;
+        }
+        axis = next.map(|v| v / length);
+    }
+
+    let project = |c: &Color| (0..3).map(|i| (c[i] - mean[i]) * axis[i]).sum::<f32>();
+    let (min, max) = colors
+        .iter()
+        .map(project)
+        .fold((0.0f32, 0.0f32), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    (
+        std::array::from_fn(|i| mean[i] + axis[i] * min),
+        std::array::from_fn(|i| mean[i] + axis[i] * max),
+    )
+}
+
+/// Indices of the nearest of the first `count` palette colors, and the total squared error
+fn fit_indices(colors: &[Color], palette: &[[u8; 4]; 4], count: usize) -> (Vec<usize>, f32) {
+    let mut error = 0.0;
+    let indices = colors
+        .iter()
+        .map(|&c| {
+            let (index, d) = (0..count)
+                .map(|i| (i, distance(c, to_color(palette[i]))))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            error += d;
+            index
+        })
+        .collect();
+    (indices, error)
+}
+
+/// Least squares endpoints for colors assigned to palette entries with the given weights of
+/// color0
+fn refine_endpoints(
+    colors: &[Color],
+    indices: &[usize],
+    weights: &[f32; 4],
+) -> Option<(Color, Color)> {
+    // Solve for the endpoints a and b minimizing the sum of |w a + (1 - w) b - c|^2
+    let (mut aa, mut ab, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+    let mut ac = [0.0f32; 3];
+    let mut bc = [0.0f32; 3];
+    for (c, &index) in colors.iter().zip(indices) {
+        let w = weights[index];
+        aa += w * w;
+        ab += w * (1.0 - w);
+        bb += (1.0 - w) * (1.0 - w);
+        for i in 0..3 {
+            ac[i] += w * c[i];
+            bc[i] += (1.0 - w) * c[i];
+        }
+    }
+    let det = aa * bb - ab * ab;
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    Some((
+        std::array::from_fn(|i| (ac[i] * bb - bc[i] * ab) / det),
+        std::array::from_fn(|i| (bc[i] * aa - ac[i] * ab) / det),
+    ))
+}
+
+/// Encode a 4x4 block, with `None` for pixels outside the image
+fn encode_cmpr_block(pixels: &[Option<[u8; 4]>; 16]) -> [u8; 8] {
+    let transparent = pixels.iter().flatten().any(|p| p[3] < CMPR_ALPHA_THRESHOLD);
+    let colors: Vec<Color> = pixels
+        .iter()
+        .flatten()
+        .filter(|p| p[3] >= CMPR_ALPHA_THRESHOLD)
+        .map(|&p| to_color(p))
+        .collect();
+
+    let order = |a: u16, b: u16| {
+        if transparent {
+            (a.min(b), a.max(b))
+        } else {
+            (a.max(b), a.min(b))
+        }
+    };
+
+    let mut best = (0u16, 0u16, f32::INFINITY);
+    if !colors.is_empty() {
+        let (mut start, mut end) = principal_endpoints(&colors);
+        for _ in 0..2 {
+            let (color0, color1) = order(quantize_rgb565(start), quantize_rgb565(end));
+            let palette = cmpr_palette(color0, color1);
+            let (indices, error) = fit_indices(&colors, &palette, cmpr_color_count(color0, color1));
+            if error < best.2 {
+                best = (color0, color1, error);
+            }
+            // Equal endpoints mean three color mode, so opaque blocks can only use color0
+            if color0 == color1 && !transparent {
+                break;
+            }
+            // Weights of color0 in each palette entry
+            let weights = if color0 > color1 {
+                [1.0, 0.0, 2.0 / 3.0, 1.0 / 3.0]
+            } else {
+                [1.0, 0.0, 0.5, 0.0]
+            };
+            match refine_endpoints(&colors, &indices, &weights) {
+                Some((a, b)) => (start, end) = (a, b),
+                None => break,
+            }
+        }
+    }
+
+    let (color0, color1, _) = best;
+    let palette = cmpr_palette(color0, color1);
+    let mut block = [0u8; 8];
+    block[0..2].copy_from_slice(&color0.to_be_bytes());
+    block[2..4].copy_from_slice(&color1.to_be_bytes());
+    for (i, pixel) in pixels.iter().enumerate() {
+        let index = match pixel {
+            Some(p) if p[3] >= CMPR_ALPHA_THRESHOLD => {
+                fit_indices(&[to_color(*p)], &palette, cmpr_color_count(color0, color1)).0[0]
+            }
+            // Transparent if the block has a transparent entry, otherwise any opaque color
+            Some(_) => 3,
+            None => 0,
+        };
+        block[4 + i / 4] |= (index as u8) << (6 - (i % 4) * 2);
+    }
+    block
+}
+
+/// Texture coordinate wrapping
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u32)]
+pub enum WrapMode {
+    #[default]
+    Clamp = 0,
+    Repeat = 1,
+    Mirror = 2,
+}
+
+impl WrapMode {
+    pub fn parse(name: &str) -> anyhow::Result<WrapMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Ok(WrapMode::Clamp),
+            "repeat" => Ok(WrapMode::Repeat),
+            "mirror" => Ok(WrapMode::Mirror),
+            _ => bail!("Unknown wrap mode '{name}'"),
+        }
+    }
+}
+
+/// Pixel format of the entries in a palette (TLUT)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u32)]
+pub enum PaletteFormat {
+    Ia8 = 0,
+    Rgb565 = 1,
+    #[default]
+    Rgb5a3 = 2,
+}
+
+impl PaletteFormat {
+    pub fn parse(name: &str) -> anyhow::Result<PaletteFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ia8" => Ok(PaletteFormat::Ia8),
+            "rgb565" => Ok(PaletteFormat::Rgb565),
+            "rgb5a3" => Ok(PaletteFormat::Rgb5a3),
+            _ => bail!("Unknown palette format '{name}'"),
+        }
+    }
+
+    fn decode_color(&self, entry: u16) -> [u8; 4] {
+        match self {
+            PaletteFormat::Ia8 => decode_ia8_pixel(entry),
+            PaletteFormat::Rgb565 => decode_rgb565_pixel(entry),
+            PaletteFormat::Rgb5a3 => decode_rgb5a3_pixel(entry),
+        }
+    }
+
+    fn encode_color(&self, color: [u8; 4]) -> u16 {
+        match self {
+            PaletteFormat::Ia8 => encode_ia8_pixel(color),
+            PaletteFormat::Rgb565 => encode_rgb565_pixel(color),
+            PaletteFormat::Rgb5a3 => encode_rgb5a3_pixel(color),
+        }
+    }
+}
+
+impl Display for PaletteFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            PaletteFormat::Ia8 => "IA8",
+            PaletteFormat::Rgb565 => "RGB565",
+            PaletteFormat::Rgb5a3 => "RGB5A3",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A palette (texture lookup table) for the color indexed formats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tlut {
+    pub format: PaletteFormat,
+    /// Big-endian 16-bit entries
+    pub data: Vec<u8>,
+}
+
+impl Tlut {
+    pub fn from_colors(format: PaletteFormat, colors: &[[u8; 4]]) -> Tlut {
+        Tlut {
+            format,
+            data: colors
+                .iter()
+                .flat_map(|&c| format.encode_color(c).to_be_bytes())
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Every entry decoded to RGBA8
+    pub fn colors(&self) -> Vec<[u8; 4]> {
+        self.data
+            .chunks_exact(2)
+            .map(|e| self.format.decode_color(u16::from_be_bytes([e[0], e[1]])))
+            .collect()
+    }
+}
+
+/// Luminance of an RGB color, as used for the intensity formats. Alpha is ignored, so decoding and
+/// re-encoding an I4 or I8 texture keeps it the same.
+fn intensity([r, g, b, _]: [u8; 4]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+/// Decode an IA8 pixel: alpha in the high byte, intensity in the low byte
+fn decode_ia8_pixel(pixel: u16) -> [u8; 4] {
+    let [a, i] = pixel.to_be_bytes();
+    [i, i, i, a]
+}
+
+fn encode_ia8_pixel(color: [u8; 4]) -> u16 {
+    u16::from_be_bytes([color[3], intensity(color)])
+}
+
+/// Median cut color quantization, returning at most `max_colors` colors. Images with few enough
+/// distinct colors keep them exactly.
+pub fn quantize(pixels: &[[u8; 4]], max_colors: usize) -> Vec<[u8; 4]> {
+    let mut histogram: HashMap<[u8; 4], u32> = HashMap::new();
+    for &p in pixels {
+        *histogram.entry(normalize_transparent(p)).or_default() += 1;
+    }
+    let mut colors: Vec<([u8; 4], u32)> = histogram.into_iter().collect();
+    colors.sort();
+    if colors.len() <= max_colors {
+        return colors.into_iter().map(|(c, _)| c).collect();
+    }
+
+    // Repeatedly split the box with the widest channel range at its weighted median
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+        let mut b = boxes.swap_remove(index);
+        b.sort_by_key(|(c, _)| c[channel]);
+        let total: u32 = b.iter().map(|(_, n)| n).sum();
+        let mut count = 0;
+        let split = b
+            .iter()
+            .position(|(_, n)| {
+                count += n;
+                count * 2 >= total
+            })
+            .unwrap()
+            .clamp(0, b.len() - 2)
+            + 1;
+        let rest = b.split_off(split);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+
+    let mut palette: Vec<[u8; 4]> = boxes
+        .iter()
+        .map(|b| {
+            let total: u64 = b.iter().map(|&(_, n)| n as u64).sum();
+            std::array::from_fn(|i| {
+                let sum: u64 = b.iter().map(|&(c, n)| c[i] as u64 * n as u64).sum();
+                ((sum + total / 2) / total) as u8
+            })
+        })
+        .collect();
+    palette.sort();
+    palette
+}
+
+/// (channel, range) of the RGBA channel with the widest range of values
+fn widest_channel(colors: &[([u8; 4], u32)]) -> (usize, u8) {
+    (0..4)
+        .map(|i| {
+            let min = colors.iter().map(|(c, _)| c[i]).min().unwrap();
+            let max = colors.iter().map(|(c, _)| c[i]).max().unwrap();
+            (i, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+/// Fully transparent pixels look the same whatever their color, so they share one palette entry
+fn normalize_transparent(color: [u8; 4]) -> [u8; 4] {
+    if color[3] == 0 {
+        [0; 4]
+    } else {
+        color
+    }
+}
+
+/// Index of the palette color closest to `color`, including alpha
+fn nearest_color(palette: &[[u8; 4]], color: [u8; 4]) -> usize {
+    let color = normalize_transparent(color);
+    (0..palette.len())
+        .min_by_key(|&i| {
+            (0..4)
+                .map(|c| (palette[i][c] as i32 - color[c] as i32).pow(2))
+                .sum::<i32>()
+        })
+        .unwrap_or(0)
+}
+
+fn encode_indices(
+    format: TextureFormat,
+    indices: &[usize],
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let index_at = |(x, y): (usize, usize)| {
+        if x < width && y < height {
+            indices[y * width + x]
+        } else {
+            0
+        }
+    };
+    let positions = tile_order(width, height, format.tile_size());
+    match format {
+        TextureFormat::Ci4 => positions
+            .collect::<Vec<_>>()
+            .chunks_exact(2)
+            .map(|pair| ((index_at(pair[0]) << 4) | index_at(pair[1])) as u8)
+            .collect(),
+        TextureFormat::Ci8 => positions.map(|p| index_at(p) as u8).collect(),
+        _ => positions
+            .flat_map(|p| (index_at(p) as u16).to_be_bytes())
+            .collect(),
+    }
+}
+
+fn decode_indexed(
+    format: TextureFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    tlut: &Tlut,
+) -> Vec<u8> {
+    let colors = tlut.colors();
+    let indices: Box<dyn Iterator<Item = usize>> = match format {
+        TextureFormat::Ci4 => Box::new(
+            data.iter()
+                .flat_map(|&b| [(b >> 4) as usize, (b & 0xF) as usize]),
+        ),
+        TextureFormat::Ci8 => Box::new(data.iter().map(|&b| b as usize)),
+        _ => Box::new(
+            data.chunks_exact(2)
+                .map(|p| (u16::from_be_bytes([p[0], p[1]]) & 0x3FFF) as usize),
+        ),
+    };
+    let mut rgba = vec![0; width * height * 4];
+    for ((x, y), index) in tile_order(width, height, format.tile_size()).zip(indices) {
+        if x < width && y < height {
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].copy_from_slice(&colors.get(index).copied().unwrap_or_default());
+        }
+    }
+    rgba
+}
+
+fn to_rgba(pixels: Vec<u8>, color_type: png::ColorType) -> anyhow::Result<Vec<u8>> {
+    Ok(match color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xFF])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&v| [v, v, v, 0xFF]).collect(),
+        png::ColorType::Indexed => bail!("Indexed PNG was not expanded"),
+    })
+}
+
+/// Decode a PNG of any color type to RGBA8, returning (pixels, width, height)
+pub fn read_png(buf: &[u8]) -> anyhow::Result<(Vec<u8>, usize, usize)> {
+    let mut decoder = png::Decoder::new(buf);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+    pixels.truncate(info.buffer_size());
+    let rgba = to_rgba(pixels, info.color_type)?;
+    Ok((rgba, info.width as usize, info.height as usize))
+}
+
+/// A frame of an animated PNG: its RGBA8 pixels, composited onto the frames before it, and how
+/// many seconds it's shown for
+pub struct PngFrame {
+    pub rgba: Vec<u8>,
+    pub delay: f32,
+}
+
+/// Decode each frame of an animated PNG, returning (frames, width, height). A PNG that isn't
+/// animated has one frame with no delay.
+pub fn read_apng(buf: &[u8]) -> anyhow::Result<(Vec<PngFrame>, usize, usize)> {
+    let mut decoder = png::Decoder::new(buf);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    let (width, height) = (width as usize, height as usize);
+    let Some(animation) = reader.info().animation_control else {
+        let (rgba, _, _) = read_png(buf)?;
+        return Ok((vec![PngFrame { rgba, delay: 0.0 }], width, height));
+    };
+    // The default image is only part of the animation if it has frame control before it
+    let mut skip_default = reader.info().frame_control.is_none();
+
+    let mut canvas = vec![0; width * height * 4];
+    let mut frames = Vec::new();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    while frames.len() < animation.num_frames as usize {
+        let info = reader.next_frame(&mut pixels)?;
+        if std::mem::take(&mut skip_default) {
+            continue;
+        }
+        let control = reader
+            .info()
+            .frame_control
+            .ok_or_else(|| anyhow!("Animated PNG frame has no frame control"))?;
+        let frame = to_rgba(pixels[..info.buffer_size()].to_vec(), info.color_type)?;
+        let (x0, y0) = (control.x_offset as usize, control.y_offset as usize);
+        let (w, h) = (control.width as usize, control.height as usize);
+        ensure!(
+            x0 + w <= width && y0 + h <= height,
+            "Animated PNG frame extends past the edge of the image"
+        );
+
+        let previous = canvas.clone();
+        for y in 0..h {
+            for x in 0..w {
+                let src = &frame[(y * w + x) * 4..][..4];
+                let dst = &mut canvas[((y0 + y) * width + x0 + x) * 4..][..4];
+                match control.blend_op {
+                    png::BlendOp::Source => dst.copy_from_slice(src),
+                    png::BlendOp::Over => blend_over(dst, src),
+                }
+            }
+        }
+        let delay_den = if control.delay_den == 0 {
+            100
+        } else {
+            control.delay_den
+        };
+        frames.push(PngFrame {
+            rgba: canvas.clone(),
+            delay: control.delay_num as f32 / delay_den as f32,
+        });
+
+        match control.dispose_op {
+            png::DisposeOp::None => {}
+            png::DisposeOp::Background => {
+                for y in y0..y0 + h {
+                    canvas[(y * width + x0) * 4..(y * width + x0 + w) * 4].fill(0);
+                }
+            }
+            png::DisposeOp::Previous => canvas = previous,
+        }
+    }
+    Ok((frames, width, height))
+}
+
+/// Composite an RGBA8 pixel over another
+fn blend_over(dst: &mut [u8], src: &[u8]) {
+    let src_alpha = src[3] as u32;
+    if src_alpha == 0xFF {
+        dst.copy_from_slice(src);
+        return;
+    }
+    let dst_alpha = dst[3] as u32 * (0xFF - src_alpha) / 0xFF;
+    let alpha = src_alpha + dst_alpha;
+    if alpha == 0 {
+        dst.fill(0);
+        return;
+    }
+    for i in 0..3 {
+        dst[i] = ((src[i] as u32 * src_alpha + dst[i] as u32 * dst_alpha) / alpha) as u8;
+    }
+    dst[3] = alpha as u8;
+}
+
+/// Encode RGBA8 pixels as a PNG
+pub fn write_png(rgba: &[u8], width: usize, height: usize) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+    const BLUE: [u8; 4] = [0, 0, 0xFF, 0xFF];
+
+    fn image(width: usize, height: usize, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn cmpr_decodes_blocks_in_tile_order() {
+        // One 8x8 tile: red, blue, red with the second palette color in its top row, transparent
+        let mut data = Vec::new();
+        for (color0, color1, rows) in [
+            (0xF800u16, 0x001Fu16, [0x00; 4]),
+            (0xF800, 0x001F, [0x55; 4]),
+            (0xF800, 0x001F, [0x55, 0, 0, 0]),
+            (0x001F, 0xF800, [0xFF; 4]),
+        ] {
+            data.extend_from_slice(&color0.to_be_bytes());
+            data.extend_from_slice(&color1.to_be_bytes());
+            data.extend_from_slice(&rows);
+        }
+        let rgba = TextureFormat::Cmpr.decode(&data, 8, 8, None).unwrap();
+        let expected = image(8, 8, |x, y| match (x / 4, y / 4) {
+            (0, 0) => RED,
+            (1, 0) => BLUE,
+            (0, 1) if y == 4 => BLUE,
+            (0, 1) => RED,
+            _ => [0; 4],
+        });
+        assert_eq!(rgba, expected);
+    }
+
+    #[test]
+    fn cmpr_round_trips_two_colors() {
+        let rgba = image(16, 16, |x, y| if (x + y) % 3 == 0 { RED } else { BLUE });
+        let data = TextureFormat::Cmpr.encode(&rgba, 16, 16).unwrap();
+        assert_eq!(data.len(), TextureFormat::Cmpr.data_size(16, 16));
+        assert_eq!(
+            TextureFormat::Cmpr.decode(&data, 16, 16, None).unwrap(),
+            rgba
+        );
+    }
+
+    #[test]
+    fn cmpr_keeps_transparency() {
+        let rgba = image(8, 8, |x, _| if x < 3 { [0x10, 0x20, 0x30, 0] } else { RED });
+        let data = TextureFormat::Cmpr.encode(&rgba, 8, 8).unwrap();
+        let decoded = TextureFormat::Cmpr.decode(&data, 8, 8, None).unwrap();
+        for (pixel, original) in decoded.chunks_exact(4).zip(rgba.chunks_exact(4)) {
+            if original[3] == 0 {
+                assert_eq!(pixel[3], 0);
+            } else {
+                assert_eq!(pixel, RED);
+            }
+        }
+    }
+
+    #[test]
+    fn cmpr_approximates_gradients() {
+        let rgba = image(12, 6, |x, y| [(x * 20) as u8, (y * 40) as u8, 0x80, 0xFF]);
+        let data = TextureFormat::Cmpr.encode(&rgba, 12, 6).unwrap();
+        // Partial tiles are padded out to whole 8x8 tiles
+        assert_eq!(data.len(), 2 * 32);
+        let decoded = TextureFormat::Cmpr.decode(&data, 12, 6, None).unwrap();
+        // Four colors on a line can't cover a 2D gradient, so only the average error is bounded
+        let error: usize = decoded
+            .iter()
+            .zip(&rgba)
+            .map(|(a, b)| a.abs_diff(*b) as usize)
+            .sum();
+        assert!(
+            error / rgba.len() <= 8,
+            "average error {}",
+            error / rgba.len()
+        );
+    }
+
+    #[test]
+    fn mipmap_chains_stop_at_one_pixel() {
+        assert_eq!(max_image_count(1024, 1024), 11);
+        assert_eq!(max_image_count(640, 8), 10);
+        assert_eq!(max_image_count(1, 1), 1);
+        // Levels past 1x1 stay 1x1 rather than overflowing the shift
+        let size = TextureFormat::I8.mipmap_data_size(8, 8, 100);
+        assert_eq!(size, 64 + 32 * 99);
+    }
+
+    #[test]
+    fn parse_rejects_more_mipmaps_than_dimensions_allow() {
+        let rgba = image(8, 8, |_, _| RED);
+        let filter = Some(MipmapFilter::Box);
+        let bti = crate::bti::Bti::from_rgba(
+            TextureFormat::I8,
+            PaletteFormat::Rgb565,
+            &rgba,
+            8,
+            8,
+            filter,
+        )
+        .unwrap();
+        let mut buf = bti.to_bytes().unwrap();
+        assert_eq!(buf[24], 4);
+        assert!(crate::bti::Bti::parse(&buf).is_ok());
+        buf[24] = 0xFF;
+        assert!(crate::bti::Bti::parse(&buf).is_err());
+
+        let image = crate::tpl::TplImage::from_rgba(
+            TextureFormat::I8,
+            PaletteFormat::Rgb565,
+            &rgba,
+            8,
+            8,
+            filter,
+        )
+        .unwrap();
+        let tpl = crate::tpl::Tpl {
+            images: vec![image],
+        };
+        let mut buf = tpl.to_bytes().unwrap();
+        // max_lod of the only image header, after the file header and image table
+        let lod = 12 + 8 + 34;
+        assert_eq!(buf[lod], 3);
+        assert!(crate::tpl::Tpl::parse(&buf).is_ok());
+        buf[lod] = 0xFF;
+        assert!(crate::tpl::Tpl::parse(&buf).is_err());
+    }
+}
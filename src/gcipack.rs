@@ -1,21 +1,40 @@
-use std::{fmt::Display, time::SystemTime};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
+#[cfg(feature = "image")]
+use anyhow::Context;
+use anyhow::{anyhow, bail, ensure};
+use serde::{de::Error as _, Deserialize, Deserializer};
 use thiserror::Error;
 use zerocopy::byteorder::big_endian;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
-const MAX_FILE_NAME_SIZE: usize = 0x20;
+#[cfg(feature = "image")]
+use crate::bundle;
+use crate::formats::gci::{GciHeader, PERMISSION_NO_COPY, PERMISSION_NO_MOVE, PERMISSION_PUBLIC};
+#[cfg(feature = "image")]
+use crate::gcimage::{self, PaletteFormat, TextureFormat, Tlut};
+use crate::memcard;
+
 const MAX_TITLE_SIZE: usize = 0x20;
 const MAX_DESCRIPTION_SIZE: usize = 0x20;
 
-const BANNER_SIZE: usize = 0x1800;
-const ICON_SIZE: usize = 0x800;
 const FILE_HEADER_SIZE: usize = 0x200;
 const BLOCK_SIZE: usize = 0x2000;
 const FILE_HEADER_PADDING_SIZE: usize =
     FILE_HEADER_SIZE - MAX_TITLE_SIZE - MAX_DESCRIPTION_SIZE - size_of::<u32>();
+pub const MAX_ICON_FRAMES: usize = 8;
+/// A CI8 image's palette: 256 RGB5A3 colors
+pub const CI8_PALETTE_SIZE: usize = 0x200;
 
-#[derive(Debug)]
+const BANNER_FORMAT_NONE: u8 = 0;
+const BANNER_FORMAT_CI8: u8 = 1;
+const BANNER_FORMAT_RGB5A3: u8 = 2;
+const ICON_FORMAT_CI8_SHARED: u16 = 1;
+const ICON_FORMAT_RGB5A3: u16 = 2;
+const ICON_FORMAT_CI8: u16 = 3;
+
+#[derive(Debug, Clone, Copy)]
 pub enum StringKind {
     FileName,
     Title,
@@ -35,6 +54,41 @@ impl Display for StringKind {
     }
 }
 
+/// How a save's title and description are stored. The IPL reads them in the card's encoding,
+/// which is Shift-JIS on Japanese consoles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextEncoding {
+    #[default]
+    Ascii,
+    #[serde(alias = "sjis")]
+    ShiftJis,
+}
+
+impl TextEncoding {
+    pub fn parse(s: &str) -> anyhow::Result<TextEncoding> {
+        match s {
+            "ascii" => Ok(TextEncoding::Ascii),
+            "shift-jis" | "sjis" => Ok(TextEncoding::ShiftJis),
+            _ => bail!("invalid encoding {s}; expected ascii or shift-jis"),
+        }
+    }
+
+    fn encode(self, input: &str, kind: StringKind) -> Result<Vec<u8>, GciPackError> {
+        match self {
+            TextEncoding::Ascii if input.is_ascii() => Ok(input.as_bytes().to_vec()),
+            TextEncoding::Ascii => Err(GciPackError::StringNonAscii(kind)),
+            TextEncoding::ShiftJis => {
+                let (bytes, _, unmappable) = encoding_rs::SHIFT_JIS.encode(input);
+                if unmappable {
+                    return Err(GciPackError::StringNotShiftJis(kind));
+                }
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ImageKind {
     Banner,
@@ -59,54 +113,378 @@ pub enum GciPackError {
     StringInvalidSize { kind: StringKind, info: String },
     #[error("{0} is non-ASCII")]
     StringNonAscii(StringKind),
+    #[error("{0} has characters that can't be encoded as Shift-JIS")]
+    StringNotShiftJis(StringKind),
+    #[error("a GCI's icon has 1 to {MAX_ICON_FRAMES} frames, got {0}")]
+    IconFrameCount(usize),
+    #[error("icon frames that share a palette must all have the same one")]
+    IconPaletteMismatch,
 }
 
-#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
-#[repr(C)]
-struct GciHeader {
-    gamecode: [u8; 6],
-    unused0: u8,
-    banner_fmt: u8,
-    filename: [u8; MAX_FILE_NAME_SIZE],
-    last_modified: big_endian::U32,
-    image_offset: big_endian::U32,
-    icon_format: big_endian::U16,
-    icon_speed: big_endian::U16,
-    permissions: u8,
-    copy_times: u8,
-    first_block_num: big_endian::U16,
-    block_count: big_endian::U16,
-    unused1: big_endian::U16,
-    comment_offset: big_endian::U32,
+impl GciPackError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GciPackError::ImageInvalidSize { .. } => "invalid-image-size",
+            GciPackError::StringInvalidSize { .. } => "invalid-string-size",
+            GciPackError::StringNonAscii(_) => "non-ascii-string",
+            GciPackError::StringNotShiftJis(_) => "non-shift-jis-string",
+            GciPackError::IconFrameCount(_) => "invalid-icon-frame-count",
+            GciPackError::IconPaletteMismatch => "icon-palette-mismatch",
+        }
+    }
+}
+
+/// How long an icon frame is shown for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSpeed {
+    /// 4 video frames
+    Fast = 1,
+    /// 8 video frames
+    Medium = 2,
+    /// 12 video frames
+    Slow = 3,
+}
+
+impl IconSpeed {
+    pub fn parse(s: &str) -> anyhow::Result<IconSpeed> {
+        match s {
+            "fast" => Ok(IconSpeed::Fast),
+            "medium" => Ok(IconSpeed::Medium),
+            "slow" => Ok(IconSpeed::Slow),
+            _ => bail!("invalid icon speed {s}; expected fast, medium, or slow"),
+        }
+    }
+
+    /// The speed that shows a frame for closest to `seconds`
+    pub fn from_delay(seconds: f32) -> IconSpeed {
+        let video_frames = seconds * 60.0;
+        [IconSpeed::Fast, IconSpeed::Medium, IconSpeed::Slow]
+            .into_iter()
+            .min_by(|a, b| {
+                let distance = |s: &IconSpeed| (s.video_frames() as f32 - video_frames).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap()
+    }
+
+    pub fn video_frames(self) -> u32 {
+        self as u32 * 4
+    }
+}
+
+/// Pixel formats of banners and icons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Rgb5a3,
+    /// 256 color palette
+    Ci8,
+}
+
+impl ImageFormat {
+    pub fn parse(s: &str) -> anyhow::Result<ImageFormat> {
+        match s.to_ascii_lowercase().as_str() {
+            "rgb5a3" => Ok(ImageFormat::Rgb5a3),
+            "ci8" => Ok(ImageFormat::Ci8),
+            _ => bail!("invalid image format {s}; expected rgb5a3 or ci8"),
+        }
+    }
+}
+
+/// An encoded banner or icon frame
+#[derive(Debug, Clone, Copy)]
+pub enum GciImage<'a> {
+    Rgb5a3(&'a [u8]),
+    /// Color indices and their own palette of [`CI8_PALETTE_SIZE`] bytes
+    Ci8 {
+        indices: &'a [u8],
+        palette: &'a [u8],
+    },
+    /// Color indices into a palette shared by every icon frame, which is stored once after the
+    /// last one. Banners can't share their palette, so store it as [`GciImage::Ci8`].
+    Ci8Shared {
+        indices: &'a [u8],
+        palette: &'a [u8],
+    },
+}
+
+impl GciImage<'_> {
+    /// Check the image's size, with `name` naming it in the error
+    fn validate(
+        &self,
+        kind: ImageKind,
+        name: &str,
+        width: usize,
+        height: usize,
+    ) -> Result<(), GciPackError> {
+        let invalid = |info| GciPackError::ImageInvalidSize { kind, info };
+        let pixels = width * height;
+        match self {
+            GciImage::Rgb5a3(data) if data.len() != pixels * 2 => Err(invalid(format!(
+                "{name}should be {} ({width}x{height} RGB5A3)",
+                pixels * 2
+            ))),
+            GciImage::Ci8 { indices, .. } | GciImage::Ci8Shared { indices, .. }
+                if indices.len() != pixels =>
+            {
+                Err(invalid(format!(
+                    "{name}should be {pixels} ({width}x{height} CI8)"
+                )))
+            }
+            GciImage::Ci8 { palette, .. } | GciImage::Ci8Shared { palette, .. }
+                if palette.len() != CI8_PALETTE_SIZE =>
+            {
+                Err(invalid(format!(
+                    "{name}palette should be {CI8_PALETTE_SIZE} (256 RGB5A3 colors)"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the image's data, and its palette unless it's shared
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            GciImage::Rgb5a3(data) => out.extend_from_slice(data),
+            GciImage::Ci8 { indices, palette } => {
+                out.extend_from_slice(indices);
+                out.extend_from_slice(palette);
+            }
+            GciImage::Ci8Shared { indices, .. } => out.extend_from_slice(indices),
+        }
+    }
+}
+
+/// A frame of a GCI's icon: a 32x32 image and how long it's shown for
+#[derive(Debug, Clone, Copy)]
+pub struct IconFrame<'a> {
+    pub image: GciImage<'a>,
+    pub speed: IconSpeed,
+}
+
+impl IconFrame<'_> {
+    /// A frame of RGB5A3 data for an icon that isn't animated
+    pub fn still(data: &[u8]) -> IconFrame<'_> {
+        IconFrame {
+            image: GciImage::Rgb5a3(data),
+            speed: IconSpeed::Slow,
+        }
+    }
 }
 
+/// gcipack's file header, after the banner and icon frames: the comment shown in the memory card
+/// menu, then the payload's size
 #[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
 #[repr(C)]
-struct GciFileMetadata {
-    banner: [u8; BANNER_SIZE],
-    icon: [u8; ICON_SIZE],
+struct GciFileHeader {
     title: [u8; MAX_TITLE_SIZE],
     description: [u8; MAX_DESCRIPTION_SIZE],
     file_size: big_endian::U32,
     padding: [u8; FILE_HEADER_PADDING_SIZE],
 }
 
-fn get_modified_time_sec() -> u32 {
-    let base = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(946684800); // Jan 1 2000
-    let now = SystemTime::now();
-    now.duration_since(base).unwrap().as_secs() as u32
+/// Marker at the start of the file header's padding for payloads XORed with a key, followed by the
+/// key's length
+const XOR_MARKER: [u8; 4] = *b"XOR\0";
+pub const MAX_XOR_KEY_SIZE: usize = 0x20;
+
+/// Who can open a save and what the IPL lets users do with it, which some loaders check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GciPermissions {
+    /// Any game can open the save, not just the one with its gamecode
+    pub public: bool,
+    /// The IPL refuses to copy the save to another card
+    pub no_copy: bool,
+    /// The IPL refuses to move the save to another card
+    pub no_move: bool,
+    /// Times the save has been copied
+    pub copy_times: u8,
+}
+
+impl Default for GciPermissions {
+    fn default() -> GciPermissions {
+        GciPermissions {
+            public: true,
+            no_copy: false,
+            no_move: false,
+            copy_times: 0,
+        }
+    }
+}
+
+impl GciPermissions {
+    /// The header's permission bits
+    pub fn bits(&self) -> u8 {
+        let mut bits = 0;
+        if self.public {
+            bits |= PERMISSION_PUBLIC;
+        }
+        if self.no_copy {
+            bits |= PERMISSION_NO_COPY;
+        }
+        if self.no_move {
+            bits |= PERMISSION_NO_MOVE;
+        }
+        bits
+    }
+}
+
+/// Everything gcipack needs to build a GCI, as read from a TOML or JSON manifest so it can be
+/// checked into source control. Paths are relative to the manifest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GciPackConfig {
+    /// The payload, or a directory whose files are bundled into it
+    pub input: PathBuf,
+    /// Defaults to the input's path with a `.gci` extension
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    pub file_name: String,
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    /// Left out for a save without a banner
+    #[serde(default)]
+    pub banner: Option<PathBuf>,
+    pub icon: PathBuf,
+    /// Further frames of an animated icon
+    #[serde(default)]
+    pub icon_frames: Vec<PathBuf>,
+    /// Defaults to the closest to an animated PNG's frame delays, or `slow`
+    #[serde(default)]
+    pub icon_speed: Option<IconSpeed>,
+    #[serde(default)]
+    pub banner_format: ImageFormat,
+    #[serde(default)]
+    pub icon_format: ImageFormat,
+    pub gamecode: String,
+    /// In hex
+    #[serde(default)]
+    pub xor_key: Option<XorKey>,
+    /// `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, or seconds since 2000-01-01. `pack` stamps GCIs
+    /// without one with 2000-01-01, so callers choose the default.
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub timestamp: Option<u32>,
+    #[serde(default)]
+    pub permissions: GciPermissions,
+}
+
+impl GciPackConfig {
+    /// Make the config's paths relative to the folder its manifest is in
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        resolve(&mut self.input);
+        self.output.iter_mut().for_each(resolve);
+        self.banner.iter_mut().for_each(resolve);
+        resolve(&mut self.icon);
+        self.icon_frames.iter_mut().for_each(resolve);
+    }
+
+    pub fn output_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.input.with_extension("gci"))
+    }
+}
+
+/// Deserialize a time as `memcard::parse_timestamp` reads it, or as seconds since 2000-01-01
+pub fn deserialize_timestamp<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Seconds(u32),
+        Text(String),
+    }
+    match Option::<Timestamp>::deserialize(d)? {
+        Some(Timestamp::Seconds(seconds)) => Ok(Some(seconds)),
+        Some(Timestamp::Text(s)) => memcard::parse_timestamp(&s)
+            .map(Some)
+            .map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Seconds from the Unix epoch to 2000-01-01, where the card's clock starts
+const UNIX_TIME_2000: u64 = 946684800;
+
+/// A Unix time as the card's seconds since 2000-01-01, clamped to the range the card can store
+pub fn card_time(unix_time: u64) -> u32 {
+    u32::try_from(unix_time.saturating_sub(UNIX_TIME_2000)).unwrap_or(u32::MAX)
+}
+
+/// The strings and header fields of a GCI, apart from its payload and images
+#[derive(Debug, Clone)]
+pub struct GciPackOptions<'a> {
+    pub file_name: &'a str,
+    pub title: &'a str,
+    pub description: &'a str,
+    pub encoding: TextEncoding,
+    pub gamecode: &'a str,
+    /// Last modified time, in seconds since 2000-01-01
+    pub timestamp: u32,
+    pub permissions: GciPermissions,
 }
 
+/// Pack `file` into a GCI with a banner, if any, and an icon of one or more frames
 pub fn gcipack(
     file: &[u8],
-    file_name: &str,
-    title: &str,
-    description: &str,
-    banner: &[u8],
-    icon: &[u8],
-    gamecode: &str,
+    banner: Option<GciImage>,
+    icons: &[IconFrame],
+    options: &GciPackOptions,
 ) -> Result<Vec<u8>, GciPackError> {
-    let unpadded_gci_file_size = size_of::<GciFileMetadata>() + file.len();
+    if let Some(banner) = &banner {
+        banner.validate(ImageKind::Banner, "", 96, 32)?;
+    }
+    if !(1..=MAX_ICON_FRAMES).contains(&icons.len()) {
+        return Err(GciPackError::IconFrameCount(icons.len()));
+    }
+    // Each frame has two bits of format and of speed
+    let mut icon_format = 0;
+    let mut icon_speed = 0;
+    let mut shared_palette = None;
+    for (i, frame) in icons.iter().enumerate() {
+        let name = if icons.len() > 1 {
+            format!("frame {i} ")
+        } else {
+            String::new()
+        };
+        frame.image.validate(ImageKind::Icon, &name, 32, 32)?;
+        let format = match frame.image {
+            GciImage::Rgb5a3(_) => ICON_FORMAT_RGB5A3,
+            GciImage::Ci8 { .. } => ICON_FORMAT_CI8,
+            GciImage::Ci8Shared { palette, .. } => {
+                if *shared_palette.get_or_insert(palette) != palette {
+                    return Err(GciPackError::IconPaletteMismatch);
+                }
+                ICON_FORMAT_CI8_SHARED
+            }
+        };
+        icon_format |= format << (i * 2);
+        icon_speed |= (frame.speed as u16) << (i * 2);
+    }
+
+    // The banner if there is one, each icon frame, and then the icon's shared palette
+    let mut images = Vec::new();
+    match banner {
+        Some(GciImage::Ci8Shared { indices, palette }) => {
+            GciImage::Ci8 { indices, palette }.write(&mut images)
+        }
+        Some(banner) => banner.write(&mut images),
+        None => {}
+    }
+    for frame in icons {
+        frame.image.write(&mut images);
+    }
+    if let Some(palette) = shared_palette {
+        images.extend_from_slice(palette);
+    }
+    let images_size = images.len();
+
+    let unpadded_gci_file_size = images_size + size_of::<GciFileHeader>() + file.len();
     let blocks = unpadded_gci_file_size.div_ceil(BLOCK_SIZE);
     let gci_file_size = blocks * BLOCK_SIZE;
 
@@ -114,53 +492,362 @@ pub fn gcipack(
 
     // Build header
     let header = GciHeader {
-        gamecode: str_to_array(gamecode, StringKind::GameCode)?,
+        gamecode: str_to_array(options.gamecode, StringKind::GameCode)?,
         unused0: 0xff,
-        banner_fmt: 2,
-        filename: str_to_padded_array(file_name, StringKind::FileName)?,
-        last_modified: get_modified_time_sec().into(),
+        banner_fmt: match banner {
+            None => BANNER_FORMAT_NONE,
+            Some(GciImage::Rgb5a3(_)) => BANNER_FORMAT_RGB5A3,
+            Some(_) => BANNER_FORMAT_CI8,
+        },
+        filename: str_to_padded_array(options.file_name, StringKind::FileName)?,
+        last_modified: options.timestamp.into(),
         image_offset: 0.into(),
-        icon_format: 2.into(),
-        icon_speed: 3.into(),
-        permissions: 4,
-        copy_times: 0,
+        icon_format: icon_format.into(),
+        icon_speed: icon_speed.into(),
+        permissions: options.permissions.bits(),
+        copy_times: options.permissions.copy_times,
         first_block_num: 0.into(),
         block_count: (blocks as u16).into(),
         unused1: 0xff.into(),
-        comment_offset: ((BANNER_SIZE + ICON_SIZE) as u32).into(),
+        comment_offset: (images_size as u32).into(),
     };
 
-    // Build file metadata
-    let banner = banner
-        .try_into()
-        .map_err(|_| GciPackError::ImageInvalidSize {
-            kind: ImageKind::Banner,
-            info: format!("should be {} (96x32 RGB5A3)", BANNER_SIZE),
-        })?;
-    let icon = icon
-        .try_into()
-        .map_err(|_| GciPackError::ImageInvalidSize {
-            kind: ImageKind::Icon,
-            info: format!("should be {} (32x32 RGB5A3)", ICON_SIZE),
-        })?;
-    let metadata = GciFileMetadata {
-        banner,
-        icon,
-        title: str_to_padded_array(title, StringKind::Title)?,
-        description: str_to_padded_array(description, StringKind::Description)?,
+    // Build file header
+    let file_header = GciFileHeader {
+        title: encoded_padded_array(options.title, options.encoding, StringKind::Title)?,
+        description: encoded_padded_array(
+            options.description,
+            options.encoding,
+            StringKind::Description,
+        )?,
         file_size: (file.len() as u32).into(),
         padding: [0; FILE_HEADER_PADDING_SIZE],
     };
 
     // Combine everything
     gci.extend_from_slice(header.as_bytes());
-    gci.extend_from_slice(metadata.as_bytes());
+    gci.extend_from_slice(&images);
+    gci.extend_from_slice(file_header.as_bytes());
     gci.extend_from_slice(file);
     gci.extend_from_slice(&vec![0; gci_file_size - unpadded_gci_file_size]);
 
     Ok(gci)
 }
 
+/// A banner or icon frame as read: RGBA8 pixels from a PNG, or raw RGB5A3 data
+#[cfg(feature = "image")]
+enum Image {
+    Rgba(Vec<u8>),
+    Rgb5a3(Vec<u8>),
+}
+
+#[cfg(feature = "image")]
+impl Image {
+    fn to_rgb5a3(&self, width: usize, height: usize) -> Vec<u8> {
+        match self {
+            Image::Rgba(rgba) => gcimage::encode_rgb5a3(rgba, width, height),
+            Image::Rgb5a3(data) => data.clone(),
+        }
+    }
+
+    fn to_rgba(&self, kind: ImageKind, width: usize, height: usize) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Image::Rgba(rgba) => Ok(rgba.clone()),
+            Image::Rgb5a3(data) if data.len() == width * height * 2 => {
+                Ok(gcimage::decode_rgb5a3(data, width, height))
+            }
+            Image::Rgb5a3(_) => Err(GciPackError::ImageInvalidSize {
+                kind,
+                info: format!("should be {} ({width}x{height} RGB5A3)", width * height * 2),
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))
+}
+
+#[cfg(feature = "image")]
+fn check_size(
+    path: &Path,
+    kind: ImageKind,
+    (w, h): (usize, usize),
+    (width, height): (usize, usize),
+) -> anyhow::Result<()> {
+    if (w, h) != (width, height) {
+        return Err(GciPackError::ImageInvalidSize {
+            kind,
+            info: format!(
+                "{} must be {width}x{height}, got {w}x{h}",
+                path.to_string_lossy()
+            ),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Read a banner: a PNG, or raw RGB5A3 data
+#[cfg(feature = "image")]
+fn read_banner(path: &Path) -> anyhow::Result<Image> {
+    let buf = read_file(path)?;
+    if !buf.starts_with(b"\x89PNG") {
+        return Ok(Image::Rgb5a3(buf));
+    }
+    let (rgba, w, h) = gcimage::read_png(&buf)
+        .with_context(|| format!("cannot decode {}", path.to_string_lossy()))?;
+    check_size(path, ImageKind::Banner, (w, h), (96, 32))?;
+    Ok(Image::Rgba(rgba))
+}
+
+/// Read the frames of an icon. Animated PNGs give each frame's speed.
+#[cfg(feature = "image")]
+fn read_icon(path: &Path) -> anyhow::Result<Vec<(Image, Option<IconSpeed>)>> {
+    let buf = read_file(path)?;
+    if !buf.starts_with(b"\x89PNG") {
+        return Ok(vec![(Image::Rgb5a3(buf), None)]);
+    }
+    let (frames, w, h) = gcimage::read_apng(&buf)
+        .with_context(|| format!("cannot decode {}", path.to_string_lossy()))?;
+    check_size(path, ImageKind::Icon, (w, h), (32, 32))?;
+    let animated = frames.len() > 1;
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let speed = animated.then(|| IconSpeed::from_delay(frame.delay));
+            (Image::Rgba(frame.rgba), speed)
+        })
+        .collect())
+}
+
+/// A CI8 palette, padded to the 256 colors GCIs store
+#[cfg(feature = "image")]
+fn ci8_palette(tlut: Tlut) -> Vec<u8> {
+    let mut palette = tlut.data;
+    palette.resize(CI8_PALETTE_SIZE, 0);
+    palette
+}
+
+/// Read the payload, bundling the files in it if it's a directory. Returns the payload and the
+/// files read.
+#[cfg(feature = "image")]
+fn read_payload(path: &Path) -> anyhow::Result<(Vec<u8>, Vec<PathBuf>)> {
+    if !path.is_dir() {
+        return Ok((read_file(path)?, vec![path.to_path_buf()]));
+    }
+    let mut files = Vec::new();
+    let mut paths = Vec::new();
+    for (name, path) in bundle::dir_files(path)? {
+        files.push((name, read_file(&path)?));
+        paths.push(path);
+    }
+    Ok((bundle::pack(&files)?, paths))
+}
+
+/// A GCI packed from a `GciPackConfig`
+#[cfg(feature = "image")]
+pub struct PackedGci {
+    pub gci: Vec<u8>,
+    /// Every file read to pack it, for dependency files
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Pack a GCI as a config describes: reading the payload, bundling it if it's a directory, and
+/// reading the banner and icon from PNGs, animated PNGs, or raw RGB5A3 data, quantizing them if
+/// they're stored as CI8
+#[cfg(feature = "image")]
+pub fn pack(config: &GciPackConfig) -> anyhow::Result<PackedGci> {
+    let (input, mut inputs) = read_payload(&config.input)?;
+    let (banner, banner_palette) = match (&config.banner, config.banner_format) {
+        (None, _) => (Vec::new(), Vec::new()),
+        (Some(path), ImageFormat::Rgb5a3) => (read_banner(path)?.to_rgb5a3(96, 32), Vec::new()),
+        (Some(path), ImageFormat::Ci8) => {
+            let rgba = read_banner(path)?.to_rgba(ImageKind::Banner, 96, 32)?;
+            let (indices, tlut) =
+                TextureFormat::Ci8.encode_indexed(&rgba, 96, 32, PaletteFormat::Rgb5a3)?;
+            (indices, ci8_palette(tlut))
+        }
+    };
+    let banner = config.banner.is_some().then(|| match config.banner_format {
+        ImageFormat::Rgb5a3 => GciImage::Rgb5a3(&banner),
+        ImageFormat::Ci8 => GciImage::Ci8 {
+            indices: &banner,
+            palette: &banner_palette,
+        },
+    });
+
+    let mut icon = read_icon(&config.icon)?;
+    for path in &config.icon_frames {
+        icon.extend(read_icon(path)?);
+    }
+    // CI8 frames are quantized together so they can share a palette
+    let (icon_data, icon_palette) = match config.icon_format {
+        ImageFormat::Rgb5a3 => (
+            icon.iter()
+                .map(|(image, _)| image.to_rgb5a3(32, 32))
+                .collect(),
+            Vec::new(),
+        ),
+        ImageFormat::Ci8 => {
+            let rgba = icon
+                .iter()
+                .map(|(image, _)| image.to_rgba(ImageKind::Icon, 32, 32))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let rgba: Vec<&[u8]> = rgba.iter().map(Vec::as_slice).collect();
+            let (indices, tlut) =
+                TextureFormat::Ci8.encode_indexed_shared(&rgba, 32, 32, PaletteFormat::Rgb5a3)?;
+            (indices, ci8_palette(tlut))
+        }
+    };
+    let icon: Vec<IconFrame> = icon
+        .iter()
+        .zip(&icon_data)
+        .map(|((_, speed), data)| IconFrame {
+            image: match config.icon_format {
+                ImageFormat::Rgb5a3 => GciImage::Rgb5a3(data),
+                ImageFormat::Ci8 => GciImage::Ci8Shared {
+                    indices: data,
+                    palette: &icon_palette,
+                },
+            },
+            speed: config.icon_speed.or(*speed).unwrap_or(IconSpeed::Slow),
+        })
+        .collect();
+
+    let mut gci = gcipack(
+        &input,
+        banner,
+        &icon,
+        &GciPackOptions {
+            file_name: &config.file_name,
+            title: &config.title,
+            description: &config.description,
+            encoding: config.encoding,
+            gamecode: &config.gamecode,
+            timestamp: config.timestamp.unwrap_or(0),
+            permissions: config.permissions.clone(),
+        },
+    )?;
+    if let Some(key) = &config.xor_key {
+        xor_payload(&mut gci, &key.0)?;
+    }
+
+    inputs.extend(config.banner.clone());
+    inputs.push(config.icon.clone());
+    inputs.extend(config.icon_frames.iter().cloned());
+    Ok(PackedGci { gci, inputs })
+}
+
+/// Offset of gcipack's file header in a GCI, which is where the comment is
+fn file_header_offset(gci: &[u8]) -> anyhow::Result<usize> {
+    let (header, _) = GciHeader::ref_from_prefix(gci)
+        .map_err(|_| anyhow!("GCI is too small to hold its directory entry"))?;
+    Ok(size_of::<GciHeader>() + header.comment_offset.get() as usize)
+}
+
+/// The payload stored in a GCI, as packed by `gcipack`
+pub fn gciunpack(gci: &[u8]) -> anyhow::Result<&[u8]> {
+    let file_header_start = file_header_offset(gci)?;
+    let payload_start = file_header_start + size_of::<GciFileHeader>();
+    let (file_header, _) =
+        GciFileHeader::ref_from_prefix(gci.get(file_header_start..).unwrap_or(&[]))
+            .map_err(|_| anyhow!("GCI is too small to hold gcipack's file header"))?;
+    let file_size = file_header.file_size.get() as usize;
+    gci.get(payload_start..payload_start + file_size)
+        .ok_or_else(|| {
+            anyhow!("GCI payload of {file_size:#x} bytes extends past the end of the file")
+        })
+}
+
+fn file_header_mut(gci: &mut [u8]) -> anyhow::Result<&mut GciFileHeader> {
+    let start = file_header_offset(gci)?;
+    GciFileHeader::mut_from_prefix(gci.get_mut(start..).unwrap_or(&mut []))
+        .map(|(file_header, _)| file_header)
+        .map_err(|_| anyhow!("GCI is too small to hold gcipack's file header"))
+}
+
+/// XOR `data` with a repeating key. Applying the same key again undoes it.
+pub fn xor(data: &mut [u8], key: &[u8]) {
+    for (b, k) in data.iter_mut().zip(key.iter().cycle()) {
+        *b ^= k;
+    }
+}
+
+/// Obfuscate a packed GCI's payload by XORing it with a repeating key, as some exploit loaders
+/// expect, and mark the file header so gciunpack knows to undo it. The key itself isn't stored.
+pub fn xor_payload(gci: &mut [u8], key: &[u8]) -> anyhow::Result<()> {
+    ensure!(
+        (1..=MAX_XOR_KEY_SIZE).contains(&key.len()),
+        "XOR keys must be 1 to {MAX_XOR_KEY_SIZE} bytes, got {}",
+        key.len()
+    );
+    let file_header = file_header_mut(gci)?;
+    ensure!(
+        !file_header.padding.starts_with(&XOR_MARKER),
+        "GCI payload is already XORed"
+    );
+    file_header.padding[..XOR_MARKER.len()].copy_from_slice(&XOR_MARKER);
+    file_header.padding[XOR_MARKER.len()] = key.len() as u8;
+    let size = file_header.file_size.get() as usize;
+    let start = file_header_offset(gci)? + size_of::<GciFileHeader>();
+    let payload = gci
+        .get_mut(start..start + size)
+        .ok_or_else(|| anyhow!("GCI payload extends past the end of the file"))?;
+    xor(payload, key);
+    Ok(())
+}
+
+/// Key a GCI's payload is XORed with, as given on the command line
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct XorKey(pub Vec<u8>);
+
+impl TryFrom<String> for XorKey {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<XorKey> {
+        XorKey::parse(&s)
+    }
+}
+
+impl XorKey {
+    /// Parse a key given in hex, such as `5A` or `DEADBEEF`
+    pub fn parse(s: &str) -> anyhow::Result<XorKey> {
+        let hex = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        ensure!(
+            hex.is_ascii() && !hex.is_empty() && hex.len().is_multiple_of(2),
+            "XOR key '{s}' should be an even number of hex digits"
+        );
+        let key = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| anyhow!("XOR key '{s}' isn't valid hex"))?;
+        ensure!(
+            key.len() <= MAX_XOR_KEY_SIZE,
+            "XOR keys must be at most {MAX_XOR_KEY_SIZE} bytes, got {}",
+            key.len()
+        );
+        Ok(XorKey(key))
+    }
+}
+
+/// Length of the key a GCI's payload was XORed with by `xor_payload`, if it was
+pub fn xor_key_size(gci: &[u8]) -> Option<usize> {
+    let (file_header, _) =
+        GciFileHeader::ref_from_prefix(gci.get(file_header_offset(gci).ok()?..)?).ok()?;
+    file_header
+        .padding
+        .starts_with(&XOR_MARKER)
+        .then_some(file_header.padding[XOR_MARKER.len()] as usize)
+}
+
 fn str_to_array<const N: usize>(input: &str, kind: StringKind) -> Result<[u8; N], GciPackError> {
     if !input.is_ascii() {
         return Err(GciPackError::StringNonAscii(kind));
@@ -179,18 +866,101 @@ fn str_to_padded_array<const N: usize>(
     input: &str,
     kind: StringKind,
 ) -> Result<[u8; N], GciPackError> {
-    if !input.is_ascii() {
-        return Err(GciPackError::StringNonAscii(kind));
-    }
+    encoded_padded_array(input, TextEncoding::Ascii, kind)
+}
 
-    if input.len() > N {
+/// `input` in `encoding`, null-padded. Sizes are of the encoded bytes, so each Shift-JIS
+/// character other than ASCII and half-width katakana takes two.
+fn encoded_padded_array<const N: usize>(
+    input: &str,
+    encoding: TextEncoding,
+    kind: StringKind,
+) -> Result<[u8; N], GciPackError> {
+    let bytes = encoding.encode(input, kind)?;
+    if bytes.len() > N {
         return Err(GciPackError::StringInvalidSize {
             kind,
-            info: format!("max size is {}, got {}", N, input.len()),
+            info: format!("max size is {}, got {}", N, bytes.len()),
         });
     }
 
     let mut array = [0; N];
-    array[..input.len()].copy_from_slice(input.as_bytes());
+    array[..bytes.len()].copy_from_slice(&bytes);
     Ok(array)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_fill_in_the_header() {
+        let icon = vec![0; 32 * 32 * 2];
+        let gci = gcipack(
+            b"payload",
+            None,
+            &[IconFrame::still(&icon)],
+            &GciPackOptions {
+                file_name: "MODFILE",
+                title: "My mod",
+                description: "v1.0",
+                encoding: TextEncoding::Ascii,
+                gamecode: "GMSE01",
+                timestamp: card_time(UNIX_TIME_2000 + 1234),
+                permissions: GciPermissions {
+                    public: false,
+                    no_copy: true,
+                    no_move: false,
+                    copy_times: 3,
+                },
+            },
+        )
+        .unwrap();
+        let (header, _) = GciHeader::ref_from_prefix(&gci).unwrap();
+        assert_eq!(&header.gamecode, b"GMSE01");
+        assert_eq!(header.last_modified.get(), 1234);
+        assert_eq!(header.permissions, PERMISSION_NO_COPY);
+        assert_eq!(header.copy_times, 3);
+        assert_eq!(gciunpack(&gci).unwrap(), b"payload");
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn pack_bundles_a_directory() {
+        let dir = std::env::temp_dir().join(format!("gctools-gcipack-{}", std::process::id()));
+        let input = dir.join("mod");
+        std::fs::create_dir_all(&input).unwrap();
+        std::fs::write(input.join("a.bin"), b"aaaa").unwrap();
+        std::fs::write(dir.join("icon.bin"), vec![0xFF; 32 * 32 * 2]).unwrap();
+        let config = GciPackConfig {
+            input: input.clone(),
+            output: None,
+            file_name: "MODFILE".into(),
+            title: "My mod".into(),
+            description: "v1.0".into(),
+            encoding: TextEncoding::Ascii,
+            banner: None,
+            icon: dir.join("icon.bin"),
+            icon_frames: Vec::new(),
+            icon_speed: None,
+            banner_format: ImageFormat::Rgb5a3,
+            icon_format: ImageFormat::Ci8,
+            gamecode: "GMSE01".into(),
+            xor_key: None,
+            timestamp: Some(1234),
+            permissions: GciPermissions::default(),
+        };
+        let packed = pack(&config).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (header, _) = GciHeader::ref_from_prefix(&packed.gci).unwrap();
+        assert_eq!(header.last_modified.get(), 1234);
+        assert_eq!(header.icon_format.get(), ICON_FORMAT_CI8_SHARED);
+        let payload = gciunpack(&packed.gci).unwrap();
+        assert_eq!(
+            crate::formats::bundle::find(payload, b"a.bin"),
+            Some(&b"aaaa"[..])
+        );
+        assert_eq!(packed.inputs, [input.join("a.bin"), dir.join("icon.bin")]);
+    }
+}
@@ -0,0 +1,253 @@
+// Client for the GDB remote serial protocol, as served by Dolphin's GDB stub, for reading and
+// writing emulated memory and registers and calling functions in the game.
+
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+// Register numbers in Dolphin's stub. r0-r31 are 0-31 and f0-f31 are 32-63.
+pub const REG_PC: u32 = 64;
+pub const REG_MSR: u32 = 65;
+pub const REG_CR: u32 = 66;
+pub const REG_LR: u32 = 67;
+pub const REG_CTR: u32 = 68;
+pub const REG_XER: u32 = 69;
+
+/// Bytes written per memory write packet, keeping packets well under the stub's buffer size
+const WRITE_CHUNK_SIZE: usize = 0x800;
+/// How long to wait for a stop reply after interrupting, which doesn't come if the game is
+/// already stopped
+const INTERRUPT_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long a called function can run before it's assumed it won't return
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct GdbClient {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    ensure!(s.len().is_multiple_of(2), "Odd length hex data from stub");
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).context("Invalid hex data from stub"))
+        .collect()
+}
+
+/// Undo a packet's escaping and run-length encoding
+fn decode_packet(raw: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut bytes = raw.iter().copied();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => data.push(bytes.next().ok_or_else(|| anyhow!("Invalid packet"))? ^ 0x20),
+            b'*' => {
+                let count = bytes.next().ok_or_else(|| anyhow!("Invalid packet"))?;
+                let last = *data.last().ok_or_else(|| anyhow!("Invalid packet"))?;
+                data.extend(std::iter::repeat_n(last, count.wrapping_sub(29) as usize));
+            }
+            byte => data.push(byte),
+        }
+    }
+    Ok(data)
+}
+
+impl GdbClient {
+    /// Connect to a stub at `address`, such as `localhost:2345`
+    pub fn connect(address: &str) -> anyhow::Result<GdbClient> {
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("cannot connect to GDB stub at {address}"))?;
+        stream.set_nodelay(true)?;
+        Ok(GdbClient {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+        })
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn send_packet(&mut self, data: &str) -> anyhow::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            match self.read_byte()? {
+                b'+' => return Ok(()),
+                b'-' => continue,
+                byte => bail!("Unexpected acknowledgement {:?} from stub", byte as char),
+            }
+        }
+    }
+
+    fn read_packet(&mut self) -> anyhow::Result<String> {
+        loop {
+            while self.read_byte()? != b'$' {}
+            let mut raw = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => raw.push(byte),
+                }
+            }
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum)?;
+            let expected = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+            if expected != Some(checksum(&raw)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return String::from_utf8(decode_packet(&raw)?).context("Invalid packet from stub");
+        }
+    }
+
+    /// Send a command and return its reply, failing on an error reply
+    fn command(&mut self, data: &str) -> anyhow::Result<String> {
+        self.send_packet(data)?;
+        let reply = self.read_packet()?;
+        if reply.len() == 3 && reply.starts_with('E') {
+            bail!("Stub returned error {} for {}", &reply[1..], &data[..1]);
+        }
+        Ok(reply)
+    }
+
+    fn command_ok(&mut self, data: &str) -> anyhow::Result<()> {
+        match self.command(data)?.as_str() {
+            "OK" => Ok(()),
+            "" => bail!("Stub doesn't support {}", &data[..1]),
+            reply => bail!("Unexpected reply {reply:?} from stub"),
+        }
+    }
+
+    /// Stop the game if it's running
+    pub fn interrupt(&mut self) -> anyhow::Result<()> {
+        self.stream.write_all(&[0x03])?;
+        self.stream.set_read_timeout(Some(INTERRUPT_TIMEOUT))?;
+        let reply = self.read_packet();
+        self.stream.set_read_timeout(None)?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.downcast_ref::<io::Error>().is_some_and(|e| {
+                    matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    )
+                }) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_memory(&mut self, address: u32, size: u32) -> anyhow::Result<Vec<u8>> {
+        let reply = self.command(&format!("m{address:x},{size:x}"))?;
+        let data = parse_hex(&reply)?;
+        ensure!(
+            data.len() == size as usize,
+            "Stub returned {:#x} bytes at {address:08X}, expected {size:#x}",
+            data.len()
+        );
+        Ok(data)
+    }
+
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) -> anyhow::Result<()> {
+        for (i, chunk) in data.chunks(WRITE_CHUNK_SIZE).enumerate() {
+            let address = address + (i * WRITE_CHUNK_SIZE) as u32;
+            self.command_ok(&format!("M{address:x},{:x}:{}", chunk.len(), hex(chunk)))
+                .with_context(|| format!("cannot write memory at {address:08X}"))?;
+        }
+        Ok(())
+    }
+
+    /// Read a register, truncated to 32 bits for floating point registers
+    pub fn read_register(&mut self, register: u32) -> anyhow::Result<u32> {
+        let reply = self.command(&format!("p{register:x}"))?;
+        let bytes = parse_hex(&reply)?;
+        ensure!(bytes.len() >= 4, "Invalid value for register {register}");
+        Ok(u32::from_be_bytes(bytes[..4].try_into().unwrap()))
+    }
+
+    pub fn write_register(&mut self, register: u32, value: u32) -> anyhow::Result<()> {
+        self.command_ok(&format!("P{register:x}={value:08x}"))
+    }
+
+    /// All registers, in the stub's own format
+    pub fn read_registers(&mut self) -> anyhow::Result<String> {
+        self.command("g")
+    }
+
+    pub fn write_registers(&mut self, registers: &str) -> anyhow::Result<()> {
+        self.command_ok(&format!("G{registers}"))
+    }
+
+    pub fn insert_breakpoint(&mut self, address: u32) -> anyhow::Result<()> {
+        self.command_ok(&format!("Z0,{address:x},4"))
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u32) -> anyhow::Result<()> {
+        self.command_ok(&format!("z0,{address:x},4"))
+    }
+
+    /// Resume the game without waiting for it to stop
+    pub fn resume(&mut self) -> anyhow::Result<()> {
+        self.send_packet("c")
+    }
+
+    /// Call the function at `address` with `args` in r3 onwards, returning r3. The function
+    /// returns to where the game is stopped, where a breakpoint catches it, and every register
+    /// is then restored, so the game is left as it was.
+    pub fn call(&mut self, address: u32, args: &[u32]) -> anyhow::Result<u32> {
+        ensure!(
+            args.len() <= 8,
+            "Functions take at most 8 arguments in registers"
+        );
+        let registers = self.read_registers()?;
+        let mut special = Vec::new();
+        for register in [REG_PC, REG_MSR, REG_CR, REG_LR, REG_CTR, REG_XER] {
+            special.push((register, self.read_register(register)?));
+        }
+        let pc = special[0].1;
+
+        for (i, &arg) in args.iter().enumerate() {
+            self.write_register(3 + i as u32, arg)?;
+        }
+        self.write_register(REG_LR, pc)?;
+        self.write_register(REG_PC, address)?;
+        self.insert_breakpoint(pc)?;
+        self.resume()?;
+        self.stream.set_read_timeout(Some(CALL_TIMEOUT))?;
+        let stop = self.read_packet();
+        self.stream.set_read_timeout(None)?;
+        stop.with_context(|| format!("function at {address:08X} didn't return"))?;
+        self.remove_breakpoint(pc)?;
+        let stopped_at = self.read_register(REG_PC)?;
+        ensure!(
+            stopped_at == pc,
+            "Game stopped at {stopped_at:08X} before the function at {address:08X} returned"
+        );
+        let result = self.read_register(3)?;
+
+        self.write_registers(&registers)?;
+        for (register, value) in special {
+            self.write_register(register, value)?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,637 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::dol::{Dol, DolSectionKind};
+use crate::patch::{self, Patch, PatchValue};
+use crate::ppc;
+use crate::symbol_map::SymbolMap;
+
+/// First line of every GCT file
+pub const GCT_HEADER: [u32; 2] = [0x00D0C0DE, 0x00D0C0DE];
+/// Last line of every GCT file, which stops the codehandler
+pub const GCT_TERMINATOR: [u32; 2] = [0xF0000000, 0x00000000];
+
+/// Address codehandlers are built to run from
+pub const CODEHANDLER_ADDRESS: u32 = 0x80001800;
+/// End of the free low memory that holds the codehandler and its codes
+pub const CODEHANDLER_END: u32 = 0x80003000;
+/// Offset of the codehandler's entry point, which runs every code and returns
+const CODEHANDLER_ENTRY_OFFSET: u32 = 0xA8;
+/// Stack frame size of the codehandler hook, which saves volatile registers
+const HOOK_FRAME_SIZE: u32 = 0x100;
+
+/// One line of a Gecko code, written as two 8 digit hex words
+pub type CodeLine = [u32; 2];
+
+/// A named Gecko code
+#[derive(Debug, Clone, Default)]
+pub struct GeckoCode {
+    pub name: String,
+    /// Author, from a `[Creator]` suffix on the name line
+    pub creator: Option<String>,
+    /// Notes, from `*` lines
+    pub notes: Vec<String>,
+    pub lines: Vec<CodeLine>,
+}
+
+/// Codetype of an instruction's first line: its first byte, without the low bit that extends
+/// the address.
+pub fn codetype(line: CodeLine) -> u8 {
+    (line[0] >> 24) as u8 & 0xFE
+}
+
+/// Split a codetype into the base codetype and whether it's relative to the pointer rather than
+/// the base address
+pub fn split_codetype(codetype: u8) -> (u8, bool) {
+    let has_pointer_variant = codetype < 0xE0 && !(0x60..0x80).contains(&codetype);
+    if has_pointer_variant && codetype & 0x10 != 0 {
+        (codetype & !0x10, true)
+    } else {
+        (codetype, false)
+    }
+}
+
+/// Name of a (base) codetype, None if it isn't one the codehandler knows
+pub fn codetype_name(codetype: u8) -> Option<&'static str> {
+    let name = match codetype {
+        0x00 => "8-bit write",
+        0x02 => "16-bit write",
+        0x04 => "32-bit write",
+        0x06 => "string write",
+        0x08 => "serial write",
+        0x20 => "if 32-bit equal",
+        0x22 => "if 32-bit not equal",
+        0x24 => "if 32-bit greater than",
+        0x26 => "if 32-bit less than",
+        0x28 => "if 16-bit masked equal",
+        0x2A => "if 16-bit masked not equal",
+        0x2C => "if 16-bit masked greater than",
+        0x2E => "if 16-bit masked less than",
+        0x40 => "load base address",
+        0x42 => "set base address",
+        0x44 => "store base address",
+        0x46 => "set base address to code address",
+        0x48 => "load pointer",
+        0x4A => "set pointer",
+        0x4C => "store pointer",
+        0x4E => "set pointer to code address",
+        0x60 => "set repeat",
+        0x62 => "execute repeat",
+        0x64 => "return",
+        0x66 => "goto",
+        0x68 => "gosub",
+        0x80 => "set gecko register",
+        0x82 => "load gecko register",
+        0x84 => "store gecko register",
+        0x86 => "gecko register operation with value",
+        0x88 => "gecko register operation",
+        0x8A => "memory copy from gecko register",
+        0x8C => "memory copy to gecko register",
+        0xA0 => "if gecko register 16-bit equal",
+        0xA2 => "if gecko register 16-bit not equal",
+        0xA4 => "if gecko register 16-bit greater than",
+        0xA6 => "if gecko register 16-bit less than",
+        0xA8 => "if counter equal",
+        0xAA => "if counter not equal",
+        0xAC => "if counter greater than",
+        0xAE => "if counter less than",
+        0xC0 => "execute ASM",
+        0xC2 => "insert ASM",
+        0xC6 => "insert branch",
+        0xCC => "on/off switch",
+        0xCE => "if address in range",
+        0xE0 => "full terminator",
+        0xE2 => "endif",
+        0xF0 => "end of code list",
+        0xF2 => "insert ASM with checksum",
+        0xF4 => "insert ASM with checksum",
+        0xF6 => "search",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Number of data lines following an instruction's first line
+fn payload_lines(line: CodeLine) -> anyhow::Result<usize> {
+    let (codetype, _) = split_codetype(codetype(line));
+    ensure!(
+        codetype_name(codetype).is_some(),
+        "Unknown codetype {codetype:02X}"
+    );
+    Ok(match codetype {
+        0x06 => line[1].div_ceil(8) as usize,
+        0x08 => 1,
+        0xC0 | 0xC2 => line[1] as usize,
+        0xF2 | 0xF4 => (line[1] & 0xFF) as usize,
+        0xF6 => (line[0] & 0xFF) as usize,
+        0xF0 => bail!("End of code list marker inside a code"),
+        _ => 0,
+    })
+}
+
+/// Split code lines into instructions, each its first line followed by its data lines
+pub fn instructions(lines: &[CodeLine]) -> anyhow::Result<Vec<&[CodeLine]>> {
+    let mut instructions = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let len = 1 + payload_lines(lines[i]).with_context(|| format!("Invalid line {}", i + 1))?;
+        let instruction = lines.get(i..i + len).ok_or_else(|| {
+            anyhow!(
+                "Codetype {:02X} on line {} needs {} data lines, but the code ends first",
+                codetype(lines[i]),
+                i + 1,
+                len - 1
+            )
+        })?;
+        instructions.push(instruction);
+        i += len;
+    }
+    Ok(instructions)
+}
+
+fn parse_word(s: &str) -> anyhow::Result<u32> {
+    ensure!(s.len() == 8, "Expected 8 hex digits, got '{s}'");
+    ensure!(
+        !s.contains(['x', 'X']),
+        "'{s}' has placeholder digits, which must be filled in first"
+    );
+    u32::from_str_radix(s, 16).map_err(|_| anyhow!("Invalid hex word '{s}'"))
+}
+
+/// Parse codes in text form without checking their codetypes. Each code starts with a `$Name`
+/// line, optionally followed by `[Creator]`, then lines of two 8 digit hex words. Lines starting
+/// with `*` are notes. Decrypted Action Replay codes are written the same way.
+pub fn parse_code_text(buf: &[u8]) -> anyhow::Result<Vec<GeckoCode>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse code list as UTF-8")?;
+    let mut codes: Vec<GeckoCode> = Vec::new();
+
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('$') {
+            let header = header.trim();
+            let (name, creator) = match header.strip_suffix(']').and_then(|h| h.rsplit_once('[')) {
+                Some((name, creator)) => (name.trim(), Some(creator.trim().to_string())),
+                None => (header, None),
+            };
+            codes.push(GeckoCode {
+                name: name.to_string(),
+                creator,
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let code = codes
+            .last_mut()
+            .ok_or_else(|| anyhow!("Code line before any $name header on line {}", line_num + 1))?;
+        if let Some(note) = line.strip_prefix('*') {
+            code.notes.push(note.trim().to_string());
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [left, right] = fields[..] else {
+            bail!("Invalid code line {}: {}", line_num + 1, line);
+        };
+        let context = || format!("Invalid code line {}", line_num + 1);
+        code.lines.push([
+            parse_word(left).with_context(context)?,
+            parse_word(right).with_context(context)?,
+        ]);
+    }
+
+    for code in &codes {
+        ensure!(!code.lines.is_empty(), "Code '{}' has no lines", code.name);
+    }
+    Ok(codes)
+}
+
+/// Parse Gecko codes in text form, checking that every line is part of a valid instruction
+pub fn parse_codes(buf: &[u8]) -> anyhow::Result<Vec<GeckoCode>> {
+    let codes = parse_code_text(buf)?;
+    for code in &codes {
+        instructions(&code.lines).with_context(|| format!("Invalid code '{}'", code.name))?;
+    }
+    Ok(codes)
+}
+
+/// Write codes in the text form read by [`parse_codes`]
+pub fn write_codes(codes: &[GeckoCode]) -> String {
+    let mut out = String::new();
+    for code in codes {
+        match &code.creator {
+            Some(creator) => writeln!(out, "${} [{creator}]", code.name).unwrap(),
+            None => writeln!(out, "${}", code.name).unwrap(),
+        }
+        for note in &code.notes {
+            writeln!(out, "*{note}").unwrap();
+        }
+        for line in &code.lines {
+            writeln!(out, "{:08X} {:08X}", line[0], line[1]).unwrap();
+        }
+    }
+    out
+}
+
+/// Write codes as a GCT file, as loaded by Gecko OS, Nintendont, and Swiss
+pub fn write_gct(codes: &[GeckoCode]) -> Vec<u8> {
+    let lines = codes.iter().flat_map(|code| &code.lines);
+    std::iter::once(&GCT_HEADER)
+        .chain(lines)
+        .chain(std::iter::once(&GCT_TERMINATOR))
+        .flat_map(|line| line.iter().flat_map(|word| word.to_be_bytes()))
+        .collect()
+}
+
+/// Offset of an address from the default base address, as stored in a code's first word
+pub(crate) fn base_offset(address: u32) -> anyhow::Result<u32> {
+    ensure!(
+        (0x80000000..0x82000000).contains(&address),
+        "Address {address:#010x} can't be targeted by a Gecko code"
+    );
+    Ok(address & 0x01FFFFFF)
+}
+
+/// Gecko lines for one line of a patch spec: `branch <destination>`, `insert <instruction
+/// words...>`, or any write [`patch::parse_patch_list`] reads
+fn generate_lines(
+    address: u32,
+    kind: &str,
+    args: &[&str],
+    symbols: &SymbolMap,
+) -> anyhow::Result<Vec<CodeLine>> {
+    let offset = base_offset(address)?;
+    let destination = || -> anyhow::Result<u32> {
+        let [destination] = args else {
+            bail!("Expected one destination");
+        };
+        symbols.resolve(destination)
+    };
+    let lines = match kind {
+        "branch" => {
+            patch::ensure_aligned(address, 4)?;
+            vec![[0xC6000000 | offset, destination()?]]
+        }
+        "insert" => {
+            patch::ensure_aligned(address, 4)?;
+            ensure!(!args.is_empty(), "Expected instruction words to insert");
+            let mut words = args
+                .iter()
+                .map(|w| parse_word(w.trim_start_matches("0x")))
+                .collect::<anyhow::Result<Vec<u32>>>()?;
+            // The last word is replaced by a branch back to the game
+            if words.len().is_multiple_of(2) {
+                words.push(0x60000000);
+            }
+            words.push(0);
+            let mut lines = vec![[0xC2000000 | offset, words.len() as u32 / 2]];
+            lines.extend(words.chunks_exact(2).map(|w| [w[0], w[1]]));
+            lines
+        }
+        _ => {
+            let value = patch::parse_write(address, kind, args, symbols)?;
+            patch::ensure_aligned(address, value.size())?;
+            vec![write_line(offset, value)]
+        }
+    };
+    Ok(lines)
+}
+
+/// The 00, 02, or 04 line writing `value` at `offset` from the base address
+fn write_line(offset: u32, value: PatchValue) -> CodeLine {
+    match value {
+        PatchValue::U8(v) => [offset, v as u32],
+        PatchValue::U16(v) => [0x02000000 | offset, v as u32],
+        PatchValue::U32(v) => [0x04000000 | offset, v],
+    }
+}
+
+/// Convert patches to Gecko codes made of 00, 02, and 04 writes, one code per patch
+pub fn codes_from_patches(patches: &[Patch]) -> anyhow::Result<Vec<GeckoCode>> {
+    let mut codes = Vec::new();
+    for patch in patches {
+        let mut lines = Vec::new();
+        for write in &patch.writes {
+            let offset = base_offset(write.address)
+                .with_context(|| format!("Can't convert patch '{}'", patch.name))?;
+            lines.push(write_line(offset, write.value));
+        }
+        codes.push(GeckoCode {
+            name: patch.name.clone(),
+            lines,
+            ..Default::default()
+        });
+    }
+    Ok(codes)
+}
+
+/// Generate Gecko codes from a patch list whose targets may be symbols, as read by
+/// [`patch::parse_patch_list`]. Writes become 04 and friends, except that `<target> branch
+/// <destination>` uses C6, and a line can also be `<target> insert <instruction words...>` (C2).
+pub fn generate_codes(buf: &[u8], symbols: &SymbolMap) -> anyhow::Result<Vec<GeckoCode>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse patch list as UTF-8")?;
+    let mut codes: Vec<GeckoCode> = Vec::new();
+
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('$') {
+            codes.push(GeckoCode {
+                name: name.trim().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let code = codes
+            .last_mut()
+            .ok_or_else(|| anyhow!("Patch before any $name header on line {}", line_num + 1))?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [target, kind, ref args @ ..] = fields[..] else {
+            bail!("Invalid patch on line {}: {}", line_num + 1, line);
+        };
+        let address = symbols
+            .resolve(target)
+            .with_context(|| format!("Failed to resolve target on line {}", line_num + 1))?;
+        let lines = generate_lines(address, kind, args, symbols)
+            .with_context(|| format!("Invalid patch on line {}", line_num + 1))?;
+        code.lines.extend(lines);
+    }
+
+    for code in &codes {
+        ensure!(
+            !code.lines.is_empty(),
+            "Code '{}' has no patches",
+            code.name
+        );
+    }
+    Ok(codes)
+}
+
+/// Read the code lines from a GCT file, up to its terminator
+pub fn parse_gct(buf: &[u8]) -> anyhow::Result<Vec<CodeLine>> {
+    let lines: Vec<CodeLine> = buf
+        .chunks_exact(8)
+        .map(|c| {
+            [
+                u32::from_be_bytes(c[..4].try_into().unwrap()),
+                u32::from_be_bytes(c[4..].try_into().unwrap()),
+            ]
+        })
+        .collect();
+    ensure!(lines.first() == Some(&GCT_HEADER), "Not a GCT file");
+
+    let mut i = 1;
+    while i < lines.len() {
+        if codetype(lines[i]) == 0xF0 {
+            return Ok(lines[1..i].to_vec());
+        }
+        i += 1 + payload_lines(lines[i]).with_context(|| format!("Invalid GCT line {i}"))?;
+    }
+    bail!("GCT has no terminator")
+}
+
+/// What the base address and pointer hold while walking a code list, where they're known
+struct Registers {
+    ba: Option<u32>,
+    po: Option<u32>,
+}
+
+impl Registers {
+    /// Address targeted by an instruction, its low 25 bits added to the base address or pointer
+    fn target(&self, line: CodeLine, pointer: bool) -> String {
+        let offset = line[0] & 0x01FFFFFF;
+        // The if codetypes use the lowest bit as an endif flag
+        let offset = if (0x20..0x30).contains(&split_codetype(codetype(line)).0) {
+            offset & !1
+        } else {
+            offset
+        };
+        let (name, base) = if pointer {
+            ("po", self.po)
+        } else {
+            ("ba", self.ba)
+        };
+        match base {
+            Some(base) => format!("{:#010x}", base.wrapping_add(offset)),
+            None => format!("{name}+{offset:#x}"),
+        }
+    }
+
+    /// Update the registers after an instruction runs
+    fn update(&mut self, line: CodeLine) {
+        let (codetype, pointer) = split_codetype(codetype(line));
+        let set_value = (!pointer && line[0] & 0x01FFFFFF == 0).then_some(line[1]);
+        match codetype {
+            0x42 => self.ba = set_value,
+            0x4A => self.po = set_value,
+            0x40 | 0x46 => self.ba = None,
+            0x48 | 0x4E => self.po = None,
+            0xE0 | 0xE2 => {
+                if line[1] >> 16 != 0 {
+                    self.ba = Some(line[1] & 0xFFFF0000);
+                }
+                if line[1] & 0xFFFF != 0 {
+                    self.po = Some(line[1] << 16);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// One line description of an instruction
+fn describe(instruction: &[CodeLine], registers: &Registers) -> String {
+    let line = instruction[0];
+    let (codetype, pointer) = split_codetype(codetype(line));
+    let target = registers.target(line, pointer);
+    let comparison = ["==", "!=", ">", "<"][(codetype as usize >> 1) & 3];
+    let repeat = match line[1] >> 16 {
+        0 => String::new(),
+        count => format!(", {} times", count + 1),
+    };
+    match codetype {
+        0x00 => format!("8-bit write of {:#04x} to {target}{repeat}", line[1] & 0xFF),
+        0x02 => format!(
+            "16-bit write of {:#06x} to {target}{repeat}",
+            line[1] & 0xFFFF
+        ),
+        0x04 => format!("32-bit write of {:#010x} to {target}", line[1]),
+        0x06 => format!("write of {} bytes to {target}", line[1]),
+        0x08 => format!("serial write starting at {target}"),
+        0x20..=0x26 => format!("if 32-bit value at {target} {comparison} {:#010x}", line[1]),
+        0x28..=0x2E => format!(
+            "if 16-bit value at {target} with bits {:#06x} cleared {comparison} {:#06x}",
+            line[1] >> 16,
+            line[1] & 0xFFFF
+        ),
+        0xC0 => format!("execute ASM, {} lines", line[1]),
+        0xC2 => format!("insert ASM at {target}, {} lines", line[1]),
+        0xC6 => format!("branch from {target} to {:#010x}", line[1]),
+        0xE2 => {
+            let else_ = if line[0] & 0x00100000 != 0 {
+                "else, "
+            } else {
+                ""
+            };
+            format!("{else_}endif x{}", (line[0] & 0xFF).max(1))
+        }
+        _ => {
+            let name = codetype_name(codetype).unwrap_or("unknown");
+            if pointer {
+                format!("{name} (pointer relative)")
+            } else {
+                name.to_string()
+            }
+        }
+    }
+}
+
+/// Disassembly of the instruction words in an ASM codetype's data lines, as note lines. Branch
+/// targets are relative to the start of the block, since it runs from the code list.
+fn disassemble_payload(out: &mut String, instruction: &[CodeLine]) {
+    let words: Vec<u32> = instruction[1..].iter().flatten().copied().collect();
+    for (i, &word) in words.iter().enumerate() {
+        let offset = i as u32 * 4;
+        let is_last = i == words.len() - 1;
+        if is_last && word == 0 && codetype(instruction[0]) & 0xEE == 0xC2 {
+            writeln!(out, "*   +{offset:#06x}  (branch back)").unwrap();
+        } else {
+            writeln!(
+                out,
+                "*   +{offset:#06x}  {}",
+                ppc::disassemble(word, offset)
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Print code lines as a commented code list, describing each instruction and disassembling
+/// ASM codetypes. The notes leave the result valid input for [`parse_codes`].
+pub fn annotate(name: &str, lines: &[CodeLine]) -> anyhow::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "${name}").unwrap();
+    let mut registers = Registers {
+        ba: Some(0x80000000),
+        po: Some(0x80000000),
+    };
+    for instruction in instructions(lines)? {
+        writeln!(out, "* {}", describe(instruction, &registers)).unwrap();
+        for line in instruction {
+            writeln!(out, "{:08X} {:08X}", line[0], line[1]).unwrap();
+        }
+        if matches!(
+            split_codetype(codetype(instruction[0])).0,
+            0xC0 | 0xC2 | 0xF2 | 0xF4
+        ) {
+            disassemble_payload(&mut out, instruction);
+        }
+        registers.update(instruction[0]);
+    }
+    Ok(out)
+}
+
+/// D-form instruction, such as a load or store
+fn d_form(opcode: u32, d: u32, a: u32, offset: i16) -> u32 {
+    opcode << 26 | d << 21 | a << 16 | offset as u16 as u32
+}
+
+/// Code that saves the volatile registers, calls the codehandler, restores them, then runs the
+/// instruction the hook replaced and returns after it
+fn hook_code(address: u32, hook: u32, replaced: u32) -> anyhow::Result<Vec<u32>> {
+    const STW: u32 = 36;
+    const LWZ: u32 = 32;
+    const STFD: u32 = 54;
+    const LFD: u32 = 50;
+    // mfspr/mtspr r0 for LR, CTR, and XER, and mfcr/mtcrf
+    const SPECIAL: [(u32, u32); 4] = [
+        (0x7C0802A6, 0x7C0803A6),
+        (0x7C0902A6, 0x7C0903A6),
+        (0x7C000026, 0x7C0FF120),
+        (0x7C0102A6, 0x7C0103A6),
+    ];
+    let frame = HOOK_FRAME_SIZE as i16;
+
+    let mut code = vec![d_form(37, 1, 1, -frame), d_form(STW, 0, 1, 8)];
+    for (i, (save, _)) in SPECIAL.iter().enumerate() {
+        code.push(*save);
+        code.push(d_form(STW, 0, 1, 12 + i as i16 * 4));
+    }
+    // stmw r3 stores r3 to r31
+    code.push(d_form(47, 3, 1, 28));
+    for f in 0..14 {
+        code.push(d_form(STFD, f, 1, 144 + f as i16 * 8));
+    }
+    let call = address + code.len() as u32 * 4;
+    code.push(ppc::branch(
+        call,
+        CODEHANDLER_ADDRESS + CODEHANDLER_ENTRY_OFFSET,
+        true,
+    )?);
+    for f in 0..14 {
+        code.push(d_form(LFD, f, 1, 144 + f as i16 * 8));
+    }
+    code.push(d_form(46, 3, 1, 28));
+    for (i, (_, restore)) in SPECIAL.iter().enumerate().rev() {
+        code.push(d_form(LWZ, 0, 1, 12 + i as i16 * 4));
+        code.push(*restore);
+    }
+    code.push(d_form(LWZ, 0, 1, 8));
+    code.push(d_form(14, 1, 1, frame));
+
+    // The replaced instruction, with relative branches retargeted for its new address
+    let here = address + code.len() as u32 * 4;
+    code.push(ppc::relocate(replaced, hook, here)?);
+    let here = address + code.len() as u32 * 4;
+    code.push(ppc::branch(here, hook + 4, false)?);
+    Ok(code)
+}
+
+/// Install a codehandler and GCT into a new DOL text section, hooked at `hook`, so the codes run
+/// without a loader. The codehandler must be built to run from [`CODEHANDLER_ADDRESS`] and read
+/// its codes from its last 8 bytes onwards, like the one Dolphin ships. The instruction at
+/// `hook` is replaced by a branch to code that runs the codehandler; hook code that runs once
+/// per frame, such as the end of the VI retrace handler.
+pub fn install_codehandler(
+    dol: &mut Dol,
+    codehandler: &[u8],
+    gct: &[u8],
+    hook: u32,
+) -> anyhow::Result<()> {
+    ensure!(
+        codehandler.len() > (CODEHANDLER_ENTRY_OFFSET + 8) as usize
+            && codehandler.len().is_multiple_of(4),
+        "Codehandler is too small or not a whole number of instructions"
+    );
+    ensure!(
+        hook.is_multiple_of(4),
+        "Hook address {hook:#010x} is not aligned"
+    );
+    let mut data = codehandler[..codehandler.len() - 8].to_vec();
+    data.extend_from_slice(gct);
+    let hook_address = CODEHANDLER_ADDRESS + data.len() as u32;
+    let replaced = dol
+        .read_u32(hook)
+        .context("Hook address must be inside the DOL")?;
+    for word in hook_code(hook_address, hook, replaced)? {
+        data.extend_from_slice(&word.to_be_bytes());
+    }
+    let end = CODEHANDLER_ADDRESS + data.len() as u32;
+    ensure!(
+        end <= CODEHANDLER_END,
+        "Codehandler and codes end at {end:#010x}, past the free space ending at \
+         {CODEHANDLER_END:#010x}; {} bytes too many",
+        end - CODEHANDLER_END
+    );
+
+    dol.add_section(DolSectionKind::Text, CODEHANDLER_ADDRESS, data)?;
+    dol.write_u32(hook, ppc::branch(hook, hook_address, false)?)
+}
@@ -0,0 +1,319 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::dol::{Dol, DolSectionKind};
+use crate::gecko::{self, GeckoCode};
+use crate::mapheader;
+use crate::patch::{self, Patch, PatchValue, PatchWrite};
+use crate::ppc;
+use crate::symbol_map::{Symbol, SymbolMap};
+
+/// One line of a hook list: the function to hook and the function to call instead
+#[derive(Debug, Clone)]
+pub struct HookSpec {
+    /// Target as written in the hook list, used to name the hook
+    pub name: String,
+    pub target: u32,
+    /// Replacement symbol, resolved against the symbol map unless the hooks are installed by a
+    /// REL, where the linker resolves it
+    pub replacement: String,
+}
+
+/// A hook, ready to install: a branch to the replacement written over the target's first
+/// instruction, and a trampoline that runs the instruction the branch displaced and continues
+/// into the rest of the target, so the replacement can still call the original
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub name: String,
+    pub target: u32,
+    pub replacement: String,
+    /// Replacement address, unless the hooks are installed by a REL
+    pub replacement_address: Option<u32>,
+    /// Instruction displaced by the branch
+    pub original: u32,
+    /// Trampoline address, unless the hooks are installed by a REL, which allocates the
+    /// trampolines itself. Trampolines without a fixed address only use absolute jumps.
+    pub trampoline_address: Option<u32>,
+    pub trampoline: Vec<u32>,
+}
+
+impl Hook {
+    /// Name of the trampoline symbol, for the replacement to call the original
+    pub fn trampoline_name(&self) -> String {
+        format!("{}_trampoline", self.replacement)
+    }
+
+    /// The branch that replaces the target's first instruction
+    pub fn branch(&self) -> anyhow::Result<u32> {
+        let replacement = self
+            .replacement_address
+            .ok_or_else(|| anyhow!("Address of {} isn't known", self.replacement))?;
+        ppc::branch(self.target, replacement, false)
+            .with_context(|| format!("Can't hook {}", self.name))
+    }
+}
+
+/// Parse a hook list. Each line is `<target address or symbol[+offset]> <replacement symbol>`.
+/// Lines starting with `#` are comments.
+pub fn parse_hook_list(buf: &[u8], symbols: &SymbolMap) -> anyhow::Result<Vec<HookSpec>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse hook list as UTF-8")?;
+    let mut hooks = Vec::new();
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [target_expr, replacement] = fields[..] else {
+            bail!("Invalid hook on line {}: {}", line_num + 1, line);
+        };
+        let target = symbols
+            .resolve(target_expr)
+            .with_context(|| format!("Failed to resolve target on line {}", line_num + 1))?;
+        patch::ensure_aligned(target, 4)
+            .with_context(|| format!("Misaligned target on line {}", line_num + 1))?;
+        hooks.push(HookSpec {
+            name: target_expr.to_string(),
+            target,
+            replacement: replacement.to_string(),
+        });
+    }
+    Ok(hooks)
+}
+
+/// `lis r12, hi; ori r12, r12, lo; mtctr r12; bctr`, or `bctrl` with `link`, which reaches any
+/// address from anywhere
+fn absolute_jump(to: u32, link: bool) -> [u32; 4] {
+    [
+        0x3D800000 | to >> 16,
+        0x618C0000 | (to & 0xFFFF),
+        0x7D8903A6,
+        0x4E800420 | link as u32,
+    ]
+}
+
+/// Build the trampoline for a hook on `target`, whose first instruction is `original`. Jumps use
+/// `b` where `address` is known and the destination is in range, and otherwise go through r12,
+/// which is free at function entry.
+fn trampoline(target: u32, original: u32, address: Option<u32>) -> anyhow::Result<Vec<u32>> {
+    let mut code = Vec::new();
+    match (ppc::relative_branch_target(original, target), address) {
+        (Some(destination), address) => {
+            let link = original & 1 != 0;
+            match address.and_then(|a| ppc::branch(a, destination, link).ok()) {
+                Some(branch) => code.push(branch),
+                None => code.extend(absolute_jump(destination, link)),
+            }
+            // A displaced `b` never returns here
+            if !link {
+                return Ok(code);
+            }
+        }
+        (None, address) => code.push(ppc::relocate(original, target, address.unwrap_or(target))?),
+    }
+    let here = address.map(|a| a + code.len() as u32 * 4);
+    match here.and_then(|h| ppc::branch(h, target + 4, false).ok()) {
+        Some(branch) => code.push(branch),
+        None => code.extend(absolute_jump(target + 4, false)),
+    }
+    Ok(code)
+}
+
+/// Build the hooks for `specs`, reading the displaced instructions from `dol`. Trampolines are
+/// placed one after another from `trampoline_address`, and replacements are resolved against
+/// `symbols`. Without a trampoline address, the hooks are for a REL to install: trampolines only
+/// use absolute jumps, and replacements are left for the linker.
+pub fn build_hooks(
+    specs: &[HookSpec],
+    dol: &Dol,
+    symbols: &SymbolMap,
+    trampoline_address: Option<u32>,
+) -> anyhow::Result<Vec<Hook>> {
+    let mut hooks = Vec::new();
+    let mut next_address = trampoline_address;
+    for spec in specs {
+        ensure!(
+            !hooks.iter().any(|h: &Hook| h.target == spec.target),
+            "{} is hooked more than once",
+            spec.name
+        );
+        let original = dol
+            .read_u32(spec.target)
+            .with_context(|| format!("Can't hook {}", spec.name))?;
+        let trampoline = trampoline(spec.target, original, next_address)
+            .with_context(|| format!("Can't hook {}", spec.name))?;
+        let replacement_address = match trampoline_address {
+            Some(_) => Some(
+                symbols
+                    .resolve(&spec.replacement)
+                    .with_context(|| format!("Failed to resolve replacement for {}", spec.name))?,
+            ),
+            None => None,
+        };
+        let hook = Hook {
+            name: spec.name.clone(),
+            target: spec.target,
+            replacement: spec.replacement.clone(),
+            replacement_address,
+            original,
+            trampoline_address: next_address,
+            trampoline,
+        };
+        if hook.replacement_address.is_some() {
+            hook.branch()?;
+        }
+        next_address = next_address.map(|a| a + hook.trampoline.len() as u32 * 4);
+        hooks.push(hook);
+    }
+    Ok(hooks)
+}
+
+/// The trampolines' addresses, for linking replacements that call them
+pub fn trampoline_map(hooks: &[Hook]) -> SymbolMap {
+    let mut map = SymbolMap::default();
+    for hook in hooks {
+        if let Some(address) = hook.trampoline_address {
+            map.symbols.push(Symbol {
+                address,
+                name: hook.trampoline_name(),
+                size: Some(hook.trampoline.len() as u32 * 4),
+                section: Some(".text".to_string()),
+            });
+        }
+    }
+    map
+}
+
+fn trampoline_bytes(hooks: &[Hook]) -> Vec<u8> {
+    hooks
+        .iter()
+        .flat_map(|h| &h.trampoline)
+        .flat_map(|w| w.to_be_bytes())
+        .collect()
+}
+
+/// Install hooks into a DOL: the branches are written over their targets, and the trampolines go
+/// in a new text section
+pub fn apply_to_dol(dol: &mut Dol, hooks: &[Hook]) -> anyhow::Result<()> {
+    for hook in hooks {
+        dol.write_u32(hook.target, hook.branch()?)?;
+    }
+    if let Some(address) = hooks.first().and_then(|h| h.trampoline_address) {
+        dol.add_section(DolSectionKind::Text, address, trampoline_bytes(hooks))?;
+    }
+    Ok(())
+}
+
+/// Hooks as a patch list, one patch per hook, writing its trampoline and branch
+pub fn to_patches(hooks: &[Hook]) -> anyhow::Result<Vec<Patch>> {
+    let mut patches = Vec::new();
+    for hook in hooks {
+        let address = hook
+            .trampoline_address
+            .ok_or_else(|| anyhow!("Trampoline for {} has no address", hook.name))?;
+        let mut writes: Vec<PatchWrite> = (address..)
+            .step_by(4)
+            .zip(&hook.trampoline)
+            .map(|(address, &word)| PatchWrite {
+                address,
+                value: PatchValue::U32(word),
+            })
+            .collect();
+        writes.push(PatchWrite {
+            address: hook.target,
+            value: PatchValue::U32(hook.branch()?),
+        });
+        patches.push(Patch {
+            name: format!("Hook {}", hook.name),
+            writes,
+        });
+    }
+    Ok(patches)
+}
+
+/// Hooks as Gecko codes, one per hook: a string write (06) of the trampoline, then a branch (C6)
+/// from the target to the replacement
+pub fn to_gecko(hooks: &[Hook]) -> anyhow::Result<Vec<GeckoCode>> {
+    let mut codes = Vec::new();
+    for hook in hooks {
+        let address = hook
+            .trampoline_address
+            .ok_or_else(|| anyhow!("Trampoline for {} has no address", hook.name))?;
+        let mut lines = vec![[
+            0x06000000 | gecko::base_offset(address)?,
+            hook.trampoline.len() as u32 * 4,
+        ]];
+        lines.extend(
+            hook.trampoline
+                .chunks(2)
+                .map(|w| [w[0], *w.get(1).unwrap_or(&0)]),
+        );
+        let replacement = hook
+            .replacement_address
+            .ok_or_else(|| anyhow!("Address of {} isn't known", hook.replacement))?;
+        lines.push([0xC6000000 | gecko::base_offset(hook.target)?, replacement]);
+        codes.push(GeckoCode {
+            name: format!("Hook {}", hook.name),
+            lines,
+            ..Default::default()
+        });
+    }
+    Ok(codes)
+}
+
+/// Hooks as C source for a REL to install at runtime. Each hook gives the target, the
+/// replacement, the instruction expected at the target, and a trampoline the REL has to flush
+/// from the data cache before it runs. The REL checks the target still holds the expected
+/// instruction, writes a branch to the replacement over it, and flushes the caches.
+pub fn to_c_source(hooks: &[Hook]) -> anyhow::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n").unwrap();
+    writeln!(out, "typedef struct {{").unwrap();
+    writeln!(out, "    unsigned int target;").unwrap();
+    writeln!(out, "    void (*replacement)(void);").unwrap();
+    writeln!(out, "    unsigned int original;").unwrap();
+    writeln!(out, "    unsigned int *trampoline;").unwrap();
+    writeln!(out, "    unsigned int trampoline_size; /* in bytes */").unwrap();
+    writeln!(out, "}} Hook;\n").unwrap();
+
+    for hook in hooks {
+        ensure!(
+            mapheader::is_identifier(&hook.replacement),
+            "Replacement {} isn't a C identifier",
+            hook.replacement
+        );
+        let words: Vec<String> = hook
+            .trampoline
+            .iter()
+            .map(|w| format!("{w:#010x}"))
+            .collect();
+        writeln!(out, "void {}(void);", hook.replacement).unwrap();
+        writeln!(
+            out,
+            "unsigned int {}[] = {{{}}};\n",
+            hook.trampoline_name(),
+            words.join(", ")
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "const Hook hooks[] = {{").unwrap();
+    for hook in hooks {
+        writeln!(
+            out,
+            "    {{{:#010x}, {}, {:#010x}, {}, sizeof({})}}, /* {} */",
+            hook.target,
+            hook.replacement,
+            hook.original,
+            hook.trampoline_name(),
+            hook.trampoline_name(),
+            hook.name
+        )
+        .unwrap();
+    }
+    writeln!(out, "}};").unwrap();
+    writeln!(out, "const unsigned int hook_count = {};", hooks.len()).unwrap();
+    writeln!(out, "\n#ifdef __cplusplus\n}}\n#endif").unwrap();
+    Ok(out)
+}
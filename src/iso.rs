@@ -0,0 +1,790 @@
+pub mod apploader;
+pub mod build;
+pub mod compressed;
+pub mod fst;
+pub mod header;
+pub mod tgc;
+pub mod trim;
+pub mod verify;
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use zerocopy::IntoBytes;
+
+use crate::dol::{DolHeader, Severity};
+use crate::formats::Format;
+use crate::progress::{ProgressFn, ProgressIo};
+use crate::safe_path;
+use apploader::Apploader;
+use compressed::{Ciso, Gcz, ImageFormat};
+use fst::{Fst, FstEntryKind};
+use header::{
+    ApploaderHeader, Bi2, DiskHeader, HeaderEdit, APPLOADER_OFFSET, BI2_OFFSET, BI2_SIZE,
+    BOOT_SIZE, DISC_SIZE,
+};
+use tgc::Tgc;
+
+/// Directory that system files are extracted to, alongside the disc's file system
+pub const SYSTEM_DIR_NAME: &str = "&&systemdata";
+/// Directories of the layout Dolphin extracts discs to, which other tools also expect
+pub const DOLPHIN_SYSTEM_DIR_NAME: &str = "sys";
+pub const DOLPHIN_FILES_DIR_NAME: &str = "files";
+
+/// How an extracted disc is laid out on the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtractLayout {
+    /// The file system in the output directory, with system files in `SYSTEM_DIR_NAME`
+    #[default]
+    SystemData,
+    /// Dolphin's layout: system files in `sys/` and the file system in `files/`
+    Dolphin,
+}
+
+impl ExtractLayout {
+    /// The layout of the extracted disc in `dir`
+    pub fn detect(dir: &Path) -> ExtractLayout {
+        if !dir.join(SYSTEM_DIR_NAME).is_dir()
+            && dir.join(DOLPHIN_SYSTEM_DIR_NAME).is_dir()
+            && dir.join(DOLPHIN_FILES_DIR_NAME).is_dir()
+        {
+            ExtractLayout::Dolphin
+        } else {
+            ExtractLayout::SystemData
+        }
+    }
+
+    /// Directories of the file system and of the system files for a disc extracted to `dir`
+    pub fn dirs(self, dir: &Path) -> (PathBuf, PathBuf) {
+        match self {
+            ExtractLayout::SystemData => (dir.to_path_buf(), dir.join(SYSTEM_DIR_NAME)),
+            ExtractLayout::Dolphin => (
+                dir.join(DOLPHIN_FILES_DIR_NAME),
+                dir.join(DOLPHIN_SYSTEM_DIR_NAME),
+            ),
+        }
+    }
+}
+
+/// Alignment of main.dol and fst.bin on disc
+const SYSTEM_FILE_ALIGNMENT: u64 = 0x100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemFile {
+    Boot,
+    Bi2,
+    Apploader,
+    MainDol,
+    Fst,
+}
+
+impl SystemFile {
+    pub const ALL: [SystemFile; 5] = [
+        SystemFile::Boot,
+        SystemFile::Bi2,
+        SystemFile::Apploader,
+        SystemFile::MainDol,
+        SystemFile::Fst,
+    ];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SystemFile::Boot => "boot.bin",
+            SystemFile::Bi2 => "bi2.bin",
+            SystemFile::Apploader => "apploader.img",
+            SystemFile::MainDol => "main.dol",
+            SystemFile::Fst => "fst.bin",
+        }
+    }
+}
+
+/// A GameCube disc image opened for reading
+pub struct Iso<R> {
+    reader: R,
+    pub header: DiskHeader,
+    pub fst: Fst,
+}
+
+impl<R: Read + Seek> Iso<R> {
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let header = DiskHeader::parse(&read_at(&mut reader, 0, BOOT_SIZE)?)?;
+        let fst_buf = read_at(
+            &mut reader,
+            header.fst_offset.get() as u64,
+            header.fst_size.get() as usize,
+        )
+        .context("Failed to read FST")?;
+        let fst = Fst::parse(&fst_buf).context("Failed to parse FST")?;
+        Ok(Iso {
+            reader,
+            header,
+            fst,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn read_at(&mut self, offset: u64, size: usize) -> anyhow::Result<Vec<u8>> {
+        read_at(&mut self.reader, offset, size)
+    }
+
+    /// Offset and size of a system file on disc
+    pub fn system_file_range(&mut self, file: SystemFile) -> anyhow::Result<(u64, u64)> {
+        let range = match file {
+            SystemFile::Boot => (0, BOOT_SIZE as u64),
+            SystemFile::Bi2 => (BI2_OFFSET, BI2_SIZE as u64),
+            SystemFile::Apploader => {
+                let buf = self.read_at(APPLOADER_OFFSET, size_of::<ApploaderHeader>())?;
+                let apploader = ApploaderHeader::parse(&buf)?;
+                (APPLOADER_OFFSET, apploader.total_size() as u64)
+            }
+            SystemFile::MainDol => {
+                let offset = self.header.dol_offset.get() as u64;
+                let buf = self.read_at(offset, size_of::<DolHeader>())?;
+                let dol = DolHeader::parse(&buf)?;
+                (offset, dol.file_size() as u64)
+            }
+            SystemFile::Fst => (
+                self.header.fst_offset.get() as u64,
+                self.header.fst_size.get() as u64,
+            ),
+        };
+        Ok(range)
+    }
+
+    pub fn read_system_file(&mut self, file: SystemFile) -> anyhow::Result<Vec<u8>> {
+        let (offset, size) = self.system_file_range(file)?;
+        self.read_at(offset, size as usize)
+            .with_context(|| format!("Failed to read {}", file.file_name()))
+    }
+
+    pub fn read_bi2(&mut self) -> anyhow::Result<Bi2> {
+        Bi2::parse(&self.read_system_file(SystemFile::Bi2)?)
+    }
+
+    /// Read the contents of the file at FST index `index`
+    pub fn read_file(&mut self, index: usize) -> anyhow::Result<Vec<u8>> {
+        let FstEntryKind::File { offset, size } = self.fst.entries[index].kind else {
+            return Err(anyhow!("FST entry {index} is not a file"));
+        };
+        self.read_at(offset as u64, size as usize)
+    }
+
+    /// Read opening.bnr, preferring the copy in the root directory
+    pub fn read_banner(&mut self) -> anyhow::Result<Vec<u8>> {
+        let index = self
+            .fst
+            .find("opening.bnr")
+            .or_else(|| {
+                self.fst
+                    .entries
+                    .iter()
+                    .position(|e| !e.is_dir() && e.name.eq_ignore_ascii_case(b"opening.bnr"))
+            })
+            .ok_or_else(|| anyhow!("opening.bnr not found on disc"))?;
+        self.read_file(index)
+    }
+
+    /// Regions of the disc in use by system files and files, sorted by offset, excluding the
+    /// given FST entry and system files
+    fn used_regions(
+        &mut self,
+        skip_entry: Option<usize>,
+        skip_system: &[SystemFile],
+    ) -> anyhow::Result<Vec<(u64, u64)>> {
+        let mut regions = Vec::new();
+        for file in SystemFile::ALL {
+            if skip_system.contains(&file) {
+                continue;
+            }
+            let (offset, size) = self.system_file_range(file)?;
+            regions.push((offset, offset + size));
+        }
+        for (i, entry) in self.fst.entries.iter().enumerate() {
+            if let FstEntryKind::File { offset, size } = entry.kind
+                && Some(i) != skip_entry
+                && size != 0
+            {
+                regions.push((offset as u64, offset as u64 + size as u64));
+            }
+        }
+        regions.sort_unstable();
+        Ok(regions)
+    }
+
+    /// Find the first free region of `size` bytes, aligned to `alignment`
+    fn find_free_region(regions: &[(u64, u64)], size: u64, alignment: u64) -> anyhow::Result<u64> {
+        let mut cursor = 0u64;
+        for &(start, end) in regions {
+            let candidate = cursor.next_multiple_of(alignment);
+            if candidate + size <= start {
+                return Ok(candidate);
+            }
+            cursor = cursor.max(end);
+        }
+        let candidate = cursor.next_multiple_of(alignment);
+        if candidate + size > DISC_SIZE {
+            return Err(anyhow!("No free space on disc for {size:#x} bytes"));
+        }
+        Ok(candidate)
+    }
+
+    /// Stream `size` bytes at `offset` into `writer` without buffering the whole range
+    pub fn copy_range<W: Write>(
+        &mut self,
+        offset: u64,
+        size: u64,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let copied = io::copy(&mut (&mut self.reader).take(size), writer)?;
+        if copied != size {
+            return Err(anyhow!(
+                "Unexpected end of disc image reading {size:#x} bytes at {offset:#x}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copy a range to a new file, reporting it as progress on top of `done`. Returns the new
+    /// amount done.
+    fn extract_range(
+        &mut self,
+        offset: u64,
+        size: u64,
+        dest: &Path,
+        done: u64,
+        total: u64,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<u64> {
+        let file = File::create(dest)
+            .with_context(|| format!("cannot create {}", dest.to_string_lossy()))?;
+        let mut writer = ProgressIo::new(file, done, total, progress);
+        self.copy_range(offset, size, &mut writer)
+            .with_context(|| format!("Failed to extract {}", dest.to_string_lossy()))?;
+        Ok(writer.done())
+    }
+
+    fn system_files_size(&mut self) -> anyhow::Result<u64> {
+        let mut size = 0;
+        for file in SystemFile::ALL {
+            size += self.system_file_range(file)?.1;
+        }
+        Ok(size)
+    }
+
+    fn entry_size(&self, index: usize) -> u64 {
+        self.fst
+            .subtree(index)
+            .map(|i| match self.fst.entries[i].kind {
+                FstEntryKind::File { size, .. } => size as u64,
+                FstEntryKind::Directory { .. } => 0,
+            })
+            .sum()
+    }
+
+    fn extract_system_files_from(
+        &mut self,
+        out_dir: &Path,
+        mut done: u64,
+        total: u64,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<u64> {
+        std::fs::create_dir_all(out_dir)?;
+        for file in SystemFile::ALL {
+            let (offset, size) = self.system_file_range(file)?;
+            let dest = out_dir.join(file.file_name());
+            done = self.extract_range(offset, size, &dest, done, total, progress)?;
+        }
+        Ok(done)
+    }
+
+    fn extract_entry_from(
+        &mut self,
+        index: usize,
+        out_dir: &Path,
+        mut done: u64,
+        total: u64,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<u64> {
+        let paths = self.fst.paths();
+        let base = Path::new(&paths[index])
+            .parent()
+            .unwrap_or(Path::new("/"))
+            .to_path_buf();
+
+        // Names come from the disc, so check them all before creating anything
+        for i in self.fst.subtree(index) {
+            if i != 0 {
+                safe_path::check_name(&self.fst.entries[i].display_name())
+                    .with_context(|| format!("Refusing to extract {}", paths[i]))?;
+            }
+        }
+
+        for i in self.fst.subtree(index) {
+            let relative = Path::new(&paths[i]).strip_prefix(&base).unwrap();
+            let dest = out_dir.join(relative);
+            match self.fst.entries[i].kind {
+                FstEntryKind::Directory { .. } => std::fs::create_dir_all(&dest)?,
+                FstEntryKind::File { offset, size } => {
+                    if let Some(parent) = dest.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    done = self.extract_range(
+                        offset as u64,
+                        size as u64,
+                        &dest,
+                        done,
+                        total,
+                        progress,
+                    )?;
+                }
+            }
+        }
+        Ok(done)
+    }
+
+    pub fn extract_system_files(&mut self, out_dir: &Path) -> anyhow::Result<()> {
+        let total = self.system_files_size()?;
+        self.extract_system_files_from(out_dir, 0, total, &mut |_, _| {})?;
+        Ok(())
+    }
+
+    /// Extract the file or directory at FST index `index` (and everything below it) into
+    /// `out_dir`
+    pub fn extract_entry(&mut self, index: usize, out_dir: &Path) -> anyhow::Result<()> {
+        self.extract_entry_with_progress(index, out_dir, &mut |_, _| {})
+    }
+
+    /// Like `extract_entry`, reporting progress in bytes extracted
+    pub fn extract_entry_with_progress(
+        &mut self,
+        index: usize,
+        out_dir: &Path,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<()> {
+        let total = self.entry_size(index);
+        self.extract_entry_from(index, out_dir, 0, total, progress)?;
+        Ok(())
+    }
+
+    /// Extract the file or directory at `path` on disc into `out_dir`
+    pub fn extract_path(&mut self, path: &str, out_dir: &Path) -> anyhow::Result<()> {
+        self.extract_path_with_progress(path, out_dir, &mut |_, _| {})
+    }
+
+    /// Like `extract_path`, reporting progress in bytes extracted
+    pub fn extract_path_with_progress(
+        &mut self,
+        path: &str,
+        out_dir: &Path,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<()> {
+        let index = self
+            .fst
+            .find(path)
+            .ok_or_else(|| anyhow!("'{path}' not found on disc"))?;
+        self.extract_entry_with_progress(index, out_dir, progress)
+    }
+
+    /// Extract the whole file system to `out_dir`, with system files in `SYSTEM_DIR_NAME`
+    pub fn extract_all(&mut self, out_dir: &Path) -> anyhow::Result<()> {
+        self.extract_all_with_progress(out_dir, &mut |_, _| {})
+    }
+
+    /// Like `extract_all`, reporting progress in bytes extracted
+    pub fn extract_all_with_progress(
+        &mut self,
+        out_dir: &Path,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<()> {
+        self.extract_all_with_layout(out_dir, ExtractLayout::SystemData, progress)
+    }
+
+    /// Like `extract_all_with_progress`, laying out the extracted files as `layout`
+    pub fn extract_all_with_layout(
+        &mut self,
+        out_dir: &Path,
+        layout: ExtractLayout,
+        progress: &mut ProgressFn,
+    ) -> anyhow::Result<()> {
+        let (files_dir, sys_dir) = layout.dirs(out_dir);
+        let total = self.system_files_size()? + self.entry_size(0);
+        let done = self.extract_system_files_from(&sys_dir, 0, total, progress)?;
+        // Extracting the root places its contents directly in files_dir
+        std::fs::create_dir_all(&files_dir)?;
+        self.extract_entry_from(0, &files_dir, done, total, progress)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    /// The new data was written over the old file
+    InPlace { offset: u32 },
+    /// The new data didn't fit, so was moved to a free region
+    Relocated { old_offset: u32, new_offset: u32 },
+}
+
+impl<R: Read + Write + Seek> Iso<R> {
+    pub fn write_at(&mut self, offset: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader
+            .write_all(data)
+            .with_context(|| format!("Failed to write {:#x} bytes at {offset:#x}", data.len()))
+    }
+
+    fn zero_range(&mut self, offset: u64, size: u64) -> anyhow::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        io::copy(&mut io::repeat(0).take(size), &mut self.reader)?;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> anyhow::Result<()> {
+        let header = self.header.clone();
+        self.write_at(0, header.as_bytes())
+    }
+
+    /// Change fields of boot.bin and bi2.bin in place
+    pub fn edit_header(&mut self, edit: &HeaderEdit) -> anyhow::Result<()> {
+        let mut bi2 = self.read_bi2()?;
+        let mut header = self.header.clone();
+        edit.apply(&mut header, &mut bi2)?;
+        self.header = header;
+        self.write_header()?;
+        self.write_at(BI2_OFFSET, bi2.as_bytes())
+    }
+
+    /// Replace the contents of the file at `path` on disc, reusing its current location if the
+    /// new data fits before the next file. The FST entry is updated in place.
+    pub fn replace_file(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        alignment: u32,
+    ) -> anyhow::Result<Replacement> {
+        let index = self
+            .fst
+            .find(path)
+            .ok_or_else(|| anyhow!("'{path}' not found on disc"))?;
+        let FstEntryKind::File {
+            offset: old_offset,
+            size: old_size,
+        } = self.fst.entries[index].kind
+        else {
+            return Err(anyhow!("'{path}' is a directory"));
+        };
+        let new_size: u32 = data
+            .len()
+            .try_into()
+            .map_err(|_| anyhow!("Replacement for '{path}' is too large"))?;
+
+        let regions = self.used_regions(Some(index), &[])?;
+        let space = regions
+            .iter()
+            .map(|&(start, _)| start)
+            .find(|&start| start >= old_offset as u64)
+            .unwrap_or(DISC_SIZE)
+            - old_offset as u64;
+
+        let replacement = if new_size as u64 <= space {
+            self.write_at(old_offset as u64, data)?;
+            if new_size < old_size {
+                self.zero_range((old_offset + new_size) as u64, (old_size - new_size) as u64)?;
+            }
+            Replacement::InPlace { offset: old_offset }
+        } else {
+            let new_offset =
+                Self::find_free_region(&regions, new_size as u64, alignment as u64)? as u32;
+            // The old file counts as free space, so the new location may overlap it
+            self.zero_range(old_offset as u64, old_size as u64)?;
+            self.write_at(new_offset as u64, data)?;
+            Replacement::Relocated {
+                old_offset,
+                new_offset,
+            }
+        };
+
+        let offset = match replacement {
+            Replacement::InPlace { offset } => offset,
+            Replacement::Relocated { new_offset, .. } => new_offset,
+        };
+        self.fst.entries[index].kind = FstEntryKind::File {
+            offset,
+            size: new_size,
+        };
+        // Only patch the offset and size words, leaving the name offset untouched
+        let entry_offset = self.header.fst_offset.get() as u64 + (index * fst::ENTRY_SIZE) as u64;
+        let mut entry = offset.to_be_bytes().to_vec();
+        entry.extend_from_slice(&new_size.to_be_bytes());
+        self.write_at(entry_offset + 4, &entry)?;
+
+        Ok(replacement)
+    }
+
+    /// Move main.dol or fst.bin to a free region outside of `avoid`, updating boot.bin
+    fn relocate_system_file(&mut self, file: SystemFile, avoid: (u64, u64)) -> anyhow::Result<()> {
+        let (old_offset, size) = self.system_file_range(file)?;
+        let data = self.read_at(old_offset, size as usize)?;
+        let mut regions = self.used_regions(None, &[file])?;
+        regions.push(avoid);
+        regions.sort_unstable();
+        let new_offset = Self::find_free_region(&regions, size, SYSTEM_FILE_ALIGNMENT)?;
+
+        // The new location may overlap the old one, so clear before writing
+        self.zero_range(old_offset, size)?;
+        self.write_at(new_offset, &data)?;
+        match file {
+            SystemFile::MainDol => self.header.dol_offset = (new_offset as u32).into(),
+            SystemFile::Fst => self.header.fst_offset = (new_offset as u32).into(),
+            _ => unreachable!("{} has a fixed location", file.file_name()),
+        }
+        self.write_header()
+    }
+
+    /// Replace main.dol or apploader.img. A larger main.dol is moved to free space; a larger
+    /// apploader pushes main.dol and fst.bin out of its way.
+    pub fn replace_system_file(
+        &mut self,
+        file: SystemFile,
+        data: &[u8],
+    ) -> anyhow::Result<Replacement> {
+        let (old_offset, old_size) = self.system_file_range(file)?;
+        let new_size = data.len() as u64;
+        let regions = self.used_regions(None, &[file])?;
+        let space = regions
+            .iter()
+            .map(|&(start, _)| start)
+            .find(|&start| start >= old_offset)
+            .unwrap_or(DISC_SIZE)
+            - old_offset;
+
+        match file {
+            SystemFile::MainDol => {
+                DolHeader::parse(data).context("Invalid DOL")?;
+                if new_size <= space {
+                    self.zero_range(old_offset, old_size)?;
+                    self.write_at(old_offset, data)?;
+                    return Ok(Replacement::InPlace {
+                        offset: old_offset as u32,
+                    });
+                }
+                let new_offset = Self::find_free_region(&regions, new_size, SYSTEM_FILE_ALIGNMENT)?;
+                self.zero_range(old_offset, old_size)?;
+                self.write_at(new_offset, data)?;
+                self.header.dol_offset = (new_offset as u32).into();
+                self.write_header()?;
+                Ok(Replacement::Relocated {
+                    old_offset: old_offset as u32,
+                    new_offset: new_offset as u32,
+                })
+            }
+            SystemFile::Apploader => {
+                let apploader = Apploader::parse(data)?;
+                if let Some(issue) = apploader
+                    .validate()
+                    .into_iter()
+                    .find(|i| i.severity == Severity::Error)
+                {
+                    bail!("Invalid apploader: {}", issue.message);
+                }
+                let new_range = (APPLOADER_OFFSET, APPLOADER_OFFSET + new_size);
+                for blocker in [SystemFile::MainDol, SystemFile::Fst] {
+                    let (offset, size) = self.system_file_range(blocker)?;
+                    if offset < new_range.1 && new_range.0 < offset + size {
+                        self.relocate_system_file(blocker, new_range)?;
+                    }
+                }
+                let regions = self.used_regions(None, &[file])?;
+                if let Some(&(start, _)) = regions
+                    .iter()
+                    .find(|&&(start, end)| start < new_range.1 && new_range.0 < end)
+                {
+                    bail!(
+                        "Apploader would overlap the file at {start:#x}; rebuild the disc instead"
+                    );
+                }
+                self.zero_range(old_offset, old_size)?;
+                self.write_at(APPLOADER_OFFSET, data)?;
+                Ok(Replacement::InPlace {
+                    offset: APPLOADER_OFFSET as u32,
+                })
+            }
+            _ => bail!("Replacing {} is not supported", file.file_name()),
+        }
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, size: usize) -> anyhow::Result<Vec<u8>> {
+    let mut buf = vec![0; size];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader
+        .read_exact(&mut buf)
+        .with_context(|| format!("Failed to read {size:#x} bytes at {offset:#x}"))?;
+    Ok(buf)
+}
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+fn read_magic<R: Read + Seek>(reader: &mut R) -> anyhow::Result<ImageFormat> {
+    let mut magic = Vec::new();
+    reader.by_ref().take(4).read_to_end(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+    // Files too short to have a magic are read as is
+    Ok(magic
+        .try_into()
+        .map_or(ImageFormat::Iso, |magic| compressed::detect_format(&magic)))
+}
+
+/// Open a plain, CISO, GCZ, or TGC disc image as a flat uncompressed GCM stream
+pub fn open_image(path: &Path) -> anyhow::Result<Box<dyn ReadSeek>> {
+    let file =
+        File::open(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+    let mut reader = io::BufReader::new(file);
+    let image: Box<dyn ReadSeek> = match read_magic(&mut reader)? {
+        ImageFormat::Iso => Box::new(reader),
+        ImageFormat::Ciso => Box::new(Ciso::new(reader)?),
+        ImageFormat::Gcz => Box::new(Gcz::new(reader)?),
+        ImageFormat::Tgc => Box::new(Tgc::new(reader)?),
+    };
+    Ok(image)
+}
+
+/// Open a plain, CISO, GCZ, or TGC disc image for reading
+pub fn open(path: &Path) -> anyhow::Result<Iso<Box<dyn ReadSeek>>> {
+    Iso::new(open_image(path)?)
+}
+
+/// Open a disc image for modification in place
+pub fn open_rw(path: &Path) -> anyhow::Result<Iso<File>> {
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("cannot open {}", path.to_string_lossy()))?;
+    if read_magic(&mut file)? != ImageFormat::Iso {
+        bail!("Only plain disc images can be modified in place");
+    }
+    Iso::new(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use zerocopy::FromZeros;
+
+    use super::*;
+    use fst::FstEntry;
+    use header::GC_MAGIC;
+
+    /// A disc with an empty apploader and DOL, and files `a.bin` at 0x10000 and `b.bin` right
+    /// after it, each 0x1000 bytes
+    fn test_disc() -> Iso<Cursor<Vec<u8>>> {
+        let fst = Fst {
+            entries: vec![
+                FstEntry {
+                    name: Vec::new(),
+                    kind: FstEntryKind::Directory { parent: 0, next: 3 },
+                },
+                FstEntry {
+                    name: b"a.bin".to_vec(),
+                    kind: FstEntryKind::File {
+                        offset: 0x10000,
+                        size: 0x1000,
+                    },
+                },
+                FstEntry {
+                    name: b"b.bin".to_vec(),
+                    kind: FstEntryKind::File {
+                        offset: 0x11000,
+                        size: 0x1000,
+                    },
+                },
+            ],
+        };
+        let fst = fst.to_bytes();
+        let mut header = DiskHeader::new_zeroed();
+        header.gc_magic = GC_MAGIC.into();
+        header.dol_offset = 0x2500.into();
+        header.fst_offset = 0x2600.into();
+        header.fst_size = (fst.len() as u32).into();
+
+        let mut disc = vec![0; 0x12000];
+        disc[..BOOT_SIZE].copy_from_slice(header.as_bytes());
+        disc[0x2600..0x2600 + fst.len()].copy_from_slice(&fst);
+        disc[0x10000..0x11000].fill(0xAA);
+        disc[0x11000..0x12000].fill(0xBB);
+        Iso::new(Cursor::new(disc)).unwrap()
+    }
+
+    #[test]
+    fn replace_file_in_place() {
+        let mut iso = test_disc();
+        let data = vec![0xCC; 0x800];
+        let replacement = iso.replace_file("a.bin", &data, 0x20).unwrap();
+        assert_eq!(replacement, Replacement::InPlace { offset: 0x10000 });
+        assert_eq!(iso.read_file(1).unwrap(), data);
+        assert_eq!(iso.read_at(0x10800, 0x800).unwrap(), vec![0; 0x800]);
+        assert_eq!(iso.read_file(2).unwrap(), vec![0xBB; 0x1000]);
+    }
+
+    #[test]
+    fn replace_file_relocated_over_old_location() {
+        let mut iso = test_disc();
+        // Too big to stay in place, and placed first fit at 0x8000 so its end overlaps where the
+        // old file was
+        let data = vec![0xCC; 0x8800];
+        let replacement = iso.replace_file("a.bin", &data, 0x8000).unwrap();
+        assert_eq!(
+            replacement,
+            Replacement::Relocated {
+                old_offset: 0x10000,
+                new_offset: 0x8000
+            }
+        );
+        assert_eq!(iso.read_file(1).unwrap(), data);
+        assert_eq!(iso.read_file(2).unwrap(), vec![0xBB; 0x1000]);
+
+        // The FST on disc points at the new location
+        let mut reopened = Iso::new(iso.into_inner()).unwrap();
+        assert_eq!(reopened.read_file(1).unwrap(), data);
+    }
+
+    #[test]
+    fn shift_jis_names_survive_extract_and_build() {
+        let mut iso = test_disc();
+        // マリオ.bin in Shift-JIS
+        let name = b"\x83\x7d\x83\x8a\x83\x49.bin".to_vec();
+        iso.fst.entries[2].name = name.clone();
+        let dir = std::env::temp_dir().join(format!("gctools-sjis-{}", std::process::id()));
+        iso.extract_all(&dir).unwrap();
+        assert!(dir.join("マリオ.bin").exists());
+
+        let (files_dir, sys_dir) = ExtractLayout::SystemData.dirs(&dir);
+        let mut built = Vec::new();
+        let result = build::build_iso(&files_dir, &sys_dir, &Default::default(), &mut built);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+
+        let mut rebuilt = Iso::new(Cursor::new(built)).unwrap();
+        let names: Vec<_> = rebuilt.fst.entries.iter().map(|e| e.name.clone()).collect();
+        assert_eq!(names, [Vec::new(), b"a.bin".to_vec(), name]);
+        assert_eq!(rebuilt.read_file(2).unwrap(), vec![0xBB; 0x1000]);
+    }
+
+    #[test]
+    fn extract_rejects_traversal() {
+        let mut iso = test_disc();
+        iso.fst.entries[1].name = b"../a.bin".to_vec();
+        let out_dir = std::env::temp_dir().join(format!("gctools-extract-{}", std::process::id()));
+        assert!(iso.extract_entry(0, &out_dir).is_err());
+        assert!(!out_dir.exists());
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{ensure, Context};
+use zerocopy::IntoBytes;
+
+use super::header::ApploaderHeader;
+use crate::dol::{DolIssue, Severity, MEM1_END};
+
+/// Address the apploader's code is copied to by the IPL
+pub const APPLOADER_LOAD_ADDRESS: u32 = 0x81200000;
+
+/// A parsed apploader.img: the header, the code loaded at [`APPLOADER_LOAD_ADDRESS`], and the
+/// trailer that follows it
+#[derive(Clone)]
+pub struct Apploader {
+    /// Build date, conventionally `YYYY/MM/DD`
+    pub date: String,
+    pub entry_point: u32,
+    pub code: Vec<u8>,
+    pub trailer: Vec<u8>,
+}
+
+impl Apploader {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Apploader> {
+        let header = ApploaderHeader::parse(buf)?;
+        ensure!(
+            buf.len() as u64 >= header.total_size() as u64,
+            "Apploader is smaller than its header describes ({:#x} < {:#x})",
+            buf.len(),
+            header.total_size()
+        );
+        let code_start = size_of::<ApploaderHeader>();
+        let code_end = code_start + header.size.get() as usize;
+        let trailer_end = code_end + header.trailer_size.get() as usize;
+
+        let date_len = header
+            .date
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(header.date.len());
+        Ok(Apploader {
+            date: String::from_utf8_lossy(&header.date[..date_len]).into_owned(),
+            entry_point: header.entry_point.get(),
+            code: buf[code_start..code_end].to_vec(),
+            trailer: buf[code_end..trailer_end].to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut date = [0; 0x10];
+        ensure!(
+            self.date.len() < date.len(),
+            "Apploader date '{}' is too long",
+            self.date
+        );
+        date[..self.date.len()].copy_from_slice(self.date.as_bytes());
+        let header = ApploaderHeader {
+            date,
+            entry_point: self.entry_point.into(),
+            size: u32::try_from(self.code.len())
+                .context("Apploader code is too large")?
+                .into(),
+            trailer_size: u32::try_from(self.trailer.len())
+                .context("Apploader trailer is too large")?
+                .into(),
+            unused: [0; 4],
+        };
+
+        let mut buf = header.as_bytes().to_vec();
+        buf.extend_from_slice(&self.code);
+        buf.extend_from_slice(&self.trailer);
+        Ok(buf)
+    }
+
+    /// Address range the code and trailer occupy once loaded
+    pub fn load_range(&self) -> (u32, u32) {
+        let size = (self.code.len() + self.trailer.len()) as u32;
+        (
+            APPLOADER_LOAD_ADDRESS,
+            APPLOADER_LOAD_ADDRESS.saturating_add(size),
+        )
+    }
+
+    /// Check for problems that would stop the IPL from running the apploader
+    pub fn validate(&self) -> Vec<DolIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |severity, message| issues.push(DolIssue { severity, message });
+
+        let code_end = APPLOADER_LOAD_ADDRESS.saturating_add(self.code.len() as u32);
+        if !(APPLOADER_LOAD_ADDRESS..code_end).contains(&self.entry_point) {
+            issue(
+                Severity::Error,
+                format!(
+                    "Entry point {:#010x} is outside the loaded code ({APPLOADER_LOAD_ADDRESS:#010x}..{code_end:#010x})",
+                    self.entry_point
+                ),
+            );
+        } else if !self.entry_point.is_multiple_of(4) {
+            issue(
+                Severity::Error,
+                format!("Entry point {:#010x} is not word aligned", self.entry_point),
+            );
+        }
+
+        let (_, load_end) = self.load_range();
+        if load_end > MEM1_END {
+            issue(
+                Severity::Error,
+                format!("Apploader loads past the end of MEM1 (ends at {load_end:#010x})"),
+            );
+        }
+
+        let date = self.date.as_bytes();
+        let date_ok = date.len() == 10
+            && date.iter().enumerate().all(|(i, &c)| match i {
+                4 | 7 => c == b'/',
+                _ => c.is_ascii_digit(),
+            });
+        if !date_ok {
+            issue(
+                Severity::Warning,
+                format!("Date '{}' is not in YYYY/MM/DD form", self.date),
+            );
+        }
+
+        issues.sort_by_key(|i| std::cmp::Reverse(i.severity));
+        issues
+    }
+}
@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use zerocopy::IntoBytes;
+
+use super::apploader::Apploader;
+use super::fst::{self, Fst, FstEntry, FstEntryKind};
+use super::header::{DiskHeader, APPLOADER_OFFSET, BI2_OFFSET, BI2_SIZE, BOOT_SIZE, DISC_SIZE};
+use super::{SystemFile, SYSTEM_DIR_NAME, SYSTEM_FILE_ALIGNMENT};
+use crate::dol::DolHeader;
+use crate::formats::Format;
+use crate::symbol_map::parse_int;
+
+#[derive(Debug, Clone)]
+pub struct IsoBuildOptions {
+    /// Alignment of each file's data on disc, unless overridden by a rule
+    pub alignment: u32,
+    /// Per-file alignment overrides; the first matching rule wins
+    pub alignment_rules: Vec<AlignmentRule>,
+}
+
+impl Default for IsoBuildOptions {
+    fn default() -> Self {
+        IsoBuildOptions {
+            alignment: 0x800,
+            alignment_rules: Vec::new(),
+        }
+    }
+}
+
+impl IsoBuildOptions {
+    /// Alignment for the file at disc path `path` (e.g. `/audio/bgm.adp`)
+    pub fn alignment_for(&self, path: &str) -> u32 {
+        self.alignment_rules
+            .iter()
+            .find(|rule| rule.pattern.matches(path))
+            .map_or(self.alignment, |rule| rule.alignment)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlignmentPattern {
+    /// `*.ext`: files with the given extension
+    Extension(String),
+    /// `/dir/`: all files under a directory
+    Directory(String),
+    /// `/dir/file`: a single file
+    File(String),
+}
+
+impl AlignmentPattern {
+    /// Matching is case-insensitive, like FST lookups
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.to_ascii_lowercase();
+        match self {
+            AlignmentPattern::Extension(ext) => path
+                .rsplit_once('/')
+                .map_or(path.as_str(), |(_, name)| name)
+                .rsplit_once('.')
+                .is_some_and(|(_, e)| e == ext),
+            AlignmentPattern::Directory(dir) => path.starts_with(dir.as_str()),
+            AlignmentPattern::File(file) => path == *file,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentRule {
+    pub pattern: AlignmentPattern,
+    pub alignment: u32,
+}
+
+fn ensure_valid_alignment(alignment: u32) -> anyhow::Result<()> {
+    ensure!(
+        alignment >= 4 && alignment.is_power_of_two(),
+        "File alignment must be a power of two of at least 4"
+    );
+    Ok(())
+}
+
+/// Parse alignment rules, one `<pattern> <alignment>` per line. Patterns are `*.ext`, a directory
+/// ending in `/`, or a file path. Lines starting with `#` are comments.
+pub fn parse_alignment_rules(buf: &[u8]) -> anyhow::Result<Vec<AlignmentRule>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse alignment rules as UTF-8")?;
+    let mut rules = Vec::new();
+
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [pattern, alignment] = fields[..] else {
+            bail!("Invalid alignment rule on line {}: {}", line_num + 1, line);
+        };
+        let alignment = parse_int(alignment)
+            .with_context(|| format!("Invalid alignment on line {}", line_num + 1))?;
+        ensure_valid_alignment(alignment)
+            .with_context(|| format!("Invalid alignment on line {}", line_num + 1))?;
+
+        let pattern = pattern.to_ascii_lowercase();
+        let pattern = if let Some(ext) = pattern.strip_prefix("*.") {
+            AlignmentPattern::Extension(ext.to_string())
+        } else {
+            let path = format!("/{}", pattern.trim_start_matches('/'));
+            if path.ends_with('/') {
+                AlignmentPattern::Directory(path)
+            } else {
+                AlignmentPattern::File(path)
+            }
+        };
+        rules.push(AlignmentRule { pattern, alignment });
+    }
+
+    Ok(rules)
+}
+
+struct SourceFile {
+    fst_index: usize,
+    path: PathBuf,
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))
+}
+
+fn sorted_dir_entries(dir: &Path) -> anyhow::Result<Vec<std::fs::DirEntry>> {
+    let mut children = std::fs::read_dir(dir)
+        .with_context(|| format!("cannot read directory {}", dir.to_string_lossy()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    // The FST is conventionally sorted case-insensitively
+    children.sort_by_key(|c| {
+        let name = c.file_name().to_string_lossy().into_owned();
+        (name.to_ascii_lowercase(), name)
+    });
+    Ok(children)
+}
+
+fn scan_dir(
+    dir: &Path,
+    parent: u32,
+    is_root: bool,
+    fst: &mut Fst,
+    files: &mut Vec<SourceFile>,
+) -> anyhow::Result<()> {
+    for child in sorted_dir_entries(dir)? {
+        let name = child.file_name().to_string_lossy().into_owned();
+        if is_root && name == SYSTEM_DIR_NAME {
+            continue;
+        }
+        // Extraction decodes Shift-JIS names, so they're encoded back for the game to find
+        let name = fst::encode_name(&name)
+            .with_context(|| format!("cannot add {}", child.path().to_string_lossy()))?;
+        let file_type = child.file_type()?;
+        if file_type.is_dir() {
+            let index = fst.entries.len();
+            fst.entries.push(FstEntry {
+                name,
+                kind: FstEntryKind::Directory { parent, next: 0 },
+            });
+            scan_dir(&child.path(), index as u32, false, fst, files)?;
+            let end = fst.entries.len() as u32;
+            if let FstEntryKind::Directory { next, .. } = &mut fst.entries[index].kind {
+                *next = end;
+            }
+        } else {
+            let size = child.metadata()?.len();
+            ensure!(
+                size <= u32::MAX as u64,
+                "{} is too large for a disc image",
+                child.path().to_string_lossy()
+            );
+            files.push(SourceFile {
+                fst_index: fst.entries.len(),
+                path: child.path(),
+            });
+            fst.entries.push(FstEntry {
+                name,
+                kind: FstEntryKind::File {
+                    offset: 0,
+                    size: size as u32,
+                },
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Build an FST for the directory tree at `root`. File offsets are left as zero.
+fn scan_root(root: &Path) -> anyhow::Result<(Fst, Vec<SourceFile>)> {
+    let mut fst = Fst {
+        entries: vec![FstEntry {
+            name: Vec::new(),
+            kind: FstEntryKind::Directory { parent: 0, next: 0 },
+        }],
+    };
+    let mut files = Vec::new();
+    scan_dir(root, 0, true, &mut fst, &mut files)?;
+    let count = fst.entries.len() as u32;
+    fst.entries[0].kind = FstEntryKind::Directory {
+        parent: 0,
+        next: count,
+    };
+    Ok((fst, files))
+}
+
+fn write_padding<W: Write>(writer: &mut W, position: &mut u64, target: u64) -> io::Result<()> {
+    io::copy(&mut io::repeat(0).take(target - *position), writer)?;
+    *position = target;
+    Ok(())
+}
+
+/// Build a disc image from a file system directory and a directory of system files
+pub fn build_iso<W: Write>(
+    root: &Path,
+    sys_dir: &Path,
+    options: &IsoBuildOptions,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    ensure_valid_alignment(options.alignment)?;
+    for rule in &options.alignment_rules {
+        ensure_valid_alignment(rule.alignment)?;
+    }
+
+    let sys_file = |file: SystemFile| read_file(&sys_dir.join(file.file_name()));
+    let boot = sys_file(SystemFile::Boot)?;
+    let bi2 = sys_file(SystemFile::Bi2)?;
+    let apploader = sys_file(SystemFile::Apploader)?;
+    let dol = sys_file(SystemFile::MainDol)?;
+
+    let mut header = DiskHeader::parse(&boot).context("Invalid boot.bin")?;
+    ensure!(bi2.len() == BI2_SIZE, "bi2.bin must be {BI2_SIZE:#x} bytes");
+    Apploader::parse(&apploader).context("Invalid apploader.img")?;
+    DolHeader::parse(&dol).context("Invalid main.dol")?;
+
+    let (mut fst, files) = scan_root(root)?;
+
+    // Lay out system files, then file data
+    let dol_offset =
+        (APPLOADER_OFFSET + apploader.len() as u64).next_multiple_of(SYSTEM_FILE_ALIGNMENT);
+    let fst_offset = (dol_offset + dol.len() as u64).next_multiple_of(SYSTEM_FILE_ALIGNMENT);
+    let fst_size = fst.to_bytes().len() as u64;
+    let paths = fst.paths();
+    let mut cursor = fst_offset + fst_size;
+    for file in &files {
+        let alignment = options.alignment_for(&paths[file.fst_index]);
+        let FstEntryKind::File { offset, size } = &mut fst.entries[file.fst_index].kind else {
+            unreachable!();
+        };
+        let file_offset = cursor.next_multiple_of(alignment as u64);
+        cursor = file_offset + *size as u64;
+        if cursor > DISC_SIZE {
+            bail!(
+                "Files do not fit on a disc ({} would end at {cursor:#x})",
+                file.path.to_string_lossy()
+            );
+        }
+        *offset = file_offset as u32;
+    }
+    let fst_bytes = fst.to_bytes();
+
+    header.dol_offset = (dol_offset as u32).into();
+    header.fst_offset = (fst_offset as u32).into();
+    header.fst_size = (fst_size as u32).into();
+    header.fst_max_size = (fst_size as u32).max(header.fst_max_size.get()).into();
+
+    let mut position = 0;
+    writer.write_all(header.as_bytes())?;
+    position += BOOT_SIZE as u64;
+    write_padding(writer, &mut position, BI2_OFFSET)?;
+    writer.write_all(&bi2)?;
+    position += bi2.len() as u64;
+    write_padding(writer, &mut position, APPLOADER_OFFSET)?;
+    writer.write_all(&apploader)?;
+    position += apploader.len() as u64;
+    write_padding(writer, &mut position, dol_offset)?;
+    writer.write_all(&dol)?;
+    position += dol.len() as u64;
+    write_padding(writer, &mut position, fst_offset)?;
+    writer.write_all(&fst_bytes)?;
+    position += fst_bytes.len() as u64;
+
+    for file in &files {
+        let FstEntryKind::File { offset, size } = fst.entries[file.fst_index].kind else {
+            unreachable!();
+        };
+        write_padding(writer, &mut position, offset as u64)?;
+        let mut source = File::open(&file.path)
+            .with_context(|| format!("cannot read {}", file.path.to_string_lossy()))?;
+        let copied = io::copy(&mut source, writer)?;
+        ensure!(
+            copied == size as u64,
+            "{} changed size during the build",
+            file.path.to_string_lossy()
+        );
+        position += copied;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use anyhow::{bail, ensure};
+use flate2::read::ZlibDecoder;
+use zerocopy::{little_endian, FromBytes, Immutable, KnownLayout};
+
+use super::header::DISC_SIZE;
+use super::tgc::TGC_MAGIC;
+
+pub const CISO_MAGIC: [u8; 4] = *b"CISO";
+pub const GCZ_MAGIC: u32 = 0xB10BC001;
+
+const CISO_HEADER_SIZE: u64 = 0x8000;
+const GCZ_UNCOMPRESSED_FLAG: u64 = 1 << 63;
+/// Far above the block sizes tools write (CISO's 2 MiB, GCZ's 16 KiB), but small enough to cache
+const MAX_BLOCK_SIZE: u64 = 0x100_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Iso,
+    Ciso,
+    Gcz,
+    Tgc,
+}
+
+/// Identify a disc image format from its first bytes
+pub fn detect_format(magic: &[u8; 4]) -> ImageFormat {
+    if *magic == CISO_MAGIC {
+        ImageFormat::Ciso
+    } else if u32::from_le_bytes(*magic) == GCZ_MAGIC {
+        ImageFormat::Gcz
+    } else if u32::from_be_bytes(*magic) == TGC_MAGIC {
+        ImageFormat::Tgc
+    } else {
+        ImageFormat::Iso
+    }
+}
+
+/// Check a block size read from an image's header before a block of it is allocated
+fn check_block_size(format: &str, block_size: u64) -> anyhow::Result<()> {
+    ensure!(
+        block_size.is_power_of_two() && block_size <= MAX_BLOCK_SIZE,
+        "{format} block size {block_size:#x} isn't a power of two up to {MAX_BLOCK_SIZE:#x}"
+    );
+    Ok(())
+}
+
+/// A compressed image format made of fixed-size blocks
+pub trait BlockSource {
+    fn block_size(&self) -> u64;
+    fn size(&self) -> u64;
+    fn read_block(&mut self, index: u64, buf: &mut [u8]) -> io::Result<()>;
+}
+
+/// Presents a block-based image as a flat disc image, caching the most recently read block
+pub struct BlockReader<S> {
+    source: S,
+    position: u64,
+    cached_block: Option<u64>,
+    cache: Vec<u8>,
+}
+
+impl<S: BlockSource> BlockReader<S> {
+    fn new(source: S) -> Self {
+        let cache = vec![0; source.block_size() as usize];
+        BlockReader {
+            source,
+            position: 0,
+            cached_block: None,
+            cache,
+        }
+    }
+}
+
+impl<S: BlockSource> Read for BlockReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.source.size() || buf.is_empty() {
+            return Ok(0);
+        }
+        let block_size = self.source.block_size();
+        let block = self.position / block_size;
+        if self.cached_block != Some(block) {
+            // Invalidate first in case the read fails part way through
+            self.cached_block = None;
+            self.source.read_block(block, &mut self.cache)?;
+            self.cached_block = Some(block);
+        }
+
+        let start = (self.position % block_size) as usize;
+        let len = buf
+            .len()
+            .min(self.cache.len() - start)
+            .min((self.source.size() - self.position) as usize);
+        buf[..len].copy_from_slice(&self.cache[start..start + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<S: BlockSource> Seek for BlockReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.source.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// CISO: a header with one presence byte per block, followed by the present blocks in order
+pub struct Ciso<R> {
+    inner: R,
+    block_size: u64,
+    /// Index into the stored blocks for each present block
+    block_map: Vec<Option<u64>>,
+}
+
+impl<R: Read + Seek> Ciso<R> {
+    pub fn new(mut inner: R) -> anyhow::Result<BlockReader<Self>> {
+        let mut header = vec![0; CISO_HEADER_SIZE as usize];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header)?;
+        ensure!(header[0..4] == CISO_MAGIC, "Not a CISO image");
+        let block_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+        check_block_size("CISO", block_size)?;
+
+        let mut stored = 0;
+        let mut block_map: Vec<Option<u64>> = header[8..]
+            .iter()
+            .map(|&present| {
+                (present != 0).then(|| {
+                    stored += 1;
+                    stored - 1
+                })
+            })
+            .collect();
+        // Trailing absent blocks are implicit zeros up to the size of a full disc
+        while block_map.last() == Some(&None) {
+            block_map.pop();
+        }
+
+        Ok(BlockReader::new(Ciso {
+            inner,
+            block_size,
+            block_map,
+        }))
+    }
+}
+
+impl<R: Read + Seek> BlockSource for Ciso<R> {
+    fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    fn size(&self) -> u64 {
+        (self.block_map.len() as u64 * self.block_size).max(DISC_SIZE)
+    }
+
+    fn read_block(&mut self, index: u64, buf: &mut [u8]) -> io::Result<()> {
+        match self.block_map.get(index as usize).copied().flatten() {
+            Some(stored) => {
+                self.inner
+                    .seek(SeekFrom::Start(CISO_HEADER_SIZE + stored * self.block_size))?;
+                self.inner.read_exact(buf)
+            }
+            None => {
+                buf.fill(0);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(FromBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct GczHeader {
+    magic: little_endian::U32,
+    sub_type: little_endian::U32,
+    compressed_data_size: little_endian::U64,
+    data_size: little_endian::U64,
+    block_size: little_endian::U32,
+    block_count: little_endian::U32,
+}
+
+/// GCZ: Dolphin's format of individually zlib-compressed blocks
+pub struct Gcz<R> {
+    inner: R,
+    block_size: u64,
+    data_size: u64,
+    compressed_data_size: u64,
+    data_offset: u64,
+    block_pointers: Vec<u64>,
+    compressed: Vec<u8>,
+}
+
+impl<R: Read + Seek> Gcz<R> {
+    pub fn new(mut inner: R) -> anyhow::Result<BlockReader<Self>> {
+        let mut header_buf = [0; size_of::<GczHeader>()];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header_buf)?;
+        let header = GczHeader::read_from_bytes(&header_buf).unwrap();
+        ensure!(header.magic.get() == GCZ_MAGIC, "Not a GCZ image");
+        if header.sub_type.get() != 0 {
+            bail!("GCZ image is not a GameCube disc");
+        }
+        let block_size = header.block_size.get() as u64;
+        check_block_size("GCZ", block_size)?;
+        let block_count = header.block_count.get() as u64;
+        ensure!(
+            block_count * block_size >= header.data_size.get(),
+            "GCZ image has {block_count} blocks of {block_size:#x} bytes, too few for its size"
+        );
+        // Each block has a pointer and a hash, which must fit in the file before allocating them
+        let data_offset = size_of::<GczHeader>() as u64 + block_count * 12;
+        let file_size = inner.seek(SeekFrom::End(0))?;
+        ensure!(
+            data_offset <= file_size,
+            "GCZ block table for {block_count} blocks extends past the end of the file"
+        );
+        inner.seek(SeekFrom::Start(size_of::<GczHeader>() as u64))?;
+
+        let mut pointer_buf = vec![0; block_count as usize * 8];
+        inner.read_exact(&mut pointer_buf)?;
+        let block_pointers = pointer_buf
+            .chunks_exact(8)
+            .map(|p| u64::from_le_bytes(p.try_into().unwrap()))
+            .collect();
+
+        Ok(BlockReader::new(Gcz {
+            inner,
+            block_size,
+            data_size: header.data_size.get(),
+            compressed_data_size: header.compressed_data_size.get(),
+            data_offset,
+            block_pointers,
+            compressed: Vec::new(),
+        }))
+    }
+}
+
+impl<R: Read + Seek> BlockSource for Gcz<R> {
+    fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    fn size(&self) -> u64 {
+        self.data_size
+    }
+
+    fn read_block(&mut self, index: u64, buf: &mut [u8]) -> io::Result<()> {
+        let index = index as usize;
+        let pointer = self.block_pointers[index];
+        let start = pointer & !GCZ_UNCOMPRESSED_FLAG;
+        let end = self
+            .block_pointers
+            .get(index + 1)
+            .map_or(self.compressed_data_size, |p| p & !GCZ_UNCOMPRESSED_FLAG);
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Corrupt GCZ block table");
+        let stored_size = end.checked_sub(start).ok_or_else(invalid)? as usize;
+
+        self.compressed.resize(stored_size, 0);
+        self.inner.seek(SeekFrom::Start(self.data_offset + start))?;
+        self.inner.read_exact(&mut self.compressed)?;
+
+        if pointer & GCZ_UNCOMPRESSED_FLAG != 0 {
+            let len = stored_size.min(buf.len());
+            buf[..len].copy_from_slice(&self.compressed[..len]);
+            buf[len..].fill(0);
+        } else {
+            // The final block may decompress to less than a full block
+            let mut decoder = ZlibDecoder::new(&self.compressed[..]);
+            let mut filled = 0;
+            while filled < buf.len() {
+                let n = decoder.read(&mut buf[filled..])?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            buf[filled..].fill(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn gcz(block_size: u32, block_count: u32, data_size: u64, blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut pointers = Vec::new();
+        let mut data = Vec::new();
+        for block in blocks {
+            pointers.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            data.extend_from_slice(block);
+        }
+        let mut gcz = Vec::new();
+        gcz.extend_from_slice(&GCZ_MAGIC.to_le_bytes());
+        gcz.extend_from_slice(&0u32.to_le_bytes());
+        gcz.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        gcz.extend_from_slice(&data_size.to_le_bytes());
+        gcz.extend_from_slice(&block_size.to_le_bytes());
+        gcz.extend_from_slice(&block_count.to_le_bytes());
+        gcz.extend_from_slice(&pointers);
+        gcz.resize(gcz.len() + blocks.len() * 4, 0);
+        gcz.extend_from_slice(&data);
+        gcz
+    }
+
+    #[test]
+    fn gcz_reads_blocks() {
+        let original: Vec<u8> = (0..0x800u32).map(|i| (i * 7) as u8).collect();
+        let blocks: Vec<Vec<u8>> = original
+            .chunks(0x400)
+            .map(|chunk| {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(chunk).unwrap();
+                encoder.finish().unwrap()
+            })
+            .collect();
+        let image = gcz(0x400, 2, 0x800, &blocks);
+        let mut reader = Gcz::new(Cursor::new(image)).unwrap();
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, original);
+    }
+
+    #[test]
+    fn gcz_rejects_bad_headers() {
+        // Zero block size, which would divide by zero
+        assert!(Gcz::new(Cursor::new(gcz(0, 1, 0, &[vec![0; 4]]))).is_err());
+        // More blocks than the file could hold pointers for
+        assert!(Gcz::new(Cursor::new(gcz(0x4000, u32::MAX, 0, &[]))).is_err());
+        // Too few blocks for the size
+        assert!(Gcz::new(Cursor::new(gcz(0x400, 1, 0x800, &[vec![0; 4]]))).is_err());
+        // Block sizes that aren't a power of two, or too large to allocate
+        assert!(Gcz::new(Cursor::new(gcz(0x600, 2, 0x800, &[vec![0; 4], vec![0; 4]]))).is_err());
+        assert!(Gcz::new(Cursor::new(gcz(0x8000_0000, 1, 0x800, &[vec![0; 4]]))).is_err());
+    }
+
+    #[test]
+    fn ciso_rejects_bad_block_sizes() {
+        for block_size in [0u32, 0x1234, 0x8000_0000] {
+            let mut image = vec![0; CISO_HEADER_SIZE as usize];
+            image[..4].copy_from_slice(&CISO_MAGIC);
+            image[4..8].copy_from_slice(&block_size.to_le_bytes());
+            assert!(Ciso::new(Cursor::new(image)).is_err());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{anyhow, ensure};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Size of a single entry in fst.bin
+pub const ENTRY_SIZE: usize = size_of::<RawFstEntry>();
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawFstEntry {
+    /// High byte is the directory flag, low 24 bits the name offset
+    flags_name_offset: big_endian::U32,
+    /// File offset, or parent index for directories
+    offset_or_parent: big_endian::U32,
+    /// File size, or index one past the last child for directories
+    size_or_next: big_endian::U32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FstEntryKind {
+    File { offset: u32, size: u32 },
+    Directory { parent: u32, next: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct FstEntry {
+    /// The name's bytes as stored on disc, kept as is so rebuilding the FST doesn't change them.
+    /// Japanese discs use Shift-JIS.
+    pub name: Vec<u8>,
+    pub kind: FstEntryKind,
+}
+
+/// A name from a disc or archive as text: UTF-8 if it's valid, which covers ASCII, and otherwise
+/// Shift-JIS
+pub fn decode_name(name: &[u8]) -> String {
+    match std::str::from_utf8(name) {
+        Ok(name) => name.to_string(),
+        Err(_) => encoding_rs::SHIFT_JIS
+            .decode_without_bom_handling(name)
+            .0
+            .into_owned(),
+    }
+}
+
+/// A name as stored on disc, the reverse of [`decode_name`] for Shift-JIS names: ASCII is kept as
+/// is, and anything else is encoded as Shift-JIS, as Japanese discs and archives store it
+pub fn encode_name(name: &str) -> anyhow::Result<Vec<u8>> {
+    if name.is_ascii() {
+        return Ok(name.as_bytes().to_vec());
+    }
+    let (bytes, _, unmappable) = encoding_rs::SHIFT_JIS.encode(name);
+    ensure!(!unmappable, "Name '{name}' can't be stored in Shift-JIS");
+    Ok(bytes.into_owned())
+}
+
+impl FstEntry {
+    /// The name as text, as decoded by [`decode_name`]
+    pub fn display_name(&self) -> String {
+        decode_name(&self.name)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, FstEntryKind::Directory { .. })
+    }
+}
+
+/// The disc file system table. Entry 0 is the root directory.
+#[derive(Debug, Clone)]
+pub struct Fst {
+    pub entries: Vec<FstEntry>,
+}
+
+fn read_name(strings: &[u8], offset: usize) -> anyhow::Result<Vec<u8>> {
+    let tail = strings
+        .get(offset..)
+        .ok_or_else(|| anyhow!("FST name offset {offset:#x} out of bounds"))?;
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    Ok(tail[..end].to_vec())
+}
+
+impl Fst {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Fst> {
+        let (root, _) = RawFstEntry::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("FST is too small to contain a root entry"))?;
+        let count = root.size_or_next.get() as usize;
+        let (raw_entries, strings) = <[RawFstEntry]>::ref_from_prefix_with_elems(buf, count)
+            .map_err(|_| anyhow!("FST is too small for {count} entries"))?;
+
+        let mut entries = Vec::with_capacity(count);
+        for (i, raw) in raw_entries.iter().enumerate() {
+            let flags_name_offset = raw.flags_name_offset.get();
+            let name = if i == 0 {
+                Vec::new()
+            } else {
+                read_name(strings, (flags_name_offset & 0xFFFFFF) as usize)?
+            };
+            let kind = if flags_name_offset >> 24 != 0 {
+                let next = raw.size_or_next.get();
+                ensure!(
+                    next as usize <= count && next as usize > i,
+                    "FST directory entry {i} has invalid end index {next}"
+                );
+                FstEntryKind::Directory {
+                    parent: raw.offset_or_parent.get(),
+                    next,
+                }
+            } else {
+                FstEntryKind::File {
+                    offset: raw.offset_or_parent.get(),
+                    size: raw.size_or_next.get(),
+                }
+            };
+            entries.push(FstEntry { name, kind });
+        }
+
+        Ok(Fst { entries })
+    }
+
+    /// Full paths of every entry, e.g. `/audio/bgm.dsp`. The root's path is `/`. Names are
+    /// decoded as by [`FstEntry::display_name`].
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths = Vec::with_capacity(self.entries.len());
+        // Stack of (path, next index) for the directories containing the current entry
+        let mut dirs: Vec<(String, u32)> = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            while dirs.last().is_some_and(|(_, next)| *next as usize <= i) {
+                dirs.pop();
+            }
+            let path = match dirs.last() {
+                Some((parent, _)) if parent == "/" => format!("/{}", entry.display_name()),
+                Some((parent, _)) => format!("{parent}/{}", entry.display_name()),
+                None => "/".to_string(),
+            };
+            if let FstEntryKind::Directory { next, .. } = entry.kind {
+                dirs.push((path.clone(), next));
+            }
+            paths.push(path);
+        }
+        paths
+    }
+
+    /// Find an entry by path, ignoring case and leading/trailing slashes
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let path = format!("/{}", path.trim_matches('/'));
+        self.paths()
+            .iter()
+            .position(|p| p.eq_ignore_ascii_case(&path))
+    }
+
+    /// Indices of an entry and all of its descendants
+    pub fn subtree(&self, index: usize) -> std::ops::Range<usize> {
+        match self.entries[index].kind {
+            FstEntryKind::Directory { next, .. } => index..next as usize,
+            FstEntryKind::File { .. } => index..index + 1,
+        }
+    }
+}
+
+impl Fst {
+    /// Serialize the entries followed by the name string table
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.serialize(false)
+    }
+
+    /// Serialize with the root's (usually empty) name stored in the string table like any other
+    /// entry's, as U8 archives do
+    pub fn to_bytes_with_root_name(&self) -> Vec<u8> {
+        self.serialize(true)
+    }
+
+    fn serialize(&self, root_name: bool) -> Vec<u8> {
+        let mut entries = Vec::with_capacity(self.entries.len() * size_of::<RawFstEntry>());
+        let mut strings = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let name_offset = if i == 0 && !root_name {
+                0
+            } else {
+                let offset = strings.len() as u32;
+                strings.extend_from_slice(&entry.name);
+                strings.push(0);
+                offset
+            };
+            let raw = match entry.kind {
+                FstEntryKind::File { offset, size } => RawFstEntry {
+                    flags_name_offset: name_offset.into(),
+                    offset_or_parent: offset.into(),
+                    size_or_next: size.into(),
+                },
+                FstEntryKind::Directory { parent, next } => RawFstEntry {
+                    flags_name_offset: (0x01000000 | name_offset).into(),
+                    offset_or_parent: parent.into(),
+                    size_or_next: next.into(),
+                },
+            };
+            entries.extend_from_slice(raw.as_bytes());
+        }
+        entries.extend_from_slice(&strings);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_jis_names_round_trip() {
+        // マリオ.bin in Shift-JIS, which isn't valid UTF-8
+        let name = b"\x83\x7d\x83\x8a\x83\x49.bin".to_vec();
+        let fst = Fst {
+            entries: vec![
+                FstEntry {
+                    name: Vec::new(),
+                    kind: FstEntryKind::Directory { parent: 0, next: 2 },
+                },
+                FstEntry {
+                    name: name.clone(),
+                    kind: FstEntryKind::File {
+                        offset: 0x8000,
+                        size: 4,
+                    },
+                },
+            ],
+        };
+        let bytes = fst.to_bytes();
+        let parsed = Fst::parse(&bytes).unwrap();
+        assert_eq!(parsed.entries[1].name, name);
+        assert_eq!(parsed.to_bytes(), bytes);
+        assert_eq!(parsed.paths()[1], "/マリオ.bin");
+        assert_eq!(parsed.find("マリオ.bin"), Some(1));
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{anyhow, bail, ensure};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+pub const GC_MAGIC: u32 = 0xC2339F3D;
+
+pub const BOOT_OFFSET: u64 = 0;
+pub const BOOT_SIZE: usize = 0x440;
+pub const BI2_OFFSET: u64 = 0x440;
+pub const BI2_SIZE: usize = 0x2000;
+pub const APPLOADER_OFFSET: u64 = 0x2440;
+
+/// Size of a full GameCube disc
+pub const DISC_SIZE: u64 = 1_459_978_240;
+
+/// The disc header stored in boot.bin
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct DiskHeader {
+    pub game_code: [u8; 4],
+    pub maker_code: [u8; 2],
+    pub disc_number: u8,
+    pub version: u8,
+    pub audio_streaming: u8,
+    pub stream_buffer_size: u8,
+    pub unused0: [u8; 0x0E],
+    pub wii_magic: big_endian::U32,
+    pub gc_magic: big_endian::U32,
+    pub game_name: [u8; 0x3E0],
+    pub debug_monitor_offset: big_endian::U32,
+    pub debug_monitor_address: big_endian::U32,
+    pub unused1: [u8; 0x18],
+    pub dol_offset: big_endian::U32,
+    pub fst_offset: big_endian::U32,
+    pub fst_size: big_endian::U32,
+    pub fst_max_size: big_endian::U32,
+    pub user_position: big_endian::U32,
+    pub user_length: big_endian::U32,
+    pub unknown: big_endian::U32,
+    pub unused2: [u8; 4],
+}
+
+/// The disc's second header, bi2.bin, read by the IPL and the SDK's boot code
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct Bi2 {
+    pub debug_monitor_size: big_endian::U32,
+    pub simulated_memory_size: big_endian::U32,
+    pub argument_offset: big_endian::U32,
+    pub debug_flag: big_endian::U32,
+    pub track_location: big_endian::U32,
+    pub track_size: big_endian::U32,
+    /// Region the console must be to boot the disc; see [`Country`]
+    pub country_code: big_endian::U32,
+    pub unknown0: big_endian::U32,
+    pub long_file_names: big_endian::U32,
+    pub unknown1: big_endian::U32,
+    pub dol_limit: big_endian::U32,
+    pub unused: [u8; BI2_SIZE - 0x2C],
+}
+
+/// The regions in bi2.bin's country code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Country {
+    Japan,
+    Usa,
+    Pal,
+    Korea,
+}
+
+impl Country {
+    pub const ALL: [Country; 4] = [Country::Japan, Country::Usa, Country::Pal, Country::Korea];
+
+    pub fn code(self) -> u32 {
+        match self {
+            Country::Japan => 0,
+            Country::Usa => 1,
+            Country::Pal => 2,
+            Country::Korea => 4,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Country> {
+        Country::ALL.into_iter().find(|c| c.code() == code)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Country::Japan => "jp",
+            Country::Usa => "us",
+            Country::Pal => "eu",
+            Country::Korea => "kr",
+        }
+    }
+
+    /// Parse a region name as `name` prints it, or a raw country code
+    pub fn parse(s: &str) -> anyhow::Result<u32> {
+        if let Some(country) = Country::ALL
+            .into_iter()
+            .find(|c| c.name().eq_ignore_ascii_case(s))
+        {
+            return Ok(country.code());
+        }
+        s.parse()
+            .map_err(|_| anyhow!("invalid country {s}; expected jp, us, eu, kr, or a number"))
+    }
+}
+
+/// Changes to make to boot.bin and bi2.bin, each left alone if `None`
+#[derive(Debug, Clone, Default)]
+pub struct HeaderEdit {
+    /// Six character game id: the four character game code and two character maker code
+    pub game_id: Option<String>,
+    pub maker_code: Option<String>,
+    /// Zero-based index of the disc in a multi-disc game
+    pub disc_number: Option<u8>,
+    pub version: Option<u8>,
+    pub audio_streaming: Option<bool>,
+    pub stream_buffer_size: Option<u8>,
+    pub game_name: Option<String>,
+    pub country_code: Option<u32>,
+    pub debug_flag: Option<u32>,
+}
+
+impl HeaderEdit {
+    pub fn is_empty(&self) -> bool {
+        self.game_id.is_none()
+            && self.maker_code.is_none()
+            && self.disc_number.is_none()
+            && self.version.is_none()
+            && self.audio_streaming.is_none()
+            && self.stream_buffer_size.is_none()
+            && self.game_name.is_none()
+            && self.country_code.is_none()
+            && self.debug_flag.is_none()
+    }
+
+    pub fn apply(&self, header: &mut DiskHeader, bi2: &mut Bi2) -> anyhow::Result<()> {
+        if let Some(id) = &self.game_id {
+            ensure!(
+                id.len() == 6 && id.is_ascii(),
+                "Game id '{id}' must be 6 ASCII characters"
+            );
+            header.game_code.copy_from_slice(&id.as_bytes()[..4]);
+            header.maker_code.copy_from_slice(&id.as_bytes()[4..]);
+        }
+        if let Some(maker) = &self.maker_code {
+            ensure!(
+                maker.len() == 2 && maker.is_ascii(),
+                "Maker code '{maker}' must be 2 ASCII characters"
+            );
+            header.maker_code.copy_from_slice(maker.as_bytes());
+        }
+        if let Some(disc_number) = self.disc_number {
+            header.disc_number = disc_number;
+        }
+        if let Some(version) = self.version {
+            header.version = version;
+        }
+        if let Some(audio_streaming) = self.audio_streaming {
+            header.audio_streaming = audio_streaming.into();
+        }
+        if let Some(size) = self.stream_buffer_size {
+            header.stream_buffer_size = size;
+        }
+        if let Some(name) = &self.game_name {
+            // Keep a null terminator
+            if name.len() >= header.game_name.len() {
+                bail!(
+                    "Game name is {} bytes, but must be under {}",
+                    name.len(),
+                    header.game_name.len()
+                );
+            }
+            header.game_name.fill(0);
+            header.game_name[..name.len()].copy_from_slice(name.as_bytes());
+        }
+        if let Some(code) = self.country_code {
+            bi2.country_code = code.into();
+        }
+        if let Some(flag) = self.debug_flag {
+            bi2.debug_flag = flag.into();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct ApploaderHeader {
+    pub date: [u8; 0x10],
+    pub entry_point: big_endian::U32,
+    pub size: big_endian::U32,
+    pub trailer_size: big_endian::U32,
+    pub unused: [u8; 4],
+}
+
+impl DiskHeader {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<DiskHeader> {
+        let (header, _) = DiskHeader::read_from_prefix(buf)
+            .map_err(|_| anyhow!("Disc is too small to contain a header"))?;
+        ensure!(
+            header.gc_magic.get() == GC_MAGIC,
+            "Not a GameCube disc image (bad magic {:#010x})",
+            header.gc_magic.get()
+        );
+        Ok(header)
+    }
+
+    /// The six character game id, e.g. `GALE01`
+    pub fn game_id(&self) -> String {
+        let mut id = self.game_code.to_vec();
+        id.extend_from_slice(&self.maker_code);
+        String::from_utf8_lossy(&id).into_owned()
+    }
+
+    pub fn game_name(&self) -> String {
+        let end = self
+            .game_name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.game_name.len());
+        String::from_utf8_lossy(&self.game_name[..end]).into_owned()
+    }
+}
+
+impl Bi2 {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Bi2> {
+        ensure!(
+            buf.len() == BI2_SIZE,
+            "bi2.bin must be {BI2_SIZE:#x} bytes, not {:#x}",
+            buf.len()
+        );
+        Ok(Bi2::read_from_bytes(buf).unwrap())
+    }
+
+    pub fn country(&self) -> Option<Country> {
+        Country::from_code(self.country_code.get())
+    }
+}
+
+impl ApploaderHeader {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<ApploaderHeader> {
+        ApploaderHeader::read_from_prefix(buf)
+            .map(|(header, _)| header)
+            .map_err(|_| anyhow!("Apploader is too small to contain a header"))
+    }
+
+    /// Size of the whole apploader image, including this header
+    pub fn total_size(&self) -> u32 {
+        size_of::<ApploaderHeader>() as u32 + self.size.get() + self.trailer_size.get()
+    }
+}
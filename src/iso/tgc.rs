@@ -0,0 +1,133 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use anyhow::{anyhow, ensure, Context};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::fst::{Fst, FstEntryKind};
+use super::header::{DiskHeader, BOOT_SIZE};
+
+pub const TGC_MAGIC: u32 = 0xAE0F38A2;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct TgcHeader {
+    magic: big_endian::U32,
+    unknown0: big_endian::U32,
+    header_size: big_endian::U32,
+    disc_header_area_size: big_endian::U32,
+    fst_real_offset: big_endian::U32,
+    fst_size: big_endian::U32,
+    fst_max_size: big_endian::U32,
+    dol_real_offset: big_endian::U32,
+    dol_size: big_endian::U32,
+    file_area_real_offset: big_endian::U32,
+    unknown1: big_endian::U32,
+    banner_offset: big_endian::U32,
+    banner_size: big_endian::U32,
+    file_area_virtual_offset: big_endian::U32,
+}
+
+/// A GCM embedded in a TGC container, presented as a flat disc image.
+///
+/// The embedded GCM starts after the TGC header, but its FST file offsets are relative to a
+/// virtual file area, so a corrected copy of the FST is served in place of the original.
+pub struct Tgc<R> {
+    inner: R,
+    header_size: u64,
+    size: u64,
+    position: u64,
+    fst_offset: u64,
+    fst: Vec<u8>,
+}
+
+impl<R: Read + Seek> Tgc<R> {
+    pub fn new(mut inner: R) -> anyhow::Result<Self> {
+        let mut header_buf = [0; size_of::<TgcHeader>()];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header_buf)?;
+        let header = TgcHeader::read_from_bytes(&header_buf).unwrap();
+        ensure!(header.magic.get() == TGC_MAGIC, "Not a TGC image");
+        let header_size = header.header_size.get() as u64;
+        let size = inner
+            .seek(SeekFrom::End(0))?
+            .checked_sub(header_size)
+            .ok_or_else(|| anyhow!("TGC is smaller than its header"))?;
+
+        let mut boot = vec![0; BOOT_SIZE];
+        inner.seek(SeekFrom::Start(header_size))?;
+        inner.read_exact(&mut boot)?;
+        let disk_header = DiskHeader::parse(&boot).context("Invalid embedded disc header")?;
+
+        let mut fst_buf = vec![0; header.fst_size.get() as usize];
+        inner.seek(SeekFrom::Start(header.fst_real_offset.get() as u64))?;
+        inner.read_exact(&mut fst_buf)?;
+        let mut fst = Fst::parse(&fst_buf).context("Failed to parse TGC FST")?;
+
+        let shift = header
+            .file_area_real_offset
+            .get()
+            .wrapping_sub(header.file_area_virtual_offset.get())
+            .wrapping_sub(header_size as u32);
+        for entry in &mut fst.entries {
+            if let FstEntryKind::File { offset, .. } = &mut entry.kind {
+                *offset = offset.wrapping_add(shift);
+            }
+        }
+        let mut fst = fst.to_bytes();
+        // Keep the FST the same size as on disc so the disc header stays consistent
+        fst.resize(fst_buf.len().max(fst.len()), 0);
+
+        Ok(Tgc {
+            inner,
+            header_size,
+            size,
+            position: 0,
+            fst_offset: disk_header.fst_offset.get() as u64,
+            fst,
+        })
+    }
+}
+
+impl<R: Read + Seek> Read for Tgc<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size {
+            return Ok(0);
+        }
+        let len = buf.len().min((self.size - self.position) as usize);
+        let fst_end = self.fst_offset + self.fst.len() as u64;
+
+        // Serve reads inside the FST from the corrected copy
+        let n = if (self.fst_offset..fst_end).contains(&self.position) {
+            let start = (self.position - self.fst_offset) as usize;
+            let n = len.min(self.fst.len() - start);
+            buf[..n].copy_from_slice(&self.fst[start..start + n]);
+            n
+        } else {
+            // Stop at the start of the FST so it's handled by the next read
+            let len = if self.position < self.fst_offset {
+                len.min((self.fst_offset - self.position) as usize)
+            } else {
+                len
+            };
+            self.inner
+                .seek(SeekFrom::Start(self.position + self.header_size))?;
+            self.inner.read(&mut buf[..len])?
+        };
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Tgc<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative position")
+        })?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, Write};
+
+use anyhow::ensure;
+use zerocopy::IntoBytes;
+
+use super::fst::FstEntryKind;
+use super::header::{APPLOADER_OFFSET, BOOT_SIZE};
+use super::{Iso, SystemFile, SYSTEM_FILE_ALIGNMENT};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrimReport {
+    pub original_size: u64,
+    pub trimmed_size: u64,
+}
+
+impl TrimReport {
+    pub fn bytes_saved(&self) -> u64 {
+        self.original_size.saturating_sub(self.trimmed_size)
+    }
+}
+
+fn write_padding<W: Write>(writer: &mut W, position: &mut u64, target: u64) -> io::Result<()> {
+    io::copy(&mut io::repeat(0).take(target - *position), writer)?;
+    *position = target;
+    Ok(())
+}
+
+impl<R: Read + Seek> Iso<R> {
+    pub fn image_size(&mut self) -> anyhow::Result<u64> {
+        Ok(self.reader.seek(io::SeekFrom::End(0))?)
+    }
+
+    /// Offset just past the last byte used by a file or system file
+    pub fn used_size(&mut self) -> anyhow::Result<u64> {
+        Ok(self
+            .used_regions(None, &[])?
+            .iter()
+            .map(|&(_, end)| end)
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Copy the image to `writer`, dropping everything after the last used byte
+    pub fn truncate_to<W: Write>(&mut self, writer: &mut W) -> anyhow::Result<TrimReport> {
+        let original_size = self.image_size()?;
+        let end = self.used_size()?;
+        self.copy_range(0, end, writer)?;
+        Ok(TrimReport {
+            original_size,
+            trimmed_size: end,
+        })
+    }
+
+    /// Copy the image to `writer` with main.dol, fst.bin and all files packed together,
+    /// keeping the files' on-disc order and aligning each to `alignment`
+    pub fn compact_to<W: Write>(
+        &mut self,
+        alignment: u32,
+        writer: &mut W,
+    ) -> anyhow::Result<TrimReport> {
+        ensure!(
+            alignment >= 4 && alignment.is_power_of_two(),
+            "File alignment must be a power of two of at least 4"
+        );
+        let original_size = self.image_size()?;
+
+        let boot_bi2 = self.read_at(0, APPLOADER_OFFSET as usize)?;
+        let apploader = self.read_system_file(SystemFile::Apploader)?;
+        let dol = self.read_system_file(SystemFile::MainDol)?;
+
+        // Files in on-disc order. Entries sharing data keep sharing it.
+        let mut files: Vec<(usize, u32, u32)> = self
+            .fst
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| match e.kind {
+                FstEntryKind::File { offset, size } => Some((i, offset, size)),
+                FstEntryKind::Directory { .. } => None,
+            })
+            .collect();
+        files.sort_by_key(|&(i, offset, _)| (offset, i));
+
+        let mut fst = self.fst.clone();
+        let dol_offset =
+            (APPLOADER_OFFSET + apploader.len() as u64).next_multiple_of(SYSTEM_FILE_ALIGNMENT);
+        let fst_offset = (dol_offset + dol.len() as u64).next_multiple_of(SYSTEM_FILE_ALIGNMENT);
+        let fst_size = fst.to_bytes().len() as u64;
+        let mut cursor = fst_offset + fst_size;
+        let mut placed: HashMap<(u32, u32), u32> = HashMap::new();
+        // (new offset, old offset, size) for each distinct piece of data
+        let mut copies = Vec::new();
+        for &(index, old_offset, size) in &files {
+            let new_offset = *placed.entry((old_offset, size)).or_insert_with(|| {
+                let new_offset = cursor.next_multiple_of(alignment as u64);
+                cursor = new_offset + size as u64;
+                copies.push((new_offset, old_offset, size));
+                new_offset as u32
+            });
+            fst.entries[index].kind = FstEntryKind::File {
+                offset: new_offset,
+                size,
+            };
+        }
+        let fst_bytes = fst.to_bytes();
+
+        let mut header = self.header.clone();
+        header.dol_offset = (dol_offset as u32).into();
+        header.fst_offset = (fst_offset as u32).into();
+        header.fst_size = (fst_size as u32).into();
+        header.fst_max_size = (fst_size as u32).max(header.fst_max_size.get()).into();
+
+        let mut position = 0;
+        writer.write_all(header.as_bytes())?;
+        writer.write_all(&boot_bi2[BOOT_SIZE..])?;
+        position += boot_bi2.len() as u64;
+        writer.write_all(&apploader)?;
+        position += apploader.len() as u64;
+        write_padding(writer, &mut position, dol_offset)?;
+        writer.write_all(&dol)?;
+        position += dol.len() as u64;
+        write_padding(writer, &mut position, fst_offset)?;
+        writer.write_all(&fst_bytes)?;
+        position += fst_bytes.len() as u64;
+        for (new_offset, old_offset, size) in copies {
+            write_padding(writer, &mut position, new_offset)?;
+            self.copy_range(old_offset as u64, size as u64, writer)?;
+            position += size as u64;
+        }
+        writer.flush()?;
+
+        Ok(TrimReport {
+            original_size,
+            trimmed_size: position,
+        })
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt::Display;
+use std::io::{self, Read};
+
+use anyhow::Context;
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksums {
+    pub size: u64,
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Crc32,
+    Md5,
+    Sha1,
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Algorithm::Crc32 => "CRC32",
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha1 => "SHA-1",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Match,
+    Mismatch,
+    /// The checksum file doesn't contain a hash for this algorithm
+    Missing,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl Checksums {
+    pub fn hex(&self, algorithm: Algorithm) -> String {
+        match algorithm {
+            Algorithm::Crc32 => format!("{:08x}", self.crc32),
+            Algorithm::Md5 => hex(&self.md5),
+            Algorithm::Sha1 => hex(&self.sha1),
+        }
+    }
+
+    /// Compare against every hash in a checksum file, as read by `parse_checksum_file`
+    pub fn verify(&self, checksum_file: &str) -> anyhow::Result<Vec<(Algorithm, Verdict)>> {
+        let expected = parse_checksum_file(checksum_file)?;
+        Ok([Algorithm::Crc32, Algorithm::Md5, Algorithm::Sha1]
+            .into_iter()
+            .map(|algorithm| {
+                let actual = self.hex(algorithm);
+                let mut candidates = expected.iter().filter(|(a, _)| *a == algorithm).peekable();
+                let verdict = if candidates.peek().is_none() {
+                    Verdict::Missing
+                } else if candidates.any(|(_, hash)| *hash == actual) {
+                    Verdict::Match
+                } else {
+                    Verdict::Mismatch
+                };
+                (algorithm, verdict)
+            })
+            .collect())
+    }
+}
+
+/// `text` as a lowercase hash if it's `len` hex digits
+fn parse_hash(text: &str, len: usize) -> Option<String> {
+    (text.len() == len && text.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| text.to_ascii_lowercase())
+}
+
+/// Values of every `name="..."` attribute in XML
+fn attributes<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let pattern = format!(" {name}=\"");
+    xml.match_indices(&pattern)
+        .filter_map(|(i, _)| {
+            let value = &xml[i + pattern.len()..];
+            value.find('"').map(|end| &value[..end])
+        })
+        .collect()
+}
+
+/// One line of `md5sum`/`sha1sum` output (`<hash>  <file>`, or `<hash> *<file>` for binary
+/// mode), BSD-style `MD5 (<file>) = <hash>`, an `.sfv` entry (`<file> <crc32>`), or a bare hash
+fn parse_line(line: &str) -> Option<(Algorithm, String)> {
+    for (tag, algorithm, len) in [
+        ("CRC32 (", Algorithm::Crc32, 8),
+        ("MD5 (", Algorithm::Md5, 32),
+        ("SHA1 (", Algorithm::Sha1, 40),
+    ] {
+        if line.starts_with(tag) {
+            let (_, hash) = line.rsplit_once(") = ")?;
+            return Some((algorithm, parse_hash(hash, len)?));
+        }
+    }
+    let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if let Some(hash) = parse_hash(first, 32) {
+        return Some((Algorithm::Md5, hash));
+    }
+    if let Some(hash) = parse_hash(first, 40) {
+        return Some((Algorithm::Sha1, hash));
+    }
+    if rest.is_empty() {
+        return Some((Algorithm::Crc32, parse_hash(first, 8)?));
+    }
+    let (_, last) = line.rsplit_once(char::is_whitespace)?;
+    Some((Algorithm::Crc32, parse_hash(last, 8)?))
+}
+
+/// Read the hashes in a checksum file: a redump `.dat`, whose `<rom>` entries give them as
+/// `crc`, `md5`, and `sha1` attributes, or lines of `md5sum`/`sha1sum` output, an `.sfv`, or
+/// bare hashes. `*sum` hashes are told apart by length, as the tools' outputs are otherwise the
+/// same; `;` and `#` start comments.
+pub fn parse_checksum_file(text: &str) -> anyhow::Result<Vec<(Algorithm, String)>> {
+    let mut hashes = Vec::new();
+    if text.trim_start().starts_with('<') {
+        for (name, algorithm, len) in [
+            ("crc", Algorithm::Crc32, 8),
+            ("md5", Algorithm::Md5, 32),
+            ("sha1", Algorithm::Sha1, 40),
+        ] {
+            for value in attributes(text, name) {
+                let hash = parse_hash(value, len)
+                    .with_context(|| format!("Invalid {algorithm} '{value}' in .dat"))?;
+                hashes.push((algorithm, hash));
+            }
+        }
+        return Ok(hashes);
+    }
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+        let hash =
+            parse_line(line).with_context(|| format!("Line {} isn't a checksum: {line}", i + 1))?;
+        hashes.push(hash);
+    }
+    Ok(hashes)
+}
+
+/// Hash an entire stream with every supported algorithm in a single pass
+pub fn compute<R: Read>(reader: &mut R) -> io::Result<Checksums> {
+    let mut crc32 = crc32fast::Hasher::new();
+    let mut md5 = Md5::new();
+    let mut sha1 = Sha1::new();
+    let mut size = 0;
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        crc32.update(&buf[..n]);
+        md5.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    Ok(Checksums {
+        size,
+        crc32: crc32.finalize(),
+        md5: md5.finalize().into(),
+        sha1: sha1.finalize().into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksums() -> Checksums {
+        compute(&mut &b"hello"[..]).unwrap()
+    }
+
+    const CRC32: &str = "3610a686";
+    const MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+    const SHA1: &str = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+
+    fn verdicts(checksum_file: &str) -> Vec<Verdict> {
+        let results = checksums().verify(checksum_file).unwrap();
+        results.into_iter().map(|(_, verdict)| verdict).collect()
+    }
+
+    #[test]
+    fn known_hashes() {
+        let checksums = checksums();
+        assert_eq!(checksums.hex(Algorithm::Crc32), CRC32);
+        assert_eq!(checksums.hex(Algorithm::Md5), MD5);
+        assert_eq!(checksums.hex(Algorithm::Sha1), SHA1);
+    }
+
+    #[test]
+    fn redump_dat() {
+        // The game's name looks like a CRC32, which mustn't be taken for one
+        let dat = format!(
+            "<?xml version=\"1.0\"?>\n<datafile>\n\t<game name=\"deadbeef\">\n\t\t<rom \
+             name=\"deadbeef.iso\" size=\"5\" crc=\"{CRC32}\" md5=\"{}\" sha1=\"{SHA1}\"/>\n\t\
+             </game>\n</datafile>\n",
+            MD5.to_ascii_uppercase()
+        );
+        assert_eq!(verdicts(&dat), [Verdict::Match; 3]);
+        let dat = dat.replace(CRC32, "00000000");
+        assert_eq!(
+            verdicts(&dat),
+            [Verdict::Mismatch, Verdict::Match, Verdict::Match]
+        );
+    }
+
+    #[test]
+    fn sum_and_sfv_lines() {
+        assert_eq!(
+            verdicts(&format!("{MD5}  game.iso\n")),
+            [Verdict::Missing, Verdict::Match, Verdict::Missing]
+        );
+        assert_eq!(
+            verdicts(&format!("{SHA1} *game.iso\n")),
+            [Verdict::Missing, Verdict::Missing, Verdict::Match]
+        );
+        assert_eq!(
+            verdicts(&format!("MD5 (game.iso) = {MD5}\n")),
+            [Verdict::Missing, Verdict::Match, Verdict::Missing]
+        );
+        assert_eq!(
+            verdicts(&format!("; made by a tool\ngame {MD5}.iso {CRC32}\n")),
+            [Verdict::Match, Verdict::Missing, Verdict::Missing]
+        );
+        assert_eq!(
+            verdicts(&format!("{CRC32}\n{}\n", "0".repeat(40))),
+            [Verdict::Match, Verdict::Missing, Verdict::Mismatch]
+        );
+    }
+
+    #[test]
+    fn unrecognized_lines_are_errors() {
+        assert!(parse_checksum_file("not a checksum\n").is_err());
+        assert!(parse_checksum_file("<rom crc=\"xyz\"/>").is_err());
+    }
+}
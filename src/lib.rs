@@ -1,2 +1,115 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod action_replay;
+#[cfg(all(feature = "compression", feature = "iso"))]
+pub mod archive;
+#[cfg(feature = "audio")]
+pub mod ast;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "iso")]
+pub mod binpatch;
+#[cfg(feature = "image")]
+pub mod bnr;
+#[cfg(feature = "image")]
+pub mod bti;
+#[cfg(feature = "std")]
+pub mod bundle;
+#[cfg(feature = "std")]
+pub mod crash;
+#[cfg(feature = "std")]
+pub mod demangle;
+#[cfg(feature = "std")]
+pub mod depfile;
+#[cfg(feature = "std")]
+pub mod diagnostic;
+#[cfg(feature = "std")]
+pub mod dist;
+#[cfg(feature = "std")]
+pub mod dol;
+#[cfg(feature = "std")]
+pub mod doldiff;
+#[cfg(feature = "std")]
+pub mod dolmap;
+#[cfg(feature = "std")]
+pub mod dolphin_ini;
+#[cfg(feature = "audio")]
+pub mod dsp;
+#[cfg(feature = "std")]
+pub mod elf2map;
+#[cfg(feature = "std")]
 pub mod elf2rel;
+#[cfg(feature = "std")]
+pub mod elf2rso;
+#[cfg(feature = "std")]
+pub mod elfcheck;
+pub mod formats;
+#[cfg(feature = "image")]
+pub mod gcimage;
+#[cfg(feature = "std")]
 pub mod gcipack;
+#[cfg(feature = "network")]
+pub mod gdb;
+#[cfg(feature = "std")]
+pub mod gecko;
+#[cfg(feature = "std")]
+pub mod hook;
+#[cfg(feature = "iso")]
+pub mod iso;
+#[cfg(feature = "std")]
+pub mod listing;
+#[cfg(feature = "compression")]
+mod lz;
+#[cfg(feature = "std")]
+pub mod manifest;
+#[cfg(feature = "std")]
+pub mod mapdiff;
+#[cfg(feature = "std")]
+pub mod mapheader;
+#[cfg(feature = "std")]
+pub mod memcard;
+#[cfg(feature = "std")]
+pub mod objdiff;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod ppc;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "std")]
+pub mod project;
+#[cfg(feature = "std")]
+pub mod rel;
+#[cfg(feature = "std")]
+pub mod rel2map;
+#[cfg(feature = "std")]
+pub mod reldeps;
+#[cfg(feature = "std")]
+pub mod reledit;
+#[cfg(feature = "std")]
+pub mod rellink;
+#[cfg(feature = "std")]
+pub mod relmerge;
+#[cfg(feature = "std")]
+pub mod relstats;
+#[cfg(feature = "std")]
+pub mod safe_path;
+#[cfg(feature = "std")]
+pub mod symbol_map;
+#[cfg(feature = "std")]
+pub mod symbolicate;
+#[cfg(feature = "audio")]
+pub mod thp;
+#[cfg(feature = "image")]
+pub mod tpl;
+#[cfg(feature = "iso")]
+pub mod vcdiff;
+#[cfg(feature = "audio")]
+pub mod wav;
+#[cfg(feature = "network")]
+pub mod wiiload;
+#[cfg(feature = "compression")]
+pub mod yay0;
+#[cfg(feature = "compression")]
+pub mod yaz0;
@@ -0,0 +1,89 @@
+// Disassembly listings of the code around interesting addresses, such as relocation sites and
+// entry points, with notes beside the instructions they're about.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::ppc;
+
+pub struct Listing<'a> {
+    code: &'a [u8],
+    /// Address of the start of `code`
+    base: u32,
+    notes: BTreeMap<u32, Vec<String>>,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(code: &'a [u8], base: u32) -> Listing<'a> {
+        Listing {
+            code,
+            base,
+            notes: BTreeMap::new(),
+        }
+    }
+
+    /// Note something about the instruction at `address`, which the listing will show along with
+    /// the instructions around it. Addresses outside the code are ignored.
+    pub fn note(&mut self, address: u32, note: impl Into<String>) {
+        let offset = address.wrapping_sub(self.base) as usize;
+        if offset < self.code.len() {
+            self.notes
+                .entry(address & !3)
+                .or_default()
+                .push(note.into());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    fn word(&self, address: u32) -> Option<u32> {
+        let offset = address.wrapping_sub(self.base) as usize;
+        let bytes = self.code.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Disassemble `context` instructions either side of each noted address, with `...` between
+    /// ranges that don't meet
+    pub fn render(&self, context: u32) -> String {
+        let end = self.base + (self.code.len() as u32 & !3);
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for &address in self.notes.keys() {
+            let start = address.saturating_sub(context * 4).max(self.base);
+            let stop = address.saturating_add(4 + context * 4).min(end);
+            match ranges.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(stop),
+                _ => ranges.push((start, stop)),
+            }
+        }
+
+        let mut out = String::new();
+        for (i, &(start, stop)) in ranges.iter().enumerate() {
+            if i > 0 {
+                writeln!(out, "  ...").unwrap();
+            }
+            for address in (start..stop).step_by(4) {
+                let Some(word) = self.word(address) else {
+                    break;
+                };
+                let notes = self.notes.get(&address).map(Vec::as_slice).unwrap_or(&[]);
+                let marker = if notes.is_empty() { ' ' } else { '>' };
+                let line = format!(
+                    "{marker} {address:08X}  {word:08X}  {}",
+                    ppc::disassemble(word, address)
+                );
+                match notes.split_first() {
+                    None => writeln!(out, "{line}").unwrap(),
+                    Some((first, rest)) => {
+                        writeln!(out, "{line:<52}; {first}").unwrap();
+                        for note in rest {
+                            writeln!(out, "{:<52}; {note}", "").unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,421 @@
+// LZ77 match finding shared by the Yaz0 and Yay0 encoders. Both formats use a 4 KiB window and
+// matches of 3 to 0x111 bytes.
+
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+pub const MIN_MATCH: usize = 3;
+pub const MAX_MATCH: usize = 0x111;
+pub const WINDOW_SIZE: usize = 0x1000;
+/// Matches at least this long need the extra length byte
+pub const LONG_MATCH: usize = 0x12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Literal(u8),
+    Match { distance: usize, length: usize },
+}
+
+/// Trade-off between encoding speed and compression ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionLevel {
+    /// Greedy matching with a short search
+    Fast,
+    /// Lazy matching, one position of lookahead
+    #[default]
+    Default,
+    /// Optimal parse over the longest match at every position, searching the whole window
+    Best,
+}
+
+impl CompressionLevel {
+    pub fn parse(name: &str) -> anyhow::Result<CompressionLevel> {
+        match name.to_ascii_lowercase().as_str() {
+            "fast" => Ok(CompressionLevel::Fast),
+            "default" => Ok(CompressionLevel::Default),
+            "best" => Ok(CompressionLevel::Best),
+            _ => anyhow::bail!("Unknown compression level '{name}'"),
+        }
+    }
+
+    pub const ALL: [CompressionLevel; 3] = [
+        CompressionLevel::Fast,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ];
+
+    /// Candidates checked per position before settling for the best match so far
+    fn max_chain(&self) -> usize {
+        match self {
+            CompressionLevel::Fast => 16,
+            CompressionLevel::Default => 256,
+            CompressionLevel::Best => WINDOW_SIZE,
+        }
+    }
+}
+
+const HASH_BITS: u32 = 15;
+
+/// Hash chain match finder over a sliding window
+pub struct MatchFinder<'a> {
+    data: &'a [u8],
+    head: Vec<u32>,
+    prev: Vec<u32>,
+    /// Next position to insert into the hash chains
+    inserted: usize,
+    max_chain: usize,
+}
+
+impl<'a> MatchFinder<'a> {
+    pub fn new(data: &'a [u8], level: CompressionLevel) -> Self {
+        MatchFinder {
+            data,
+            head: vec![u32::MAX; 1 << HASH_BITS],
+            prev: vec![u32::MAX; data.len()],
+            inserted: 0,
+            max_chain: level.max_chain(),
+        }
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let v = u32::from_be_bytes([0, self.data[pos], self.data[pos + 1], self.data[pos + 2]]);
+        (v.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    /// Add all positions before `pos` to the hash chains
+    fn insert_until(&mut self, pos: usize) {
+        while self.inserted < pos {
+            let i = self.inserted;
+            if i + MIN_MATCH <= self.data.len() {
+                let h = self.hash(i);
+                self.prev[i] = self.head[h];
+                self.head[h] = i as u32;
+            }
+            self.inserted += 1;
+        }
+    }
+
+    /// Longest match for the bytes at `pos`, as (distance, length)
+    pub fn find(&mut self, pos: usize) -> Option<(usize, usize)> {
+        self.insert_until(pos);
+        if pos + MIN_MATCH > self.data.len() {
+            return None;
+        }
+        let max_length = MAX_MATCH.min(self.data.len() - pos);
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[self.hash(pos)];
+        for _ in 0..self.max_chain {
+            if candidate == u32::MAX || pos - candidate as usize > WINDOW_SIZE {
+                break;
+            }
+            let start = candidate as usize;
+            let length = self.data[start..]
+                .iter()
+                .zip(&self.data[pos..pos + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH && best.is_none_or(|(_, best_length)| length > best_length) {
+                best = Some((pos - start, length));
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = self.prev[start];
+        }
+        best
+    }
+}
+
+/// Bytes of input tokenized at a time when streaming
+const STREAM_CHUNK_SIZE: usize = 0x10000;
+
+/// Tokenize a stream, keeping only the match window and one chunk of input in memory. Returns
+/// the number of bytes read.
+pub fn tokenize_stream<R: Read>(
+    reader: &mut R,
+    level: CompressionLevel,
+    mut emit: impl FnMut(Token) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut buf: Vec<u8> = Vec::with_capacity(WINDOW_SIZE + STREAM_CHUNK_SIZE + MAX_MATCH);
+    // Start of the bytes not yet tokenized; everything before it is match history
+    let mut pos = 0;
+    let mut total = 0;
+    loop {
+        // Read enough that matches starting anywhere in the chunk can reach full length
+        let wanted = pos + STREAM_CHUNK_SIZE + MAX_MATCH;
+        let read = reader
+            .take((wanted - buf.len()) as u64)
+            .read_to_end(&mut buf)?;
+        total += read as u64;
+        let eof = buf.len() < wanted;
+        let end = if eof {
+            buf.len()
+        } else {
+            pos + STREAM_CHUNK_SIZE
+        };
+
+        pos = tokenize_range(&buf, pos, end, level, &mut emit)?;
+        if eof {
+            return Ok(total);
+        }
+        let discard = pos.saturating_sub(WINDOW_SIZE);
+        buf.drain(..discard);
+        pos -= discard;
+    }
+}
+
+/// Tokenize the bytes of `data` from `start` up to at least `end`, using the bytes before `start`
+/// as history. Tokens may extend past `end`; returns the position after the last one.
+fn tokenize_range(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    level: CompressionLevel,
+    emit: impl FnMut(Token) -> io::Result<()>,
+) -> io::Result<usize> {
+    match level {
+        CompressionLevel::Fast => tokenize_greedy(data, start, end, level, false, emit),
+        CompressionLevel::Default => tokenize_greedy(data, start, end, level, true, emit),
+        CompressionLevel::Best => tokenize_optimal(data, start, end, level, emit),
+    }
+}
+
+fn tokenize_greedy(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    level: CompressionLevel,
+    lazy: bool,
+    mut emit: impl FnMut(Token) -> io::Result<()>,
+) -> io::Result<usize> {
+    let mut finder = MatchFinder::new(data, level);
+    let mut pos = start;
+    while pos < end {
+        match finder.find(pos) {
+            // Prefer a literal if the next position has a longer match
+            Some((distance, length))
+                if !lazy
+                    || finder
+                        .find(pos + 1)
+                        .is_none_or(|(_, next_length)| next_length <= length) =>
+            {
+                emit(Token::Match { distance, length })?;
+                pos += length;
+            }
+            _ => {
+                emit(Token::Literal(data[pos]))?;
+                pos += 1;
+            }
+        }
+    }
+    Ok(pos)
+}
+
+/// Encoded size in bits of a token, including its flag bit, in both Yaz0 and Yay0
+fn token_cost(length: usize) -> u32 {
+    match length {
+        1 => 9,
+        _ if length >= LONG_MATCH => 25,
+        _ => 17,
+    }
+}
+
+fn tokenize_optimal(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    level: CompressionLevel,
+    mut emit: impl FnMut(Token) -> io::Result<()>,
+) -> io::Result<usize> {
+    let mut finder = MatchFinder::new(data, level);
+    let matches: Vec<_> = (start..end).map(|pos| finder.find(pos)).collect();
+
+    // Cheapest encoding of everything from each position to `end`, and the token length that
+    // achieves it. Any prefix of a match is also a match at the same distance. Tokens running past
+    // `end` cost nothing extra there.
+    let count = end - start;
+    let mut cost = vec![0u32; count + MAX_MATCH + 1];
+    let mut choice = vec![1usize; count];
+    for i in (0..count).rev() {
+        cost[i] = token_cost(1) + cost[i + 1];
+        if let Some((_, max_length)) = matches[i] {
+            for length in MIN_MATCH..=max_length {
+                let c = token_cost(length) + cost[i + length];
+                if c < cost[i] {
+                    cost[i] = c;
+                    choice[i] = length;
+                }
+            }
+        }
+    }
+
+    let mut i = 0;
+    while i < count {
+        let length = choice[i];
+        if length == 1 {
+            emit(Token::Literal(data[start + i]))?;
+        } else {
+            let (distance, _) = matches[i].unwrap();
+            emit(Token::Match { distance, length })?;
+        }
+        i += length;
+    }
+    Ok(start + i)
+}
+
+/// Decompressed output that keeps the match window in memory and passes older bytes on to a
+/// writer
+pub struct OutputWindow<W> {
+    writer: W,
+    buf: Vec<u8>,
+    /// Bytes already passed to the writer
+    flushed: u64,
+}
+
+impl<W: Write> OutputWindow<W> {
+    pub fn new(writer: W) -> Self {
+        OutputWindow {
+            writer,
+            buf: Vec::with_capacity(WINDOW_SIZE + STREAM_CHUNK_SIZE + MAX_MATCH),
+            flushed: 0,
+        }
+    }
+
+    /// Total bytes output so far
+    pub fn len(&self) -> u64 {
+        self.flushed + self.buf.len() as u64
+    }
+
+    pub fn push(&mut self, b: u8) -> io::Result<()> {
+        self.buf.push(b);
+        self.flush_history()
+    }
+
+    /// Copy `length` bytes starting `distance` bytes back
+    pub fn copy(&mut self, distance: usize, length: usize) -> io::Result<()> {
+        if distance > self.len() as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "back-reference at {:#x} points before the start of the data",
+                    self.len()
+                ),
+            ));
+        }
+        let start = self.buf.len() - distance;
+        // Copy byte by byte since the source may overlap the bytes being written
+        for i in 0..length {
+            self.buf.push(self.buf[start + i]);
+        }
+        self.flush_history()
+    }
+
+    fn flush_history(&mut self) -> io::Result<()> {
+        if self.buf.len() >= WINDOW_SIZE + STREAM_CHUNK_SIZE {
+            let count = self.buf.len() - WINDOW_SIZE;
+            self.writer.write_all(&self.buf[..count])?;
+            self.buf.drain(..count);
+            self.flushed += count as u64;
+        }
+        Ok(())
+    }
+
+    /// Write out the remaining bytes, truncated to `size` in total
+    pub fn finish(mut self, size: u64) -> io::Result<W> {
+        let keep = (size.saturating_sub(self.flushed) as usize).min(self.buf.len());
+        self.writer.write_all(&self.buf[..keep])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Inputs for round trip tests: empty, shorter than a match, runs, text, noise, and data that
+    /// spans more than one stream chunk with repeats further back than the window
+    pub(crate) fn samples() -> Vec<Vec<u8>> {
+        let mut state = 0x1234_5678u32;
+        let mut noise = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        };
+        let random: Vec<u8> = (0..0x3000).map(|_| noise()).collect();
+        let mut long = Vec::new();
+        for i in 0..0x30 {
+            long.extend_from_slice(&random[(i * 0x100) % 0x2000..][..0x800]);
+            long.extend(std::iter::repeat_n(i as u8, i * 7));
+        }
+        vec![
+            Vec::new(),
+            b"ab".to_vec(),
+            vec![0; 1000],
+            b"abcabcabca".to_vec(),
+            b"the quick brown fox jumps over the lazy dog; the quick brown fox".repeat(20),
+            random,
+            long,
+        ]
+    }
+
+    #[test]
+    fn matches_stay_in_the_window() {
+        for level in CompressionLevel::ALL {
+            for data in samples() {
+                let mut finder = MatchFinder::new(&data, level);
+                for pos in 0..data.len() {
+                    if let Some((distance, length)) = finder.find(pos) {
+                        assert!((1..=WINDOW_SIZE.min(pos)).contains(&distance));
+                        assert!((MIN_MATCH..=MAX_MATCH).contains(&length));
+                        let start = pos - distance;
+                        assert_eq!(data[start..start + length], data[pos..pos + length]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_longest_match() {
+        let data = b"abcdXabcdeYabcdefZabcdef";
+        let mut finder = MatchFinder::new(data, CompressionLevel::Best);
+        assert_eq!(finder.find(18), Some((7, 6)));
+    }
+
+    #[test]
+    fn output_window_copies_overlapping_matches() {
+        let mut window = OutputWindow::new(Vec::new());
+        window.push(b'a').unwrap();
+        window.push(b'b').unwrap();
+        window.copy(2, 5).unwrap();
+        assert_eq!(window.len(), 7);
+        assert_eq!(window.finish(6).unwrap(), b"ababab");
+    }
+
+    #[test]
+    fn output_window_rejects_references_before_the_start() {
+        let mut window = OutputWindow::new(Vec::new());
+        window.push(b'a').unwrap();
+        assert!(window.copy(2, 3).is_err());
+    }
+
+    #[test]
+    fn tokens_rebuild_the_stream() {
+        for level in CompressionLevel::ALL {
+            for data in samples() {
+                let mut window = OutputWindow::new(Vec::new());
+                let read = tokenize_stream(&mut data.as_slice(), level, |token| match token {
+                    Token::Literal(b) => window.push(b),
+                    Token::Match { distance, length } => window.copy(distance, length),
+                })
+                .unwrap();
+                assert_eq!(read, data.len() as u64);
+                assert_eq!(window.finish(read).unwrap(), data, "{level:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Text formats accepted for metadata manifests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Toml,
+    Json,
+}
+
+impl ManifestFormat {
+    /// Pick a format from a file extension, defaulting to TOML
+    pub fn from_path(path: &Path) -> ManifestFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ManifestFormat::Json,
+            _ => ManifestFormat::Toml,
+        }
+    }
+
+    pub fn parse(name: &str) -> anyhow::Result<ManifestFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "toml" => Ok(ManifestFormat::Toml),
+            "json" => Ok(ManifestFormat::Json),
+            _ => bail!("Unknown manifest format '{name}'"),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ManifestFormat::Toml => "toml",
+            ManifestFormat::Json => "json",
+        }
+    }
+}
+
+pub fn parse<T: DeserializeOwned>(buf: &[u8], format: ManifestFormat) -> anyhow::Result<T> {
+    let s = std::str::from_utf8(buf).context("Failed to parse manifest as UTF-8")?;
+    let value = match format {
+        ManifestFormat::Toml => toml::from_str(s).context("Failed to parse TOML manifest")?,
+        ManifestFormat::Json => serde_json::from_str(s).context("Failed to parse JSON manifest")?,
+    };
+    Ok(value)
+}
+
+pub fn write<T: Serialize>(value: &T, format: ManifestFormat) -> anyhow::Result<String> {
+    let mut s = match format {
+        ManifestFormat::Toml => toml::to_string_pretty(value)?,
+        ManifestFormat::Json => serde_json::to_string_pretty(value)?,
+    };
+    if !s.ends_with('\n') {
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+/// Read a manifest, picking the format from the file extension
+pub fn read_file<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let buf =
+        std::fs::read(path).with_context(|| format!("cannot read {}", path.to_string_lossy()))?;
+    parse(&buf, ManifestFormat::from_path(path))
+        .with_context(|| format!("Invalid manifest {}", path.to_string_lossy()))
+}
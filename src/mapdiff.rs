@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::symbol_map::{Symbol, SymbolMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapChangeKind {
+    /// Only in the new map
+    Added,
+    /// Only in the old map
+    Removed,
+    /// Same name at a different address
+    Moved,
+    /// Different name at the same address, with the old name gone from the new map
+    Renamed,
+    /// Same name and address with a different known size
+    Resized,
+}
+
+impl Display for MapChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            MapChangeKind::Added => "added",
+            MapChangeKind::Removed => "removed",
+            MapChangeKind::Moved => "moved",
+            MapChangeKind::Renamed => "renamed",
+            MapChangeKind::Resized => "resized",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapChange {
+    pub kind: MapChangeKind,
+    /// The symbol in the old map, if present there
+    pub old: Option<Symbol>,
+    /// The symbol in the new map, if present there
+    pub new: Option<Symbol>,
+}
+
+impl MapChange {
+    fn address(&self) -> u32 {
+        self.new.as_ref().or(self.old.as_ref()).unwrap().address
+    }
+}
+
+/// Compare two symbol maps by name. Only addresses, names, and sizes present in both maps are
+/// compared, so maps in different formats can be diffed. Changes are sorted by address.
+pub fn mapdiff(old: &SymbolMap, new: &SymbolMap) -> Vec<MapChange> {
+    let old_by_name: HashMap<&str, &Symbol> =
+        old.symbols.iter().map(|s| (s.name.as_str(), s)).collect();
+    let new_by_name: HashMap<&str, &Symbol> =
+        new.symbols.iter().map(|s| (s.name.as_str(), s)).collect();
+
+    let mut changes = Vec::new();
+    for symbol in new
+        .symbols
+        .iter()
+        .filter(|s| old_by_name.contains_key(s.name.as_str()))
+    {
+        let old_symbol = old_by_name[symbol.name.as_str()];
+        let kind = if old_symbol.address != symbol.address {
+            MapChangeKind::Moved
+        } else if old_symbol.size.is_some()
+            && symbol.size.is_some()
+            && old_symbol.size != symbol.size
+        {
+            MapChangeKind::Resized
+        } else {
+            continue;
+        };
+        changes.push(MapChange {
+            kind,
+            old: Some(old_symbol.clone()),
+            new: Some(symbol.clone()),
+        });
+    }
+
+    // Symbols only in one map, paired up as renames where the address matches
+    let removed: Vec<&Symbol> = old
+        .symbols
+        .iter()
+        .filter(|s| !new_by_name.contains_key(s.name.as_str()))
+        .collect();
+    let mut renamed = HashSet::new();
+    for symbol in new
+        .symbols
+        .iter()
+        .filter(|s| !old_by_name.contains_key(s.name.as_str()))
+    {
+        let old_symbol = removed
+            .iter()
+            .find(|r| r.address == symbol.address && !renamed.contains(&r.name));
+        match old_symbol {
+            Some(old_symbol) => {
+                renamed.insert(&old_symbol.name);
+                changes.push(MapChange {
+                    kind: MapChangeKind::Renamed,
+                    old: Some((*old_symbol).clone()),
+                    new: Some(symbol.clone()),
+                });
+            }
+            None => changes.push(MapChange {
+                kind: MapChangeKind::Added,
+                old: None,
+                new: Some(symbol.clone()),
+            }),
+        }
+    }
+    for symbol in removed.into_iter().filter(|s| !renamed.contains(&s.name)) {
+        changes.push(MapChange {
+            kind: MapChangeKind::Removed,
+            old: Some(symbol.clone()),
+            new: None,
+        });
+    }
+
+    changes.sort_by_key(|c| c.address());
+    changes
+}
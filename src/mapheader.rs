@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+use crate::symbol_map::{Symbol, SymbolMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderStyle {
+    /// `extern` declarations, to be resolved by the linker
+    #[default]
+    Extern,
+    /// `#define` address constants
+    Constants,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HeaderOptions {
+    pub style: HeaderStyle,
+    /// Prepended to each constant's name
+    pub prefix: String,
+    /// Only symbols matching one of these patterns are included, if any are given. Patterns may
+    /// contain `*` wildcards.
+    pub include: Vec<String>,
+    /// Symbols matching any of these patterns are left out
+    pub exclude: Vec<String>,
+    /// Name of the include guard macro
+    pub guard: String,
+}
+
+/// Match a name against a pattern where `*` matches any run of characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| wildcard_match(rest, &name[i..]))
+        }
+    }
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether a symbol is code, going by its section. Symbols without a section are assumed to be.
+fn is_function(symbol: &Symbol) -> bool {
+    symbol
+        .section
+        .as_deref()
+        .is_none_or(|s| s == ".init" || s.starts_with(".text"))
+}
+
+/// Generate a C/C++ header declaring the symbols in a map. Returns the header and the names of
+/// symbols that were skipped for not being valid C identifiers, such as C++ operators.
+pub fn generate_header(map: &SymbolMap, options: &HeaderOptions) -> (String, Vec<String>) {
+    let mut out = String::new();
+    let mut skipped = Vec::new();
+    writeln!(out, "#ifndef {0}\n#define {0}\n", options.guard).unwrap();
+    if options.style == HeaderStyle::Extern {
+        writeln!(out, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n").unwrap();
+    }
+
+    for symbol in &map.symbols {
+        let included = options.include.is_empty()
+            || options
+                .include
+                .iter()
+                .any(|p| wildcard_match(p, &symbol.name));
+        let excluded = options
+            .exclude
+            .iter()
+            .any(|p| wildcard_match(p, &symbol.name));
+        if !included || excluded {
+            continue;
+        }
+        if !is_identifier(&symbol.name) {
+            skipped.push(symbol.name.clone());
+            continue;
+        }
+        match options.style {
+            HeaderStyle::Extern if is_function(symbol) => {
+                writeln!(out, "void {}(); // {:#010x}", symbol.name, symbol.address)
+            }
+            HeaderStyle::Extern => match symbol.size {
+                Some(size) => writeln!(
+                    out,
+                    "extern char {}[{size:#x}]; // {:#010x}",
+                    symbol.name, symbol.address
+                ),
+                None => writeln!(
+                    out,
+                    "extern char {}[]; // {:#010x}",
+                    symbol.name, symbol.address
+                ),
+            },
+            HeaderStyle::Constants => writeln!(
+                out,
+                "#define {}{} {:#010x}",
+                options.prefix, symbol.name, symbol.address
+            ),
+        }
+        .unwrap();
+    }
+
+    if options.style == HeaderStyle::Extern {
+        writeln!(out, "\n#ifdef __cplusplus\n}}\n#endif").unwrap();
+    }
+    writeln!(out, "\n#endif").unwrap();
+    (out, skipped)
+}
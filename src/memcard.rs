@@ -0,0 +1,507 @@
+// Raw memory card images, as dumped from a card or used by Dolphin: a header block, two copies
+// each of the directory and block allocation table, and then the saves' blocks.
+
+use anyhow::{anyhow, bail, ensure};
+use serde::Serialize;
+use zerocopy::{FromBytes, IntoBytes};
+
+use crate::formats::gci::GciHeader;
+
+pub const BLOCK_SIZE: usize = 0x2000;
+/// Header, directory, directory backup, BAT, and BAT backup
+pub const SYSTEM_BLOCKS: usize = 5;
+pub const DIRECTORY_ENTRIES: usize = 127;
+
+/// In the header block: 0 for a card formatted on a Western console, 1 for a Japanese one
+const HEADER_ENCODING: usize = 0x24;
+const ENCODING_SHIFT_JIS: u16 = 1;
+const DIRECTORY_UPDATE_COUNTER: usize = 0x1FFA;
+const DIRECTORY_CHECKSUM: usize = 0x1FFC;
+const BAT_UPDATE_COUNTER: usize = 0x4;
+const BAT_FREE_BLOCKS: usize = 0x6;
+const BAT_LAST_ALLOCATED: usize = 0x8;
+const BAT_MAP: usize = 0xA;
+/// Save blocks the BAT has room to map, after its header
+const MAX_SAVE_BLOCKS: usize = (BLOCK_SIZE - BAT_MAP) / 2;
+const LAST_BLOCK: u16 = 0xFFFF;
+const FREE_BLOCK: u16 = 0;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+/// The card's checksum of big-endian halfwords and of their inverses
+fn checksum(data: &[u8]) -> (u16, u16) {
+    let (mut sum, mut inverse) = (0u16, 0u16);
+    for word in data.chunks_exact(2) {
+        let word = u16::from_be_bytes([word[0], word[1]]);
+        sum = sum.wrapping_add(word);
+        inverse = inverse.wrapping_add(word ^ 0xFFFF);
+    }
+    // 0xFFFF is reserved to mean an unformatted block
+    (
+        if sum == 0xFFFF { 0 } else { sum },
+        if inverse == 0xFFFF { 0 } else { inverse },
+    )
+}
+
+/// Format seconds since 2000-01-01, as the card keeps time, as `YYYY-MM-DD HH:MM:SS`
+pub fn format_timestamp(seconds: u32) -> String {
+    let days = (seconds / 86400) as i64 + 10957; // Days from 1970 to 2000
+    let time = seconds % 86400;
+    // Civil date from days since 1970, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parse a time as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` (or with a `T` between the date and time),
+/// or a number of seconds, into seconds since 2000-01-01
+pub fn parse_timestamp(s: &str) -> anyhow::Result<u32> {
+    if let Ok(seconds) = s.parse() {
+        return Ok(seconds);
+    }
+    let invalid = || anyhow!("invalid time {s}; expected YYYY-MM-DD[ HH:MM:SS]");
+    let (date, time) = s.split_once([' ', 'T']).unwrap_or((s, "00:00:00"));
+    let fields = |text: &str, sep| -> anyhow::Result<Vec<i64>> {
+        let fields = text
+            .split(sep)
+            .map(|f| f.parse().map_err(|_| invalid()))
+            .collect::<anyhow::Result<Vec<i64>>>()?;
+        ensure!(fields.len() == 3, invalid());
+        Ok(fields)
+    };
+    let (date, time) = (fields(date, '-')?, fields(time, ':')?);
+    let (year, month, day) = (date[0], date[1], date[2]);
+    ensure!(
+        (1..=12).contains(&month)
+            && (1..=31).contains(&day)
+            && (0..24).contains(&time[0])
+            && (0..60).contains(&time[1])
+            && (0..60).contains(&time[2]),
+        invalid()
+    );
+    // Days since 1970 from a civil date, the inverse of `format_timestamp`'s conversion
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468 - 10957;
+    let seconds = days * 86400 + time[0] * 3600 + time[1] * 60 + time[2];
+    let seconds = u32::try_from(seconds)
+        .map_err(|_| anyhow!("{s} is outside the range the card can store"))?;
+    // Days past the end of the month would roll over into the next one
+    ensure!(
+        format_timestamp(seconds).starts_with(&format!("{year:04}-{month:02}-{day:02}")),
+        invalid()
+    );
+    Ok(seconds)
+}
+
+/// Text up to the first null, with bytes outside ASCII read as Latin-1
+fn decode_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect()
+}
+
+/// A save's title or description, in the encoding the card was formatted with
+fn decode_comment(bytes: &[u8], shift_jis: bool) -> String {
+    if !shift_jis {
+        return decode_string(bytes);
+    }
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    encoding_rs::SHIFT_JIS
+        .decode_without_bom_handling(&bytes[..len])
+        .0
+        .into_owned()
+}
+
+/// A save in a memory card's directory
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveInfo {
+    /// Index of the save's entry in the directory
+    pub index: usize,
+    pub gamecode: String,
+    pub file_name: String,
+    pub title: String,
+    pub description: String,
+    pub blocks: u16,
+    pub first_block: u16,
+    /// Seconds since 2000-01-01
+    pub last_modified: u32,
+    pub modified: String,
+    pub banner: bool,
+    pub permissions: u8,
+}
+
+pub struct MemoryCard {
+    pub data: Vec<u8>,
+    /// Blocks holding the current copies of the directory and BAT
+    directory: usize,
+    bat: usize,
+}
+
+impl MemoryCard {
+    pub fn parse(data: Vec<u8>) -> anyhow::Result<MemoryCard> {
+        ensure!(
+            data.len() >= SYSTEM_BLOCKS * BLOCK_SIZE && data.len().is_multiple_of(BLOCK_SIZE),
+            "{:#x} bytes isn't a memory card image, which is a whole number of {BLOCK_SIZE:#x} \
+             byte blocks",
+            data.len()
+        );
+        ensure!(
+            data.len() / BLOCK_SIZE - SYSTEM_BLOCKS <= MAX_SAVE_BLOCKS,
+            "{:#x} bytes is more blocks than a memory card's allocation table can hold",
+            data.len()
+        );
+        let block = |i: usize| &data[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE];
+
+        // The card keeps two copies of each table and updates them alternately, so the current one
+        // is the valid copy that was updated last
+        let current =
+            |first: usize, checksummed: std::ops::Range<usize>, checksum_at, counter_at| {
+                let valid: Vec<usize> = [first, first + 1]
+                    .into_iter()
+                    .filter(|&i| {
+                        let b = block(i);
+                        checksum(&b[checksummed.clone()])
+                            == (read_u16(b, checksum_at), read_u16(b, checksum_at + 2))
+                    })
+                    .collect();
+                valid
+                    .into_iter()
+                    .max_by_key(|&i| read_u16(block(i), counter_at) as i16)
+            };
+        let directory = current(
+            1,
+            0..DIRECTORY_CHECKSUM,
+            DIRECTORY_CHECKSUM,
+            DIRECTORY_UPDATE_COUNTER,
+        )
+        .ok_or_else(|| anyhow!("Both copies of the directory are corrupt"))?;
+        let bat = current(3, 4..BLOCK_SIZE, 0, BAT_UPDATE_COUNTER)
+            .ok_or_else(|| anyhow!("Both copies of the block allocation table are corrupt"))?;
+
+        Ok(MemoryCard {
+            data,
+            directory,
+            bat,
+        })
+    }
+
+    fn block(&self, index: usize) -> &[u8] {
+        &self.data[index * BLOCK_SIZE..(index + 1) * BLOCK_SIZE]
+    }
+
+    /// Whether saves' titles and descriptions are in Shift-JIS, as on Japanese consoles
+    pub fn is_shift_jis(&self) -> bool {
+        read_u16(self.block(0), HEADER_ENCODING) == ENCODING_SHIFT_JIS
+    }
+
+    /// Blocks on the card, including the system blocks
+    pub fn total_blocks(&self) -> usize {
+        self.data.len() / BLOCK_SIZE
+    }
+
+    /// Blocks left for saves
+    pub fn free_blocks(&self) -> u16 {
+        read_u16(self.block(self.bat), BAT_FREE_BLOCKS)
+    }
+
+    fn entry(&self, index: usize) -> &GciHeader {
+        let offset = self.directory * BLOCK_SIZE + index * size_of::<GciHeader>();
+        GciHeader::ref_from_prefix(&self.data[offset..]).unwrap().0
+    }
+
+    /// The directory's entries in use, with their indices
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &GciHeader)> {
+        let directory = self.block(self.directory);
+        <[GciHeader]>::ref_from_prefix_with_elems(directory, DIRECTORY_ENTRIES)
+            .unwrap()
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.gamecode != [0xFF; 6])
+    }
+
+    /// The blocks a save occupies, in order, following its chain in the BAT
+    pub fn save_blocks(&self, entry: &GciHeader) -> anyhow::Result<Vec<u16>> {
+        let bat = self.block(self.bat);
+        let name = decode_string(&entry.filename);
+        let mut blocks = Vec::new();
+        let mut block = entry.first_block_num.get();
+        loop {
+            if !(SYSTEM_BLOCKS..self.total_blocks()).contains(&(block as usize)) {
+                bail!("{name} refers to block {block}, which isn't a save block");
+            }
+            if blocks.len() >= entry.block_count.get() as usize || blocks.contains(&block) {
+                bail!(
+                    "{name}'s blocks don't match its size of {}",
+                    entry.block_count
+                );
+            }
+            blocks.push(block);
+            let next = read_u16(bat, BAT_MAP + (block as usize - SYSTEM_BLOCKS) * 2);
+            if next == LAST_BLOCK {
+                break;
+            }
+            block = next;
+        }
+        ensure!(
+            blocks.len() == entry.block_count.get() as usize,
+            "{name}'s blocks don't match its size of {}",
+            entry.block_count
+        );
+        Ok(blocks)
+    }
+
+    /// The contents of a save: its blocks joined in order, without its directory entry
+    pub fn save_data(&self, entry: &GciHeader) -> anyhow::Result<Vec<u8>> {
+        Ok(self
+            .save_blocks(entry)?
+            .into_iter()
+            .flat_map(|block| self.block(block as usize))
+            .copied()
+            .collect())
+    }
+
+    /// Details of the save in a directory entry
+    pub fn save_info(&self, index: usize) -> anyhow::Result<SaveInfo> {
+        let entry = self.entry(index);
+        let data = self.save_data(entry)?;
+        let comment = entry.comment_offset.get() as usize;
+        let comment = data.get(comment..comment + 0x40).unwrap_or(&[]);
+        let (title, description) = comment.split_at(comment.len() / 2);
+        Ok(SaveInfo {
+            index,
+            gamecode: decode_string(&entry.gamecode),
+            file_name: decode_string(&entry.filename),
+            title: decode_comment(title, self.is_shift_jis()),
+            description: decode_comment(description, self.is_shift_jis()),
+            blocks: entry.block_count.get(),
+            first_block: entry.first_block_num.get(),
+            last_modified: entry.last_modified.get(),
+            modified: format_timestamp(entry.last_modified.get()),
+            banner: entry.banner_fmt & 3 != 0,
+            permissions: entry.permissions,
+        })
+    }
+
+    pub fn saves(&self) -> anyhow::Result<Vec<SaveInfo>> {
+        self.entries()
+            .map(|(index, _)| self.save_info(index))
+            .collect()
+    }
+
+    /// Find a save by file name, and by gamecode if several games use the name
+    pub fn find(&self, file_name: &str, gamecode: Option<&str>) -> anyhow::Result<usize> {
+        let matches: Vec<(usize, &GciHeader)> = self
+            .entries()
+            .filter(|(_, e)| decode_string(&e.filename) == file_name)
+            .filter(|(_, e)| gamecode.is_none_or(|g| decode_string(&e.gamecode) == g))
+            .collect();
+        match matches.as_slice() {
+            [] => bail!("No save named {file_name}"),
+            [(index, _)] => Ok(*index),
+            _ => bail!(
+                "Several games have saves named {file_name}; choose one by gamecode: {}",
+                matches
+                    .iter()
+                    .map(|(_, e)| decode_string(&e.gamecode))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// A save as a GCI: its directory entry followed by its data
+    pub fn export_gci(&self, index: usize) -> anyhow::Result<Vec<u8>> {
+        let entry = self.entry(index);
+        let mut gci = entry.as_bytes().to_vec();
+        gci.extend_from_slice(&self.save_data(entry)?);
+        Ok(gci)
+    }
+
+    /// Write new copies of the directory and BAT, with their update counters advanced and their
+    /// checksums fixed, to both slots
+    /// Count the unallocated blocks in `bat`'s map
+    fn count_free(&self, bat: &[u8]) -> u16 {
+        (SYSTEM_BLOCKS..self.total_blocks())
+            .filter(|&b| read_u16(bat, BAT_MAP + (b - SYSTEM_BLOCKS) * 2) == FREE_BLOCK)
+            .count() as u16
+    }
+
+    fn write_tables(&mut self, mut directory: Vec<u8>, mut bat: Vec<u8>) {
+        let counter = read_u16(&directory, DIRECTORY_UPDATE_COUNTER).wrapping_add(1);
+        write_u16(&mut directory, DIRECTORY_UPDATE_COUNTER, counter);
+        let (sum, inverse) = checksum(&directory[..DIRECTORY_CHECKSUM]);
+        write_u16(&mut directory, DIRECTORY_CHECKSUM, sum);
+        write_u16(&mut directory, DIRECTORY_CHECKSUM + 2, inverse);
+
+        let counter = read_u16(&bat, BAT_UPDATE_COUNTER).wrapping_add(1);
+        write_u16(&mut bat, BAT_UPDATE_COUNTER, counter);
+        let (sum, inverse) = checksum(&bat[4..]);
+        write_u16(&mut bat, 0, sum);
+        write_u16(&mut bat, 2, inverse);
+
+        for (block, table) in [(1, &directory), (2, &directory), (3, &bat), (4, &bat)] {
+            self.data[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE].copy_from_slice(table);
+        }
+        self.directory = 1;
+        self.bat = 3;
+    }
+
+    /// Remove a save, freeing its blocks
+    pub fn delete(&mut self, index: usize) -> anyhow::Result<()> {
+        let blocks = self.save_blocks(self.entry(index))?;
+        let mut directory = self.block(self.directory).to_vec();
+        let mut bat = self.block(self.bat).to_vec();
+        let offset = index * size_of::<GciHeader>();
+        directory[offset..offset + size_of::<GciHeader>()].fill(0xFF);
+        for &block in &blocks {
+            write_u16(
+                &mut bat,
+                BAT_MAP + (block as usize - SYSTEM_BLOCKS) * 2,
+                FREE_BLOCK,
+            );
+        }
+        let free = self.count_free(&bat);
+        write_u16(&mut bat, BAT_FREE_BLOCKS, free);
+        self.write_tables(directory, bat);
+        Ok(())
+    }
+
+    /// Add a save from a GCI, allocating blocks and a directory entry for it. Returns the entry's
+    /// index.
+    pub fn import_gci(&mut self, gci: &[u8]) -> anyhow::Result<usize> {
+        let (header, data) = GciHeader::ref_from_prefix(gci)
+            .map_err(|_| anyhow!("GCI is too small to hold its directory entry"))?;
+        let name = decode_string(&header.filename);
+        let count = header.block_count.get() as usize;
+        ensure!(
+            data.len() == count * BLOCK_SIZE,
+            "{name} should have {count} blocks of data, but has {:#x} bytes",
+            data.len()
+        );
+        if self
+            .entries()
+            .any(|(_, e)| e.gamecode == header.gamecode && e.filename == header.filename)
+        {
+            bail!("The card already has a save named {name} for this game");
+        }
+        let index = (0..DIRECTORY_ENTRIES)
+            .find(|&i| self.entry(i).gamecode == [0xFF; 6])
+            .ok_or_else(|| anyhow!("The card's directory is full"))?;
+
+        let mut bat = self.block(self.bat).to_vec();
+        let free: Vec<u16> = (SYSTEM_BLOCKS..self.total_blocks())
+            .filter(|&b| read_u16(&bat, BAT_MAP + (b - SYSTEM_BLOCKS) * 2) == FREE_BLOCK)
+            .take(count)
+            .map(|b| b as u16)
+            .collect();
+        ensure!(
+            free.len() == count,
+            "{name} needs {count} blocks, but the card only has {} free",
+            free.len()
+        );
+        for (i, &block) in free.iter().enumerate() {
+            let next = free.get(i + 1).copied().unwrap_or(LAST_BLOCK);
+            write_u16(
+                &mut bat,
+                BAT_MAP + (block as usize - SYSTEM_BLOCKS) * 2,
+                next,
+            );
+            let start = block as usize * BLOCK_SIZE;
+            self.data[start..start + BLOCK_SIZE]
+                .copy_from_slice(&data[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE]);
+        }
+        let free_count = self.count_free(&bat);
+        write_u16(&mut bat, BAT_FREE_BLOCKS, free_count);
+        if let Some(&last) = free.last() {
+            write_u16(&mut bat, BAT_LAST_ALLOCATED, last);
+        }
+
+        let mut entry = header.clone();
+        entry.first_block_num = free.first().copied().unwrap_or(LAST_BLOCK).into();
+        let mut directory = self.block(self.directory).to_vec();
+        let offset = index * size_of::<GciHeader>();
+        directory[offset..offset + size_of::<GciHeader>()].copy_from_slice(entry.as_bytes());
+        self.write_tables(directory, bat);
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zerocopy::FromZeros;
+
+    /// A freshly formatted card with `blocks` blocks, its BAT claiming `free` of them are free
+    fn blank_card(blocks: usize, free: u16) -> MemoryCard {
+        let mut card = MemoryCard {
+            data: vec![0; blocks * BLOCK_SIZE],
+            directory: 1,
+            bat: 3,
+        };
+        let mut directory = vec![0; BLOCK_SIZE];
+        directory[..DIRECTORY_ENTRIES * size_of::<GciHeader>()].fill(0xFF);
+        let mut bat = vec![0; BLOCK_SIZE];
+        write_u16(&mut bat, BAT_FREE_BLOCKS, free);
+        card.write_tables(directory, bat);
+        MemoryCard::parse(card.data).unwrap()
+    }
+
+    fn gci(name: &[u8], blocks: usize) -> Vec<u8> {
+        let mut header = GciHeader::new_zeroed();
+        header.gamecode = *b"GALE01";
+        header.filename[..name.len()].copy_from_slice(name);
+        header.block_count = (blocks as u16).into();
+        let mut gci = header.as_bytes().to_vec();
+        gci.resize(gci.len() + blocks * BLOCK_SIZE, 0xAB);
+        gci
+    }
+
+    #[test]
+    fn free_count_follows_the_bat() {
+        // A stored count that disagrees with the map is corrected rather than underflowing
+        let mut card = blank_card(SYSTEM_BLOCKS + 8, 0);
+        let index = card.import_gci(&gci(b"save", 3)).unwrap();
+        assert_eq!(card.free_blocks(), 5);
+        let exported = card.export_gci(index).unwrap();
+        assert!(exported[size_of::<GciHeader>()..] == gci(b"save", 3)[size_of::<GciHeader>()..]);
+        card.delete(index).unwrap();
+        assert_eq!(card.free_blocks(), 8);
+
+        let mut card = blank_card(SYSTEM_BLOCKS + 8, u16::MAX);
+        let index = card.import_gci(&gci(b"save", 8)).unwrap();
+        card.delete(index).unwrap();
+        assert_eq!(card.free_blocks(), 8);
+    }
+
+    #[test]
+    fn parse_rejects_more_blocks_than_the_bat_maps() {
+        let blocks = SYSTEM_BLOCKS + MAX_SAVE_BLOCKS + 1;
+        assert!(MemoryCard::parse(vec![0; blocks * BLOCK_SIZE]).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+// Progress reports in objdiff's JSON format, comparing a reference DOL or REL with one rebuilt from
+// a decompilation, so projects can track how much of the game matches.
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::demangle;
+use crate::dol::{Dol, DolSectionKind};
+use crate::rel::Rel;
+use crate::symbol_map::{Symbol, SymbolMap};
+
+/// A loaded section of a DOL or REL
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub address: u32,
+    pub data: Vec<u8>,
+    pub code: bool,
+}
+
+impl Section {
+    fn contains(&self, address: u32) -> bool {
+        address.wrapping_sub(self.address) < self.data.len() as u32
+    }
+
+    fn bytes(&self, address: u32, size: u32) -> &[u8] {
+        let start = (address - self.address) as usize;
+        let end = (start + size as usize).min(self.data.len());
+        &self.data[start..end]
+    }
+}
+
+/// Load the sections of a DOL, or of a REL as if linked at `rel_address`
+pub fn load_sections(buf: &[u8], rel: bool, rel_address: u32) -> anyhow::Result<Vec<Section>> {
+    if !rel {
+        let dol = Dol::parse(buf)?;
+        return Ok(dol
+            .sections
+            .iter()
+            .map(|s| Section {
+                name: s.name(),
+                address: s.address,
+                data: s.data.clone(),
+                code: s.kind == DolSectionKind::Text,
+            })
+            .collect());
+    }
+
+    let parsed = Rel::parse(buf)?;
+    let mut sections = Vec::new();
+    for (i, section) in parsed.sections.iter().enumerate() {
+        if section.offset == 0 || section.size == 0 {
+            continue;
+        }
+        let data = buf
+            .get(section.offset as usize..(section.offset + section.size) as usize)
+            .with_context(|| format!("REL section {i} extends past the end of the file"))?;
+        sections.push(Section {
+            name: format!("section{i}"),
+            address: rel_address + section.offset,
+            data: data.to_vec(),
+            code: section.executable,
+        });
+    }
+    Ok(sections)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Measures {
+    pub fuzzy_match_percent: f32,
+    pub total_code: u64,
+    pub matched_code: u64,
+    pub matched_code_percent: f32,
+    pub total_data: u64,
+    pub matched_data: u64,
+    pub matched_data_percent: f32,
+    pub total_functions: u32,
+    pub matched_functions: u32,
+    pub matched_functions_percent: f32,
+    pub total_units: u32,
+    pub complete_units: u32,
+}
+
+impl Measures {
+    fn calculate_percents(&mut self) {
+        self.matched_code_percent = percent(self.matched_code, self.total_code);
+        self.matched_data_percent = percent(self.matched_data, self.total_data);
+        self.matched_functions_percent =
+            percent(self.matched_functions as u64, self.total_functions as u64);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportItemMetadata {
+    pub demangled_name: Option<String>,
+}
+
+/// A section or function and how much of it matches
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportItem {
+    pub name: String,
+    pub size: u64,
+    pub fuzzy_match_percent: f32,
+    pub address: Option<u64>,
+    pub metadata: Option<ReportItemMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportUnit {
+    pub name: String,
+    pub measures: Measures,
+    pub sections: Vec<ReportItem>,
+    pub functions: Vec<ReportItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub measures: Measures,
+    pub units: Vec<ReportUnit>,
+}
+
+/// One binary to compare: the reference from the game and the rebuilt one, each with an optional
+/// symbol map
+pub struct Unit<'a> {
+    pub name: String,
+    pub reference: &'a [Section],
+    pub rebuilt: &'a [Section],
+    pub reference_map: Option<&'a SymbolMap>,
+    pub rebuilt_map: Option<&'a SymbolMap>,
+}
+
+/// Count the matching bytes of two ranges, a whole instruction at a time for code
+fn matching_bytes(reference: &[u8], rebuilt: &[u8], code: bool) -> u64 {
+    let chunk = if code { 4 } else { 1 };
+    reference
+        .chunks(chunk)
+        .zip(rebuilt.chunks(chunk))
+        .filter(|(a, b)| a == b)
+        .map(|(a, _)| a.len() as u64)
+        .sum()
+}
+
+fn percent(part: u64, total: u64) -> f32 {
+    if total == 0 {
+        100.0
+    } else {
+        part as f32 / total as f32 * 100.0
+    }
+}
+
+/// Sized functions in the code sections of `sections`. Symbols without a size extend to the next
+/// symbol or the end of their section.
+fn functions<'a>(
+    map: &'a SymbolMap,
+    sections: &'a [Section],
+) -> Vec<(&'a Symbol, &'a Section, u32)> {
+    let mut symbols: Vec<&Symbol> = map.symbols.iter().collect();
+    symbols.sort_by_key(|s| s.address);
+    symbols.dedup_by_key(|s| s.address);
+    let mut functions = Vec::new();
+    for (i, symbol) in symbols.iter().enumerate() {
+        let Some(section) = sections
+            .iter()
+            .find(|s| s.code && s.contains(symbol.address))
+        else {
+            continue;
+        };
+        let section_end = section.address + section.data.len() as u32;
+        let end = symbols
+            .get(i + 1)
+            .map_or(section_end, |next| next.address.min(section_end));
+        let size = symbol.size.unwrap_or(end - symbol.address);
+        if size != 0 {
+            functions.push((*symbol, section, size));
+        }
+    }
+    functions
+}
+
+fn compare_unit(unit: &Unit) -> ReportUnit {
+    let mut measures = Measures {
+        total_units: 1,
+        ..Default::default()
+    };
+    let rebuilt_section = |name: &str| unit.rebuilt.iter().find(|s| s.name == name);
+
+    let mut sections = Vec::new();
+    let mut fuzzy_code = 0;
+    for section in unit.reference {
+        let size = section.data.len() as u64;
+        let matched = rebuilt_section(&section.name)
+            .map_or(0, |r| matching_bytes(&section.data, &r.data, section.code));
+        if section.code {
+            measures.total_code += size;
+            fuzzy_code += matched;
+        } else {
+            measures.total_data += size;
+            measures.matched_data += matched;
+        }
+        sections.push(ReportItem {
+            name: section.name.clone(),
+            size,
+            fuzzy_match_percent: percent(matched, size),
+            address: Some(section.address as u64),
+            metadata: None,
+        });
+    }
+
+    let mut functions = Vec::new();
+    match unit.reference_map {
+        Some(map) => {
+            let rebuilt_addresses = unit.rebuilt_map.map(SymbolMap::addresses_by_name);
+            fuzzy_code = 0;
+            for (symbol, section, size) in self::functions(map, unit.reference) {
+                // Find the function in the rebuilt binary by name if it has a map, since code
+                // before it may have moved it, otherwise at the same address
+                let address = match &rebuilt_addresses {
+                    Some(addresses) => addresses.get(symbol.name.as_str()).copied(),
+                    None => Some(symbol.address),
+                };
+                let reference = section.bytes(symbol.address, size);
+                let matched = address
+                    .and_then(|address| {
+                        let rebuilt = unit
+                            .rebuilt
+                            .iter()
+                            .find(|s| s.code && s.contains(address))?;
+                        Some(matching_bytes(
+                            reference,
+                            rebuilt.bytes(address, size),
+                            true,
+                        ))
+                    })
+                    .unwrap_or(0);
+                let size = reference.len() as u64;
+                measures.total_functions += 1;
+                fuzzy_code += matched;
+                if matched == size {
+                    measures.matched_functions += 1;
+                    measures.matched_code += size;
+                }
+                functions.push(ReportItem {
+                    name: symbol.name.clone(),
+                    size,
+                    fuzzy_match_percent: percent(matched, size),
+                    address: Some(symbol.address as u64),
+                    metadata: Some(ReportItemMetadata {
+                        demangled_name: demangle::demangle(&symbol.name),
+                    }),
+                });
+            }
+        }
+        // Without symbols, code matches an instruction at a time
+        None => measures.matched_code = fuzzy_code,
+    }
+
+    measures.fuzzy_match_percent = percent(fuzzy_code, measures.total_code);
+    measures.calculate_percents();
+    if measures.matched_code == measures.total_code && measures.matched_data == measures.total_data
+    {
+        measures.complete_units = 1;
+    }
+    ReportUnit {
+        name: unit.name.clone(),
+        measures,
+        sections,
+        functions,
+    }
+}
+
+/// Compare each unit's reference and rebuilt binaries
+pub fn report(units: &[Unit]) -> Report {
+    let units: Vec<ReportUnit> = units.iter().map(compare_unit).collect();
+    let mut measures = Measures::default();
+    let mut fuzzy_code = 0.0;
+    for unit in &units {
+        let m = &unit.measures;
+        fuzzy_code += m.fuzzy_match_percent * m.total_code as f32;
+        measures.total_code += m.total_code;
+        measures.matched_code += m.matched_code;
+        measures.total_data += m.total_data;
+        measures.matched_data += m.matched_data;
+        measures.total_functions += m.total_functions;
+        measures.matched_functions += m.matched_functions;
+        measures.total_units += m.total_units;
+        measures.complete_units += m.complete_units;
+    }
+    measures.fuzzy_match_percent = if measures.total_code == 0 {
+        100.0
+    } else {
+        fuzzy_code / measures.total_code as f32
+    };
+    measures.calculate_percents();
+    Report { measures, units }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::dol::Dol;
+use crate::ppc;
+use crate::symbol_map::{parse_int, SymbolMap};
+
+/// `nop`, written by the `nop` patch kind
+const INS_NOP: u32 = 0x60000000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+}
+
+impl PatchValue {
+    pub fn size(&self) -> u32 {
+        match self {
+            PatchValue::U8(_) => 1,
+            PatchValue::U16(_) => 2,
+            PatchValue::U32(_) => 4,
+        }
+    }
+
+    pub fn to_be_bytes(&self) -> Vec<u8> {
+        match self {
+            PatchValue::U8(v) => v.to_be_bytes().to_vec(),
+            PatchValue::U16(v) => v.to_be_bytes().to_vec(),
+            PatchValue::U32(v) => v.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchWrite {
+    pub address: u32,
+    pub value: PatchValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub name: String,
+    pub writes: Vec<PatchWrite>,
+}
+
+pub(crate) fn parse_value(type_: &str, value: &str) -> anyhow::Result<PatchValue> {
+    let value = parse_int(value).with_context(|| format!("Invalid value '{value}'"))?;
+    let value = match type_ {
+        "u8" | "byte" => PatchValue::U8(
+            value
+                .try_into()
+                .map_err(|_| anyhow!("Value {value:#x} does not fit in a byte"))?,
+        ),
+        "u16" | "word" => PatchValue::U16(
+            value
+                .try_into()
+                .map_err(|_| anyhow!("Value {value:#x} does not fit in a word"))?,
+        ),
+        "u32" | "dword" => PatchValue::U32(value),
+        _ => bail!("Unknown patch value type '{type_}'"),
+    };
+    Ok(value)
+}
+
+/// The write for one line of a patch list: `<u8|u16|u32> <value>`, `nop`, `branch <destination>`,
+/// `call <destination>`, or `ptr <address or symbol[+offset]>`
+pub(crate) fn parse_write(
+    address: u32,
+    kind: &str,
+    args: &[&str],
+    symbols: &SymbolMap,
+) -> anyhow::Result<PatchValue> {
+    let value = match (kind, args) {
+        ("nop", []) => PatchValue::U32(INS_NOP),
+        ("branch" | "call", [destination]) => {
+            let destination = symbols.resolve(destination)?;
+            PatchValue::U32(ppc::branch(address, destination, kind == "call")?)
+        }
+        ("ptr", [target]) => PatchValue::U32(symbols.resolve(target)?),
+        ("nop" | "branch" | "call" | "ptr", _) => bail!("Wrong number of arguments for {kind}"),
+        (_, [value]) => parse_value(kind, value)?,
+        _ => bail!("Expected one value"),
+    };
+    Ok(value)
+}
+
+/// Parse a patch list. Each patch starts with a `$Name` line, followed by writes in the form
+/// `<address or symbol[+offset]> <u8|u16|u32> <value>`. A write can also be `<target> nop`,
+/// `<target> branch <destination>` or `<target> call <destination>` (a `b` or `bl` to an address
+/// or symbol), or `<target> ptr <symbol>` (the symbol's address, as a u32). Lines starting with
+/// `#` are comments.
+pub fn parse_patch_list(buf: &[u8], symbols: &SymbolMap) -> anyhow::Result<Vec<Patch>> {
+    let s = std::str::from_utf8(buf).context("Failed to parse patch list as UTF-8")?;
+    let mut patches: Vec<Patch> = Vec::new();
+
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('$') {
+            patches.push(Patch {
+                name: name.trim().to_string(),
+                writes: Vec::new(),
+            });
+            continue;
+        }
+
+        let patch = patches.last_mut().ok_or_else(|| {
+            anyhow!(
+                "Patch write before any $name header on line {}",
+                line_num + 1
+            )
+        })?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [target, kind, ref args @ ..] = fields[..] else {
+            bail!("Invalid patch write on line {}: {}", line_num + 1, line);
+        };
+        let address = symbols
+            .resolve(target)
+            .with_context(|| format!("Failed to resolve target on line {}", line_num + 1))?;
+        let value = parse_write(address, kind, args, symbols)
+            .with_context(|| format!("Failed to parse write on line {}", line_num + 1))?;
+        ensure_aligned(address, value.size())
+            .with_context(|| format!("Misaligned write on line {}", line_num + 1))?;
+        patch.writes.push(PatchWrite { address, value });
+    }
+
+    Ok(patches)
+}
+
+/// Write patches in the form read by [`parse_patch_list`]
+pub fn write_patch_list(patches: &[Patch]) -> String {
+    let mut out = String::new();
+    for patch in patches {
+        writeln!(out, "${}", patch.name).unwrap();
+        for write in &patch.writes {
+            let (type_, value) = match write.value {
+                PatchValue::U8(v) => ("u8", v as u32),
+                PatchValue::U16(v) => ("u16", v as u32),
+                PatchValue::U32(v) => ("u32", v),
+            };
+            writeln!(out, "{:08X} {type_} {value:#x}", write.address).unwrap();
+        }
+    }
+    out
+}
+
+/// Apply patches directly to a DOL. Every write must be inside one of its sections.
+pub fn apply_to_dol(dol: &mut Dol, patches: &[Patch]) -> anyhow::Result<()> {
+    for patch in patches {
+        for write in &patch.writes {
+            dol.write(write.address, &write.value.to_be_bytes())
+                .with_context(|| format!("Can't apply patch '{}'", patch.name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write patches as C source with a table of writes, for code that applies them at runtime. The
+/// installer has to flush the caches for any writes to code.
+pub fn write_c_array(patches: &[Patch]) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "typedef struct {{\n    unsigned int address;\n    unsigned int size; /* 1, 2, or 4 bytes \
+         */\n    unsigned int value;\n}} PatchWrite;\n"
+    )
+    .unwrap();
+    writeln!(out, "const PatchWrite patches[] = {{").unwrap();
+    let mut count = 0;
+    for patch in patches {
+        writeln!(out, "    /* {} */", patch.name.replace("*/", "* /")).unwrap();
+        for write in &patch.writes {
+            let value = match write.value {
+                PatchValue::U8(v) => v as u32,
+                PatchValue::U16(v) => v as u32,
+                PatchValue::U32(v) => v,
+            };
+            writeln!(
+                out,
+                "    {{{:#010x}, {}, {value:#x}}},",
+                write.address,
+                write.value.size()
+            )
+            .unwrap();
+            count += 1;
+        }
+    }
+    writeln!(out, "}};").unwrap();
+    writeln!(out, "const unsigned int patch_count = {count};").unwrap();
+    out
+}
+
+pub(crate) fn ensure_aligned(address: u32, size: u32) -> anyhow::Result<()> {
+    ensure!(
+        address.is_multiple_of(size),
+        "Address {address:#010x} is not aligned to {size} bytes"
+    );
+    Ok(())
+}
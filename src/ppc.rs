@@ -0,0 +1,1136 @@
+// Disassembler for the Gekko's PowerPC instruction set, including paired singles. Common
+// simplified mnemonics (li, mr, blr, beq, slwi, ...) are used where they apply. Also encodes the
+// few instructions that patching code needs to generate.
+
+use std::fmt::{self, Display};
+
+use anyhow::{bail, ensure};
+
+/// A disassembled instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub mnemonic: String,
+    pub operands: String,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{:<10}{}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+fn op(mnemonic: impl Into<String>, operands: impl Into<String>) -> Instruction {
+    Instruction {
+        mnemonic: mnemonic.into(),
+        operands: operands.into(),
+    }
+}
+
+fn rd(ins: u32) -> u32 {
+    (ins >> 21) & 0x1F
+}
+
+fn ra(ins: u32) -> u32 {
+    (ins >> 16) & 0x1F
+}
+
+fn rb(ins: u32) -> u32 {
+    (ins >> 11) & 0x1F
+}
+
+fn rc(ins: u32) -> u32 {
+    (ins >> 6) & 0x1F
+}
+
+fn simm(ins: u32) -> i32 {
+    ins as i16 as i32
+}
+
+fn uimm(ins: u32) -> u32 {
+    ins & 0xFFFF
+}
+
+fn rc_dot(ins: u32) -> &'static str {
+    if ins & 1 != 0 {
+        "."
+    } else {
+        ""
+    }
+}
+
+/// Signed hex, as `-0x10` or `0x10`
+fn hex(value: i32) -> String {
+    if value < 0 {
+        format!("-{:#x}", value.unsigned_abs())
+    } else {
+        format!("{value:#x}")
+    }
+}
+
+/// `offset(rA)` operand of loads and stores
+fn mem(offset: i32, ra: u32) -> String {
+    format!("{}(r{ra})", hex(offset))
+}
+
+fn branch_target(address: u32, offset: i32, absolute: bool) -> String {
+    let target = if absolute {
+        offset as u32
+    } else {
+        address.wrapping_add(offset as u32)
+    };
+    format!("{target:#x}")
+}
+
+/// SPR number, which is stored with its two 5-bit halves swapped
+fn spr(ins: u32) -> u32 {
+    ((ins >> 11) & 0x1F) << 5 | ((ins >> 16) & 0x1F)
+}
+
+fn spr_name(spr: u32) -> Option<&'static str> {
+    Some(match spr {
+        1 => "xer",
+        8 => "lr",
+        9 => "ctr",
+        18 => "dsisr",
+        19 => "dar",
+        22 => "dec",
+        26 => "srr0",
+        27 => "srr1",
+        272..=275 => ["sprg0", "sprg1", "sprg2", "sprg3"][spr as usize - 272],
+        912..=919 => [
+            "gqr0", "gqr1", "gqr2", "gqr3", "gqr4", "gqr5", "gqr6", "gqr7",
+        ][spr as usize - 912],
+        920 => "hid2",
+        1008 => "hid0",
+        1009 => "hid1",
+        1017 => "l2cr",
+        _ => return None,
+    })
+}
+
+/// Condition register bit, as `lt`, `cr1gt`, ...
+fn cr_bit(bit: u32) -> String {
+    let cond = ["lt", "gt", "eq", "so"][bit as usize & 3];
+    match bit >> 2 {
+        0 => cond.to_string(),
+        field => format!("cr{field}{cond}"),
+    }
+}
+
+/// Simplified mnemonic for a conditional branch, e.g. `beq` or `bdnz`, with the operand
+/// prefix naming the condition register field if it isn't cr0
+fn branch_condition(bo: u32, bi: u32) -> Option<(String, String)> {
+    let field = bi >> 2;
+    let prefix = if field == 0 {
+        String::new()
+    } else {
+        format!("cr{field}, ")
+    };
+    // The low bit of BO is only a branch prediction hint
+    let name = match (bo & 0x1E, bi & 3) {
+        (12, 0) => "lt",
+        (12, 1) => "gt",
+        (12, 2) => "eq",
+        (12, 3) => "so",
+        (4, 0) => "ge",
+        (4, 1) => "le",
+        (4, 2) => "ne",
+        (4, 3) => "ns",
+        (16, _) => return Some(("dnz".to_string(), String::new())),
+        (18, _) => return Some(("dz".to_string(), String::new())),
+        (20, _) => return Some((String::new(), String::new())),
+        _ => return None,
+    };
+    Some((name.to_string(), prefix))
+}
+
+fn disassemble_bc(ins: u32, address: u32) -> Instruction {
+    let (bo, bi) = (rd(ins), ra(ins));
+    let offset = (ins & 0xFFFC) as i16 as i32;
+    let target = branch_target(address, offset, ins & 2 != 0);
+    let suffix = format!(
+        "{}{}",
+        if ins & 1 != 0 { "l" } else { "" },
+        if ins & 2 != 0 { "a" } else { "" }
+    );
+    match branch_condition(bo, bi) {
+        Some((cond, prefix)) => op(format!("b{cond}{suffix}"), format!("{prefix}{target}")),
+        None => op(
+            format!("bc{suffix}"),
+            format!("{bo}, {}, {target}", cr_bit(bi)),
+        ),
+    }
+}
+
+/// Branch to the link or count register
+fn disassemble_bclr(ins: u32, register: &str) -> Instruction {
+    let (bo, bi) = (rd(ins), ra(ins));
+    let link = if ins & 1 != 0 { "l" } else { "" };
+    match branch_condition(bo, bi) {
+        Some((cond, prefix)) => op(
+            format!("b{cond}{register}{link}"),
+            prefix.trim_end_matches([',', ' ']),
+        ),
+        None => op(
+            format!("bc{register}{link}"),
+            format!("{bo}, {}", cr_bit(bi)),
+        ),
+    }
+}
+
+fn disassemble_19(ins: u32) -> Option<Instruction> {
+    let (d, a, b) = (rd(ins), ra(ins), rb(ins));
+    let cr_op = |name: &str| op(name, format!("{}, {}, {}", cr_bit(d), cr_bit(a), cr_bit(b)));
+    Some(match (ins >> 1) & 0x3FF {
+        0 => op("mcrf", format!("cr{}, cr{}", d >> 2, a >> 2)),
+        16 => disassemble_bclr(ins, "lr"),
+        33 => cr_op("crnor"),
+        50 => op("rfi", ""),
+        129 => cr_op("crandc"),
+        150 => op("isync", ""),
+        193 if d == a && a == b => op("crclr", cr_bit(d)),
+        193 => cr_op("crxor"),
+        225 => cr_op("crnand"),
+        257 => cr_op("crand"),
+        289 if d == a && a == b => op("crset", cr_bit(d)),
+        289 => cr_op("creqv"),
+        417 => cr_op("crorc"),
+        449 => cr_op("cror"),
+        528 => disassemble_bclr(ins, "ctr"),
+        _ => return None,
+    })
+}
+
+fn disassemble_rlwinm(ins: u32) -> Instruction {
+    let (s, a, sh, mb, me) = (rd(ins), ra(ins), rb(ins), rc(ins), (ins >> 1) & 0x1F);
+    let dot = rc_dot(ins);
+    if mb == 0 && me == 31 {
+        op(format!("rotlwi{dot}"), format!("r{a}, r{s}, {sh}"))
+    } else if mb == 0 && sh + me == 31 {
+        op(format!("slwi{dot}"), format!("r{a}, r{s}, {sh}"))
+    } else if me == 31 && sh != 0 && sh + mb == 32 {
+        op(format!("srwi{dot}"), format!("r{a}, r{s}, {mb}"))
+    } else if sh == 0 && me == 31 {
+        op(format!("clrlwi{dot}"), format!("r{a}, r{s}, {mb}"))
+    } else if sh == 0 && mb == 0 {
+        op(format!("clrrwi{dot}"), format!("r{a}, r{s}, {}", 31 - me))
+    } else {
+        op(
+            format!("rlwinm{dot}"),
+            format!("r{a}, r{s}, {sh}, {mb}, {me}"),
+        )
+    }
+}
+
+/// Arithmetic with the XO form's overflow flag
+fn disassemble_31_arith(ins: u32) -> Option<Instruction> {
+    let (d, a, b) = (rd(ins), ra(ins), rb(ins));
+    let (name, operands) = match (ins >> 1) & 0x1FF {
+        8 => ("subfc", 3),
+        10 => ("addc", 3),
+        11 => ("mulhwu", 3),
+        40 => ("subf", 3),
+        75 => ("mulhw", 3),
+        104 => ("neg", 2),
+        136 => ("subfe", 3),
+        138 => ("adde", 3),
+        200 => ("subfze", 2),
+        202 => ("addze", 2),
+        232 => ("subfme", 2),
+        234 => ("addme", 2),
+        235 => ("mullw", 3),
+        266 => ("add", 3),
+        459 => ("divwu", 3),
+        491 => ("divw", 3),
+        _ => return None,
+    };
+    let oe = if ins & 0x400 != 0 { "o" } else { "" };
+    let mnemonic = format!("{name}{oe}{}", rc_dot(ins));
+    Some(if operands == 3 {
+        op(mnemonic, format!("r{d}, r{a}, r{b}"))
+    } else {
+        op(mnemonic, format!("r{d}, r{a}"))
+    })
+}
+
+fn disassemble_31(ins: u32) -> Option<Instruction> {
+    if let Some(ins) = disassemble_31_arith(ins) {
+        return Some(ins);
+    }
+    let (d, a, b) = (rd(ins), ra(ins), rb(ins));
+    let dot = rc_dot(ins);
+    // Indexed loads and stores, rD, rA, rB
+    let indexed = |name: &str, prefix: &str| op(name, format!("{prefix}{d}, r{a}, r{b}"));
+    // Logical operations, rA, rS, rB
+    let logical = |name: &str| op(format!("{name}{dot}"), format!("r{a}, r{d}, r{b}"));
+    let cache = |name: &str| op(name, format!("r{a}, r{b}"));
+    Some(match (ins >> 1) & 0x3FF {
+        0 | 32 => {
+            let name = if (ins >> 1) & 0x3FF == 0 {
+                "cmpw"
+            } else {
+                "cmplw"
+            };
+            if d >> 2 == 0 {
+                op(name, format!("r{a}, r{b}"))
+            } else {
+                op(name, format!("cr{}, r{a}, r{b}", d >> 2))
+            }
+        }
+        4 if d == 31 => op("trap", ""),
+        4 => op("tw", format!("{d}, r{a}, r{b}")),
+        19 => op("mfcr", format!("r{d}")),
+        20 => indexed("lwarx", "r"),
+        23 => indexed("lwzx", "r"),
+        24 => logical("slw"),
+        26 => op(format!("cntlzw{dot}"), format!("r{a}, r{d}")),
+        28 => logical("and"),
+        54 => cache("dcbst"),
+        55 => indexed("lwzux", "r"),
+        60 => logical("andc"),
+        83 => op("mfmsr", format!("r{d}")),
+        86 => cache("dcbf"),
+        87 => indexed("lbzx", "r"),
+        119 => indexed("lbzux", "r"),
+        124 if d == b => op(format!("not{dot}"), format!("r{a}, r{d}")),
+        124 => logical("nor"),
+        144 => op("mtcrf", format!("{:#x}, r{d}", (ins >> 12) & 0xFF)),
+        146 => op("mtmsr", format!("r{d}")),
+        150 => indexed("stwcx.", "r"),
+        151 => indexed("stwx", "r"),
+        183 => indexed("stwux", "r"),
+        210 => op("mtsr", format!("{}, r{d}", a & 0xF)),
+        215 => indexed("stbx", "r"),
+        242 => op("mtsrin", format!("r{d}, r{b}")),
+        246 => cache("dcbtst"),
+        247 => indexed("stbux", "r"),
+        278 => cache("dcbt"),
+        279 => indexed("lhzx", "r"),
+        284 => logical("eqv"),
+        306 => op("tlbie", format!("r{b}")),
+        310 => indexed("eciwx", "r"),
+        311 => indexed("lhzux", "r"),
+        316 => logical("xor"),
+        339 => match spr_name(spr(ins)) {
+            Some(name @ ("xer" | "lr" | "ctr")) => op(format!("mf{name}"), format!("r{d}")),
+            Some(name) => op("mfspr", format!("r{d}, {name}")),
+            None => op("mfspr", format!("r{d}, {}", spr(ins))),
+        },
+        343 => indexed("lhax", "r"),
+        371 => match spr(ins) {
+            268 => op("mftb", format!("r{d}")),
+            269 => op("mftbu", format!("r{d}")),
+            tbr => op("mftb", format!("r{d}, {tbr}")),
+        },
+        375 => indexed("lhaux", "r"),
+        407 => indexed("sthx", "r"),
+        412 => logical("orc"),
+        438 => indexed("ecowx", "r"),
+        439 => indexed("sthux", "r"),
+        444 if d == b => op(format!("mr{dot}"), format!("r{a}, r{d}")),
+        444 => logical("or"),
+        467 => match spr_name(spr(ins)) {
+            Some(name @ ("xer" | "lr" | "ctr")) => op(format!("mt{name}"), format!("r{d}")),
+            Some(name) => op("mtspr", format!("{name}, r{d}")),
+            None => op("mtspr", format!("{}, r{d}", spr(ins))),
+        },
+        470 => cache("dcbi"),
+        476 => logical("nand"),
+        512 => op("mcrxr", format!("cr{}", d >> 2)),
+        533 => indexed("lswx", "r"),
+        534 => indexed("lwbrx", "r"),
+        535 => indexed("lfsx", "f"),
+        536 => logical("srw"),
+        566 => op("tlbsync", ""),
+        567 => indexed("lfsux", "f"),
+        595 => op("mfsr", format!("r{d}, {}", a & 0xF)),
+        597 => op("lswi", format!("r{d}, r{a}, {b}")),
+        598 => op("sync", ""),
+        599 => indexed("lfdx", "f"),
+        631 => indexed("lfdux", "f"),
+        659 => op("mfsrin", format!("r{d}, r{b}")),
+        661 => indexed("stswx", "r"),
+        662 => indexed("stwbrx", "r"),
+        663 => indexed("stfsx", "f"),
+        695 => indexed("stfsux", "f"),
+        725 => op("stswi", format!("r{d}, r{a}, {b}")),
+        727 => indexed("stfdx", "f"),
+        759 => indexed("stfdux", "f"),
+        790 => indexed("lhbrx", "r"),
+        792 => logical("sraw"),
+        824 => op(format!("srawi{dot}"), format!("r{a}, r{d}, {b}")),
+        854 => op("eieio", ""),
+        918 => indexed("sthbrx", "r"),
+        922 => op(format!("extsh{dot}"), format!("r{a}, r{d}")),
+        954 => op(format!("extsb{dot}"), format!("r{a}, r{d}")),
+        982 => cache("icbi"),
+        983 => indexed("stfiwx", "f"),
+        1014 => cache("dcbz"),
+        _ => return None,
+    })
+}
+
+/// Floating point arithmetic shared by opcodes 59 (single) and 63 (double)
+fn disassemble_float_arith(ins: u32, suffix: &str) -> Option<Instruction> {
+    let (d, a, b, c) = (rd(ins), ra(ins), rb(ins), rc(ins));
+    let mnemonic = |name: &str| format!("{name}{suffix}{}", rc_dot(ins));
+    Some(match (ins >> 1) & 0x1F {
+        18 => op(mnemonic("fdiv"), format!("f{d}, f{a}, f{b}")),
+        20 => op(mnemonic("fsub"), format!("f{d}, f{a}, f{b}")),
+        21 => op(mnemonic("fadd"), format!("f{d}, f{a}, f{b}")),
+        25 => op(mnemonic("fmul"), format!("f{d}, f{a}, f{c}")),
+        28 => op(mnemonic("fmsub"), format!("f{d}, f{a}, f{c}, f{b}")),
+        29 => op(mnemonic("fmadd"), format!("f{d}, f{a}, f{c}, f{b}")),
+        30 => op(mnemonic("fnmsub"), format!("f{d}, f{a}, f{c}, f{b}")),
+        31 => op(mnemonic("fnmadd"), format!("f{d}, f{a}, f{c}, f{b}")),
+        _ => return None,
+    })
+}
+
+fn disassemble_59(ins: u32) -> Option<Instruction> {
+    let (d, b) = (rd(ins), rb(ins));
+    match (ins >> 1) & 0x1F {
+        24 => Some(op(format!("fres{}", rc_dot(ins)), format!("f{d}, f{b}"))),
+        _ => disassemble_float_arith(ins, "s"),
+    }
+}
+
+fn disassemble_63(ins: u32) -> Option<Instruction> {
+    let (d, a, b, c) = (rd(ins), ra(ins), rb(ins), rc(ins));
+    let dot = rc_dot(ins);
+    match (ins >> 1) & 0x1F {
+        23 => {
+            return Some(op(format!("fsel{dot}"), format!("f{d}, f{a}, f{c}, f{b}")));
+        }
+        26 => return Some(op(format!("frsqrte{dot}"), format!("f{d}, f{b}"))),
+        18 | 20 | 21 | 25 | 28..=31 => return disassemble_float_arith(ins, ""),
+        _ => {}
+    }
+    let unary = |name: &str| op(format!("{name}{dot}"), format!("f{d}, f{b}"));
+    Some(match (ins >> 1) & 0x3FF {
+        0 => op("fcmpu", format!("cr{}, f{a}, f{b}", d >> 2)),
+        12 => unary("frsp"),
+        14 => unary("fctiw"),
+        15 => unary("fctiwz"),
+        32 => op("fcmpo", format!("cr{}, f{a}, f{b}", d >> 2)),
+        38 => op(format!("mtfsb1{dot}"), d.to_string()),
+        40 => unary("fneg"),
+        64 => op("mcrfs", format!("cr{}, cr{}", d >> 2, a >> 2)),
+        70 => op(format!("mtfsb0{dot}"), d.to_string()),
+        72 => unary("fmr"),
+        134 => op(format!("mtfsfi{dot}"), format!("cr{}, {}", d >> 2, b >> 1)),
+        136 => unary("fnabs"),
+        264 => unary("fabs"),
+        583 => op(format!("mffs{dot}"), format!("f{d}")),
+        711 => op(
+            format!("mtfsf{dot}"),
+            format!("{:#x}, f{b}", (ins >> 17) & 0xFF),
+        ),
+        _ => return None,
+    })
+}
+
+/// Paired single instructions
+fn disassemble_4(ins: u32) -> Option<Instruction> {
+    let (d, a, b, c) = (rd(ins), ra(ins), rb(ins), rc(ins));
+    let dot = rc_dot(ins);
+    let quantized = |name: &str| {
+        let (w, i) = ((ins >> 10) & 1, (ins >> 7) & 7);
+        op(name, format!("f{d}, r{a}, r{b}, {w}, qr{i}"))
+    };
+    match (ins >> 1) & 0x3F {
+        6 => return Some(quantized("psq_lx")),
+        7 => return Some(quantized("psq_stx")),
+        38 => return Some(quantized("psq_lux")),
+        39 => return Some(quantized("psq_stux")),
+        _ => {}
+    }
+    let three = |name: &str, x: u32| op(format!("{name}{dot}"), format!("f{d}, f{a}, f{x}"));
+    let four = |name: &str| op(format!("{name}{dot}"), format!("f{d}, f{a}, f{c}, f{b}"));
+    let instruction = match (ins >> 1) & 0x1F {
+        10 => Some(four("ps_sum0")),
+        11 => Some(four("ps_sum1")),
+        12 => Some(three("ps_muls0", c)),
+        13 => Some(three("ps_muls1", c)),
+        14 => Some(four("ps_madds0")),
+        15 => Some(four("ps_madds1")),
+        18 => Some(three("ps_div", b)),
+        20 => Some(three("ps_sub", b)),
+        21 => Some(three("ps_add", b)),
+        23 => Some(four("ps_sel")),
+        24 => Some(op(format!("ps_res{dot}"), format!("f{d}, f{b}"))),
+        25 => Some(three("ps_mul", c)),
+        26 => Some(op(format!("ps_rsqrte{dot}"), format!("f{d}, f{b}"))),
+        28 => Some(four("ps_msub")),
+        29 => Some(four("ps_madd")),
+        30 => Some(four("ps_nmsub")),
+        31 => Some(four("ps_nmadd")),
+        _ => None,
+    };
+    if instruction.is_some() {
+        return instruction;
+    }
+    let unary = |name: &str| op(format!("{name}{dot}"), format!("f{d}, f{b}"));
+    let binary = |name: &str| op(format!("{name}{dot}"), format!("f{d}, f{a}, f{b}"));
+    let compare = |name: &str| op(name, format!("cr{}, f{a}, f{b}", d >> 2));
+    Some(match (ins >> 1) & 0x3FF {
+        0 => compare("ps_cmpu0"),
+        32 => compare("ps_cmpo0"),
+        40 => unary("ps_neg"),
+        64 => compare("ps_cmpu1"),
+        72 => unary("ps_mr"),
+        96 => compare("ps_cmpo1"),
+        136 => unary("ps_nabs"),
+        264 => unary("ps_abs"),
+        528 => binary("ps_merge00"),
+        560 => binary("ps_merge01"),
+        592 => binary("ps_merge10"),
+        624 => binary("ps_merge11"),
+        1014 => op("dcbz_l", format!("r{a}, r{b}")),
+        _ => return None,
+    })
+}
+
+/// Disassemble one instruction at `address`, which is used for branch targets. Words that
+/// aren't valid instructions are returned as `.word`.
+pub fn disassemble(ins: u32, address: u32) -> Instruction {
+    disassemble_known(ins, address).unwrap_or_else(|| op(".word", format!("{ins:#010x}")))
+}
+
+fn disassemble_known(ins: u32, address: u32) -> Option<Instruction> {
+    let (d, a) = (rd(ins), ra(ins));
+    let load_store =
+        |name: &str, prefix: &str| op(name, format!("{prefix}{d}, {}", mem(simm(ins), a)));
+    let imm = |name: &str| op(name, format!("r{d}, r{a}, {}", hex(simm(ins))));
+    let logical_imm = |name: &str| op(name, format!("r{a}, r{d}, {:#x}", uimm(ins)));
+    Some(match ins >> 26 {
+        4 => return disassemble_4(ins),
+        7 => imm("mulli"),
+        8 => imm("subfic"),
+        10 | 11 => {
+            let (name, value) = if ins >> 26 == 10 {
+                ("cmplwi", format!("{:#x}", uimm(ins)))
+            } else {
+                ("cmpwi", hex(simm(ins)))
+            };
+            if d >> 2 == 0 {
+                op(name, format!("r{a}, {value}"))
+            } else {
+                op(name, format!("cr{}, r{a}, {value}", d >> 2))
+            }
+        }
+        12 => imm("addic"),
+        13 => imm("addic."),
+        14 if a == 0 => op("li", format!("r{d}, {}", hex(simm(ins)))),
+        14 => imm("addi"),
+        15 if a == 0 => op("lis", format!("r{d}, {:#x}", uimm(ins))),
+        15 => imm("addis"),
+        16 => disassemble_bc(ins, address),
+        17 if ins & 2 != 0 => op("sc", ""),
+        18 => {
+            let offset = (((ins & 0x03FFFFFC) << 6) as i32) >> 6;
+            let link = if ins & 1 != 0 { "l" } else { "" };
+            let absolute = if ins & 2 != 0 { "a" } else { "" };
+            op(
+                format!("b{link}{absolute}"),
+                branch_target(address, offset, ins & 2 != 0),
+            )
+        }
+        19 => return disassemble_19(ins),
+        20 => op(
+            format!("rlwimi{}", rc_dot(ins)),
+            format!(
+                "r{a}, r{d}, {}, {}, {}",
+                rb(ins),
+                rc(ins),
+                (ins >> 1) & 0x1F
+            ),
+        ),
+        21 => disassemble_rlwinm(ins),
+        23 => op(
+            format!("rlwnm{}", rc_dot(ins)),
+            format!(
+                "r{a}, r{d}, r{}, {}, {}",
+                rb(ins),
+                rc(ins),
+                (ins >> 1) & 0x1F
+            ),
+        ),
+        24 if ins == 0x60000000 => op("nop", ""),
+        24 => logical_imm("ori"),
+        25 => logical_imm("oris"),
+        26 => logical_imm("xori"),
+        27 => logical_imm("xoris"),
+        28 => logical_imm("andi."),
+        29 => logical_imm("andis."),
+        31 => return disassemble_31(ins),
+        32 => load_store("lwz", "r"),
+        33 => load_store("lwzu", "r"),
+        34 => load_store("lbz", "r"),
+        35 => load_store("lbzu", "r"),
+        36 => load_store("stw", "r"),
+        37 => load_store("stwu", "r"),
+        38 => load_store("stb", "r"),
+        39 => load_store("stbu", "r"),
+        40 => load_store("lhz", "r"),
+        41 => load_store("lhzu", "r"),
+        42 => load_store("lha", "r"),
+        43 => load_store("lhau", "r"),
+        44 => load_store("sth", "r"),
+        45 => load_store("sthu", "r"),
+        46 => load_store("lmw", "r"),
+        47 => load_store("stmw", "r"),
+        48 => load_store("lfs", "f"),
+        49 => load_store("lfsu", "f"),
+        50 => load_store("lfd", "f"),
+        51 => load_store("lfdu", "f"),
+        52 => load_store("stfs", "f"),
+        53 => load_store("stfsu", "f"),
+        54 => load_store("stfd", "f"),
+        55 => load_store("stfdu", "f"),
+        56 | 57 | 60 | 61 => {
+            let name = ["psq_l", "psq_lu", "", "", "psq_st", "psq_stu"][(ins >> 26) as usize - 56];
+            let offset = ((ins & 0xFFF) << 20) as i32 >> 20;
+            let (w, i) = ((ins >> 15) & 1, (ins >> 12) & 7);
+            op(name, format!("f{d}, {}, {w}, qr{i}", mem(offset, a)))
+        }
+        59 => return disassemble_59(ins),
+        63 => return disassemble_63(ins),
+        _ => return None,
+    })
+}
+
+/// Encode a `b` or `bl` from `from` to `to`
+pub fn branch(from: u32, to: u32, link: bool) -> anyhow::Result<u32> {
+    let delta = to.wrapping_sub(from) as i32;
+    ensure!(
+        (-0x02000000..0x02000000).contains(&delta),
+        "Branch from {from:#010x} to {to:#010x} is out of range"
+    );
+    Ok(0x48000000 | (delta as u32 & 0x03FFFFFC) | link as u32)
+}
+
+/// Target of a relative unconditional branch at `address`, or `None` for other instructions
+pub fn relative_branch_target(ins: u32, address: u32) -> Option<u32> {
+    (ins >> 26 == 18 && ins & 2 == 0).then(|| {
+        let offset = (((ins & 0x03FFFFFC) << 6) as i32) >> 6;
+        address.wrapping_add(offset as u32)
+    })
+}
+
+/// Move an instruction from `from` to `to`, retargeting relative unconditional branches so they
+/// still reach the same address
+pub fn relocate(ins: u32, from: u32, to: u32) -> anyhow::Result<u32> {
+    if let Some(target) = relative_branch_target(ins, from) {
+        return branch(to, target, ins & 1 != 0);
+    }
+    if ins >> 26 == 16 && ins & 2 == 0 {
+        bail!("Can't move a relative conditional branch; choose another instruction");
+    }
+    Ok(ins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where an operand goes in the instruction word
+    #[derive(Clone, Copy)]
+    enum Field {
+        /// A register, field, or plain number at this bit
+        At(u32),
+        Simm,
+        /// `offset(rA)` of loads and stores
+        Mem,
+        /// `offset(rA)` of paired single loads and stores, with a 12-bit offset
+        PsMem,
+        Spr,
+        /// Condition register bit, as `cr1gt`
+        Bit(u32),
+    }
+    use Field::*;
+
+    const fn primary(opcode: u32) -> u32 {
+        opcode << 26
+    }
+
+    const fn extended(opcode: u32, xo: u32) -> u32 {
+        opcode << 26 | xo << 1
+    }
+
+    const ONE: &[Field] = &[At(21)];
+    const D_A_B: &[Field] = &[At(21), At(16), At(11)];
+    const D_A_C: &[Field] = &[At(21), At(16), At(6)];
+    const D_A_C_B: &[Field] = &[At(21), At(16), At(6), At(11)];
+    const D_A: &[Field] = &[At(21), At(16)];
+    const D_B: &[Field] = &[At(21), At(11)];
+    const A_S_B: &[Field] = &[At(16), At(21), At(11)];
+    const A_S: &[Field] = &[At(16), At(21)];
+    const A_B: &[Field] = &[At(16), At(11)];
+    const D_A_SIMM: &[Field] = &[At(21), At(16), Simm];
+    const A_S_UIMM: &[Field] = &[At(16), At(21), At(0)];
+    const LOAD_STORE: &[Field] = &[At(21), Mem];
+    const CRF_A_B: &[Field] = &[At(23), At(16), At(11)];
+    const CRF_CRF: &[Field] = &[At(23), At(18)];
+    const CR_BITS: &[Field] = &[Bit(21), Bit(16), Bit(11)];
+    const PSQ_INDEXED: &[Field] = &[At(21), At(16), At(11), At(10), At(7)];
+    const PSQ: &[Field] = &[At(21), PsMem, At(15), At(12)];
+    const NONE: &[Field] = &[];
+
+    /// Every instruction the disassembler knows by its own name, encoded from the Gekko's opcode
+    /// tables, with how it should disassemble. Simplified mnemonics are checked against known
+    /// words in `simplified_mnemonics` instead.
+    const INSTRUCTIONS: &[(&str, u32, &[Field])] = &[
+        // Primary opcodes
+        ("mulli r3, r4, -0x10", primary(7), D_A_SIMM),
+        ("subfic r3, r4, 0x10", primary(8), D_A_SIMM),
+        (
+            "cmplwi cr1, r4, 0x8000",
+            primary(10),
+            &[At(23), At(16), At(0)],
+        ),
+        ("cmpwi cr1, r4, -0x1", primary(11), &[At(23), At(16), Simm]),
+        ("addic r3, r4, 0x1", primary(12), D_A_SIMM),
+        ("addic. r3, r4, 0x1", primary(13), D_A_SIMM),
+        ("addi r3, r4, 0x10", primary(14), D_A_SIMM),
+        ("addis r3, r4, -0x8000", primary(15), D_A_SIMM),
+        ("sc", primary(17) | 2, NONE),
+        (
+            "rlwimi r3, r4, 5, 6, 7",
+            primary(20),
+            &[At(16), At(21), At(11), At(6), At(1)],
+        ),
+        (
+            "rlwinm r3, r4, 5, 6, 7",
+            primary(21),
+            &[At(16), At(21), At(11), At(6), At(1)],
+        ),
+        (
+            "rlwnm r3, r4, r5, 6, 7",
+            primary(23),
+            &[At(16), At(21), At(11), At(6), At(1)],
+        ),
+        ("ori r3, r4, 0xffff", primary(24), A_S_UIMM),
+        ("oris r3, r4, 0x8000", primary(25), A_S_UIMM),
+        ("xori r3, r4, 0x1", primary(26), A_S_UIMM),
+        ("xoris r3, r4, 0x1", primary(27), A_S_UIMM),
+        ("andi. r3, r4, 0xff", primary(28), A_S_UIMM),
+        ("andis. r3, r4, 0xff", primary(29), A_S_UIMM),
+        ("lwz r3, 0x10(r4)", primary(32), LOAD_STORE),
+        ("lwzu r3, -0x10(r4)", primary(33), LOAD_STORE),
+        ("lbz r3, 0x10(r4)", primary(34), LOAD_STORE),
+        ("lbzu r3, 0x10(r4)", primary(35), LOAD_STORE),
+        ("stw r3, 0x10(r4)", primary(36), LOAD_STORE),
+        ("stwu r1, -0x10(r1)", primary(37), LOAD_STORE),
+        ("stb r3, 0x10(r4)", primary(38), LOAD_STORE),
+        ("stbu r3, 0x10(r4)", primary(39), LOAD_STORE),
+        ("lhz r3, 0x10(r4)", primary(40), LOAD_STORE),
+        ("lhzu r3, 0x10(r4)", primary(41), LOAD_STORE),
+        ("lha r3, 0x10(r4)", primary(42), LOAD_STORE),
+        ("lhau r3, 0x10(r4)", primary(43), LOAD_STORE),
+        ("sth r3, 0x10(r4)", primary(44), LOAD_STORE),
+        ("sthu r3, 0x10(r4)", primary(45), LOAD_STORE),
+        ("lmw r29, 0x14(r1)", primary(46), LOAD_STORE),
+        ("stmw r29, 0x14(r1)", primary(47), LOAD_STORE),
+        ("lfs f3, 0x10(r4)", primary(48), LOAD_STORE),
+        ("lfsu f3, 0x10(r4)", primary(49), LOAD_STORE),
+        ("lfd f3, -0x8(r4)", primary(50), LOAD_STORE),
+        ("lfdu f3, 0x10(r4)", primary(51), LOAD_STORE),
+        ("stfs f3, 0x10(r4)", primary(52), LOAD_STORE),
+        ("stfsu f3, 0x10(r4)", primary(53), LOAD_STORE),
+        ("stfd f3, 0x10(r4)", primary(54), LOAD_STORE),
+        ("stfdu f3, 0x10(r4)", primary(55), LOAD_STORE),
+        ("psq_l f3, -0x8(r4), 1, qr2", primary(56), PSQ),
+        ("psq_lu f3, 0x8(r4), 0, qr7", primary(57), PSQ),
+        ("psq_st f3, 0x7ff(r4), 1, qr2", primary(60), PSQ),
+        ("psq_stu f3, -0x800(r4), 0, qr2", primary(61), PSQ),
+        // Opcode 19, branches to registers and condition register logic
+        ("mcrf cr1, cr2", extended(19, 0), CRF_CRF),
+        ("bclr 3, cr1lt", extended(19, 16), &[At(21), Bit(16)]),
+        ("crnor cr1lt, cr2gt, cr3eq", extended(19, 33), CR_BITS),
+        ("rfi", extended(19, 50), NONE),
+        ("crandc cr1lt, cr2gt, cr3eq", extended(19, 129), CR_BITS),
+        ("isync", extended(19, 150), NONE),
+        ("crxor cr1lt, cr2gt, cr3eq", extended(19, 193), CR_BITS),
+        ("crnand cr1lt, cr2gt, cr3eq", extended(19, 225), CR_BITS),
+        ("crand cr1lt, cr2gt, cr3eq", extended(19, 257), CR_BITS),
+        ("creqv cr1lt, cr2gt, cr3eq", extended(19, 289), CR_BITS),
+        ("crorc cr1lt, cr2gt, cr3so", extended(19, 417), CR_BITS),
+        ("cror eq, cr2gt, cr3eq", extended(19, 449), CR_BITS),
+        ("bcctr 3, cr1lt", extended(19, 528), &[At(21), Bit(16)]),
+        // Opcode 31, integer arithmetic
+        ("subfc r3, r4, r5", extended(31, 8), D_A_B),
+        ("addc r3, r4, r5", extended(31, 10), D_A_B),
+        ("mulhwu r3, r4, r5", extended(31, 11), D_A_B),
+        ("subf r3, r4, r5", extended(31, 40), D_A_B),
+        ("mulhw r3, r4, r5", extended(31, 75), D_A_B),
+        ("neg r3, r4", extended(31, 104), D_A),
+        ("subfe r3, r4, r5", extended(31, 136), D_A_B),
+        ("adde r3, r4, r5", extended(31, 138), D_A_B),
+        ("subfze r3, r4", extended(31, 200), D_A),
+        ("addze r3, r4", extended(31, 202), D_A),
+        ("subfme r3, r4", extended(31, 232), D_A),
+        ("addme r3, r4", extended(31, 234), D_A),
+        ("mullw r3, r4, r5", extended(31, 235), D_A_B),
+        ("add r3, r4, r5", extended(31, 266), D_A_B),
+        ("divwu r3, r4, r5", extended(31, 459), D_A_B),
+        ("divw r3, r4, r5", extended(31, 491), D_A_B),
+        // Opcode 31, everything else
+        ("cmpw cr1, r4, r5", extended(31, 0), CRF_A_B),
+        ("tw 4, r4, r5", extended(31, 4), D_A_B),
+        ("mfcr r3", extended(31, 19), ONE),
+        ("lwarx r3, r4, r5", extended(31, 20), D_A_B),
+        ("lwzx r3, r4, r5", extended(31, 23), D_A_B),
+        ("slw r3, r4, r5", extended(31, 24), A_S_B),
+        ("cntlzw r3, r4", extended(31, 26), A_S),
+        ("and r3, r4, r5", extended(31, 28), A_S_B),
+        ("cmplw cr1, r4, r5", extended(31, 32), CRF_A_B),
+        ("dcbst r4, r5", extended(31, 54), A_B),
+        ("lwzux r3, r4, r5", extended(31, 55), D_A_B),
+        ("andc r3, r4, r5", extended(31, 60), A_S_B),
+        ("mfmsr r3", extended(31, 83), ONE),
+        ("dcbf r4, r5", extended(31, 86), A_B),
+        ("lbzx r3, r4, r5", extended(31, 87), D_A_B),
+        ("lbzux r3, r4, r5", extended(31, 119), D_A_B),
+        ("nor r3, r4, r5", extended(31, 124), A_S_B),
+        ("mtcrf 0xff, r3", extended(31, 144), &[At(12), At(21)]),
+        ("mtmsr r3", extended(31, 146), ONE),
+        ("stwcx. r3, r4, r5", extended(31, 150) | 1, D_A_B),
+        ("stwx r3, r4, r5", extended(31, 151), D_A_B),
+        ("stwux r3, r4, r5", extended(31, 183), D_A_B),
+        ("mtsr 2, r3", extended(31, 210), &[At(16), At(21)]),
+        ("stbx r3, r4, r5", extended(31, 215), D_A_B),
+        ("mtsrin r3, r5", extended(31, 242), D_B),
+        ("dcbtst r4, r5", extended(31, 246), A_B),
+        ("stbux r3, r4, r5", extended(31, 247), D_A_B),
+        ("dcbt r4, r5", extended(31, 278), A_B),
+        ("lhzx r3, r4, r5", extended(31, 279), D_A_B),
+        ("eqv r3, r4, r5", extended(31, 284), A_S_B),
+        ("tlbie r5", extended(31, 306), &[At(11)]),
+        ("eciwx r3, r4, r5", extended(31, 310), D_A_B),
+        ("lhzux r3, r4, r5", extended(31, 311), D_A_B),
+        ("xor r3, r4, r5", extended(31, 316), A_S_B),
+        ("mfspr r3, hid2", extended(31, 339), &[At(21), Spr]),
+        ("mfspr r3, 1000", extended(31, 339), &[At(21), Spr]),
+        ("lhax r3, r4, r5", extended(31, 343), D_A_B),
+        ("mftb r3, 300", extended(31, 371), &[At(21), Spr]),
+        ("lhaux r3, r4, r5", extended(31, 375), D_A_B),
+        ("sthx r3, r4, r5", extended(31, 407), D_A_B),
+        ("orc r3, r4, r5", extended(31, 412), A_S_B),
+        ("ecowx r3, r4, r5", extended(31, 438), D_A_B),
+        ("sthux r3, r4, r5", extended(31, 439), D_A_B),
+        ("or r3, r4, r5", extended(31, 444), A_S_B),
+        ("mtspr gqr1, r3", extended(31, 467), &[Spr, At(21)]),
+        ("dcbi r4, r5", extended(31, 470), A_B),
+        ("nand r3, r4, r5", extended(31, 476), A_S_B),
+        ("mcrxr cr1", extended(31, 512), &[At(23)]),
+        ("lswx r3, r4, r5", extended(31, 533), D_A_B),
+        ("lwbrx r3, r4, r5", extended(31, 534), D_A_B),
+        ("lfsx f3, r4, r5", extended(31, 535), D_A_B),
+        ("srw r3, r4, r5", extended(31, 536), A_S_B),
+        ("tlbsync", extended(31, 566), NONE),
+        ("lfsux f3, r4, r5", extended(31, 567), D_A_B),
+        ("mfsr r3, 2", extended(31, 595), D_A),
+        ("lswi r3, r4, 5", extended(31, 597), D_A_B),
+        ("sync", extended(31, 598), NONE),
+        ("lfdx f3, r4, r5", extended(31, 599), D_A_B),
+        ("lfdux f3, r4, r5", extended(31, 631), D_A_B),
+        ("mfsrin r3, r5", extended(31, 659), D_B),
+        ("stswx r3, r4, r5", extended(31, 661), D_A_B),
+        ("stwbrx r3, r4, r5", extended(31, 662), D_A_B),
+        ("stfsx f3, r4, r5", extended(31, 663), D_A_B),
+        ("stfsux f3, r4, r5", extended(31, 695), D_A_B),
+        ("stswi r3, r4, 5", extended(31, 725), D_A_B),
+        ("stfdx f3, r4, r5", extended(31, 727), D_A_B),
+        ("stfdux f3, r4, r5", extended(31, 759), D_A_B),
+        ("lhbrx r3, r4, r5", extended(31, 790), D_A_B),
+        ("sraw r3, r4, r5", extended(31, 792), A_S_B),
+        ("srawi r3, r4, 5", extended(31, 824), A_S_B),
+        ("eieio", extended(31, 854), NONE),
+        ("sthbrx r3, r4, r5", extended(31, 918), D_A_B),
+        ("extsh r3, r4", extended(31, 922), A_S),
+        ("extsb r3, r4", extended(31, 954), A_S),
+        ("icbi r4, r5", extended(31, 982), A_B),
+        ("stfiwx f3, r4, r5", extended(31, 983), D_A_B),
+        ("dcbz r4, r5", extended(31, 1014), A_B),
+        // Opcode 59, single precision floating point
+        ("fdivs f3, f4, f5", extended(59, 18), D_A_B),
+        ("fsubs f3, f4, f5", extended(59, 20), D_A_B),
+        ("fadds f3, f4, f5", extended(59, 21), D_A_B),
+        ("fres f3, f5", extended(59, 24), D_B),
+        ("fmuls f3, f4, f6", extended(59, 25), D_A_C),
+        ("fmsubs f3, f4, f6, f5", extended(59, 28), D_A_C_B),
+        ("fmadds f3, f4, f6, f5", extended(59, 29), D_A_C_B),
+        ("fnmsubs f3, f4, f6, f5", extended(59, 30), D_A_C_B),
+        ("fnmadds f3, f4, f6, f5", extended(59, 31), D_A_C_B),
+        // Opcode 63, double precision floating point
+        ("fcmpu cr1, f4, f5", extended(63, 0), CRF_A_B),
+        ("frsp f3, f5", extended(63, 12), D_B),
+        ("fctiw f3, f5", extended(63, 14), D_B),
+        ("fctiwz f3, f5", extended(63, 15), D_B),
+        ("fdiv f3, f4, f5", extended(63, 18), D_A_B),
+        ("fsub f3, f4, f5", extended(63, 20), D_A_B),
+        ("fadd f3, f4, f5", extended(63, 21), D_A_B),
+        ("fsel f3, f4, f6, f5", extended(63, 23), D_A_C_B),
+        ("fmul f3, f4, f6", extended(63, 25), D_A_C),
+        ("frsqrte f3, f5", extended(63, 26), D_B),
+        ("fmsub f3, f4, f6, f5", extended(63, 28), D_A_C_B),
+        ("fmadd f3, f4, f6, f5", extended(63, 29), D_A_C_B),
+        ("fnmsub f3, f4, f6, f5", extended(63, 30), D_A_C_B),
+        ("fnmadd f3, f4, f6, f5", extended(63, 31), D_A_C_B),
+        ("fcmpo cr1, f4, f5", extended(63, 32), CRF_A_B),
+        ("mtfsb1 3", extended(63, 38), ONE),
+        ("fneg f3, f5", extended(63, 40), D_B),
+        ("mcrfs cr1, cr2", extended(63, 64), CRF_CRF),
+        ("mtfsb0 3", extended(63, 70), ONE),
+        ("fmr f3, f5", extended(63, 72), D_B),
+        ("mtfsfi cr1, 3", extended(63, 134), &[At(23), At(12)]),
+        ("fnabs f3, f5", extended(63, 136), D_B),
+        ("fabs f3, f5", extended(63, 264), D_B),
+        ("mffs f3", extended(63, 583), ONE),
+        ("mtfsf 0xff, f5", extended(63, 711), &[At(17), At(11)]),
+        // Opcode 4, paired singles
+        ("ps_cmpu0 cr1, f4, f5", extended(4, 0), CRF_A_B),
+        ("psq_lx f3, r4, r5, 1, qr2", extended(4, 6), PSQ_INDEXED),
+        ("psq_stx f3, r4, r5, 0, qr7", extended(4, 7), PSQ_INDEXED),
+        ("ps_sum0 f3, f4, f6, f5", extended(4, 10), D_A_C_B),
+        ("ps_sum1 f3, f4, f6, f5", extended(4, 11), D_A_C_B),
+        ("ps_muls0 f3, f4, f6", extended(4, 12), D_A_C),
+        ("ps_muls1 f3, f4, f6", extended(4, 13), D_A_C),
+        ("ps_madds0 f3, f4, f6, f5", extended(4, 14), D_A_C_B),
+        ("ps_madds1 f3, f4, f6, f5", extended(4, 15), D_A_C_B),
+        ("ps_div f3, f4, f5", extended(4, 18), D_A_B),
+        ("ps_sub f3, f4, f5", extended(4, 20), D_A_B),
+        ("ps_add f3, f4, f5", extended(4, 21), D_A_B),
+        ("ps_sel f3, f4, f6, f5", extended(4, 23), D_A_C_B),
+        ("ps_res f3, f5", extended(4, 24), D_B),
+        ("ps_mul f3, f4, f6", extended(4, 25), D_A_C),
+        ("ps_rsqrte f3, f5", extended(4, 26), D_B),
+        ("ps_msub f3, f4, f6, f5", extended(4, 28), D_A_C_B),
+        ("ps_madd f3, f4, f6, f5", extended(4, 29), D_A_C_B),
+        ("ps_nmsub f3, f4, f6, f5", extended(4, 30), D_A_C_B),
+        ("ps_nmadd f3, f4, f6, f5", extended(4, 31), D_A_C_B),
+        ("ps_cmpo0 cr1, f4, f5", extended(4, 32), CRF_A_B),
+        ("psq_lux f3, r4, r5, 1, qr2", extended(4, 38), PSQ_INDEXED),
+        ("psq_stux f3, r4, r5, 1, qr2", extended(4, 39), PSQ_INDEXED),
+        ("ps_neg f3, f5", extended(4, 40), D_B),
+        ("ps_cmpu1 cr1, f4, f5", extended(4, 64), CRF_A_B),
+        ("ps_mr f3, f5", extended(4, 72), D_B),
+        ("ps_cmpo1 cr1, f4, f5", extended(4, 96), CRF_A_B),
+        ("ps_nabs f3, f5", extended(4, 136), D_B),
+        ("ps_abs f3, f5", extended(4, 264), D_B),
+        ("ps_merge00 f3, f4, f5", extended(4, 528), D_A_B),
+        ("ps_merge01 f3, f4, f5", extended(4, 560), D_A_B),
+        ("ps_merge10 f3, f4, f5", extended(4, 592), D_A_B),
+        ("ps_merge11 f3, f4, f5", extended(4, 624), D_A_B),
+        ("dcbz_l r4, r5", extended(4, 1014), A_B),
+    ];
+
+    fn find(mnemonic: &str) -> Option<(u32, &'static [Field])> {
+        INSTRUCTIONS
+            .iter()
+            .find(|(text, _, _)| text.split(' ').next() == Some(mnemonic))
+            .map(|&(_, word, fields)| (word, fields))
+    }
+
+    /// Opcode bits and operand fields for a mnemonic, allowing the record (`.`) and overflow
+    /// (`o`) forms of instructions that have them
+    fn lookup(mnemonic: &str) -> Option<(u32, &'static [Field])> {
+        let (name, record) = match mnemonic.strip_suffix('.') {
+            Some(name) if find(mnemonic).is_none() => (name, 1),
+            _ => (mnemonic, 0),
+        };
+        let (word, fields) = match find(name) {
+            Some(found) => found,
+            None => {
+                let (word, fields) = find(name.strip_suffix('o')?)?;
+                (word | 0x400, fields)
+            }
+        };
+        Some((word | record, fields))
+    }
+
+    fn number(text: &str) -> i64 {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let value = match text.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16).unwrap(),
+            None => text
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .parse()
+                .unwrap(),
+        };
+        if negative {
+            -value
+        } else {
+            value
+        }
+    }
+
+    fn memory_operand(text: &str) -> (i64, u32) {
+        let (offset, register) = text.strip_suffix(')').unwrap().split_once('(').unwrap();
+        (number(offset), number(register) as u32)
+    }
+
+    /// Encode an instruction written the way the disassembler writes it
+    fn assemble(text: &str) -> u32 {
+        let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+        let (mut word, fields) =
+            lookup(mnemonic).unwrap_or_else(|| panic!("No encoding for {mnemonic}"));
+        let operands: Vec<&str> = operands.split(", ").filter(|s| !s.is_empty()).collect();
+        assert_eq!(operands.len(), fields.len(), "{text}");
+        for (operand, field) in operands.into_iter().zip(fields) {
+            word |= match *field {
+                At(shift) => (number(operand) as u32) << shift,
+                Simm => number(operand) as u32 & 0xFFFF,
+                Mem => {
+                    let (offset, ra) = memory_operand(operand);
+                    offset as u32 & 0xFFFF | ra << 16
+                }
+                PsMem => {
+                    let (offset, ra) = memory_operand(operand);
+                    offset as u32 & 0xFFF | ra << 16
+                }
+                Spr => {
+                    let spr = (0..1024)
+                        .find(|&spr| spr_name(spr) == Some(operand))
+                        .unwrap_or_else(|| number(operand) as u32);
+                    (spr & 0x1F) << 16 | (spr >> 5) << 11
+                }
+                Bit(shift) => (0..32).find(|&bit| cr_bit(bit) == operand).unwrap() << shift,
+            };
+        }
+        word
+    }
+
+    fn text(ins: u32, address: u32) -> String {
+        let ins = disassemble(ins, address);
+        format!("{} {}", ins.mnemonic, ins.operands)
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn instructions_round_trip() {
+        for &(expected, _, _) in INSTRUCTIONS {
+            assert_eq!(text(assemble(expected), 0), expected);
+        }
+        for expected in [
+            "add. r3, r4, r5",
+            "addo r3, r4, r5",
+            "subfeo. r3, r4, r5",
+            "neg. r3, r4",
+            "and. r3, r4, r5",
+            "rlwinm. r3, r4, 5, 6, 7",
+            "srawi. r3, r4, 5",
+            "fadds. f3, f4, f5",
+            "fmadd. f3, f4, f6, f5",
+            "fabs. f3, f5",
+            "mtfsb1. 3",
+            "ps_add. f3, f4, f5",
+            "ps_merge10. f3, f4, f5",
+        ] {
+            assert_eq!(text(assemble(expected), 0), expected);
+        }
+    }
+
+    /// Every instruction each opcode table decodes has an encoding above, so none go untested
+    #[test]
+    fn opcode_tables_are_covered() {
+        let operands = 3 << 21 | 4 << 16 | 5 << 11;
+        // Branches are checked against known words below
+        let primaries = (0..64).filter(|opcode| !(16..=18).contains(opcode));
+        let words = primaries.map(|opcode| primary(opcode) | operands).chain(
+            [4, 19, 31, 59, 63]
+                .into_iter()
+                .flat_map(|opcode| (0..1024).map(move |xo| extended(opcode, xo) | operands)),
+        );
+        for word in words {
+            let ins = disassemble(word, 0);
+            if ins.mnemonic != ".word" {
+                assert!(
+                    lookup(&ins.mnemonic).is_some(),
+                    "{word:#010x} disassembles to {ins} but has no encoding"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplified_mnemonics() {
+        for (word, expected) in [
+            (0x38600000, "li r3, 0x0"),
+            (0x3C608000, "lis r3, 0x8000"),
+            (0x60000000, "nop"),
+            (0x7C641B78, "mr r4, r3"),
+            (0x7C8320F8, "not r3, r4"),
+            (0x7FE00008, "trap"),
+            (0x5483283E, "rotlwi r3, r4, 5"),
+            (0x5483103A, "slwi r3, r4, 2"),
+            (0x5483F0BE, "srwi r3, r4, 2"),
+            (0x5483063E, "clrlwi r3, r4, 24"),
+            (0x5483003A, "clrrwi r3, r4, 2"),
+            (0x4CC63182, "crclr cr1eq"),
+            (0x4CC63242, "crset cr1eq"),
+            (0x7C0802A6, "mflr r0"),
+            (0x7C0803A6, "mtlr r0"),
+            (0x7C0902A6, "mfctr r0"),
+            (0x7C0903A6, "mtctr r0"),
+            (0x7C6102A6, "mfxer r3"),
+            (0x7C6C42E6, "mftb r3"),
+            (0x7C6D42E6, "mftbu r3"),
+            (0x4E800020, "blr"),
+            (0x4E800021, "blrl"),
+            (0x4E800420, "bctr"),
+            (0x4E800421, "bctrl"),
+            (0x4D820020, "beqlr"),
+            (0x4C860020, "bnelr cr1"),
+            (0x48000010, "b 0x80003010"),
+            (0x4BFFFFF1, "bl 0x80002ff0"),
+            (0x48000102, "ba 0x100"),
+            (0x41820008, "beq 0x80003008"),
+            (0x4082FFF8, "bne 0x80002ff8"),
+            (0x41860008, "beq cr1, 0x80003008"),
+            (0x41800008, "blt 0x80003008"),
+            (0x40810008, "ble 0x80003008"),
+            (0x4200FFF0, "bdnz 0x80002ff0"),
+            (0x42400010, "bdz 0x80003010"),
+            (0x00000000, ".word 0x00000000"),
+        ] {
+            assert_eq!(text(word, 0x80003000), expected);
+        }
+    }
+
+    #[test]
+    fn branches_round_trip() {
+        for (from, to) in [
+            (0x80003000, 0x80003100),
+            (0x80003000, 0x80002000),
+            (0x80000000, 0x81FFFFFC),
+            (0x81FFFFFC, 0x80000000),
+        ] {
+            let ins = branch(from, to, false).unwrap();
+            assert_eq!(relative_branch_target(ins, from), Some(to));
+            assert_eq!(text(ins, from), format!("b {to:#x}"));
+            let ins = branch(from, to, true).unwrap();
+            assert_eq!(text(ins, from), format!("bl {to:#x}"));
+        }
+        assert!(branch(0x80000000, 0x82000000, false).is_err());
+
+        let ins = branch(0x80003000, 0x80004000, true).unwrap();
+        let moved = relocate(ins, 0x80003000, 0x80005000).unwrap();
+        assert_eq!(relative_branch_target(moved, 0x80005000), Some(0x80004000));
+        assert_eq!(moved & 1, 1);
+        assert_eq!(
+            relocate(0x38600000, 0x80003000, 0x80005000).unwrap(),
+            0x38600000
+        );
+        assert!(relocate(0x41820008, 0x80003000, 0x80005000).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// Progress reporting for operations that can take minutes, such as extracting a disc image,
+// compressing a large file, or generating a huge relocation table, so that front ends can show the
+// work moving.
+
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+/// Called with the work done so far and the total, in bytes or whatever the operation counts
+pub type ProgressFn<'a> = dyn FnMut(u64, u64) + 'a;
+
+/// Time between redraws of a progress bar
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+/// A reader or writer that reports every byte passed through it, counting on from `done`
+pub struct ProgressIo<'a, 'b, T> {
+    inner: T,
+    done: u64,
+    total: u64,
+    progress: &'a mut ProgressFn<'b>,
+}
+
+impl<'a, 'b, T> ProgressIo<'a, 'b, T> {
+    pub fn new(inner: T, done: u64, total: u64, progress: &'a mut ProgressFn<'b>) -> Self {
+        ProgressIo {
+            inner,
+            done,
+            total,
+            progress,
+        }
+    }
+
+    pub fn done(&self) -> u64 {
+        self.done
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.done += n as u64;
+        (self.progress)(self.done, self.total);
+    }
+}
+
+impl<T: Read> Read for ProgressIo<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for ProgressIo<'_, '_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for ProgressIo<'_, '_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// A progress bar on stderr, drawn only when stderr is a terminal so logs and pipes stay clean
+pub struct ProgressBar {
+    label: String,
+    /// What's counted, or `None` for bytes, which are shown in MiB
+    unit: Option<&'static str>,
+    enabled: bool,
+    last_draw: Option<Instant>,
+}
+
+impl ProgressBar {
+    pub fn new(label: impl Into<String>) -> ProgressBar {
+        ProgressBar {
+            label: label.into(),
+            unit: None,
+            enabled: io::stderr().is_terminal(),
+            last_draw: None,
+        }
+    }
+
+    /// A bar counting items such as relocations rather than bytes
+    pub fn counting(label: impl Into<String>, unit: &'static str) -> ProgressBar {
+        ProgressBar {
+            unit: Some(unit),
+            ..ProgressBar::new(label)
+        }
+    }
+
+    pub fn update(&mut self, done: u64, total: u64) {
+        if !self.enabled
+            || self
+                .last_draw
+                .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL && done < total)
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        let fraction = if total == 0 {
+            1.0
+        } else {
+            (done as f64 / total as f64).min(1.0)
+        };
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+        const MIB: f64 = 1024.0 * 1024.0;
+        let count = match self.unit {
+            Some(unit) => format!("{}/{total} {unit}", done.min(total)),
+            None => format!(
+                "{:.1}/{:.1} MiB",
+                done.min(total) as f64 / MIB,
+                total as f64 / MIB
+            ),
+        };
+        eprint!(
+            "\r{} [{}{}] {:3.0}% {count}",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            fraction * 100.0,
+        );
+    }
+
+    /// End the bar's line, so later output starts on its own
+    pub fn finish(&mut self) {
+        if self.enabled && self.last_draw.is_some() {
+            eprintln!();
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// Project file for `gctools build`, describing how a mod's linked ELF becomes a REL and then a
+// memory card file, so the whole chain runs from one command.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::gcipack::{self, GciPermissions, TextEncoding};
+
+/// Name `gctools build` looks for when no project file is given
+pub const PROJECT_FILE_NAME: &str = "gctools.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    pub rel: ProjectRel,
+    #[serde(default)]
+    pub gci: Option<ProjectGci>,
+    #[serde(default)]
+    pub push: Option<ProjectPush>,
+}
+
+/// REL built from the ELF, as elf2rel does
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectRel {
+    pub elf: PathBuf,
+    /// Game's symbol map, in `address:name` form
+    pub symbol_map: PathBuf,
+    /// Defaults to the ELF's path with a `.rel` extension
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    #[serde(default = "default_module_id")]
+    pub module_id: u32,
+    #[serde(default = "default_rel_version")]
+    pub version: u8,
+    /// Check the ELF for code and data the console can't run first
+    #[serde(default = "default_true")]
+    pub check: bool,
+    /// Fail instead of warning when the ELF defines a symbol that's also in the symbol map
+    #[serde(default)]
+    pub deny_duplicate_symbols: bool,
+    /// Input sections to rename before deciding which to keep, as elf2rel's `--rename-section`
+    /// does, such as `".hooks" = ".text"`
+    #[serde(default)]
+    pub rename_sections: BTreeMap<String, String>,
+}
+
+fn default_module_id() -> u32 {
+    0x1000
+}
+
+fn default_rel_version() -> u8 {
+    3
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Memory card file holding the REL, as gcipack builds
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectGci {
+    /// Defaults to the REL's path with a `.gci` extension
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    pub file_name: String,
+    pub title: String,
+    pub description: String,
+    /// Encoding of the title and description, `shift-jis` for Japanese saves
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    /// Left out for a save without a banner
+    #[serde(default)]
+    pub banner: Option<PathBuf>,
+    pub icon: PathBuf,
+    pub gamecode: String,
+    /// Most blocks the file may take, such as to leave room for the game's own save
+    #[serde(default)]
+    pub max_blocks: Option<u16>,
+    /// `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, or seconds since 2000-01-01. Defaults to
+    /// `SOURCE_DATE_EPOCH` if it's set, otherwise the current time.
+    #[serde(default, deserialize_with = "gcipack::deserialize_timestamp")]
+    pub timestamp: Option<u32>,
+    #[serde(default)]
+    pub permissions: GciPermissions,
+}
+
+/// Where `gctools build --push` writes the REL into a game running in Dolphin
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectPush {
+    /// Address of Dolphin's GDB stub, as set by GDBPort in Dolphin.ini
+    #[serde(default = "default_gdb_host")]
+    pub host: String,
+    pub address: u32,
+    #[serde(default)]
+    pub bss_address: Option<u32>,
+    /// Function to call once the REL is written, such as OSLink, with the REL's address and BSS
+    /// address as arguments
+    #[serde(default)]
+    pub call: Option<String>,
+}
+
+fn default_gdb_host() -> String {
+    "localhost:2345".to_string()
+}
+
+impl Project {
+    /// Make the project's paths relative to the folder it's in
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        resolve(&mut self.rel.elf);
+        resolve(&mut self.rel.symbol_map);
+        self.rel.output.iter_mut().for_each(resolve);
+        if let Some(gci) = &mut self.gci {
+            gci.output.iter_mut().for_each(resolve);
+            gci.banner.iter_mut().for_each(resolve);
+            resolve(&mut gci.icon);
+        }
+    }
+
+    /// Files the build reads, which `--watch` rebuilds on changes to
+    pub fn inputs(&self) -> Vec<&Path> {
+        let mut inputs = vec![self.rel.elf.as_path(), self.rel.symbol_map.as_path()];
+        if let Some(gci) = &self.gci {
+            inputs.extend(gci.banner.as_deref());
+            inputs.push(&gci.icon);
+        }
+        inputs
+    }
+
+    pub fn rel_path(&self) -> PathBuf {
+        self.rel
+            .output
+            .clone()
+            .unwrap_or_else(|| self.rel.elf.with_extension("rel"))
+    }
+
+    pub fn gci_path(&self) -> Option<PathBuf> {
+        let gci = self.gci.as_ref()?;
+        Some(
+            gci.output
+                .clone()
+                .unwrap_or_else(|| self.rel_path().with_extension("gci")),
+        )
+    }
+}
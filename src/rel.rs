@@ -0,0 +1,331 @@
+use anyhow::{anyhow, bail, ensure};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+use zerocopy::{big_endian, FromBytes};
+
+pub use crate::formats::rel::{
+    ImportInfo, ModuleHeader, ModuleV2HeaderAddendum, ModuleV3HeaderAddendum, Relocation,
+    SectionInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum RelocationType {
+    PpcNone,
+    PpcAddr32,
+    PpcAddr24,
+    PpcAddr16,
+    PpcAddr16Lo,
+    PpcAddr16Hi,
+    PpcAddr16Ha,
+    PpcAddr14,
+    PpcAddr14BrTaken,
+    PpcAddr14BrNkTaken,
+    PpcRel24,
+    PpcRel14,
+
+    PpcRel32 = 26,
+
+    DolphinNop = 201,
+    DolphinSection,
+    DolphinEnd,
+}
+
+/// One section of a REL. BSS sections have no data in the file.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RelSection {
+    /// Offset of the section's data from the start of the REL, or 0 for BSS and removed sections
+    pub offset: u32,
+    pub size: u32,
+    pub executable: bool,
+}
+
+impl RelSection {
+    pub fn is_bss(&self) -> bool {
+        self.offset == 0 && self.size != 0
+    }
+}
+
+/// A relocation, with the offset resolved from the delta encoding used in the file
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RelRelocation {
+    /// Section being patched
+    pub section: u8,
+    /// Offset being patched, within `section`
+    pub offset: u32,
+    pub type_: RelocationType,
+    /// Section of the target within the imported module. Unused for the DOL, module 0.
+    pub target_section: u8,
+    /// Target address for the DOL, or offset within `target_section` for a REL
+    pub addend: u32,
+}
+
+/// Relocations against one module: the DOL (module 0) or a REL
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RelImport {
+    pub module_id: u32,
+    /// File offset of the relocation entries
+    pub offset: u32,
+    pub relocations: Vec<RelRelocation>,
+}
+
+/// A reference to a function by section and offset, such as the REL's prolog
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RelFunction {
+    pub section: u8,
+    pub offset: u32,
+}
+
+/// A parsed REL module
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rel {
+    pub id: u32,
+    pub version: u32,
+    pub name_offset: u32,
+    pub name_size: u32,
+    pub sections: Vec<RelSection>,
+    pub total_bss_size: u32,
+    pub imports: Vec<RelImport>,
+    pub prolog: RelFunction,
+    pub epilog: RelFunction,
+    pub unresolved: RelFunction,
+    /// Alignment of the REL and its BSS, from version 2
+    pub max_align: u32,
+    pub max_bss_align: u32,
+    /// Size of the data that can be freed after linking, from version 3
+    pub fixed_data_size: u32,
+}
+
+fn parse_relocations(buf: &[u8], offset: usize) -> anyhow::Result<Vec<RelRelocation>> {
+    let mut relocations = Vec::new();
+    let mut section = None;
+    let mut position = 0u32;
+    let mut entries = buf
+        .get(offset..)
+        .and_then(|b| <[Relocation]>::ref_from_prefix_with_elems(b, b.len() / 8).ok())
+        .ok_or_else(|| anyhow!("Relocations extend past end of file"))?
+        .0
+        .iter();
+    loop {
+        let entry = entries
+            .next()
+            .ok_or_else(|| anyhow!("Relocations have no end marker"))?;
+        let type_ = RelocationType::try_from(entry.type_)
+            .map_err(|_| anyhow!("Unknown relocation type {}", entry.type_))?;
+        position += entry.offset.get() as u32;
+        match type_ {
+            RelocationType::DolphinEnd => break,
+            RelocationType::DolphinNop => {}
+            RelocationType::DolphinSection => {
+                section = Some(entry.section);
+                position = 0;
+            }
+            type_ => relocations.push(RelRelocation {
+                section: section.ok_or_else(|| anyhow!("Relocation before any section"))?,
+                offset: position,
+                type_,
+                target_section: entry.section,
+                addend: entry.addend.get(),
+            }),
+        }
+    }
+    Ok(relocations)
+}
+
+impl Rel {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Rel> {
+        let (header, rest) = ModuleHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("REL is too small to contain a header"))?;
+        let version = header.version.get();
+        ensure!(
+            (1..=3).contains(&version),
+            "Unsupported REL version {version}"
+        );
+        let (mut max_align, mut max_bss_align, mut fixed_data_size) = (0, 0, 0);
+        if version >= 2 {
+            let (v2, rest) = ModuleV2HeaderAddendum::ref_from_prefix(rest)
+                .map_err(|_| anyhow!("REL is too small to contain a header"))?;
+            max_align = v2.max_align.get();
+            max_bss_align = v2.max_bss_align.get();
+            if version >= 3 {
+                let (v3, _) = ModuleV3HeaderAddendum::ref_from_prefix(rest)
+                    .map_err(|_| anyhow!("REL is too small to contain a header"))?;
+                fixed_data_size = v3.fixed_data_size.get();
+            }
+        }
+
+        let section_count = header.section_count.get() as usize;
+        let section_infos = buf
+            .get(header.section_info_offset.get() as usize..)
+            .and_then(|b| <[SectionInfo]>::ref_from_prefix_with_elems(b, section_count).ok())
+            .ok_or_else(|| anyhow!("REL section table extends past end of file"))?
+            .0;
+        let mut sections = Vec::with_capacity(section_count);
+        for (i, info) in section_infos.iter().enumerate() {
+            let section = RelSection {
+                offset: info.offset.get() & !1,
+                size: info.size.get(),
+                executable: info.offset.get() & 1 != 0,
+            };
+            if section.offset != 0 && buf.len() < (section.offset + section.size) as usize {
+                bail!("REL section {i} extends past end of file");
+            }
+            sections.push(section);
+        }
+
+        let import_count = header.import_info_size.get() as usize / size_of::<ImportInfo>();
+        let import_infos = buf
+            .get(header.import_info_offset.get() as usize..)
+            .and_then(|b| <[ImportInfo]>::ref_from_prefix_with_elems(b, import_count).ok())
+            .ok_or_else(|| anyhow!("REL import table extends past end of file"))?
+            .0;
+        let mut imports = Vec::with_capacity(import_count);
+        for info in import_infos {
+            let module_id = info.id.get();
+            let relocations = parse_relocations(buf, info.offset.get() as usize).map_err(|e| {
+                e.context(format!("Invalid relocations against module {module_id}"))
+            })?;
+            imports.push(RelImport {
+                module_id,
+                offset: info.offset.get(),
+                relocations,
+            });
+        }
+
+        let function = |section: u8, offset: &big_endian::U32| RelFunction {
+            section,
+            offset: offset.get(),
+        };
+        Ok(Rel {
+            id: header.id.get(),
+            version,
+            name_offset: header.name_offset.get(),
+            name_size: header.name_size.get(),
+            sections,
+            total_bss_size: header.total_bss_size.get(),
+            imports,
+            prolog: function(header.prolog_section, &header.prolog_offset),
+            epilog: function(header.epilog_section, &header.epilog_offset),
+            unresolved: function(header.unresolved_section, &header.unresolved_offset),
+            max_align,
+            max_bss_align,
+            fixed_data_size,
+        })
+    }
+}
+
+/// One section in a [`RelManifest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelSectionManifest {
+    pub index: u8,
+    /// Offset of the section's data in the REL, or 0 for BSS and removed sections
+    pub offset: u32,
+    pub size: u32,
+    pub executable: bool,
+    pub bss: bool,
+    /// File holding the section's data, absent for sections with no data in the REL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Layout of a REL's sections, written alongside their data by relsplit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelManifest {
+    pub id: u32,
+    pub version: u32,
+    pub total_bss_size: u32,
+    pub sections: Vec<RelSectionManifest>,
+}
+
+impl Rel {
+    /// The data of a section in `buf`, the REL it was parsed from, or `None` if the section
+    /// has no data in the file
+    pub fn section_data<'a>(&self, buf: &'a [u8], section: usize) -> Option<&'a [u8]> {
+        let info = self.sections.get(section)?;
+        if info.offset == 0 || info.size == 0 {
+            return None;
+        }
+        buf.get(info.offset as usize..(info.offset + info.size) as usize)
+    }
+
+    /// The manifest for splitting the REL's sections into files, with the name of the file for
+    /// each section that has data
+    pub fn manifest(&self) -> RelManifest {
+        let sections = self
+            .sections
+            .iter()
+            .enumerate()
+            .map(|(i, section)| RelSectionManifest {
+                index: i as u8,
+                offset: section.offset,
+                size: section.size,
+                executable: section.executable,
+                bss: section.is_bss(),
+                file: (section.offset != 0 && section.size != 0).then(|| format!("section{i}.bin")),
+            })
+            .collect();
+        RelManifest {
+            id: self.id,
+            version: self.version,
+            total_bss_size: self.total_bss_size,
+            sections,
+        }
+    }
+}
+
+/// A REL and where it was loaded
+#[derive(Debug, Clone)]
+pub struct LoadedRel {
+    /// Name to show in place of a symbol, usually the file name
+    pub name: String,
+    pub rel: Rel,
+    pub address: u32,
+    /// Address of the REL's BSS, which is allocated separately from the module
+    pub bss_address: Option<u32>,
+}
+
+impl LoadedRel {
+    /// The index and start address of the section containing `address`
+    pub(crate) fn section_at(&self, address: u32) -> Option<(usize, u32)> {
+        self.rel
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.size != 0)
+            .find_map(|(i, section)| {
+                let start = if section.is_bss() {
+                    self.bss_address?
+                } else {
+                    self.address + section.offset
+                };
+                (start..start + section.size)
+                    .contains(&address)
+                    .then_some((i, start))
+            })
+    }
+
+    /// Runtime address of a section, or `None` for removed sections
+    pub fn section_address(&self, section: u8) -> anyhow::Result<Option<u32>> {
+        let info = self
+            .rel
+            .sections
+            .get(section as usize)
+            .ok_or_else(|| anyhow!("{} has no section {section}", self.name))?;
+        if info.is_bss() {
+            let bss_address = self
+                .bss_address
+                .ok_or_else(|| anyhow!("{} has BSS but no BSS address was given", self.name))?;
+            Ok(Some(bss_address))
+        } else if info.offset == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(self.address + info.offset))
+        }
+    }
+}
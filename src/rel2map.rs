@@ -0,0 +1,65 @@
+use anyhow::{anyhow, bail};
+
+use crate::elf2map;
+use crate::elf2rel::{self, RelVersion};
+use crate::rel::LoadedRel;
+use crate::symbol_map::{Symbol, SymbolMap};
+
+/// Name of the map section a REL section goes in
+fn section_kind(rel: &LoadedRel, section: usize) -> &'static str {
+    let info = &rel.rel.sections[section];
+    if info.is_bss() {
+        ".bss"
+    } else if info.executable {
+        ".text"
+    } else {
+        ".data"
+    }
+}
+
+/// Build a symbol map for a loaded REL.
+///
+/// Without `elf`, each section is given one symbol covering it, named after the REL and section
+/// index, so a debugger can at least tell which module an address is in. The BSS is included if
+/// `rel.bss_address` is set. With `elf`, the ELF the REL was built from, its global symbols are
+/// placed at their runtime addresses instead; the ELF must lay its sections out the same way as
+/// the REL.
+pub fn rel2map(rel: &LoadedRel, elf: Option<&[u8]>) -> anyhow::Result<SymbolMap> {
+    let Some(elf) = elf else {
+        let mut map = SymbolMap::default();
+        for (i, section) in rel.rel.sections.iter().enumerate() {
+            if section.size == 0 || (section.is_bss() && rel.bss_address.is_none()) {
+                continue;
+            }
+            let Some(address) = rel.section_address(i as u8)? else {
+                continue;
+            };
+            map.symbols.push(Symbol {
+                address,
+                name: format!("{}_section{i}", rel.name),
+                size: Some(section.size),
+                section: Some(section_kind(rel, i).to_string()),
+            });
+        }
+        map.sort_by_address();
+        return Ok(map);
+    };
+
+    let version = RelVersion::try_from(rel.rel.version as u8)
+        .map_err(|_| anyhow!("Unsupported REL version {}", rel.rel.version))?;
+    let map = elf2map::elf2map(elf, Some(rel.address), version)?;
+    for (index, offset) in elf2rel::rel_section_offsets(elf, version)? {
+        let matches = rel
+            .rel
+            .sections
+            .get(index.0)
+            .is_some_and(|s| s.offset as usize == offset);
+        if !matches {
+            bail!(
+                "ELF section {} doesn't match the REL's layout; was the REL built from it?",
+                index.0
+            );
+        }
+    }
+    Ok(map)
+}
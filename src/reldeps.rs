@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use anyhow::bail;
+
+use crate::rel::{Rel, RelRelocation};
+use crate::symbol_map::SymbolMap;
+
+/// Imports from one module of another, where module 0 is the DOL
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub from: u32,
+    pub to: u32,
+    pub relocations: usize,
+}
+
+/// Why relocations can't be resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DanglingReason {
+    /// No REL with the target module ID was given
+    MissingModule,
+    /// The target section doesn't exist, or the target offset is outside it
+    BadTarget,
+    /// The target DOL address isn't covered by the symbol map
+    UnknownDolAddress,
+}
+
+#[derive(Debug, Clone)]
+pub struct DanglingImport {
+    pub from: u32,
+    pub to: u32,
+    pub reason: DanglingReason,
+    pub relocation: RelRelocation,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Module IDs and names of the RELs, in the order given
+    pub modules: Vec<(u32, String)>,
+    pub dependencies: Vec<Dependency>,
+    pub dangling: Vec<DanglingImport>,
+}
+
+/// Whether a relocation lands within its target, for imports between RELs
+fn target_exists(target: &Rel, relocation: &RelRelocation) -> bool {
+    target
+        .sections
+        .get(relocation.target_section as usize)
+        .is_some_and(|s| s.size != 0 && relocation.addend <= s.size)
+}
+
+/// Build the import graph of a set of RELs. DOL imports are checked against `dol_map` when it
+/// has any symbols.
+pub fn dependency_graph(
+    rels: &[(String, Rel)],
+    dol_map: &SymbolMap,
+) -> anyhow::Result<DependencyGraph> {
+    for (i, (name, rel)) in rels.iter().enumerate() {
+        if let Some((other, _)) = rels[..i].iter().find(|(_, other)| other.id == rel.id) {
+            bail!("{name} and {other} have the same module ID {}", rel.id);
+        }
+    }
+    let mut graph = DependencyGraph {
+        modules: rels
+            .iter()
+            .map(|(name, rel)| (rel.id, name.clone()))
+            .collect(),
+        ..Default::default()
+    };
+
+    for (_, rel) in rels {
+        for import in &rel.imports {
+            graph.dependencies.push(Dependency {
+                from: rel.id,
+                to: import.module_id,
+                relocations: import.relocations.len(),
+            });
+            let target = rels.iter().find(|(_, r)| r.id == import.module_id);
+            for relocation in &import.relocations {
+                let reason = match (import.module_id, target) {
+                    (0, _) if dol_map.symbols.is_empty() => None,
+                    (0, _) => dol_map
+                        .find_enclosing(relocation.addend)
+                        .is_none()
+                        .then_some(DanglingReason::UnknownDolAddress),
+                    // Relocations against the REL itself name its own sections
+                    (id, _) if id == rel.id => {
+                        (!target_exists(rel, relocation)).then_some(DanglingReason::BadTarget)
+                    }
+                    (_, None) => Some(DanglingReason::MissingModule),
+                    (_, Some((_, target))) => {
+                        (!target_exists(target, relocation)).then_some(DanglingReason::BadTarget)
+                    }
+                };
+                if let Some(reason) = reason {
+                    graph.dangling.push(DanglingImport {
+                        from: rel.id,
+                        to: import.module_id,
+                        reason,
+                        relocation: *relocation,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(graph)
+}
+
+impl DependencyGraph {
+    fn module_name(&self, id: u32) -> String {
+        match id {
+            0 => "DOL".to_string(),
+            id => self
+                .modules
+                .iter()
+                .find(|(m, _)| *m == id)
+                .map_or_else(|| format!("module {id}"), |(_, name)| name.clone()),
+        }
+    }
+
+    /// Write the graph in Graphviz format, with dangling modules drawn dashed
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph rels {\n");
+        for dependency in &self.dependencies {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                self.module_name(dependency.from),
+                self.module_name(dependency.to),
+                dependency.relocations
+            )
+            .unwrap();
+        }
+        for dangling in &self.dangling {
+            if dangling.reason == DanglingReason::MissingModule {
+                writeln!(
+                    out,
+                    "    \"{}\" [style=dashed];",
+                    self.module_name(dangling.to)
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Write the graph as a list of each module's imports, followed by any dangling imports
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (id, name) in &self.modules {
+            writeln!(out, "{name} (module {id})").unwrap();
+            for dependency in self.dependencies.iter().filter(|d| d.from == *id) {
+                writeln!(
+                    out,
+                    "    imports from {}: {} relocations",
+                    self.module_name(dependency.to),
+                    dependency.relocations
+                )
+                .unwrap();
+            }
+        }
+        if !self.dangling.is_empty() {
+            writeln!(out, "\nDangling imports:").unwrap();
+        }
+        for dangling in &self.dangling {
+            let relocation = &dangling.relocation;
+            let what = match dangling.reason {
+                DanglingReason::MissingModule => "module isn't loaded",
+                DanglingReason::BadTarget => "target is outside the section",
+                DanglingReason::UnknownDolAddress => "address isn't in the DOL map",
+            };
+            let target = match dangling.to {
+                0 => format!("{:08X}", relocation.addend),
+                _ => format!(
+                    "section {}+{:#x}",
+                    relocation.target_section, relocation.addend
+                ),
+            };
+            writeln!(
+                out,
+                "    {} section {}+{:#x} -> {} {target}: {what}",
+                self.module_name(dangling.from),
+                relocation.section,
+                relocation.offset,
+                self.module_name(dangling.to),
+            )
+            .unwrap();
+        }
+        out
+    }
+}
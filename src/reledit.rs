@@ -0,0 +1,145 @@
+use anyhow::{anyhow, ensure};
+use zerocopy::FromBytes;
+
+use crate::rel::{ImportInfo, ModuleHeader};
+
+/// The REL's import table, for editing in place
+fn imports_mut(buf: &mut [u8]) -> anyhow::Result<&mut [ImportInfo]> {
+    let (header, _) = ModuleHeader::ref_from_prefix(buf)
+        .map_err(|_| anyhow!("REL is too small to contain a header"))?;
+    let offset = header.import_info_offset.get() as usize;
+    let count = header.import_info_size.get() as usize / size_of::<ImportInfo>();
+    Ok(buf
+        .get_mut(offset..)
+        .and_then(|b| <[ImportInfo]>::mut_from_prefix_with_elems(b, count).ok())
+        .ok_or_else(|| anyhow!("REL import table extends past end of file"))?
+        .0)
+}
+
+/// Change a REL's module id in place, along with the id of its import against itself, so a
+/// prebuilt REL can be given an id that doesn't clash with a project's other modules. Returns
+/// the number of imports changed.
+pub fn set_module_id(buf: &mut [u8], id: u32) -> anyhow::Result<usize> {
+    ensure!(id != 0, "Module id 0 is reserved for the DOL");
+    let old_id = ModuleHeader::ref_from_prefix(buf)
+        .map_err(|_| anyhow!("REL is too small to contain a header"))?
+        .0
+        .id
+        .get();
+    if old_id == id {
+        return Ok(0);
+    }
+
+    let imports = imports_mut(buf)?;
+    ensure!(
+        imports.iter().all(|import| import.id.get() != id),
+        "REL already imports from module {id}, which would become a self-reference"
+    );
+    let mut changed = 0;
+    for import in imports
+        .iter_mut()
+        .filter(|import| import.id.get() == old_id)
+    {
+        import.id = id.into();
+        changed += 1;
+    }
+    let (header, _) = ModuleHeader::mut_from_prefix(buf).unwrap();
+    header.id = id.into();
+    Ok(changed)
+}
+
+/// Retarget a REL's imports from module `from` to module `to` in place, for when a dependency's
+/// id changes. Relocations against the DOL give absolute addresses rather than section offsets,
+/// so imports can't be moved to or from module 0, and the REL's import against itself moves with
+/// its id in `set_module_id` instead. Returns the number of imports changed.
+pub fn retarget_imports(buf: &mut [u8], from: u32, to: u32) -> anyhow::Result<usize> {
+    ensure!(
+        from != 0 && to != 0,
+        "Imports can't be retargeted to or from the DOL"
+    );
+    let id = ModuleHeader::ref_from_prefix(buf)
+        .map_err(|_| anyhow!("REL is too small to contain a header"))?
+        .0
+        .id
+        .get();
+    ensure!(
+        from != id && to != id,
+        "Module {id} is the REL itself; use set-id to change its id"
+    );
+    let imports = imports_mut(buf)?;
+    ensure!(
+        imports.iter().any(|import| import.id.get() == from),
+        "REL doesn't import from module {from}"
+    );
+    if from == to {
+        return Ok(0);
+    }
+    ensure!(
+        imports.iter().all(|import| import.id.get() != to),
+        "REL already imports from module {to}"
+    );
+    let mut changed = 0;
+    for import in imports.iter_mut().filter(|import| import.id.get() == from) {
+        import.id = to.into();
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use super::*;
+
+    /// A REL with id 5 importing from the DOL, itself, and module 7
+    fn rel() -> Vec<u8> {
+        let header = ModuleHeader {
+            id: 5.into(),
+            import_info_offset: (size_of::<ModuleHeader>() as u32).into(),
+            import_info_size: (3 * size_of::<ImportInfo>() as u32).into(),
+            ..Default::default()
+        };
+        let mut buf = header.as_bytes().to_vec();
+        for id in [0u32, 5, 7] {
+            let import = ImportInfo {
+                id: id.into(),
+                ..Default::default()
+            };
+            buf.extend_from_slice(import.as_bytes());
+        }
+        buf
+    }
+
+    fn import_ids(buf: &mut [u8]) -> Vec<u32> {
+        imports_mut(buf)
+            .unwrap()
+            .iter()
+            .map(|i| i.id.get())
+            .collect()
+    }
+
+    #[test]
+    fn retarget() {
+        let mut buf = rel();
+        assert_eq!(retarget_imports(&mut buf, 7, 9).unwrap(), 1);
+        assert_eq!(import_ids(&mut buf), [0, 5, 9]);
+    }
+
+    #[test]
+    fn retarget_rejects_own_id() {
+        let mut buf = rel();
+        assert!(retarget_imports(&mut buf, 5, 9).is_err());
+        assert!(retarget_imports(&mut buf, 7, 5).is_err());
+        assert!(retarget_imports(&mut buf, 0, 9).is_err());
+        assert_eq!(import_ids(&mut buf), [0, 5, 7]);
+    }
+
+    #[test]
+    fn set_id_moves_self_import() {
+        let mut buf = rel();
+        assert_eq!(set_module_id(&mut buf, 6).unwrap(), 1);
+        assert_eq!(import_ids(&mut buf), [0, 6, 7]);
+        assert!(set_module_id(&mut buf, 7).is_err());
+    }
+}
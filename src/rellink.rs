@@ -0,0 +1,198 @@
+use anyhow::{anyhow, bail, ensure, Context};
+use zerocopy::{big_endian, FromBytes};
+
+use crate::rel::{ImportInfo, LoadedRel, ModuleHeader, RelRelocation, RelocationType, SectionInfo};
+
+/// An import that couldn't be applied because its module isn't loaded
+#[derive(Debug, Clone)]
+pub struct UnresolvedRelocation {
+    pub module_id: u32,
+    /// Address being patched
+    pub address: u32,
+    pub relocation: RelRelocation,
+}
+
+/// A REL as it is in memory after OSLink
+#[derive(Debug, Clone)]
+pub struct LinkedRel {
+    pub image: Vec<u8>,
+    pub unresolved: Vec<UnresolvedRelocation>,
+}
+
+/// Patch the instruction or data at `offset` in `image`, which is loaded at `address`, the way
+/// OSLink does for a relocation with target address `target`
+fn apply_relocation(
+    image: &mut [u8],
+    offset: usize,
+    address: u32,
+    type_: RelocationType,
+    target: u32,
+) -> anyhow::Result<()> {
+    let width = match type_ {
+        RelocationType::PpcAddr16
+        | RelocationType::PpcAddr16Lo
+        | RelocationType::PpcAddr16Hi
+        | RelocationType::PpcAddr16Ha => 2,
+        _ => 4,
+    };
+    let bytes = image
+        .get_mut(offset..offset + width)
+        .ok_or_else(|| anyhow!("Relocation at {address:08X} is outside the REL"))?;
+    let word = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap());
+    match type_ {
+        RelocationType::PpcNone => {}
+        RelocationType::PpcAddr32 => bytes.copy_from_slice(&target.to_be_bytes()),
+        RelocationType::PpcAddr24 => {
+            let value = (word(bytes) & !0x03FFFFFC) | (target & 0x03FFFFFC);
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+        RelocationType::PpcAddr16 | RelocationType::PpcAddr16Lo => {
+            bytes.copy_from_slice(&(target as u16).to_be_bytes())
+        }
+        RelocationType::PpcAddr16Hi => {
+            bytes.copy_from_slice(&((target >> 16) as u16).to_be_bytes())
+        }
+        RelocationType::PpcAddr16Ha => {
+            let ha = (target >> 16) as u16 + ((target & 0x8000) != 0) as u16;
+            bytes.copy_from_slice(&ha.to_be_bytes());
+        }
+        RelocationType::PpcAddr14
+        | RelocationType::PpcAddr14BrTaken
+        | RelocationType::PpcAddr14BrNkTaken => {
+            let value = (word(bytes) & !0xFFFC) | (target & 0xFFFC);
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+        RelocationType::PpcRel24 => {
+            let delta = target.wrapping_sub(address);
+            let value = (word(bytes) & !0x03FFFFFC) | (delta & 0x03FFFFFC);
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+        RelocationType::PpcRel14 => {
+            let delta = target.wrapping_sub(address);
+            let value = (word(bytes) & !0xFFFC) | (delta & 0xFFFC);
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+        RelocationType::PpcRel32 => {
+            bytes.copy_from_slice(&target.wrapping_sub(address).to_be_bytes())
+        }
+        RelocationType::DolphinNop
+        | RelocationType::DolphinSection
+        | RelocationType::DolphinEnd => {
+            bail!("Unexpected relocation type {type_:?}")
+        }
+    }
+    Ok(())
+}
+
+/// Link a REL the way OSLink does when loading it at `rel.address`: apply its relocations against
+/// the DOL and against every module in `loaded`, and convert the header's offsets to addresses.
+/// `buf` is the REL file, and `loaded` may include `rel` itself. Imports from modules that aren't
+/// loaded are left unapplied and reported, as OSLink applies them once that module is linked.
+pub fn rellink(buf: &[u8], rel: &LoadedRel, loaded: &[LoadedRel]) -> anyhow::Result<LinkedRel> {
+    let mut image = buf.to_vec();
+    let mut unresolved = Vec::new();
+
+    for import in &rel.rel.imports {
+        let target_module = match import.module_id {
+            0 => None,
+            id if id == rel.rel.id => Some(rel),
+            id => match loaded.iter().find(|m| m.rel.id == id) {
+                Some(module) => Some(module),
+                None => {
+                    for relocation in &import.relocations {
+                        let address = rel.section_address(relocation.section)?.unwrap_or(0)
+                            + relocation.offset;
+                        unresolved.push(UnresolvedRelocation {
+                            module_id: id,
+                            address,
+                            relocation: *relocation,
+                        });
+                    }
+                    continue;
+                }
+            },
+        };
+
+        for relocation in &import.relocations {
+            let section = &rel.rel.sections[relocation.section as usize];
+            ensure!(
+                !section.is_bss() && section.offset != 0,
+                "Relocation in section {} of {}, which isn't in the file",
+                relocation.section,
+                rel.name
+            );
+            let offset = (section.offset + relocation.offset) as usize;
+            let address = rel.address + section.offset + relocation.offset;
+            let target = match target_module {
+                None => relocation.addend,
+                Some(module) => module
+                    .section_address(relocation.target_section)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Relocation at {address:08X} targets removed section {} of {}",
+                            relocation.target_section,
+                            module.name
+                        )
+                    })?
+                    .wrapping_add(relocation.addend),
+            };
+            apply_relocation(&mut image, offset, address, relocation.type_, target)?;
+        }
+    }
+
+    link_header(&mut image, rel).context("Invalid REL header")?;
+    Ok(LinkedRel { image, unresolved })
+}
+
+/// Convert the header's offsets to addresses, as OSLink does
+fn link_header(image: &mut [u8], rel: &LoadedRel) -> anyhow::Result<()> {
+    let function_address = |section: u8, offset: u32| -> anyhow::Result<u32> {
+        Ok(match section {
+            0 => 0,
+            section => rel.section_address(section)?.unwrap_or(0) + offset,
+        })
+    };
+    let prolog = function_address(rel.rel.prolog.section, rel.rel.prolog.offset)?;
+    let epilog = function_address(rel.rel.epilog.section, rel.rel.epilog.offset)?;
+    let unresolved = function_address(rel.rel.unresolved.section, rel.rel.unresolved.offset)?;
+
+    let (header, _) = ModuleHeader::mut_from_prefix(image)
+        .map_err(|_| anyhow!("REL is too small to contain a header"))?;
+    let section_info_offset = header.section_info_offset.get() as usize;
+    let import_info_offset = header.import_info_offset.get() as usize;
+    let import_count = header.import_info_size.get() as usize / size_of::<ImportInfo>();
+    let relocate = |offset: &mut big_endian::U32| {
+        if offset.get() != 0 {
+            *offset = (offset.get() + rel.address).into();
+        }
+    };
+    relocate(&mut header.section_info_offset);
+    relocate(&mut header.name_offset);
+    relocate(&mut header.import_info_offset);
+    relocate(&mut header.relocation_offset);
+    header.prolog_offset = prolog.into();
+    header.epilog_offset = epilog.into();
+    header.unresolved_offset = unresolved.into();
+
+    let (imports, _) = image
+        .get_mut(import_info_offset..)
+        .and_then(|b| <[ImportInfo]>::mut_from_prefix_with_elems(b, import_count).ok())
+        .ok_or_else(|| anyhow!("REL import table extends past end of file"))?;
+    for import in imports {
+        import.offset = (import.offset.get() + rel.address).into();
+    }
+
+    let count = rel.rel.sections.len();
+    let (infos, _) = image
+        .get_mut(section_info_offset..)
+        .and_then(|b| <[SectionInfo]>::mut_from_prefix_with_elems(b, count).ok())
+        .ok_or_else(|| anyhow!("REL section table extends past end of file"))?;
+    for (section, info) in rel.rel.sections.iter().zip(infos.iter_mut()) {
+        if section.is_bss() {
+            info.offset = rel.bss_address.unwrap_or(0).into();
+        } else if section.offset != 0 {
+            info.offset = (info.offset.get() + rel.address).into();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure};
+use object::SectionIndex;
+use zerocopy::IntoBytes;
+
+use crate::elf2rel::{self, ElfRelocation};
+use crate::rel::{
+    ModuleHeader, ModuleV2HeaderAddendum, ModuleV3HeaderAddendum, Rel, RelFunction, RelSection,
+    RelocationType, SectionInfo,
+};
+
+/// A REL made by merging two others
+#[derive(Debug, Clone)]
+pub struct MergedRel {
+    pub data: Vec<u8>,
+    /// The second REL's prolog, epilog, and unresolved functions, in the merged REL's sections.
+    /// The loader only calls the first REL's, so these have to be called from those.
+    pub second_prolog: RelFunction,
+    pub second_epilog: RelFunction,
+    pub second_unresolved: RelFunction,
+}
+
+/// Copy the data of a REL's sections to the end of `out`, keeping their layout relative to each
+/// other, since relative branches between them were applied when the REL was built. Returns
+/// the old and new offsets of the start of the data.
+fn copy_sections(out: &mut Vec<u8>, buf: &[u8], rel: &Rel, align: u32) -> (u32, u32) {
+    let data_sections = rel.sections.iter().filter(|s| s.offset != 0 && s.size != 0);
+    let Some(start) = data_sections.clone().map(|s| s.offset).min() else {
+        return (0, 0);
+    };
+    let end = data_sections.map(|s| s.offset + s.size).max().unwrap();
+    // Keep the data's alignment
+    let new_start = (out.len() as u32).next_multiple_of(align) + start % align;
+    out.resize(new_start as usize, 0);
+    out.extend_from_slice(&buf[start as usize..end as usize]);
+    (start, new_start)
+}
+
+/// Merge two RELs into one module with the first REL's id, or `id` if given, as if they had been
+/// built from one ELF. The first REL keeps its section indices, and the second's non-empty
+/// sections follow them. Imports between the two become imports of the merged module against
+/// itself, with relative branches between them applied in place.
+pub fn merge_rels(
+    first_buf: &[u8],
+    second_buf: &[u8],
+    id: Option<u32>,
+) -> anyhow::Result<MergedRel> {
+    let first = Rel::parse(first_buf).map_err(|e| e.context("Invalid first REL"))?;
+    let second = Rel::parse(second_buf).map_err(|e| e.context("Invalid second REL"))?;
+    ensure!(
+        first.id != second.id,
+        "Both RELs have module id {}",
+        first.id
+    );
+    let id = id.unwrap_or(first.id);
+    ensure!(id != 0, "Module id 0 is reserved for the DOL");
+
+    let version = first.version.max(second.version);
+    let mut header_size = size_of::<ModuleHeader>();
+    if version >= 2 {
+        header_size += size_of::<ModuleV2HeaderAddendum>();
+    }
+    if version >= 3 {
+        header_size += size_of::<ModuleV3HeaderAddendum>();
+    }
+    let align = first.max_align.max(second.max_align).max(32);
+
+    let mut rel = vec![0; header_size];
+    let first_start = copy_sections(&mut rel, first_buf, &first, align);
+    let second_start = copy_sections(&mut rel, second_buf, &second, align);
+    let moved = |section: &RelSection, (old_start, new_start): (u32, u32)| RelSection {
+        offset: match section.offset {
+            0 => 0,
+            offset => offset - old_start + new_start,
+        },
+        ..section.clone()
+    };
+
+    // The second REL's sections go after the first's, leaving out empty ones
+    let mut sections: Vec<RelSection> = first
+        .sections
+        .iter()
+        .map(|s| moved(s, first_start))
+        .collect();
+    let mut second_indices = vec![None; second.sections.len()];
+    for (i, section) in second.sections.iter().enumerate().skip(1) {
+        if section.size != 0 {
+            second_indices[i] = Some(sections.len());
+            sections.push(moved(section, second_start));
+        }
+    }
+    ensure!(
+        sections.len() <= 256,
+        "Merged REL would have {} sections, more than the 256 a REL can have",
+        sections.len()
+    );
+    let second_section = |section: u8| -> anyhow::Result<SectionIndex> {
+        second_indices
+            .get(section as usize)
+            .copied()
+            .flatten()
+            .map(SectionIndex)
+            .ok_or_else(|| anyhow!("Second REL has a relocation against empty section {section}"))
+    };
+
+    let mut relocations = Vec::new();
+    for (module, is_second) in [(&first, false), (&second, true)] {
+        for import in &module.imports {
+            let dest_module = match import.module_id {
+                m if m == first.id || m == second.id => id,
+                m => m,
+            };
+            for relocation in &import.relocations {
+                let src_section = match is_second {
+                    true => second_section(relocation.section)?,
+                    false => SectionIndex(relocation.section as usize),
+                };
+                let dest_section = match import.module_id {
+                    m if m == second.id => second_section(relocation.target_section)?,
+                    _ => SectionIndex(relocation.target_section as usize),
+                };
+                relocations.push(ElfRelocation {
+                    src_section,
+                    src_offset: relocation.offset,
+                    dest_module,
+                    dest_section,
+                    addend: relocation.addend,
+                    type_: relocation.type_,
+                });
+            }
+        }
+    }
+
+    let section_offsets: HashMap<SectionIndex, usize> = sections
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.offset != 0)
+        .map(|(i, s)| (SectionIndex(i), s.offset as usize))
+        .collect();
+    for relocation in &relocations {
+        if relocation.dest_module == id
+            && matches!(
+                relocation.type_,
+                RelocationType::PpcRel24 | RelocationType::PpcRel32
+            )
+            && !section_offsets.contains_key(&relocation.dest_section)
+        {
+            bail!(
+                "Relative relocation against section {}, which isn't in the file",
+                relocation.dest_section.0
+            );
+        }
+    }
+    relocations.sort_unstable();
+
+    rel.resize(rel.len().next_multiple_of(4), 0);
+    let section_info_offset = rel.len();
+    for section in &sections {
+        let info = SectionInfo {
+            offset: (section.offset | section.executable as u32).into(),
+            size: section.size.into(),
+        };
+        rel.extend_from_slice(info.as_bytes());
+    }
+    let relocation_stats =
+        elf2rel::write_relocations(&mut rel, &relocations, id, &section_offsets)?;
+
+    let second_function = |function: RelFunction| -> anyhow::Result<RelFunction> {
+        Ok(match function.section {
+            0 => function,
+            section => RelFunction {
+                section: second_section(section)?.0 as u8,
+                offset: function.offset,
+            },
+        })
+    };
+    let header = ModuleHeader {
+        id: id.into(),
+        prev_link: 0.into(),
+        next_link: 0.into(),
+        section_count: (sections.len() as u32).into(),
+        section_info_offset: (section_info_offset as u32).into(),
+        name_offset: first.name_offset.into(),
+        name_size: first.name_size.into(),
+        version: version.into(),
+        total_bss_size: (first.total_bss_size + second.total_bss_size).into(),
+        relocation_offset: relocation_stats.relocations_offset.into(),
+        import_info_offset: relocation_stats.import_info_offset.into(),
+        import_info_size: relocation_stats.import_info_size.into(),
+        prolog_section: first.prolog.section,
+        epilog_section: first.epilog.section,
+        unresolved_section: first.unresolved.section,
+        pad: 0,
+        prolog_offset: first.prolog.offset.into(),
+        epilog_offset: first.epilog.offset.into(),
+        unresolved_offset: first.unresolved.offset.into(),
+    };
+    let mut header_bytes = header.as_bytes().to_vec();
+    if version >= 2 {
+        let header_v2 = ModuleV2HeaderAddendum {
+            max_align: first.max_align.max(second.max_align).max(2).into(),
+            max_bss_align: first.max_bss_align.max(second.max_bss_align).max(2).into(),
+        };
+        header_bytes.extend_from_slice(header_v2.as_bytes());
+    }
+    if version >= 3 {
+        let header_v3 = ModuleV3HeaderAddendum {
+            fixed_data_size: relocation_stats.relocations_offset.into(),
+        };
+        header_bytes.extend_from_slice(header_v3.as_bytes());
+    }
+    rel[..header_size].copy_from_slice(&header_bytes);
+
+    Ok(MergedRel {
+        data: rel,
+        second_prolog: second_function(second.prolog)?,
+        second_epilog: second_function(second.epilog)?,
+        second_unresolved: second_function(second.unresolved)?,
+    })
+}
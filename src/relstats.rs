@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use zerocopy::FromBytes;
+
+use crate::rel::{Rel, Relocation, RelocationType};
+
+/// Relocations patching one section
+#[derive(Debug, Clone)]
+pub struct SectionRelocations {
+    pub section: u8,
+    pub size: u32,
+    pub relocations: usize,
+}
+
+impl SectionRelocations {
+    /// Relocations per KiB of section data
+    pub fn density(&self) -> f64 {
+        match self.size {
+            0 => 0.0,
+            size => self.relocations as f64 * 1024.0 / size as f64,
+        }
+    }
+}
+
+/// A run of consecutive `DolphinNop` entries, which pad the relocation stream when the gap
+/// between two patched offsets is too large for one entry
+#[derive(Debug, Clone)]
+pub struct NopRun {
+    pub module_id: u32,
+    pub section: Option<u8>,
+    /// Offset within `section` where the run starts
+    pub offset: u32,
+    pub entries: usize,
+    /// Bytes the run skips
+    pub skipped: u32,
+}
+
+/// Relocation counts for a REL
+#[derive(Debug, Clone)]
+pub struct RelocationStats {
+    pub id: u32,
+    pub total: usize,
+    pub sections: Vec<SectionRelocations>,
+    /// Counts by type, most common first
+    pub by_type: Vec<(RelocationType, usize)>,
+    /// Counts by the module the relocations target, most common first
+    pub by_module: Vec<(u32, usize)>,
+    /// `DolphinNop` runs, longest first
+    pub nop_runs: Vec<NopRun>,
+}
+
+fn count<K: PartialEq>(counts: &mut Vec<(K, usize)>, key: K) {
+    match counts.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, n)) => *n += 1,
+        None => counts.push((key, 1)),
+    }
+}
+
+/// Find the `DolphinNop` runs in the relocation entries at `offset`, which the parsed relocations
+/// leave out
+fn nop_runs(buf: &[u8], module_id: u32, offset: usize) -> anyhow::Result<Vec<NopRun>> {
+    let entries = buf
+        .get(offset..)
+        .and_then(|b| <[Relocation]>::ref_from_prefix_with_elems(b, b.len() / 8).ok())
+        .ok_or_else(|| anyhow!("Relocations extend past end of file"))?
+        .0;
+    let mut runs = Vec::new();
+    let mut run: Option<NopRun> = None;
+    let mut section = None;
+    let mut position = 0u32;
+    for entry in entries {
+        let type_ = RelocationType::try_from(entry.type_).ok();
+        if type_ == Some(RelocationType::DolphinNop) {
+            let run = run.get_or_insert(NopRun {
+                module_id,
+                section,
+                offset: position,
+                entries: 0,
+                skipped: 0,
+            });
+            run.entries += 1;
+            run.skipped += entry.offset.get() as u32;
+        } else {
+            runs.extend(run.take());
+        }
+        position += entry.offset.get() as u32;
+        match type_ {
+            Some(RelocationType::DolphinEnd) | None => break,
+            Some(RelocationType::DolphinSection) => {
+                section = Some(entry.section);
+                position = 0;
+            }
+            _ => {}
+        }
+    }
+    Ok(runs)
+}
+
+/// Count a REL's relocations by the section they patch, their type, and the module they target.
+/// `buf` is the REL file `rel` was parsed from.
+pub fn relocation_stats(buf: &[u8], rel: &Rel) -> anyhow::Result<RelocationStats> {
+    let mut sections: Vec<SectionRelocations> = rel
+        .sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| SectionRelocations {
+            section: i as u8,
+            size: section.size,
+            relocations: 0,
+        })
+        .collect();
+    let mut by_type = Vec::new();
+    let mut by_module = Vec::new();
+    let mut runs = Vec::new();
+    let mut total = 0;
+
+    for import in &rel.imports {
+        for relocation in &import.relocations {
+            if let Some(section) = sections.get_mut(relocation.section as usize) {
+                section.relocations += 1;
+            }
+            count(&mut by_type, relocation.type_);
+            count(&mut by_module, import.module_id);
+            total += 1;
+        }
+        runs.extend(nop_runs(buf, import.module_id, import.offset as usize)?);
+    }
+
+    sections.retain(|s| s.relocations != 0);
+    by_type.sort_by_key(|&(_, n)| Reverse(n));
+    by_module.sort_by_key(|&(_, n)| Reverse(n));
+    runs.sort_by(|a, b| b.entries.cmp(&a.entries).then(b.skipped.cmp(&a.skipped)));
+    Ok(RelocationStats {
+        id: rel.id,
+        total,
+        sections,
+        by_type,
+        by_module,
+        nop_runs: runs,
+    })
+}
+
+impl RelocationStats {
+    /// A readable report, listing at most `max_nop_runs` of the longest `DolphinNop` runs
+    pub fn to_text(&self, max_nop_runs: usize) -> String {
+        let mut out = String::new();
+        writeln!(out, "{} relocations", self.total).unwrap();
+
+        writeln!(out, "\nBy section:").unwrap();
+        for section in &self.sections {
+            writeln!(
+                out,
+                "    section {}: {} relocations in {:#x} bytes ({:.1} per KiB)",
+                section.section,
+                section.relocations,
+                section.size,
+                section.density()
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "\nBy type:").unwrap();
+        for (type_, n) in &self.by_type {
+            writeln!(out, "    {type_:?}: {n}").unwrap();
+        }
+
+        writeln!(out, "\nBy target module:").unwrap();
+        for (module_id, n) in &self.by_module {
+            let note = match *module_id {
+                0 => " (DOL)",
+                id if id == self.id => " (self)",
+                _ => "",
+            };
+            writeln!(out, "    module {module_id}{note}: {n}").unwrap();
+        }
+
+        if !self.nop_runs.is_empty() {
+            writeln!(
+                out,
+                "\nLongest DolphinNop runs ({} in total):",
+                self.nop_runs.len()
+            )
+            .unwrap();
+        }
+        for run in self.nop_runs.iter().take(max_nop_runs) {
+            let location = match run.section {
+                Some(section) => format!("section {section}+{:#x}", run.offset),
+                None => "before any section".to_string(),
+            };
+            writeln!(
+                out,
+                "    module {} {location}: {} entries skipping {:#x} bytes",
+                run.module_id, run.entries, run.skipped
+            )
+            .unwrap();
+        }
+        out
+    }
+}
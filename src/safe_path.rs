@@ -0,0 +1,51 @@
+// Checks on names read from disc images and archives before they're joined onto an output
+// directory, so a crafted file can't write outside of it.
+
+use std::path::{Component, Path};
+
+use anyhow::ensure;
+
+/// Check that `name` is a single plain file or directory name: not empty, `.`, or `..`, without
+/// separators, and not absolute or a drive prefix
+pub fn check_name(name: &str) -> anyhow::Result<()> {
+    let mut components = Path::new(name).components();
+    ensure!(
+        !name.contains(['/', '\\'])
+            && matches!(components.next(), Some(Component::Normal(_)))
+            && components.next().is_none(),
+        "Invalid name '{name}'"
+    );
+    Ok(())
+}
+
+/// Check that every component of a `/`-separated relative path is a plain name, so the path stays
+/// within the directory it's joined onto
+pub fn check_relative_path(path: &str) -> anyhow::Result<()> {
+    ensure!(!path.is_empty(), "Empty path");
+    for name in path.split('/') {
+        check_name(name).map_err(|_| anyhow::anyhow!("Invalid path '{path}'"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_names() {
+        assert!(check_name("opening.bnr").is_ok());
+        assert!(check_name("..bnr").is_ok());
+        assert!(check_relative_path("audio/bgm.dsp").is_ok());
+    }
+
+    #[test]
+    fn traversal() {
+        for name in ["", ".", "..", "a/b", "a\\b", "/etc"] {
+            assert!(check_name(name).is_err(), "{name}");
+        }
+        for path in ["", "/a", "a//b", "a/../../b", "a/", "./a"] {
+            assert!(check_relative_path(path).is_err(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub address: u32,
+    pub name: String,
+    pub size: Option<u32>,
+    /// Section name such as `.text`, where the map format records it
+    pub section: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolMap {
+    pub fn sort_by_address(&mut self) {
+        self.symbols
+            .sort_by(|a, b| a.address.cmp(&b.address).then(a.name.cmp(&b.name)));
+    }
+
+    pub fn addresses_by_name(&self) -> HashMap<&str, u32> {
+        self.symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.address))
+            .collect()
+    }
+
+    /// Find the symbol with the highest address at or below `address`. Symbols with a known size
+    /// must also contain the address.
+    pub fn find_enclosing(&self, address: u32) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|s| s.address <= address)
+            .filter(|s| s.size.is_none_or(|size| address - s.address < size))
+            .max_by_key(|s| s.address)
+    }
+
+    /// Resolve an address expression: a symbol name, or a `0x`-prefixed or 8 digit hex address,
+    /// optionally followed by `+offset` or `-offset`. A symbol whose name looks like an address
+    /// wins over the address.
+    pub fn resolve(&self, expr: &str) -> anyhow::Result<u32> {
+        let expr = expr.trim();
+        let split = expr
+            .rfind(['+', '-'])
+            .filter(|&pos| pos > 0)
+            .and_then(|pos| Some((pos, parse_int(&expr[pos + 1..]).ok()?)));
+        let (base, offset) = match split {
+            Some((pos, offset)) if expr.as_bytes()[pos] == b'-' => {
+                (expr[..pos].trim(), offset.wrapping_neg())
+            }
+            Some((pos, offset)) => (expr[..pos].trim(), offset),
+            None => (expr, 0),
+        };
+
+        let symbol = self.symbols.iter().find(|s| s.name == base);
+        let address = match symbol.map(|s| s.address).or_else(|| parse_address(base)) {
+            Some(address) => address,
+            None => bail!("Symbol '{base}' not found in symbol map"),
+        };
+        Ok(address.wrapping_add(offset))
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hex integer
+pub fn parse_int(s: &str) -> anyhow::Result<u32> {
+    let s = s.trim();
+    let value = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16)?,
+        None => s.parse()?,
+    };
+    Ok(value)
+}
+
+fn parse_address(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    if s.len() == 8 {
+        return u32::from_str_radix(s, 16).ok();
+    }
+    None
+}
+
+/// Parse a symbol map in the `address:name` format, one symbol per line
+pub fn parse_lst(buf: &[u8]) -> anyhow::Result<SymbolMap> {
+    let mut map = SymbolMap::default();
+    let s = std::str::from_utf8(buf).context("Failed to parse symbol map as UTF-8")?;
+
+    for (line_num, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let (addr, name) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid symbol mapping on line {}: {}", line_num + 1, line))?;
+        if name.is_empty() {
+            bail!("Empty symbol name on line {}", line_num + 1);
+        }
+        let addr = u32::from_str_radix(addr.trim(), 16).with_context(|| {
+            format!("Failed to parse address on line {}: {}", line_num + 1, addr)
+        })?;
+        map.symbols.push(Symbol {
+            address: addr,
+            name: name.to_string(),
+            size: None,
+            section: None,
+        });
+    }
+
+    Ok(map)
+}
+
+pub fn write_lst(map: &SymbolMap) -> String {
+    let mut out = String::new();
+    for symbol in &map.symbols {
+        writeln!(out, "{:08X}:{}", symbol.address, symbol.name).unwrap();
+    }
+    out
+}
+
+/// Symbols grouped by section, in order of first appearance. Symbols without a section are
+/// assumed to be in `.text`.
+fn by_section(map: &SymbolMap) -> Vec<(&str, Vec<&Symbol>)> {
+    let mut sections: Vec<(&str, Vec<&Symbol>)> = Vec::new();
+    for symbol in &map.symbols {
+        let section = symbol.section.as_deref().unwrap_or(".text");
+        match sections.iter_mut().find(|(name, _)| *name == section) {
+            Some((_, symbols)) => symbols.push(symbol),
+            None => sections.push((section, vec![symbol])),
+        }
+    }
+    sections
+}
+
+/// Write a map in the format produced by Dolphin's "Save Symbol Map"
+pub fn write_dolphin_map(map: &SymbolMap) -> String {
+    let mut out = String::new();
+    for (i, (section, symbols)) in by_section(map).into_iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        writeln!(out, "{section} section layout").unwrap();
+        for symbol in symbols {
+            writeln!(
+                out,
+                "{:08x} {:08x} {:08x} 0 {}",
+                symbol.address,
+                symbol.size.unwrap_or(0),
+                symbol.address,
+                symbol.name
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Write a map in the layout of a CodeWarrior linker map. Object file names aren't known, so
+/// that column is left empty.
+pub fn write_codewarrior_map(map: &SymbolMap) -> String {
+    let mut out = String::new();
+    for (section, symbols) in by_section(map) {
+        writeln!(out, "\n\n{section} section layout").unwrap();
+        writeln!(out, "  Starting        Virtual").unwrap();
+        writeln!(out, "  address  Size   address").unwrap();
+        writeln!(out, "  -----------------------").unwrap();
+        let start = symbols.iter().map(|s| s.address).min().unwrap_or(0);
+        for symbol in symbols {
+            writeln!(
+                out,
+                "  {:08x} {:06x} {:08x}  4 {}",
+                symbol.address - start,
+                symbol.size.unwrap_or(0),
+                symbol.address,
+                symbol.name
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse a map with `section layout` headers, as written by CodeWarrior's linker or Dolphin.
+/// Each symbol line has a section offset, size, virtual address, optionally a file offset, then
+/// an alignment and the symbol name. Entries for whole sections and unused functions are skipped.
+pub fn parse_map(buf: &[u8]) -> anyhow::Result<SymbolMap> {
+    let mut map = SymbolMap::default();
+    let s = std::str::from_utf8(buf).context("Failed to parse symbol map as UTF-8")?;
+    let mut section: Option<&str> = None;
+
+    for line in s.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if line.trim_end().ends_with("section layout") {
+            section = fields.first().copied();
+            continue;
+        }
+        // Other parts of the map, such as the memory map, end the section
+        if fields.first().is_some_and(|f| f.ends_with(':')) {
+            section = None;
+            continue;
+        }
+        let Some(section) = section else {
+            continue;
+        };
+        let [_, size, address, rest @ ..] = &fields[..] else {
+            continue;
+        };
+        if !is_hex(size) || !is_hex(address) || address.len() != 8 {
+            continue;
+        }
+        // Skip the file offset column, when present, and the alignment
+        let name = match rest {
+            [file_offset, _, name, ..] if file_offset.len() == 8 && is_hex(file_offset) => name,
+            [_, name, ..] => name,
+            _ => continue,
+        };
+        if *name == section {
+            continue;
+        }
+        map.symbols.push(Symbol {
+            address: u32::from_str_radix(address, 16)?,
+            name: name.to_string(),
+            size: Some(u32::from_str_radix(size, 16)?).filter(|&s| s != 0),
+            section: Some(section.to_string()),
+        });
+    }
+
+    Ok(map)
+}
+
+/// Split a CSV line into fields, handling double-quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Parse a CSV symbol map. The header line names the columns: `address` and `name` are required,
+/// `size` and `section` are optional.
+pub fn parse_csv(buf: &[u8]) -> anyhow::Result<SymbolMap> {
+    let mut map = SymbolMap::default();
+    let s = std::str::from_utf8(buf).context("Failed to parse symbol map as UTF-8")?;
+    let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| anyhow!("CSV symbol map is empty"))?;
+    let header = split_csv(header);
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let (Some(address_column), Some(name_column)) = (column("address"), column("name")) else {
+        bail!("CSV symbol map must have address and name columns");
+    };
+    let (size_column, section_column) = (column("size"), column("section"));
+
+    for (line_num, line) in lines {
+        let fields = split_csv(line);
+        let field = |i: usize| fields.get(i).map(|f| f.trim()).unwrap_or_default();
+        let address = parse_address(field(address_column))
+            .or_else(|| u32::from_str_radix(field(address_column), 16).ok())
+            .ok_or_else(|| anyhow!("Invalid address on line {}", line_num + 1))?;
+        let name = field(name_column);
+        if name.is_empty() {
+            bail!("Empty symbol name on line {}", line_num + 1);
+        }
+        let size = match size_column.map(field).filter(|s| !s.is_empty()) {
+            Some(size) => Some(
+                parse_int(size)
+                    .with_context(|| format!("Invalid size on line {}", line_num + 1))?,
+            ),
+            None => None,
+        };
+        map.symbols.push(Symbol {
+            address,
+            name: name.to_string(),
+            size,
+            section: section_column
+                .map(field)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        });
+    }
+
+    Ok(map)
+}
+
+pub fn write_csv(map: &SymbolMap) -> String {
+    let mut out = String::from("address,size,section,name\n");
+    for symbol in &map.symbols {
+        writeln!(
+            out,
+            "0x{:08X},{},{},{}",
+            symbol.address,
+            symbol.size.map(|s| format!("{s:#x}")).unwrap_or_default(),
+            csv_field(symbol.section.as_deref().unwrap_or_default()),
+            csv_field(&symbol.name)
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn is_plain_symbol_name(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'))
+}
+
+/// Write a GNU ld script with a `PROVIDE` for each symbol, so code linked with it can refer to
+/// the game's symbols directly
+pub fn write_linker_script(map: &SymbolMap) -> String {
+    let mut out = String::new();
+    for symbol in &map.symbols {
+        if is_plain_symbol_name(&symbol.name) {
+            writeln!(out, "PROVIDE({} = {:#010x});", symbol.name, symbol.address).unwrap();
+        } else {
+            writeln!(
+                out,
+                "PROVIDE(\"{}\" = {:#010x});",
+                symbol.name, symbol.address
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Write `--defsym` arguments for each symbol, one per line, to pass to ld as an `@file`
+pub fn write_defsyms(map: &SymbolMap) -> String {
+    let mut out = String::new();
+    for symbol in &map.symbols {
+        if is_plain_symbol_name(&symbol.name) {
+            writeln!(out, "--defsym {}={:#010x}", symbol.name, symbol.address).unwrap();
+        } else {
+            writeln!(out, "--defsym '{}={:#010x}'", symbol.name, symbol.address).unwrap();
+        }
+    }
+    out
+}
+
+/// Parse a symbol map in any supported format, detected from its contents
+pub fn parse(buf: &[u8]) -> anyhow::Result<SymbolMap> {
+    let s = String::from_utf8_lossy(buf);
+    let first_line = s.lines().find(|l| !l.trim().is_empty()).unwrap_or_default();
+    if s.contains("section layout") {
+        parse_map(buf)
+    } else if first_line.to_ascii_lowercase().contains("address,")
+        || first_line.to_ascii_lowercase().contains(",address")
+    {
+        parse_csv(buf)
+    } else {
+        parse_lst(buf)
+    }
+}
+
+/// A disagreement between maps being merged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The same name at different addresses. `kept` is the symbol from the map with precedence.
+    Address { kept: Symbol, dropped: Symbol },
+    /// Different names at the same address
+    Name { kept: Symbol, dropped: Symbol },
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conflict::Address { kept, dropped } => write!(
+                f,
+                "{} is at {:08X}, but also at {:08X}",
+                kept.name, kept.address, dropped.address
+            ),
+            Conflict::Name { kept, dropped } => write!(
+                f,
+                "{:08X} is named {}, but also {}",
+                kept.address, kept.name, dropped.name
+            ),
+        }
+    }
+}
+
+/// Merge maps, given in order of precedence. A symbol that conflicts with one already merged is
+/// dropped and reported. Symbols present in several maps are merged, with any missing size or
+/// section filled in from the others.
+pub fn merge(maps: &[SymbolMap]) -> (SymbolMap, Vec<Conflict>) {
+    let mut merged = SymbolMap::default();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut by_address: HashMap<u32, usize> = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for symbol in maps.iter().flat_map(|m| &m.symbols) {
+        if let Some(&i) = by_name.get(&symbol.name) {
+            let existing = &mut merged.symbols[i];
+            if existing.address == symbol.address {
+                existing.size = existing.size.or(symbol.size);
+                existing.section = existing.section.take().or(symbol.section.clone());
+            } else {
+                conflicts.push(Conflict::Address {
+                    kept: existing.clone(),
+                    dropped: symbol.clone(),
+                });
+            }
+            continue;
+        }
+        if let Some(&i) = by_address.get(&symbol.address) {
+            conflicts.push(Conflict::Name {
+                kept: merged.symbols[i].clone(),
+                dropped: symbol.clone(),
+            });
+            continue;
+        }
+        by_name.insert(symbol.name.clone(), merged.symbols.len());
+        by_address.insert(symbol.address, merged.symbols.len());
+        merged.symbols.push(symbol.clone());
+    }
+
+    merged.sort_by_address();
+    (merged, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> SymbolMap {
+        parse_lst(b"80003100:main\n80004000:deadbeef\n80005000:cafe\n").unwrap()
+    }
+
+    #[test]
+    fn resolves_names_and_addresses() {
+        let map = map();
+        assert_eq!(map.resolve("main").unwrap(), 0x80003100);
+        assert_eq!(map.resolve("main+0x10").unwrap(), 0x80003110);
+        assert_eq!(map.resolve("main - 4").unwrap(), 0x800030FC);
+        assert_eq!(map.resolve("0x80001234").unwrap(), 0x80001234);
+        assert_eq!(map.resolve("80001234").unwrap(), 0x80001234);
+        assert!(map.resolve("missing").is_err());
+    }
+
+    #[test]
+    fn hex_looking_names_are_symbols() {
+        let map = map();
+        assert_eq!(map.resolve("deadbeef").unwrap(), 0x80004000);
+        assert_eq!(map.resolve("deadbeef+8").unwrap(), 0x80004008);
+        assert_eq!(map.resolve("cafe").unwrap(), 0x80005000);
+        assert_eq!(map.resolve("0xdeadbeef").unwrap(), 0xDEADBEEF);
+    }
+}
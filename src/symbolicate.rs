@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use crate::rel::LoadedRel;
+use crate::symbol_map::{Symbol, SymbolMap};
+
+#[derive(Debug, Clone)]
+pub enum Location<'a> {
+    Symbol {
+        symbol: &'a Symbol,
+        offset: u32,
+    },
+    RelSection {
+        rel: &'a LoadedRel,
+        section: usize,
+        offset: u32,
+    },
+}
+
+impl Display for Location<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Symbol { symbol, offset } => write!(f, "{}+{:#x}", symbol.name, offset),
+            Location::RelSection {
+                rel,
+                section,
+                offset,
+            } => write!(f, "{} section {}+{:#x}", rel.name, section, offset),
+        }
+    }
+}
+
+/// Find the symbol or REL section containing `address`. A symbol is preferred, unless it's
+/// outside the REL section containing the address, as is the case when the symbol map only
+/// covers the DOL.
+pub fn symbolicate<'a>(
+    address: u32,
+    map: &'a SymbolMap,
+    rels: &'a [LoadedRel],
+) -> Option<Location<'a>> {
+    let rel_section = rels
+        .iter()
+        .find_map(|rel| rel.section_at(address).map(|(i, start)| (rel, i, start)));
+    let symbol = map
+        .find_enclosing(address)
+        .filter(|s| rel_section.is_none_or(|(_, _, start)| s.address >= start));
+    match (symbol, rel_section) {
+        (Some(symbol), _) => Some(Location::Symbol {
+            symbol,
+            offset: address - symbol.address,
+        }),
+        (None, Some((rel, section, start))) => Some(Location::RelSection {
+            rel,
+            section,
+            offset: address - start,
+        }),
+        (None, None) => None,
+    }
+}
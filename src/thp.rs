@@ -0,0 +1,375 @@
+// THP movies: a JPEG per frame, optionally with a run of DSP-ADPCM audio per frame that has its
+// own coefficients.
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{self, History, COEF_PAIRS};
+
+const MAGIC: &[u8; 4] = b"THP\0";
+const VERSION_1_0: u32 = 0x10000;
+const VERSION_1_1: u32 = 0x11000;
+const HEADER_SIZE: usize = 0x30;
+const COMPONENT_VIDEO: u8 = 0;
+const COMPONENT_AUDIO: u8 = 1;
+const COMPONENT_NONE: u8 = 0xff;
+const MAX_COMPONENTS: usize = 16;
+const AUDIO_HEADER_SIZE: usize = 0x50;
+const ALIGN: usize = 0x20;
+
+/// Written next to the frames of an unpacked movie
+pub const MANIFEST_NAME: &str = "thp.toml";
+
+/// Settings of an unpacked movie that its frames and audio don't carry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThpManifest {
+    pub fps: f32,
+    /// WAV in the same folder with the movie's audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThpAudio {
+    pub sample_rate: u32,
+    /// Samples of each channel
+    pub channels: Vec<Vec<i16>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Thp {
+    pub fps: f32,
+    pub width: u32,
+    pub height: u32,
+    /// A baseline JPEG per frame
+    pub frames: Vec<Vec<u8>>,
+    pub audio: Option<ThpAudio>,
+}
+
+fn be32(buf: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = buf
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("THP is truncated at {offset:#x}"))?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be16(buf: &[u8], offset: usize) -> anyhow::Result<i16> {
+    let bytes = buf
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow!("THP is truncated at {offset:#x}"))?;
+    Ok(i16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// First audio sample of a frame
+fn sample_at(frame: usize, sample_rate: u32, fps: f32) -> usize {
+    (frame as f64 * sample_rate as f64 / fps as f64).round() as usize
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(ALIGN), 0);
+}
+
+/// Width and height of a baseline JPEG, the only kind the THP decoder plays
+pub fn jpeg_size(jpeg: &[u8]) -> anyhow::Result<(u32, u32)> {
+    ensure!(jpeg.starts_with(&[0xff, 0xd8]), "Not a JPEG");
+    let mut offset = 2;
+    while offset + 4 <= jpeg.len() {
+        ensure!(jpeg[offset] == 0xff, "Invalid JPEG marker at {offset:#x}");
+        let marker = jpeg[offset + 1];
+        let length = u16::from_be_bytes([jpeg[offset + 2], jpeg[offset + 3]]) as usize;
+        match marker {
+            0xc0 => {
+                let segment = jpeg
+                    .get(offset + 4..offset + 9)
+                    .ok_or_else(|| anyhow!("JPEG is truncated"))?;
+                let height = u16::from_be_bytes([segment[1], segment[2]]);
+                let width = u16::from_be_bytes([segment[3], segment[4]]);
+                return Ok((width as u32, height as u32));
+            }
+            0xc1..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                bail!("Only baseline JPEGs can be played, not progressive or lossless ones")
+            }
+            0xda => break,
+            _ => offset += 2 + length,
+        }
+    }
+    bail!("JPEG has no frame header")
+}
+
+/// A JPEG without the padding after its end marker
+fn trim_jpeg(data: &[u8]) -> &[u8] {
+    match data.windows(2).rposition(|w| w == [0xff, 0xd9]) {
+        Some(end) => &data[..end + 2],
+        None => data,
+    }
+}
+
+impl Thp {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Thp> {
+        ensure!(
+            buf.len() >= HEADER_SIZE && buf.starts_with(MAGIC),
+            "Not a THP file"
+        );
+        let version = be32(buf, 0x04)?;
+        ensure!(
+            version == VERSION_1_0 || version == VERSION_1_1,
+            "Unsupported THP version {version:#x}"
+        );
+        let fps = f32::from_bits(be32(buf, 0x10)?);
+        let frame_count = be32(buf, 0x14)? as usize;
+        let first_frame_size = be32(buf, 0x18)? as usize;
+        let component_offset = be32(buf, 0x20)? as usize;
+        let first_frame_offset = be32(buf, 0x28)? as usize;
+
+        let component_count = be32(buf, component_offset)? as usize;
+        ensure!(
+            component_count <= MAX_COMPONENTS,
+            "THP has {component_count} components"
+        );
+        let types = buf
+            .get(component_offset + 4..component_offset + 4 + MAX_COMPONENTS)
+            .ok_or_else(|| anyhow!("THP is truncated"))?;
+        let mut info_offset = component_offset + 4 + MAX_COMPONENTS;
+        let (mut width, mut height) = (0, 0);
+        let mut audio_info = None;
+        for &kind in &types[..component_count] {
+            match kind {
+                COMPONENT_VIDEO => {
+                    width = be32(buf, info_offset)?;
+                    height = be32(buf, info_offset + 4)?;
+                    info_offset += if version == VERSION_1_0 { 8 } else { 12 };
+                }
+                COMPONENT_AUDIO => {
+                    let channel_count = be32(buf, info_offset)?;
+                    let sample_rate = be32(buf, info_offset + 4)?;
+                    if version == VERSION_1_1 {
+                        let tracks = be32(buf, info_offset + 12)?;
+                        ensure!(
+                            tracks <= 1,
+                            "THPs with {tracks} audio tracks aren't supported"
+                        );
+                    }
+                    ensure!(
+                        (1..=2).contains(&channel_count),
+                        "THP audio has {channel_count} channels"
+                    );
+                    audio_info = Some((channel_count as usize, sample_rate));
+                    info_offset += if version == VERSION_1_0 { 12 } else { 16 };
+                }
+                COMPONENT_NONE => {}
+                kind => bail!("Unknown THP component type {kind}"),
+            }
+        }
+
+        let mut frames = Vec::with_capacity(frame_count);
+        let mut audio = audio_info.map(|(channel_count, sample_rate)| ThpAudio {
+            sample_rate,
+            channels: vec![Vec::new(); channel_count],
+        });
+        let mut offset = first_frame_offset;
+        let mut frame_size = first_frame_size;
+        for i in 0..frame_count {
+            let frame = buf
+                .get(offset..offset + frame_size)
+                .ok_or_else(|| anyhow!("Frame {i} is truncated"))?;
+            let next_size = be32(frame, 0)? as usize;
+            let mut data_offset = 8 + 4 * component_count;
+            for (c, &kind) in types[..component_count].iter().enumerate() {
+                let size = be32(frame, 8 + 4 * c)? as usize;
+                let data = frame
+                    .get(data_offset..data_offset + size)
+                    .ok_or_else(|| anyhow!("Frame {i} is truncated"))?;
+                match kind {
+                    COMPONENT_VIDEO => frames.push(trim_jpeg(data).to_vec()),
+                    COMPONENT_AUDIO => {
+                        if let Some(audio) = &mut audio {
+                            decode_audio(data, &mut audio.channels)
+                                .with_context(|| format!("Invalid audio in frame {i}"))?;
+                        }
+                    }
+                    _ => {}
+                }
+                data_offset += size;
+            }
+            offset += frame_size;
+            frame_size = next_size;
+        }
+
+        Ok(Thp {
+            fps,
+            width,
+            height,
+            frames,
+            audio,
+        })
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        ensure!(!self.frames.is_empty(), "THP has no frames");
+        ensure!(self.fps > 0.0, "Invalid frame rate {}", self.fps);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let size = jpeg_size(frame).with_context(|| format!("Invalid frame {i}"))?;
+            ensure!(
+                size == (self.width, self.height),
+                "Frame {i} is {}x{}, but the movie is {}x{}",
+                size.0,
+                size.1,
+                self.width,
+                self.height
+            );
+        }
+
+        let mut components = vec![COMPONENT_VIDEO];
+        let mut component_info = Vec::new();
+        component_info.extend_from_slice(&self.width.to_be_bytes());
+        component_info.extend_from_slice(&self.height.to_be_bytes());
+        // Progressive scan
+        component_info.extend_from_slice(&0u32.to_be_bytes());
+        if let Some(audio) = &self.audio {
+            ensure!(
+                (1..=2).contains(&audio.channels.len()),
+                "THP audio can only have one or two channels"
+            );
+            components.push(COMPONENT_AUDIO);
+            let sample_count = sample_at(self.frames.len(), audio.sample_rate, self.fps) as u32;
+            for field in [
+                audio.channels.len() as u32,
+                audio.sample_rate,
+                sample_count,
+                1,
+            ] {
+                component_info.extend_from_slice(&field.to_be_bytes());
+            }
+        }
+
+        // Each frame's audio runs from where the previous one's stopped, up to the frame's end
+        let mut frame_data = Vec::with_capacity(self.frames.len());
+        let mut max_audio_samples = 0;
+        let mut histories = vec![History::default(); 2];
+        for (i, jpeg) in self.frames.iter().enumerate() {
+            let mut video = jpeg.clone();
+            pad(&mut video);
+            let mut sizes = vec![video.len() as u32];
+            let mut data = video;
+            if let Some(audio) = &self.audio {
+                let start = sample_at(i, audio.sample_rate, self.fps);
+                let end = sample_at(i + 1, audio.sample_rate, self.fps);
+                max_audio_samples = max_audio_samples.max(end - start);
+                let block = encode_audio(&audio.channels, start, end, &mut histories);
+                sizes.push(block.len() as u32);
+                data.extend_from_slice(&block);
+            }
+            let mut frame = Vec::with_capacity(8 + 4 * sizes.len() + data.len());
+            // Filled in with the next and previous frames' sizes below
+            frame.extend_from_slice(&[0; 8]);
+            sizes
+                .iter()
+                .for_each(|s| frame.extend_from_slice(&s.to_be_bytes()));
+            frame.extend_from_slice(&data);
+            pad(&mut frame);
+            frame_data.push(frame);
+        }
+        let frame_sizes: Vec<u32> = frame_data.iter().map(|f| f.len() as u32).collect();
+        let count = frame_data.len();
+        for (i, frame) in frame_data.iter_mut().enumerate() {
+            // The last frame points back at the first, for looping playback
+            let next = frame_sizes[(i + 1) % count];
+            let previous = frame_sizes[(i + count - 1) % count];
+            frame[0..4].copy_from_slice(&next.to_be_bytes());
+            frame[4..8].copy_from_slice(&previous.to_be_bytes());
+        }
+
+        let mut component_data = Vec::new();
+        component_data.extend_from_slice(&(components.len() as u32).to_be_bytes());
+        let mut types = [COMPONENT_NONE; MAX_COMPONENTS];
+        types[..components.len()].copy_from_slice(&components);
+        component_data.extend_from_slice(&types);
+        component_data.extend_from_slice(&component_info);
+        pad(&mut component_data);
+
+        let first_frame_offset = HEADER_SIZE + component_data.len();
+        let data_size: u32 = frame_sizes.iter().sum();
+        let last_frame_offset = first_frame_offset as u32 + data_size - frame_sizes[count - 1];
+        let mut out = Vec::with_capacity(first_frame_offset + data_size as usize);
+        out.extend_from_slice(MAGIC);
+        for field in [
+            VERSION_1_1,
+            *frame_sizes.iter().max().unwrap(),
+            max_audio_samples as u32,
+            self.fps.to_bits(),
+            count as u32,
+            frame_sizes[0],
+            data_size,
+            HEADER_SIZE as u32,
+            // No table of frame offsets
+            0,
+            first_frame_offset as u32,
+            last_frame_offset,
+        ] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        out.extend_from_slice(&component_data);
+        frame_data.iter().for_each(|f| out.extend_from_slice(f));
+        Ok(out)
+    }
+}
+
+/// Decode a frame's audio block, appending each channel's samples
+fn decode_audio(block: &[u8], channels: &mut [Vec<i16>]) -> anyhow::Result<()> {
+    let channel_size = be32(block, 0)? as usize;
+    let sample_count = be32(block, 4)? as usize;
+    for (c, channel) in channels.iter_mut().enumerate() {
+        let mut coefs = [[0; 2]; COEF_PAIRS];
+        for (i, coef) in coefs.iter_mut().flatten().enumerate() {
+            *coef = be16(block, 0x08 + c * 0x20 + i * 2)?;
+        }
+        let mut history = History {
+            yn1: be16(block, 0x48 + c * 4)?,
+            yn2: be16(block, 0x4a + c * 4)?,
+        };
+        let start = AUDIO_HEADER_SIZE + c * channel_size;
+        let data = block
+            .get(start..start + channel_size)
+            .ok_or_else(|| anyhow!("Audio block is truncated"))?;
+        let samples = dsp::decode(data, &coefs, sample_count, &mut history);
+        ensure!(samples.len() == sample_count, "Audio block is truncated");
+        channel.extend(samples);
+    }
+    Ok(())
+}
+
+/// Encode samples `start` to `end` of each channel as a frame's audio block, with coefficients
+/// of its own. Samples past the end of the audio are silent.
+fn encode_audio(
+    channels: &[Vec<i16>],
+    start: usize,
+    end: usize,
+    histories: &mut [History],
+) -> Vec<u8> {
+    let sample_count = end - start;
+    let channel_size = (sample_count.div_ceil(dsp::SAMPLES_PER_FRAME) * dsp::BYTES_PER_FRAME)
+        .next_multiple_of(ALIGN);
+    let mut block = vec![0; AUDIO_HEADER_SIZE];
+    block[0..4].copy_from_slice(&(channel_size as u32).to_be_bytes());
+    block[4..8].copy_from_slice(&(sample_count as u32).to_be_bytes());
+    for (c, (channel, history)) in channels.iter().zip(histories).enumerate() {
+        let mut samples: Vec<i16> = channel
+            .iter()
+            .skip(start)
+            .take(sample_count)
+            .copied()
+            .collect();
+        samples.resize(sample_count, 0);
+        let coefs = dsp::calculate_coefs(&samples);
+        for (i, coef) in coefs.iter().flatten().enumerate() {
+            let offset = 0x08 + c * 0x20 + i * 2;
+            block[offset..offset + 2].copy_from_slice(&coef.to_be_bytes());
+        }
+        block[0x48 + c * 4..0x4a + c * 4].copy_from_slice(&history.yn1.to_be_bytes());
+        block[0x4a + c * 4..0x4c + c * 4].copy_from_slice(&history.yn2.to_be_bytes());
+        let mut data = dsp::encode(&samples, &coefs, history);
+        data.resize(channel_size, 0);
+        block.extend_from_slice(&data);
+    }
+    block
+}
@@ -0,0 +1,308 @@
+use anyhow::{anyhow, ensure, Context};
+use zerocopy::{big_endian, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::gcimage::{encode_texture, max_image_count};
+pub use crate::gcimage::{MipmapFilter, PaletteFormat, TextureFormat, Tlut, WrapMode};
+
+pub const TPL_MAGIC: u32 = 0x0020AF30;
+const ALIGNMENT: usize = 0x20;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawHeader {
+    magic: big_endian::U32,
+    image_count: big_endian::U32,
+    image_table_offset: big_endian::U32,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawImageTableEntry {
+    image_header_offset: big_endian::U32,
+    palette_header_offset: big_endian::U32,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawImageHeader {
+    height: big_endian::U16,
+    width: big_endian::U16,
+    format: big_endian::U32,
+    data_offset: big_endian::U32,
+    wrap_s: big_endian::U32,
+    wrap_t: big_endian::U32,
+    min_filter: big_endian::U32,
+    mag_filter: big_endian::U32,
+    lod_bias: big_endian::F32,
+    edge_lod_enable: u8,
+    min_lod: u8,
+    max_lod: u8,
+    unpacked: u8,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RawPaletteHeader {
+    entry_count: big_endian::U16,
+    unpacked: u8,
+    padding: u8,
+    format: big_endian::U32,
+    data_offset: big_endian::U32,
+}
+
+/// Linear filtering, the usual choice for both minification and magnification
+const FILTER_LINEAR: u32 = 1;
+/// The first of the minification filters that sample mipmaps
+const FILTER_NEAR_MIPMAP_NEAR: u32 = 2;
+/// Trilinear filtering
+const FILTER_LINEAR_MIPMAP_LINEAR: u32 = 5;
+
+/// One texture in a TPL file
+#[derive(Debug, Clone)]
+pub struct TplImage {
+    pub format: TextureFormat,
+    pub width: usize,
+    pub height: usize,
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub min_filter: u32,
+    pub mag_filter: u32,
+    pub lod_bias: f32,
+    pub edge_lod: bool,
+    pub min_lod: u8,
+    pub max_lod: u8,
+    /// Encoded texture data
+    pub data: Vec<u8>,
+    /// Palette for the color indexed formats
+    pub palette: Option<Tlut>,
+}
+
+impl TplImage {
+    /// Encode RGBA8 pixels with default sampler settings. Color indexed formats get a palette
+    /// in `palette_format`. With a mipmap filter, a full mipmap chain is generated and the
+    /// texture is set up for trilinear filtering.
+    pub fn from_rgba(
+        format: TextureFormat,
+        palette_format: PaletteFormat,
+        rgba: &[u8],
+        width: usize,
+        height: usize,
+        mipmap_filter: Option<MipmapFilter>,
+    ) -> anyhow::Result<TplImage> {
+        ensure!(
+            (1..=1024).contains(&width) && (1..=1024).contains(&height),
+            "Texture dimensions must be between 1 and 1024, got {width}x{height}"
+        );
+        let encoded = encode_texture(format, rgba, width, height, palette_format, mipmap_filter)?;
+        Ok(TplImage {
+            format,
+            width,
+            height,
+            wrap_s: WrapMode::default(),
+            wrap_t: WrapMode::default(),
+            min_filter: if encoded.image_count > 1 {
+                FILTER_LINEAR_MIPMAP_LINEAR
+            } else {
+                FILTER_LINEAR
+            },
+            mag_filter: FILTER_LINEAR,
+            lod_bias: 0.0,
+            edge_lod: false,
+            min_lod: 0,
+            max_lod: (encoded.image_count - 1) as u8,
+            data: encoded.data,
+            palette: encoded.tlut,
+        })
+    }
+
+    /// Number of images stored, the full size texture followed by its mipmaps. Mipmaps are only
+    /// used with a mipmapping minification filter.
+    pub fn image_count(&self) -> usize {
+        if self.min_filter >= FILTER_NEAR_MIPMAP_NEAR {
+            self.max_lod as usize + 1
+        } else {
+            1
+        }
+    }
+
+    /// Decode the texture to linear RGBA8
+    pub fn to_rgba(&self) -> anyhow::Result<Vec<u8>> {
+        self.format
+            .decode(&self.data, self.width, self.height, self.palette.as_ref())
+    }
+}
+
+fn parse_palette(buf: &[u8], offset: usize) -> anyhow::Result<Tlut> {
+    let raw = buf
+        .get(offset..)
+        .and_then(|h| RawPaletteHeader::ref_from_prefix(h).ok())
+        .ok_or_else(|| anyhow!("Palette header extends past end of file"))?
+        .0;
+    let format = PaletteFormat::try_from(raw.format.get())
+        .map_err(|_| anyhow!("Unknown palette format {}", raw.format))?;
+    let start = raw.data_offset.get() as usize;
+    let data = buf
+        .get(start..start + raw.entry_count.get() as usize * 2)
+        .ok_or_else(|| anyhow!("Palette data extends past end of file"))?;
+    Ok(Tlut {
+        format,
+        data: data.to_vec(),
+    })
+}
+
+/// A TPL texture palette file, holding any number of textures
+#[derive(Debug, Clone, Default)]
+pub struct Tpl {
+    pub images: Vec<TplImage>,
+}
+
+impl Tpl {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Tpl> {
+        let (header, _) = RawHeader::ref_from_prefix(buf)
+            .map_err(|_| anyhow!("TPL is too small to contain a header"))?;
+        ensure!(header.magic.get() == TPL_MAGIC, "Not a TPL file");
+        let count = header.image_count.get() as usize;
+        let table = buf
+            .get(header.image_table_offset.get() as usize..)
+            .and_then(|t| <[RawImageTableEntry]>::ref_from_prefix_with_elems(t, count).ok())
+            .ok_or_else(|| anyhow!("TPL image table extends past end of file"))?
+            .0;
+
+        let mut images = Vec::with_capacity(count);
+        for (i, entry) in table.iter().enumerate() {
+            let raw = buf
+                .get(entry.image_header_offset.get() as usize..)
+                .and_then(|h| RawImageHeader::ref_from_prefix(h).ok())
+                .ok_or_else(|| anyhow!("TPL image {i} header extends past end of file"))?
+                .0;
+            let format = TextureFormat::try_from(raw.format.get())
+                .map_err(|_| anyhow!("TPL image {i} has unknown format {}", raw.format))?;
+            let (width, height) = (raw.width.get() as usize, raw.height.get() as usize);
+            let image_count = if raw.min_filter.get() >= FILTER_NEAR_MIPMAP_NEAR {
+                raw.max_lod as usize + 1
+            } else {
+                1
+            };
+            ensure!(
+                image_count <= max_image_count(width, height),
+                "TPL image {i} has {image_count} mipmap levels, more than a {width}x{height} texture can have"
+            );
+            let start = raw.data_offset.get() as usize;
+            let data = buf
+                .get(start..start + format.mipmap_data_size(width, height, image_count))
+                .ok_or_else(|| anyhow!("TPL image {i} data extends past end of file"))?;
+            let palette = match entry.palette_header_offset.get() as usize {
+                0 => None,
+                offset => Some(
+                    parse_palette(buf, offset)
+                        .with_context(|| format!("Invalid palette for TPL image {i}"))?,
+                ),
+            };
+            images.push(TplImage {
+                format,
+                width,
+                height,
+                wrap_s: WrapMode::try_from(raw.wrap_s.get()).unwrap_or_default(),
+                wrap_t: WrapMode::try_from(raw.wrap_t.get()).unwrap_or_default(),
+                min_filter: raw.min_filter.get(),
+                mag_filter: raw.mag_filter.get(),
+                lod_bias: raw.lod_bias.get(),
+                edge_lod: raw.edge_lod_enable != 0,
+                min_lod: raw.min_lod,
+                max_lod: raw.max_lod,
+                data: data.to_vec(),
+                palette,
+            });
+        }
+        Ok(Tpl { images })
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let count = self.images.len();
+        let palette_count = self.images.iter().filter(|i| i.palette.is_some()).count();
+        let table_offset = size_of::<RawHeader>();
+        let headers_offset = table_offset + count * size_of::<RawImageTableEntry>();
+        let palette_headers_offset = headers_offset + count * size_of::<RawImageHeader>();
+        let data_start = (palette_headers_offset + palette_count * size_of::<RawPaletteHeader>())
+            .next_multiple_of(ALIGNMENT);
+
+        // Each image's palette then texture data, aligned, after all of the headers
+        let mut data = Vec::new();
+        let mut add_data = |bytes: &[u8]| -> anyhow::Result<big_endian::U32> {
+            let offset = u32::try_from(data_start + data.len()).context("TPL is too large")?;
+            data.extend_from_slice(bytes);
+            data.resize(data.len().next_multiple_of(ALIGNMENT), 0);
+            Ok(offset.into())
+        };
+
+        let mut table = Vec::new();
+        let mut headers = Vec::new();
+        let mut palette_headers = Vec::new();
+        for (i, image) in self.images.iter().enumerate() {
+            let palette_header_offset = match &image.palette {
+                Some(tlut) => {
+                    let offset = palette_headers_offset + palette_headers.len();
+                    palette_headers.extend_from_slice(
+                        RawPaletteHeader {
+                            entry_count: u16::try_from(tlut.len())
+                                .context("Palette has too many entries")?
+                                .into(),
+                            unpacked: 0,
+                            padding: 0,
+                            format: u32::from(tlut.format).into(),
+                            data_offset: add_data(&tlut.data)?,
+                        }
+                        .as_bytes(),
+                    );
+                    offset as u32
+                }
+                None => 0,
+            };
+            table.extend_from_slice(
+                RawImageTableEntry {
+                    image_header_offset: ((headers_offset + i * size_of::<RawImageHeader>())
+                        as u32)
+                        .into(),
+                    palette_header_offset: palette_header_offset.into(),
+                }
+                .as_bytes(),
+            );
+            headers.extend_from_slice(
+                RawImageHeader {
+                    height: u16::try_from(image.height)
+                        .context("Texture is too tall")?
+                        .into(),
+                    width: u16::try_from(image.width)
+                        .context("Texture is too wide")?
+                        .into(),
+                    format: u32::from(image.format).into(),
+                    data_offset: add_data(&image.data)?,
+                    wrap_s: u32::from(image.wrap_s).into(),
+                    wrap_t: u32::from(image.wrap_t).into(),
+                    min_filter: image.min_filter.into(),
+                    mag_filter: image.mag_filter.into(),
+                    lod_bias: image.lod_bias.into(),
+                    edge_lod_enable: image.edge_lod as u8,
+                    min_lod: image.min_lod,
+                    max_lod: image.max_lod,
+                    unpacked: 0,
+                }
+                .as_bytes(),
+            );
+        }
+
+        let header = RawHeader {
+            magic: TPL_MAGIC.into(),
+            image_count: (count as u32).into(),
+            image_table_offset: (table_offset as u32).into(),
+        };
+        let mut buf = Vec::with_capacity(data_start + data.len());
+        buf.extend_from_slice(header.as_bytes());
+        buf.extend_from_slice(&table);
+        buf.extend_from_slice(&headers);
+        buf.extend_from_slice(&palette_headers);
+        buf.resize(data_start, 0);
+        buf.extend_from_slice(&data);
+        Ok(buf)
+    }
+}
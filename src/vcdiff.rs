@@ -0,0 +1,489 @@
+// VCDIFF (RFC 3284) delta patches, the format written by xdelta3 and expected by most users for
+// disc image mods. Patches are decoded and encoded a window at a time, so only a window of the
+// source and target is held in memory.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::binpatch::{self, read_segment, DiffOp, MovedRange, PatchReader};
+
+const MAGIC: &[u8] = &[0xD6, 0xC3, 0xC4, 0x00];
+
+// Header indicator bits
+const VCD_DECOMPRESS: u8 = 0x01;
+const VCD_CODETABLE: u8 = 0x02;
+/// xdelta3's application header, which holds file names
+const VCD_APPHEADER: u8 = 0x04;
+
+// Window indicator bits
+const VCD_SOURCE: u8 = 0x01;
+const VCD_TARGET: u8 = 0x02;
+/// xdelta3's extension adding an Adler-32 checksum of each target window
+const VCD_ADLER32: u8 = 0x04;
+
+const NEAR_CACHE_SIZE: usize = 4;
+const SAME_CACHE_SIZE: usize = 3;
+
+// Addressing modes
+const VCD_SELF: u8 = 0;
+const VCD_HERE: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstructionKind {
+    Noop,
+    Add,
+    Run,
+    Copy,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Instruction {
+    kind: InstructionKind,
+    /// 0 if the size follows the opcode in the instruction section
+    size: u8,
+    mode: u8,
+}
+
+impl Instruction {
+    const NOOP: Instruction = Instruction::new(InstructionKind::Noop, 0, 0);
+
+    const fn new(kind: InstructionKind, size: u8, mode: u8) -> Instruction {
+        Instruction { kind, size, mode }
+    }
+}
+
+/// The default code table from RFC 3284 section 5.6, indexed by opcode
+fn default_code_table() -> Vec<[Instruction; 2]> {
+    use InstructionKind::*;
+    let modes = 2 + NEAR_CACHE_SIZE as u8 + SAME_CACHE_SIZE as u8;
+    let mut table = vec![[Instruction::new(Run, 0, 0), Instruction::NOOP]];
+    for size in 0..=17 {
+        table.push([Instruction::new(Add, size, 0), Instruction::NOOP]);
+    }
+    for mode in 0..modes {
+        table.push([Instruction::new(Copy, 0, mode), Instruction::NOOP]);
+        for size in 4..=18 {
+            table.push([Instruction::new(Copy, size, mode), Instruction::NOOP]);
+        }
+    }
+    for mode in 0..modes {
+        let copy_sizes = if mode < 6 { 4..=6 } else { 4..=4 };
+        for add_size in 1..=4 {
+            for copy_size in copy_sizes.clone() {
+                table.push([
+                    Instruction::new(Add, add_size, 0),
+                    Instruction::new(Copy, copy_size, mode),
+                ]);
+            }
+        }
+    }
+    for mode in 0..modes {
+        table.push([Instruction::new(Copy, 4, mode), Instruction::new(Add, 1, 0)]);
+    }
+    table
+}
+
+/// The caches COPY addresses are encoded against
+struct AddressCache {
+    near: [u64; NEAR_CACHE_SIZE],
+    next_slot: usize,
+    same: [u64; SAME_CACHE_SIZE * 256],
+}
+
+impl AddressCache {
+    fn new() -> AddressCache {
+        AddressCache {
+            near: [0; NEAR_CACHE_SIZE],
+            next_slot: 0,
+            same: [0; SAME_CACHE_SIZE * 256],
+        }
+    }
+
+    fn decode(&mut self, addrs: &mut PatchReader, here: u64, mode: u8) -> anyhow::Result<u64> {
+        let mode = mode as usize;
+        let addr = match mode {
+            0 => read_int(addrs)?,
+            1 => here
+                .checked_sub(read_int(addrs)?)
+                .ok_or_else(|| anyhow!("COPY address is out of range"))?,
+            m if m < 2 + NEAR_CACHE_SIZE => self.near[m - 2] + read_int(addrs)?,
+            m => self.same[(m - 2 - NEAR_CACHE_SIZE) * 256 + addrs.byte()? as usize],
+        };
+        self.near[self.next_slot] = addr;
+        self.next_slot = (self.next_slot + 1) % NEAR_CACHE_SIZE;
+        self.same[(addr % (SAME_CACHE_SIZE * 256) as u64) as usize] = addr;
+        Ok(addr)
+    }
+}
+
+/// A VCDIFF integer: big-endian base 128, with the top bit set on all but the last byte
+fn read_int(reader: &mut PatchReader) -> anyhow::Result<u64> {
+    let mut value = 0u64;
+    loop {
+        let byte = reader.byte()?;
+        ensure!(value >> 57 == 0, "Number too large at {:#x}", reader.pos);
+        value = value << 7 | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn write_int(out: &mut Vec<u8>, value: u64) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value != 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Largest number of bytes that can be summed before the sums can overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    b << 16 | a
+}
+
+/// A VCDIFF patch. Its windows are decoded as it's applied.
+#[derive(Debug, Clone)]
+pub struct Vcdiff<'a> {
+    /// xdelta3's application header, which usually holds the file names
+    pub app_header: Option<&'a [u8]>,
+    windows: &'a [u8],
+}
+
+impl<'a> Vcdiff<'a> {
+    pub fn parse(buf: &'a [u8]) -> anyhow::Result<Vcdiff<'a>> {
+        ensure!(buf.starts_with(&MAGIC[..3]), "Not a VCDIFF patch");
+        ensure!(
+            buf.get(3) == Some(&0),
+            "Unsupported VCDIFF version {}",
+            buf.get(3).copied().unwrap_or_default()
+        );
+        let mut reader = PatchReader {
+            buf,
+            pos: MAGIC.len(),
+        };
+        let indicator = reader.byte()?;
+        if indicator & VCD_DECOMPRESS != 0 {
+            bail!(
+                "Secondary compression isn't supported. Create the patch with `xdelta3 -S none`."
+            );
+        }
+        if indicator & VCD_CODETABLE != 0 {
+            bail!("Custom code tables aren't supported");
+        }
+        let app_header = if indicator & VCD_APPHEADER != 0 {
+            let len = read_int(&mut reader)?;
+            Some(reader.bytes(len as usize)?)
+        } else {
+            None
+        };
+        Ok(Vcdiff {
+            app_header,
+            windows: &buf[reader.pos..],
+        })
+    }
+
+    /// Write the patched file to `target`, which must be empty, reading from `source`. Windows
+    /// with checksums are checked as they're written. Returns the size of the patched file.
+    pub fn apply<R: Read + Seek, W: Read + Write + Seek>(
+        &self,
+        source: &mut R,
+        target: &mut W,
+    ) -> anyhow::Result<u64> {
+        let table = default_code_table();
+        let mut reader = PatchReader {
+            buf: self.windows,
+            pos: 0,
+        };
+        let mut written = 0;
+        let mut window_index = 0;
+        while !reader.is_empty() {
+            let window = decode_window(&table, &mut reader, source, target, written)
+                .with_context(|| format!("Invalid window {window_index}"))?;
+            target.seek(SeekFrom::Start(written))?;
+            target.write_all(&window)?;
+            written += window.len() as u64;
+            window_index += 1;
+        }
+        Ok(written)
+    }
+}
+
+fn decode_window<R: Read + Seek, W: Read + Write + Seek>(
+    table: &[[Instruction; 2]],
+    reader: &mut PatchReader,
+    source: &mut R,
+    target: &mut W,
+    written: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let indicator = reader.byte()?;
+    ensure!(
+        indicator & !(VCD_SOURCE | VCD_TARGET | VCD_ADLER32) == 0,
+        "Unknown window indicator {indicator:#x}"
+    );
+    let segment = match indicator & (VCD_SOURCE | VCD_TARGET) {
+        0 => Vec::new(),
+        VCD_SOURCE => {
+            let size = read_int(reader)?;
+            let pos = read_int(reader)?;
+            read_segment(source, pos, size)?
+        }
+        VCD_TARGET => {
+            let size = read_int(reader)?;
+            let pos = read_int(reader)?;
+            ensure!(
+                pos + size <= written,
+                "Target segment reads past the output written so far"
+            );
+            read_segment(target, pos, size)?
+        }
+        _ => bail!("Window copies from both the source and the target"),
+    };
+
+    let delta_len = read_int(reader)? as usize;
+    let delta_end = reader.pos + delta_len;
+    let window_size = read_int(reader)? as usize;
+    ensure!(
+        reader.byte()? == 0,
+        "Secondary compression isn't supported. Create the patch with `xdelta3 -S none`."
+    );
+    let data_len = read_int(reader)? as usize;
+    let inst_len = read_int(reader)? as usize;
+    let addr_len = read_int(reader)? as usize;
+    let checksum = if indicator & VCD_ADLER32 != 0 {
+        Some(reader.be(4)?)
+    } else {
+        None
+    };
+    let mut data = PatchReader {
+        buf: reader.bytes(data_len)?,
+        pos: 0,
+    };
+    let mut insts = PatchReader {
+        buf: reader.bytes(inst_len)?,
+        pos: 0,
+    };
+    let mut addrs = PatchReader {
+        buf: reader.bytes(addr_len)?,
+        pos: 0,
+    };
+    ensure!(
+        reader.pos == delta_end,
+        "Delta encoding has the wrong length"
+    );
+
+    let mut out = Vec::with_capacity(window_size);
+    let mut cache = AddressCache::new();
+    while !insts.is_empty() {
+        let opcode = insts.byte()?;
+        for inst in table[opcode as usize] {
+            let size = match inst.size {
+                0 if inst.kind != InstructionKind::Noop => read_int(&mut insts)? as usize,
+                size => size as usize,
+            };
+            ensure!(
+                out.len() + size <= window_size,
+                "Instructions write past the end of the window"
+            );
+            match inst.kind {
+                InstructionKind::Noop => {}
+                InstructionKind::Add => out.extend_from_slice(data.bytes(size)?),
+                InstructionKind::Run => {
+                    let byte = data.byte()?;
+                    out.resize(out.len() + size, byte);
+                }
+                InstructionKind::Copy => {
+                    let here = (segment.len() + out.len()) as u64;
+                    let addr = cache.decode(&mut addrs, here, inst.mode)?;
+                    ensure!(addr < here, "COPY reads past the output written so far");
+                    let addr = addr as usize;
+                    if addr + size <= segment.len() {
+                        out.extend_from_slice(&segment[addr..addr + size]);
+                    } else {
+                        // Copies from the target can overlap what's being written
+                        for addr in addr..addr + size {
+                            let byte = match addr.checked_sub(segment.len()) {
+                                None => segment[addr],
+                                Some(offset) => out[offset],
+                            };
+                            out.push(byte);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ensure!(
+        out.len() == window_size,
+        "Window produced {} bytes, expected {window_size}",
+        out.len()
+    );
+    if let Some(checksum) = checksum {
+        let actual = adler32(&out);
+        ensure!(
+            actual == checksum,
+            "Patched data has Adler-32 {actual:08x}, expected {checksum:08x}. The patch may be \
+             for a different file."
+        );
+    }
+    Ok(out)
+}
+
+/// The sections of one window being encoded
+#[derive(Default)]
+struct WindowEncoder {
+    data: Vec<u8>,
+    insts: Vec<u8>,
+    addrs: Vec<u8>,
+}
+
+impl WindowEncoder {
+    fn add(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if bytes.len() <= 17 {
+            self.insts.push(1 + bytes.len() as u8);
+        } else {
+            self.insts.push(1);
+            write_int(&mut self.insts, bytes.len() as u64);
+        }
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn run(&mut self, byte: u8, len: usize) {
+        self.insts.push(0);
+        write_int(&mut self.insts, len as u64);
+        self.data.push(byte);
+    }
+
+    /// A COPY from the source segment, addressed from the start of the window or back from
+    /// the current position, whichever is shorter
+    fn copy(&mut self, addr: usize, len: usize, here: usize) {
+        let (mode, value) = if here - addr < addr {
+            (VCD_HERE, here - addr)
+        } else {
+            (VCD_SELF, addr)
+        };
+        let base = 19 + 16 * mode;
+        if (4..=18).contains(&len) {
+            self.insts.push(base + len as u8 - 3);
+        } else {
+            self.insts.push(base);
+            write_int(&mut self.insts, len as u64);
+        }
+        write_int(&mut self.addrs, value as u64);
+    }
+}
+
+/// Create a VCDIFF patch from `source` to `target`, written to `patch`, with a window for each of
+/// the windows `binpatch::diff_windows` matches. Windows carry Adler-32 checksums, as xdelta3
+/// writes them.
+pub fn create<R: Read + Seek, T: Read, W: Write>(
+    source: &mut R,
+    target: &mut T,
+    moved: &[MovedRange],
+    patch: &mut W,
+) -> anyhow::Result<()> {
+    patch.write_all(MAGIC)?;
+    patch.write_all(&[0])?;
+
+    binpatch::diff_windows(source, target, moved, |window| {
+        let mut encoder = WindowEncoder::default();
+        for op in &window.ops {
+            match *op {
+                DiffOp::Literal { start, len } => encoder.add(&window.data[start..start + len]),
+                DiffOp::Run { byte, len } => encoder.run(byte, len),
+                DiffOp::Copy { pos, len, at } => encoder.copy(pos, len, window.segment.len() + at),
+            }
+        }
+
+        let mut header = Vec::new();
+        if window.segment.is_empty() {
+            header.push(VCD_ADLER32);
+        } else {
+            header.push(VCD_SOURCE | VCD_ADLER32);
+            write_int(&mut header, window.segment.len() as u64);
+            write_int(&mut header, window.segment_start);
+        }
+        let mut delta = Vec::new();
+        write_int(&mut delta, window.data.len() as u64);
+        delta.push(0);
+        write_int(&mut delta, encoder.data.len() as u64);
+        write_int(&mut delta, encoder.insts.len() as u64);
+        write_int(&mut delta, encoder.addrs.len() as u64);
+        delta.extend_from_slice(&adler32(window.data).to_be_bytes());
+        let delta_len =
+            delta.len() + encoder.data.len() + encoder.insts.len() + encoder.addrs.len();
+        write_int(&mut header, delta_len as u64);
+
+        patch.write_all(&header)?;
+        patch.write_all(&delta)?;
+        patch.write_all(&encoder.data)?;
+        patch.write_all(&encoder.insts)?;
+        patch.write_all(&encoder.addrs)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::binpatch::tests::pairs;
+    use crate::binpatch::{detect, PatchFormat};
+
+    #[test]
+    fn round_trips() {
+        for (source, target, moved) in pairs() {
+            let mut patch = Vec::new();
+            create(
+                &mut Cursor::new(&source),
+                &mut target.as_slice(),
+                &moved,
+                &mut patch,
+            )
+            .unwrap();
+            assert_eq!(detect(&patch), Some(PatchFormat::Vcdiff));
+            let vcdiff = Vcdiff::parse(&patch).unwrap();
+            let mut out = Cursor::new(Vec::new());
+            let size = vcdiff.apply(&mut Cursor::new(&source), &mut out).unwrap();
+            assert_eq!(size, target.len() as u64);
+            assert_eq!(out.into_inner(), target);
+        }
+    }
+
+    #[test]
+    fn unchanged_data_is_copied() {
+        let (source, target, _) = pairs().swap_remove(0);
+        let mut patch = Vec::new();
+        create(
+            &mut Cursor::new(&source),
+            &mut target.as_slice(),
+            &[],
+            &mut patch,
+        )
+        .unwrap();
+        assert!(patch.len() < target.len() / 100);
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert!(Vcdiff::parse(b"PATCH").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+// Reading and writing WAV files, as 16-bit PCM, with loop points from the sampler chunk.
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+#[derive(Debug, Clone, Default)]
+pub struct Wav {
+    pub sample_rate: u32,
+    /// Samples of each channel
+    pub channels: Vec<Vec<i16>>,
+    /// First and last sample of the loop, inclusive
+    pub loop_points: Option<(u32, u32)>,
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Convert a sample of any supported format to 16 bits
+fn read_sample(bytes: &[u8], format: u16, bits: u16) -> i16 {
+    match (format, bits) {
+        (FORMAT_PCM, 8) => ((bytes[0] as i16) - 128) << 8,
+        (FORMAT_PCM, 16) => i16::from_le_bytes([bytes[0], bytes[1]]),
+        (FORMAT_PCM, 24) => i16::from_le_bytes([bytes[1], bytes[2]]),
+        (FORMAT_PCM, 32) => i16::from_le_bytes([bytes[2], bytes[3]]),
+        (FORMAT_FLOAT, 32) => {
+            let value = f32::from_le_bytes(bytes[..4].try_into().unwrap());
+            (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+        }
+        _ => unreachable!(),
+    }
+}
+
+impl Wav {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Wav> {
+        ensure!(
+            buf.len() >= 12 && &buf[0..4] == b"RIFF" && &buf[8..12] == b"WAVE",
+            "Not a WAV file"
+        );
+        let mut format = None;
+        let mut data = None;
+        let mut loop_points = None;
+        let mut offset = 12;
+        while offset + 8 <= buf.len() {
+            let id = &buf[offset..offset + 4];
+            let size = u32_at(buf, offset + 4) as usize;
+            let body = buf
+                .get(offset + 8..offset + 8 + size)
+                .ok_or_else(|| anyhow!("WAV chunk {} is truncated", String::from_utf8_lossy(id)))?;
+            match id {
+                b"fmt " => {
+                    ensure!(body.len() >= 16, "WAV format chunk is too small");
+                    let mut tag = u16_at(body, 0);
+                    if tag == FORMAT_EXTENSIBLE {
+                        ensure!(body.len() >= 26, "WAV format chunk is too small");
+                        tag = u16_at(body, 24);
+                    }
+                    format = Some((tag, u16_at(body, 2), u32_at(body, 4), u16_at(body, 14)));
+                }
+                b"data" => data = Some(body),
+                b"smpl" if body.len() >= 36 + 24 && u32_at(body, 28) > 0 => {
+                    loop_points = Some((u32_at(body, 36 + 8), u32_at(body, 36 + 12)));
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            offset += 8 + size + (size & 1);
+        }
+
+        let (tag, channel_count, sample_rate, bits) =
+            format.ok_or_else(|| anyhow!("WAV has no format chunk"))?;
+        let data = data.ok_or_else(|| anyhow!("WAV has no data chunk"))?;
+        match (tag, bits) {
+            (FORMAT_PCM, 8 | 16 | 24 | 32) | (FORMAT_FLOAT, 32) => {}
+            _ => bail!("Unsupported WAV format {tag} with {bits} bit samples"),
+        }
+        ensure!(channel_count > 0, "WAV has no channels");
+
+        let sample_size = bits as usize / 8;
+        let frame_size = sample_size * channel_count as usize;
+        let mut channels = vec![Vec::new(); channel_count as usize];
+        for frame in data.chunks_exact(frame_size) {
+            for (channel, bytes) in channels.iter_mut().zip(frame.chunks_exact(sample_size)) {
+                channel.push(read_sample(bytes, tag, bits));
+            }
+        }
+        Ok(Wav {
+            sample_rate,
+            channels,
+            loop_points,
+        })
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.channels.first().map_or(0, |c| c.len())
+    }
+
+    /// Write as 16-bit PCM, with a sampler chunk holding the loop points if there are any
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let channel_count = self.channels.len() as u16;
+        ensure!(channel_count > 0, "WAV has no channels");
+        ensure!(
+            self.channels.iter().all(|c| c.len() == self.sample_count()),
+            "WAV channels have different lengths"
+        );
+        let data_size = u32::try_from(self.sample_count() * 2 * channel_count as usize)
+            .context("Audio is too long for a WAV file")?;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+        out.extend_from_slice(b"fmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        out.extend_from_slice(&channel_count.to_le_bytes());
+        out.extend_from_slice(&self.sample_rate.to_le_bytes());
+        out.extend_from_slice(&(self.sample_rate * 2 * channel_count as u32).to_le_bytes());
+        out.extend_from_slice(&(2 * channel_count).to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_size.to_le_bytes());
+        for i in 0..self.sample_count() {
+            for channel in &self.channels {
+                out.extend_from_slice(&channel[i].to_le_bytes());
+            }
+        }
+
+        if let Some((start, end)) = self.loop_points {
+            out.extend_from_slice(b"smpl");
+            out.extend_from_slice(&(36u32 + 24).to_le_bytes());
+            let sample_period = 1_000_000_000 / self.sample_rate.max(1);
+            // Manufacturer, product, period, MIDI note and pitch, SMPTE format and offset, loop
+            // count, and extra data size, then the loop's ID, type, start, end, fraction, and
+            // play count
+            let header = [0, 0, sample_period, 60, 0, 0, 0, 1, 0];
+            let loop_fields = [0, 0, start, end, 0, 0];
+            for field in header.into_iter().chain(loop_fields) {
+                out.extend_from_slice(&u32::to_le_bytes(field));
+            }
+        }
+
+        let riff_size = out.len() as u32 - 8;
+        out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        Ok(out)
+    }
+}
@@ -0,0 +1,60 @@
+// The wiiload protocol, used to send an executable and its arguments to a loader running on the
+// console, over a USB Gecko or over the network through a Broadband Adapter.
+
+use std::io::Write;
+
+use anyhow::{ensure, Context};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// TCP port loaders listen on
+pub const PORT: u16 = 4299;
+
+const MAGIC: &[u8; 4] = b"HAXX";
+const VERSION_MAJOR: u8 = 0;
+const VERSION_MINOR: u8 = 5;
+/// Bytes written at a time, small enough for the USB Gecko's FIFO to keep up with
+const CHUNK_SIZE: usize = 0x1000;
+
+/// Send `data` to a loader. `args` are passed to the executable, the first being its file name.
+/// Compressed data is inflated by the loader before it's run.
+pub fn send<W: Write>(
+    out: &mut W,
+    data: &[u8],
+    args: &[String],
+    compress: bool,
+) -> anyhow::Result<()> {
+    let mut arg_data = Vec::new();
+    for arg in args {
+        ensure!(!arg.contains('\0'), "Argument {arg:?} contains a NUL byte");
+        arg_data.extend_from_slice(arg.as_bytes());
+        arg_data.push(0);
+    }
+    let args_len = u16::try_from(arg_data.len()).context("Arguments are too long")?;
+    let size = u32::try_from(data.len()).context("File is too large")?;
+
+    let compressed;
+    let (payload, uncompressed_size) = if compress {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data)?;
+        compressed = encoder.finish()?;
+        (compressed.as_slice(), size)
+    } else {
+        // An uncompressed size of 0 tells the loader the data isn't compressed
+        (data, 0)
+    };
+
+    let mut header = Vec::with_capacity(16);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&[VERSION_MAJOR, VERSION_MINOR]);
+    header.extend_from_slice(&args_len.to_be_bytes());
+    header.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    header.extend_from_slice(&uncompressed_size.to_be_bytes());
+    out.write_all(&header)?;
+    for chunk in payload.chunks(CHUNK_SIZE) {
+        out.write_all(chunk)?;
+    }
+    out.write_all(&arg_data)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+pub use crate::lz::CompressionLevel;
+use crate::lz::{self, OutputWindow, Token, LONG_MATCH};
+
+pub const YAY0_MAGIC: &[u8; 4] = b"Yay0";
+const HEADER_SIZE: usize = 0x10;
+/// Bytes of each stream buffered at a time
+const STREAM_BUFFER_SIZE: usize = 0x4000;
+
+pub fn is_yay0(buf: &[u8]) -> bool {
+    buf.starts_with(YAY0_MAGIC)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Size of the data once decompressed, from the Yay0 header
+pub fn decompressed_size(buf: &[u8]) -> anyhow::Result<usize> {
+    ensure!(
+        buf.len() >= HEADER_SIZE && is_yay0(buf),
+        "Not Yay0 compressed data"
+    );
+    Ok(read_u32(buf, 4) as usize)
+}
+
+pub fn decompress(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(decompressed_size(buf)?);
+    decompress_stream(&mut Cursor::new(buf), &mut out)?;
+    Ok(out)
+}
+
+/// One of the three Yay0 streams, read a buffer at a time from its own offset
+struct StreamReader {
+    offset: u64,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl StreamReader {
+    fn new(offset: u64) -> Self {
+        StreamReader {
+            offset,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    fn read<R: Read + Seek, const N: usize>(&mut self, reader: &mut R) -> anyhow::Result<[u8; N]> {
+        if self.pos + N > self.buf.len() {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+            let start = self.offset + self.buf.len() as u64;
+            reader.seek(SeekFrom::Start(start))?;
+            reader
+                .take(STREAM_BUFFER_SIZE as u64)
+                .read_to_end(&mut self.buf)?;
+            if self.buf.len() < N {
+                bail!("Yay0 data is truncated");
+            }
+        }
+        let value = self.buf[self.pos..self.pos + N].try_into().unwrap();
+        self.pos += N;
+        self.offset += N as u64;
+        Ok(value)
+    }
+}
+
+/// Decompress from a reader to a writer. The mask bits, back-references, and literal bytes are
+/// stored in three separate streams, which are each buffered rather than loaded whole. Returns the
+/// decompressed size.
+pub fn decompress_stream<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+) -> anyhow::Result<u64> {
+    let base = reader.stream_position()?;
+    let mut header = [0; HEADER_SIZE];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| anyhow!("Not Yay0 compressed data"))?;
+    let size = decompressed_size(&header)? as u64;
+    let mut masks = StreamReader::new(base + HEADER_SIZE as u64);
+    let mut links = StreamReader::new(base + read_u32(&header, 8) as u64);
+    let mut chunks = StreamReader::new(base + read_u32(&header, 12) as u64);
+    let mut out = OutputWindow::new(writer);
+
+    let mut mask = 0u32;
+    let mut bits_left = 0;
+    while out.len() < size {
+        if bits_left == 0 {
+            mask = u32::from_be_bytes(masks.read(reader)?);
+            bits_left = 32;
+        }
+        if mask & 0x8000_0000 != 0 {
+            let [b] = chunks.read(reader)?;
+            out.push(b)?;
+        } else {
+            let link = u16::from_be_bytes(links.read(reader)?) as usize;
+            let distance = (link & 0xFFF) + 1;
+            let length = match link >> 12 {
+                0 => u8::from_be_bytes(chunks.read(reader)?) as usize + LONG_MATCH,
+                n => n + 2,
+            };
+            out.copy(distance, length).context("Invalid Yay0 data")?;
+        }
+        mask <<= 1;
+        bits_left -= 1;
+    }
+    out.finish(size)?;
+
+    Ok(size)
+}
+
+pub fn compress(data: &[u8], level: CompressionLevel) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    compress_stream(&mut Cursor::new(data), &mut Cursor::new(&mut out), level)?;
+    Ok(out)
+}
+
+/// One of the three Yay0 streams, written a buffer at a time at its own offset
+struct StreamWriter {
+    offset: u64,
+    buf: Vec<u8>,
+}
+
+impl StreamWriter {
+    fn new(offset: u64) -> Self {
+        StreamWriter {
+            offset,
+            buf: Vec::with_capacity(STREAM_BUFFER_SIZE),
+        }
+    }
+
+    fn write<W: Write + Seek>(&mut self, writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() >= STREAM_BUFFER_SIZE {
+            self.flush(writer)?;
+        }
+        Ok(())
+    }
+
+    fn flush<W: Write + Seek>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.seek(SeekFrom::Start(self.offset))?;
+        writer.write_all(&self.buf)?;
+        self.offset += self.buf.len() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Compress the rest of a reader to a writer. The input is tokenized twice, once to size the three
+/// streams and once to write them, so only the match window and a chunk of input are held in
+/// memory.
+pub fn compress_stream<R: Read + Seek, W: Write + Seek>(
+    reader: &mut R,
+    writer: &mut W,
+    level: CompressionLevel,
+) -> anyhow::Result<()> {
+    let start = reader.stream_position()?;
+    let (mut token_count, mut link_size) = (0u64, 0u64);
+    let size = lz::tokenize_stream(reader, level, |token| {
+        token_count += 1;
+        if let Token::Match { .. } = token {
+            link_size += 2;
+        }
+        Ok(())
+    })?;
+    let size = u32::try_from(size).map_err(|_| anyhow!("Data is too large for Yay0"))?;
+
+    let base = writer.stream_position()?;
+    let link_offset = HEADER_SIZE as u64 + token_count.div_ceil(32) * 4;
+    let chunk_offset = link_offset + link_size;
+    let offset = |o: u64| u32::try_from(o).map_err(|_| anyhow!("Yay0 output is too large"));
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(YAY0_MAGIC);
+    header.extend_from_slice(&size.to_be_bytes());
+    header.extend_from_slice(&offset(link_offset)?.to_be_bytes());
+    header.extend_from_slice(&offset(chunk_offset)?.to_be_bytes());
+    writer.write_all(&header)?;
+
+    let mut masks = StreamWriter::new(base + HEADER_SIZE as u64);
+    let mut links = StreamWriter::new(base + link_offset);
+    let mut chunks = StreamWriter::new(base + chunk_offset);
+    let mut mask = 0u32;
+    let mut bit = 32;
+
+    reader.seek(SeekFrom::Start(start))?;
+    let read = lz::tokenize_stream(&mut reader.take(size as u64), level, |token| {
+        bit -= 1;
+        match token {
+            Token::Literal(b) => {
+                mask |= 1 << bit;
+                chunks.write(writer, &[b])?;
+            }
+            Token::Match { distance, length } => {
+                let distance = distance - 1;
+                if length >= LONG_MATCH {
+                    links.write(writer, &(distance as u16).to_be_bytes())?;
+                    chunks.write(writer, &[(length - LONG_MATCH) as u8])?;
+                } else {
+                    let link = ((length - 2) << 12 | distance) as u16;
+                    links.write(writer, &link.to_be_bytes())?;
+                }
+            }
+        }
+        if bit == 0 {
+            masks.write(writer, &mask.to_be_bytes())?;
+            mask = 0;
+            bit = 32;
+        }
+        Ok(())
+    })?;
+    ensure!(read == size as u64, "Input changed size while compressing");
+    if bit != 32 {
+        masks.write(writer, &mask.to_be_bytes())?;
+    }
+    for stream in [&mut masks, &mut links, &mut chunks] {
+        stream.flush(writer)?;
+    }
+    writer.seek(SeekFrom::Start(chunks.offset))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lz::tests::samples;
+
+    #[test]
+    fn round_trips() {
+        for level in CompressionLevel::ALL {
+            for data in samples() {
+                let compressed = compress(&data, level).unwrap();
+                assert!(is_yay0(&compressed));
+                assert_eq!(decompressed_size(&compressed).unwrap(), data.len());
+                assert_eq!(decompress(&compressed).unwrap(), data, "{level:?}");
+
+                let mut streamed = Vec::new();
+                let size = decompress_stream(&mut Cursor::new(&compressed), &mut streamed).unwrap();
+                assert_eq!(size, data.len() as u64);
+                assert_eq!(streamed, data);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_the_three_streams() {
+        let mut buf = YAY0_MAGIC.to_vec();
+        for word in [10u32, 0x14, 0x16] {
+            buf.extend_from_slice(&word.to_be_bytes());
+        }
+        // Mask: three literals, then a link of 7 bytes from 3 back
+        buf.extend_from_slice(&0xE000_0000u32.to_be_bytes());
+        buf.extend_from_slice(&[0x50, 0x02]);
+        buf.extend_from_slice(b"abc");
+        assert_eq!(decompress(&buf).unwrap(), b"abcabcabca");
+    }
+
+    #[test]
+    fn compressed_streams_are_laid_out_in_order() {
+        let data = b"abcabcabca";
+        let compressed = compress(data, CompressionLevel::Best).unwrap();
+        let word = |offset: usize| {
+            u32::from_be_bytes(compressed[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        assert_eq!(word(4), data.len());
+        assert!(HEADER_SIZE < word(8) && word(8) <= word(12) && word(12) <= compressed.len());
+    }
+}
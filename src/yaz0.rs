@@ -0,0 +1,214 @@
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+pub use crate::lz::CompressionLevel;
+use crate::lz::{self, OutputWindow, Token, LONG_MATCH};
+
+pub const YAZ0_MAGIC: &[u8; 4] = b"Yaz0";
+const HEADER_SIZE: usize = 0x10;
+
+pub fn is_yaz0(buf: &[u8]) -> bool {
+    buf.starts_with(YAZ0_MAGIC)
+}
+
+/// Size of the data once decompressed, from the Yaz0 header
+pub fn decompressed_size(buf: &[u8]) -> anyhow::Result<usize> {
+    ensure!(
+        buf.len() >= HEADER_SIZE && is_yaz0(buf),
+        "Not Yaz0 compressed data"
+    );
+    Ok(u32::from_be_bytes(buf[4..8].try_into().unwrap()) as usize)
+}
+
+pub fn decompress(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(decompressed_size(buf)?);
+    decompress_stream(&mut &buf[..], &mut out)?;
+    Ok(out)
+}
+
+/// Decompress from a reader to a writer, keeping only the match window in memory. Returns the
+/// decompressed size.
+pub fn decompress_stream<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> anyhow::Result<u64> {
+    let mut header = [0; HEADER_SIZE];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| anyhow!("Not Yaz0 compressed data"))?;
+    let size = decompressed_size(&header)? as u64;
+
+    let mut input = BufReader::new(reader).bytes();
+    let mut next = || match input.next() {
+        Some(b) => Ok(b?),
+        None => bail!("Yaz0 data is truncated"),
+    };
+    let mut out = OutputWindow::new(writer);
+
+    let mut code = 0u8;
+    let mut bits_left = 0;
+    while out.len() < size {
+        if bits_left == 0 {
+            code = next()?;
+            bits_left = 8;
+        }
+        if code & 0x80 != 0 {
+            out.push(next()?)?;
+        } else {
+            let b1 = next()? as usize;
+            let b2 = next()? as usize;
+            let distance = ((b1 & 0xF) << 8 | b2) + 1;
+            let length = match b1 >> 4 {
+                0 => next()? as usize + LONG_MATCH,
+                n => n + 2,
+            };
+            out.copy(distance, length).context("Invalid Yaz0 data")?;
+        }
+        code <<= 1;
+        bits_left -= 1;
+    }
+    out.finish(size)?;
+
+    Ok(size)
+}
+
+/// Writes groups of eight literal/back-reference chunks, each preceded by a code byte
+struct GroupWriter<W> {
+    writer: W,
+    group: Vec<u8>,
+    count: u32,
+}
+
+impl<W: Write> GroupWriter<W> {
+    fn new(writer: W) -> Self {
+        GroupWriter {
+            writer,
+            group: Vec::with_capacity(1 + 8 * 3),
+            count: 0,
+        }
+    }
+
+    fn next_chunk(&mut self, literal: bool) -> std::io::Result<()> {
+        if self.count == 8 {
+            self.flush()?;
+        }
+        if self.count == 0 {
+            self.group.push(0);
+        }
+        if literal {
+            self.group[0] |= 0x80 >> self.count;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&self.group)?;
+        self.group.clear();
+        self.count = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, token: Token) -> std::io::Result<()> {
+        match token {
+            Token::Literal(b) => {
+                self.next_chunk(true)?;
+                self.group.push(b);
+            }
+            Token::Match { distance, length } => {
+                self.next_chunk(false)?;
+                let distance = distance - 1;
+                if length >= LONG_MATCH {
+                    self.group.push((distance >> 8) as u8);
+                    self.group.push(distance as u8);
+                    self.group.push((length - LONG_MATCH) as u8);
+                } else {
+                    self.group.push(((length - 2) << 4 | distance >> 8) as u8);
+                    self.group.push(distance as u8);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn compress(data: &[u8], level: CompressionLevel) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + data.len() + data.len() / 8 + 1);
+    compress_stream(&mut std::io::Cursor::new(data), &mut out, level)?;
+    Ok(out)
+}
+
+/// Compress the rest of a reader to a writer, keeping only the match window and a chunk of input
+/// in memory
+pub fn compress_stream<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    level: CompressionLevel,
+) -> anyhow::Result<()> {
+    let start = reader.stream_position()?;
+    let size = reader.seek(SeekFrom::End(0))? - start;
+    reader.seek(SeekFrom::Start(start))?;
+    let size = u32::try_from(size).map_err(|_| anyhow!("Data is too large for Yaz0"))?;
+
+    writer.write_all(YAZ0_MAGIC)?;
+    writer.write_all(&size.to_be_bytes())?;
+    writer.write_all(&[0; 8])?;
+    let mut groups = GroupWriter::new(writer);
+    let read = lz::tokenize_stream(&mut reader.take(size as u64), level, |token| {
+        groups.write(token)
+    })?;
+    ensure!(read == size as u64, "Input changed size while compressing");
+    groups.flush()?;
+    groups.writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lz::tests::samples;
+
+    fn yaz0(size: u32, body: &[u8]) -> Vec<u8> {
+        let mut buf = YAZ0_MAGIC.to_vec();
+        buf.extend_from_slice(&size.to_be_bytes());
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(body);
+        buf
+    }
+
+    #[test]
+    fn round_trips() {
+        for level in CompressionLevel::ALL {
+            for data in samples() {
+                let compressed = compress(&data, level).unwrap();
+                assert!(is_yaz0(&compressed));
+                assert_eq!(decompressed_size(&compressed).unwrap(), data.len());
+                assert_eq!(decompress(&compressed).unwrap(), data, "{level:?}");
+
+                let mut streamed = Vec::new();
+                let size = decompress_stream(&mut compressed.as_slice(), &mut streamed).unwrap();
+                assert_eq!(size, data.len() as u64);
+                assert_eq!(streamed, data);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_short_matches() {
+        // Three literals, then 7 bytes from 3 back
+        let buf = yaz0(10, &[0xE0, b'a', b'b', b'c', 0x50, 0x02]);
+        assert_eq!(decompress(&buf).unwrap(), b"abcabcabca");
+    }
+
+    #[test]
+    fn decodes_long_matches() {
+        // One literal, then 0x12 + 1 bytes from 1 back in the three byte form
+        let buf = yaz0(20, &[0x80, b'a', 0x00, 0x00, 0x01]);
+        assert_eq!(decompress(&buf).unwrap(), [b'a'; 20]);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let buf = yaz0(10, &[0xE0, b'a', b'b', b'c', 0x50]);
+        assert!(decompress(&buf).is_err());
+    }
+}